@@ -0,0 +1,259 @@
+//! Measures how much memory is allocated while a toolchain is downloaded, with different
+//! download buffer sizes.
+//!
+//! A release is served by the tests' mock GitHub server, and installed once for
+//! each buffer size, both as-is and gzip-encoded in transit (as some proxies do). For each
+//! download, this prints the number of allocations made and the most extra heap memory in use
+//! between the start and end of the download, and checks that the downloaded archive is
+//! identical to the one that was served. Nothing is downloaded from GitHub:
+//!
+//! ```sh
+//! cargo run --release --example download_buffers --no-default-features
+//! ```
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    error::Error,
+    io::Write,
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering::Relaxed},
+    },
+    time::Duration,
+};
+
+use arm_toolchain::toolchain::{
+    DEFAULT_DOWNLOAD_BUFFER_SIZE, HostArch, HostOS, InstallState, ToolchainVersion,
+};
+use common::{MockGitHub, MockRelease, MockServer, Response};
+use flate2::{Compression, write::GzEncoder};
+use tokio_util::sync::CancellationToken;
+
+/// How large the toolchain's largest file is. It's random, so the archive is about as large.
+const PAYLOAD_SIZE: usize = 16 * 1024 * 1024;
+/// How much of a response body the server sends at once, and how long it waits in between.
+const SEND_CHUNK_SIZE: usize = 16 * 1024;
+const SEND_INTERVAL: Duration = Duration::from_micros(200);
+/// The buffer sizes to compare.
+const BUFFER_SIZES: &[usize] = &[0, DEFAULT_DOWNLOAD_BUFFER_SIZE, 64 * 1024, 1024 * 1024];
+
+/// An allocator that counts allocations and keeps track of how much memory is in use.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static IN_USE: AtomicU64 = AtomicU64::new(0);
+static PEAK_IN_USE: AtomicU64 = AtomicU64::new(0);
+
+impl CountingAllocator {
+    fn grow(size: usize) {
+        ALLOCATIONS.fetch_add(1, Relaxed);
+        let in_use = IN_USE.fetch_add(size as u64, Relaxed) + size as u64;
+        PEAK_IN_USE.fetch_max(in_use, Relaxed);
+    }
+
+    fn shrink(size: usize) {
+        IN_USE.fetch_sub(size as u64, Relaxed);
+    }
+}
+
+// SAFETY: Every call is forwarded to the system allocator unchanged.
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        Self::grow(layout.size());
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        Self::shrink(layout.size());
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        Self::shrink(layout.size());
+        Self::grow(new_size);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// What was allocated while an asset was downloaded.
+#[derive(Debug, Default)]
+struct DownloadUsage {
+    allocations: u64,
+    peak_extra_bytes: u64,
+}
+
+/// Records the allocator's counters when the download starts and finishes, and checks the
+/// downloaded archive before it's extracted and deleted.
+struct UsageRecorder {
+    archive_path: PathBuf,
+    expected_archive: Arc<Vec<u8>>,
+    archive_matches: AtomicBool,
+    start_allocations: AtomicU64,
+    start_in_use: AtomicU64,
+    allocations: AtomicU64,
+    peak_extra_bytes: AtomicU64,
+}
+
+impl UsageRecorder {
+    fn new(archive_path: PathBuf, expected_archive: Arc<Vec<u8>>) -> Self {
+        Self {
+            archive_path,
+            expected_archive,
+            archive_matches: AtomicBool::new(false),
+            start_allocations: AtomicU64::new(0),
+            start_in_use: AtomicU64::new(0),
+            allocations: AtomicU64::new(0),
+            peak_extra_bytes: AtomicU64::new(0),
+        }
+    }
+
+    fn update(&self, state: &InstallState) {
+        match state {
+            InstallState::DownloadBegin { .. } => {
+                let in_use = IN_USE.load(Relaxed);
+                PEAK_IN_USE.store(in_use, Relaxed);
+                self.start_in_use.store(in_use, Relaxed);
+                self.start_allocations
+                    .store(ALLOCATIONS.load(Relaxed), Relaxed);
+            }
+            InstallState::DownloadFinish => {
+                let allocations = ALLOCATIONS.load(Relaxed) - self.start_allocations.load(Relaxed);
+                let peak = PEAK_IN_USE.load(Relaxed);
+                self.allocations.store(allocations, Relaxed);
+                self.peak_extra_bytes.store(
+                    peak.saturating_sub(self.start_in_use.load(Relaxed)),
+                    Relaxed,
+                );
+
+                let matches = std::fs::read(&self.archive_path)
+                    .is_ok_and(|archive| archive == *self.expected_archive);
+                self.archive_matches.store(matches, Relaxed);
+            }
+            _ => {}
+        }
+    }
+
+    fn usage(&self) -> DownloadUsage {
+        DownloadUsage {
+            allocations: self.allocations.load(Relaxed),
+            peak_extra_bytes: self.peak_extra_bytes.load(Relaxed),
+        }
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+
+    let asset_name = common::host_asset_name("21.1.0");
+    let root = asset_name.trim_end_matches(".tar.xz");
+
+    println!("Building a {} MiB toolchain archive...", PAYLOAD_SIZE >> 20);
+    let mut files = common::toolchain_files(root);
+    files.push((
+        format!("{root}/lib/payload.bin"),
+        common::noise(PAYLOAD_SIZE),
+        0o644,
+    ));
+    let archive = common::tar_xz(&files);
+    let release = MockRelease::empty("21.1.0").asset_with_checksum(&asset_name, archive.clone());
+    let archive = Arc::new(archive);
+
+    let mut gzip = GzEncoder::new(Vec::new(), Compression::fast());
+    gzip.write_all(&archive)?;
+    let gzipped_archive = gzip.finish()?;
+
+    for gzip in [false, true] {
+        let github = MockGitHub::new([release.clone()]);
+        let asset_name = asset_name.clone();
+        let gzipped_archive = gzipped_archive.clone();
+        let server = MockServer::start(move |request| {
+            let response = if gzip && request.file_name() == asset_name {
+                Response::ok(gzipped_archive.clone()).header("Content-Encoding", "gzip")
+            } else {
+                github.respond(request)
+            };
+            response.throttle(SEND_CHUNK_SIZE, SEND_INTERVAL)
+        });
+
+        println!();
+        println!(
+            "{}:",
+            if gzip {
+                "Gzip-encoded in transit"
+            } else {
+                "Not encoded"
+            }
+        );
+        for &buffer_size in BUFFER_SIZES {
+            let usage = runtime.block_on(download(&server, buffer_size, &archive))?;
+            println!(
+                "  {:>7} buffer: {:>6} allocations, {:>9} bytes of extra heap at most",
+                format_size(buffer_size),
+                usage.allocations,
+                usage.peak_extra_bytes,
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Installs the served release to a temporary directory with the given download buffer
+/// size, and checks that the cached archive is the one that was served.
+async fn download(
+    server: &MockServer,
+    buffer_size: usize,
+    archive: &Arc<Vec<u8>>,
+) -> Result<DownloadUsage, Box<dyn Error>> {
+    let dir = tempfile::tempdir()?;
+    let client = common::client_builder(dir.path(), server)
+        .download_buffer_size(buffer_size)
+        .build()
+        .await?;
+
+    let release = client
+        .get_release(&ToolchainVersion::named("21.1.0"))
+        .await?;
+    let asset = release.asset_for(HostOS::try_current()?, HostArch::try_current()?)?;
+
+    let recorder = Arc::new(UsageRecorder::new(
+        client.cache_path_for(asset)?,
+        archive.clone(),
+    ));
+    let progress = recorder.clone();
+    client
+        .install_to(
+            &release,
+            asset,
+            dir.path().join("toolchain"),
+            Arc::new(move |state| progress.update(&state)),
+            CancellationToken::new(),
+        )
+        .await?;
+
+    if !recorder.archive_matches.load(Relaxed) {
+        return Err(
+            format!("the archive downloaded with a {buffer_size} byte buffer differs").into(),
+        );
+    }
+
+    Ok(recorder.usage())
+}
+
+/// Formats a buffer size, such as `64 KiB`.
+fn format_size(size: usize) -> String {
+    match size {
+        0 => "no".to_string(),
+        size if size >= 1024 * 1024 => format!("{} MiB", size >> 20),
+        size => format!("{} KiB", size >> 10),
+    }
+}
@@ -0,0 +1,186 @@
+//! Installs a toolchain the way a GUI application (such as one built with egui or Tauri)
+//! would.
+//!
+//! GUI frameworks run their event loop on the main thread, so the installation runs on a
+//! background thread with its own Tokio runtime and sends messages to the UI over a channel.
+//! This example's "UI" prints each message instead of drawing it. Press Enter while the
+//! toolchain is installing to cancel it, like a Cancel button would.
+//!
+//! The toolchain is installed to a temporary directory, so this doesn't affect the toolchains
+//! used by the `arm-toolchain` command. None of the CLI features are needed:
+//!
+//! ```sh
+//! cargo run --example gui_progress --no-default-features
+//! ```
+
+use std::{
+    io::stdin,
+    path::Path,
+    pin::pin,
+    sync::mpsc::{Sender, channel},
+    thread,
+};
+
+use arm_toolchain::toolchain::{
+    ErrorReport, HostArch, HostOS, InstallEvent, InstallOutcome, InstallState, Phase,
+    ToolchainClient, ToolchainVersion,
+};
+use futures::StreamExt;
+use tokio_util::sync::CancellationToken;
+
+/// A message from the background worker to the UI.
+enum UiMessage {
+    Started(ToolchainVersion),
+    Progress(InstallState),
+    Finished(InstallOutcome),
+    Failed(ErrorReport),
+}
+
+/// What the UI shows while a toolchain is being installed.
+#[derive(Default)]
+struct ProgressModel {
+    phase: Option<Phase>,
+    total: u64,
+    done: u64,
+    /// The last percentage that was shown, so that the "UI" isn't redrawn for every event.
+    shown_percent: Option<u64>,
+}
+
+impl ProgressModel {
+    fn update(&mut self, state: &InstallState) {
+        if self.phase != Some(state.phase()) {
+            self.phase = Some(state.phase());
+            (self.total, self.done) = (0, 0);
+            self.shown_percent = None;
+            println!("[{}]", state.phase());
+        }
+
+        match *state {
+            InstallState::DownloadBegin {
+                asset_size,
+                bytes_read,
+            } => (self.total, self.done) = (asset_size, bytes_read),
+            InstallState::VerifyingBegin { asset_size } => self.total = asset_size,
+            InstallState::ExtractBegin {
+                estimated_size: Some(estimated_size),
+            } => self.total = estimated_size,
+            InstallState::Download { bytes_read } | InstallState::Verifying { bytes_read } => {
+                self.done = bytes_read;
+            }
+            InstallState::ExtractCopy {
+                total_size,
+                bytes_copied,
+            } => (self.total, self.done) = (total_size, bytes_copied),
+            InstallState::ApplyPermissionsBegin { total_entries } => self.total = total_entries,
+            InstallState::ApplyPermissions { entries_done } => self.done = entries_done,
+            _ => {}
+        }
+
+        // Sizes may be unknown, in which case a GUI would show an indeterminate progress bar.
+        if let Some(percent) = (self.done * 100).checked_div(self.total)
+            && self.shown_percent.is_none_or(|shown| percent >= shown + 10)
+        {
+            self.shown_percent = Some(percent);
+            println!("  {percent}%");
+        }
+    }
+}
+
+fn main() {
+    let dir = tempfile::tempdir().expect("failed to create a temporary directory");
+    let (ui, messages) = channel();
+    let cancel_token = CancellationToken::new();
+
+    let worker = thread::spawn({
+        let cancel_token = cancel_token.clone();
+        let dir = dir.path().to_owned();
+        move || {
+            let runtime = tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()
+                .expect("failed to start the Tokio runtime");
+
+            if let Err(report) = runtime.block_on(install(&dir, &ui, cancel_token)) {
+                _ = ui.send(UiMessage::Failed(report));
+            }
+        }
+    });
+
+    // This thread is never joined, since it's blocked reading stdin until the process exits.
+    thread::spawn({
+        let cancel_token = cancel_token.clone();
+        move || {
+            _ = stdin().read_line(&mut String::new());
+            cancel_token.cancel();
+        }
+    });
+
+    // The worker's sender is dropped when it finishes, which ends this loop.
+    let mut model = ProgressModel::default();
+    for message in messages {
+        match message {
+            UiMessage::Started(version) => {
+                println!("Installing {version}. Press Enter to cancel.");
+            }
+            UiMessage::Progress(state) => model.update(&state),
+            UiMessage::Finished(outcome) => {
+                println!(
+                    "Installed {} to {} in {:.1?}",
+                    outcome.version,
+                    outcome.path.display(),
+                    outcome.duration
+                );
+            }
+            UiMessage::Failed(report) => {
+                println!("Error: {report}");
+                for cause in &report.causes {
+                    println!("  Caused by: {cause}");
+                }
+                if let Some(help) = &report.help {
+                    println!("  Help: {help}");
+                }
+                if let Some(code) = &report.code {
+                    println!("  ({code})");
+                }
+            }
+        }
+    }
+
+    worker.join().unwrap();
+}
+
+/// Installs the latest toolchain to `dir`, sending its progress to the UI.
+async fn install(
+    dir: &Path,
+    ui: &Sender<UiMessage>,
+    cancel_token: CancellationToken,
+) -> Result<(), ErrorReport> {
+    let client = ToolchainClient::new(dir.join("toolchains"), dir.join("cache"))
+        .await
+        .map_err(|error| error.to_report())?;
+    let release = client
+        .latest_release()
+        .await
+        .map_err(|error| error.to_report())?;
+
+    let os = HostOS::try_current().map_err(|error| ErrorReport::from_diagnostic(&error))?;
+    let arches = HostArch::try_current().map_err(|error| ErrorReport::from_diagnostic(&error))?;
+    let asset = release
+        .asset_for(os, arches)
+        .map_err(|error| error.to_report())?;
+
+    // Sending only fails if the UI was closed, in which case there's nobody to tell.
+    _ = ui.send(UiMessage::Started(release.version().clone()));
+
+    let mut events = pin!(client.install_events(&release, asset, cancel_token));
+    while let Some(event) = events.next().await {
+        let message = match event {
+            InstallEvent::Progress(state) => UiMessage::Progress(state),
+            InstallEvent::Finished(Ok(outcome)) => UiMessage::Finished(outcome),
+            InstallEvent::Finished(Err(error)) => UiMessage::Failed(error.to_report()),
+        };
+        _ = ui.send(message);
+    }
+
+    Ok(())
+}
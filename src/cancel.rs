@@ -0,0 +1,109 @@
+//! Helpers for stopping long-running operations with a [`CancellationToken`].
+//!
+//! Every long-running method of [`ToolchainClient`](crate::toolchain::ToolchainClient) takes
+//! a cancellation token. Cancellation is cooperative: the token is checked between units of
+//! work (such as archive entries or copied chunks), and the method then returns
+//! [`ToolchainError::Cancelled`] after cleaning up. The documentation of each method describes
+//! what is left behind when it's cancelled.
+
+use std::io::{self, Read, Write};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+pub use tokio_util::sync::CancellationToken;
+
+use crate::toolchain::ToolchainError;
+
+/// The size of the chunks copied between cancellation checks by [`copy`] and
+/// [`copy_blocking`].
+pub const COPY_CHUNK_SIZE: usize = 64 * 1024;
+
+/// An extension trait for checking whether an operation should stop.
+pub trait CheckCancellation {
+    /// Returns the given error if cancellation was requested, or `Ok` otherwise.
+    ///
+    /// This is intended to be used with `?` between units of work.
+    fn check_cancellation<E>(&self, error: E) -> Result<(), E>;
+}
+
+impl CheckCancellation for CancellationToken {
+    fn check_cancellation<E>(&self, error: E) -> Result<(), E> {
+        if self.is_cancelled() {
+            Err(error)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Copies everything from a reader to a writer, stopping early if the token is cancelled.
+///
+/// The token is checked before each chunk of at most [`COPY_CHUNK_SIZE`] bytes, and `progress`
+/// is called with the total number of bytes copied after each chunk. Returns the number of
+/// bytes copied, or [`ToolchainError::Cancelled`] if the copy was stopped. The writer is
+/// flushed before returning successfully.
+pub async fn copy<R, W>(
+    cancel_token: &CancellationToken,
+    reader: &mut R,
+    writer: &mut W,
+    mut progress: impl FnMut(u64),
+) -> Result<u64, ToolchainError>
+where
+    R: AsyncRead + Unpin + ?Sized,
+    W: AsyncWrite + Unpin + ?Sized,
+{
+    let mut buf = vec![0; COPY_CHUNK_SIZE];
+    let mut copied = 0;
+
+    loop {
+        cancel_token.check_cancellation(ToolchainError::Cancelled)?;
+
+        let len = match reader.read(&mut buf).await {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+
+        writer.write_all(&buf[..len]).await?;
+        copied += len as u64;
+        progress(copied);
+    }
+
+    writer.flush().await?;
+    Ok(copied)
+}
+
+/// Like [`copy`], but for blocking readers and writers.
+///
+/// This should be called from a blocking task (e.g. with [`tokio::task::spawn_blocking`]).
+pub fn copy_blocking<R, W>(
+    cancel_token: &CancellationToken,
+    reader: &mut R,
+    writer: &mut W,
+    mut progress: impl FnMut(u64),
+) -> Result<u64, ToolchainError>
+where
+    R: Read + ?Sized,
+    W: Write + ?Sized,
+{
+    let mut buf = vec![0; COPY_CHUNK_SIZE];
+    let mut copied = 0;
+
+    loop {
+        cancel_token.check_cancellation(ToolchainError::Cancelled)?;
+
+        let len = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+
+        writer.write_all(&buf[..len])?;
+        copied += len as u64;
+        progress(copied);
+    }
+
+    writer.flush()?;
+    Ok(copied)
+}
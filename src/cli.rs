@@ -1,8 +1,19 @@
-use std::{io, sync::LazyLock};
+#[cfg(feature = "cli-interactive")]
+use std::sync::LazyLock;
+use std::{
+    io,
+    path::PathBuf,
+    process::ExitCode,
+    sync::atomic::{AtomicBool, AtomicU8, Ordering},
+    time::Duration,
+};
 
-use crate::toolchain::{ToolchainClient, ToolchainError, ToolchainVersion};
+use crate::toolchain::{
+    CancelledState, InstallReasons, Product, ToolchainClient, ToolchainClientBuilder,
+    ToolchainError, ToolchainVersion, UnsupportedHost, listed_code,
+};
 use clap::builder::styling;
-use humansize::DECIMAL;
+#[cfg(feature = "cli-interactive")]
 use indicatif::ProgressStyle;
 use miette::Diagnostic;
 use thiserror::Error;
@@ -10,10 +21,16 @@ use tokio_util::{future::FutureExt, sync::CancellationToken};
 
 #[derive(Debug, Error, Diagnostic)]
 pub enum CliError {
+    #[cfg(feature = "cli-interactive")]
     #[error(transparent)]
     #[diagnostic(code(arm_toolchain::cli::interactive_prompt_failed))]
     Inquire(#[from] inquire::InquireError),
 
+    #[error("Cannot ask for confirmation because the terminal is not interactive")]
+    #[diagnostic(code(arm_toolchain::cli::non_interactive))]
+    #[diagnostic(help("Pass `{flag}` to continue without being asked."))]
+    NonInteractive { flag: &'static str },
+
     #[error(transparent)]
     #[diagnostic(transparent)]
     Toolchain(ToolchainError),
@@ -23,39 +40,359 @@ pub enum CliError {
     #[diagnostic(help("Install and activate a toolchain by running the `use latest` subcommand."))]
     NoToolchainEnabled,
 
-    #[error("The toolchain {:?} is not installed.", version.name)]
+    #[error("The toolchain {version} is not installed.")]
     #[diagnostic(code(arm_toolchain::cli::toolchain_missing))]
-    #[diagnostic(help("Install and activate it by running the `install {version}` subcommand."))]
-    ToolchainNotInstalled { version: ToolchainVersion },
+    #[diagnostic(help("{}", suggestion_help(suggestions).unwrap_or_else(|| format!(
+        "Install and activate it by running the `install {version}` subcommand."
+    ))))]
+    ToolchainNotInstalled {
+        version: ToolchainVersion,
+        /// Versions with similar names that may have been meant instead.
+        suggestions: Vec<ToolchainVersion>,
+    },
 
     #[error("No ARM toolchains are installed on this system")]
     #[diagnostic(code(arm_toolchain::cli::no_toolchains_installed))]
     #[diagnostic(help("There is nothing to remove."))]
     NoToolchainsToRemove,
 
-    #[error("The toolchain {:?} is not installed.", version.name)]
+    #[error("The toolchain {version} is not installed.")]
+    #[diagnostic(code(arm_toolchain::cli::remove_missing))]
+    #[diagnostic(help("{}", suggestion_help(suggestions).unwrap_or_else(|| {
+        "Run `list` to see which toolchains are installed.".to_string()
+    })))]
+    CannotRemoveMissingToolchain {
+        version: ToolchainVersion,
+        /// Installed versions with similar names that may have been meant instead.
+        suggestions: Vec<ToolchainVersion>,
+    },
+
+    #[error(
+        "These toolchains are not installed: {}",
+        versions.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+    )]
     #[diagnostic(code(arm_toolchain::cli::remove_missing))]
-    CannotRemoveMissingToolchain { version: ToolchainVersion },
+    #[diagnostic(help("Nothing was removed. Run `list` to see which toolchains are installed."))]
+    CannotRemoveMissingToolchains { versions: Vec<ToolchainVersion> },
+
+    #[error("\"all\" can't be combined with other versions")]
+    #[diagnostic(code(arm_toolchain::cli::remove_all_with_versions))]
+    #[diagnostic(help("Pass either \"all\" or a list of versions to remove."))]
+    RemoveAllWithVersions,
+
+    #[error("No releases of the ARM toolchain have the major version {major}")]
+    #[diagnostic(code(arm_toolchain::cli::no_release_in_major))]
+    #[diagnostic(help("Run `list --check-updates` to see which versions are available."))]
+    NoReleaseInMajor { major: u64 },
+
+    #[error(transparent)]
+    #[diagnostic(code(arm_toolchain::cli::unsupported_host))]
+    #[diagnostic(help(
+        "The Arm Toolchain for Embedded is available for macOS (x86_64/aarch64), Linux (x86_64/aarch64), and Windows (x86_64).
+To download a toolchain for another machine, pass the `--host-os` and `--host-arch` options to the `install` subcommand."
+    ))]
+    UnsupportedHost(#[from] UnsupportedHost),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Config(#[from] ConfigError),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Pin(#[from] PinError),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Template(#[from] TemplateError),
+
+    #[error("Setup can only be run in an interactive terminal")]
+    #[diagnostic(code(arm_toolchain::cli::setup_not_interactive))]
+    #[diagnostic(help(
+        "Run `use latest` to install and activate the newest toolchain, or `use <version> --local` to pin one for a project."
+    ))]
+    SetupNotInteractive,
+
+    #[error("Failed to write the support bundle to {}", path.display())]
+    #[diagnostic(code(arm_toolchain::cli::bundle_failed))]
+    Bundle {
+        path: PathBuf,
+        #[source]
+        source: zip::result::ZipError,
+    },
+
+    #[error("Another `update --daemon` is already running for this product")]
+    #[diagnostic(code(arm_toolchain::cli::update_daemon_running))]
+    #[diagnostic(help(
+        "Only one daemon can update a product at a time. The lock is held on {}.",
+        path.display()
+    ))]
+    UpdateDaemonRunning { path: PathBuf },
+
+    #[error("There is no installed toolchain older than the active one ({active})")]
+    #[diagnostic(code(arm_toolchain::cli::no_previous_toolchain))]
+    #[diagnostic(help("Run `list` to see which toolchains are installed."))]
+    NoPreviousToolchain { active: ToolchainVersion },
+
+    #[error("Unknown version alias {name:?}")]
+    #[diagnostic(code(arm_toolchain::cli::unknown_alias))]
+    #[diagnostic(help(
+        "{}",
+        if defined.is_empty() {
+            "Define aliases in the `[aliases]` section of the config file, such as `stable = \"20.1.0\"`.".to_string()
+        } else {
+            format!("The defined aliases are: {}", defined.join(", "))
+        }
+    ))]
+    UnknownAlias { name: String, defined: Vec<String> },
+
+    #[error("The version alias {name:?} is invalid: {reason}")]
+    #[diagnostic(code(arm_toolchain::cli::invalid_alias))]
+    #[diagnostic(help("Aliases in the config file must refer to a version or \"latest\"."))]
+    InvalidAlias { name: String, reason: String },
+
+    #[error("No release notes were saved when {version} was installed")]
+    #[diagnostic(code(arm_toolchain::cli::release_notes_missing))]
+    #[diagnostic(help(
+        "Older versions of arm-toolchain didn't save release notes. Reinstall the toolchain with `arm-toolchain install {} --force` to save them.",
+        version.name
+    ))]
+    ReleaseNotesMissing { version: ToolchainVersion },
+
+    #[error("Can't update pins to {target}, since it won't be installed after the removal")]
+    #[diagnostic(code(arm_toolchain::cli::invalid_pin_target))]
+    #[diagnostic(help(
+        "Nothing was removed. Pass a version that is installed and isn't being removed, \"active\", or \"latest-installed\"."
+    ))]
+    InvalidPinTarget { target: String },
+
+    #[error("Several installed toolchains match {prefix}")]
+    #[diagnostic(code(arm_toolchain::cli::ambiguous_version))]
+    #[diagnostic(help(
+        "Pass the full version of one of them: {}",
+        candidates.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+    ))]
+    AmbiguousVersion {
+        prefix: ToolchainVersion,
+        candidates: Vec<ToolchainVersion>,
+    },
+
+    #[error("Unknown CPU {cpu:?}")]
+    #[diagnostic(code(arm_toolchain::cli::unknown_cpu))]
+    #[diagnostic(help(
+        "{}",
+        if supported.is_empty() {
+            "This toolchain has no runtime libraries for any known CPU.".to_string()
+        } else {
+            format!("This toolchain supports: {}", supported.join(", "))
+        }
+    ))]
+    UnknownCpu {
+        cpu: String,
+        supported: Vec<&'static str>,
+    },
+
+    #[error("The toolchain has no runtime libraries for {target}")]
+    #[diagnostic(code(arm_toolchain::cli::no_runtime_for_target))]
+    #[diagnostic(help(
+        "{}No variant matched the flags {}. Run `targets` to see which variants are available.",
+        reason.as_ref().map(|reason| format!("{reason}\n")).unwrap_or_default(),
+        flags.join(" ")
+    ))]
+    NoRuntimeForTarget {
+        target: String,
+        flags: Vec<String>,
+        reason: Option<String>,
+    },
+
+    #[error("The installation of {} was cancelled", state.version)]
+    #[diagnostic(code(arm_toolchain::cli::install_cancelled))]
+    #[diagnostic(help("{}", cancelled_help(state, resume_command)))]
+    InstallCancelled {
+        state: Box<CancelledState>,
+        /// The command that resumes the installation.
+        resume_command: String,
+    },
+
+    #[error("The download stalled: no data arrived for {} seconds", timeout.as_secs())]
+    #[diagnostic(code(arm_toolchain::cli::download_stalled))]
+    #[diagnostic(help(
+        "The {} downloaded so far were kept. Check your network connection, then resume the download with `{resume_command}`.",
+        humansize::format_size(*bytes_written, humansize::DECIMAL)
+    ))]
+    DownloadStalled {
+        timeout: Duration,
+        bytes_written: u64,
+        /// The command that resumes the download.
+        resume_command: String,
+    },
 }
 
 impl From<ToolchainError> for CliError {
     fn from(value: ToolchainError) -> Self {
         match value {
             // CLI version has a different help message.
-            ToolchainError::ToolchainNotInstalled { version } => {
-                Self::ToolchainNotInstalled { version }
-            }
+            ToolchainError::ToolchainNotInstalled { version } => Self::ToolchainNotInstalled {
+                version,
+                suggestions: vec![],
+            },
             other => Self::Toolchain(other),
         }
     }
 }
 
+/// Describes versions that may have been meant instead of one that wasn't found, such as
+/// "Did you mean 20.1.0?".
+fn suggestion_help(suggestions: &[ToolchainVersion]) -> Option<String> {
+    let names = suggestions
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    match names.split_last()? {
+        (last, []) => Some(format!("Did you mean {last}?")),
+        (last, rest) => Some(format!("Did you mean {} or {last}?", rest.join(", "))),
+    }
+}
+
+/// Describes what a cancelled installation kept, and how to resume it.
+fn cancelled_help(state: &CancelledState, resume_command: &str) -> String {
+    let kept = match &state.archive {
+        Some(archive) if archive.is_complete() => format!(
+            "The downloaded archive was kept at {}, so it won't be downloaded again.",
+            archive.path.display()
+        ),
+        Some(archive) => format!(
+            "The partial download was kept at {} ({}{} downloaded).",
+            archive.path.display(),
+            humansize::format_size(archive.downloaded_bytes, humansize::DECIMAL),
+            archive
+                .percent()
+                .map(|percent| format!(", {percent}%"))
+                .unwrap_or_default(),
+        ),
+        None => "Nothing was downloaded yet.".to_string(),
+    };
+    format!("{kept}\nResume the installation with `{resume_command}`.")
+}
+
+impl CliError {
+    /// The diagnostic codes of errors that only the CLI reports.
+    const CLI_CODES: &[&str] = &[
+        "arm_toolchain::cli::interactive_prompt_failed",
+        "arm_toolchain::cli::non_interactive",
+        "arm_toolchain::cli::no_toolchain_enabled",
+        "arm_toolchain::cli::toolchain_missing",
+        "arm_toolchain::cli::no_toolchains_installed",
+        "arm_toolchain::cli::remove_missing",
+        "arm_toolchain::cli::remove_all_with_versions",
+        "arm_toolchain::cli::no_release_in_major",
+        "arm_toolchain::cli::unsupported_host",
+        "arm_toolchain::cli::invalid_config",
+        "arm_toolchain::cli::invalid_pin",
+        "arm_toolchain::cli::invalid_env_template",
+        "arm_toolchain::cli::setup_not_interactive",
+        "arm_toolchain::cli::bundle_failed",
+        "arm_toolchain::cli::update_daemon_running",
+        "arm_toolchain::cli::no_previous_toolchain",
+        "arm_toolchain::cli::unknown_alias",
+        "arm_toolchain::cli::invalid_alias",
+        "arm_toolchain::cli::release_notes_missing",
+        "arm_toolchain::cli::invalid_pin_target",
+        "arm_toolchain::cli::ambiguous_version",
+        "arm_toolchain::cli::unknown_cpu",
+        "arm_toolchain::cli::no_runtime_for_target",
+        "arm_toolchain::cli::install_cancelled",
+        "arm_toolchain::cli::download_stalled",
+    ];
+
+    /// Returns every diagnostic code that [`stable_code`](Self::stable_code) can return,
+    /// including the codes of wrapped [`ToolchainError`]s.
+    pub fn all_codes() -> impl Iterator<Item = &'static str> {
+        Self::CLI_CODES
+            .iter()
+            .copied()
+            .chain(ToolchainError::all_codes())
+    }
+
+    /// Returns the error's diagnostic code, such as `arm_toolchain::cli::no_toolchain_enabled`.
+    ///
+    /// Like [`ToolchainError::stable_code`], this is the code reported through
+    /// [`miette::Diagnostic::code`], and is always one of [`all_codes`](Self::all_codes).
+    pub fn stable_code(&self) -> &'static str {
+        listed_code(self, Self::all_codes())
+    }
+
+    /// Returns structured information about the error for `--output json`, beyond its
+    /// message and help, or `None` if there isn't any.
+    pub fn details(&self) -> Option<serde_json::Value> {
+        match self {
+            Self::InstallCancelled {
+                state,
+                resume_command,
+            } => Some(serde_json::json!({
+                "cancelled": state,
+                "resume_command": resume_command,
+            })),
+            Self::DownloadStalled {
+                timeout,
+                bytes_written,
+                resume_command,
+            } => Some(serde_json::json!({
+                "timeout_secs": timeout.as_secs(),
+                "bytes_written": bytes_written,
+                "resume_command": resume_command,
+            })),
+            _ => None,
+        }
+    }
+
+    /// Converts an error from an interactive prompt, explaining how to skip the prompt
+    /// with the given flag if the error was caused by a non-interactive terminal.
+    #[cfg(feature = "cli-interactive")]
+    pub fn from_prompt_error(error: inquire::InquireError, flag: &'static str) -> Self {
+        use inquire::InquireError;
+
+        match error {
+            InquireError::NotTTY => Self::NonInteractive { flag },
+            InquireError::IO(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::BrokenPipe
+                        | io::ErrorKind::UnexpectedEof
+                        | io::ErrorKind::NotConnected
+                ) =>
+            {
+                Self::NonInteractive { flag }
+            }
+            other => Self::Inquire(other),
+        }
+    }
+}
+
 impl From<io::Error> for CliError {
     fn from(value: io::Error) -> Self {
         ToolchainError::from(value).into()
     }
 }
 
+/// The arguments of the `arm-toolchain` executable.
+#[derive(Debug, clap::Parser)]
+#[clap(version, author, styles(STYLES))]
+pub struct CliArgs {
+    /// Append debug logs to this file, regardless of `RUST_LOG`.
+    ///
+    /// The file is included in support bundles created by `doctor --bundle`.
+    #[clap(long, global = true, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+    /// Never access the network.
+    ///
+    /// Commands that would fetch releases or download toolchains fail instead, while
+    /// installed toolchains can still be listed, located, run, activated, and removed.
+    #[clap(long, global = true)]
+    pub offline: bool,
+    #[clap(subcommand)]
+    pub cmd: ArmToolchainCmd,
+}
+
 /// Arm Toolchain Manager is a tool for installing and managing the LLVM-based ARM embedded toolchain.
 ///
 /// See also: `atrun`
@@ -74,11 +411,14 @@ pub enum ArmToolchainCmd {
         visible_alias("i"),
     )]
     Install(InstallArgs),
-    /// Uninstall a single toolchain version, or all versions.
+    /// Uninstall one or more toolchain versions, or all versions.
     ///
     /// When a toolchain is uninstalled, it is unset as the current toolchain and deleted
     /// from the toolchains directory and download cache.
     ///
+    /// If several versions are given, they must all be installed, and you will be asked to
+    /// confirm their combined size before they are removed.
+    ///
     /// If "all" is specified as the version to remove, every toolchain on the system will be
     /// uninstalled.
     #[clap(
@@ -86,12 +426,27 @@ pub enum ArmToolchainCmd {
         visible_alias("rm"),
     )]
     Remove(RemoveArgs),
+    /// Uninstall every toolchain except the active one.
+    ///
+    /// Tools that share the toolchains directory record why they need each toolchain, and
+    /// these reasons are shown before anything is removed. Pass `--unused` to only remove
+    /// toolchains that have no reasons recorded.
+    Prune(PruneArgs),
     /// Run a command with the active toolchain added to the `PATH`.
     ///
+    /// If the current directory or one of its parents contains an `arm-toolchain.toml` file,
+    /// the toolchain it's pinned to is used instead of the active toolchain.
+    ///
     /// Unless you specify `--no-cross-env`, the `TARGET_CC` and `TARGET_AR` environment
     /// variables will also be set to `clang` and `llvm-ar` respectively. These will resolve
     /// to the toolchain's versions of clang and llvm-ar.
     ///
+    /// More variables can be set in the `[run.env]` section of the config file, such as
+    /// `TOOLCHAIN_ROOT = "{install_dir}"`. These override the defaults.
+    ///
+    /// For reproducible builds, `--isolate` runs the command with a minimal environment
+    /// instead of inheriting the current one, so variables like `CFLAGS` can't leak in.
+    ///
     /// An alias for this command is the external `atrun` executable. You may need to pass an
     /// extra `--` to the command if some flags look like ones `arm-toolchain` would accept.
     Run(RunArgs),
@@ -103,27 +458,153 @@ pub enum ArmToolchainCmd {
     )]
     Locate(LocateArgs),
     /// Active a desired version of the ARM Embedded Toolchain, downloading it if necessary.
+    ///
+    /// With `--local`, the toolchain is pinned for the current project by writing an
+    /// `arm-toolchain.toml` file instead, and the global default is left unchanged.
     #[clap(
         visible_alias("set"),
         visible_alias("activate"),
     )]
     Use(UseArgs),
+    /// List the runtime library variants that a toolchain ships for each target.
+    ///
+    /// Pass `--print-variant-for` with `--cpu` (and optionally `--float-abi` and `--fpu`) to
+    /// print the variant that clang would choose for a chip, along with its library and
+    /// header directories.
+    Targets(TargetsArgs),
+    /// Check that an installed toolchain's files haven't changed since it was installed.
+    ///
+    /// Every file is hashed and compared against the digests recorded by
+    /// `install --record-digests`. Exits with code 4 if any files were modified, added, or
+    /// removed, and with code 5 if no digests were recorded for the toolchain.
+    Verify(VerifyArgs),
     /// List all installed toolchain versions and the current active version.
     #[clap(visible_alias("ls"))]
-    List,
+    List(ListArgs),
+    /// Install the newest version of the ARM Embedded Toolchain, or the newest version
+    /// with a specific major version.
+    ///
+    /// If the active toolchain is older than the new version (and has the same major
+    /// version, if one was specified), the new version will be activated.
+    ///
+    /// Pass `--daemon` to keep checking for updates on an interval, or `--once` to check
+    /// without being asked for confirmation (e.g. from cron).
+    #[clap(visible_alias("upgrade"))]
+    Update(UpdateArgs),
+    /// List the releases that can be installed, newest first.
+    ///
+    /// Pass `--since` to only list releases newer than a version, and `--host-only` to
+    /// skip releases that have no download for this host. Pass `--assets` to list each
+    /// release's downloads, and `--digests` to include their SHA256 digests.
+    Available(AvailableArgs),
+    /// Show details about a release without installing it.
+    ///
+    /// Pass `--urls` to print where the toolchain would be downloaded from and its expected
+    /// checksum, so that it can be downloaded with another tool. Pass `--notes` to print the
+    /// release notes saved when an installed toolchain (by default, the active one) was
+    /// installed, without network access.
+    Info(InfoArgs),
+    /// Save a snapshot of a release's metadata to a lock file.
+    ///
+    /// The lock records the release's tag and the URLs, sizes, and checksums of its
+    /// downloads. `install --locked <PATH>` installs the release using only the lock and the
+    /// download itself, without asking the GitHub API about it, and fails if the download
+    /// doesn't match the lock.
+    Lock(LockArgs),
     /// Delete the cache which stores incomplete downloads.
-    PurgeCache,
+    ///
+    /// Files that can't be deleted (e.g. because they're in use) are reported, and don't
+    /// prevent the rest of the cache from being deleted.
+    PurgeCache(PurgeCacheArgs),
+    /// Manage individual entries in the download cache.
+    #[clap(subcommand)]
+    Cache(CacheCmd),
+    /// Delete everything arm-toolchain has stored: every installed toolchain, the download
+    /// cache, the journal, and the configuration file.
+    ///
+    /// This can't be undone. The `arm-toolchain` executable itself isn't removed.
+    PurgeAll(PurgeAllArgs),
+    /// Check for problems, such as operations that were interrupted by a crash.
+    Doctor(DoctorArgs),
+    /// Walk through installing a toolchain for the first time.
+    ///
+    /// This asks whether to install and activate the latest toolchain, pin it for the
+    /// project in the current directory, install shell completions, and add it to your shell's
+    /// `PATH`. Any step can be skipped, and the commands that were run are listed at the end.
+    Setup(SetupArgs),
+    /// Print the completion script for a shell.
+    ///
+    /// Load the script from your shell's startup file, or save it where your shell looks for
+    /// completions (`setup` can do this for you).
+    Completions(CompletionsArgs),
 }
 
 impl ArmToolchainCmd {
     /// Run the command.
     pub async fn run(self) -> Result<(), CliError> {
+        // Creating a client brings the toolchains directory's layout up to date, which is
+        // mentioned the one time it happens. Any error is reported by the command itself.
+        if let Ok(client) = client_builder().build().await {
+            for migration in client.layout_migrations() {
+                msg!(
+                    "Migrated",
+                    "the toolchains directory to layout version {} ({})",
+                    migration.version(),
+                    migration.description()
+                );
+            }
+        }
+
+        // The config is loaded before running the command, since `purge-all` deletes it.
+        let metrics = match self.mutating_name() {
+            Some(name) => Config::load()
+                .await
+                .ok()
+                .and_then(|config| config.metrics_textfile)
+                .map(|path| (path, name)),
+            None => None,
+        };
+
+        let result = self.dispatch().await;
+
+        if let Some((path, name)) = metrics {
+            write_metrics(&path, name, result.is_ok()).await;
+        }
+
+        result
+    }
+
+    /// Returns the name of the command if it can change the installed toolchains or the
+    /// download cache, in which case the metrics textfile is updated after it runs.
+    fn mutating_name(&self) -> Option<&'static str> {
+        match self {
+            Self::Install(args) if !args.dry_run => Some("install"),
+            Self::Remove(_) => Some("remove"),
+            Self::Prune(args) if !args.dry_run => Some("prune"),
+            Self::Use(args) if !args.dry_run => Some("use"),
+            Self::Update(_) => Some("update"),
+            Self::PurgeCache(_) => Some("purge-cache"),
+            Self::Cache(_) => Some("cache"),
+            Self::PurgeAll(_) => Some("purge-all"),
+            Self::Doctor(args) if args.fix => Some("doctor"),
+            Self::Setup(_) => Some("setup"),
+            _ => None,
+        }
+    }
+
+    async fn dispatch(self) -> Result<(), CliError> {
         match self {
             ArmToolchainCmd::Install(config) => {
-                install(config).await?;
+                let output = config.output;
+                output.finish(install(config).await)?;
             }
             ArmToolchainCmd::Remove(args) => {
-                remove(args).await?;
+                let output = args.output;
+                output.finish(remove(args).await)?;
+            }
+            ArmToolchainCmd::Prune(args) => {
+                let output = args.output;
+                output.finish(prune(args).await)?;
             }
             ArmToolchainCmd::Run(args) => {
                 run(args).await?;
@@ -132,13 +613,57 @@ impl ArmToolchainCmd {
                 locate(args).await?;
             }
             ArmToolchainCmd::Use(args) => {
-                use_cmd(args).await?;
+                let output = args.output;
+                output.finish(use_cmd(args).await)?;
+            }
+            ArmToolchainCmd::Targets(args) => {
+                let output = args.output;
+                output.finish(targets(args).await)?;
+            }
+            ArmToolchainCmd::Verify(args) => {
+                let output = args.output;
+                output.finish(verify(args).await)?;
+            }
+            ArmToolchainCmd::List(args) => {
+                list(args).await?;
+            }
+            ArmToolchainCmd::Update(args) => {
+                update(args).await?;
+            }
+            ArmToolchainCmd::Available(args) => {
+                let output = args.output;
+                output.finish(available(args).await)?;
+            }
+            ArmToolchainCmd::Info(args) => {
+                let output = args.output;
+                if args.notes {
+                    output.finish(release_notes(args).await)?;
+                } else {
+                    output.finish(info(args).await)?;
+                }
+            }
+            ArmToolchainCmd::Lock(args) => {
+                lock(args).await?;
+            }
+            ArmToolchainCmd::PurgeCache(args) => {
+                let output = args.output;
+                output.finish(purge_cache(args).await)?;
+            }
+            ArmToolchainCmd::Cache(cmd) => {
+                cmd.run().await?;
             }
-            ArmToolchainCmd::List => {
-                list().await?;
+            ArmToolchainCmd::PurgeAll(args) => {
+                let output = args.output;
+                output.finish(purge_all(args).await)?;
             }
-            ArmToolchainCmd::PurgeCache => {
-                purge_cache().await?;
+            ArmToolchainCmd::Doctor(args) => {
+                doctor(args).await?;
+            }
+            ArmToolchainCmd::Setup(args) => {
+                setup(args).await?;
+            }
+            ArmToolchainCmd::Completions(args) => {
+                completions(args)?;
             }
         }
 
@@ -146,24 +671,97 @@ impl ArmToolchainCmd {
     }
 }
 
+mod config;
+pub use config::*;
+
+mod render;
+pub use render::*;
+
+mod output;
+pub use output::*;
+
 mod install;
 pub use install::*;
 
 mod run;
 pub use run::*;
 
+mod timing;
+pub use timing::*;
+
 mod use_cmd;
 pub use use_cmd::*;
 
 mod remove;
 pub use remove::*;
 
+mod prune;
+pub use prune::*;
+
+mod doctor;
+pub use doctor::*;
+
+mod update;
+pub use update::*;
+
+mod cache;
+pub use cache::*;
+
+mod info;
+pub use info::*;
+
+mod purge_all;
+pub use purge_all::*;
+
+mod pin;
+pub use pin::*;
+
+mod metrics;
+pub use metrics::*;
+
+mod bundle;
+pub use bundle::*;
+
+mod template;
+pub use template::*;
+
+mod setup;
+pub use setup::*;
+
+mod completions;
+pub use completions::*;
+
+mod available;
+pub use available::*;
+
+mod lock;
+pub use lock::*;
+
+mod targets;
+pub use targets::*;
+
+mod verify;
+pub use verify::*;
+
+mod version_spec;
+pub use version_spec::*;
+
+mod notice;
+pub use notice::*;
+
+mod suggest;
+pub use suggest::*;
+
 /// Options for locating a toolchain.
 #[derive(Debug, clap::Args)]
 pub struct LocateArgs {
-    /// The toolchain that should be located.
-    #[arg(short = 'T', long)]
-    toolchain: Option<ToolchainVersion>,
+    /// The toolchain that should be located: a version, "previous", or an alias from the
+    /// config file (default: the pinned or active version).
+    #[arg(short = 'T', long, value_parser = installed_spec)]
+    toolchain: Option<VersionSpec>,
+    /// Which toolchain to locate ("embedded" or "linux").
+    #[arg(long, default_value = "embedded")]
+    product: Product,
     /// Which path should be displayed.
     #[clap(default_value = "install-dir")]
     what: LocateWhat,
@@ -185,13 +783,15 @@ enum LocateWhat {
 
 /// Locate a toolchain's path and print it to stdio.
 pub async fn locate(args: LocateArgs) -> Result<(), CliError> {
-    let client = ToolchainClient::using_data_dir().await?;
-    let version = args
-        .toolchain
-        .or_else(|| client.active_toolchain())
-        .ok_or(CliError::NoToolchainEnabled)?;
+    let client = client_builder().product(args.product).build().await?;
+    let version = match args.toolchain {
+        Some(spec) => spec.resolve_one(&client, Lookup::Installed).await?.version,
+        None => effective_toolchain(args.product, client.active_toolchain())
+            .await?
+            .ok_or(CliError::NoToolchainEnabled)?,
+    };
 
-    let toolchain = client.toolchain(&version).await?;
+    let toolchain = installed_toolchain(&client, &version).await?;
 
     match args.what {
         LocateWhat::InstallDir => {
@@ -211,12 +811,70 @@ pub async fn locate(args: LocateArgs) -> Result<(), CliError> {
     Ok(())
 }
 
-/// Print a list of all toolchains to stdio.
-pub async fn list() -> Result<(), CliError> {
-    let client = ToolchainClient::using_data_dir().await?;
+/// Options for listing toolchains.
+#[derive(Debug, clap::Args)]
+pub struct ListArgs {
+    /// Check whether each installed major version has a newer release available.
+    #[clap(long)]
+    check_updates: bool,
+    /// With `--check-updates`, ask GitHub whether the releases changed, instead of using
+    /// release information that was fetched in the last 15 minutes.
+    #[clap(long, requires = "check_updates")]
+    refresh: bool,
+    /// Show why each toolchain is installed, as recorded by this tool and others that share
+    /// its toolchains directory.
+    #[clap(long, short)]
+    verbose: bool,
+}
+
+/// Print a list of all toolchains to stdio, grouped by product.
+pub async fn list(args: ListArgs) -> Result<(), CliError> {
+    let mut first = true;
 
-    let active = client.active_toolchain();
-    let installed = client.installed_versions().await?;
+    for &product in Product::ALL {
+        let client = client_builder()
+            .product(product)
+            .refresh_releases(args.refresh)
+            .build()
+            .await?;
+
+        let active = client.active_toolchain();
+        let mut installed = client.installed_versions().await?;
+        installed.sort();
+
+        // Products other than the default are only shown once they've been used.
+        if product != Product::default() && active.is_none() && installed.is_empty() {
+            continue;
+        }
+
+        if !first {
+            println!();
+        }
+        first = false;
+
+        println!("{}", product.display_name.bold());
+        list_product(&client, active, &installed, &args).await?;
+    }
+
+    Ok(())
+}
+
+async fn list_product(
+    client: &ToolchainClient,
+    active: Option<ToolchainVersion>,
+    installed: &[ToolchainVersion],
+    args: &ListArgs,
+) -> Result<(), CliError> {
+    let releases = if args.check_updates {
+        Some(client.list_releases(None, true).await?)
+    } else {
+        None
+    };
+    let reasons = if args.verbose {
+        Some(client.install_reasons().await?)
+    } else {
+        None
+    };
 
     println!(
         "Active: {}",
@@ -233,29 +891,90 @@ pub async fn list() -> Result<(), CliError> {
     }
 
     for version in installed {
-        println!("- {version}");
+        let mut notes = Vec::new();
+        if version.is_prerelease() {
+            notes.push("pre-release".to_string());
+        }
+
+        if let Some(releases) = &releases {
+            // Releases are sorted newest first, so the first one with the same major is the
+            // newest. Pre-releases are only offered as updates to other pre-releases.
+            let newest_in_major = version.major().and_then(|major| {
+                releases.iter().find(|r| {
+                    r.version().major() == Some(major)
+                        && (version.is_prerelease() || !r.is_prerelease())
+                })
+            });
+
+            match newest_in_major {
+                Some(newest) if newest.version() > version => {
+                    if installed.contains(newest.version()) {
+                        notes.push(format!(
+                            "superseded by {}, already installed",
+                            newest.version()
+                        ));
+                    } else {
+                        notes.push(format!(
+                            "{} available",
+                            newest.version().to_string().yellow().bold()
+                        ));
+                    }
+                }
+                Some(_) => notes.push("up to date".to_string()),
+                None => {}
+            }
+        }
+
+        if notes.is_empty() {
+            println!("- {version}");
+        } else {
+            println!("- {version} ({})", notes.join(", "));
+        }
+        print_reasons(reasons.as_ref(), version);
+    }
+
+    if let Some(releases) = &releases {
+        let latest = releases.iter().find(|r| !r.is_prerelease());
+        let latest_pre = releases
+            .iter()
+            .find(|r| r.is_prerelease())
+            .filter(|pre| latest.is_none_or(|latest| pre.version() > latest.version()));
+
+        if latest.is_some() || latest_pre.is_some() {
+            println!();
+        }
+        if let Some(latest) = latest {
+            println!("Latest: {}", latest.version());
+        }
+        if let Some(pre) = latest_pre {
+            println!("Latest pre-release: {} (install with --pre)", pre.version());
+        }
     }
 
     Ok(())
 }
 
-/// Purge the download cache and print results to stdio.
-pub async fn purge_cache() -> Result<(), CliError> {
-    let client = ToolchainClient::using_data_dir().await?;
-    let bytes = client.purge_cache().await?;
-
-    println!(
-        "ARM Toolchain download cache purged ({} deleted)",
-        humansize::format_size(bytes, DECIMAL)
-    );
+/// Prints the install reasons for a version under its entry in the list, if they were
+/// requested.
+fn print_reasons(reasons: Option<&InstallReasons>, version: &ToolchainVersion) {
+    let Some(reasons) = reasons else {
+        return;
+    };
 
-    Ok(())
+    match reasons.get(version) {
+        [] => println!("    (no install reasons recorded)"),
+        reasons => {
+            for reason in reasons {
+                println!("    {reason}");
+            }
+        }
+    }
 }
 
 macro_rules! msg {
     ($label:expr, $($rest:tt)+) => {
         {
-            use owo_colors::OwoColorize;
+            use $crate::cli::Colorize;
             eprintln!("{:>12} {}", $label.green().bold(), format_args!($($rest)+))
         }
     };
@@ -294,44 +1013,115 @@ pub fn ctrl_c_cancel() -> CancellationToken {
     cancel_token
 }
 
+/// The exit code used when a command did what it was asked to, but a step after that failed
+/// and was reported as a warning. For example, a toolchain was installed but couldn't be
+/// activated.
+pub const PARTIAL_SUCCESS_EXIT_CODE: u8 = 3;
+
+/// The exit code used by `verify` when files in the toolchain were modified, added, or
+/// removed since it was installed.
+pub const VERIFY_MODIFIED_EXIT_CODE: u8 = 4;
+
+/// The exit code used by `verify` when the toolchain can't be verified because no file
+/// digests were recorded when it was installed.
+pub const VERIFY_UNAVAILABLE_EXIT_CODE: u8 = 5;
+
+/// The code to exit with once the command returns successfully, or 0 for success.
+static EXIT_CODE: AtomicU8 = AtomicU8::new(0);
+
+/// Records that the process should exit with `code` after the command returns.
+///
+/// If several codes are set, the highest one is used.
+pub(crate) fn set_exit_code(code: u8) {
+    EXIT_CODE.fetch_max(code, Ordering::Relaxed);
+}
+
+/// Records that a warning was reported about a step that failed after the command's main
+/// work succeeded, so that the process exits with [`PARTIAL_SUCCESS_EXIT_CODE`].
+pub(crate) fn set_partial_success() {
+    set_exit_code(PARTIAL_SUCCESS_EXIT_CODE);
+}
+
+/// Returns the code the process should exit with after
+/// [`ArmToolchainCmd::run`] returned successfully.
+///
+/// This is [`ExitCode::SUCCESS`] unless part of the command failed in a way that didn't
+/// stop it, in which case it's [`PARTIAL_SUCCESS_EXIT_CODE`], or `verify` found a problem, in
+/// which case it's [`VERIFY_MODIFIED_EXIT_CODE`] or [`VERIFY_UNAVAILABLE_EXIT_CODE`].
+pub fn exit_code() -> ExitCode {
+    match EXIT_CODE.load(Ordering::Relaxed) {
+        0 => ExitCode::SUCCESS,
+        code => ExitCode::from(code),
+    }
+}
+
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Forbids every client created by the CLI from accessing the network, for the global
+/// `--offline` flag.
+pub fn set_offline(offline: bool) {
+    OFFLINE.store(offline, Ordering::Relaxed);
+}
+
+/// Returns a builder for a client with the CLI's global options applied, which every command
+/// should create its clients with.
+pub(crate) fn client_builder() -> ToolchainClientBuilder {
+    ToolchainClient::builder().offline(OFFLINE.load(Ordering::Relaxed))
+}
+
+#[cfg(feature = "cli-interactive")]
 const PROGRESS_CHARS: &str = "=> ";
 
+#[cfg(feature = "cli-interactive")]
 pub static PROGRESS_STYLE_DL: LazyLock<ProgressStyle> = LazyLock::new(|| {
     ProgressStyle::with_template("{percent:>3.bold}% [{bar:40.blue}] ({bytes}/{total_bytes}, {eta} remaining) {bytes_per_sec}")
     .expect("progress style valid")
     .progress_chars(PROGRESS_CHARS)
 });
 
+#[cfg(feature = "cli-interactive")]
+pub static PROGRESS_STYLE_DL_SPINNER: LazyLock<ProgressStyle> = LazyLock::new(|| {
+    ProgressStyle::with_template("{spinner:.blue} {bytes} downloaded ({elapsed}) {bytes_per_sec}")
+        .expect("progress style valid")
+        .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏✓")
+});
+
+#[cfg(feature = "cli-interactive")]
 pub static PROGRESS_STYLE_DL_MSG: LazyLock<ProgressStyle> = LazyLock::new(|| {
     ProgressStyle::with_template("{percent:>3.bold}% [{bar:40.blue}] ({bytes}/{total_bytes}) {msg}")
         .expect("progress style valid")
         .progress_chars(PROGRESS_CHARS)
 });
 
+#[cfg(feature = "cli-interactive")]
 pub static PROGRESS_STYLE_VERIFY: LazyLock<ProgressStyle> = LazyLock::new(|| {
     ProgressStyle::with_template("{percent:>3.bold}% [{bar:40.green}] {msg} ({eta} remaining)")
         .expect("progress style valid")
         .progress_chars(PROGRESS_CHARS)
 });
 
+#[cfg(feature = "cli-interactive")]
 pub static PROGRESS_STYLE_EXTRACT_SPINNER: LazyLock<ProgressStyle> = LazyLock::new(|| {
     ProgressStyle::with_template("{spinner:.green} {msg}")
         .expect("progress style valid")
         .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏✓")
 });
 
+#[cfg(feature = "cli-interactive")]
 pub static PROGRESS_STYLE_EXTRACT: LazyLock<ProgressStyle> = LazyLock::new(|| {
     ProgressStyle::with_template("{percent:>3.bold}% [{bar:40.dim}] {msg} ({eta} remaining)")
         .expect("progress style valid")
         .progress_chars(PROGRESS_CHARS)
 });
 
+#[cfg(feature = "cli-interactive")]
 pub static PROGRESS_STYLE_DELETE_SPINNER: LazyLock<ProgressStyle> = LazyLock::new(|| {
     ProgressStyle::with_template("{spinner:.red} {msg}")
         .expect("progress style valid")
         .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏✓")
 });
 
+#[cfg(feature = "cli-interactive")]
 pub static PROGRESS_STYLE_DELETE: LazyLock<ProgressStyle> = LazyLock::new(|| {
     ProgressStyle::with_template("{percent:>3.bold}% [{bar:40.red}] {msg} ({eta} remaining)")
         .expect("progress style valid")
@@ -343,3 +1133,130 @@ pub const STYLES: styling::Styles = styling::Styles::styled()
     .usage(styling::AnsiColor::Green.on_default().bold())
     .literal(styling::AnsiColor::Blue.on_default().bold())
     .placeholder(styling::AnsiColor::Cyan.on_default());
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::toolchain::{KeptArchive, Phase};
+
+    fn cancelled(phase: Phase, archive: Option<(u64, u64)>) -> CancelledState {
+        CancelledState {
+            version: ToolchainVersion::named("21.1.0"),
+            phase,
+            archive: archive.map(|(downloaded_bytes, total_bytes)| KeptArchive {
+                path: PathBuf::from("/cache/ATfE-21.1.0-Linux-x86_64.tar.xz"),
+                downloaded_bytes,
+                total_bytes,
+            }),
+        }
+    }
+
+    #[test]
+    fn cancelled_help_describes_what_was_kept() {
+        let resume = "arm-toolchain install v21.1.0";
+        for (state, kept) in [
+            (
+                cancelled(Phase::Download, None),
+                "Nothing was downloaded yet.",
+            ),
+            (
+                cancelled(Phase::Download, Some((250_000_000, 1_000_000_000))),
+                "The partial download was kept at /cache/ATfE-21.1.0-Linux-x86_64.tar.xz (250 MB, 25% downloaded).",
+            ),
+            // Without a known size, there's no percentage, and it can't be complete.
+            (
+                cancelled(Phase::Download, Some((250_000_000, 0))),
+                "The partial download was kept at /cache/ATfE-21.1.0-Linux-x86_64.tar.xz (250 MB downloaded).",
+            ),
+            (
+                cancelled(Phase::Extract, Some((1_000_000_000, 1_000_000_000))),
+                "The downloaded archive was kept at /cache/ATfE-21.1.0-Linux-x86_64.tar.xz, so it won't be downloaded again.",
+            ),
+        ] {
+            assert_eq!(
+                cancelled_help(&state, resume),
+                format!("{kept}\nResume the installation with `{resume}`.")
+            );
+        }
+    }
+
+    #[test]
+    fn cancelled_install_details_carry_the_state() {
+        let error = CliError::InstallCancelled {
+            state: Box::new(cancelled(Phase::Verify, Some((1_000, 1_000)))),
+            resume_command: "arm-toolchain install v21.1.0".to_string(),
+        };
+
+        assert_eq!(
+            error.details(),
+            Some(serde_json::json!({
+                "cancelled": {
+                    "version": "21.1.0",
+                    "phase": "verify",
+                    "archive": {
+                        "path": "/cache/ATfE-21.1.0-Linux-x86_64.tar.xz",
+                        "downloaded_bytes": 1_000,
+                        "total_bytes": 1_000,
+                    },
+                },
+                "resume_command": "arm-toolchain install v21.1.0",
+            }))
+        );
+    }
+
+    #[test]
+    fn missing_toolchain_help_suggests_a_command_or_similar_versions() {
+        let version = ToolchainVersion::named("20.1.0");
+        let help = |error: CliError| (error.to_string(), error.help().map(|help| help.to_string()));
+
+        assert_eq!(
+            help(CliError::ToolchainNotInstalled {
+                version: version.clone(),
+                suggestions: vec![],
+            }),
+            (
+                "The toolchain v20.1.0 is not installed.".to_string(),
+                Some(
+                    "Install and activate it by running the `install v20.1.0` subcommand."
+                        .to_string()
+                ),
+            )
+        );
+        assert_eq!(
+            help(CliError::ToolchainNotInstalled {
+                version: version.clone(),
+                suggestions: vec![ToolchainVersion::named("20.1.1")],
+            }),
+            (
+                "The toolchain v20.1.0 is not installed.".to_string(),
+                Some("Did you mean v20.1.1?".to_string()),
+            )
+        );
+        assert_eq!(
+            help(CliError::CannotRemoveMissingToolchain {
+                version: version.clone(),
+                suggestions: vec![],
+            }),
+            (
+                "The toolchain v20.1.0 is not installed.".to_string(),
+                Some("Run `list` to see which toolchains are installed.".to_string()),
+            )
+        );
+        assert_eq!(
+            help(CliError::CannotRemoveMissingToolchain {
+                version,
+                suggestions: vec![
+                    ToolchainVersion::named("20.1.1"),
+                    ToolchainVersion::named("21.1.0"),
+                    ToolchainVersion::named("19.1.5"),
+                ],
+            }),
+            (
+                "The toolchain v20.1.0 is not installed.".to_string(),
+                Some("Did you mean v20.1.1, v21.1.0 or v19.1.5?".to_string()),
+            )
+        );
+    }
+}
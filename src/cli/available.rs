@@ -0,0 +1,157 @@
+use humansize::DECIMAL;
+use serde::Serialize;
+
+use crate::{
+    cli::{CliError, Colorize, HostArgs, OutputFormat, client_builder},
+    toolchain::{Product, ReleaseAssetInfo, ToolchainVersion},
+};
+
+/// Configuration for [`available`].
+#[derive(Debug, clap::Parser)]
+pub struct AvailableArgs {
+    /// Only list releases newer than this version.
+    #[clap(long)]
+    pub since: Option<ToolchainVersion>,
+    /// Only list releases that can be installed on this host, or the one chosen with
+    /// `--host-os` and `--host-arch`.
+    #[clap(long)]
+    pub host_only: bool,
+    #[clap(flatten)]
+    pub host: HostArgs,
+    /// Include each release's downloadable assets, with their sizes and URLs.
+    #[clap(long)]
+    pub assets: bool,
+    /// Include each asset's SHA256 digest. Implies `--assets`.
+    ///
+    /// GitHub's digest is used when it has one. Otherwise, the asset's `.sha256` file is
+    /// downloaded. Assets whose digest can't be resolved are reported with an error instead
+    /// of failing the listing.
+    #[clap(long)]
+    pub digests: bool,
+    /// Which toolchain to list releases of ("embedded" or "linux").
+    #[clap(long, default_value = "embedded")]
+    pub product: Product,
+    /// Ask GitHub whether the releases changed, instead of using release information that
+    /// was fetched in the last 15 minutes.
+    #[clap(long)]
+    pub refresh: bool,
+    /// How the result should be reported.
+    #[clap(long, default_value = "human")]
+    pub output: OutputFormat,
+}
+
+/// The result of [`available`].
+#[derive(Debug, Serialize)]
+pub struct AvailableReport {
+    /// The releases that matched the filters, newest first.
+    pub releases: Vec<AvailableRelease>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AvailableRelease {
+    pub version: ToolchainVersion,
+    pub tag_name: String,
+    /// Whether this version is already installed.
+    pub installed: bool,
+    /// Whether the release is a pre-release, such as a release candidate.
+    pub prerelease: bool,
+    /// The release's assets, if `--assets` or `--digests` was passed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assets: Option<Vec<ReleaseAssetInfo>>,
+}
+
+/// List the releases that are available to install.
+pub async fn available(args: AvailableArgs) -> Result<AvailableReport, CliError> {
+    let client = client_builder()
+        .product(args.product)
+        .refresh_releases(args.refresh)
+        .build()
+        .await?;
+
+    let mut releases = match &args.since {
+        Some(since) => client.releases_since(since).await?,
+        None => client.list_releases(None, true).await?,
+    };
+
+    if args.host_only {
+        let (os, arches) = args.host.host()?;
+        releases.retain(|release| release.supports(os, &arches));
+    }
+
+    let installed = client.installed_versions().await?;
+    let active = client.active_toolchain();
+
+    if releases.is_empty() {
+        match &args.since {
+            Some(since) => args
+                .output
+                .message(format_args!("No releases are newer than {since}.")),
+            None => args.output.message("No releases were found."),
+        }
+    }
+
+    let mut report = AvailableReport { releases: vec![] };
+    for release in &releases {
+        let version = release.version();
+        let mut notes = Vec::new();
+        if release.is_prerelease() {
+            notes.push("pre-release");
+        }
+        if active.as_ref() == Some(version) {
+            notes.push("active");
+        } else if installed.contains(version) {
+            notes.push("installed");
+        }
+        let note = if notes.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", notes.join(", "))
+        };
+        args.output
+            .message(format_args!("- {}{}", version.bold(), note));
+
+        // Releases are handled one at a time so that only one release's checksum files are
+        // being downloaded at once.
+        let assets = if args.digests {
+            Some(client.release_assets_with_digests(release).await)
+        } else if args.assets {
+            Some(client.release_assets(release))
+        } else {
+            None
+        };
+        for asset in assets.iter().flatten() {
+            report_asset(asset, args.output);
+        }
+
+        report.releases.push(AvailableRelease {
+            version: version.clone(),
+            tag_name: release.tag_name().to_string(),
+            installed: installed.contains(version),
+            prerelease: release.is_prerelease(),
+            assets,
+        });
+    }
+
+    Ok(report)
+}
+
+/// Prints an asset under its release in the list.
+fn report_asset(asset: &ReleaseAssetInfo, output: OutputFormat) {
+    output.message(format_args!(
+        "    {} ({})",
+        asset.name,
+        humansize::format_size(asset.size, DECIMAL)
+    ));
+    output.message(format_args!("      {}", asset.url));
+
+    if let Some(sha256) = &asset.sha256 {
+        output.message(format_args!("      sha256: {sha256}"));
+    } else if let Some(error) = &asset.digest_error {
+        // The cause (such as a 404 status) says more than the error itself.
+        let reason = error.causes.first().unwrap_or(&error.message);
+        output.message(format_args!(
+            "      sha256: {} ({reason})",
+            "unavailable".yellow(),
+        ));
+    }
+}
@@ -0,0 +1,268 @@
+use std::{
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use serde::Serialize;
+use zip::{ZipWriter, write::SimpleFileOptions};
+
+use crate::{
+    cli::{CliError, Config, Pin, PinFile, client_builder},
+    fs,
+    toolchain::{
+        InstalledToolchain, Product, ToolchainClient, ToolchainError, ToolchainVersion,
+        journal::Journal,
+    },
+};
+
+/// The file that debug logs are being written to, if the binary was asked to write them
+/// to one with `--log-file` or the `log_file` config option.
+///
+/// This is included in support bundles created by [`create_bundle`].
+pub static LOG_FILE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Text that replaces secrets in a support bundle.
+const REDACTED: &str = "[redacted]";
+
+/// The toolchains of one product, as recorded in a support bundle.
+#[derive(Debug, Serialize)]
+struct ProductInventory {
+    product: &'static str,
+    active: Option<ToolchainVersion>,
+    installed: Vec<InstalledInventory>,
+}
+
+#[derive(Debug, Serialize)]
+struct InstalledInventory {
+    version: ToolchainVersion,
+    path: PathBuf,
+    /// What's missing from the toolchain, if anything.
+    problems: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Inventory {
+    arm_toolchain_version: &'static str,
+    host_os: &'static str,
+    host_arch: &'static str,
+    products: Vec<ProductInventory>,
+}
+
+/// Writes a zip archive containing everything needed to diagnose a problem: the log file,
+/// the config file, each product's active toolchain file, the journal, the project's pin file,
+/// and a list of the installed toolchains.
+///
+/// Anything that looks like a token or password is redacted. Returns the names of the files
+/// in the archive.
+pub async fn create_bundle(path: &Path) -> Result<Vec<String>, CliError> {
+    let mut files: Vec<(String, String)> = vec![];
+
+    let log_file = match LOG_FILE.get() {
+        Some(path) => Some(path.clone()),
+        None => Config::load().await.ok().and_then(|config| config.log_file),
+    };
+    if let Some(log_file) = log_file
+        && let Some(contents) = read_optional(&log_file).await?
+    {
+        files.push(("arm-toolchain.log".to_string(), contents));
+    }
+
+    if let Some(config_path) = Config::path()
+        && let Some(contents) = read_optional(&config_path).await?
+    {
+        files.push((Config::FILENAME.to_string(), contents));
+    }
+
+    let client = client_builder().build().await?;
+    let journal = client.journal();
+    for journal_path in [
+        journal.path().to_owned(),
+        journal.path().with_file_name(Journal::ROTATED_FILENAME),
+    ] {
+        if let Some(contents) = read_optional(&journal_path).await? {
+            let name = journal_path.file_name().unwrap().to_string_lossy();
+            files.push((name.into_owned(), contents));
+        }
+    }
+
+    let mut products = vec![];
+    for &product in Product::ALL {
+        let client = client_builder().product(product).build().await?;
+
+        let current_path = client
+            .toolchains_path
+            .join(product.id)
+            .join(ToolchainClient::CURRENT_TOOLCHAIN_FILENAME);
+        // The active toolchain file may be a directory or otherwise unreadable, which is
+        // worth knowing about but shouldn't stop the bundle from being created.
+        let current = match fs::read_to_string(&current_path).await {
+            Ok(contents) => Some(contents),
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => Some(format!("<unreadable: {e}>")),
+        };
+        if let Some(current) = current {
+            files.push((
+                format!(
+                    "{}/{}",
+                    product.id,
+                    ToolchainClient::CURRENT_TOOLCHAIN_FILENAME
+                ),
+                current,
+            ));
+        }
+
+        let mut installed = vec![];
+        let mut versions = client.installed_versions().await?;
+        versions.sort();
+        for version in versions {
+            let path = client.install_path_for(&version);
+            let problems = match InstalledToolchain::new(path.clone())
+                .check_structure(product)
+                .await
+            {
+                Ok(()) => vec![],
+                Err(ToolchainError::IncompleteExtraction { details, .. }) => details,
+                Err(error) => vec![error.to_string()],
+            };
+
+            installed.push(InstalledInventory {
+                version,
+                path,
+                problems,
+            });
+        }
+
+        products.push(ProductInventory {
+            product: product.id,
+            active: client.active_toolchain(),
+            installed,
+        });
+    }
+
+    let inventory = Inventory {
+        arm_toolchain_version: env!("CARGO_PKG_VERSION"),
+        host_os: std::env::consts::OS,
+        host_arch: std::env::consts::ARCH,
+        products,
+    };
+    files.push((
+        "toolchains.json".to_string(),
+        serde_json::to_string_pretty(&inventory).expect("inventories are serializable"),
+    ));
+
+    if let Some(pin_file) = PinFile::find(&std::env::current_dir()?)
+        .await
+        .ok()
+        .flatten()
+        && let Some(contents) = read_optional(&pin_file.path).await?
+    {
+        files.push((Pin::FILENAME.to_string(), contents));
+    }
+
+    let names = files.iter().map(|(name, _)| name.clone()).collect();
+
+    let bundle_path = path.to_owned();
+    tokio::task::spawn_blocking(move || write_zip(&bundle_path, files))
+        .await
+        .unwrap()
+        .map_err(|source| CliError::Bundle {
+            path: path.to_owned(),
+            source,
+        })?;
+
+    Ok(names)
+}
+
+/// Reads a text file, or returns `None` if it doesn't exist.
+async fn read_optional(path: &Path) -> Result<Option<String>, CliError> {
+    match fs::read(path).await {
+        Ok(bytes) => Ok(Some(String::from_utf8_lossy(&bytes).into_owned())),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn write_zip(path: &Path, files: Vec<(String, String)>) -> zip::result::ZipResult<()> {
+    let mut zip = ZipWriter::new(std::fs::File::create(path)?);
+    let options = SimpleFileOptions::default();
+
+    for (name, contents) in files {
+        zip.start_file(name, options)?;
+        zip.write_all(redact(&contents).as_bytes())?;
+    }
+
+    zip.finish()?;
+    Ok(())
+}
+
+/// Replaces anything that looks like a secret with a placeholder.
+///
+/// This covers the values of settings whose names mention tokens, secrets, or passwords
+/// (such as `GITHUB_TOKEN=...` or `token: "..."`), and GitHub tokens anywhere else.
+/// Authorization headers are redacted to the end of the line, since their values contain
+/// spaces (such as `Authorization: Bearer ...`).
+pub fn redact(text: &str) -> String {
+    const SECRET_KEYS: &[&str] = &["token", "secret", "password", "authorization"];
+    const WHOLE_LINE_KEYS: &[&str] = &["authorization"];
+    const TOKEN_PREFIXES: &[&str] = &["github_pat_", "ghp_", "gho_", "ghu_", "ghs_", "ghr_"];
+
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+    let is_value_end = |c: char| c.is_whitespace() || matches!(c, '"' | '\'' | ',' | ';' | '}');
+
+    // Ranges of the text to replace, which may overlap.
+    let mut secrets = vec![];
+    let lowercase = text.to_ascii_lowercase();
+
+    for key in SECRET_KEYS {
+        for (key_start, _) in lowercase.match_indices(key) {
+            // Find the end of the setting's name (e.g. the rest of `github_token`), then
+            // the separator between it and its value.
+            let after_key = &text[key_start + key.len()..];
+            let name_len = after_key.find(|c| !is_word(c)).unwrap_or(after_key.len());
+            let after_name = &after_key[name_len..];
+            let trimmed = after_name.trim_start_matches([' ', '"', '\'']);
+            let Some(after_separator) = trimmed.strip_prefix(['=', ':']) else {
+                continue;
+            };
+
+            let value = after_separator.trim_start_matches([' ', '"', '\'']);
+            let value_start = text.len() - value.len();
+            let value_len = if WHOLE_LINE_KEYS.contains(key) {
+                value.find(['\r', '\n']).unwrap_or(value.len())
+            } else {
+                value.find(is_value_end).unwrap_or(value.len())
+            };
+            if value_len > 0 {
+                secrets.push(value_start..value_start + value_len);
+            }
+        }
+    }
+
+    for prefix in TOKEN_PREFIXES {
+        for (start, _) in text.match_indices(prefix) {
+            let rest = &text[start..];
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            secrets.push(start..start + len);
+        }
+    }
+
+    secrets.sort_by_key(|range| range.start);
+
+    let mut redacted = String::with_capacity(text.len());
+    let mut copied_to = 0;
+    for range in secrets {
+        if range.end <= copied_to {
+            continue;
+        }
+
+        redacted.push_str(&text[copied_to..range.start.max(copied_to)]);
+        redacted.push_str(REDACTED);
+        copied_to = range.end;
+    }
+    redacted.push_str(&text[copied_to..]);
+
+    redacted
+}
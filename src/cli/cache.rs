@@ -0,0 +1,169 @@
+use humansize::DECIMAL;
+
+use crate::{
+    cli::{
+        CliError, Colorize, Config, HostArgs, Lookup, OutputFormat, VersionSpec, client_builder,
+        installable_spec, msg,
+    },
+    toolchain::{ArchiveFormats, AssetName, Product, PurgeReport},
+};
+
+/// Configuration for [`purge_cache`].
+#[derive(Debug, clap::Args)]
+pub struct PurgeCacheArgs {
+    /// Only print files that couldn't be deleted.
+    #[clap(long, short)]
+    pub quiet: bool,
+    /// How the result should be reported.
+    #[clap(long, default_value = "human")]
+    pub output: OutputFormat,
+}
+
+/// Purge the download cache and print results to stdio.
+pub async fn purge_cache(args: PurgeCacheArgs) -> Result<PurgeReport, CliError> {
+    let client = client_builder().build().await?;
+    let report = client.purge_cache().await?;
+
+    if !args.quiet {
+        for entry in &report.removed {
+            args.output.message(format_args!(
+                "Deleted {} ({})",
+                entry.path.display(),
+                humansize::format_size(entry.bytes, DECIMAL)
+            ));
+        }
+    }
+
+    for failure in &report.failed {
+        eprintln!(
+            "{} could not delete {}: {}",
+            "warning:".yellow().bold(),
+            failure.path.display(),
+            failure.error
+        );
+    }
+
+    if !args.quiet {
+        args.output.message(format_args!(
+            "ARM Toolchain download cache purged ({} deleted{})",
+            humansize::format_size(report.bytes_removed, DECIMAL),
+            if report.failed.is_empty() {
+                String::new()
+            } else {
+                format!(", {} files kept", report.failed.len())
+            }
+        ));
+    }
+
+    Ok(report)
+}
+
+/// Manage the cache which stores incomplete downloads.
+#[derive(Debug, clap::Subcommand)]
+pub enum CacheCmd {
+    /// Delete the cached download of one asset, keeping other downloads.
+    #[clap(visible_alias("rm"))]
+    Remove(CacheRemoveArgs),
+    /// Print the path that an asset is downloaded to, so that it can be placed there
+    /// beforehand.
+    ///
+    /// The file name is always the asset's name, so it doesn't change between versions of
+    /// this tool.
+    Path(CachePathArgs),
+}
+
+/// Configuration for [`cache_remove`].
+#[derive(Debug, clap::Args)]
+pub struct CacheRemoveArgs {
+    /// File name of the cached asset, or a toolchain version to remove all of its assets.
+    pub name: String,
+}
+
+/// Configuration for [`cache_path`].
+#[derive(Debug, clap::Args)]
+pub struct CachePathArgs {
+    /// File name of an asset, or a toolchain version, "latest", or an alias from the config
+    /// file to use its asset for the selected host.
+    #[clap(value_parser = cache_target)]
+    pub target: CacheTarget,
+    /// Which toolchain the version refers to ("embedded" or "linux").
+    #[clap(long, default_value = "embedded")]
+    pub product: Product,
+    #[clap(flatten)]
+    pub host: HostArgs,
+}
+
+/// What [`cache_path`] prints the path of.
+#[derive(Debug, Clone)]
+pub enum CacheTarget {
+    /// An asset, by its file name.
+    Asset(String),
+    /// The asset of a release for the selected host.
+    Version(VersionSpec),
+}
+
+/// Parses a [`CacheTarget`]. Names of archives in a known format or toolchain assets are
+/// assets, and anything else is a version.
+fn cache_target(value: &str) -> Result<CacheTarget, String> {
+    if ArchiveFormats::builtin().supports(value) || AssetName::parse(value).is_some() {
+        Ok(CacheTarget::Asset(value.to_string()))
+    } else {
+        installable_spec(value).map(CacheTarget::Version)
+    }
+}
+
+impl CacheCmd {
+    /// Run the command.
+    pub async fn run(self) -> Result<(), CliError> {
+        match self {
+            CacheCmd::Remove(args) => cache_remove(args).await,
+            CacheCmd::Path(args) => cache_path(args).await,
+        }
+    }
+}
+
+/// Remove a single entry from the download cache and print results to stdio.
+pub async fn cache_remove(args: CacheRemoveArgs) -> Result<(), CliError> {
+    let client = client_builder().build().await?;
+    let bytes = client.evict_cached(&args.name).await?;
+
+    if bytes == 0 {
+        println!("Nothing named {:?} is in the download cache", args.name);
+    } else {
+        msg!(
+            "Removed",
+            "{} from the download cache ({})",
+            args.name,
+            humansize::format_size(bytes, DECIMAL)
+        );
+    }
+
+    Ok(())
+}
+
+/// Print the path in the download cache that an asset is downloaded to.
+///
+/// Only versions are looked up on GitHub, to find the name of their asset.
+pub async fn cache_path(mut args: CachePathArgs) -> Result<(), CliError> {
+    // The same build as `install` would download is chosen.
+    let config = Config::load().await?;
+    args.host.set_default_preference(config.arch_preference);
+
+    let client = client_builder().product(args.product).build().await?;
+
+    let path = match &args.target {
+        CacheTarget::Asset(name) => client.cache_path_for_name(name)?,
+        CacheTarget::Version(spec) => {
+            let requested = spec.resolve_one(&client, Lookup::Releases).await?;
+            let release = match requested.release {
+                Some(release) => release,
+                None => client.get_release(&requested.version).await?,
+            };
+            let asset = args.host.select_asset(&client, &release)?;
+            client.cache_path_for(asset)?
+        }
+    };
+
+    println!("{}", path.display());
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use std::{io::Write, path::PathBuf};
+
+use clap::CommandFactory;
+use clap_complete::Shell;
+
+use crate::cli::{CliArgs, CliError};
+
+/// Configuration for [`completions`].
+#[derive(Debug, clap::Args)]
+pub struct CompletionsArgs {
+    /// The shell to generate completions for (default: the current shell).
+    pub shell: Option<Shell>,
+}
+
+/// Print the completion script for a shell.
+pub fn completions(args: CompletionsArgs) -> Result<(), CliError> {
+    let shell = args.shell.or_else(Shell::from_env).unwrap_or(Shell::Bash);
+    write_completions(shell, &mut std::io::stdout().lock());
+    Ok(())
+}
+
+/// Writes the completion script for `arm-toolchain` in the given shell.
+pub fn write_completions(shell: Shell, out: &mut dyn Write) {
+    clap_complete::generate(shell, &mut CliArgs::command(), "arm-toolchain", out);
+}
+
+/// Returns the file that `setup` writes a shell's completion script to, or `None` if the
+/// shell has to load the script from its startup file instead.
+pub fn completions_path(shell: Shell) -> Option<PathBuf> {
+    let dirs = directories::BaseDirs::new()?;
+    match shell {
+        Shell::Bash => Some(
+            dirs.data_local_dir()
+                .join("bash-completion/completions/arm-toolchain"),
+        ),
+        Shell::Fish => Some(
+            dirs.home_dir()
+                .join(".config/fish/completions/arm-toolchain.fish"),
+        ),
+        // This isn't in `$fpath` by default, so it has to be added in `~/.zshrc`.
+        Shell::Zsh => Some(dirs.home_dir().join(".zfunc/_arm-toolchain")),
+        _ => None,
+    }
+}
@@ -0,0 +1,111 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use miette::Diagnostic;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{
+    DIRS,
+    cli::CliError,
+    fs,
+    toolchain::{ArchPreference, PermissionPolicy},
+};
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("Failed to load the configuration file at {}", path.display())]
+#[diagnostic(code(arm_toolchain::cli::invalid_config))]
+pub struct ConfigError {
+    path: PathBuf,
+    #[source]
+    source: toml::de::Error,
+}
+
+/// User preferences loaded from the `config.toml` file in the platform-specific
+/// config directory.
+///
+/// For example, on Linux this is `~/.config/arm-toolchain/config.toml`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The permissions applied to newly installed toolchains.
+    ///
+    /// Can be set to `"preserve"`, `"shared-read"`, or `{ mask = 0o022 }`.
+    pub permissions: Option<PermissionPolicy>,
+    /// A file that debug logs are appended to, as if `--log-file` was passed.
+    pub log_file: Option<PathBuf>,
+    /// A local file listing the approved checksums of toolchain assets, as if
+    /// `--checksum-file` was passed. Checksums are never downloaded when this is set.
+    pub checksum_file: Option<PathBuf>,
+    /// A file that a snapshot of metrics (such as the number of installed toolchains) is
+    /// written to after every command that changes the toolchains, in the Prometheus text
+    /// format read by node_exporter's textfile collector.
+    pub metrics_textfile: Option<PathBuf>,
+    /// Names that can be used instead of a version on the command line, such as
+    /// `stable = "20.1.0"`. Each one refers to a version or `"latest"`.
+    pub aliases: BTreeMap<String, String>,
+    /// Whether `run` mentions when a newer toolchain has been released. GitHub is asked
+    /// about the latest release at most once a day, and never when this is off.
+    pub notify_updates: bool,
+    /// Which build to install on hosts that can run both a native build and a universal one,
+    /// such as Macs: `"native"` (the default) or `"universal"`.
+    ///
+    /// `update` keeps installing the same kind of build as the active toolchain, so changing
+    /// this only affects toolchains that are installed with `install` or `use`.
+    pub arch_preference: Option<ArchPreference>,
+    /// Options for the `run` command.
+    pub run: RunConfig,
+    /// Options for unattended updates (`update --daemon` and `update --once`).
+    pub update: UpdateConfig,
+}
+
+/// The `[run]` section of the configuration file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RunConfig {
+    /// Extra environment variables to set when running a command, which take precedence
+    /// over the ones set by default.
+    ///
+    /// Values can contain placeholders such as `{install_dir}`, which are replaced with
+    /// paths from the toolchain (see [`PLACEHOLDERS`](crate::cli::PLACEHOLDERS)).
+    pub env: BTreeMap<String, String>,
+    /// Environment variables that are passed through to the command when it's run with
+    /// `--isolate`, in addition to the ones passed with `--keep`.
+    pub keep: Vec<String>,
+}
+
+/// The `[update]` section of the configuration file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UpdateConfig {
+    /// How many superseded toolchains to keep after an unattended update activates a newer
+    /// one. Older ones are removed.
+    ///
+    /// Only toolchains that were installed by `update` are removed, so ones installed by
+    /// hand or needed by other tools are always kept. Nothing is removed if this isn't set.
+    pub keep: Option<usize>,
+}
+
+impl Config {
+    pub const FILENAME: &str = "config.toml";
+
+    /// Returns the path of the configuration file, or `None` if there is no home directory.
+    pub fn path() -> Option<PathBuf> {
+        Some(DIRS.as_ref()?.config_dir().join(Self::FILENAME))
+    }
+
+    /// Loads the configuration file, or returns the default configuration if it doesn't exist.
+    pub async fn load() -> Result<Self, CliError> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+
+        let contents = match fs::read_to_string(&path).await {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let config = toml::from_str(&contents).map_err(|source| ConfigError { path, source })?;
+        Ok(config)
+    }
+}
@@ -0,0 +1,405 @@
+use std::path::PathBuf;
+
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    cli::{CliError, Colorize, client_builder, create_bundle, msg},
+    toolchain::{
+        InstalledToolchain, InterruptedOperation, Product, RecordedVersions, ToolchainClient,
+        ToolchainError, ToolchainVersion, UnusableInstall, UnusableReason, VersionMatch,
+        journal::Operation,
+    },
+};
+
+/// Configuration for [`doctor`].
+#[derive(Debug, clap::Parser)]
+pub struct DoctorArgs {
+    /// Fix the problems that can be fixed automatically.
+    ///
+    /// Binaries in installed toolchains that have lost their execute permission (e.g. after
+    /// being copied through a FAT-formatted drive) are made executable, invalid active
+    /// toolchain files are moved to the trash, and so are toolchains left behind by
+    /// interrupted or failed installs and removals.
+    #[clap(long, visible_alias = "fix-permissions")]
+    pub fix: bool,
+    /// Write a zip archive containing the logs, configuration, and list of installed
+    /// toolchains to this path, for attaching to a bug report.
+    ///
+    /// Tokens and passwords are redacted.
+    #[clap(long, value_name = "PATH")]
+    pub bundle: Option<PathBuf>,
+}
+
+/// Check the toolchains directory for problems and print recommendations to stdio.
+pub async fn doctor(args: DoctorArgs) -> Result<(), CliError> {
+    let client = client_builder().build().await?;
+    if args.fix {
+        clean_up_interrupted(&client).await?;
+    }
+    let interrupted = client.interrupted_operations().await?;
+    let incomplete = incomplete_toolchains().await?;
+    let invalid_active = invalid_active_toolchain_files(args.fix).await?;
+    let ambiguous = ambiguous_toolchains().await?;
+    let mismatched = mismatched_toolchains().await?;
+    let unusable = unusable_toolchains().await?;
+
+    if args.fix {
+        fix_permissions().await?;
+    }
+
+    if let Some(path) = &args.bundle {
+        let files = create_bundle(path).await?;
+        msg!("Bundled", "{} file(s) into {}", files.len(), path.display());
+    }
+
+    if interrupted.is_empty()
+        && incomplete.is_empty()
+        && invalid_active.is_empty()
+        && ambiguous.is_empty()
+        && mismatched.is_empty()
+        && unusable.is_empty()
+    {
+        msg!("Checked", "no problems found");
+        return Ok(());
+    }
+
+    if !invalid_active.is_empty() {
+        println!(
+            "Found {} invalid active toolchain file(s):",
+            invalid_active.len().to_string().bold()
+        );
+
+        for error in &invalid_active {
+            println!();
+            println!("- {error}");
+            println!(
+                "  Move it to the trash with `doctor --fix`, then choose the active toolchain with `use`."
+            );
+        }
+
+        if !interrupted.is_empty() || !incomplete.is_empty() {
+            println!();
+        }
+    }
+
+    if !interrupted.is_empty() {
+        println!(
+            "Found {} interrupted operation(s):",
+            interrupted.len().to_string().bold()
+        );
+
+        for operation in &interrupted {
+            let entry = &operation.entry;
+            println!();
+            println!(
+                "- {} {} ({} {})",
+                entry.operation.yellow().bold(),
+                entry.subject,
+                if operation.failed {
+                    "failed after starting"
+                } else {
+                    "started"
+                },
+                entry.timestamp.format("%Y-%m-%d %H:%M:%S UTC"),
+            );
+            println!("  {}", recommendation(operation));
+        }
+    }
+
+    if !incomplete.is_empty() {
+        if !interrupted.is_empty() {
+            println!();
+        }
+
+        println!(
+            "Found {} incomplete toolchain(s):",
+            incomplete.len().to_string().bold()
+        );
+
+        for (product, version, details) in &incomplete {
+            let product_flag = if *product == Product::default() {
+                String::new()
+            } else {
+                format!("--product {product} ")
+            };
+
+            println!();
+            println!("- {} {version}", product.display_name.yellow().bold());
+            for detail in details {
+                println!("  • {detail}");
+            }
+            println!("  Reinstall it with `install {product_flag}--force {version}`.");
+        }
+    }
+
+    if !ambiguous.is_empty() {
+        if !interrupted.is_empty() || !incomplete.is_empty() || !invalid_active.is_empty() {
+            println!();
+        }
+
+        println!(
+            "Found {} group(s) of toolchain directories with similar names:",
+            ambiguous.len().to_string().bold()
+        );
+
+        for paths in &ambiguous {
+            println!();
+            for path in paths {
+                // Debug formatting makes surrounding whitespace visible.
+                println!("- {}", format!("{path:?}").yellow().bold());
+            }
+            println!(
+                "  Names that only differ by case or whitespace can be mistaken for each other. Remove or rename all but one."
+            );
+        }
+    }
+
+    if !mismatched.is_empty() {
+        if !interrupted.is_empty()
+            || !incomplete.is_empty()
+            || !invalid_active.is_empty()
+            || !ambiguous.is_empty()
+        {
+            println!();
+        }
+
+        println!(
+            "Found {} toolchain(s) containing a different major version than their release:",
+            mismatched.len().to_string().bold()
+        );
+
+        for (product, versions) in &mismatched {
+            let content = versions
+                .content
+                .as_ref()
+                .map_or_else(String::new, ToString::to_string);
+            println!();
+            println!(
+                "- {} {} contains {}",
+                product.display_name.yellow().bold(),
+                versions.release,
+                content.yellow().bold()
+            );
+            println!(
+                "  The release may have been packaged incorrectly. Check that `clang --version` reports the version you expect."
+            );
+        }
+    }
+
+    if !unusable.is_empty() {
+        if !interrupted.is_empty()
+            || !incomplete.is_empty()
+            || !invalid_active.is_empty()
+            || !ambiguous.is_empty()
+            || !mismatched.is_empty()
+        {
+            println!();
+        }
+
+        println!(
+            "Found {} toolchain directory(s) with unusable names:",
+            unusable.len().to_string().bold()
+        );
+
+        for install in &unusable {
+            println!();
+            println!("- {}", format!("{:?}", install.path).yellow().bold());
+            match install.reason {
+                UnusableReason::NonUtf8Name => println!(
+                    "  The name isn't valid UTF-8, so it's ignored. Rename it to the version it contains, or delete it."
+                ),
+                UnusableReason::ReservedName => println!(
+                    "  The name is reserved on Windows, so it's ignored. Rename it to the version it contains, or delete it."
+                ),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns every directory in the toolchains directory that's ignored because of its name.
+async fn unusable_toolchains() -> Result<Vec<UnusableInstall>, CliError> {
+    let mut unusable = vec![];
+
+    for &product in Product::ALL {
+        let client = client_builder().product(product).build().await?;
+        unusable.extend(client.unusable_installs().await?);
+    }
+
+    Ok(unusable)
+}
+
+/// Returns every group of installed toolchains whose directory names could be confused.
+async fn ambiguous_toolchains() -> Result<Vec<Vec<PathBuf>>, CliError> {
+    let mut ambiguous = vec![];
+
+    for &product in Product::ALL {
+        let client = client_builder().product(product).build().await?;
+        ambiguous.extend(client.ambiguous_installs().await?);
+    }
+
+    Ok(ambiguous)
+}
+
+/// Returns the recorded versions of every installed toolchain whose archive contained a
+/// different major version than its release.
+///
+/// Smaller differences (such as `20.1.0-rc1` in the `20.1.0` release) happen occasionally and
+/// aren't reported.
+async fn mismatched_toolchains() -> Result<Vec<(Product, RecordedVersions)>, CliError> {
+    let mut mismatched = vec![];
+
+    for &product in Product::ALL {
+        let client = client_builder().product(product).build().await?;
+        let mut versions = client.installed_versions().await?;
+        versions.sort();
+
+        for version in versions {
+            let toolchain = InstalledToolchain::new(client.install_path_for(&version));
+            if let Some(recorded) = toolchain.recorded_versions().await
+                && recorded.compare() == VersionMatch::MajorDiffers
+            {
+                mismatched.push((product, recorded));
+            }
+        }
+    }
+
+    Ok(mismatched)
+}
+
+/// Returns the errors of every product's active toolchain file that can't be used, or moves
+/// them to the trash if `fix` is set.
+async fn invalid_active_toolchain_files(fix: bool) -> Result<Vec<ToolchainError>, CliError> {
+    let mut invalid = vec![];
+
+    for &product in Product::ALL {
+        let client = client_builder().product(product).build().await?;
+        match client.check_active_toolchain_file().await {
+            Ok(()) => {}
+            Err(ToolchainError::InvalidActiveToolchainFile { path, .. }) if fix => {
+                client.reset_active_toolchain_file().await?;
+                msg!(
+                    "Fixed",
+                    "removed the invalid active toolchain file {}",
+                    path.display()
+                );
+            }
+            Err(error @ ToolchainError::InvalidActiveToolchainFile { .. }) => invalid.push(error),
+            Err(error) => return Err(error.into()),
+        }
+    }
+
+    Ok(invalid)
+}
+
+/// Moves the toolchains left behind by interrupted or failed installs and removals to the
+/// trash.
+async fn clean_up_interrupted(client: &ToolchainClient) -> Result<(), CliError> {
+    let removed = client
+        .clean_up_interrupted(&CancellationToken::new())
+        .await?;
+    for path in &removed {
+        msg!(
+            "Fixed",
+            "removed the toolchain left behind at {}",
+            path.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Restores the execute permission of the binaries in every installed toolchain.
+async fn fix_permissions() -> Result<(), CliError> {
+    for &product in Product::ALL {
+        let client = client_builder().product(product).build().await?;
+        let mut versions = client.installed_versions().await?;
+        versions.sort();
+
+        for version in versions {
+            let toolchain = InstalledToolchain::new(client.install_path_for(&version));
+            let report = toolchain.fix_permissions().await?;
+            if report.fixed.is_empty() {
+                continue;
+            }
+
+            msg!(
+                "Fixed",
+                "permissions of {} file(s) in {} {version}",
+                report.fixed.len(),
+                product.display_name
+            );
+            for path in &report.fixed {
+                let path = path.strip_prefix(&toolchain.path).unwrap_or(path);
+                println!("  • {}", path.display());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns every installed toolchain that is missing files, and what's missing.
+async fn incomplete_toolchains() -> Result<Vec<(Product, ToolchainVersion, Vec<String>)>, CliError>
+{
+    let mut incomplete = vec![];
+
+    for &product in Product::ALL {
+        let client = client_builder().product(product).build().await?;
+        let mut versions = client.installed_versions().await?;
+        versions.sort();
+
+        for version in versions {
+            let toolchain = InstalledToolchain::new(client.install_path_for(&version));
+            match toolchain.check_structure(product).await {
+                Ok(()) => {}
+                Err(ToolchainError::IncompleteExtraction { details, .. }) => {
+                    incomplete.push((product, version, details));
+                }
+                Err(error) => return Err(error.into()),
+            }
+        }
+    }
+
+    Ok(incomplete)
+}
+
+fn recommendation(operation: &InterruptedOperation) -> String {
+    // Entries written before products were introduced only contain the version.
+    let (product, version) = operation
+        .entry
+        .subject
+        .split_once('/')
+        .unwrap_or((Product::EMBEDDED.id, &operation.entry.subject));
+    let product_flag = if product == Product::default().id {
+        String::new()
+    } else {
+        format!("--product {product} ")
+    };
+
+    match (operation.entry.operation, &operation.artifact) {
+        (Operation::Download, Some(path)) => format!(
+            "A partial download was left at {}. Re-run the install to resume it, or run `purge-cache` to delete it.",
+            path.display()
+        ),
+        (Operation::Extract, Some(path)) => format!(
+            "The toolchain at {} may be incomplete. Reinstall it with `install {product_flag}--force {version}`, or remove it with `doctor --fix`.",
+            path.display()
+        ),
+        (Operation::Remove, Some(path)) => format!(
+            "The toolchain at {} was partially removed. Finish removing it with `remove {product_flag}{version}` or `doctor --fix`.",
+            path.display()
+        ),
+        (Operation::Activate, _) if version.is_empty() => {
+            "The active toolchain may not have been unset. Check it with `list`.".to_string()
+        }
+        (Operation::Activate, _) => format!(
+            "The active toolchain may not have been updated. Activate it with `use {product_flag}{version}`."
+        ),
+        (Operation::Migrate, _) => {
+            "Updating the toolchains directory's layout was interrupted. It will be finished the next time arm-toolchain runs.".to_string()
+        }
+        (_, None) => "No files were left behind.".to_string(),
+    }
+}
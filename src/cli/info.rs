@@ -0,0 +1,168 @@
+use std::path::PathBuf;
+
+use humansize::DECIMAL;
+use serde::Serialize;
+
+use crate::{
+    cli::{
+        CliError, Colorize, HostArgs, Lookup, OutputFormat, VersionSpec, client_builder,
+        installable_spec,
+    },
+    toolchain::{
+        AssetDownloadInfo, DownloadVia, Product, ReleaseNotes, ToolchainVersion, VersionMatch,
+    },
+};
+
+/// Configuration for [`info`].
+#[derive(Debug, clap::Parser)]
+pub struct InfoArgs {
+    /// Version of the toolchain to show, "latest", or an alias from the config file.
+    #[clap(value_parser = installable_spec)]
+    pub version: Option<VersionSpec>,
+    /// Which toolchain to show ("embedded" or "linux").
+    #[clap(long, default_value = "embedded")]
+    pub product: Product,
+    /// Print the URLs and expected checksum of the download, for use with another downloader.
+    #[clap(long)]
+    pub urls: bool,
+    /// Print the release notes saved when the toolchain was installed, without network access.
+    #[clap(long, conflicts_with = "urls")]
+    pub notes: bool,
+    /// Which endpoint the printed URLs should use.
+    #[clap(long, default_value = "auto")]
+    pub download_via: DownloadVia,
+    /// Let "latest" resolve to a pre-release, such as a release candidate.
+    #[clap(long)]
+    pub pre: bool,
+    #[clap(flatten)]
+    pub host: HostArgs,
+    /// How the result should be reported.
+    #[clap(long, default_value = "human")]
+    pub output: OutputFormat,
+}
+
+/// The result of [`info`].
+#[derive(Debug, Serialize)]
+pub struct InfoReport {
+    /// The version of the release.
+    pub version: ToolchainVersion,
+    /// The name of the release's GitHub tag.
+    pub tag_name: String,
+    /// The name of the asset for the selected host.
+    pub asset_name: String,
+    /// Where the asset is downloaded from, if `--urls` was passed.
+    pub download: Option<AssetDownloadInfo>,
+    /// Where the toolchain is installed, if it is.
+    pub installed_path: Option<PathBuf>,
+    /// The version in the name of the installed archive's top-level directory, if it was
+    /// recorded. This occasionally differs from the release version.
+    pub content_version: Option<ToolchainVersion>,
+}
+
+/// Print the release notes saved in an installed toolchain.
+///
+/// Nothing is fetched from GitHub unless the version is `latest`, which has to be resolved.
+pub async fn release_notes(args: InfoArgs) -> Result<ReleaseNotes, CliError> {
+    let client = client_builder()
+        .product(args.product)
+        .include_prereleases(args.pre)
+        .build()
+        .await?;
+
+    let version = match args.version {
+        Some(spec) => spec.resolve_one(&client, Lookup::Installed).await?.version,
+        None => client
+            .active_toolchain()
+            .ok_or(CliError::NoToolchainEnabled)?,
+    };
+    let toolchain = client.toolchain(&version).await?;
+
+    let Some(notes) = toolchain.release_notes().await? else {
+        return Err(CliError::ReleaseNotesMissing { version });
+    };
+
+    args.output.message(notes.markdown.trim_end());
+    Ok(notes)
+}
+
+/// Print information about a release, without installing it.
+pub async fn info(args: InfoArgs) -> Result<InfoReport, CliError> {
+    let client = client_builder()
+        .download_via(args.download_via)
+        .product(args.product)
+        .include_prereleases(args.pre)
+        .build()
+        .await?;
+
+    let requested = args
+        .version
+        .unwrap_or(VersionSpec::Latest)
+        .resolve_one(&client, Lookup::Releases)
+        .await?;
+    let release = match requested.release {
+        Some(release) => release,
+        None => client.get_release(&requested.version).await?,
+    };
+    let asset = args.host.select_asset(&client, &release)?;
+
+    let download = if args.urls {
+        Some(client.asset_download_info(&release, asset, true).await?)
+    } else {
+        None
+    };
+
+    args.output.message(format_args!(
+        "{} {}",
+        release.product().display_name,
+        release.version().to_string().bold()
+    ));
+    args.output
+        .message(format_args!("Tag: {}", release.tag_name()));
+    args.output.message(format_args!(
+        "Asset: {} ({})",
+        asset.name,
+        humansize::format_size(asset.size as u64, DECIMAL)
+    ));
+
+    if let Some(download) = &download {
+        args.output.message(format_args!("URL: {}", download.url));
+        args.output
+            .message(format_args!("Size: {} bytes", download.size));
+        args.output
+            .message(format_args!("Checksum URL: {}", download.checksum_url));
+        if let Some(sha256) = &download.expected_sha256 {
+            args.output.message(format_args!("SHA256: {sha256}"));
+        }
+    }
+
+    let installed = client.toolchain(release.version()).await.ok();
+    let recorded = match &installed {
+        Some(toolchain) => toolchain.recorded_versions().await,
+        None => None,
+    };
+
+    if let Some(toolchain) = &installed {
+        args.output
+            .message(format_args!("Installed at: {}", toolchain.path.display()));
+    }
+    if let Some(versions) = &recorded
+        && let Some(content) = &versions.content
+    {
+        let note = match versions.compare() {
+            VersionMatch::Same => "",
+            VersionMatch::Differs => " (differs from the release version)",
+            VersionMatch::MajorDiffers => " (different major version than the release)",
+        };
+        args.output
+            .message(format_args!("Contents version: {content}{note}"));
+    }
+
+    Ok(InfoReport {
+        version: release.version().clone(),
+        tag_name: release.tag_name().to_string(),
+        asset_name: asset.name.clone(),
+        download,
+        installed_path: installed.map(|toolchain| toolchain.path),
+        content_version: recorded.and_then(|versions| versions.content),
+    })
+}
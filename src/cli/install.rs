@@ -1,106 +1,777 @@
-use std::{sync::Arc, time::Duration};
+use std::path::{Path, PathBuf};
 
-use indicatif::{MultiProgress, ProgressBar};
-use inquire::Confirm;
-use owo_colors::OwoColorize;
-use tokio::task::spawn_blocking;
-use tokio_util::sync::CancellationToken;
+use chrono::Utc;
+use clap::ValueEnum as _;
+use humansize::DECIMAL;
+
+use octocrab::models::repos::Asset;
+use reqwest::Url;
+use serde::Serialize;
+use tokio_util::{future::FutureExt as _, sync::CancellationToken};
+use tracing::warn;
 
 use crate::{
     cli::{
-        CliError, PROGRESS_STYLE_DL, PROGRESS_STYLE_EXTRACT, PROGRESS_STYLE_EXTRACT_SPINNER,
-        PROGRESS_STYLE_VERIFY, ctrl_c_cancel, msg,
+        CliError, Colorize, Config, Lookup, OutputFormat, ProgressBars, VersionSpec,
+        activatable_spec, client_builder, confirm, ctrl_c_cancel, installable_spec, msg,
+        set_partial_success,
     },
     toolchain::{
-        HostArch, HostOS, InstallState, ToolchainClient, ToolchainError, ToolchainRelease,
-        ToolchainVersion,
+        ActivationPolicy, ArchPreference, ChecksumFallback, DownloadVia, HostArch, HostOS,
+        InstallOutcome, InstallPlan, InstalledToolchain, PermissionPolicy, Product, ReleaseLock,
+        ToolchainClient, ToolchainError, ToolchainRelease, ToolchainVersion,
     },
 };
 
 /// Configuration for [`install`].
 #[derive(Debug, clap::Parser)]
 pub struct InstallArgs {
-    /// Version of the toolchain to install
-    pub version: Option<ToolchainVersion>,
+    /// Version of the toolchain to install, "latest", or an alias from the config file.
+    #[clap(value_parser = installable_spec)]
+    pub version: Option<VersionSpec>,
+    /// Which toolchain to install ("embedded" or "linux").
+    #[clap(long, default_value = "embedded")]
+    pub product: Product,
     /// Skip install if toolchain is up-to-date.
     #[clap(long, short)]
     pub force: bool,
+    /// Don't ask for confirmation before installing.
+    #[clap(long, short)]
+    pub yes: bool,
+    /// Where to download the toolchain from.
+    ///
+    /// Use "api" if your network blocks GitHub's browser download URLs but allows
+    /// access to the GitHub API.
+    #[clap(long, default_value = "auto")]
+    pub download_via: DownloadVia,
+    /// Don't activate the toolchain, even if no toolchain is active.
+    #[clap(long)]
+    pub no_activate: bool,
+    /// Make the toolchain usable by other members of its directory's group.
+    ///
+    /// Overrides the `permissions` option in the config file.
+    #[clap(long)]
+    pub shared: bool,
+    #[clap(flatten)]
+    pub host: HostArgs,
+    /// If the extracted toolchain turns out to be incomplete, keep it for inspection instead
+    /// of deleting it.
+    #[clap(long)]
+    pub keep_failed: bool,
+    /// Record the SHA-256 digest of every installed file, so that the toolchain can later be
+    /// checked with `verify --installed`.
+    ///
+    /// This makes the install take noticeably longer.
+    #[clap(long)]
+    pub record_digests: bool,
+    /// Extract the toolchain to this directory instead of the toolchains directory.
+    ///
+    /// The toolchain won't be activated or managed by this tool. The directory must be
+    /// empty unless `--force` is also passed, in which case its contents will be replaced.
+    #[clap(long)]
+    pub prefix: Option<PathBuf>,
+    /// Verify the download against the checksums listed in this file instead of the one
+    /// published with the release.
+    ///
+    /// The file can be in the format written by `sha256sum` or `shasum --tag`. Overrides the
+    /// `checksum_file` option in the config file.
+    #[clap(long, value_name = "PATH")]
+    pub checksum_file: Option<PathBuf>,
+    /// What to do if the asset isn't listed in the checksum file.
+    #[clap(long, default_value = "none")]
+    pub checksum_fallback: ChecksumFallback,
+    /// Show what would be downloaded and installed without doing it.
+    ///
+    /// Release metadata is still fetched from GitHub, but nothing is downloaded or written.
+    #[clap(long)]
+    pub dry_run: bool,
+    /// With `--force`, overwrite files that were changed since the toolchain was installed
+    /// without asking for confirmation.
+    #[clap(long, requires = "force")]
+    pub discard_local_changes: bool,
+    /// With `--force`, copy files that were changed since the toolchain was installed into a
+    /// backup directory before overwriting them.
+    #[clap(long, requires = "force")]
+    pub backup_local_changes: bool,
+    /// Install the release recorded in this lock file, which was created with the `lock`
+    /// subcommand.
+    ///
+    /// The GitHub API isn't used, and the download is verified against the checksum
+    /// recorded in the lock, so the install fails if the release changed since it was locked.
+    #[clap(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["version", "product", "checksum_file", "checksum_fallback"]
+    )]
+    pub locked: Option<PathBuf>,
+    /// Download the toolchain archive from this URL instead of a release, and install it
+    /// under the name given with `--name`.
+    ///
+    /// The URL must end with the archive's file name. The download is only verified if its
+    /// checksum is given with `--sha256`.
+    #[clap(
+        long,
+        requires = "name",
+        conflicts_with_all = [
+            "version", "locked", "prefix", "dry_run", "checksum_file", "checksum_fallback",
+            "download_via", "host_os", "host_arch", "prefer_universal", "prefer_native",
+        ]
+    )]
+    pub url: Option<Url>,
+    /// The version to install the toolchain downloaded from `--url` as, such as
+    /// "20.1.0-patched".
+    ///
+    /// It's what other commands, such as `use` and `run`, refer to the toolchain by.
+    #[clap(long, requires = "url", value_parser = url_install_name)]
+    pub name: Option<ToolchainVersion>,
+    /// The SHA-256 checksum of the archive downloaded from `--url`, in hex format.
+    #[clap(long, requires = "url", value_parser = sha256_hex)]
+    pub sha256: Option<String>,
+    /// Ask GitHub whether the releases changed, instead of using release information that
+    /// was fetched in the last 15 minutes.
+    #[clap(long)]
+    pub refresh: bool,
+    /// Let "latest" resolve to a pre-release, such as a release candidate.
+    #[clap(long)]
+    pub pre: bool,
+    /// How the result should be reported.
+    #[clap(long, default_value = "human")]
+    pub output: OutputFormat,
+}
+
+/// The result of [`install`].
+#[derive(Debug, Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum InstallReport {
+    /// The toolchain was downloaded and installed.
+    Installed(InstallOutcome),
+    /// The toolchain was already installed, so nothing was done.
+    AlreadyInstalled {
+        version: ToolchainVersion,
+        path: PathBuf,
+        active: bool,
+    },
+    /// Nothing was done because `--dry-run` was passed.
+    DryRun(InstallPlan),
+}
+
+/// Options for choosing which platform's toolchain should be downloaded.
+#[derive(Debug, Default, clap::Args)]
+pub struct HostArgs {
+    /// Download the toolchain for this operating system instead of the current one.
+    #[clap(long)]
+    pub host_os: Option<HostOS>,
+    /// Download the toolchain for this architecture instead of the current one.
+    #[clap(long)]
+    pub host_arch: Option<HostArch>,
+    /// On hosts that can run both, download the universal build instead of the native one.
+    ///
+    /// Universal builds are about twice as large. Overrides the `arch_preference` option in
+    /// the config file.
+    #[clap(long, conflicts_with_all = ["host_arch", "prefer_native"])]
+    pub prefer_universal: bool,
+    /// On hosts that can run both, download the native build instead of the universal one.
+    ///
+    /// Overrides the `arch_preference` option in the config file.
+    #[clap(long, conflicts_with = "host_arch")]
+    pub prefer_native: bool,
+}
+
+impl HostArgs {
+    /// Finds the release asset for the selected host, defaulting to the current one, in one
+    /// of the formats that the client can extract.
+    pub fn select_asset<'a>(
+        &self,
+        client: &ToolchainClient,
+        release: &'a ToolchainRelease,
+    ) -> Result<&'a Asset, CliError> {
+        let (os, arches) = self.host()?;
+        Ok(release.asset_for_formats(os, &arches, client.archive_formats())?)
+    }
+
+    /// Returns the selected host's OS and the architectures it can run, in order of
+    /// preference, defaulting to the current host.
+    pub fn host(&self) -> Result<(HostOS, Vec<HostArch>), CliError> {
+        let os = match self.host_os {
+            Some(os) => os,
+            None => HostOS::try_current()?,
+        };
+        let arches = match self.host_arch {
+            Some(arch) => vec![arch],
+            None => {
+                let mut arches = HostArch::try_current()?.to_vec();
+                self.arch_preference()
+                    .unwrap_or_default()
+                    .apply(&mut arches);
+                arches
+            }
+        };
+
+        Ok((os, arches))
+    }
+
+    /// Returns options for the current host that prefer the given kind of build.
+    pub fn preferring(preference: ArchPreference) -> Self {
+        let mut host = Self::default();
+        host.set_default_preference(Some(preference));
+        host
+    }
+
+    /// Prefers the given kind of build, unless a preference was already passed on the
+    /// command line.
+    pub fn set_default_preference(&mut self, preference: Option<ArchPreference>) {
+        if self.arch_preference().is_none() {
+            self.prefer_universal = preference == Some(ArchPreference::Universal);
+            self.prefer_native = preference == Some(ArchPreference::Native);
+        }
+    }
+
+    /// Returns which kind of build was preferred, if any.
+    pub fn arch_preference(&self) -> Option<ArchPreference> {
+        if self.prefer_universal {
+            Some(ArchPreference::Universal)
+        } else if self.prefer_native {
+            Some(ArchPreference::Native)
+        } else {
+            None
+        }
+    }
 }
 
 /// Remove a toolchain to the system.
-pub async fn install(args: InstallArgs) -> Result<(), CliError> {
-    let client = ToolchainClient::using_data_dir().await?;
+pub async fn install(mut args: InstallArgs) -> Result<InstallReport, CliError> {
+    let config = Config::load().await?;
+    args.host.set_default_preference(config.arch_preference);
+    let permission_policy = if args.shared {
+        PermissionPolicy::SharedRead
+    } else {
+        config.permissions.unwrap_or_default()
+    };
+
+    let lock = match &args.locked {
+        Some(path) => Some(ReleaseLock::read(path).await?),
+        None => None,
+    };
 
-    // If "latest" specified we have to figure out what that actually means first
-    let toolchain_release;
-    let toolchain_version;
-    let install_latest;
+    let client = client_builder()
+        .download_via(args.download_via)
+        .permission_policy(permission_policy)
+        .product(lock.as_ref().map_or(args.product, |lock| lock.product))
+        .refresh_releases(args.refresh)
+        .write_release_cache(!args.dry_run)
+        .include_prereleases(args.pre)
+        .keep_failed(args.keep_failed)
+        .record_file_digests(args.record_digests)
+        .checksum_file(args.checksum_file.clone().or(config.checksum_file))
+        .checksum_fallback(args.checksum_fallback)
+        .release_lock(lock.clone())
+        .activation_policy(if args.no_activate {
+            ActivationPolicy::Never
+        } else {
+            ActivationPolicy::IfNoneActive
+        })
+        .build()
+        .await?;
 
-    if let Some(version) = args.version
-        && version.name != "latest"
+    let spec = match &lock {
+        Some(lock) => VersionSpec::Exact(lock.version()),
+        None => args.version.clone().unwrap_or(VersionSpec::Latest),
+    };
+    let install_latest = spec == VersionSpec::Latest;
+
+    // Ctrl-C cancels every network request from here on, not just the download. The
+    // listener is released whenever this returns.
+    let token = ctrl_c_cancel();
+    let _release_listener = token.clone().drop_guard();
+
+    if let (Some(url), Some(version)) = (args.url.clone(), args.name.clone()) {
+        return install_url(&client, &args, url, version, token).await;
+    }
+
+    let requested = spec
+        .resolve_one(&client, Lookup::Releases)
+        .with_cancellation_token(&token)
+        .await
+        .ok_or(ToolchainError::Cancelled)??;
+
+    // A concrete version can be checked without asking GitHub about it, which avoids a
+    // round-trip (and works offline) when there's nothing to do.
+    if requested.release.is_none()
+        && args.prefix.is_none()
+        && !args.force
+        && let Some(report) = already_installed(&client, &requested.version, false, args.output)
     {
-        install_latest = false;
-        toolchain_version = version;
-        toolchain_release = client.get_release(&toolchain_version).await?;
+        return Ok(report);
+    }
+
+    let toolchain_release = match (&lock, requested.release) {
+        (Some(lock), _) => lock.release(),
+        (None, Some(release)) => release,
+        (None, None) => {
+            client
+                .get_release_with_cancellation(&requested.version, &token)
+                .await?
+        }
+    };
+    let toolchain_version = toolchain_release.version().to_owned();
+
+    if args.dry_run {
+        if !args.force
+            && args.prefix.is_none()
+            && let Some(report) =
+                already_installed(&client, &toolchain_version, install_latest, args.output)
+        {
+            return Ok(report);
+        }
+
+        let asset = args.host.select_asset(&client, &toolchain_release)?;
+        let plan = match &args.prefix {
+            Some(prefix) => {
+                let plan = client
+                    .plan_install_to(&toolchain_release, asset, prefix)
+                    .await?;
+                if plan.replaces_existing && !args.force {
+                    return Err(ToolchainError::DestinationNotEmpty {
+                        path: plan.destination,
+                    })?;
+                }
+                plan
+            }
+            None => client.plan_install(&toolchain_release, asset).await?,
+        };
+
+        report_plan(&plan, args.output);
+        return Ok(InstallReport::DryRun(plan));
+    }
+
+    let change_handling = LocalChangeHandling {
+        discard: args.discard_local_changes,
+        backup: args.backup_local_changes,
+    };
+    // With `--force`, the existing toolchain is only replaced once the new one has been
+    // confirmed and downloaded.
+    if let Some(prefix) = &args.prefix {
+        if args.force && prefix.exists() {
+            check_local_changes(prefix, &toolchain_version, &change_handling).await?;
+        }
+    } else if !args.force {
+        if let Some(report) =
+            already_installed(&client, &toolchain_version, install_latest, args.output)
+        {
+            return Ok(report);
+        }
     } else {
-        install_latest = true;
-        toolchain_release = client.latest_release().await?;
-        toolchain_version = toolchain_release.version().to_owned();
+        let path = client.install_path_for(&toolchain_version);
+        if path.exists() {
+            check_local_changes(&path, &toolchain_version, &change_handling).await?;
+        }
+    }
+
+    if !args.yes {
+        let plan = plan_for_summary(
+            &client,
+            &toolchain_release,
+            &args.host,
+            args.prefix.as_deref(),
+        )
+        .with_cancellation_token(&token)
+        .await
+        .ok_or(ToolchainError::Cancelled)?;
+        confirm_install(&toolchain_version, install_latest, plan.as_ref()).await?;
     }
 
+    let prefix = args.prefix.as_deref().map(|path| InstallPrefix {
+        path,
+        replace: args.force,
+    });
+    let result = install_with_progress_bar(
+        &client,
+        &toolchain_release,
+        &args.host,
+        prefix,
+        None,
+        token.clone(),
+    )
+    .await;
+    let resume = || {
+        resume_command(
+            &toolchain_version,
+            client.product(),
+            &args.host,
+            args.prefix.as_deref(),
+            args.locked.as_deref(),
+            args.force,
+        )
+    };
+    let outcome = with_resume_command(result, resume)?;
+
+    if outcome.activated {
+        msg!("Activated", "{toolchain_version}");
+    }
+    if args.prefix.is_none() {
+        record_install_reason(&client, &toolchain_version, "cli: install").await;
+    }
+
+    Ok(InstallReport::Installed(outcome))
+}
+
+/// Installs the toolchain downloaded from `--url` under the name given with `--name`.
+async fn install_url(
+    client: &ToolchainClient,
+    args: &InstallArgs,
+    url: Url,
+    version: ToolchainVersion,
+    token: CancellationToken,
+) -> Result<InstallReport, CliError> {
     if !args.force {
-        let already_installed = client.install_path_for(&toolchain_version);
-        if already_installed.exists() {
-            println!(
-                "Toolchain already installed: {} at {}",
-                toolchain_version.to_string().bold(),
-                already_installed.display().green()
-            );
+        if let Some(report) = already_installed(client, &version, false, args.output) {
+            return Ok(report);
+        }
+    } else {
+        let path = client.install_path_for(&version);
+        if path.exists() {
+            let change_handling = LocalChangeHandling {
+                discard: args.discard_local_changes,
+                backup: args.backup_local_changes,
+            };
+            check_local_changes(&path, &version, &change_handling).await?;
+        }
+    }
 
-            if client.active_toolchain().as_ref() == Some(&toolchain_version) {
-                println!(
-                    "(Enable it with the `use {}` subcommand)",
-                    if install_latest {
-                        "latest".to_string()
-                    } else {
-                        toolchain_version.to_string()
-                    }
-                );
-            }
+    if !args.yes {
+        confirm_install(&version, false, None).await?;
+    }
+
+    msg!("Downloading", "{url}");
+    let result = client
+        .install_from_url(
+            url.clone(),
+            version.clone(),
+            args.sha256.clone(),
+            ProgressBars::default().install_handler(),
+            token,
+        )
+        .await
+        .map_err(CliError::from);
+    let outcome = with_resume_command(result, || {
+        url_resume_command(
+            &url,
+            &version,
+            client.product(),
+            args.sha256.as_deref(),
+            args.force,
+        )
+    })?;
+    report_outcome(client, &outcome);
+
+    if outcome.activated {
+        msg!("Activated", "{version}");
+    }
+    record_install_reason(client, &version, "cli: install").await;
+
+    Ok(InstallReport::Installed(outcome))
+}
 
-            return Ok(());
+/// Adds the command that resumes the installation to errors that leave a partial download
+/// behind.
+fn with_resume_command(
+    result: Result<InstallOutcome, CliError>,
+    resume: impl FnOnce() -> String,
+) -> Result<InstallOutcome, CliError> {
+    match result {
+        Err(CliError::Toolchain(ToolchainError::InstallCancelled(state))) => {
+            Err(CliError::InstallCancelled {
+                state,
+                resume_command: resume(),
+            })
         }
+        // The partial download is kept, so installing again picks up where it stalled.
+        Err(CliError::Toolchain(ToolchainError::DownloadStalled {
+            timeout,
+            bytes_written,
+        })) => Err(CliError::DownloadStalled {
+            timeout,
+            bytes_written,
+            resume_command: resume(),
+        }),
+        result => result,
+    }
+}
+
+/// Returns the `install` command that resumes a cancelled installation, with the options that
+/// chose what was being installed and where.
+fn resume_command(
+    version: &ToolchainVersion,
+    product: Product,
+    host: &HostArgs,
+    prefix: Option<&Path>,
+    locked: Option<&Path>,
+    force: bool,
+) -> String {
+    let mut command = match locked {
+        Some(path) => format!("arm-toolchain install --locked {}", quote_path(path)),
+        None => format!("arm-toolchain install {version}"),
+    };
+    if product != Product::default() {
+        command += &format!(" --product {}", product.id);
+    }
+    for (flag, value) in [
+        (
+            "--host-os",
+            host.host_os.and_then(|os| os.to_possible_value()),
+        ),
+        (
+            "--host-arch",
+            host.host_arch.and_then(|arch| arch.to_possible_value()),
+        ),
+    ] {
+        if let Some(value) = value {
+            command += &format!(" {flag} {}", value.get_name());
+        }
+    }
+    if host.prefer_universal {
+        command += " --prefer-universal";
     }
+    if host.prefer_native {
+        command += " --prefer-native";
+    }
+    if let Some(prefix) = prefix {
+        command += &format!(" --prefix {}", quote_path(prefix));
+    }
+    if force {
+        command += " --force";
+    }
+    command
+}
 
-    confirm_install(&toolchain_version, install_latest).await?;
+/// Returns the `install` command that resumes a cancelled installation from a URL.
+fn url_resume_command(
+    url: &Url,
+    version: &ToolchainVersion,
+    product: Product,
+    sha256: Option<&str>,
+    force: bool,
+) -> String {
+    let mut command = format!(
+        "arm-toolchain install --url {} --name {}",
+        quote(url.as_str()),
+        quote(&version.name)
+    );
+    if product != Product::default() {
+        command += &format!(" --product {}", product.id);
+    }
+    if let Some(sha256) = sha256 {
+        command += &format!(" --sha256 {sha256}");
+    }
+    if force {
+        command += " --force";
+    }
+    command
+}
 
-    let old_version = client.active_toolchain();
+/// Quotes a path for a shell if it contains anything but common path characters.
+fn quote_path(path: &Path) -> String {
+    quote(&path.display().to_string())
+}
 
-    let token = ctrl_c_cancel();
-    install_with_progress_bar(&client, &toolchain_release, token.clone()).await?;
+/// Quotes a value for a shell if it contains anything but common path characters.
+fn quote(value: &str) -> String {
+    if value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "/\\._-:~+".contains(c))
+    {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
 
-    if old_version.is_none() {
-        msg!("Activated", "{toolchain_version}");
+/// Parses the name given with `--name`, which has to be read as a version by every command
+/// that takes one.
+fn url_install_name(value: &str) -> Result<ToolchainVersion, String> {
+    match activatable_spec(value)? {
+        VersionSpec::Exact(version) => Ok(version),
+        spec => Err(format!(
+            "other commands would read {spec} as an alias or a pseudo-version, so use a name that starts with a digit, such as \"20.1.0-patched\""
+        )),
     }
+}
 
-    token.cancel();
-    Ok(())
+/// Parses a SHA-256 checksum in hex format.
+fn sha256_hex(value: &str) -> Result<String, String> {
+    if value.len() == 64 && value.bytes().all(|b| b.is_ascii_hexdigit()) {
+        Ok(value.to_ascii_lowercase())
+    } else {
+        Err("expected 64 hexadecimal digits".to_string())
+    }
+}
+
+/// Prints what an installation would do.
+pub fn report_plan(plan: &InstallPlan, output: OutputFormat) {
+    output.message(format_args!(
+        "Would download {} ({})",
+        plan.asset_name.bold(),
+        plan_summary(plan)
+    ));
+    output.message(format_args!(
+        "Would install {} to {}{}",
+        plan.version.to_string().bold(),
+        plan.destination.display().green(),
+        if plan.replaces_existing {
+            " (replacing its current contents)"
+        } else {
+            ""
+        }
+    ));
+    if plan.activates {
+        output.message(format_args!(
+            "Would activate {} because no toolchain is active",
+            plan.version
+        ));
+    }
+    warn_space_shortfall(plan);
 }
 
-pub async fn confirm_install(version: &ToolchainVersion, latest: bool) -> Result<(), CliError> {
+/// Summarizes the sizes in an install plan, such as "682 MB download, ~2.1 GB installed,
+/// 14.3 GB free on /, 410 MB already in cache (resuming)".
+pub fn plan_summary(plan: &InstallPlan) -> String {
+    let size = |bytes| humansize::format_size(bytes, DECIMAL);
+    let mut parts = vec![];
+
+    if plan.download_size == 0 {
+        parts.push("unknown download size".to_string());
+    } else {
+        parts.push(format!("{} download", size(plan.download_size)));
+    }
+    if let Some(install_size) = plan.estimated_install_size {
+        parts.push(format!("~{} installed", size(install_size)));
+    }
+
+    if let Some(destination) = &plan.destination_space {
+        parts.push(format!(
+            "{} free on {}",
+            size(destination.available),
+            destination.mount_point.display()
+        ));
+    }
+    if let Some(cache) = &plan.cache_space
+        && plan
+            .destination_space
+            .as_ref()
+            .is_none_or(|destination| destination.mount_point != cache.mount_point)
+    {
+        parts.push(format!(
+            "{} free on {} for the download",
+            size(cache.available),
+            cache.mount_point.display()
+        ));
+    }
+
+    if plan.cached_bytes > 0 {
+        if plan.cached_bytes == plan.download_size {
+            parts.push("already in cache".to_string());
+        } else {
+            parts.push(format!(
+                "{} already in cache (resuming)",
+                size(plan.cached_bytes)
+            ));
+        }
+    }
+
+    parts.join(", ")
+}
+
+/// Warns if an install plan needs more space than is free.
+fn warn_space_shortfall(plan: &InstallPlan) {
+    let shortfall = plan.space_shortfall();
+    if shortfall > 0 {
+        eprintln!(
+            "{} the toolchain may not fit, about {} more free space is needed",
+            "warning:".yellow().bold(),
+            humansize::format_size(shortfall, DECIMAL)
+        );
+    }
+}
+
+/// Plans an install so that its sizes can be shown before asking for confirmation.
+///
+/// The summary is only informational, so `None` is returned if planning fails. The install
+/// itself reports the error.
+pub async fn plan_for_summary(
+    client: &ToolchainClient,
+    release: &ToolchainRelease,
+    host: &HostArgs,
+    prefix: Option<&Path>,
+) -> Option<InstallPlan> {
+    let asset = host.select_asset(client, release).ok()?;
+    let plan = match prefix {
+        Some(prefix) => client.plan_install_to(release, asset, prefix).await,
+        None => client.plan_install(release, asset).await,
+    };
+
+    plan.inspect_err(|error| warn!(%error, "Failed to plan the install"))
+        .ok()
+}
+
+/// Reports that the given version is already installed, if it is.
+fn already_installed(
+    client: &ToolchainClient,
+    version: &ToolchainVersion,
+    latest: bool,
+    output: OutputFormat,
+) -> Option<InstallReport> {
+    let path = client.install_path_for(version);
+    if !path.exists() {
+        return None;
+    }
+
+    output.message(format_args!(
+        "Toolchain already installed: {} at {}",
+        version.to_string().bold(),
+        path.display().green()
+    ));
+
+    let active = client.active_toolchain().as_ref() == Some(version);
+    if active {
+        output.message(format_args!(
+            "(Enable it with the `use {}` subcommand)",
+            if latest {
+                "latest".to_string()
+            } else {
+                version.to_string()
+            }
+        ));
+    }
+
+    Some(InstallReport::AlreadyInstalled {
+        version: version.clone(),
+        path,
+        active,
+    })
+}
+
+/// Asks whether to install a toolchain, after showing the sizes in its plan if there is one.
+pub async fn confirm_install(
+    version: &ToolchainVersion,
+    latest: bool,
+    plan: Option<&InstallPlan>,
+) -> Result<(), CliError> {
+    if let Some(plan) = plan {
+        eprintln!("{}", plan_summary(plan));
+        warn_space_shortfall(plan);
+    }
+
     let confirm_message = format!(
         "Download & install {}ARM toolchain {version}?",
         if latest { "latest " } else { "" },
     );
 
-    let confirmation = spawn_blocking(move || {
-        Confirm::new(&confirm_message)
-            .with_default(true)
-            .with_help_message("Required support libraries for building C/C++ code. No = cancel")
-            .prompt()
-    })
-    .await
-    .unwrap()?;
+    let confirmation = confirm(
+        confirm_message,
+        "Required support libraries for building C/C++ code. No = cancel",
+        "--yes",
+    )
+    .await?;
 
     if !confirmation {
         eprintln!("Cancelled.");
@@ -110,84 +781,321 @@ pub async fn confirm_install(version: &ToolchainVersion, latest: bool) -> Result
     Ok(())
 }
 
+/// What to do with local changes to a toolchain that `--force` replaces.
+struct LocalChangeHandling {
+    discard: bool,
+    backup: bool,
+}
+
+/// The most paths of each kind of change that are listed before overwriting a toolchain.
+const MAX_LISTED_CHANGES: usize = 10;
+
+/// Checks whether the files of the toolchain at `path`, which `--force` is about to replace,
+/// were changed since it was installed.
+///
+/// If they were, the changes are listed and either backed up (with `--backup-local-changes`),
+/// discarded (with `--discard-local-changes`), or discarded once the user confirms it.
+async fn check_local_changes(
+    path: &Path,
+    version: &ToolchainVersion,
+    handling: &LocalChangeHandling,
+) -> Result<(), CliError> {
+    let toolchain = InstalledToolchain::new(path.to_owned());
+    let Some(changes) = toolchain.local_changes().await? else {
+        // Toolchains installed by older versions don't have a file list to compare against.
+        eprintln!(
+            "{} {} will be replaced, including any changes made to its files since it was installed.",
+            "warning:".yellow().bold(),
+            path.display()
+        );
+        return Ok(());
+    };
+
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    eprintln!(
+        "{} {} file(s) in {} were changed since it was installed:",
+        "warning:".yellow().bold(),
+        changes.len(),
+        path.display()
+    );
+    for (kind, paths) in [
+        ("modified", &changes.modified),
+        ("added", &changes.added),
+        ("deleted", &changes.deleted),
+    ] {
+        for changed in paths.iter().take(MAX_LISTED_CHANGES) {
+            eprintln!("  {kind:>8}: {}", changed.display());
+        }
+        if paths.len() > MAX_LISTED_CHANGES {
+            eprintln!(
+                "  {kind:>8}: ...and {} more",
+                paths.len() - MAX_LISTED_CHANGES
+            );
+        }
+    }
+
+    if handling.backup {
+        let timestamp = Utc::now().format("%Y%m%dT%H%M%SZ");
+        let backup_path = crate::dirs()?
+            .data_local_dir()
+            .join("backups")
+            .join(format!("{}-{timestamp}", version.name));
+        toolchain
+            .backup_local_changes(&changes, &backup_path)
+            .await?;
+        msg!("Backed up", "changed files to {}", backup_path.display());
+    } else if !handling.discard {
+        let confirmed = confirm(
+            "Discard these changes and reinstall?".to_string(),
+            "Use --backup-local-changes to keep a copy of them. No = cancel",
+            "--discard-local-changes",
+        )
+        .await?;
+
+        if !confirmed {
+            eprintln!("Cancelled.");
+            return Err(ToolchainError::Cancelled)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// A directory to extract a toolchain to instead of the toolchains directory.
+#[derive(Debug, Clone, Copy)]
+pub struct InstallPrefix<'a> {
+    pub path: &'a Path,
+    /// Whether anything already in the directory is replaced, rather than refusing to install
+    /// over it.
+    pub replace: bool,
+}
+
+/// Installs a toolchain while displaying its progress.
+///
+/// If a prefix is given, the toolchain is extracted there using [`ToolchainClient::install_to`]
+/// (or [`ToolchainClient::install_to_replacing`]) instead of being installed to the toolchains
+/// directory.
+///
+/// The progress bars are added to `progress_bars` if it's given, which lets them be displayed
+/// alongside the caller's own progress bars and cleared once the installation is done.
+/// Otherwise, they're added to a new group of progress bars.
+///
+/// ```no_run
+/// # async fn example(
+/// #     client: arm_toolchain::toolchain::ToolchainClient,
+/// #     release: arm_toolchain::toolchain::ToolchainRelease,
+/// # ) -> Result<(), arm_toolchain::cli::CliError> {
+/// use arm_toolchain::cli::{HostArgs, ProgressBars, install_with_progress_bar};
+/// use indicatif::MultiProgress;
+/// use tokio_util::sync::CancellationToken;
+///
+/// // The application's existing progress bars.
+/// let multi = MultiProgress::new();
+///
+/// let progress_bars = ProgressBars::with_multi_progress(multi.clone());
+/// install_with_progress_bar(
+///     &client,
+///     &release,
+///     &HostArgs::default(),
+///     None,
+///     Some(&progress_bars),
+///     CancellationToken::new(),
+/// )
+/// .await?;
+///
+/// // Remove the installation's bars, leaving the application's bars in place.
+/// progress_bars.clear();
+/// # Ok(())
+/// # }
+/// ```
 pub async fn install_with_progress_bar(
     client: &ToolchainClient,
     release: &ToolchainRelease,
+    host: &HostArgs,
+    prefix: Option<InstallPrefix<'_>>,
+    progress_bars: Option<&ProgressBars>,
     cancel_token: CancellationToken,
-) -> Result<(), CliError> {
-    let asset = release.asset_for(HostOS::current(), HostArch::current())?;
+) -> Result<InstallOutcome, CliError> {
+    let asset = host.select_asset(client, release)?;
 
     msg!("Downloading", "{}", asset.name,);
 
-    let multi_bar = MultiProgress::new();
-    let download_bar = ProgressBar::no_length().with_style(PROGRESS_STYLE_DL.clone());
-    multi_bar.add(download_bar.clone());
-
-    let verify_bar = ProgressBar::no_length()
-        .with_style(PROGRESS_STYLE_VERIFY.clone())
-        .with_message("Verifying");
-    multi_bar.add(verify_bar.clone());
-
-    let extract_bar = ProgressBar::no_length()
-        .with_message("Extracting toolchain")
-        .with_style(PROGRESS_STYLE_EXTRACT_SPINNER.clone());
-    multi_bar.add(extract_bar.clone());
-
-    let progress_handler = Arc::new(move |update| match update {
-        InstallState::DownloadBegin {
-            asset_size,
-            bytes_read,
-        } => {
-            download_bar.reset();
-            download_bar.enable_steady_tick(Duration::from_millis(300));
-            download_bar.set_length(asset_size);
-            download_bar.set_position(bytes_read);
-            download_bar.reset_eta();
-        }
-        InstallState::Download { bytes_read } => {
-            download_bar.set_position(bytes_read);
-        }
-        InstallState::DownloadFinish => {
-            download_bar.disable_steady_tick();
-            download_bar.finish_with_message("Download complete");
-        }
-        InstallState::VerifyingBegin { asset_size } => {
-            verify_bar.reset();
-            verify_bar.set_length(asset_size);
-        }
-        InstallState::Verifying { bytes_read } => {
-            verify_bar.set_position(bytes_read);
-        }
-        InstallState::VerifyingFinish => {
-            verify_bar.finish_with_message("Verification complete");
-        }
-        InstallState::ExtractBegin => {
-            extract_bar.set_style(PROGRESS_STYLE_EXTRACT_SPINNER.clone());
-            extract_bar.enable_steady_tick(Duration::from_millis(300));
-        }
-        InstallState::ExtractCopy {
-            bytes_copied,
-            total_size,
-        } => {
-            if extract_bar.length().is_none() {
-                extract_bar.set_style(PROGRESS_STYLE_EXTRACT.clone());
-                extract_bar.reset();
-            }
+    let progress_handler = match progress_bars {
+        Some(progress_bars) => progress_bars.install_handler(),
+        None => ProgressBars::default().install_handler(),
+    };
 
-            extract_bar.set_length(total_size);
-            extract_bar.set_position(bytes_copied);
+    let outcome = match prefix {
+        Some(InstallPrefix {
+            path,
+            replace: false,
+        }) => {
+            client
+                .install_to(release, asset, path, progress_handler, cancel_token)
+                .await?
         }
-        InstallState::ExtractCleanUp => {}
-        InstallState::ExtractDone => {
-            extract_bar.finish_with_message("Extraction complete");
+        Some(InstallPrefix {
+            path,
+            replace: true,
+        }) => {
+            client
+                .install_to_replacing(release, asset, path, progress_handler, cancel_token)
+                .await?
         }
-    });
+        None => {
+            client
+                .download_and_install(release, asset, progress_handler, cancel_token)
+                .await?
+        }
+    };
 
-    let destination = client
-        .download_and_install(release, asset, progress_handler, cancel_token)
-        .await?;
+    report_outcome(client, &outcome);
 
-    msg!("Downloaded", "to {}", destination.display());
+    Ok(outcome)
+}
 
-    Ok(())
+/// Reports where a toolchain was installed, and how to activate it if activating it failed.
+fn report_outcome(client: &ToolchainClient, outcome: &InstallOutcome) {
+    msg!("Downloaded", "to {}", outcome.path.display());
+
+    if let Some(error) = &outcome.activation_error {
+        eprintln!(
+            "{} {} was installed, but could not be activated: {error}",
+            "warning:".yellow().bold(),
+            outcome.version
+        );
+        let product = match client.product() {
+            product if product == Product::default() => String::new(),
+            product => format!(" --product {}", product.id),
+        };
+        eprintln!(
+            "Activate it by running the `use {}{product}` subcommand.",
+            outcome.version
+        );
+        set_partial_success();
+    }
+}
+
+/// Records why a command needs a toolchain, so that `prune --unused` keeps it.
+///
+/// The toolchain is usable either way, so a failure is only logged instead of failing the
+/// command.
+pub(crate) async fn record_install_reason(
+    client: &ToolchainClient,
+    version: &ToolchainVersion,
+    reason: &str,
+) {
+    if let Err(error) = client.add_install_reason(version, reason).await {
+        warn!(%error, %version, reason, "Failed to record install reason");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::toolchain::VolumeSpace;
+
+    use super::*;
+
+    fn plan(download_size: u64, cached_bytes: u64) -> InstallPlan {
+        InstallPlan {
+            version: ToolchainVersion::named("21.1.0"),
+            asset_name: "ATfE-21.1.0-Linux-x86_64.tar.xz".to_string(),
+            download_size,
+            cached_bytes,
+            destination: PathBuf::from("/data/toolchains/21.1.0"),
+            replaces_existing: false,
+            activates: true,
+            estimated_install_size: (download_size > 0).then(|| download_size * 3),
+            destination_space: None,
+            cache_space: None,
+        }
+    }
+
+    fn space(mount_point: &str, available: u64) -> Option<VolumeSpace> {
+        Some(VolumeSpace {
+            mount_point: PathBuf::from(mount_point),
+            available,
+        })
+    }
+
+    #[test]
+    fn summary_of_a_resumed_download_on_one_volume() {
+        let plan = InstallPlan {
+            destination_space: space("/", 14_300_000_000),
+            cache_space: space("/", 14_300_000_000),
+            ..plan(682_000_000, 410_000_000)
+        };
+
+        assert_eq!(
+            plan_summary(&plan),
+            "682 MB download, ~2.05 GB installed, 14.30 GB free on /, 410 MB already in cache (resuming)"
+        );
+    }
+
+    #[test]
+    fn summary_shows_the_cache_volume_when_it_differs() {
+        let plan = InstallPlan {
+            destination_space: space("/data", 50_000_000_000),
+            cache_space: space("/", 1_000_000_000),
+            ..plan(682_000_000, 682_000_000)
+        };
+
+        assert_eq!(
+            plan_summary(&plan),
+            "682 MB download, ~2.05 GB installed, 50 GB free on /data, 1 GB free on / for the download, already in cache"
+        );
+    }
+
+    #[test]
+    fn prefer_flags_override_the_config_file() {
+        use clap::Parser;
+
+        for (flags, config, expected) in [
+            (&[][..], None, None),
+            (
+                &[],
+                Some(ArchPreference::Universal),
+                Some(ArchPreference::Universal),
+            ),
+            (
+                &["--prefer-universal"],
+                Some(ArchPreference::Native),
+                Some(ArchPreference::Universal),
+            ),
+            (
+                &["--prefer-native"],
+                Some(ArchPreference::Universal),
+                Some(ArchPreference::Native),
+            ),
+        ] {
+            let mut args =
+                InstallArgs::try_parse_from(["install", "21.1.0"].iter().chain(flags)).unwrap();
+            args.host.set_default_preference(config);
+
+            assert_eq!(
+                args.host.arch_preference(),
+                expected,
+                "{flags:?} {config:?}"
+            );
+        }
+
+        for flags in [
+            &["--prefer-universal", "--prefer-native"][..],
+            &["--prefer-universal", "--host-arch", "aarch64"],
+            &["--prefer-native", "--host-arch", "universal"],
+        ] {
+            assert!(
+                InstallArgs::try_parse_from(["install", "21.1.0"].iter().chain(flags)).is_err(),
+                "{flags:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn summary_without_sizes() {
+        assert_eq!(plan_summary(&plan(0, 0)), "unknown download size");
+    }
 }
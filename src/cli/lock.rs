@@ -0,0 +1,53 @@
+use std::path::PathBuf;
+
+use crate::{
+    cli::{CliError, Lookup, VersionSpec, client_builder, installable_spec, msg},
+    toolchain::Product,
+};
+
+/// Configuration for [`lock`].
+#[derive(Debug, clap::Parser)]
+pub struct LockArgs {
+    /// Version of the toolchain to lock, "latest", or an alias from the config file.
+    #[clap(long, value_parser = installable_spec)]
+    pub version: Option<VersionSpec>,
+    /// Which toolchain to lock ("embedded" or "linux").
+    #[clap(long, default_value = "embedded")]
+    pub product: Product,
+    /// Write the lock to this file instead of printing it.
+    #[clap(long = "out", short = 'o', value_name = "PATH")]
+    pub out: Option<PathBuf>,
+}
+
+/// Snapshot a release's metadata, so that it can be installed with `install --locked`
+/// without asking GitHub about it.
+pub async fn lock(args: LockArgs) -> Result<(), CliError> {
+    let client = client_builder().product(args.product).build().await?;
+
+    let requested = args
+        .version
+        .unwrap_or(VersionSpec::Latest)
+        .resolve_one(&client, Lookup::Releases)
+        .await?;
+    let release = match requested.release {
+        Some(release) => release,
+        None => client.get_release(&requested.version).await?,
+    };
+    let lock = client.lock_release(&release).await?;
+
+    match &args.out {
+        Some(path) => {
+            lock.write(path).await?;
+            msg!(
+                "Locked",
+                "{} ({} assets) to {}",
+                release.version(),
+                lock.assets.len(),
+                path.display()
+            );
+        }
+        None => print!("{}", lock.to_json()),
+    }
+
+    Ok(())
+}
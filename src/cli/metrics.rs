@@ -0,0 +1,238 @@
+use std::{fmt::Write as _, path::Path};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    cli::{CliError, Colorize, client_builder},
+    fs,
+    toolchain::{Product, ToolchainVersion, journal::CompletedInstall},
+};
+
+/// A snapshot of the state of the toolchains directory, which is written to the
+/// `metrics_textfile` in the Prometheus text exposition format after each command that
+/// changes it.
+///
+/// The file is meant to be collected by node_exporter's textfile collector.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricsSnapshot {
+    /// The products that have been used, and their installed and active toolchains.
+    pub products: Vec<ProductMetrics>,
+    /// The size of the download cache in bytes.
+    pub cache_bytes: u64,
+    /// The most recent installation, if one was recorded in the journal.
+    pub last_install: Option<CompletedInstall>,
+    /// The command that wrote the snapshot.
+    pub last_operation: OperationMetrics,
+}
+
+/// The part of a [`MetricsSnapshot`] about one product.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProductMetrics {
+    pub product: Product,
+    pub installed: usize,
+    pub active: Option<ToolchainVersion>,
+}
+
+/// The command described by a [`MetricsSnapshot`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct OperationMetrics {
+    /// The name of the command, such as `install`.
+    pub command: &'static str,
+    pub success: bool,
+    pub finished: DateTime<Utc>,
+}
+
+impl MetricsSnapshot {
+    /// Collects the metrics after a command finished.
+    pub async fn collect(command: &'static str, success: bool) -> Result<Self, CliError> {
+        let mut products = vec![];
+        let mut cache_bytes = 0;
+        let mut last_install = None;
+
+        for &product in Product::ALL {
+            let client = client_builder().product(product).build().await?;
+            let installed = client.installed_versions().await?.len();
+            let active = client.active_toolchain();
+
+            // Every product shares the same cache and journal.
+            if product == Product::default() {
+                cache_bytes = client.cache_size().await?;
+                last_install = client.journal().last_install().await?;
+            }
+
+            // Products other than the default are only included once they've been used, like
+            // in `list`.
+            if product == Product::default() || installed > 0 || active.is_some() {
+                products.push(ProductMetrics {
+                    product,
+                    installed,
+                    active,
+                });
+            }
+        }
+
+        Ok(Self {
+            products,
+            cache_bytes,
+            last_install,
+            last_operation: OperationMetrics {
+                command,
+                success,
+                finished: Utc::now(),
+            },
+        })
+    }
+
+    /// Formats the snapshot in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "installed_toolchains",
+            "Number of installed toolchains.",
+        );
+        for product in &self.products {
+            sample(
+                &mut out,
+                "installed_toolchains",
+                &[("product", product.product.id)],
+                product.installed,
+            );
+        }
+
+        header(
+            &mut out,
+            "active_toolchain_info",
+            "The active toolchain of each product, as a label. Absent if none is active.",
+        );
+        for product in &self.products {
+            if let Some(active) = &product.active {
+                sample(
+                    &mut out,
+                    "active_toolchain_info",
+                    &[("product", product.product.id), ("version", &active.name)],
+                    1,
+                );
+            }
+        }
+
+        header(
+            &mut out,
+            "cache_bytes",
+            "Size of the download cache in bytes.",
+        );
+        sample(&mut out, "cache_bytes", &[], self.cache_bytes);
+
+        if let Some(install) = &self.last_install {
+            header(
+                &mut out,
+                "last_install_timestamp_seconds",
+                "Unix time when the most recent installation finished.",
+            );
+            sample(
+                &mut out,
+                "last_install_timestamp_seconds",
+                &[],
+                install.finished.timestamp(),
+            );
+
+            header(
+                &mut out,
+                "last_install_duration_seconds",
+                "How long the most recent installation took.",
+            );
+            sample(
+                &mut out,
+                "last_install_duration_seconds",
+                &[],
+                install.duration.as_secs(),
+            );
+        }
+
+        let operation = &self.last_operation;
+        let labels = [("command", operation.command)];
+        header(
+            &mut out,
+            "last_operation_success",
+            "Whether the most recent command that changed the toolchains succeeded.",
+        );
+        sample(
+            &mut out,
+            "last_operation_success",
+            &labels,
+            u8::from(operation.success),
+        );
+
+        header(
+            &mut out,
+            "last_operation_timestamp_seconds",
+            "Unix time when the most recent command that changed the toolchains finished.",
+        );
+        sample(
+            &mut out,
+            "last_operation_timestamp_seconds",
+            &labels,
+            operation.finished.timestamp(),
+        );
+
+        out
+    }
+}
+
+/// Writes the `# HELP` and `# TYPE` lines of a gauge.
+fn header(out: &mut String, name: &str, help: &str) {
+    _ = writeln!(out, "# HELP arm_toolchain_{name} {help}");
+    _ = writeln!(out, "# TYPE arm_toolchain_{name} gauge");
+}
+
+/// Writes one sample of a metric.
+fn sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: impl std::fmt::Display) {
+    _ = write!(out, "arm_toolchain_{name}");
+
+    if !labels.is_empty() {
+        let labels = labels
+            .iter()
+            .map(|(key, value)| format!("{key}=\"{}\"", escape_label(value)))
+            .collect::<Vec<_>>();
+        _ = write!(out, "{{{}}}", labels.join(","));
+    }
+
+    _ = writeln!(out, " {value}");
+}
+
+/// Escapes a label value, as required by the exposition format.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', r#"\""#)
+        .replace('\n', r"\n")
+}
+
+/// Writes a metrics snapshot for a command that changed the toolchains.
+///
+/// Metrics are only for monitoring, so a failure is reported as a warning instead of failing
+/// the command.
+pub async fn write_metrics(path: &Path, command: &'static str, success: bool) {
+    let result = async {
+        let snapshot = MetricsSnapshot::collect(command, success).await?;
+
+        // node_exporter may read the file at any time, so it's replaced in one step.
+        let mut temp_name = path.file_name().unwrap_or_default().to_owned();
+        temp_name.push(".tmp");
+        let temp_path = path.with_file_name(temp_name);
+
+        fs::write(&temp_path, snapshot.render()).await?;
+        fs::rename(&temp_path, path).await?;
+        Ok::<_, CliError>(())
+    }
+    .await;
+
+    if let Err(error) = result {
+        eprintln!(
+            "{} could not write metrics to {}: {error}",
+            "warning:".yellow().bold(),
+            path.display()
+        );
+    }
+}
@@ -0,0 +1,264 @@
+//! The opt-in notice that `run` prints when a newer toolchain is available.
+//!
+//! The notice is enabled with `notify_updates = true` in the config file. GitHub is asked
+//! about the latest release at most once per [`CHECK_INTERVAL`], and the time of the last
+//! check is stored in the data directory along with the version it found, which the notice
+//! is based on in the meantime.
+
+use std::{
+    io::ErrorKind,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use tokio::task::JoinHandle;
+use tracing::debug;
+
+use crate::{
+    cli::Colorize,
+    fs,
+    toolchain::{Product, ToolchainClient, ToolchainVersion},
+};
+
+/// How often the latest release is checked for.
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+/// How long to wait for GitHub before giving up on a check.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The result of the last check for a newer release, stored in the data directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoticeState {
+    /// When the check finished, whether or not it found the latest release.
+    pub checked_at: SystemTime,
+    /// The latest release found by the check, or by an earlier one if it failed.
+    pub latest: Option<ToolchainVersion>,
+}
+
+impl NoticeState {
+    /// Returns whether enough time has passed since this check to check again.
+    ///
+    /// A check that seems to be from the future (because the clock was changed) is treated
+    /// as if it just happened.
+    pub fn is_due(&self, now: SystemTime) -> bool {
+        now.duration_since(self.checked_at)
+            .is_ok_and(|elapsed| elapsed >= CHECK_INTERVAL)
+    }
+
+    /// Parses the state file, which contains the time of the check in seconds since the Unix
+    /// epoch, followed by the latest version on the next line if one was found.
+    fn parse(text: &str) -> Option<Self> {
+        let mut lines = text.lines();
+        let seconds = lines.next()?.trim().parse().ok()?;
+        let latest = lines
+            .next()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(ToolchainVersion::named);
+
+        Some(Self {
+            checked_at: UNIX_EPOCH + Duration::from_secs(seconds),
+            latest,
+        })
+    }
+
+    fn to_text(&self) -> String {
+        let seconds = self
+            .checked_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        match &self.latest {
+            Some(latest) => format!("{seconds}\n{}\n", latest.name),
+            None => format!("{seconds}\n"),
+        }
+    }
+}
+
+/// Returns the path of a product's state file, or `None` if there is no home directory.
+fn state_path(product: Product) -> Option<PathBuf> {
+    let dirs = crate::dirs().ok()?;
+    Some(
+        dirs.data_local_dir()
+            .join(format!("update-notice-{}", product.id)),
+    )
+}
+
+/// Reads the state of the last check, returning `None` if there wasn't one or the state
+/// file is unreadable.
+async fn read_state(product: Product) -> Option<NoticeState> {
+    let path = state_path(product)?;
+    match fs::read_to_string(&path).await {
+        Ok(text) => NoticeState::parse(&text),
+        Err(e) if e.kind() == ErrorKind::NotFound => None,
+        Err(error) => {
+            debug!(?path, %error, "Failed to read the update notice state");
+            None
+        }
+    }
+}
+
+async fn write_state(product: Product, state: &NoticeState) {
+    let Some(path) = state_path(product) else {
+        return;
+    };
+
+    let result = async {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(&path, state.to_text()).await
+    };
+    if let Err(error) = result.await {
+        debug!(?path, %error, "Failed to write the update notice state");
+    }
+}
+
+/// A check for a newer toolchain than any installed one, which runs while `run`'s command
+/// does.
+///
+/// GitHub is only asked if the last check was at least [`CHECK_INTERVAL`] ago and the client
+/// isn't in offline mode. Otherwise, or if the command exits before GitHub answers, the
+/// notice is based on the latest release found by the last check.
+pub struct UpdateCheck {
+    client: ToolchainClient,
+    started_at: SystemTime,
+    cached: Option<ToolchainVersion>,
+    fetch: Option<JoinHandle<Option<ToolchainVersion>>>,
+}
+
+impl UpdateCheck {
+    /// Starts checking for a newer toolchain in the background if a check is due.
+    pub async fn start(client: &ToolchainClient) -> Self {
+        let started_at = SystemTime::now();
+        let state = read_state(client.product()).await;
+        let due = state.as_ref().is_none_or(|state| state.is_due(started_at));
+
+        let fetch = (due && !client.is_offline()).then(|| {
+            let client = client.clone();
+            tokio::spawn(async move { fetch_latest(&client).await })
+        });
+
+        Self {
+            client: client.clone(),
+            started_at,
+            cached: state.and_then(|state| state.latest),
+            fetch,
+        }
+    }
+
+    /// Returns the notice to print if a newer toolchain has been released.
+    ///
+    /// This never waits for GitHub: a check that's still running is abandoned, and is tried
+    /// again the next time. A finished check is saved, even if it failed, so that the next
+    /// one waits for [`CHECK_INTERVAL`]. Errors are ignored, so this never prints anything
+    /// but the notice.
+    pub async fn finish(self) -> Option<String> {
+        let product = self.client.product();
+        let mut latest = self.cached;
+
+        if let Some(fetch) = self.fetch {
+            if fetch.is_finished() {
+                // A failed check keeps the release found by an earlier one.
+                latest = fetch.await.ok().flatten().or(latest);
+                write_state(
+                    product,
+                    &NoticeState {
+                        checked_at: self.started_at,
+                        latest: latest.clone(),
+                    },
+                )
+                .await;
+            } else {
+                fetch.abort();
+            }
+        }
+
+        let latest = latest?;
+        let installed = self
+            .client
+            .installed_versions()
+            .await
+            .ok()?
+            .into_iter()
+            .max()?;
+        if latest <= installed {
+            return None;
+        }
+
+        let product_flag = if product == Product::default() {
+            String::new()
+        } else {
+            format!(" --product {product}")
+        };
+        Some(format!(
+            "{} {} {latest} is available (installed: {installed}); run `arm-toolchain update{product_flag}`",
+            "note:".cyan().bold(),
+            product.display_name,
+        ))
+    }
+}
+
+/// Asks GitHub for the latest release, returning `None` if it can't be found out.
+async fn fetch_latest(client: &ToolchainClient) -> Option<ToolchainVersion> {
+    match tokio::time::timeout(CHECK_TIMEOUT, client.latest_release()).await {
+        Ok(Ok(release)) => Some(release.version().clone()),
+        Ok(Err(error)) => {
+            debug!(%error, "Failed to check for a newer toolchain");
+            None
+        }
+        Err(_) => {
+            debug!("Timed out checking for a newer toolchain");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checked_at(seconds: u64) -> NoticeState {
+        NoticeState {
+            checked_at: UNIX_EPOCH + Duration::from_secs(seconds),
+            latest: None,
+        }
+    }
+
+    #[test]
+    fn check_is_due_once_the_interval_has_passed() {
+        let state = checked_at(1_000_000);
+        let at = |elapsed: Duration| state.checked_at + elapsed;
+
+        assert!(!state.is_due(at(Duration::ZERO)));
+        assert!(!state.is_due(at(CHECK_INTERVAL - Duration::from_secs(1))));
+        assert!(state.is_due(at(CHECK_INTERVAL)));
+        assert!(state.is_due(at(CHECK_INTERVAL * 30)));
+    }
+
+    #[test]
+    fn check_from_the_future_is_not_due() {
+        let state = checked_at(1_000_000);
+
+        assert!(!state.is_due(state.checked_at - CHECK_INTERVAL * 2));
+    }
+
+    #[test]
+    fn state_round_trips_through_its_file() {
+        for state in [
+            checked_at(1_700_000_000),
+            NoticeState {
+                latest: Some(ToolchainVersion::named("21.1.0")),
+                ..checked_at(1_700_000_000)
+            },
+        ] {
+            assert_eq!(NoticeState::parse(&state.to_text()), Some(state));
+        }
+    }
+
+    #[test]
+    fn unreadable_state_is_ignored() {
+        for text in ["", "yesterday\n21.1.0\n", "-5\n"] {
+            assert_eq!(NoticeState::parse(text), None, "{text:?}");
+        }
+    }
+}
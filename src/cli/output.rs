@@ -0,0 +1,81 @@
+use std::fmt::Display;
+
+use miette::Diagnostic;
+use serde::Serialize;
+
+use crate::cli::CliError;
+
+/// How the final result of a command is reported.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Print human-readable messages.
+    #[default]
+    Human,
+    /// Print a single JSON document to stdout once the command finishes.
+    ///
+    /// Human-readable messages and progress bars are printed to stderr instead.
+    Json,
+}
+
+#[derive(Serialize)]
+struct JsonReport<'a, T> {
+    success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<&'a T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<JsonError>,
+}
+
+#[derive(Serialize)]
+struct JsonError {
+    /// The stable diagnostic code, e.g. `arm_toolchain::toolchain::checksum_mismatch`.
+    code: &'static str,
+    message: String,
+    help: Option<String>,
+    /// Structured information about the error, such as what a cancelled installation kept.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<serde_json::Value>,
+}
+
+impl OutputFormat {
+    /// Prints a human-readable message, which goes to stderr if stdout is reserved for JSON.
+    pub fn message(self, message: impl Display) {
+        match self {
+            Self::Human => println!("{message}"),
+            Self::Json => eprintln!("{message}"),
+        }
+    }
+
+    /// Reports the final result of a command.
+    ///
+    /// In JSON mode, this prints the result (or error) as a JSON document to stdout. The error
+    /// is still returned so that it's also displayed on stderr and sets the exit code.
+    pub fn finish<T: Serialize>(self, result: Result<T, CliError>) -> Result<(), CliError> {
+        if self == Self::Json {
+            let report = match &result {
+                Ok(value) => JsonReport {
+                    success: true,
+                    result: Some(value),
+                    error: None,
+                },
+                Err(error) => JsonReport {
+                    success: false,
+                    result: None,
+                    error: Some(JsonError {
+                        code: error.stable_code(),
+                        message: error.to_string(),
+                        help: error.help().map(|help| help.to_string()),
+                        details: error.details(),
+                    }),
+                },
+            };
+
+            println!(
+                "{}",
+                serde_json::to_string(&report).expect("reports are serializable")
+            );
+        }
+
+        result.map(|_| ())
+    }
+}
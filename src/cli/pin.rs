@@ -0,0 +1,380 @@
+use std::{
+    fmt::{self, Display},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use miette::Diagnostic;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::debug;
+
+use crate::{
+    cli::CliError,
+    fs,
+    toolchain::{Product, ToolchainVersion},
+};
+
+/// The name of the file in the data directory that lists every pin file written by
+/// `use --local`, one absolute path per line.
+const PIN_REFERENCES_FILENAME: &str = "pin-references.txt";
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("Failed to load the toolchain pin file at {}", path.display())]
+#[diagnostic(code(arm_toolchain::cli::invalid_pin))]
+pub struct PinError {
+    path: PathBuf,
+    #[source]
+    source: toml::de::Error,
+}
+
+/// The toolchain a project is pinned to, loaded from an `arm-toolchain.toml` file in the
+/// project's directory or one of its parents.
+///
+/// A pinned toolchain is used instead of the active toolchain when running commands
+/// in that directory.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Pin {
+    /// The version of the toolchain.
+    pub version: String,
+    /// The product the version belongs to, if it isn't the Arm Toolchain for Embedded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub product: Option<String>,
+}
+
+/// A [`Pin`] and the file it was loaded from.
+#[derive(Debug, Clone)]
+pub struct PinFile {
+    pub path: PathBuf,
+    pub pin: Pin,
+}
+
+impl Pin {
+    pub const FILENAME: &str = "arm-toolchain.toml";
+
+    /// Creates a pin for the given version of a product.
+    pub fn new(version: &ToolchainVersion, product: Product) -> Self {
+        Self {
+            version: version.name.clone(),
+            product: (product != Product::default()).then(|| product.id.to_string()),
+        }
+    }
+
+    /// Returns the pinned version if it belongs to the given product.
+    pub fn version_for(&self, product: Product) -> Option<ToolchainVersion> {
+        let pinned_product = match &self.product {
+            Some(id) => id.parse::<Product>().ok()?,
+            None => Product::default(),
+        };
+
+        (pinned_product == product).then(|| ToolchainVersion::named(&self.version))
+    }
+}
+
+impl PinFile {
+    /// Finds the pin file that applies to the given directory by searching it and its parents.
+    pub async fn find(dir: &Path) -> Result<Option<Self>, CliError> {
+        for dir in dir.ancestors() {
+            if let Some(pin_file) = Self::load(&dir.join(Pin::FILENAME)).await? {
+                return Ok(Some(pin_file));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Loads a pin file, or returns `None` if it doesn't exist.
+    pub async fn load(path: &Path) -> Result<Option<Self>, CliError> {
+        let Some(contents) = read_pin_file(path).await? else {
+            return Ok(None);
+        };
+
+        let pin = toml::from_str(&contents).map_err(|source| PinError {
+            path: path.to_owned(),
+            source,
+        })?;
+
+        Ok(Some(Self {
+            path: path.to_owned(),
+            pin,
+        }))
+    }
+
+    /// Writes the pin to its file, replacing any existing pin.
+    pub async fn save(&self) -> Result<(), CliError> {
+        let contents = toml::to_string(&self.pin).expect("pins are serializable");
+        fs::write(&self.path, contents).await?;
+        Ok(())
+    }
+
+    /// Loads a pin file like [`load`](Self::load), but ignores fields that this version doesn't
+    /// know about, such as ones written by a newer version.
+    async fn load_known_fields(path: &Path) -> Result<Option<Self>, CliError> {
+        let Some(contents) = read_pin_file(path).await? else {
+            return Ok(None);
+        };
+
+        let pin = toml::from_str::<toml::Table>(&contents)
+            .and_then(|mut table| {
+                table.retain(|key, _| matches!(key, "version" | "product"));
+                table.try_into()
+            })
+            .map_err(|source| PinError {
+                path: path.to_owned(),
+                source,
+            })?;
+
+        Ok(Some(Self {
+            path: path.to_owned(),
+            pin,
+        }))
+    }
+
+    /// Changes the pinned version in the file, keeping the rest of its fields, including ones
+    /// this version doesn't know about.
+    ///
+    /// The file is only rewritten if it's in the simple format that [`save`](Self::save)
+    /// writes, so that comments and formatting added by hand are never lost. Returns whether it
+    /// was rewritten. The new file replaces the old one in one step, so the project is never
+    /// left without a pin.
+    pub async fn repin(&mut self, version: &ToolchainVersion) -> Result<bool, CliError> {
+        let contents = fs::read_to_string(&self.path).await?;
+        let Some(contents) = repinned(&contents, &version.name) else {
+            return Ok(false);
+        };
+
+        let temp_path = self.path.with_file_name(format!(".{}.tmp", Pin::FILENAME));
+        fs::write(&temp_path, contents).await?;
+        fs::rename(&temp_path, &self.path).await?;
+
+        self.pin.version = version.name.clone();
+        Ok(true)
+    }
+}
+
+/// Reads a pin file, or returns `None` if it doesn't exist.
+async fn read_pin_file(path: &Path) -> Result<Option<String>, CliError> {
+    match fs::read_to_string(path).await {
+        Ok(contents) => Ok(Some(contents)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Changes the version in the contents of a pin file, keeping every other line as it is.
+///
+/// Returns `None` unless every line is a field with a simple value, written the way `toml`
+/// writes it (in any order). Anything else was edited by hand.
+fn repinned(contents: &str, version: &str) -> Option<String> {
+    let table: toml::Table = toml::from_str(contents).ok()?;
+    let old_version = table.get("version")?.as_str()?;
+    if table
+        .values()
+        .any(|value| value.is_table() || value.is_array())
+    {
+        return None;
+    }
+
+    let serialized = toml::to_string(&table).ok()?;
+    let mut expected: Vec<_> = serialized.lines().collect();
+    let mut lines: Vec<_> = contents.lines().collect();
+    expected.sort_unstable();
+    lines.sort_unstable();
+    if lines != expected || !contents.ends_with('\n') {
+        return None;
+    }
+
+    let version_line = |version: &str| format!("version = {}", toml::Value::from(version));
+    let (old_line, new_line) = (version_line(old_version), version_line(version));
+
+    let mut repinned = String::new();
+    for line in contents.lines() {
+        repinned += if line == old_line { &new_line } else { line };
+        repinned.push('\n');
+    }
+    Some(repinned)
+}
+
+/// Returns the path of the list of pin files, or `None` if there is no home directory.
+fn pin_references_path() -> Option<PathBuf> {
+    let dirs = crate::dirs().ok()?;
+    Some(dirs.data_local_dir().join(PIN_REFERENCES_FILENAME))
+}
+
+/// Returns the pin files that `use --local` wrote, including ones that were since deleted.
+pub async fn pin_references() -> Result<Vec<PathBuf>, CliError> {
+    let Some(path) = pin_references_path() else {
+        return Ok(vec![]);
+    };
+
+    match fs::read_to_string(&path).await {
+        Ok(contents) => Ok(contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(PathBuf::from)
+            .collect()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Adds a pin file to the list used to find the projects that a removal affects.
+///
+/// Pin files that no longer exist are dropped from the list at the same time.
+pub async fn record_pin_reference(pin_path: &Path) -> Result<(), CliError> {
+    let Some(path) = pin_references_path() else {
+        return Ok(());
+    };
+    let pin_path = fs::canonicalize(pin_path).await?;
+
+    let mut references = pin_references().await?;
+    references.retain(|reference| reference != &pin_path && reference.exists());
+    references.push(pin_path);
+
+    let mut contents = String::new();
+    for reference in &references {
+        contents += &reference.to_string_lossy();
+        contents.push('\n');
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    let temp_path = path.with_extension("txt.tmp");
+    fs::write(&temp_path, contents).await?;
+    fs::rename(&temp_path, &path).await?;
+    Ok(())
+}
+
+/// Finds the pin files written by `use --local` that pin one of the given versions of a
+/// product.
+///
+/// Fields this version doesn't know about are ignored. Pin files that were deleted, or that
+/// can't be read anymore, are skipped.
+pub async fn pins_of(
+    product: Product,
+    versions: &[ToolchainVersion],
+) -> Result<Vec<PinFile>, CliError> {
+    let mut pins = vec![];
+    for path in pin_references().await? {
+        let pin_file = match PinFile::load_known_fields(&path).await {
+            Ok(Some(pin_file)) => pin_file,
+            Ok(None) => continue,
+            Err(error) => {
+                debug!(?path, %error, "Skipping unreadable pin file");
+                continue;
+            }
+        };
+
+        if pin_file
+            .pin
+            .version_for(product)
+            .is_some_and(|version| versions.contains(&version))
+        {
+            pins.push(pin_file);
+        }
+    }
+
+    Ok(pins)
+}
+
+/// What `remove --update-pins-to` changes project pins to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PinTarget {
+    /// A specific installed version.
+    Version(ToolchainVersion),
+    /// The active toolchain (`active`).
+    Active,
+    /// The newest installed toolchain (`latest-installed`).
+    LatestInstalled,
+}
+
+impl Display for PinTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Version(version) => write!(f, "{version}"),
+            Self::Active => f.write_str("\"active\""),
+            Self::LatestInstalled => f.write_str("\"latest-installed\""),
+        }
+    }
+}
+
+/// Parses the argument of `remove --update-pins-to`.
+pub fn pin_target(value: &str) -> Result<PinTarget, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "active" => Ok(PinTarget::Active),
+        "latest-installed" => Ok(PinTarget::LatestInstalled),
+        _ if value.is_empty() => Err("the version can't be empty".to_string()),
+        _ => Ok(PinTarget::Version(ToolchainVersion::from(value))),
+    }
+}
+
+/// Returns the toolchain that should be used in the current directory: the one it's pinned to
+/// (if it's for the given product), or otherwise the active toolchain.
+pub async fn effective_toolchain(
+    product: Product,
+    active: Option<ToolchainVersion>,
+) -> Result<Option<ToolchainVersion>, CliError> {
+    effective_toolchain_in(&std::env::current_dir()?, product, active).await
+}
+
+/// Returns the toolchain that should be used in the given directory, like
+/// [`effective_toolchain`].
+pub async fn effective_toolchain_in(
+    dir: &Path,
+    product: Product,
+    active: Option<ToolchainVersion>,
+) -> Result<Option<ToolchainVersion>, CliError> {
+    let pinned = PinFile::find(dir)
+        .await?
+        .and_then(|pin_file| pin_file.pin.version_for(product));
+
+    Ok(pinned.or(active))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_pins_are_repinned() {
+        let pin = Pin::new(&ToolchainVersion::named("20.1.0"), Product::default());
+        let contents = toml::to_string(&pin).unwrap();
+
+        assert_eq!(
+            repinned(&contents, "21.1.0").as_deref(),
+            Some("version = \"21.1.0\"\n")
+        );
+    }
+
+    #[test]
+    fn unknown_fields_are_kept_in_place() {
+        let contents =
+            "channel = \"beta\"\nversion = \"20.1.0\"\nproduct = \"linux\"\nretries = 3\n";
+
+        assert_eq!(
+            repinned(contents, "21.1.0").as_deref(),
+            Some("channel = \"beta\"\nversion = \"21.1.0\"\nproduct = \"linux\"\nretries = 3\n")
+        );
+    }
+
+    #[test]
+    fn hand_edited_pins_are_not_repinned() {
+        for contents in [
+            "# Keep in sync with CI\nversion = \"20.1.0\"\n",
+            "version = \"20.1.0\" # for now\n",
+            "version=\"20.1.0\"\n",
+            "version = '20.1.0'\n",
+            "version = \"20.1.0\"\n\n",
+            "version = \"20.1.0\"",
+            "version = \"20.1.0\"\ntargets = [\"thumbv7em\"]\n",
+            "version = \"20.1.0\"\n\n[env]\nCC = \"clang\"\n",
+            "product = \"linux\"\n",
+            "version = 20\n",
+            "version = \n",
+        ] {
+            assert_eq!(repinned(contents, "21.1.0"), None, "{contents:?}");
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use humansize::DECIMAL;
+use serde::Serialize;
+
+use crate::{
+    cli::{
+        CliError, Colorize, OutputFormat, RemoveSummary, client_builder, confirm,
+        remove_concurrently,
+    },
+    toolchain::{InstalledToolchain, Product, ToolchainError, ToolchainVersion},
+};
+
+/// Configuration for [`prune`].
+#[derive(Debug, clap::Parser)]
+pub struct PruneArgs {
+    /// Only remove toolchains that have no install reasons recorded.
+    ///
+    /// Reasons are recorded by `install`, `use`, and `update`, and by other tools that share
+    /// the toolchains directory. They're shown by `list --verbose`.
+    #[clap(long)]
+    pub unused: bool,
+    /// Don't ask for confirmation before removing the toolchains.
+    #[clap(long, short)]
+    pub yes: bool,
+    /// Show which toolchains would be removed without removing them.
+    #[clap(long)]
+    pub dry_run: bool,
+    /// Which toolchains to prune ("embedded" or "linux").
+    #[clap(long, default_value = "embedded")]
+    pub product: Product,
+    /// How the result should be reported.
+    #[clap(long, default_value = "human")]
+    pub output: OutputFormat,
+}
+
+/// A toolchain that [`prune`] removes, or would remove.
+#[derive(Debug, Serialize)]
+pub struct PruneCandidate {
+    pub version: ToolchainVersion,
+    /// Why the toolchain was installed, as recorded by this tool and others.
+    pub reasons: Vec<String>,
+}
+
+/// The result of [`prune`].
+#[derive(Debug, Serialize)]
+pub struct PruneReport {
+    /// The toolchains that were chosen for removal.
+    pub candidates: Vec<PruneCandidate>,
+    /// The toolchains that were removed, which is empty if `--dry-run` was passed.
+    #[serde(flatten)]
+    pub summary: RemoveSummary,
+    /// Whether nothing was removed because `--dry-run` was passed.
+    pub dry_run: bool,
+}
+
+/// Remove every installed toolchain except the active one, or only the ones that nothing has
+/// recorded a reason for.
+pub async fn prune(args: PruneArgs) -> Result<PruneReport, CliError> {
+    let client = client_builder().product(args.product).build().await?;
+
+    let active = client.active_toolchain();
+    let reasons = client.install_reasons().await?;
+    let mut installed = client.installed_versions().await?;
+    installed.sort();
+
+    let candidates = installed
+        .into_iter()
+        .filter(|version| active.as_ref() != Some(version))
+        .map(|version| PruneCandidate {
+            reasons: reasons.get(&version).to_vec(),
+            version,
+        })
+        .filter(|candidate| !args.unused || candidate.reasons.is_empty())
+        .collect::<Vec<_>>();
+
+    let mut report = PruneReport {
+        candidates,
+        summary: RemoveSummary {
+            removed: vec![],
+            bytes_removed: 0,
+            dangling_pins: vec![],
+        },
+        dry_run: args.dry_run,
+    };
+
+    if report.candidates.is_empty() {
+        args.output.message("Nothing to prune.");
+        return Ok(report);
+    }
+
+    let mut total_size = 0;
+    for candidate in &report.candidates {
+        let toolchain = InstalledToolchain::new(client.install_path_for(&candidate.version));
+        total_size += toolchain.size(false).await?.bytes;
+
+        match candidate.reasons.as_slice() {
+            [] => args
+                .output
+                .message(format_args!("- {}", candidate.version.to_string().bold())),
+            reasons => args.output.message(format_args!(
+                "- {} (installed for: {})",
+                candidate.version.to_string().bold(),
+                reasons.join(", ")
+            )),
+        }
+    }
+
+    let count = report.candidates.len();
+    let size = humansize::format_size(total_size, DECIMAL);
+    if args.dry_run {
+        args.output.message(format_args!(
+            "Would remove {count} toolchains, freeing {size}"
+        ));
+        return Ok(report);
+    }
+
+    if !args.yes {
+        let has_reasons = report
+            .candidates
+            .iter()
+            .any(|candidate| !candidate.reasons.is_empty());
+        let help = if has_reasons {
+            "Some of them are still needed for the reasons shown. No = cancel"
+        } else {
+            "Their files will be deleted. No = cancel"
+        };
+
+        let confirmed = confirm(
+            format!("Remove {count} toolchains, freeing {size}?"),
+            help,
+            "--yes",
+        )
+        .await?;
+
+        if !confirmed {
+            eprintln!("Cancelled.");
+            return Err(ToolchainError::Cancelled)?;
+        }
+    }
+
+    let versions = report
+        .candidates
+        .iter()
+        .map(|candidate| candidate.version.clone())
+        .collect();
+    report.summary = remove_concurrently(client, versions, args.output).await?;
+
+    Ok(report)
+}
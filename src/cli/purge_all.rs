@@ -0,0 +1,117 @@
+use std::{io::ErrorKind, path::PathBuf};
+
+use humansize::DECIMAL;
+use serde::Serialize;
+
+use crate::{
+    DIRS,
+    cli::{
+        CliError, Colorize, Config, OutputFormat, client_builder, confirm_typed, ctrl_c_cancel, msg,
+    },
+    fs,
+    toolchain::{PurgeAllReport, ToolchainError},
+};
+
+/// Configuration for [`purge_all`].
+#[derive(Debug, clap::Args)]
+pub struct PurgeAllArgs {
+    /// Skip the confirmation prompt.
+    #[clap(long, short)]
+    pub yes: bool,
+    /// How the result should be reported.
+    #[clap(long, default_value = "human")]
+    pub output: OutputFormat,
+}
+
+/// The result of [`purge_all`].
+#[derive(Debug, Serialize)]
+pub struct PurgeAllSummary {
+    #[serde(flatten)]
+    pub report: PurgeAllReport,
+    /// The configuration files that were deleted.
+    pub config_files: Vec<PathBuf>,
+    /// The empty directories that were deleted afterwards.
+    pub directories: Vec<PathBuf>,
+}
+
+/// Delete every toolchain, the cache, and the configuration, then print a summary to stdio.
+pub async fn purge_all(args: PurgeAllArgs) -> Result<PurgeAllSummary, CliError> {
+    let client = client_builder().build().await?;
+
+    if !args.yes {
+        let confirmed = confirm_typed(
+            "Delete every installed toolchain, the download cache, and the configuration?"
+                .to_string(),
+            "yes",
+            "--yes",
+        )
+        .await?;
+
+        if !confirmed {
+            eprintln!("Cancelled.");
+            return Err(ToolchainError::Cancelled)?;
+        }
+    }
+
+    msg!("Deleting", "every toolchain and the download cache");
+    let cancel_token = ctrl_c_cancel();
+    let report = client.purge_all(|_| {}, &cancel_token).await?;
+    cancel_token.cancel();
+
+    let mut config_files = vec![];
+    if let Some(path) = Config::path() {
+        match fs::remove_file(&path).await {
+            Ok(()) => config_files.push(path),
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    // Only remove directories that are now empty, in case something else was put in them.
+    let mut directories = vec![];
+    if let Some(dirs) = DIRS.as_ref() {
+        let candidates = [
+            dirs.cache_dir().join("downloads"),
+            dirs.cache_dir().to_owned(),
+            dirs.data_local_dir().to_owned(),
+            dirs.data_dir().to_owned(),
+            dirs.config_dir().to_owned(),
+        ];
+
+        for dir in candidates {
+            if !directories.contains(&dir) && fs::remove_dir(&dir).await.is_ok() {
+                directories.push(dir);
+            }
+        }
+    }
+
+    for path in &report.toolchains {
+        args.output
+            .message(format_args!("Deleted toolchain {}", path.display()));
+    }
+    for failure in &report.cache.failed {
+        eprintln!(
+            "{} could not delete {}: {}",
+            "warning:".yellow().bold(),
+            failure.path.display(),
+            failure.error
+        );
+    }
+    for path in config_files.iter().chain(&directories) {
+        args.output
+            .message(format_args!("Deleted {}", path.display()));
+    }
+
+    msg!(
+        "Purged",
+        "{} toolchain(s) and the download cache ({})",
+        report.toolchains.len(),
+        humansize::format_size(report.bytes_removed, DECIMAL)
+    );
+
+    Ok(PurgeAllSummary {
+        report,
+        config_files,
+        directories,
+    })
+}
@@ -1,26 +1,92 @@
+use std::{fmt, path::PathBuf};
+
 use futures::future::try_join_all;
 use humansize::DECIMAL;
-use indicatif::{MultiProgress, ProgressBar};
+use serde::Serialize;
 use tokio_util::sync::CancellationToken;
 
 use crate::{
-    cli::{CliError, PROGRESS_STYLE_DELETE, PROGRESS_STYLE_DELETE_SPINNER, ctrl_c_cancel, msg},
-    toolchain::{RemoveProgress, ToolchainClient, ToolchainError, ToolchainVersion},
+    cli::{
+        CliError, Colorize, Lookup, OutputFormat, PinTarget, ProgressBars, VersionSpec,
+        client_builder, closest_versions, confirm, ctrl_c_cancel, msg, pin_target, pins_of,
+        removable_spec, resolve_spec,
+    },
+    toolchain::{
+        InstalledToolchain, Product, RemoveReport, ToolchainClient, ToolchainError,
+        ToolchainVersion,
+    },
 };
 
 /// Configuration for [`remove`].
 #[derive(Debug, clap::Parser)]
 pub struct RemoveArgs {
-    /// Version of toolchain to remove, or "all"
+    /// Versions of toolchains to remove, "previous", aliases from the config file, or "all"
+    #[clap(required = true, value_parser = removable_spec)]
+    pub version: Vec<VersionSpec>,
+    /// Don't ask for confirmation before removing several toolchains.
+    #[clap(long, short)]
+    pub yes: bool,
+    /// Which toolchain to remove ("embedded" or "linux").
+    #[clap(long, default_value = "embedded")]
+    pub product: Product,
+    /// Change the projects pinned to a removed toolchain to this one instead: a version,
+    /// "active", or "latest-installed".
+    ///
+    /// Only pin files that were written by `use --local` and haven't been edited since are
+    /// changed. Otherwise, the projects are only listed.
+    #[clap(long, value_name = "VERSION", value_parser = pin_target)]
+    pub update_pins_to: Option<PinTarget>,
+    /// How the result should be reported.
+    #[clap(long, default_value = "human")]
+    pub output: OutputFormat,
+}
+
+/// The result of [`remove`].
+#[derive(Debug, Serialize)]
+pub struct RemoveSummary {
+    /// The toolchains that were removed.
+    pub removed: Vec<RemoveReport>,
+    /// The total number of bytes deleted.
+    pub bytes_removed: u64,
+    /// The projects that were pinned to a removed toolchain.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dangling_pins: Vec<DanglingPin>,
+}
+
+/// A project that was pinned to a toolchain that [`remove`] removed.
+#[derive(Debug, Serialize)]
+pub struct DanglingPin {
+    /// The project's pin file.
+    pub path: PathBuf,
+    /// The removed version that it pinned.
     pub version: ToolchainVersion,
+    /// The version it was changed to with `--update-pins-to`, if it was changed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_to: Option<ToolchainVersion>,
 }
 
-/// Remove a toolchain from the system.
-pub async fn remove(args: RemoveArgs) -> Result<(), CliError> {
-    let client = ToolchainClient::using_data_dir().await?;
+/// Remove toolchains from the system.
+pub async fn remove(args: RemoveArgs) -> Result<RemoveSummary, CliError> {
+    let client = client_builder().product(args.product).build().await?;
     let toolchains = client.installed_versions().await?;
 
-    if args.version.name == "all" {
+    let remove_all = args.version.contains(&VersionSpec::All);
+    let versions = if remove_all {
+        if args.version.len() > 1 {
+            return Err(CliError::RemoveAllWithVersions);
+        }
+        toolchains.clone()
+    } else {
+        resolve_versions(&client, &args.version, &toolchains).await?
+    };
+
+    // Checked before anything is removed, since the pins can't be changed otherwise.
+    let pin_target = match &args.update_pins_to {
+        Some(target) => Some(resolve_pin_target(&client, target, &toolchains, &versions)?),
+        None => None,
+    };
+
+    let mut summary = if remove_all {
         let old_active = client.active_toolchain();
         client.set_active_toolchain(None).await?;
 
@@ -28,85 +94,285 @@ pub async fn remove(args: RemoveArgs) -> Result<(), CliError> {
             return Err(CliError::NoToolchainEnabled);
         }
 
-        let cancel_token = ctrl_c_cancel();
-        let multi_progress = MultiProgress::new();
-        let mut futs = vec![];
+        report_install_reasons(&client, &versions, args.output).await?;
+        remove_concurrently(client, versions, args.output).await?
+    } else {
+        remove_versions(client, versions, args.yes, args.output).await?
+    };
 
-        for version in toolchains {
-            let client = client.clone();
-            let tok = cancel_token.clone();
-            let multi_progress = multi_progress.clone();
+    let removed = summary
+        .removed
+        .iter()
+        .map(|report| report.version.clone())
+        .collect::<Vec<_>>();
+    summary.dangling_pins =
+        report_dangling_pins(args.product, &removed, pin_target.as_ref(), args.output).await;
 
-            futs.push(remove_with_progress_bar(
-                client,
-                version,
-                tok,
-                multi_progress,
-            ));
-        }
-
-        let out = try_join_all(futs).await?;
-        let total_bytes = out.iter().sum::<u64>();
+    Ok(summary)
+}
 
-        println!(
-            "Removed {} toolchains ({})",
-            out.len(),
-            humansize::format_size(total_bytes, DECIMAL),
+/// Resolves the versions given to [`remove`], failing if any of them isn't installed.
+async fn resolve_versions(
+    client: &ToolchainClient,
+    specs: &[VersionSpec],
+    toolchains: &[ToolchainVersion],
+) -> Result<Vec<ToolchainVersion>, CliError> {
+    let mut versions = vec![];
+    for spec in specs {
+        versions.extend(
+            resolve_spec(client, spec, Lookup::Installed)
+                .await?
+                .into_iter()
+                .map(|resolved| resolved.version),
         );
+    }
+    versions.sort();
+    versions.dedup();
 
-        cancel_token.cancel();
-    } else {
-        if !toolchains.contains(&args.version) {
-            return Err(CliError::CannotRemoveMissingToolchain {
-                version: args.version,
-            });
+    // Every missing version is reported at once, so that nothing is removed if any are wrong.
+    let mut missing: Vec<_> = versions
+        .iter()
+        .filter(|version| !toolchains.contains(version))
+        .cloned()
+        .collect();
+    match missing.len() {
+        0 => Ok(versions),
+        1 => {
+            let version = missing.remove(0);
+            let suggestions = closest_versions(&version, toolchains.iter().cloned());
+            Err(CliError::CannotRemoveMissingToolchain {
+                version,
+                suggestions,
+            })
         }
+        _ => Err(CliError::CannotRemoveMissingToolchains { versions: missing }),
+    }
+}
+
+/// Removes the given toolchains, asking for confirmation if there are several.
+async fn remove_versions(
+    client: ToolchainClient,
+    versions: Vec<ToolchainVersion>,
+    yes: bool,
+    output: OutputFormat,
+) -> Result<RemoveSummary, CliError> {
+    report_install_reasons(&client, &versions, output).await?;
 
+    if let [version] = versions.as_slice() {
         let cancel_token = ctrl_c_cancel();
-        let multi = MultiProgress::new();
-        let bytes =
-            remove_with_progress_bar(client, args.version.clone(), cancel_token.clone(), multi)
-                .await?;
+        let report =
+            remove_with_progress_bar(client, version.clone(), cancel_token.clone(), None).await?;
 
         cancel_token.cancel();
 
         msg!(
             "Removed",
-            "{} ({})",
-            args.version,
-            humansize::format_size(bytes, DECIMAL),
+            "{version} ({})",
+            humansize::format_size(report.bytes_removed, DECIMAL),
         );
+
+        return Ok(RemoveSummary {
+            bytes_removed: report.bytes_removed,
+            removed: vec![report],
+            dangling_pins: vec![],
+        });
+    }
+
+    if !yes {
+        let mut total_size = 0;
+        for version in &versions {
+            let toolchain = InstalledToolchain::new(client.install_path_for(version));
+            total_size += toolchain.size(false).await?.bytes;
+        }
+
+        let names = versions
+            .iter()
+            .map(|version| version.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let confirmed = confirm(
+            format!(
+                "Remove {} toolchains ({names}), freeing {}?",
+                versions.len(),
+                humansize::format_size(total_size, DECIMAL)
+            ),
+            "Their files will be deleted. No = cancel",
+            "--yes",
+        )
+        .await?;
+
+        if !confirmed {
+            eprintln!("Cancelled.");
+            return Err(ToolchainError::Cancelled)?;
+        }
+    }
+
+    remove_concurrently(client, versions, output).await
+}
+
+/// Returns the version that `--update-pins-to` refers to once the given toolchains are
+/// removed, failing if it won't be installed anymore.
+fn resolve_pin_target(
+    client: &ToolchainClient,
+    target: &PinTarget,
+    installed: &[ToolchainVersion],
+    removing: &[ToolchainVersion],
+) -> Result<ToolchainVersion, CliError> {
+    let remaining =
+        |version: &ToolchainVersion| installed.contains(version) && !removing.contains(version);
+
+    let version = match target {
+        PinTarget::Version(version) => Some(version.clone()),
+        PinTarget::Active => client.active_toolchain(),
+        PinTarget::LatestInstalled => installed.iter().filter(|v| remaining(v)).max().cloned(),
+    };
+
+    version
+        .filter(remaining)
+        .ok_or_else(|| CliError::InvalidPinTarget {
+            target: target.to_string(),
+        })
+}
+
+/// Tells the user which projects were pinned to a removed toolchain, and changes their pins
+/// to `target` if it's given.
+///
+/// The toolchains are already gone by now, so problems with the pins are only warned about.
+async fn report_dangling_pins(
+    product: Product,
+    removed: &[ToolchainVersion],
+    target: Option<&ToolchainVersion>,
+    output: OutputFormat,
+) -> Vec<DanglingPin> {
+    let warn = |message: fmt::Arguments| {
+        output.message(format_args!("{} {message}", "warning:".yellow().bold()));
+    };
+
+    let pin_files = match pins_of(product, removed).await {
+        Ok(pin_files) => pin_files,
+        Err(error) => {
+            warn(format_args!(
+                "couldn't check which projects were pinned: {error}"
+            ));
+            return vec![];
+        }
+    };
+
+    let mut dangling = vec![];
+    for mut pin_file in pin_files {
+        let version = ToolchainVersion::named(&pin_file.pin.version);
+        let path = pin_file.path.display().to_string();
+        let mut updated_to = None;
+
+        match target {
+            Some(target) => match pin_file.repin(target).await {
+                Ok(true) => {
+                    msg!("Repinned", "{path} to {target}");
+                    updated_to = Some(target.clone());
+                }
+                Ok(false) => warn(format_args!(
+                    "{path} pins {version}, but wasn't changed because it was edited by hand"
+                )),
+                Err(error) => warn(format_args!(
+                    "{path} pins {version}, but couldn't be changed: {error}"
+                )),
+            },
+            None => warn(format_args!("{path} pins {version}, which was removed")),
+        }
+
+        dangling.push(DanglingPin {
+            path: pin_file.path,
+            version,
+            updated_to,
+        });
+    }
+
+    if target.is_none() && !dangling.is_empty() {
+        output.message(
+            "Pass `--update-pins-to <VERSION>` to change them, or run `use <VERSION> --local` in each project.",
+        );
+    }
+
+    dangling
+}
+
+/// Tells the user which of the given toolchains other tools (or earlier commands) recorded a
+/// reason for, since removing them may break whatever needed them.
+pub(crate) async fn report_install_reasons(
+    client: &ToolchainClient,
+    versions: &[ToolchainVersion],
+    output: OutputFormat,
+) -> Result<(), CliError> {
+    let reasons = client.install_reasons().await?;
+    for version in versions {
+        let reasons = reasons.get(version);
+        if !reasons.is_empty() {
+            output.message(format_args!(
+                "{} is installed for: {}",
+                version.to_string().bold(),
+                reasons.join(", ")
+            ));
+        }
     }
 
     Ok(())
 }
 
-async fn remove_with_progress_bar(
+/// Removes several toolchains at once, displaying a progress bar for each.
+pub(crate) async fn remove_concurrently(
+    client: ToolchainClient,
+    versions: Vec<ToolchainVersion>,
+    output: OutputFormat,
+) -> Result<RemoveSummary, CliError> {
+    let cancel_token = ctrl_c_cancel();
+    let progress_bars = ProgressBars::default();
+    let mut futs = vec![];
+
+    for version in versions {
+        let client = client.clone();
+        let tok = cancel_token.clone();
+
+        futs.push(remove_with_progress_bar(
+            client,
+            version,
+            tok,
+            Some(&progress_bars),
+        ));
+    }
+
+    let removed = try_join_all(futs).await?;
+    let bytes_removed = removed.iter().map(|r| r.bytes_removed).sum::<u64>();
+
+    output.message(format_args!(
+        "Removed {} toolchains ({})",
+        removed.len(),
+        humansize::format_size(bytes_removed, DECIMAL),
+    ));
+
+    cancel_token.cancel();
+
+    Ok(RemoveSummary {
+        removed,
+        bytes_removed,
+        dangling_pins: vec![],
+    })
+}
+
+/// Removes a toolchain while displaying its progress.
+///
+/// The progress bar is added to `progress_bars` if it's given, which lets it be displayed
+/// alongside the caller's own progress bars and cleared once the removal is done.
+/// Otherwise, it's added to a new group of progress bars.
+pub async fn remove_with_progress_bar(
     client: ToolchainClient,
     version: ToolchainVersion,
     cancel_token: CancellationToken,
-    multi_progress: MultiProgress,
-) -> Result<u64, ToolchainError> {
-    let bar = ProgressBar::no_length()
-        .with_style(PROGRESS_STYLE_DELETE_SPINNER.clone())
-        .with_message(format!("Removing {version}"));
-    multi_progress.add(bar.clone());
-
-    let progress = |status| match status {
-        RemoveProgress::Start { total_bytes } => {
-            bar.reset();
-            bar.set_length(total_bytes);
-            bar.set_style(PROGRESS_STYLE_DELETE.clone());
-        }
-        RemoveProgress::Progress { bytes_removed } => {
-            bar.set_position(bytes_removed);
-        }
-        RemoveProgress::End => {
-            bar.finish_with_message(format!("{version} is removed"));
-        }
+    progress_bars: Option<&ProgressBars>,
+) -> Result<RemoveReport, ToolchainError> {
+    let progress = match progress_bars {
+        Some(progress_bars) => progress_bars.remove_handler(&version),
+        None => ProgressBars::default().remove_handler(&version),
     };
 
-    client.remove(&version, progress, &cancel_token).await?;
-
-    Ok(bar.length().unwrap_or(0))
+    client.remove(&version, progress, &cancel_token).await
 }
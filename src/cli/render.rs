@@ -0,0 +1,15 @@
+//! How the CLI displays progress, colors, and prompts.
+//!
+//! With the `cli-interactive` feature, these use progress bars and interactive prompts.
+//! Otherwise, plain text is printed and prompts can't be shown, so commands that need
+//! confirmation must be passed a flag such as `--yes`.
+
+#[cfg(feature = "cli-interactive")]
+mod interactive;
+#[cfg(feature = "cli-interactive")]
+pub use interactive::*;
+
+#[cfg(not(feature = "cli-interactive"))]
+mod plain;
+#[cfg(not(feature = "cli-interactive"))]
+pub use plain::*;
@@ -0,0 +1,286 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use indicatif::{MultiProgress, ProgressBar};
+use inquire::{Confirm, Text};
+pub use owo_colors::OwoColorize as Colorize;
+use tokio::task::spawn_blocking;
+
+use crate::{
+    cli::{
+        CliError, PROGRESS_STYLE_DELETE, PROGRESS_STYLE_DELETE_SPINNER, PROGRESS_STYLE_DL,
+        PROGRESS_STYLE_DL_SPINNER, PROGRESS_STYLE_EXTRACT, PROGRESS_STYLE_EXTRACT_SPINNER,
+        PROGRESS_STYLE_VERIFY,
+    },
+    toolchain::{InstallState, RemoveProgress, ToolchainVersion, VerifyProgress},
+};
+
+/// A group of progress bars that the CLI displays an operation's progress with.
+///
+/// By default, the bars are added to a new [`MultiProgress`]. Applications that already
+/// display their own progress bars should use [`ProgressBars::with_multi_progress`] so that
+/// both sets of bars are drawn together instead of overwriting each other.
+///
+/// Cloning this creates another handle to the same group.
+#[derive(Clone, Default)]
+pub struct ProgressBars {
+    multi: MultiProgress,
+    bars: Arc<Mutex<Vec<ProgressBar>>>,
+}
+
+impl ProgressBars {
+    /// Creates a group of progress bars that adds its bars to an existing [`MultiProgress`].
+    pub fn with_multi_progress(multi: MultiProgress) -> Self {
+        Self {
+            multi,
+            bars: Arc::default(),
+        }
+    }
+
+    /// Returns the [`MultiProgress`] that bars are added to.
+    pub fn multi_progress(&self) -> &MultiProgress {
+        &self.multi
+    }
+
+    /// Removes every bar that was added by this group from the terminal.
+    ///
+    /// Other bars in the [`MultiProgress`] are left alone.
+    pub fn clear(&self) {
+        for bar in self.bars.lock().unwrap().drain(..) {
+            bar.finish_and_clear();
+            self.multi.remove(&bar);
+        }
+    }
+
+    fn add(&self, bar: ProgressBar) -> ProgressBar {
+        let bar = self.multi.add(bar);
+        self.bars.lock().unwrap().push(bar.clone());
+        bar
+    }
+
+    /// Returns a progress handler that displays an installation's progress with progress bars.
+    pub fn install_handler(&self) -> Arc<dyn Fn(InstallState) + Send + Sync> {
+        let download_bar = self.add(ProgressBar::no_length().with_style(PROGRESS_STYLE_DL.clone()));
+
+        let verify_bar = self.add(
+            ProgressBar::no_length()
+                .with_style(PROGRESS_STYLE_VERIFY.clone())
+                .with_message("Verifying"),
+        );
+
+        let extract_bar = self.add(
+            ProgressBar::no_length()
+                .with_message("Extracting toolchain")
+                .with_style(PROGRESS_STYLE_EXTRACT_SPINNER.clone()),
+        );
+
+        let multi = self.multi.clone();
+        Arc::new(move |update| match update {
+            InstallState::DownloadBegin {
+                asset_size,
+                bytes_read,
+            } => {
+                download_bar.reset();
+                download_bar.enable_steady_tick(Duration::from_millis(300));
+                // A size of zero means the size is unknown, so a percentage can't be shown.
+                if asset_size == 0 {
+                    download_bar.set_style(PROGRESS_STYLE_DL_SPINNER.clone());
+                    download_bar.unset_length();
+                } else {
+                    download_bar.set_style(PROGRESS_STYLE_DL.clone());
+                    download_bar.set_length(asset_size);
+                }
+                download_bar.set_position(bytes_read);
+                download_bar.reset_eta();
+
+                // The whole file is verified once it's downloaded, even if only part of it
+                // was downloaded just now, so the verification bar can be sized up front.
+                if asset_size != 0 {
+                    verify_bar.set_length(asset_size);
+                }
+            }
+            InstallState::Download { bytes_read } => {
+                download_bar.set_position(bytes_read);
+            }
+            InstallState::DownloadRetry {
+                attempt,
+                max_attempts,
+                delay,
+                reason,
+            } => {
+                // The next attempt starts with `DownloadBegin`, which resets the bar to how
+                // much was kept.
+                multi.suspend(|| {
+                    eprintln!(
+                        "{} Download attempt {attempt} of {max_attempts} failed ({reason}), retrying in {:.1}s",
+                        "warning:".yellow().bold(),
+                        delay.as_secs_f64(),
+                    )
+                });
+            }
+            InstallState::DownloadFinish => {
+                download_bar.disable_steady_tick();
+                download_bar.finish_with_message("Download complete");
+            }
+            InstallState::VerifyingBegin { asset_size } => {
+                verify_bar.reset();
+                verify_bar.set_length(asset_size);
+            }
+            InstallState::Verifying { bytes_read } => {
+                verify_bar.set_position(bytes_read);
+            }
+            InstallState::VerifyingFinish => {
+                verify_bar.finish_with_message("Verification complete");
+            }
+            InstallState::VerifyingSkipped => {
+                verify_bar.finish_and_clear();
+                multi.suspend(|| {
+                    eprintln!(
+                        "{} No checksum was given, so the download won't be verified",
+                        "warning:".yellow().bold(),
+                    )
+                });
+            }
+            InstallState::ExtractBegin { estimated_size } => match estimated_size {
+                Some(estimated_size) => {
+                    extract_bar.set_style(PROGRESS_STYLE_EXTRACT.clone());
+                    extract_bar.reset();
+                    extract_bar.set_length(estimated_size);
+                }
+                None => {
+                    extract_bar.set_style(PROGRESS_STYLE_EXTRACT_SPINNER.clone());
+                    extract_bar.enable_steady_tick(Duration::from_millis(300));
+                }
+            },
+            InstallState::RemoveOldInstallBegin => {
+                extract_bar.set_message("Removing previous installation");
+            }
+            InstallState::RemoveOldInstallFinish => {
+                extract_bar.set_message("Extracting toolchain");
+            }
+            InstallState::ExtractCopy {
+                bytes_copied,
+                total_size,
+            } => {
+                if extract_bar.length().is_none() {
+                    extract_bar.set_style(PROGRESS_STYLE_EXTRACT.clone());
+                    extract_bar.reset();
+                }
+
+                extract_bar.set_length(total_size);
+                extract_bar.set_position(bytes_copied);
+            }
+            InstallState::ApplyPermissionsBegin { total_entries } => {
+                extract_bar.set_style(PROGRESS_STYLE_EXTRACT.clone());
+                extract_bar.reset();
+                extract_bar.set_length(total_entries);
+                extract_bar.set_message("Applying permissions");
+            }
+            InstallState::ApplyPermissions { entries_done } => {
+                extract_bar.set_position(entries_done);
+            }
+            InstallState::ApplyPermissionsFinish => {}
+            InstallState::ExtractCleanUp => {
+                // Deleting the archive can take a while on slow disks, so show that something
+                // is still happening.
+                extract_bar.set_style(PROGRESS_STYLE_EXTRACT_SPINNER.clone());
+                extract_bar.enable_steady_tick(Duration::from_millis(300));
+                extract_bar.set_message("Cleaning up");
+            }
+            InstallState::ExtractDone => {
+                extract_bar.finish_with_message("Extraction complete");
+            }
+            // Activation is reported once installation finishes.
+            InstallState::Activated { .. } => {}
+        })
+    }
+
+    /// Returns a progress handler that displays the removal of the given version.
+    pub fn remove_handler(&self, version: &ToolchainVersion) -> impl FnMut(RemoveProgress) + use<> {
+        let bar = self.add(
+            ProgressBar::no_length()
+                .with_style(PROGRESS_STYLE_DELETE_SPINNER.clone())
+                .with_message(format!("Removing {version}")),
+        );
+
+        let version = version.clone();
+        move |status| match status {
+            RemoveProgress::Start { total_bytes } => {
+                bar.reset();
+                bar.set_length(total_bytes);
+                bar.set_style(PROGRESS_STYLE_DELETE.clone());
+            }
+            RemoveProgress::Progress { bytes_removed } => {
+                bar.set_position(bytes_removed);
+            }
+            RemoveProgress::End => {
+                bar.finish_with_message(format!("{version} is removed"));
+            }
+        }
+    }
+
+    /// Returns a progress handler that displays the verification of the given version.
+    pub fn verify_handler(&self, version: &ToolchainVersion) -> impl FnMut(VerifyProgress) + use<> {
+        let bar = self.add(
+            ProgressBar::no_length()
+                .with_style(PROGRESS_STYLE_EXTRACT_SPINNER.clone())
+                .with_message(format!("Verifying {version}")),
+        );
+
+        move |status| match status {
+            VerifyProgress::Start { total_bytes, .. } => {
+                bar.reset();
+                bar.set_length(total_bytes);
+                bar.set_style(PROGRESS_STYLE_VERIFY.clone());
+            }
+            VerifyProgress::Progress { bytes_done, .. } => {
+                bar.set_position(bytes_done);
+            }
+            VerifyProgress::End => {
+                bar.finish_and_clear();
+            }
+        }
+    }
+}
+
+/// Asks the user a yes/no question.
+///
+/// If the terminal isn't interactive, the error explains how to skip the question with `flag`.
+pub async fn confirm(
+    message: String,
+    help_message: &'static str,
+    flag: &'static str,
+) -> Result<bool, CliError> {
+    spawn_blocking(move || {
+        Confirm::new(&message)
+            .with_default(true)
+            .with_help_message(help_message)
+            .prompt()
+    })
+    .await
+    .unwrap()
+    .map_err(|e| CliError::from_prompt_error(e, flag))
+}
+
+/// Asks the user to type `expected` to confirm a destructive action.
+///
+/// Returns `false` if they typed anything else. If the terminal isn't interactive, the error
+/// explains how to skip the question with `flag`.
+pub async fn confirm_typed(
+    message: String,
+    expected: &'static str,
+    flag: &'static str,
+) -> Result<bool, CliError> {
+    let answer = spawn_blocking(move || {
+        Text::new(&message)
+            .with_help_message(&format!("Type \"{expected}\" to continue"))
+            .prompt()
+    })
+    .await
+    .unwrap()
+    .map_err(|e| CliError::from_prompt_error(e, flag))?;
+
+    Ok(answer.trim() == expected)
+}
@@ -0,0 +1,123 @@
+use std::{fmt::Display, sync::Arc};
+
+use crate::{
+    cli::CliError,
+    toolchain::{InstallState, RemoveProgress, ToolchainVersion, VerifyProgress},
+};
+
+/// Provides the same methods as `owo_colors::OwoColorize`, without adding any colors.
+///
+/// Only the methods used by the CLI are included.
+pub trait Colorize: Display {
+    fn bold(&self) -> &Self {
+        self
+    }
+
+    fn green(&self) -> &Self {
+        self
+    }
+
+    fn yellow(&self) -> &Self {
+        self
+    }
+
+    fn red(&self) -> &Self {
+        self
+    }
+
+    fn cyan(&self) -> &Self {
+        self
+    }
+}
+
+impl<T: Display + ?Sized> Colorize for T {}
+
+/// Prints a line to stderr when an operation enters a new phase.
+///
+/// This has the same methods as the progress bars shown with the `cli-interactive` feature.
+#[derive(Clone, Default)]
+pub struct ProgressBars {}
+
+impl ProgressBars {
+    /// Does nothing, since no progress bars are shown.
+    pub fn clear(&self) {}
+
+    /// Returns a progress handler that prints a line to stderr when an installation enters
+    /// a new phase.
+    pub fn install_handler(&self) -> Arc<dyn Fn(InstallState) + Send + Sync> {
+        Arc::new(|update| {
+            if let InstallState::DownloadRetry {
+                attempt,
+                max_attempts,
+                delay,
+                reason,
+            } = &update
+            {
+                eprintln!(
+                    "warning: Download attempt {attempt} of {max_attempts} failed ({reason}), retrying in {:.1}s",
+                    delay.as_secs_f64(),
+                );
+                return;
+            }
+            if update == InstallState::VerifyingSkipped {
+                eprintln!("warning: No checksum was given, so the download won't be verified");
+                return;
+            }
+
+            let message = match update {
+                InstallState::DownloadFinish => "Download complete",
+                InstallState::VerifyingBegin { .. } => "Verifying",
+                InstallState::VerifyingFinish => "Verification complete",
+                InstallState::ExtractBegin { .. } => "Extracting toolchain",
+                InstallState::RemoveOldInstallBegin => "Removing previous installation",
+                InstallState::ApplyPermissionsBegin { .. } => "Applying permissions",
+                InstallState::ExtractCleanUp => "Cleaning up",
+                InstallState::ExtractDone => "Extraction complete",
+                _ => return,
+            };
+
+            eprintln!("{message}");
+        })
+    }
+
+    /// Returns a progress handler that reports the removal of the given version.
+    pub fn remove_handler(&self, version: &ToolchainVersion) -> impl FnMut(RemoveProgress) + use<> {
+        let version = version.clone();
+        move |status| match status {
+            RemoveProgress::Start { .. } => eprintln!("Removing {version}"),
+            RemoveProgress::Progress { .. } => {}
+            RemoveProgress::End => eprintln!("{version} is removed"),
+        }
+    }
+
+    /// Returns a progress handler that reports the verification of the given version.
+    pub fn verify_handler(&self, version: &ToolchainVersion) -> impl FnMut(VerifyProgress) + use<> {
+        let version = version.clone();
+        move |status| match status {
+            VerifyProgress::Start { total_files, .. } => {
+                eprintln!("Verifying {total_files} files of {version}");
+            }
+            VerifyProgress::Progress { .. } | VerifyProgress::End => {}
+        }
+    }
+}
+
+/// Questions can't be asked without the `cli-interactive` feature, so this always returns an
+/// error explaining how to skip the question with `flag`.
+pub async fn confirm(
+    _message: String,
+    _help_message: &'static str,
+    flag: &'static str,
+) -> Result<bool, CliError> {
+    Err(CliError::NonInteractive { flag })
+}
+
+/// Questions can't be asked without the `cli-interactive` feature, so this always returns an
+/// error explaining how to skip the question with `flag`.
+pub async fn confirm_typed(
+    _message: String,
+    _expected: &'static str,
+    flag: &'static str,
+) -> Result<bool, CliError> {
+    Err(CliError::NonInteractive { flag })
+}
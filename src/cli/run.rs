@@ -1,22 +1,70 @@
-use std::{env, ffi::OsString, process::exit};
+use std::{
+    collections::BTreeMap,
+    env,
+    ffi::OsString,
+    path::{Path, PathBuf},
+    process::exit,
+};
 
 use futures::never::Never;
+use serde::Serialize;
 use tokio::process::Command;
 
 use crate::{
-    cli::CliError,
-    toolchain::{ToolchainClient, ToolchainVersion},
+    cli::{
+        CliError, Config, Lookup, OutputFormat, RunConfig, UpdateCheck, VersionSpec,
+        client_builder, effective_toolchain, expand_template, installed_spec, installed_toolchain,
+        msg, run_measured,
+    },
+    fs,
+    toolchain::{InstalledToolchain, Product, ToolchainVersion},
 };
 
+/// The separator between entries in the `PATH` variable.
+const PATH_SEPARATOR: &str = if cfg!(windows) { ";" } else { ":" };
+
 /// Configuration for [`run`].
 #[derive(Debug, clap::Args)]
 pub struct RunArgs {
-    /// Toolchain version override (default: the active version)
-    #[arg(short = 'T', long)]
-    toolchain: Option<ToolchainVersion>,
+    /// Toolchain version override: a version, "previous", or an alias from the config file
+    /// (default: the pinned or active version)
+    #[arg(short = 'T', long, value_parser = installed_spec)]
+    toolchain: Option<VersionSpec>,
+    /// Which toolchain to run with ("embedded" or "linux").
+    #[arg(long, default_value = "embedded")]
+    product: Product,
     /// Do not set extra environment variables to enable cross-compilation.
     #[arg(long)]
     no_cross_env: bool,
+    /// Run the command with a minimal environment instead of inheriting this one.
+    ///
+    /// Only the toolchain and the system's executables are in the `PATH`, and only the
+    /// home and temporary directories, the cross-compilation variables, the `[run.env]`
+    /// variables from the config file, and the variables passed with `--keep` or listed in
+    /// `run.keep` are set.
+    #[arg(long)]
+    isolate: bool,
+    /// Pass this environment variable through to the command when using `--isolate`.
+    #[arg(long, value_name = "VAR", requires = "isolate")]
+    keep: Vec<String>,
+    /// Print the environment variables that are set for the command before running it.
+    #[arg(long)]
+    verbose: bool,
+    /// Don't print notices, such as when a newer toolchain is available.
+    #[arg(long, short)]
+    quiet: bool,
+    /// After the command exits, print how long it took, the CPU time it used, and its peak
+    /// memory usage to stderr.
+    #[arg(long)]
+    time: bool,
+    /// How the `--time` report is printed. It always goes to stderr, since stdout belongs
+    /// to the command.
+    #[arg(long, default_value = "human", requires = "time")]
+    output: OutputFormat,
+    /// Before running the command, write a JSON record of the toolchain and environment
+    /// variables it's run with to this file.
+    #[arg(long, value_name = "PATH")]
+    emit_env_record: Option<PathBuf>,
     /// The command to run with the modified environment.
     command: OsString,
     /// Arguments to pass to the command.
@@ -30,29 +78,331 @@ pub struct RunArgs {
 
 /// Run a CLI tool with the toolchain in the PATH.
 pub async fn run(args: RunArgs) -> Result<Never, CliError> {
-    let client = ToolchainClient::using_data_dir().await?;
-    let version = args
-        .toolchain
-        .or_else(|| client.active_toolchain())
-        .ok_or(CliError::NoToolchainEnabled)?;
+    let client = client_builder().product(args.product).build().await?;
+    let version = match args.toolchain {
+        Some(spec) => spec.resolve_one(&client, Lookup::Installed).await?.version,
+        None => effective_toolchain(args.product, client.active_toolchain())
+            .await?
+            .ok_or(CliError::NoToolchainEnabled)?,
+    };
 
-    let toolchain = client.toolchain(&version).await?;
+    let toolchain = installed_toolchain(&client, &version).await?;
 
-    let mut path = OsString::from(toolchain.host_bin_dir());
-    if let Some(old_path) = env::var_os("PATH") {
-        path.push(":");
-        path.push(old_path);
+    let config = Config::load().await?;
+    let policy = if args.isolate {
+        let mut keep = args.keep;
+        keep.extend(config.run.keep.iter().cloned());
+        EnvPolicy::Isolate { keep }
+    } else {
+        EnvPolicy::Inherit
+    };
+    let vars = command_env(
+        &policy,
+        &toolchain,
+        &version,
+        !args.no_cross_env,
+        &config.run,
+    )?;
+
+    if args.verbose {
+        for (name, value) in &vars {
+            eprintln!("{name}={}", value.to_string_lossy());
+        }
+    }
+
+    if let Some(record_path) = &args.emit_env_record {
+        let record = EnvRecord {
+            toolchain_version: version,
+            install_path: toolchain.path.clone(),
+            clang_version: clang_version(&toolchain.host_bin_dir()).await,
+            env: vars
+                .iter()
+                .map(|(name, value)| (name.clone(), value.to_string_lossy().into_owned()))
+                .collect(),
+        };
+
+        let json = serde_json::to_string_pretty(&record).expect("records are serializable");
+        fs::write(record_path, json).await?;
     }
 
     let mut cmd = Command::new(args.command);
     cmd.args(args.args);
-    cmd.env("PATH", path);
+    if policy != EnvPolicy::Inherit {
+        cmd.env_clear();
+    }
+    cmd.envs(vars);
+
+    // The check runs alongside the command and is abandoned if it's still running when the
+    // command exits, so it never delays it.
+    let update_check = if config.notify_updates && !args.quiet {
+        Some(UpdateCheck::start(&client).await)
+    } else {
+        None
+    };
 
-    if !args.no_cross_env {
-        cmd.env("TARGET_CC", "clang");
-        cmd.env("TARGET_AR", "llvm-ar");
+    let code = if args.time {
+        let (status, usage) = run_measured(cmd.as_std_mut()).await?;
+        match args.output {
+            OutputFormat::Human => msg!("Finished", "{usage}"),
+            OutputFormat::Json => eprintln!(
+                "{}",
+                serde_json::json!({ "exit_code": status.code(), "usage": usage })
+            ),
+        }
+        status.code()
+    } else {
+        cmd.spawn()?.wait().await?.code()
+    };
+
+    if let Some(update_check) = update_check
+        && let Some(notice) = update_check.finish().await
+    {
+        eprintln!("{notice}");
     }
 
-    let code = cmd.status().await?.code();
     exit(code.unwrap_or(1));
 }
+
+/// Which of this process's environment variables a command run with the toolchain inherits.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub enum EnvPolicy {
+    /// The command inherits every variable, and the toolchain is added to the front of the
+    /// existing `PATH`.
+    #[default]
+    Inherit,
+    /// The command only gets a minimal environment, plus the listed variables if they're set.
+    Isolate { keep: Vec<String> },
+}
+
+impl EnvPolicy {
+    /// Variables that most programs need to work, which are kept even when isolated.
+    const BASELINE: &[&str] = if cfg!(windows) {
+        &["USERPROFILE", "TEMP", "TMP", "SystemRoot"]
+    } else {
+        &["HOME", "TMPDIR"]
+    };
+}
+
+/// Returns the `PATH` entries of the system's own executables, which are used instead of the
+/// current `PATH` when the environment is isolated.
+fn system_path() -> OsString {
+    if cfg!(windows) {
+        let root = PathBuf::from(env::var_os("SystemRoot").unwrap_or_else(|| r"C:\Windows".into()));
+        let dirs = [
+            root.join("System32"),
+            root.clone(),
+            root.join(r"System32\Wbem"),
+        ];
+        env::join_paths(dirs).unwrap_or_default()
+    } else {
+        "/usr/local/bin:/usr/bin:/bin".into()
+    }
+}
+
+/// Assembles the environment variables to run a command with the given toolchain.
+///
+/// With [`EnvPolicy::Inherit`], these are the variables to set in addition to the inherited
+/// ones. With [`EnvPolicy::Isolate`], they are the command's entire environment. Variables
+/// from the `[run.env]` config section are added last, so they override everything else.
+pub fn command_env(
+    policy: &EnvPolicy,
+    toolchain: &InstalledToolchain,
+    version: &ToolchainVersion,
+    cross_env: bool,
+    config: &RunConfig,
+) -> Result<BTreeMap<String, OsString>, CliError> {
+    let mut vars = BTreeMap::new();
+
+    let mut path = OsString::from(toolchain.host_bin_dir());
+    match policy {
+        EnvPolicy::Inherit => {
+            if let Some(old_path) = env::var_os("PATH") {
+                path.push(PATH_SEPARATOR);
+                path.push(old_path);
+            }
+        }
+        EnvPolicy::Isolate { keep } => {
+            path.push(PATH_SEPARATOR);
+            path.push(system_path());
+
+            let kept = EnvPolicy::BASELINE
+                .iter()
+                .copied()
+                .chain(keep.iter().map(String::as_str));
+            for name in kept {
+                if let Some(value) = env::var_os(name) {
+                    vars.insert(name.to_string(), value);
+                }
+            }
+        }
+    }
+    vars.insert("PATH".to_string(), path);
+
+    if cross_env {
+        vars.insert("TARGET_CC".to_string(), "clang".into());
+        vars.insert("TARGET_AR".to_string(), "llvm-ar".into());
+    }
+
+    for (name, template) in &config.env {
+        let value = expand_template(name, template, toolchain, version)?;
+        vars.insert(name.clone(), value.into());
+    }
+
+    Ok(vars)
+}
+
+/// Which toolchain and environment a command was run with, written by `--emit-env-record`.
+#[derive(Debug, Serialize)]
+struct EnvRecord {
+    toolchain_version: ToolchainVersion,
+    install_path: PathBuf,
+    /// The first line of `clang --version`, if it could be run.
+    clang_version: Option<String>,
+    /// The environment variables that were set or changed for the command.
+    env: BTreeMap<String, String>,
+}
+
+/// Runs the toolchain's `clang --version` and returns the first line of its output.
+async fn clang_version(bin_dir: &Path) -> Option<String> {
+    let output = Command::new(bin_dir.join("clang"))
+        .arg("--version")
+        .output()
+        .await
+        .ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    stdout.lines().next().map(|line| line.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(config_env: &[(&str, &str)]) -> BTreeMap<String, OsString> {
+        let config = RunConfig {
+            env: config_env
+                .iter()
+                .map(|&(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            keep: vec![],
+        };
+        command_env(
+            &EnvPolicy::Isolate { keep: vec![] },
+            &InstalledToolchain::new(PathBuf::from("/opt/atfe")),
+            &ToolchainVersion::named("21.1.0"),
+            true,
+            &config,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn config_variables_are_added() {
+        let vars = env(&[("TOOLCHAIN_ROOT", "{install_dir}"), ("EXTRA", "-v")]);
+
+        assert_eq!(
+            vars["TOOLCHAIN_ROOT"],
+            PathBuf::from("/opt/atfe").into_os_string()
+        );
+        assert_eq!(vars["EXTRA"], "-v");
+        assert_eq!(vars["TARGET_CC"], "clang");
+        assert_eq!(vars["TARGET_AR"], "llvm-ar");
+        assert!(
+            vars["PATH"]
+                .to_string_lossy()
+                .starts_with(&*PathBuf::from("/opt/atfe/bin").to_string_lossy())
+        );
+    }
+
+    #[test]
+    fn config_variables_override_the_defaults() {
+        let vars = env(&[
+            ("TARGET_CC", "{bin_dir}/clang"),
+            ("PATH", "/only/here"),
+            ("HOME", "/elsewhere"),
+        ]);
+
+        assert_eq!(
+            vars["TARGET_CC"],
+            *format!("{}/clang", PathBuf::from("/opt/atfe/bin").display())
+        );
+        assert_eq!(vars["TARGET_AR"], "llvm-ar");
+        assert_eq!(vars["PATH"], "/only/here");
+        assert_eq!(vars["HOME"], "/elsewhere");
+    }
+
+    #[test]
+    fn isolated_environment_only_has_the_baseline_and_kept_variables() {
+        let vars = command_env(
+            &EnvPolicy::Isolate {
+                keep: vec![
+                    "CARGO_MANIFEST_DIR".to_string(),
+                    "ARM_TOOLCHAIN_TEST_UNSET".to_string(),
+                ],
+            },
+            &InstalledToolchain::new(PathBuf::from("/opt/atfe")),
+            &ToolchainVersion::named("21.1.0"),
+            false,
+            &RunConfig::default(),
+        )
+        .unwrap();
+
+        for name in vars.keys() {
+            assert!(
+                name == "PATH"
+                    || name == "CARGO_MANIFEST_DIR"
+                    || EnvPolicy::BASELINE.contains(&name.as_str()),
+                "{name}"
+            );
+        }
+        assert_eq!(vars["CARGO_MANIFEST_DIR"], env!("CARGO_MANIFEST_DIR"));
+        assert!(!vars.contains_key("ARM_TOOLCHAIN_TEST_UNSET"));
+        assert!(!vars.contains_key("TARGET_CC"));
+
+        let mut path = OsString::from(PathBuf::from("/opt/atfe/bin"));
+        path.push(PATH_SEPARATOR);
+        path.push(system_path());
+        assert_eq!(vars["PATH"], path);
+    }
+
+    #[test]
+    fn inherited_environment_only_has_the_changed_variables() {
+        let vars = command_env(
+            &EnvPolicy::Inherit,
+            &InstalledToolchain::new(PathBuf::from("/opt/atfe")),
+            &ToolchainVersion::named("21.1.0"),
+            true,
+            &RunConfig::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            vars.keys().collect::<Vec<_>>(),
+            ["PATH", "TARGET_AR", "TARGET_CC"]
+        );
+        let mut path = OsString::from(PathBuf::from("/opt/atfe/bin"));
+        if let Some(old_path) = env::var_os("PATH") {
+            path.push(PATH_SEPARATOR);
+            path.push(old_path);
+        }
+        assert_eq!(vars["PATH"], path);
+    }
+
+    #[test]
+    fn invalid_config_variables_are_an_error() {
+        let config = RunConfig {
+            env: [("TOOLCHAIN_ROOT".to_string(), "{root}".to_string())].into(),
+            keep: vec![],
+        };
+
+        let result = command_env(
+            &EnvPolicy::Inherit,
+            &InstalledToolchain::new(PathBuf::from("/opt/atfe")),
+            &ToolchainVersion::named("21.1.0"),
+            false,
+            &config,
+        );
+
+        assert!(matches!(result, Err(CliError::Template(_))), "{result:?}");
+    }
+}
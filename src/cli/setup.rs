@@ -0,0 +1,293 @@
+use std::{
+    collections::VecDeque,
+    env,
+    io::{self, IsTerminal},
+};
+
+use clap_complete::Shell;
+
+use crate::{
+    cli::{
+        CliError, Colorize, OutputFormat, UseArgs, VersionSpec, client_builder, completions_path,
+        confirm, msg, use_cmd, write_completions,
+    },
+    fs,
+    toolchain::Product,
+};
+
+/// The environment variable that answers to `setup`'s questions can be read from, instead of
+/// asking them in the terminal.
+///
+/// The answers are separated by commas, and each one is `yes` or `no`.
+const SETUP_ANSWERS_VAR: &str = "ARM_TOOLCHAIN_SETUP_ANSWERS";
+
+#[derive(Debug, clap::Parser)]
+pub struct SetupArgs {
+    /// Which toolchain to set up ("embedded" or "linux").
+    #[clap(long, default_value = "embedded")]
+    pub product: Product,
+    /// Fail instead of asking any questions.
+    ///
+    /// Setup only asks questions, so this always fails, pointing to the commands that do each
+    /// step instead.
+    #[clap(long)]
+    pub no_input: bool,
+}
+
+/// Answers the questions that [`setup`] asks.
+trait Prompt {
+    /// Asks a yes-or-no question.
+    async fn confirm(
+        &mut self,
+        message: &str,
+        help_message: &'static str,
+    ) -> Result<bool, CliError>;
+}
+
+/// Asks questions in the terminal.
+struct TerminalPrompt;
+
+impl Prompt for TerminalPrompt {
+    async fn confirm(
+        &mut self,
+        message: &str,
+        help_message: &'static str,
+    ) -> Result<bool, CliError> {
+        match confirm(message.to_string(), help_message, "").await {
+            Err(CliError::NonInteractive { .. }) => Err(CliError::SetupNotInteractive),
+            result => result,
+        }
+    }
+}
+
+/// Answers questions from a list, such as the one in [`SETUP_ANSWERS_VAR`].
+struct ScriptedPrompt {
+    answers: VecDeque<bool>,
+}
+
+impl ScriptedPrompt {
+    /// Reads a comma-separated list of answers. Anything other than `yes` or `y` means no.
+    fn parse(answers: &str) -> Self {
+        Self {
+            answers: answers
+                .split(',')
+                .map(str::trim)
+                .filter(|answer| !answer.is_empty())
+                .map(|answer| matches!(answer.to_ascii_lowercase().as_str(), "y" | "yes"))
+                .collect(),
+        }
+    }
+}
+
+impl Prompt for ScriptedPrompt {
+    async fn confirm(
+        &mut self,
+        message: &str,
+        _help_message: &'static str,
+    ) -> Result<bool, CliError> {
+        let answer = self
+            .answers
+            .pop_front()
+            .ok_or(CliError::SetupNotInteractive)?;
+        println!("{message} {}", if answer { "yes" } else { "no" });
+        Ok(answer)
+    }
+}
+
+/// Walks a new user through installing and activating a toolchain, pinning it for the current
+/// project, installing shell completions, and adding it to their shell's `PATH`.
+///
+/// Every step can be skipped by answering no. Each step runs the same code as the command that
+/// does it on its own, and those commands are listed at the end.
+pub async fn setup(args: SetupArgs) -> Result<(), CliError> {
+    if args.no_input {
+        return Err(CliError::SetupNotInteractive);
+    }
+
+    match env::var(SETUP_ANSWERS_VAR) {
+        Ok(answers) => run_setup(args, &mut ScriptedPrompt::parse(&answers)).await,
+        Err(_) if !io::stdin().is_terminal() => Err(CliError::SetupNotInteractive),
+        Err(_) => run_setup(args, &mut TerminalPrompt).await,
+    }
+}
+
+async fn run_setup(args: SetupArgs, prompt: &mut impl Prompt) -> Result<(), CliError> {
+    let client = client_builder().product(args.product).build().await?;
+
+    let mut installed = client.installed_versions().await?;
+    installed.sort();
+    let active = client.active_toolchain();
+
+    if installed.is_empty() {
+        println!("{} isn't installed yet.", args.product.display_name);
+    } else {
+        let names = installed
+            .iter()
+            .map(|version| version.to_string())
+            .collect::<Vec<_>>();
+        println!("Installed toolchains: {}", names.join(", "));
+        match &active {
+            Some(active) => println!("Active toolchain: {active}"),
+            None => println!("No toolchain is active."),
+        }
+    }
+    println!();
+
+    // Non-default products need to be passed to the commands that are listed.
+    let product_flag = if args.product == Product::default() {
+        String::new()
+    } else {
+        format!(" --product {}", args.product)
+    };
+    let mut commands = vec![];
+    let mut version = active;
+
+    let question = match &version {
+        Some(_) => "Install and activate the latest toolchain?",
+        None => "Install and activate the latest toolchain? (recommended)",
+    };
+    if prompt
+        .confirm(
+            question,
+            "The toolchain is downloaded from GitHub and becomes the default for every project.",
+        )
+        .await?
+    {
+        let report = use_cmd(UseArgs {
+            llvm_version: VersionSpec::Latest,
+            product: args.product,
+            yes: true,
+            local: false,
+            path: None,
+            dry_run: false,
+            refresh: false,
+            pre: false,
+            output: OutputFormat::Human,
+        })
+        .await?;
+
+        commands.push(format!("arm-toolchain use latest{product_flag}"));
+        version = Some(report.version);
+    }
+
+    if let Some(version) = &version {
+        let dir = env::current_dir()?;
+        let question = format!("Pin {version} for the project in {}?", dir.display());
+        if prompt
+            .confirm(
+                &question,
+                "This writes an `arm-toolchain.toml` file, so that `run` always uses this toolchain in the project.",
+            )
+            .await?
+        {
+            use_cmd(UseArgs {
+                llvm_version: VersionSpec::Exact(version.clone()),
+                product: args.product,
+                yes: true,
+                local: true,
+                path: Some(dir),
+                dry_run: false,
+                refresh: false,
+                pre: false,
+                output: OutputFormat::Human,
+            })
+            .await?;
+
+            commands.push(format!("arm-toolchain use {version} --local{product_flag}"));
+        }
+    }
+
+    let shell = Shell::from_env().unwrap_or(Shell::Bash);
+    if prompt
+        .confirm(
+            &format!("Install completions for {shell}?"),
+            "This lets your shell complete `arm-toolchain` commands and flags when you press Tab.",
+        )
+        .await?
+    {
+        match completions_path(shell) {
+            Some(path) => {
+                let mut script = vec![];
+                write_completions(shell, &mut script);
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).await?;
+                }
+                fs::write(&path, script).await?;
+
+                msg!("Installed", "completions for {shell} to {}", path.display());
+                commands.push(format!(
+                    "arm-toolchain completions {shell} > {}",
+                    path.display()
+                ));
+                if shell == Shell::Zsh {
+                    print_startup_lines(
+                        shell,
+                        &["fpath+=(~/.zfunc)", "autoload -Uz compinit && compinit"],
+                    );
+                }
+            }
+            None => print_startup_lines(shell, &[&load_completions_line(shell)]),
+        }
+    }
+
+    if prompt
+        .confirm(
+            "Show how to add the active toolchain to your shell's PATH?",
+            "Otherwise, prefix commands with `arm-toolchain run` to use the toolchain.",
+        )
+        .await?
+    {
+        print_startup_lines(shell, &[&path_line(shell, &product_flag)]);
+    }
+
+    println!();
+    if commands.is_empty() {
+        println!("Nothing was changed. Run `arm-toolchain setup` again at any time.");
+    } else {
+        println!("{}", "Setup complete. These commands were run:".bold());
+        for command in commands {
+            println!("    {command}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints lines for the user to add to their shell's startup file.
+fn print_startup_lines(shell: Shell, lines: &[&str]) {
+    let file = match shell {
+        Shell::Fish => "~/.config/fish/config.fish",
+        Shell::Zsh => "~/.zshrc",
+        Shell::PowerShell => "your PowerShell profile ($PROFILE)",
+        Shell::Elvish => "~/.config/elvish/rc.elv",
+        _ => "~/.bashrc",
+    };
+
+    println!();
+    println!("Add this to {file}:");
+    println!();
+    for line in lines {
+        println!("    {line}");
+    }
+}
+
+/// Returns a line for a shell's startup file that loads the completion script, for shells
+/// that [`completions_path`] has no file for.
+fn load_completions_line(shell: Shell) -> String {
+    match shell {
+        Shell::Elvish => "eval (arm-toolchain completions elvish | slurp)".to_string(),
+        _ => format!("arm-toolchain completions {shell} | Out-String | Invoke-Expression"),
+    }
+}
+
+/// Returns a line for a shell's startup file that puts the active toolchain's executables in
+/// the `PATH`.
+fn path_line(shell: Shell, product_flag: &str) -> String {
+    let locate = format!("arm-toolchain locate{product_flag} bin");
+    match shell {
+        Shell::PowerShell => format!("$env:PATH = \"$({locate});$env:PATH\""),
+        Shell::Fish => format!("set -gx PATH ({locate}) $PATH"),
+        Shell::Elvish => format!("set paths = [({locate}) $@paths]"),
+        _ => format!("export PATH=\"$({locate}):$PATH\""),
+    }
+}
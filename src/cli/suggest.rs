@@ -0,0 +1,126 @@
+//! Suggestions for versions that were probably meant when a toolchain isn't installed, such as
+//! `20.1.0` for a mistyped `20.0.1`.
+
+use reqwest::StatusCode;
+use tracing::debug;
+
+use crate::{
+    cli::CliError,
+    toolchain::{InstalledToolchain, ToolchainClient, ToolchainError, ToolchainVersion},
+};
+
+/// The most suggestions that are offered at once.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Returns the number of single-character insertions, deletions, substitutions, or swaps of
+/// adjacent characters needed to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    // Each row holds the distances from a prefix of `a` to every prefix of `b`.
+    let mut before_previous = vec![0; b.len() + 1];
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut current = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+            // Swapped digits (`20.0.1` for `20.1.0`) are a common typo.
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(before_previous[j - 2] + 1);
+            }
+        }
+        before_previous = previous;
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// Returns the candidates that are close enough to `version` to have been what was meant,
+/// closest (then newest) first.
+pub fn closest_versions(
+    version: &ToolchainVersion,
+    candidates: impl IntoIterator<Item = ToolchainVersion>,
+) -> Vec<ToolchainVersion> {
+    let name = version.name.to_ascii_lowercase();
+    // Short names only tolerate one mistake, so that `19` doesn't suggest `21`.
+    let max_distance = (name.chars().count() / 3).max(1);
+
+    let mut close: Vec<(usize, ToolchainVersion)> = candidates
+        .into_iter()
+        .filter(|candidate| candidate != version)
+        .filter_map(|candidate| {
+            let distance = edit_distance(&name, &candidate.name.to_ascii_lowercase());
+            (distance <= max_distance).then_some((distance, candidate))
+        })
+        .collect();
+    close.sort_by(|(a_distance, a), (b_distance, b)| a_distance.cmp(b_distance).then(b.cmp(a)));
+    close.dedup_by(|(_, a), (_, b)| a == b);
+
+    close
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, version)| version)
+        .collect()
+}
+
+/// Creates a [`CliError::ToolchainNotInstalled`] for `version`, suggesting the installed
+/// toolchains whose names are close to it.
+pub async fn not_installed(client: &ToolchainClient, version: ToolchainVersion) -> CliError {
+    let installed = client.installed_versions().await.unwrap_or_default();
+    let suggestions = closest_versions(&version, installed);
+    CliError::ToolchainNotInstalled {
+        version,
+        suggestions,
+    }
+}
+
+/// Returns the installed toolchain of the given version, or a
+/// [`CliError::ToolchainNotInstalled`] with suggestions if it isn't installed.
+pub async fn installed_toolchain(
+    client: &ToolchainClient,
+    version: &ToolchainVersion,
+) -> Result<InstalledToolchain, CliError> {
+    match client.toolchain(version).await {
+        Ok(toolchain) => Ok(toolchain),
+        Err(ToolchainError::ToolchainNotInstalled { .. }) => {
+            Err(not_installed(client, version.clone()).await)
+        }
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Returns whether GitHub reported that the requested release doesn't exist.
+pub fn is_missing_release(error: &ToolchainError) -> bool {
+    matches!(
+        error,
+        ToolchainError::GitHubApi(octocrab::Error::GitHub { source, .. })
+            if source.status_code == StatusCode::NOT_FOUND
+    )
+}
+
+/// Creates a [`CliError::ToolchainNotInstalled`] for a version that has no release, suggesting
+/// both installed toolchains and releases whose names are close to it.
+///
+/// Releases are only suggested if the client is online, and they can be fetched.
+pub async fn missing_release(client: &ToolchainClient, version: ToolchainVersion) -> CliError {
+    let mut candidates = client.installed_versions().await.unwrap_or_default();
+    if !client.is_offline() {
+        match client.list_releases(None, true).await {
+            Ok(releases) => {
+                candidates.extend(releases.iter().map(|release| release.version().clone()));
+            }
+            Err(error) => debug!(%error, "Couldn't fetch releases to suggest"),
+        }
+    }
+
+    let suggestions = closest_versions(&version, candidates);
+    CliError::ToolchainNotInstalled {
+        version,
+        suggestions,
+    }
+}
@@ -0,0 +1,145 @@
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::{
+    cli::{
+        CliError, Colorize, Lookup, OutputFormat, VersionSpec, client_builder, effective_toolchain,
+        installed_spec, installed_toolchain,
+    },
+    toolchain::{FloatAbi, MultilibVariant, Product, TargetSpec, ToolchainVersion},
+};
+
+/// Configuration for [`targets`].
+#[derive(Debug, clap::Parser)]
+pub struct TargetsArgs {
+    /// The toolchain whose targets should be listed: a version, "previous", or an alias from
+    /// the config file (default: the pinned or active version).
+    #[arg(short = 'T', long, value_parser = installed_spec)]
+    pub toolchain: Option<VersionSpec>,
+    /// Which toolchain to use ("embedded" or "linux").
+    #[arg(long, default_value = "embedded")]
+    pub product: Product,
+    /// Print the runtime variant that clang would use for a CPU, instead of listing every
+    /// variant.
+    #[arg(long, requires = "cpu")]
+    pub print_variant_for: bool,
+    /// The CPU to find a variant for, such as `cortex-m4`.
+    #[arg(long, requires = "print_variant_for")]
+    pub cpu: Option<String>,
+    /// The float ABI to find a variant for.
+    #[arg(long, default_value = "soft", requires = "print_variant_for")]
+    pub float_abi: FloatAbi,
+    /// The FPU to find a variant for, such as `fpv4-sp-d16` (default: the CPU's usual FPU).
+    #[arg(long, requires = "print_variant_for")]
+    pub fpu: Option<String>,
+    /// How the result should be reported.
+    #[arg(long, default_value = "human")]
+    pub output: OutputFormat,
+}
+
+/// The result of [`targets`].
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum TargetsReport {
+    /// Every variant in the toolchain's multilib.
+    Variants {
+        version: ToolchainVersion,
+        variants: Vec<MultilibVariant>,
+    },
+    /// The variant selected for a CPU.
+    Selected(SelectedVariant),
+}
+
+/// The variant selected by `targets --print-variant-for`.
+#[derive(Debug, Serialize)]
+pub struct SelectedVariant {
+    /// The version of the toolchain the variant is from.
+    pub version: ToolchainVersion,
+    /// The flags clang matches against the multilib for the requested target.
+    pub flags: Vec<String>,
+    /// The variant's triple, such as `arm-none-eabi`.
+    pub triple: String,
+    /// The variant's name, such as `armv7em_hard_fpv4_sp_d16`.
+    pub variant: String,
+    /// The variant's `lib` directory.
+    pub lib_dir: PathBuf,
+    /// The variant's header directories.
+    pub include_dirs: Vec<PathBuf>,
+}
+
+/// List the runtime library variants of a toolchain, or find the one clang would use for a
+/// CPU.
+pub async fn targets(args: TargetsArgs) -> Result<TargetsReport, CliError> {
+    let client = client_builder().product(args.product).build().await?;
+    let version = match args.toolchain {
+        Some(spec) => spec.resolve_one(&client, Lookup::Installed).await?.version,
+        None => effective_toolchain(args.product, client.active_toolchain())
+            .await?
+            .ok_or(CliError::NoToolchainEnabled)?,
+    };
+    let toolchain = installed_toolchain(&client, &version).await?;
+    let multilib = toolchain.multilib().await?;
+
+    let Some(cpu) = args.cpu.filter(|_| args.print_variant_for) else {
+        for variant in multilib.variants() {
+            let Some(dir) = &variant.dir else {
+                continue;
+            };
+            args.output
+                .message(format_args!("{} {}", dir.bold(), variant.flags.join(" ")));
+        }
+
+        return Ok(TargetsReport::Variants {
+            version,
+            variants: multilib.variants().to_vec(),
+        });
+    };
+
+    let target =
+        TargetSpec::new(&cpu, args.float_abi, args.fpu).ok_or_else(|| CliError::UnknownCpu {
+            cpu: cpu.clone(),
+            supported: multilib.supported_cpus(),
+        })?;
+
+    let flags = target.multilib_flags();
+    let selection = multilib.select(&flags);
+    if let Some(error) = selection.error() {
+        return Err(CliError::NoRuntimeForTarget {
+            target: target.to_string(),
+            flags,
+            reason: Some(error.to_string()),
+        });
+    }
+    let Some((triple, variant)) = selection.primary().and_then(|v| v.triple_and_name()) else {
+        return Err(CliError::NoRuntimeForTarget {
+            target: target.to_string(),
+            flags,
+            reason: None,
+        });
+    };
+
+    let lib_dir = toolchain.target_lib_dir(triple, variant);
+    let include_dirs = toolchain.target_include_dirs(triple, variant);
+
+    args.output
+        .message(format_args!("Variant: {}", variant.bold()));
+    args.output.message(format_args!("Triple: {triple}"));
+    args.output
+        .message(format_args!("Flags: {}", flags.join(" ")));
+    args.output
+        .message(format_args!("Lib: {}", lib_dir.display()));
+    for dir in &include_dirs {
+        args.output
+            .message(format_args!("Include: {}", dir.display()));
+    }
+
+    Ok(TargetsReport::Selected(SelectedVariant {
+        version,
+        flags,
+        triple: triple.to_string(),
+        variant: variant.to_string(),
+        lib_dir,
+        include_dirs,
+    }))
+}
@@ -0,0 +1,206 @@
+use miette::Diagnostic;
+use thiserror::Error;
+
+use crate::toolchain::{InstalledToolchain, ToolchainVersion};
+
+/// The placeholders that can be used in the values of `[run.env]` variables.
+pub const PLACEHOLDERS: &[&str] = &[
+    "{install_dir}",
+    "{bin_dir}",
+    "{lib_dir}",
+    "{multilib_dir}",
+    "{version}",
+    "{sysroot:<triple>:<variant>}",
+    "{target_lib_dir:<triple>:<variant>}",
+];
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("Invalid placeholder {placeholder:?} in the value of the {variable} environment variable")]
+#[diagnostic(code(arm_toolchain::cli::invalid_env_template))]
+#[diagnostic(help(
+    "Valid placeholders are: {}. Use `{{{{` and `}}}}` for literal braces.",
+    PLACEHOLDERS.join(", ")
+))]
+pub struct TemplateError {
+    variable: String,
+    placeholder: String,
+}
+
+/// Replaces the placeholders in the value of an environment variable set by the
+/// `[run.env]` config section with paths from the given toolchain.
+///
+/// See [`PLACEHOLDERS`] for the supported placeholders. `{{` and `}}` are replaced with
+/// literal braces.
+pub fn expand_template(
+    variable: &str,
+    template: &str,
+    toolchain: &InstalledToolchain,
+    version: &ToolchainVersion,
+) -> Result<String, TemplateError> {
+    let error = |placeholder: &str| TemplateError {
+        variable: variable.to_string(),
+        placeholder: placeholder.to_string(),
+    };
+
+    let mut expanded = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find(['{', '}']) {
+        expanded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if let Some(after) = rest.strip_prefix("{{") {
+            expanded.push('{');
+            rest = after;
+            continue;
+        }
+        if let Some(after) = rest.strip_prefix("}}") {
+            expanded.push('}');
+            rest = after;
+            continue;
+        }
+        if rest.starts_with('}') {
+            return Err(error("}"));
+        }
+
+        let Some(end) = rest.find('}') else {
+            return Err(error(rest));
+        };
+        let placeholder = &rest[1..end];
+
+        let mut parts = placeholder.split(':');
+        let name = parts.next().unwrap_or_default();
+        let args: Vec<&str> = parts.collect();
+
+        let value = match (name, args.as_slice()) {
+            ("install_dir", []) => toolchain.path.display().to_string(),
+            ("bin_dir", []) => toolchain.host_bin_dir().display().to_string(),
+            ("lib_dir", []) => toolchain.lib_dir().display().to_string(),
+            ("multilib_dir", []) => toolchain.multilib_dir().display().to_string(),
+            ("version", []) => version.name.clone(),
+            ("sysroot", [triple, variant]) if !triple.is_empty() && !variant.is_empty() => {
+                toolchain.sysroot(triple, variant).display().to_string()
+            }
+            ("target_lib_dir", [triple, variant]) if !triple.is_empty() && !variant.is_empty() => {
+                toolchain
+                    .target_lib_dir(triple, variant)
+                    .display()
+                    .to_string()
+            }
+            _ => return Err(error(&rest[..=end])),
+        };
+
+        expanded.push_str(&value);
+        rest = &rest[end + 1..];
+    }
+
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn expand(template: &str) -> Result<String, TemplateError> {
+        let toolchain = InstalledToolchain::new(PathBuf::from("/opt/atfe"));
+        expand_template(
+            "VAR",
+            template,
+            &toolchain,
+            &ToolchainVersion::named("21.1.0"),
+        )
+    }
+
+    fn path(path: &str) -> String {
+        PathBuf::from(path).display().to_string()
+    }
+
+    #[test]
+    fn replaces_each_placeholder() {
+        let toolchain = InstalledToolchain::new(PathBuf::from("/opt/atfe"));
+        for (template, expected) in [
+            ("{install_dir}", path("/opt/atfe")),
+            ("{bin_dir}", toolchain.host_bin_dir().display().to_string()),
+            ("{lib_dir}", toolchain.lib_dir().display().to_string()),
+            (
+                "{multilib_dir}",
+                toolchain.multilib_dir().display().to_string(),
+            ),
+            ("{version}", "21.1.0".to_string()),
+            (
+                "{sysroot:arm-none-eabi:armv7a_soft_nofp}",
+                toolchain
+                    .sysroot("arm-none-eabi", "armv7a_soft_nofp")
+                    .display()
+                    .to_string(),
+            ),
+            (
+                "{target_lib_dir:arm-none-eabi:armv7a_soft_nofp}",
+                toolchain
+                    .target_lib_dir("arm-none-eabi", "armv7a_soft_nofp")
+                    .display()
+                    .to_string(),
+            ),
+        ] {
+            assert_eq!(expand(template).unwrap(), expected, "{template}");
+        }
+    }
+
+    #[test]
+    fn keeps_the_text_around_placeholders() {
+        assert_eq!(expand("").unwrap(), "");
+        assert_eq!(expand("-O2 -g").unwrap(), "-O2 -g");
+        assert_eq!(
+            expand("--sysroot={sysroot:arm-none-eabi:v7} -v{version}!").unwrap(),
+            format!(
+                "--sysroot={} -v21.1.0!",
+                path("/opt/atfe/lib/clang-runtimes/arm-none-eabi/v7")
+            )
+        );
+        assert_eq!(expand("{version}{version}").unwrap(), "21.1.021.1.0");
+    }
+
+    #[test]
+    fn doubled_braces_are_literal() {
+        assert_eq!(expand("{{version}}").unwrap(), "{version}");
+        assert_eq!(expand("{{{version}}}").unwrap(), "{21.1.0}");
+        assert_eq!(expand("a}}b{{c").unwrap(), "a}b{c");
+    }
+
+    #[test]
+    fn rejects_invalid_placeholders() {
+        for (template, placeholder) in [
+            ("{home}", "{home}"),
+            ("x{install_dir:extra}", "{install_dir:extra}"),
+            ("{sysroot}", "{sysroot}"),
+            ("{sysroot:arm-none-eabi}", "{sysroot:arm-none-eabi}"),
+            ("{sysroot::v7}", "{sysroot::v7}"),
+            ("{sysroot:a:b:c}", "{sysroot:a:b:c}"),
+            ("{}", "{}"),
+            ("-I{bin_dir", "{bin_dir"),
+            ("a}b", "}"),
+        ] {
+            let error = expand(template).unwrap_err();
+            assert_eq!(error.placeholder, placeholder, "{template}");
+            assert_eq!(error.variable, "VAR");
+        }
+    }
+
+    #[test]
+    fn lists_the_valid_placeholders() {
+        let error = expand("{home}").unwrap_err();
+
+        let help = error.help().unwrap().to_string();
+
+        for placeholder in PLACEHOLDERS {
+            assert!(help.contains(placeholder), "{help}");
+        }
+        assert_eq!(
+            error.to_string(),
+            "Invalid placeholder \"{home}\" in the value of the VAR environment variable"
+        );
+    }
+}
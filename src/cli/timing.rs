@@ -0,0 +1,315 @@
+//! Measuring how long a command took and how much it used, for `run --time`.
+
+use std::{
+    fmt::{self, Display},
+    io,
+    process::{Child, Command, ExitStatus},
+    time::{Duration, Instant},
+};
+
+use serde::{Serialize, Serializer};
+
+/// The resources that a command used while it ran.
+///
+/// CPU times and peak memory are only measured on Unix and Windows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ResourceUsage {
+    /// How long the command ran for.
+    #[serde(rename = "wall_time_secs", serialize_with = "as_secs")]
+    pub wall_time: Duration,
+    /// CPU time spent running the command's own code.
+    #[serde(rename = "user_time_secs", serialize_with = "as_secs_opt")]
+    pub user_time: Option<Duration>,
+    /// CPU time the system spent on the command's behalf.
+    #[serde(rename = "system_time_secs", serialize_with = "as_secs_opt")]
+    pub system_time: Option<Duration>,
+    /// The most memory the command had resident at once, in bytes.
+    pub max_rss_bytes: Option<u64>,
+}
+
+fn as_secs<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+fn as_secs_opt<S: Serializer>(
+    duration: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match duration {
+        Some(duration) => as_secs(duration, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+impl Display for ResourceUsage {
+    /// Formats the usage on one line, such as `1.204s wall, 0.981s user, 0.113s system,
+    /// 58.2 MB max RSS`. Measurements that aren't available are left out.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} wall", format_duration(self.wall_time))?;
+        if let Some(user_time) = self.user_time {
+            write!(f, ", {} user", format_duration(user_time))?;
+        }
+        if let Some(system_time) = self.system_time {
+            write!(f, ", {} system", format_duration(system_time))?;
+        }
+        if let Some(max_rss) = self.max_rss_bytes {
+            write!(
+                f,
+                ", {} max RSS",
+                humansize::format_size(max_rss, humansize::DECIMAL)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats a duration with millisecond precision, such as `1.204s` or `2m03.500s`.
+fn format_duration(duration: Duration) -> String {
+    let millis = duration.as_millis();
+    let (minutes, millis) = (millis / 60_000, millis % 60_000);
+    let (secs, millis) = (millis / 1000, millis % 1000);
+
+    if minutes > 0 {
+        format!("{minutes}m{secs:02}.{millis:03}s")
+    } else {
+        format!("{secs}.{millis:03}s")
+    }
+}
+
+/// Runs a command to completion, and returns its exit status along with the resources it
+/// used.
+///
+/// The command is waited for on a blocking thread, since the CPU times and peak memory can
+/// only be collected when reaping the process (or right after, on Windows).
+pub async fn run_measured(command: &mut Command) -> io::Result<(ExitStatus, ResourceUsage)> {
+    let started_at = Instant::now();
+    let child = command.spawn()?;
+
+    tokio::task::spawn_blocking(move || {
+        let (status, mut usage) = wait_with_usage(child)?;
+        usage.wall_time = started_at.elapsed();
+        Ok((status, usage))
+    })
+    .await
+    .unwrap()
+}
+
+/// Waits for a child to exit, collecting its resource usage with `wait4`.
+#[cfg(unix)]
+fn wait_with_usage(child: Child) -> io::Result<(ExitStatus, ResourceUsage)> {
+    use std::{mem::MaybeUninit, os::unix::process::ExitStatusExt};
+
+    let pid = child.id() as libc::pid_t;
+    let mut status = 0;
+    let mut rusage = MaybeUninit::<libc::rusage>::uninit();
+
+    // SAFETY: `pid` is a child of this process that hasn't been reaped, since `Child` only
+    // reaps it when waited on, and `rusage` is only read if the call succeeds.
+    let rusage = loop {
+        if unsafe { libc::wait4(pid, &mut status, 0, rusage.as_mut_ptr()) } != -1 {
+            break unsafe { rusage.assume_init() };
+        }
+        let error = io::Error::last_os_error();
+        if error.kind() != io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    };
+
+    let to_duration = |time: libc::timeval| {
+        Duration::from_secs(time.tv_sec as u64) + Duration::from_micros(time.tv_usec as u64)
+    };
+    // Linux reports the peak in kibibytes, but macOS reports it in bytes.
+    let rss_unit = if cfg!(target_os = "macos") { 1 } else { 1024 };
+
+    Ok((
+        ExitStatus::from_raw(status),
+        ResourceUsage {
+            wall_time: Duration::ZERO,
+            user_time: Some(to_duration(rusage.ru_utime)),
+            system_time: Some(to_duration(rusage.ru_stime)),
+            max_rss_bytes: Some(rusage.ru_maxrss as u64 * rss_unit),
+        },
+    ))
+}
+
+/// Waits for a child to exit, and then collects its resource usage from its process handle,
+/// which stays valid until the `Child` is dropped.
+#[cfg(windows)]
+fn wait_with_usage(mut child: Child) -> io::Result<(ExitStatus, ResourceUsage)> {
+    use std::{ffi::c_void, os::windows::io::AsRawHandle};
+
+    /// `PROCESS_MEMORY_COUNTERS` from `psapi.h`.
+    #[repr(C)]
+    #[derive(Default)]
+    #[allow(dead_code, reason = "only the peak working set is read")]
+    struct ProcessMemoryCounters {
+        cb: u32,
+        page_fault_count: u32,
+        peak_working_set_size: usize,
+        working_set_size: usize,
+        quota_peak_paged_pool_usage: usize,
+        quota_paged_pool_usage: usize,
+        quota_peak_non_paged_pool_usage: usize,
+        quota_non_paged_pool_usage: usize,
+        pagefile_usage: usize,
+        peak_pagefile_usage: usize,
+    }
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn GetProcessTimes(
+            process: *mut c_void,
+            creation_time: *mut u64,
+            exit_time: *mut u64,
+            kernel_time: *mut u64,
+            user_time: *mut u64,
+        ) -> i32;
+        fn K32GetProcessMemoryInfo(
+            process: *mut c_void,
+            counters: *mut ProcessMemoryCounters,
+            size: u32,
+        ) -> i32;
+    }
+
+    let status = child.wait()?;
+    let handle = child.as_raw_handle();
+
+    let (mut creation_time, mut exit_time, mut kernel_time, mut user_time) = (0, 0, 0, 0);
+    // SAFETY: `handle` is a valid process handle, and each `FILETIME` is a little-endian
+    // 64-bit count that fits in a `u64`.
+    let has_times = unsafe {
+        GetProcessTimes(
+            handle,
+            &mut creation_time,
+            &mut exit_time,
+            &mut kernel_time,
+            &mut user_time,
+        )
+    } != 0;
+    // `FILETIME`s count in units of 100 nanoseconds.
+    let to_duration = |time: u64| Duration::from_nanos(time * 100);
+
+    let size = size_of::<ProcessMemoryCounters>() as u32;
+    let mut counters = ProcessMemoryCounters {
+        cb: size,
+        ..Default::default()
+    };
+    // SAFETY: `handle` is a valid process handle, and `counters` is `size` bytes long.
+    let has_memory = unsafe { K32GetProcessMemoryInfo(handle, &mut counters, size) } != 0;
+
+    Ok((
+        status,
+        ResourceUsage {
+            wall_time: Duration::ZERO,
+            user_time: has_times.then(|| to_duration(user_time)),
+            system_time: has_times.then(|| to_duration(kernel_time)),
+            max_rss_bytes: has_memory.then_some(counters.peak_working_set_size as u64),
+        },
+    ))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn wait_with_usage(mut child: Child) -> io::Result<(ExitStatus, ResourceUsage)> {
+    let status = child.wait()?;
+    Ok((
+        status,
+        ResourceUsage {
+            wall_time: Duration::ZERO,
+            user_time: None,
+            system_time: None,
+            max_rss_bytes: None,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_have_millisecond_precision() {
+        for (duration, expected) in [
+            (Duration::ZERO, "0.000s"),
+            (Duration::from_micros(1_204_999), "1.204s"),
+            (Duration::from_millis(59_999), "59.999s"),
+            (Duration::from_millis(60_000), "1m00.000s"),
+            (Duration::from_millis(123_500), "2m03.500s"),
+        ] {
+            assert_eq!(format_duration(duration), expected, "{duration:?}");
+        }
+    }
+
+    #[test]
+    fn usage_is_formatted_on_one_line() {
+        let usage = ResourceUsage {
+            wall_time: Duration::from_millis(1204),
+            user_time: Some(Duration::from_millis(981)),
+            system_time: Some(Duration::from_millis(113)),
+            max_rss_bytes: Some(58_200_000),
+        };
+
+        assert_eq!(
+            usage.to_string(),
+            "1.204s wall, 0.981s user, 0.113s system, 58.20 MB max RSS"
+        );
+    }
+
+    #[test]
+    fn missing_measurements_are_left_out() {
+        let usage = ResourceUsage {
+            wall_time: Duration::from_millis(1204),
+            user_time: None,
+            system_time: None,
+            max_rss_bytes: None,
+        };
+
+        assert_eq!(usage.to_string(), "1.204s wall");
+        assert_eq!(
+            serde_json::to_value(usage).unwrap(),
+            serde_json::json!({
+                "wall_time_secs": 1.204,
+                "user_time_secs": null,
+                "system_time_secs": null,
+                "max_rss_bytes": null,
+            })
+        );
+    }
+
+    #[test]
+    fn usage_is_serialized_in_seconds() {
+        let usage = ResourceUsage {
+            wall_time: Duration::from_millis(1500),
+            user_time: Some(Duration::from_millis(250)),
+            system_time: Some(Duration::from_millis(125)),
+            max_rss_bytes: Some(4096),
+        };
+
+        assert_eq!(
+            serde_json::to_value(usage).unwrap(),
+            serde_json::json!({
+                "wall_time_secs": 1.5,
+                "user_time_secs": 0.25,
+                "system_time_secs": 0.125,
+                "max_rss_bytes": 4096,
+            })
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn busy_command_is_measured() {
+        let mut command = Command::new("sh");
+        command.args([
+            "-c",
+            "i=0; while [ $i -lt 200000 ]; do i=$((i + 1)); done; exit 3",
+        ]);
+
+        let (status, usage) = run_measured(&mut command).await.unwrap();
+
+        assert_eq!(status.code(), Some(3));
+        let cpu_time = usage.user_time.unwrap() + usage.system_time.unwrap();
+        assert!(cpu_time > Duration::ZERO, "{usage:?}");
+        assert!(usage.wall_time >= cpu_time / 2, "{usage:?}");
+        assert!(usage.max_rss_bytes.unwrap() > 0, "{usage:?}");
+    }
+}
@@ -0,0 +1,507 @@
+use std::{fs::File, path::PathBuf, time::Duration};
+
+use tokio::time::sleep;
+use tokio_util::{future::FutureExt, sync::CancellationToken};
+use tracing::debug;
+
+use crate::{
+    cli::{
+        CliError, Colorize, Config, HostArgs, client_builder, confirm_install, ctrl_c_cancel,
+        install_with_progress_bar, msg, plan_for_summary, record_install_reason, write_metrics,
+    },
+    toolchain::{
+        ArchPreference, Product, ToolchainClient, ToolchainError, ToolchainVersion,
+        file_lock::try_lock_file,
+    },
+};
+
+/// The install reason recorded for toolchains installed by `update`.
+const UPDATE_REASON: &str = "cli: update";
+/// How long the daemon waits before checking again after the first failed check. The wait
+/// doubles after each failure, up to the update interval.
+const MIN_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Configuration for [`update`].
+#[derive(Debug, clap::Parser)]
+pub struct UpdateArgs {
+    /// Only update within this major version (e.g. `19` to get the newest 19.x.x release).
+    #[clap(long)]
+    pub major: Option<u64>,
+    /// Which toolchain to update ("embedded" or "linux").
+    #[clap(long, default_value = "embedded")]
+    pub product: Product,
+    /// Don't ask for confirmation before installing.
+    #[clap(long, short)]
+    pub yes: bool,
+    /// Keep running and check for updates on an interval, installing them without asking.
+    ///
+    /// Stops when it receives Ctrl-C or SIGTERM. Only one daemon can run for each product.
+    #[clap(long, conflicts_with = "once")]
+    pub daemon: bool,
+    /// How often the daemon checks for updates, such as `30m`, `12h`, or `1d`.
+    #[clap(long, default_value = "24h", value_parser = parse_interval, requires = "daemon")]
+    pub interval: Duration,
+    /// Check for updates once without asking for confirmation, like a single check of
+    /// `--daemon`. Useful for running from cron.
+    #[clap(long)]
+    pub once: bool,
+    /// Ask GitHub whether the releases changed, instead of using release information that
+    /// was fetched in the last 15 minutes.
+    #[clap(long)]
+    pub refresh: bool,
+    /// Update to pre-releases, such as release candidates, as well as releases.
+    #[clap(long)]
+    pub pre: bool,
+}
+
+/// Install the newest toolchain (within a major version, if specified) and activate it if
+/// it replaces the active toolchain.
+pub async fn update(args: UpdateArgs) -> Result<(), CliError> {
+    let config = Config::load().await?;
+    let client = client_builder()
+        .permission_policy(config.permissions.unwrap_or_default())
+        .checksum_file(config.checksum_file.clone())
+        .product(args.product)
+        .refresh_releases(args.refresh)
+        .include_prereleases(args.pre)
+        .build()
+        .await?;
+
+    if args.daemon {
+        return run_daemon(&client, &args, &config).await;
+    }
+
+    let token = ctrl_c_cancel();
+    let result = if args.once {
+        update_unattended(&client, &args, &config, &token).await
+    } else {
+        update_to_newest(&client, &args, &config, false, &token)
+            .await
+            .map(drop)
+    };
+
+    // Release Ctrl-C listener
+    token.cancel();
+    result
+}
+
+/// Installs and activates the newest toolchain, and returns the version that was activated,
+/// if the active toolchain changed.
+///
+/// Confirmation is only asked for when the update isn't `unattended` and `--yes` wasn't
+/// passed.
+async fn update_to_newest(
+    client: &ToolchainClient,
+    args: &UpdateArgs,
+    config: &Config,
+    unattended: bool,
+    cancel_token: &CancellationToken,
+) -> Result<Option<ToolchainVersion>, CliError> {
+    let release = match args.major {
+        Some(major) => client
+            .newest_in_major(major)
+            .with_cancellation_token(cancel_token)
+            .await
+            .ok_or(ToolchainError::Cancelled)??
+            .ok_or(CliError::NoReleaseInMajor { major })?,
+        None => {
+            client
+                .latest_release_with_cancellation(cancel_token)
+                .await?
+        }
+    };
+    let version = release.version().clone();
+
+    if client.version_is_installed(&version) {
+        println!(
+            "Toolchain {} is already installed and up to date.",
+            version.to_string().bold()
+        );
+    } else {
+        let host = update_host(client, config).await;
+        if !args.yes && !unattended {
+            let plan = plan_for_summary(client, &release, &host, None)
+                .with_cancellation_token(cancel_token)
+                .await
+                .ok_or(ToolchainError::Cancelled)?;
+            confirm_install(&version, args.major.is_none(), plan.as_ref()).await?;
+        }
+
+        install_with_progress_bar(
+            client,
+            &release,
+            &host,
+            None,
+            None,
+            cancel_token.child_token(),
+        )
+        .await?;
+
+        record_install_reason(client, &version, UPDATE_REASON).await;
+    }
+
+    // Only switch over if the active toolchain is an older version of the same line.
+    let should_activate = match client.active_toolchain() {
+        Some(active) => active < version && (args.major.is_none() || active.major() == args.major),
+        None => true,
+    };
+
+    if should_activate && client.active_toolchain().as_ref() != Some(&version) {
+        client.set_active_toolchain(Some(version.clone())).await?;
+        msg!("Activated", "{version}");
+        return Ok(Some(version));
+    }
+
+    Ok(None)
+}
+
+/// Returns the host options for installing an update, which prefer the same kind of build
+/// (native or universal) as the one the active toolchain was installed from.
+///
+/// The newest installed toolchain is used if none is active. The config file's preference is
+/// only used if neither recorded which build it was installed from.
+async fn update_host(client: &ToolchainClient, config: &Config) -> HostArgs {
+    let current = match client.active_toolchain() {
+        Some(active) => Some(active),
+        None => client
+            .installed_versions()
+            .await
+            .ok()
+            .and_then(|versions| versions.into_iter().max()),
+    };
+    let recorded = match current {
+        Some(version) => match client.toolchain(&version).await {
+            Ok(toolchain) => toolchain.installed_arch().await,
+            Err(_) => None,
+        },
+        None => None,
+    };
+    debug!(?recorded, "Recorded architecture of the current toolchain");
+
+    let mut host = HostArgs::default();
+    host.set_default_preference(recorded.map(ArchPreference::of).or(config.arch_preference));
+    host
+}
+
+/// Updates without asking for confirmation, then removes the toolchains that the update
+/// superseded if `[update] keep` is set in the config.
+async fn update_unattended(
+    client: &ToolchainClient,
+    args: &UpdateArgs,
+    config: &Config,
+    cancel_token: &CancellationToken,
+) -> Result<(), CliError> {
+    let activated = update_to_newest(client, args, config, true, cancel_token).await?;
+
+    if let (Some(active), Some(keep)) = (activated, config.update.keep) {
+        // The update itself succeeded, so a toolchain that can't be removed now is only
+        // reported. It'll be removed after the next update.
+        if let Err(error) = remove_superseded(client, args, &active, keep, cancel_token).await {
+            eprintln!(
+                "{} could not remove superseded toolchains: {error}",
+                "warning:".yellow().bold()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes the toolchains older than `active` (within the major version being updated),
+/// except for the newest `keep` of them.
+///
+/// Only toolchains that were installed by `update` are removed. A toolchain that anything
+/// else recorded a reason for, or that has no reasons at all, is left alone.
+async fn remove_superseded(
+    client: &ToolchainClient,
+    args: &UpdateArgs,
+    active: &ToolchainVersion,
+    keep: usize,
+    cancel_token: &CancellationToken,
+) -> Result<(), CliError> {
+    let reasons = client.install_reasons().await?;
+    let mut superseded = client
+        .installed_versions()
+        .await?
+        .into_iter()
+        .filter(|version| version < active)
+        .filter(|version| args.major.is_none() || version.major() == args.major)
+        .filter(|version| {
+            let reasons = reasons.get(version);
+            !reasons.is_empty() && reasons.iter().all(|reason| reason == UPDATE_REASON)
+        })
+        .collect::<Vec<_>>();
+
+    // Newest first, so that the newest `keep` versions are skipped.
+    superseded.sort_by(|a, b| b.cmp(a));
+
+    for version in superseded.into_iter().skip(keep) {
+        client.remove(&version, |_| {}, cancel_token).await?;
+        msg!("Removed", "{version} (superseded by {active})");
+    }
+
+    Ok(())
+}
+
+/// Checks for updates every `--interval` until Ctrl-C or SIGTERM is received.
+///
+/// A failed check (e.g. because the network is down) doesn't stop the daemon. It's retried
+/// after a delay that doubles after each consecutive failure.
+async fn run_daemon(
+    client: &ToolchainClient,
+    args: &UpdateArgs,
+    config: &Config,
+) -> Result<(), CliError> {
+    let lock_path = client.product_path().join("update-daemon.lock");
+    let _lock = lock_daemon(lock_path).await?;
+
+    let shutdown = shutdown_signal();
+    msg!(
+        "Watching",
+        "for {} updates every {}",
+        args.product.id,
+        format_interval(args.interval)
+    );
+
+    let mut retry_delay = None;
+    loop {
+        let result = update_unattended(client, args, config, &shutdown).await;
+        if shutdown.is_cancelled() {
+            break;
+        }
+
+        if let Some(path) = &config.metrics_textfile {
+            write_metrics(path, "update", result.is_ok()).await;
+        }
+
+        let delay = match result {
+            Ok(()) => {
+                retry_delay = None;
+                jitter(args.interval)
+            }
+            Err(error) => {
+                let delay = retry_delay
+                    .map_or(MIN_RETRY_DELAY, |delay: Duration| delay.saturating_mul(2))
+                    .min(args.interval);
+                retry_delay = Some(delay);
+
+                eprintln!(
+                    "{} update check failed: {error}. Retrying in {}.",
+                    "warning:".yellow().bold(),
+                    format_interval(delay)
+                );
+                delay
+            }
+        };
+
+        debug!(?delay, "Waiting for the next update check");
+        if sleep(delay)
+            .with_cancellation_token(&shutdown)
+            .await
+            .is_none()
+        {
+            break;
+        }
+    }
+
+    msg!("Stopped", "watching for updates");
+    Ok(())
+}
+
+/// Takes the lock that stops two daemons from updating the same product at once, which is
+/// held until the returned file is closed.
+async fn lock_daemon(path: PathBuf) -> Result<File, CliError> {
+    match try_lock_file(path.clone()).await? {
+        Some(file) => Ok(file),
+        None => Err(CliError::UpdateDaemonRunning { path }),
+    }
+}
+
+/// Returns a token that's cancelled when Ctrl-C or SIGTERM is received.
+fn shutdown_signal() -> CancellationToken {
+    let token = CancellationToken::new();
+
+    tokio::spawn({
+        let token = token.clone();
+        async move {
+            #[cfg(unix)]
+            {
+                use tokio::signal::unix::{SignalKind, signal};
+
+                let mut terminate =
+                    signal(SignalKind::terminate()).expect("failed to listen for SIGTERM");
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+            }
+            #[cfg(not(unix))]
+            tokio::signal::ctrl_c().await.unwrap();
+
+            eprintln!("Stopping...");
+            token.cancel();
+        }
+    });
+
+    token
+}
+
+/// Spreads out the checks of daemons that were started at the same time by changing the
+/// interval by up to 10% in either direction.
+fn jitter(interval: Duration) -> Duration {
+    // A factor between 0.9 and 1.1.
+    interval.mul_f64(0.9 + fastrand::f64() * 0.2)
+}
+
+const INTERVAL_UNITS: &[(&str, u64)] = &[("d", 86_400), ("h", 3_600), ("m", 60), ("s", 1)];
+
+/// Parses an interval such as `30m`, `12h`, or `1d`.
+fn parse_interval(value: &str) -> Result<Duration, String> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let number = number
+        .parse::<u64>()
+        .map_err(|_| "expected a number followed by a unit, such as `24h`".to_string())?;
+    let Some(&(_, seconds)) = INTERVAL_UNITS.iter().find(|(name, _)| *name == unit) else {
+        return Err(format!(
+            "unknown unit {unit:?}, expected one of `s`, `m`, `h`, or `d`"
+        ));
+    };
+
+    match number.checked_mul(seconds) {
+        Some(0) => Err("the interval must be longer than zero".to_string()),
+        Some(seconds) => Ok(Duration::from_secs(seconds)),
+        None => Err("the interval is too long".to_string()),
+    }
+}
+
+/// Formats an interval in the largest unit that it's a whole number of.
+fn format_interval(interval: Duration) -> String {
+    let seconds = interval.as_secs().max(1);
+    let (unit, size) = INTERVAL_UNITS
+        .iter()
+        .find(|(_, size)| seconds.is_multiple_of(*size))
+        .unwrap_or(&("s", 1));
+    format!("{}{unit}", seconds / size)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::toolchain::HostArch;
+
+    async fn client(dir: &Path) -> ToolchainClient {
+        ToolchainClient::builder()
+            .toolchains_path(dir.join("toolchains"))
+            .cache_path(dir.join("cache"))
+            .github_token(None)
+            .offline(true)
+            .build()
+            .await
+            .unwrap()
+    }
+
+    /// Creates an installed toolchain whose manifest records the given architecture, if any.
+    fn install(client: &ToolchainClient, version: &str, arch: Option<HostArch>) {
+        let path = client.install_path_for(&ToolchainVersion::named(version));
+        std::fs::create_dir_all(&path).unwrap();
+        if let Some(arch) = arch {
+            std::fs::write(
+                path.join(".arm-toolchain-manifest"),
+                format!("asset_arch={}\n", arch.as_ref()),
+            )
+            .unwrap();
+        }
+    }
+
+    fn config(arch_preference: Option<ArchPreference>) -> Config {
+        Config {
+            arch_preference,
+            ..Config::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn prefers_the_build_recorded_for_the_active_toolchain() {
+        let dir = tempfile::tempdir().unwrap();
+        let client = client(dir.path()).await;
+        install(&client, "20.1.0", Some(HostArch::Universal));
+        install(&client, "21.1.0", Some(HostArch::AAarch64));
+        client
+            .set_active_toolchain(Some(ToolchainVersion::named("20.1.0")))
+            .await
+            .unwrap();
+
+        // The recorded build wins over the config file.
+        let host = update_host(&client, &config(Some(ArchPreference::Native))).await;
+        assert_eq!(host.arch_preference(), Some(ArchPreference::Universal));
+
+        client
+            .set_active_toolchain(Some(ToolchainVersion::named("21.1.0")))
+            .await
+            .unwrap();
+        let host = update_host(&client, &config(Some(ArchPreference::Universal))).await;
+        assert_eq!(host.arch_preference(), Some(ArchPreference::Native));
+    }
+
+    #[tokio::test]
+    async fn prefers_the_build_recorded_for_the_newest_toolchain_if_none_is_active() {
+        let dir = tempfile::tempdir().unwrap();
+        let client = client(dir.path()).await;
+        install(&client, "20.1.0", Some(HostArch::AAarch64));
+        install(&client, "21.1.0", Some(HostArch::Universal));
+
+        let host = update_host(&client, &config(None)).await;
+
+        assert_eq!(host.arch_preference(), Some(ArchPreference::Universal));
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_the_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let client = client(dir.path()).await;
+
+        let host = update_host(&client, &config(Some(ArchPreference::Universal))).await;
+        assert_eq!(host.arch_preference(), Some(ArchPreference::Universal));
+
+        // Toolchains installed by older versions didn't record their build.
+        install(&client, "21.1.0", None);
+        let host = update_host(&client, &config(Some(ArchPreference::Universal))).await;
+        assert_eq!(host.arch_preference(), Some(ArchPreference::Universal));
+        let host = update_host(&client, &config(None)).await;
+        assert_eq!(host.arch_preference(), None);
+    }
+
+    #[test]
+    fn jitter_stays_within_ten_percent() {
+        let interval = Duration::from_secs(3600);
+        for _ in 0..1000 {
+            let jittered = jitter(interval);
+            assert!(
+                (interval.mul_f64(0.9)..=interval.mul_f64(1.1)).contains(&jittered),
+                "{jittered:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn only_one_daemon_holds_the_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("daemons/embedded.lock");
+
+        let lock = lock_daemon(path.clone()).await.unwrap();
+        let second = lock_daemon(path.clone()).await;
+        assert!(
+            matches!(&second, Err(CliError::UpdateDaemonRunning { path: p }) if *p == path),
+            "{second:?}"
+        );
+
+        drop(lock);
+        lock_daemon(path).await.unwrap();
+    }
+}
@@ -1,54 +1,289 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+use tokio_util::{future::FutureExt as _, sync::CancellationToken};
+use tracing::warn;
+
 use crate::{
-    cli::{CliError, confirm_install, ctrl_c_cancel, install_with_progress_bar, msg},
-    toolchain::{ToolchainClient, ToolchainVersion},
+    cli::{
+        CliError, Config, HostArgs, Lookup, OutputFormat, Pin, PinFile, VersionSpec,
+        activatable_spec, client_builder, confirm_install, ctrl_c_cancel, effective_toolchain_in,
+        install_with_progress_bar, is_missing_release, missing_release, msg, not_installed,
+        plan_for_summary, record_install_reason, record_pin_reference, report_plan,
+    },
+    toolchain::{
+        InstallOutcome, InstallPlan, Product, ToolchainClient, ToolchainError, ToolchainRelease,
+        ToolchainVersion,
+    },
 };
 
 #[derive(Debug, clap::Parser)]
 pub struct UseArgs {
-    /// Version of LLVM to install
-    pub llvm_version: ToolchainVersion,
+    /// Version of LLVM to install, "latest", "previous", or an alias from the config file.
+    #[clap(value_parser = activatable_spec)]
+    pub llvm_version: VersionSpec,
+    /// Which toolchain to activate ("embedded" or "linux").
+    #[clap(long, default_value = "embedded")]
+    pub product: Product,
+    /// Don't ask for confirmation before installing.
+    #[clap(long, short)]
+    pub yes: bool,
+    /// Pin the toolchain for the current project instead of changing the global default.
+    #[clap(long)]
+    pub local: bool,
+    /// The project directory to pin the toolchain for (default: the current directory).
+    #[clap(long, requires = "local")]
+    pub path: Option<PathBuf>,
+    /// Show whether the toolchain would be installed and activated without doing it.
+    ///
+    /// Release metadata is still fetched from GitHub, but nothing is downloaded or written.
+    #[clap(long)]
+    pub dry_run: bool,
+    /// Ask GitHub whether the releases changed, instead of using release information that
+    /// was fetched in the last 15 minutes.
+    #[clap(long)]
+    pub refresh: bool,
+    /// Let "latest" resolve to a pre-release, such as a release candidate.
+    #[clap(long)]
+    pub pre: bool,
+    /// How the result should be reported.
+    #[clap(long, default_value = "human")]
+    pub output: OutputFormat,
+}
+
+/// The result of [`use_cmd`].
+#[derive(Debug, Serialize)]
+pub struct UseReport {
+    /// The version that is now active.
+    pub version: ToolchainVersion,
+    /// Whether the active toolchain changed. This is false if it was already active.
+    pub activated: bool,
+    /// Details about the installation, if the toolchain had to be installed first.
+    pub installed: Option<InstallOutcome>,
+    /// The pin file that was written, if `--local` was passed.
+    pub pin_file: Option<PathBuf>,
+    /// Whether this describes what would happen, because `--dry-run` was passed.
+    pub dry_run: bool,
+    /// What would be installed, if `--dry-run` was passed and the toolchain isn't installed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub install_plan: Option<InstallPlan>,
 }
 
-pub async fn use_cmd(args: UseArgs) -> Result<(), CliError> {
-    let mut version = args.llvm_version;
+pub async fn use_cmd(args: UseArgs) -> Result<UseReport, CliError> {
+    let config = Config::load().await?;
+    let client = client_builder()
+        .permission_policy(config.permissions.unwrap_or_default())
+        .checksum_file(config.checksum_file)
+        .product(args.product)
+        .refresh_releases(args.refresh)
+        .write_release_cache(!args.dry_run)
+        .include_prereleases(args.pre)
+        .build()
+        .await?;
+    let mut host = HostArgs::default();
+    host.set_default_preference(config.arch_preference);
 
-    let client = ToolchainClient::using_data_dir().await?;
+    let install_latest = args.llvm_version == VersionSpec::Latest;
 
-    let install_latest = version.name == "latest";
-    let mut release = None;
+    // Ctrl-C cancels fetching the release as well as installing it.
+    let token = ctrl_c_cancel();
+    let release_listener = token.clone().drop_guard();
 
-    // If "latest" specified we have to figure out what that actually means first
-    if install_latest {
-        let latest = client.latest_release().await?;
-        version = latest.version().clone();
-        release = Some(latest);
-    }
+    let resolved = args
+        .llvm_version
+        .resolve_one(&client, Lookup::Releases)
+        .with_cancellation_token(&token)
+        .await
+        .ok_or(ToolchainError::Cancelled)??;
+    let version = resolved.version;
+    let release = resolved.release;
 
     let installed_versions = client.installed_versions().await?;
     let is_installed = installed_versions.contains(&version);
+    let mut installed = None;
+
+    if args.dry_run {
+        return plan_use(&client, args, &host, version, release, is_installed, &token).await;
+    }
 
     if !is_installed {
         let release = if let Some(rel) = release {
             rel
+        } else if client.is_offline() {
+            // The release can't be fetched, so the version was either mistyped or needs to
+            // be installed once the client is online again.
+            return Err(not_installed(&client, version).await);
         } else {
-            client.get_release(&version).await?
+            match client.get_release_with_cancellation(&version, &token).await {
+                Ok(release) => release,
+                Err(error) if is_missing_release(&error) => {
+                    return Err(missing_release(&client, version).await);
+                }
+                Err(error) => return Err(error.into()),
+            }
         };
 
-        confirm_install(&version, install_latest).await?;
+        if !args.yes {
+            let plan = plan_for_summary(&client, &release, &host, None)
+                .with_cancellation_token(&token)
+                .await
+                .ok_or(ToolchainError::Cancelled)?;
+            confirm_install(&version, install_latest, plan.as_ref()).await?;
+        }
+
+        installed = Some(
+            install_with_progress_bar(&client, &release, &host, None, None, token.clone()).await?,
+        );
+    }
+
+    // Release Ctrl-C listener
+    drop(release_listener);
+
+    record_install_reason(&client, &version, "cli: use").await;
+
+    if args.local {
+        let dir = match args.path {
+            Some(path) => path,
+            None => env::current_dir()?,
+        };
+
+        let pin_file = pin_locally(&dir, &version, client.product()).await?;
+        msg!("Pinned", "{version} in {}", pin_file.display());
+
+        let effective =
+            effective_toolchain_in(&dir, client.product(), client.active_toolchain()).await?;
+        if let Some(effective) = effective {
+            args.output.message(format_args!(
+                "The toolchain used in {} is now {effective}.",
+                dir.display()
+            ));
+        }
 
-        let token = ctrl_c_cancel();
-        install_with_progress_bar(&client, &release, token.clone()).await?;
+        return Ok(UseReport {
+            version,
+            activated: false,
+            installed,
+            pin_file: Some(pin_file),
+            dry_run: false,
+            install_plan: None,
+        });
+    }
 
-        // Release Ctrl-C listener
-        token.cancel();
-    } else if client.active_toolchain().as_ref() == Some(&version) {
-        println!("Toolchain {version} is already enabled.");
-        return Ok(());
+    if is_installed && client.active_toolchain().as_ref() == Some(&version) {
+        args.output
+            .message(format_args!("Toolchain {version} is already enabled."));
+        return Ok(UseReport {
+            version,
+            activated: false,
+            installed,
+            pin_file: None,
+            dry_run: false,
+            install_plan: None,
+        });
     }
 
     client.set_active_toolchain(Some(version.clone())).await?;
 
     msg!("Activated", "{version}");
 
-    Ok(())
+    Ok(UseReport {
+        version,
+        activated: true,
+        installed,
+        pin_file: None,
+        dry_run: false,
+        install_plan: None,
+    })
+}
+
+/// Reports what [`use_cmd`] would do, without installing or activating anything.
+async fn plan_use(
+    client: &ToolchainClient,
+    args: UseArgs,
+    host: &HostArgs,
+    version: ToolchainVersion,
+    release: Option<ToolchainRelease>,
+    is_installed: bool,
+    cancel_token: &CancellationToken,
+) -> Result<UseReport, CliError> {
+    let install_plan = if is_installed {
+        None
+    } else {
+        let release = match release {
+            Some(release) => release,
+            None => {
+                client
+                    .get_release_with_cancellation(&version, cancel_token)
+                    .await?
+            }
+        };
+        let asset = host.select_asset(client, &release)?;
+        let plan = client.plan_install(&release, asset).await?;
+        report_plan(&plan, args.output);
+        Some(plan)
+    };
+
+    if args.local {
+        let dir = match args.path {
+            Some(path) => path,
+            None => env::current_dir()?,
+        };
+        let pin_file = dir.join(Pin::FILENAME);
+        args.output.message(format_args!(
+            "Would pin {version} in {}",
+            pin_file.display()
+        ));
+
+        return Ok(UseReport {
+            version,
+            activated: false,
+            installed: None,
+            pin_file: Some(pin_file),
+            dry_run: true,
+            install_plan,
+        });
+    }
+
+    // Installing a toolchain activates it if nothing is active, which `use` would do anyway.
+    let activated = client.active_toolchain().as_ref() != Some(&version);
+    if activated {
+        args.output
+            .message(format_args!("Would activate {version}"));
+    } else {
+        args.output
+            .message(format_args!("Toolchain {version} is already enabled."));
+    }
+
+    Ok(UseReport {
+        version,
+        activated,
+        installed: None,
+        pin_file: None,
+        dry_run: true,
+        install_plan,
+    })
+}
+
+/// Pins a version in the given directory's pin file, replacing any existing pin, and returns
+/// the file's path.
+async fn pin_locally(
+    dir: &Path,
+    version: &ToolchainVersion,
+    product: Product,
+) -> Result<PathBuf, CliError> {
+    let pin_file = PinFile {
+        path: dir.join(Pin::FILENAME),
+        pin: Pin::new(version, product),
+    };
+
+    pin_file.save().await?;
+
+    // The list is only used to find pins that `remove` breaks, so it isn't worth failing over.
+    if let Err(error) = record_pin_reference(&pin_file.path).await {
+        warn!(%error, "Failed to record the pin file");
+    }
+    Ok(pin_file.path)
 }
@@ -0,0 +1,110 @@
+use serde::Serialize;
+
+use crate::{
+    cli::{
+        CliError, Colorize, Lookup, OutputFormat, ProgressBars, VERIFY_MODIFIED_EXIT_CODE,
+        VERIFY_UNAVAILABLE_EXIT_CODE, VersionSpec, client_builder, ctrl_c_cancel, installed_spec,
+        installed_toolchain, msg, set_exit_code,
+    },
+    toolchain::{Product, ToolchainError, ToolchainVersion, VerifyReport},
+};
+
+/// Configuration for [`verify`].
+#[derive(Debug, clap::Parser)]
+pub struct VerifyArgs {
+    /// The installed toolchain to verify: a version, "previous", or an alias from the config
+    /// file.
+    #[arg(long, value_parser = installed_spec, value_name = "VERSION")]
+    pub installed: VersionSpec,
+    /// Which toolchain to verify ("embedded" or "linux").
+    #[arg(long, default_value = "embedded")]
+    pub product: Product,
+    /// How the result should be reported.
+    #[arg(long, default_value = "human")]
+    pub output: OutputFormat,
+}
+
+/// The result of [`verify`].
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum VerifyOutcome {
+    /// Every file matches the digest recorded when the toolchain was installed.
+    Clean(VerifyReport),
+    /// Files were modified, added, or deleted since the toolchain was installed.
+    Modified(VerifyReport),
+    /// No digests were recorded when the toolchain was installed.
+    Unavailable { version: ToolchainVersion },
+}
+
+/// Check an installed toolchain's files against the digests recorded when it was installed.
+///
+/// The process exits with [`VERIFY_MODIFIED_EXIT_CODE`] if any files changed, and with
+/// [`VERIFY_UNAVAILABLE_EXIT_CODE`] if the toolchain can't be verified.
+pub async fn verify(args: VerifyArgs) -> Result<VerifyOutcome, CliError> {
+    let client = client_builder().product(args.product).build().await?;
+    let version = args
+        .installed
+        .resolve_one(&client, Lookup::Installed)
+        .await?
+        .version;
+    // Checked first so that a missing toolchain gets suggestions.
+    installed_toolchain(&client, &version).await?;
+
+    let cancel_token = ctrl_c_cancel();
+    let progress_bars = ProgressBars::default();
+    let result = client
+        .verify_installed(
+            &version,
+            progress_bars.verify_handler(&version),
+            &cancel_token,
+        )
+        .await;
+    cancel_token.cancel();
+
+    let report = match result {
+        Ok(report) => report,
+        Err(ToolchainError::DigestsNotRecorded { version }) => {
+            eprintln!(
+                "{} No file digests were recorded when {version} was installed, so it can't be verified.",
+                "warning:".yellow().bold(),
+            );
+            eprintln!(
+                "Reinstall it with `install --force --record-digests` to be able to verify it."
+            );
+            set_exit_code(VERIFY_UNAVAILABLE_EXIT_CODE);
+            return Ok(VerifyOutcome::Unavailable { version });
+        }
+        Err(error) => return Err(error.into()),
+    };
+
+    if report.is_clean() {
+        msg!(
+            "Verified",
+            "{version} ({} files are unchanged)",
+            report.files_checked
+        );
+        return Ok(VerifyOutcome::Clean(report));
+    }
+
+    let changes = &report.changes;
+    for (label, paths) in [
+        ("modified", &changes.modified),
+        ("added", &changes.added),
+        ("deleted", &changes.deleted),
+    ] {
+        for path in paths {
+            args.output
+                .message(format_args!("{:>8} {}", label.red(), path.display()));
+        }
+    }
+    msg!(
+        "Changed",
+        "{version}: {} modified, {} added, {} deleted",
+        changes.modified.len(),
+        changes.added.len(),
+        changes.deleted.len(),
+    );
+    set_exit_code(VERIFY_MODIFIED_EXIT_CODE);
+
+    Ok(VerifyOutcome::Modified(report))
+}
@@ -0,0 +1,426 @@
+use std::fmt::{self, Display};
+
+use crate::{
+    cli::{CliError, Config, msg},
+    toolchain::{ToolchainClient, ToolchainRelease, ToolchainVersion},
+};
+
+/// A version given on the command line, which may be a pseudo-version such as `latest`.
+///
+/// Each command only accepts some kinds of spec (see [`SpecKind`]), which is checked when
+/// the arguments are parsed. Specs are turned into real versions with [`resolve_spec`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionSpec {
+    /// A specific version, such as `20.1.0`, or the start of one, such as `20` or `20.1`,
+    /// which resolves to the newest matching version (see [`Lookup`]).
+    Exact(ToolchainVersion),
+    /// The newest release (`latest`).
+    Latest,
+    /// Every installed toolchain (`all`).
+    All,
+    /// The newest installed toolchain that's older than the active one (`previous`).
+    Previous,
+    /// A name defined in the `[aliases]` section of the config file.
+    Alias(String),
+}
+
+/// Where a partial version (such as `20`) is looked up when a [`VersionSpec`] is resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lookup {
+    /// The releases on GitHub, for commands that can install a toolchain. The installed
+    /// toolchains are used instead when the client is offline.
+    Releases,
+    /// The installed toolchains, for commands that only use or remove them.
+    Installed,
+}
+
+/// The kinds of [`VersionSpec`], used to declare which ones a command accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpecKind {
+    Exact,
+    Latest,
+    All,
+    Previous,
+    Alias,
+}
+
+impl SpecKind {
+    /// The specs accepted by commands that install a release: `install`, `info`, and `lock`.
+    pub const INSTALLABLE: &[Self] = &[Self::Exact, Self::Latest, Self::Alias];
+    /// The specs accepted by `use`, which installs the toolchain if needed.
+    pub const ACTIVATABLE: &[Self] = &[Self::Exact, Self::Latest, Self::Previous, Self::Alias];
+    /// The specs accepted by `remove`.
+    pub const REMOVABLE: &[Self] = &[Self::Exact, Self::All, Self::Previous, Self::Alias];
+    /// The specs accepted by commands that use an installed toolchain: `locate` and `run`.
+    pub const INSTALLED: &[Self] = &[Self::Exact, Self::Previous, Self::Alias];
+
+    /// Describes the kind for a list of what's accepted.
+    fn description(self) -> &'static str {
+        match self {
+            Self::Exact => "a version",
+            Self::Latest => "\"latest\"",
+            Self::All => "\"all\"",
+            Self::Previous => "\"previous\"",
+            Self::Alias => "an alias",
+        }
+    }
+}
+
+impl VersionSpec {
+    /// Returns the kind of the spec.
+    pub fn kind(&self) -> SpecKind {
+        match self {
+            Self::Exact(_) => SpecKind::Exact,
+            Self::Latest => SpecKind::Latest,
+            Self::All => SpecKind::All,
+            Self::Previous => SpecKind::Previous,
+            Self::Alias(_) => SpecKind::Alias,
+        }
+    }
+
+    /// Parses a spec, failing if it isn't one of the accepted kinds.
+    ///
+    /// Anything that starts with a digit (optionally after a `v`), or is a release tag such as
+    /// `release-20.1.0-ATfE`, is a version. Other names are aliases, unless they're one of the
+    /// pseudo-versions. Aliases aren't accepted where only versions are, so those names are
+    /// treated as versions instead.
+    pub fn parse(value: &str, accepted: &[SpecKind]) -> Result<Self, String> {
+        let spec = match value.to_ascii_lowercase().as_str() {
+            "latest" => Self::Latest,
+            "all" => Self::All,
+            "previous" => Self::Previous,
+            _ if value.is_empty() => return Err("the version can't be empty".to_string()),
+            _ => {
+                let version = ToolchainVersion::from(value);
+                if version.name.starts_with(|c: char| c.is_ascii_digit())
+                    || !accepted.contains(&SpecKind::Alias)
+                {
+                    Self::Exact(version)
+                } else {
+                    Self::Alias(value.to_string())
+                }
+            }
+        };
+
+        if !accepted.contains(&spec.kind()) {
+            let expected = accepted
+                .iter()
+                .map(|kind| kind.description())
+                .collect::<Vec<_>>();
+            let expected = match expected.split_last() {
+                Some((last, [])) => last.to_string(),
+                Some((last, [first])) => format!("{first} or {last}"),
+                Some((last, rest)) => format!("{}, or {last}", rest.join(", ")),
+                None => String::new(),
+            };
+            return Err(format!(
+                "{spec} can't be used with this command (expected {expected})"
+            ));
+        }
+
+        Ok(spec)
+    }
+
+    /// Resolves a spec that refers to a single version, which is every kind except
+    /// [`VersionSpec::All`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the spec is [`VersionSpec::All`].
+    pub async fn resolve_one(
+        &self,
+        client: &ToolchainClient,
+        lookup: Lookup,
+    ) -> Result<ResolvedVersion, CliError> {
+        assert_ne!(
+            self.kind(),
+            SpecKind::All,
+            "\"all\" refers to several versions"
+        );
+        let mut resolved = resolve_spec(client, self, lookup).await?;
+        Ok(resolved.remove(0))
+    }
+}
+
+impl Display for VersionSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exact(version) => write!(f, "{version}"),
+            Self::Latest => f.write_str("\"latest\""),
+            Self::All => f.write_str("\"all\""),
+            Self::Previous => f.write_str("\"previous\""),
+            Self::Alias(name) => write!(f, "{name:?}"),
+        }
+    }
+}
+
+/// Parses a spec accepted by `install`, `info`, and `lock`.
+pub fn installable_spec(value: &str) -> Result<VersionSpec, String> {
+    VersionSpec::parse(value, SpecKind::INSTALLABLE)
+}
+
+/// Parses a spec accepted by `use`.
+pub fn activatable_spec(value: &str) -> Result<VersionSpec, String> {
+    VersionSpec::parse(value, SpecKind::ACTIVATABLE)
+}
+
+/// Parses a spec accepted by `remove`.
+pub fn removable_spec(value: &str) -> Result<VersionSpec, String> {
+    VersionSpec::parse(value, SpecKind::REMOVABLE)
+}
+
+/// Parses a spec accepted by `locate` and `run`.
+pub fn installed_spec(value: &str) -> Result<VersionSpec, String> {
+    VersionSpec::parse(value, SpecKind::INSTALLED)
+}
+
+/// A version that a [`VersionSpec`] resolved to.
+#[derive(Clone)]
+pub struct ResolvedVersion {
+    pub version: ToolchainVersion,
+    /// The version's release, if it was fetched to resolve the spec.
+    pub release: Option<ToolchainRelease>,
+}
+
+impl ResolvedVersion {
+    fn exact(version: ToolchainVersion) -> Self {
+        Self {
+            version,
+            release: None,
+        }
+    }
+}
+
+/// Resolves a spec to the versions it refers to.
+///
+/// Only [`VersionSpec::Latest`] and partial versions looked up in the [`Lookup::Releases`]
+/// contact GitHub, and [`VersionSpec::All`] is the only spec that can resolve to anything
+/// other than one version. An alias resolves to whatever its target in the config file
+/// resolves to.
+pub async fn resolve_spec(
+    client: &ToolchainClient,
+    spec: &VersionSpec,
+    lookup: Lookup,
+) -> Result<Vec<ResolvedVersion>, CliError> {
+    let resolved = match spec {
+        VersionSpec::Exact(version) if version.is_partial() => {
+            vec![resolve_partial(client, version, lookup).await?]
+        }
+        VersionSpec::Exact(version) => vec![ResolvedVersion::exact(version.clone())],
+        VersionSpec::Latest => {
+            let release = client.latest_release().await?;
+            vec![ResolvedVersion {
+                version: release.version().clone(),
+                release: Some(release),
+            }]
+        }
+        VersionSpec::All => client
+            .installed_versions()
+            .await?
+            .into_iter()
+            .map(ResolvedVersion::exact)
+            .collect(),
+        VersionSpec::Previous => {
+            let active = client
+                .active_toolchain()
+                .ok_or(CliError::NoToolchainEnabled)?;
+            let previous = client
+                .installed_versions()
+                .await?
+                .into_iter()
+                .filter(|version| *version < active)
+                .max()
+                .ok_or(CliError::NoPreviousToolchain { active })?;
+            vec![ResolvedVersion::exact(previous)]
+        }
+        VersionSpec::Alias(name) => {
+            let config = Config::load().await?;
+            let Some(target) = config.aliases.get(name) else {
+                return Err(CliError::UnknownAlias {
+                    name: name.clone(),
+                    defined: config.aliases.keys().cloned().collect(),
+                });
+            };
+
+            // Aliases can't refer to other aliases, so they can't form a cycle.
+            let target = VersionSpec::parse(target, &[SpecKind::Exact, SpecKind::Latest]).map_err(
+                |reason| CliError::InvalidAlias {
+                    name: name.clone(),
+                    reason,
+                },
+            )?;
+            Box::pin(resolve_spec(client, &target, lookup)).await?
+        }
+    };
+
+    Ok(resolved)
+}
+
+/// Resolves a partial version such as `20` to the newest release or installed toolchain that
+/// starts with it. A version that matches exactly always wins.
+///
+/// A partial version that matches no installed toolchain is returned as-is, so that the
+/// command reports that it isn't installed.
+async fn resolve_partial(
+    client: &ToolchainClient,
+    prefix: &ToolchainVersion,
+    lookup: Lookup,
+) -> Result<ResolvedVersion, CliError> {
+    if lookup == Lookup::Releases && !client.is_offline() {
+        let release = client.get_release(prefix).await?;
+        if release.version() != prefix {
+            msg!("Resolved", "{prefix} -> {}", release.version());
+        }
+        return Ok(ResolvedVersion {
+            version: release.version().clone(),
+            release: Some(release),
+        });
+    }
+
+    let installed = client.installed_versions().await?;
+    if installed.contains(prefix) {
+        return Ok(ResolvedVersion::exact(prefix.clone()));
+    }
+
+    // When standing in for the releases, installed pre-releases are skipped the same way
+    // unreleased ones would be.
+    let skip_prereleases = lookup == Lookup::Releases && !client.includes_prereleases();
+    let mut candidates = installed
+        .into_iter()
+        .filter(|version| version.starts_with(prefix))
+        .filter(|version| !(skip_prereleases && version.is_prerelease()))
+        .collect::<Vec<_>>();
+    match candidates.len() {
+        0 => Ok(ResolvedVersion::exact(prefix.clone())),
+        1 => {
+            let version = candidates.remove(0);
+            msg!("Resolved", "{prefix} -> {version}");
+            Ok(ResolvedVersion::exact(version))
+        }
+        // Unlike releases, it's not clear which of several installed toolchains is meant,
+        // since the newest one isn't necessarily the one that's wanted.
+        _ => {
+            candidates.sort();
+            Err(CliError::AmbiguousVersion {
+                prefix: prefix.clone(),
+                candidates,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::toolchain::Product;
+
+    type Parser = fn(&str) -> Result<VersionSpec, String>;
+
+    const COMMANDS: &[(&str, Parser, &[SpecKind])] = &[
+        ("install", installable_spec, SpecKind::INSTALLABLE),
+        ("use", activatable_spec, SpecKind::ACTIVATABLE),
+        ("remove", removable_spec, SpecKind::REMOVABLE),
+        ("run", installed_spec, SpecKind::INSTALLED),
+    ];
+
+    #[test]
+    fn each_command_accepts_only_its_kinds() {
+        for (value, kind) in [
+            ("20.1.0", SpecKind::Exact),
+            ("latest", SpecKind::Latest),
+            ("all", SpecKind::All),
+            ("previous", SpecKind::Previous),
+            ("stable", SpecKind::Alias),
+        ] {
+            for &(command, parse, accepted) in COMMANDS {
+                let result = parse(value);
+
+                if accepted.contains(&kind) {
+                    assert_eq!(result.unwrap().kind(), kind, "{command} {value}");
+                } else {
+                    let error = result.unwrap_err();
+                    assert!(
+                        error.contains("can't be used with this command"),
+                        "{command} {value}: {error}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn expected_kinds_are_listed() {
+        assert_eq!(
+            installable_spec("all").unwrap_err(),
+            "\"all\" can't be used with this command (expected a version, \"latest\", or an alias)"
+        );
+        assert_eq!(
+            VersionSpec::parse("previous", &[SpecKind::Exact, SpecKind::Latest]).unwrap_err(),
+            "\"previous\" can't be used with this command (expected a version or \"latest\")"
+        );
+    }
+
+    #[test]
+    fn versions_are_read_from_tags_and_prefixes() {
+        for (value, version) in [
+            ("20.1.0", "20.1.0"),
+            ("v20.1.0", "20.1.0"),
+            ("20", "20"),
+            ("21.1.0-rc1", "21.1.0-rc1"),
+            ("release-20.1.0-ATfE", "20.1.0"),
+        ] {
+            for &(command, parse, _) in COMMANDS {
+                assert_eq!(
+                    parse(value),
+                    Ok(VersionSpec::Exact(ToolchainVersion::named(version))),
+                    "{command} {value}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn printed_versions_parse_back() {
+        for name in ["21.1.0", "19.1.5", "21.1.0-rc1"] {
+            let version = ToolchainVersion::named(name);
+            let mut printed = vec![version.to_string(), version.name.clone()];
+            printed.extend(Product::ALL.iter().map(|product| product.tag_for(&version)));
+
+            for printed in printed {
+                for &(command, parse, _) in COMMANDS {
+                    assert_eq!(
+                        parse(&printed),
+                        Ok(VersionSpec::Exact(version.clone())),
+                        "{command} {printed}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pseudo_versions_ignore_case() {
+        assert_eq!(installable_spec("LATEST"), Ok(VersionSpec::Latest));
+        assert_eq!(removable_spec("All"), Ok(VersionSpec::All));
+        assert_eq!(installed_spec("Previous"), Ok(VersionSpec::Previous));
+    }
+
+    #[test]
+    fn names_are_versions_where_aliases_are_not_accepted() {
+        assert_eq!(
+            VersionSpec::parse("stable", &[SpecKind::Exact, SpecKind::Latest]),
+            Ok(VersionSpec::Exact(ToolchainVersion::named("stable")))
+        );
+    }
+
+    #[test]
+    fn empty_version_is_rejected() {
+        for &(command, parse, _) in COMMANDS {
+            assert_eq!(
+                parse(""),
+                Err("the version can't be empty".to_string()),
+                "{command}"
+            );
+        }
+    }
+}
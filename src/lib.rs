@@ -11,6 +11,9 @@
 //! From there, you can do things like getting a list of installed toolchains, getting their
 //! `bin` paths, downloading a new toolchain, etc.
 //!
+//! Choosing a toolchain's runtime libraries from its `multilib.yaml` needs the `multilib`
+//! feature, which `cli-core` enables.
+//!
 //! ## CLI module
 //!
 //! (Cargo feature: `cli` [default])
@@ -20,6 +23,10 @@
 //! applications using [`clap`]. The functions in this module will print to stdio and read
 //! user input.
 //!
+//! The `cli` feature is made up of two smaller features. `cli-core` contains the argument types
+//! and commands, which print plain text. `cli-interactive` adds colors, progress bars, and
+//! confirmation prompts. Without it, commands that need confirmation must be passed `--yes`.
+//!
 //! ## CLI Binaries
 //!
 //! (Cargo feature: `bin`)
@@ -36,15 +43,23 @@ use std::sync::LazyLock;
 use directories::ProjectDirs;
 
 pub(crate) use fs_err::tokio as fs;
-use tokio_util::sync::CancellationToken;
 use trash::TrashContext;
 
+pub mod cancel;
+#[cfg(feature = "cli-core")]
 pub mod cli;
 pub mod toolchain;
 
-pub static DIRS: LazyLock<ProjectDirs> = LazyLock::new(|| {
-    ProjectDirs::from("dev", "vexide", "arm-toolchain").expect("home directory must be available")
-});
+/// The platform-specific directories used by default, or `None` if the current user has no
+/// home directory (which is common in containers and system services).
+pub static DIRS: LazyLock<Option<ProjectDirs>> =
+    LazyLock::new(|| ProjectDirs::from("dev", "vexide", "arm-toolchain"));
+
+/// Returns the platform-specific directories used by default, or an error if they aren't available.
+pub(crate) fn dirs() -> Result<&'static ProjectDirs, toolchain::ToolchainError> {
+    DIRS.as_ref()
+        .ok_or(toolchain::ToolchainError::NoHomeDirectory)
+}
 
 pub static TRASH: LazyLock<TrashContext> = LazyLock::new(|| {
     #[allow(unused_mut)]
@@ -59,17 +74,3 @@ pub static TRASH: LazyLock<TrashContext> = LazyLock::new(|| {
 
     ctx
 });
-
-trait CheckCancellation {
-    fn check_cancellation<E>(&self, error: E) -> Result<(), E>;
-}
-
-impl CheckCancellation for CancellationToken {
-    fn check_cancellation<E>(&self, error: E) -> Result<(), E> {
-        if self.is_cancelled() {
-            Err(error)
-        } else {
-            Ok(())
-        }
-    }
-}
@@ -1,24 +1,60 @@
-use arm_toolchain::cli::{ArmToolchainCmd, STYLES};
-use clap::Parser;
-use tracing_subscriber::{EnvFilter, util::SubscriberInitExt};
+use std::{fs::OpenOptions, process::ExitCode, sync::Mutex};
 
-#[derive(clap::Parser)]
-#[clap(version, author, styles(STYLES))]
-enum CliArgs {
-    #[clap(flatten)]
-    Cmd(ArmToolchainCmd),
-}
+use arm_toolchain::cli::{CliArgs, Config, LOG_FILE, exit_code, set_offline};
+use clap::Parser;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::{
+    EnvFilter, Layer, filter::Targets, fmt::format::FmtSpan, layer::SubscriberExt,
+    util::SubscriberInitExt,
+};
 
 #[tokio::main]
-async fn main() -> miette::Result<()> {
-    tracing_subscriber::fmt::fmt()
-        .pretty()
-        .with_env_filter(EnvFilter::from_default_env())
-        .finish()
+async fn main() -> miette::Result<ExitCode> {
+    let args = CliArgs::parse();
+
+    // An invalid config file is reported by the command once it loads it, so it's ignored here.
+    let log_file = match args.log_file {
+        Some(path) => Some(path),
+        None => Config::load().await.ok().and_then(|config| config.log_file),
+    };
+
+    let file_layer = log_file.and_then(|path| {
+        let file = match OpenOptions::new().create(true).append(true).open(&path) {
+            Ok(file) => file,
+            Err(error) => {
+                eprintln!(
+                    "warning: could not open log file {}: {error}",
+                    path.display()
+                );
+                return None;
+            }
+        };
+        _ = LOG_FILE.set(path);
+
+        // Only this crate's debug logs are included, since the HTTP client's are very noisy.
+        let layer = tracing_subscriber::fmt::layer()
+            .with_ansi(false)
+            .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE)
+            .with_writer(Mutex::new(file))
+            .with_filter(
+                Targets::new()
+                    .with_target(env!("CARGO_CRATE_NAME"), LevelFilter::DEBUG)
+                    .with_default(LevelFilter::INFO),
+            );
+        Some(layer)
+    });
+
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .pretty()
+                .with_filter(EnvFilter::from_default_env()),
+        )
+        .with(file_layer)
         .init();
 
-    let CliArgs::Cmd(args) = CliArgs::parse();
-    args.run().await?;
+    set_offline(args.offline);
+    args.cmd.run().await?;
 
-    Ok(())
+    Ok(exit_code())
 }
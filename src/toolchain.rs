@@ -12,26 +12,92 @@
 //! allow you to access information such as the filesystem directory where its executables are contained.
 
 use std::{
-    cell::OnceCell,
+    cmp::Ordering,
     fmt::{self, Debug, Display},
-    path::PathBuf,
-    sync::Arc,
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+    time::Duration,
 };
 
+use chrono::{DateTime, Utc};
 use miette::Diagnostic;
 use octocrab::models::repos::{Asset, Release};
-use strum::AsRefStr;
+use serde::{Deserialize, Serialize, Serializer};
+use strum::{AsRefStr, EnumString};
 use thiserror::Error;
-use tracing::{debug, error, trace};
+use tracing::{debug, trace};
 
+mod archive;
+mod asset_name;
+mod auth;
+mod base_url;
+mod builder;
+mod changes;
+mod checksum;
 mod client;
+mod decode;
+mod digests;
+mod download;
 mod extract;
+pub(crate) mod file_lock;
+mod in_use;
+pub mod journal;
+mod layout;
+mod lock;
+mod manifest;
+#[cfg(feature = "multilib")]
+mod multilib;
+mod notes;
+mod permissions;
+mod product;
+mod rate_limit;
+mod reasons;
+mod release_cache;
 mod remove;
+mod report;
+mod resume;
+mod scan;
+mod similar;
+mod space;
+mod structure;
 
+pub use archive::{Archive, ArchiveFormat, ArchiveFormats};
+pub use asset_name::AssetName;
+pub use base_url::BaseUrlProblem;
+pub use builder::*;
+pub use changes::LocalChanges;
 pub use client::*;
+pub use digests::{VerifyProgress, VerifyReport};
+pub use download::{
+    DEFAULT_CONNECT_TIMEOUT, DEFAULT_DOWNLOAD_ATTEMPTS, DEFAULT_DOWNLOAD_BUFFER_SIZE,
+    DEFAULT_DOWNLOAD_STALL_TIMEOUT,
+};
+pub use extract::ExtractLimits;
+pub use in_use::InUseReason;
+pub use layout::{LAYOUT_VERSION, LayoutMigration};
+pub use lock::{LockedAsset, ReleaseLock};
+pub use manifest::{RecordedVersions, ToolchainSize, VersionMatch};
+#[cfg(feature = "multilib")]
+pub use multilib::{
+    CpuInfo, FloatAbi, KNOWN_CPUS, MULTILIB_FILENAME, Multilib, MultilibSelection, MultilibVariant,
+    TargetSpec,
+};
+pub use notes::ReleaseNotes;
+pub use permissions::PermissionPolicy;
+pub use product::Product;
+pub use reasons::InstallReasons;
+pub use release_cache::{DEFAULT_RELEASE_CACHE_TTL, DEFAULT_RELEASE_MEMORY_TTL};
 pub use remove::RemoveProgress;
+pub use report::{
+    AssetDownloadInfo, CancelledState, DigestSource, ErrorReport, FixReport, InstallOutcome,
+    InstallPlan, KeptArchive, PurgeAllReport, PurgeFailure, PurgeReport, PurgedEntry,
+    ReleaseAssetInfo, RemoveReport, VolumeSpace,
+};
 
-static APP_USER_AGENT: &str = concat!(
+/// The user agent sent with every request, unless it's replaced with
+/// [`ToolchainClientBuilder::user_agent`] or a suffix is added with
+/// [`ToolchainClientBuilder::user_agent_suffix`].
+pub const APP_USER_AGENT: &str = concat!(
     "vexide/",
     env!("CARGO_PKG_NAME"),
     "@",
@@ -49,6 +115,20 @@ pub enum ToolchainError {
     )]
     #[diagnostic(code(arm_toolchain::toolchain::latest_release_not_found))]
     LatestReleaseMissing { candidates: Vec<String> },
+    #[error("No release has a version starting with {prefix}")]
+    #[diagnostic(code(arm_toolchain::toolchain::no_matching_release))]
+    #[diagnostic(help(
+        "{}",
+        if candidates.is_empty() {
+            "There are no releases to choose from.".to_string()
+        } else {
+            format!("Releases include: {}", candidates.join(", "))
+        }
+    ))]
+    NoMatchingRelease {
+        prefix: ToolchainVersion,
+        candidates: Vec<String>,
+    },
     #[error(
         "Failed to determine a compatible toolchain asset for {allowed_os:?} {}.\nCandidates:\n{}",
         allowed_arches.iter().map(|a| a.as_ref()).collect::<Vec<_>>().join("/"),
@@ -63,6 +143,12 @@ pub enum ToolchainError {
     #[error("Cannot download {name} because it has an invalid name")]
     #[diagnostic(code(arm_toolchain::toolchain::invalid_asset_name))]
     InvalidAssetName { name: String },
+    #[error("{name:?} can't be used as the name of a toolchain")]
+    #[diagnostic(code(arm_toolchain::toolchain::invalid_toolchain_name))]
+    #[diagnostic(help(
+        "The name is used as the toolchain's directory name, so it can't contain path separators or be a name that's reserved on Windows. Use a name such as `20.1.0-patched`."
+    ))]
+    InvalidToolchainName { name: String },
 
     #[error(
         "The checksum of the downloaded asset did not match the expected value.
@@ -70,8 +156,54 @@ pub enum ToolchainError {
 - Actual: {actual:?}"
     )]
     #[diagnostic(code(arm_toolchain::toolchain::checksum_mismatch))]
-    #[diagnostic(help("the downloaded file may be corrupted or incomplete"))]
-    ChecksumMismatch { expected: String, actual: String },
+    #[diagnostic(help(
+        "The downloaded file may be corrupted or incomplete. If this keeps happening, run `arm-toolchain cache remove {file_name}` to make sure nothing is left over from earlier attempts."
+    ))]
+    ChecksumMismatch {
+        file_name: String,
+        expected: String,
+        actual: String,
+    },
+    #[error("{file_name} isn't listed in the checksum file at {}", path.display())]
+    #[diagnostic(code(arm_toolchain::toolchain::checksum_not_listed))]
+    #[diagnostic(help(
+        "Add the asset's checksum to the file, or pass `--checksum-fallback network` to download it from the release instead."
+    ))]
+    ChecksumNotListed { file_name: String, path: PathBuf },
+    #[error(
+        "{file_name} no longer matches the release lock.
+- Locked: {expected:?}
+- Actual: {actual:?}"
+    )]
+    #[diagnostic(code(arm_toolchain::toolchain::locked_checksum_mismatch))]
+    #[diagnostic(help(
+        "The asset on the server changed since the lock was created. If the change is expected, create a new lock with `arm-toolchain lock`."
+    ))]
+    LockedChecksumMismatch {
+        file_name: String,
+        expected: String,
+        actual: String,
+    },
+    #[error("{file_name} isn't listed in the release lock")]
+    #[diagnostic(code(arm_toolchain::toolchain::asset_not_locked))]
+    #[diagnostic(help(
+        "The lock only lists the assets the release had when it was created. Create a new lock with `arm-toolchain lock`."
+    ))]
+    AssetNotLocked { file_name: String },
+    #[error("The release lock at {} is invalid: {reason}", path.display())]
+    #[diagnostic(code(arm_toolchain::toolchain::invalid_release_lock))]
+    #[diagnostic(help("Create a new lock with `arm-toolchain lock`."))]
+    InvalidReleaseLock { path: PathBuf, reason: String },
+    #[error("Cannot lock {file_name} because its checksum is unavailable: {reason}")]
+    #[diagnostic(code(arm_toolchain::toolchain::asset_digest_unavailable))]
+    #[diagnostic(help(
+        "Every asset's checksum is recorded in the lock. Check your network connection and try again."
+    ))]
+    AssetDigestUnavailable { file_name: String, reason: String },
+    #[error("Could not read the toolchain's multilib file at {}: {reason}", path.display())]
+    #[diagnostic(code(arm_toolchain::toolchain::invalid_multilib))]
+    #[diagnostic(help("The toolchain may be damaged. Reinstall it with `install --force`."))]
+    InvalidMultilib { path: PathBuf, reason: String },
 
     #[error("Could not extract the toolchain asset")]
     #[diagnostic(transparent)]
@@ -81,40 +213,468 @@ pub enum ToolchainError {
     #[diagnostic(code(arm_toolchain::toolchain::cancelled))]
     Cancelled,
 
-    #[error("The toolchain {:?} is not installed.", version.name)]
+    /// An installation was cancelled, along with what it left behind so that it can be
+    /// resumed. This has the same code as [`Cancelled`](Self::Cancelled).
+    #[error("The installation of {} was cancelled during the {} phase", .0.version, .0.phase)]
+    #[diagnostic(code(arm_toolchain::toolchain::cancelled))]
+    InstallCancelled(Box<CancelledState>),
+
+    #[error("The toolchain {version} is not installed.")]
     #[diagnostic(code(arm_toolchain::toolchain::not_installed))]
     ToolchainNotInstalled { version: ToolchainVersion },
 
+    #[error("No file digests were recorded when the toolchain {version} was installed")]
+    #[diagnostic(code(arm_toolchain::toolchain::digests_not_recorded))]
+    #[diagnostic(help(
+        "Reinstall it with `install --force --record-digests` to be able to verify it."
+    ))]
+    DigestsNotRecorded { version: ToolchainVersion },
+
+    #[error(
+        "Could not determine the default toolchains directory because no home directory is available"
+    )]
+    #[diagnostic(code(arm_toolchain::toolchain::no_home_directory))]
+    #[diagnostic(help(
+        "set the HOME environment variable, or specify the toolchains and cache paths explicitly"
+    ))]
+    NoHomeDirectory,
+
+    #[error(
+        "The toolchain extracted to {} is incomplete:\n{}",
+        path.display(),
+        details.iter().map(|detail| format!(" • {detail}")).collect::<Vec<_>>().join("\n")
+    )]
+    #[diagnostic(code(arm_toolchain::toolchain::incomplete_extraction))]
+    #[diagnostic(help(
+        "the downloaded archive may have been truncated. Try installing the toolchain again."
+    ))]
+    IncompleteExtraction { path: PathBuf, details: Vec<String> },
+
+    #[error("Cannot install to {} because it is not empty", path.display())]
+    #[diagnostic(code(arm_toolchain::toolchain::destination_not_empty))]
+    DestinationNotEmpty { path: PathBuf },
+
+    #[error("Cannot install to {} because it is a {kind}", path.display())]
+    #[diagnostic(code(arm_toolchain::toolchain::unsupported_destination))]
+    #[diagnostic(help(
+        "Only directories, regular files, and symlinks are replaced automatically. Move or delete it, then try again."
+    ))]
+    UnsupportedDestination { path: PathBuf, kind: &'static str },
+
+    #[error("Cannot remove or replace {} because {reason}", path.display())]
+    #[diagnostic(code(arm_toolchain::toolchain::toolchain_in_use))]
+    #[diagnostic(help(
+        "Change to a directory outside of it (for example, `cd ~`), and close any programs that are using the toolchain, such as terminals, editors, and build tools. Then try again."
+    ))]
+    ToolchainInUse { path: PathBuf, reason: InUseReason },
+
+    #[error(
+        "Cannot install {version} because a toolchain with a similar name is already installed:\n{}",
+        paths.iter().map(|path| format!(" • {:?}", path)).collect::<Vec<_>>().join("\n")
+    )]
+    #[diagnostic(code(arm_toolchain::toolchain::similar_install_exists))]
+    #[diagnostic(help(
+        "The names only differ by case or whitespace. Rename or remove the existing directory, then try again."
+    ))]
+    SimilarInstallExists {
+        version: ToolchainVersion,
+        paths: Vec<PathBuf>,
+    },
+
+    #[error(
+        "The toolchains directory at {} has layout version {found}, but this version of arm-toolchain only supports up to version {supported}",
+        path.display()
+    )]
+    #[diagnostic(code(arm_toolchain::toolchain::newer_layout))]
+    #[diagnostic(help(
+        "The directory was last used by a newer version of arm-toolchain. Upgrade arm-toolchain to keep using it."
+    ))]
+    NewerLayout {
+        path: PathBuf,
+        found: u32,
+        supported: u32,
+    },
+
+    #[error("The active toolchain file at {} is invalid: {reason}", path.display())]
+    #[diagnostic(code(arm_toolchain::toolchain::invalid_active_toolchain_file))]
+    #[diagnostic(help(
+        "Move it out of the way (or run `arm-toolchain doctor --fix`), then choose the active toolchain again with `arm-toolchain use`."
+    ))]
+    InvalidActiveToolchainFile { path: PathBuf, reason: String },
+
+    #[error("Invalid install reason {reason:?}")]
+    #[diagnostic(code(arm_toolchain::toolchain::invalid_install_reason))]
+    #[diagnostic(help(
+        "Install reasons must contain some text, and can't contain tabs, line breaks, or other control characters."
+    ))]
+    InvalidInstallReason { reason: String },
+
+    #[error("Timed out waiting for another process to update the install reasons")]
+    #[diagnostic(code(arm_toolchain::toolchain::install_reasons_locked))]
+    #[diagnostic(help(
+        "Another process is holding the lock on {}. Try again once it has finished.",
+        path.display()
+    ))]
+    InstallReasonsLocked { path: PathBuf },
+
+    #[error("Invalid user agent {user_agent:?}")]
+    #[diagnostic(code(arm_toolchain::toolchain::invalid_user_agent))]
+    #[diagnostic(help(
+        "The user agent is sent in an HTTP header, so it can only contain visible ASCII characters and spaces."
+    ))]
+    InvalidUserAgent { user_agent: String },
+
+    #[error("The GitHub token can't be sent in an HTTP header")]
+    #[diagnostic(code(arm_toolchain::toolchain::invalid_github_token))]
+    #[diagnostic(help(
+        "Tokens can only contain visible ASCII characters. Check the value of GITHUB_TOKEN or GH_TOKEN."
+    ))]
+    InvalidGitHubToken,
+
+    #[error("Invalid GitHub API URL {url:?} set by {setting}: {problem}")]
+    #[diagnostic(code(arm_toolchain::toolchain::invalid_github_api_url))]
+    #[diagnostic(help(
+        "Set {setting} to the base URL of the API, such as `https://github.example.com/api/v3` for GitHub Enterprise Server, or unset it to use `https://api.github.com`."
+    ))]
+    InvalidGitHubApiUrl {
+        url: String,
+        /// The environment variable or builder method that the URL was set with.
+        setting: &'static str,
+        problem: BaseUrlProblem,
+    },
+
+    #[error("Invalid asset mirror URL {url:?} set by {setting}: {problem}")]
+    #[diagnostic(code(arm_toolchain::toolchain::invalid_mirror_url))]
+    #[diagnostic(help(
+        "Set {setting} to the base URL that the mirror serves GitHub's download paths under, such as `https://mirror.example.com/github/`, or unset it to download from GitHub."
+    ))]
+    InvalidMirrorUrl {
+        url: String,
+        /// The environment variable or builder method that the URL was set with.
+        setting: &'static str,
+        problem: BaseUrlProblem,
+    },
+
+    #[error("Invalid GitHub repository {repo:?}")]
+    #[diagnostic(code(arm_toolchain::toolchain::invalid_github_repo))]
+    #[diagnostic(help(
+        "The repository must be written as `owner/name`. Check the value of ARM_TOOLCHAIN_GITHUB_REPO."
+    ))]
+    InvalidGitHubRepo { repo: String },
+
+    #[error("GitHub's API rate limit was exceeded")]
+    #[diagnostic(code(arm_toolchain::toolchain::rate_limited))]
+    #[diagnostic(help(
+        "The limit resets at {}. Set GITHUB_TOKEN (or GH_TOKEN) to a GitHub access token to get a much higher limit.",
+        rate_limit::describe_reset(resets_at)
+    ))]
+    RateLimited { resets_at: DateTime<Utc> },
+
+    #[error("Cannot {action} because network access is disabled")]
+    #[diagnostic(code(arm_toolchain::toolchain::offline))]
+    #[diagnostic(help(
+        "Only toolchains that are already installed can be used offline. Try again once you're connected."
+    ))]
+    Offline { action: String },
+
     #[error("A request to the GitHub API failed")]
     #[diagnostic(code(arm_toolchain::toolchain::github_api))]
     GitHubApi(#[from] octocrab::Error),
     #[error("Failed to download the toolchain asset")]
     #[diagnostic(code(arm_toolchain::toolchain::download_failed))]
     Reqwest(#[from] reqwest::Error),
+    #[error("The download stalled: no data arrived for {} seconds", timeout.as_secs())]
+    #[diagnostic(code(arm_toolchain::toolchain::download_stalled))]
+    #[diagnostic(help(
+        "The {bytes_written} bytes downloaded so far were kept, so installing again resumes the download."
+    ))]
+    DownloadStalled {
+        timeout: Duration,
+        bytes_written: u64,
+    },
+    #[error("Could not remove {}", path.display())]
+    #[diagnostic(code(arm_toolchain::toolchain::remove_failed))]
+    #[diagnostic(help("Moving it to the trash also failed: {trash_error}"))]
+    RemoveFailed {
+        path: PathBuf,
+        trash_error: trash::Error,
+        #[source]
+        source: Box<ToolchainError>,
+    },
     #[error("Failed to move a file to the trash")]
     #[diagnostic(code(arm_toolchain::toolchain::trash_op_failed))]
     Trash(#[from] trash::Error),
     #[error(transparent)]
+    #[diagnostic(transparent)]
+    UnsupportedHost(#[from] UnsupportedHost),
+    #[error(transparent)]
     #[diagnostic(code(arm_toolchain::toolchain::io_error))]
     Io(#[from] std::io::Error),
 }
 
+impl ToolchainError {
+    const CODES: &[&str] = &[
+        "arm_toolchain::toolchain::latest_release_not_found",
+        "arm_toolchain::toolchain::no_matching_release",
+        "arm_toolchain::toolchain::release_asset_not_found",
+        "arm_toolchain::toolchain::invalid_asset_name",
+        "arm_toolchain::toolchain::invalid_toolchain_name",
+        "arm_toolchain::toolchain::checksum_mismatch",
+        "arm_toolchain::toolchain::checksum_not_listed",
+        "arm_toolchain::toolchain::locked_checksum_mismatch",
+        "arm_toolchain::toolchain::asset_not_locked",
+        "arm_toolchain::toolchain::invalid_release_lock",
+        "arm_toolchain::toolchain::asset_digest_unavailable",
+        "arm_toolchain::toolchain::invalid_multilib",
+        "arm_toolchain::toolchain::cancelled",
+        "arm_toolchain::toolchain::not_installed",
+        "arm_toolchain::toolchain::digests_not_recorded",
+        "arm_toolchain::toolchain::no_home_directory",
+        "arm_toolchain::toolchain::incomplete_extraction",
+        "arm_toolchain::toolchain::destination_not_empty",
+        "arm_toolchain::toolchain::unsupported_destination",
+        "arm_toolchain::toolchain::toolchain_in_use",
+        "arm_toolchain::toolchain::similar_install_exists",
+        "arm_toolchain::toolchain::newer_layout",
+        "arm_toolchain::toolchain::invalid_active_toolchain_file",
+        "arm_toolchain::toolchain::invalid_install_reason",
+        "arm_toolchain::toolchain::install_reasons_locked",
+        "arm_toolchain::toolchain::invalid_user_agent",
+        "arm_toolchain::toolchain::invalid_github_token",
+        "arm_toolchain::toolchain::invalid_github_api_url",
+        "arm_toolchain::toolchain::invalid_mirror_url",
+        "arm_toolchain::toolchain::invalid_github_repo",
+        "arm_toolchain::toolchain::rate_limited",
+        "arm_toolchain::toolchain::offline",
+        "arm_toolchain::toolchain::github_api",
+        "arm_toolchain::toolchain::download_failed",
+        "arm_toolchain::toolchain::download_stalled",
+        "arm_toolchain::toolchain::remove_failed",
+        "arm_toolchain::toolchain::trash_op_failed",
+        "arm_toolchain::toolchain::unsupported_host",
+        "arm_toolchain::toolchain::io_error",
+        "arm_toolchain::extract::dmg_not_supported",
+        "arm_toolchain::extract::unsupported_format",
+        "arm_toolchain::extract::contents_not_found",
+        "arm_toolchain::extract::dmg_failed",
+        "arm_toolchain::extract::zip_failed",
+        "arm_toolchain::extract::suspicious_archive",
+    ];
+
+    /// Returns every diagnostic code that [`stable_code`](Self::stable_code) can return,
+    /// including the codes of errors that occur while extracting a toolchain.
+    pub fn all_codes() -> impl Iterator<Item = &'static str> {
+        Self::CODES.iter().copied()
+    }
+
+    /// Returns the error's diagnostic code, such as
+    /// `arm_toolchain::toolchain::checksum_mismatch`.
+    ///
+    /// This is the code reported through [`miette::Diagnostic::code`], but it's always one of
+    /// [`all_codes`](Self::all_codes), which are guaranteed not to change between releases, so
+    /// it can be used to handle errors programmatically.
+    pub fn stable_code(&self) -> &'static str {
+        listed_code(self, Self::all_codes())
+    }
+
+    /// Converts this error into an owned description of it, including its diagnostic code,
+    /// help text, and causes.
+    ///
+    /// Unlike the error itself, the report can be cloned and serialized, which is useful for
+    /// sending it to another thread or process (such as a GUI's frontend).
+    pub fn to_report(&self) -> ErrorReport {
+        ErrorReport::from_diagnostic(self)
+    }
+}
+
+/// Returns the code in `codes` that's the same as the diagnostic's code.
+///
+/// # Panics
+///
+/// Panics if the diagnostic has no code, or a code that isn't in `codes`. The crate's tests
+/// check that every code it declares is listed.
+pub(crate) fn listed_code(
+    diagnostic: &dyn Diagnostic,
+    codes: impl IntoIterator<Item = &'static str>,
+) -> &'static str {
+    let code = diagnostic
+        .code()
+        .expect("every error has a diagnostic code")
+        .to_string();
+    codes
+        .into_iter()
+        .find(|listed| *listed == code)
+        .unwrap_or_else(|| panic!("the diagnostic code {code} isn't listed"))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum InstallState {
-    DownloadBegin { asset_size: u64, bytes_read: u64 },
-    Download { bytes_read: u64 },
+    DownloadBegin {
+        asset_size: u64,
+        bytes_read: u64,
+    },
+    Download {
+        bytes_read: u64,
+    },
     DownloadFinish,
+    /// An attempt at downloading the asset failed in a way that might not happen again, and
+    /// the next attempt starts after `delay`.
+    ///
+    /// The next attempt resumes the download, and starts with another
+    /// [`DownloadBegin`](Self::DownloadBegin) whose `bytes_read` is how much was kept.
+    DownloadRetry {
+        /// The attempt that failed, starting at 1.
+        attempt: u32,
+        max_attempts: u32,
+        delay: Duration,
+        /// Why the attempt failed.
+        reason: String,
+    },
 
-    VerifyingBegin { asset_size: u64 },
-    Verifying { bytes_read: u64 },
+    VerifyingBegin {
+        asset_size: u64,
+    },
+    Verifying {
+        bytes_read: u64,
+    },
     VerifyingFinish,
+    /// The asset won't be verified, since no checksum was given for it. This takes the place
+    /// of the other verification events.
+    VerifyingSkipped,
+
+    /// Extraction started. The size of the extracted files is estimated if the archive's
+    /// format records it (see [`ExtractCopy`](Self::ExtractCopy) for the actual progress).
+    ExtractBegin {
+        estimated_size: Option<u64>,
+    },
+    RemoveOldInstallBegin,
+    RemoveOldInstallFinish,
+    ExtractCopy {
+        total_size: u64,
+        bytes_copied: u64,
+    },
+
+    ApplyPermissionsBegin {
+        total_entries: u64,
+    },
+    ApplyPermissions {
+        entries_done: u64,
+    },
+    ApplyPermissionsFinish,
 
-    ExtractBegin,
-    ExtractCopy { total_size: u64, bytes_copied: u64 },
     ExtractCleanUp,
     ExtractDone,
+
+    Activated {
+        version: ToolchainVersion,
+    },
 }
 
-#[derive(Debug, AsRefStr, Clone, Copy)]
+impl InstallState {
+    /// Returns the phase of the installation that this event belongs to.
+    pub fn phase(&self) -> Phase {
+        match self {
+            Self::DownloadBegin { .. }
+            | Self::Download { .. }
+            | Self::DownloadFinish
+            | Self::DownloadRetry { .. } => Phase::Download,
+            Self::VerifyingBegin { .. }
+            | Self::Verifying { .. }
+            | Self::VerifyingFinish
+            | Self::VerifyingSkipped => Phase::Verify,
+            Self::ExtractBegin { .. }
+            | Self::RemoveOldInstallBegin
+            | Self::RemoveOldInstallFinish
+            | Self::ExtractCopy { .. } => Phase::Extract,
+            Self::ApplyPermissionsBegin { .. }
+            | Self::ApplyPermissions { .. }
+            | Self::ApplyPermissionsFinish => Phase::Permissions,
+            Self::ExtractCleanUp | Self::ExtractDone => Phase::Cleanup,
+            Self::Activated { .. } => Phase::Activate,
+        }
+    }
+
+    /// Returns whether this is the last event of its phase.
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            Self::DownloadFinish
+                | Self::VerifyingFinish
+                | Self::VerifyingSkipped
+                | Self::ApplyPermissionsFinish
+                | Self::ExtractDone
+                | Self::Activated { .. }
+        )
+    }
+}
+
+/// An update from [`ToolchainClient::install_events`].
+#[derive(Debug)]
+pub enum InstallEvent {
+    /// The installation made progress.
+    Progress(InstallState),
+    /// The installation finished or failed. This is always the last event.
+    Finished(Result<InstallOutcome, ToolchainError>),
+}
+
+/// A phase of an installation or removal, as reported by [`InstallState::phase`] and
+/// [`RemoveProgress::phase`].
+///
+/// Each phase has a short, lowercase name (e.g. `download`) which is used by the crate's
+/// own logs in their `phase` field.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    strum::Display,
+    strum::EnumString,
+    strum::IntoStaticStr,
+    strum::VariantArray,
+    Serialize,
+)]
+#[strum(serialize_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    Download,
+    Verify,
+    /// Extracting the archive, which also includes removing a previous installation.
+    Extract,
+    Permissions,
+    Cleanup,
+    Activate,
+    Remove,
+}
+
+impl Phase {
+    /// Returns the name of the phase.
+    pub fn as_str(self) -> &'static str {
+        self.into()
+    }
+}
+
+/// The current host isn't supported by the Arm Toolchain for Embedded.
+#[derive(Debug, Clone, Error, Diagnostic)]
+#[error("The Arm Toolchain for Embedded is not available for this host ({os} {arch})")]
+#[diagnostic(code(arm_toolchain::toolchain::unsupported_host))]
+pub struct UnsupportedHost {
+    pub os: String,
+    pub arch: String,
+}
+
+impl UnsupportedHost {
+    fn current() -> Self {
+        Self {
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, AsRefStr, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli-core", derive(clap::ValueEnum))]
 pub enum HostOS {
     Darwin,
     Linux,
@@ -122,6 +682,12 @@ pub enum HostOS {
 }
 
 impl HostOS {
+    /// Returns the current host OS.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current OS is not supported. See [`Self::try_current`] for
+    /// a non-panicking version of this function.
     pub const fn current() -> Self {
         if cfg!(target_os = "macos") {
             Self::Darwin
@@ -133,18 +699,43 @@ impl HostOS {
             panic!("This OS is not supported by the ARM toolchain")
         }
     }
+
+    /// Returns the current host OS, or an error if it's not supported.
+    pub fn try_current() -> Result<Self, UnsupportedHost> {
+        Self::for_target(std::env::consts::OS).ok_or_else(UnsupportedHost::current)
+    }
+
+    /// Returns the host OS corresponding to a Rust `target_os` value.
+    pub fn for_target(target_os: &str) -> Option<Self> {
+        match target_os {
+            "macos" => Some(Self::Darwin),
+            "linux" => Some(Self::Linux),
+            "windows" => Some(Self::Windows),
+            _ => None,
+        }
+    }
 }
 
-#[derive(Debug, AsRefStr, Clone, Copy)]
+#[derive(Debug, AsRefStr, EnumString, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli-core", derive(clap::ValueEnum))]
 pub enum HostArch {
     #[strum(serialize = "universal")]
     Universal,
+    #[strum(serialize = "AArch64")]
+    #[cfg_attr(feature = "cli-core", value(name = "aarch64"))]
     AAarch64,
     #[strum(serialize = "x86_64")]
+    #[cfg_attr(feature = "cli-core", value(name = "x86_64"))]
     X86_64,
 }
 
 impl HostArch {
+    /// Returns the architectures that can run on the current host, in order of preference.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current architecture is not supported. See [`Self::try_current`] for
+    /// a non-panicking version of this function.
     pub const fn current() -> &'static [Self] {
         const ALLOWED_ARCHES: &[HostArch] = &[
             #[cfg(target_arch = "x86_64")]
@@ -165,68 +756,179 @@ impl HostArch {
 
         ALLOWED_ARCHES
     }
+
+    /// Returns the architectures that can run on the current host, or an error if
+    /// none of them are supported.
+    pub fn try_current() -> Result<&'static [Self], UnsupportedHost> {
+        Self::for_target(std::env::consts::OS, std::env::consts::ARCH)
+            .ok_or_else(UnsupportedHost::current)
+    }
+
+    /// Returns the architectures that can run on a host with the given Rust `target_os`
+    /// and `target_arch` values, in order of preference.
+    pub fn for_target(target_os: &str, target_arch: &str) -> Option<&'static [Self]> {
+        match (target_os, target_arch) {
+            ("macos", "aarch64") => Some(&[Self::AAarch64, Self::Universal]),
+            ("macos", "x86_64") => Some(&[Self::X86_64, Self::Universal]),
+            (_, "aarch64") => Some(&[Self::AAarch64]),
+            (_, "x86_64") => Some(&[Self::X86_64]),
+            _ => None,
+        }
+    }
+
+    /// Returns the architecture that a release asset was built for, based on its file name
+    /// (such as [`Universal`](Self::Universal) for `ATfE-20.1.0-Darwin-universal.dmg`).
+    pub fn of_asset(file_name: &str) -> Option<Self> {
+        AssetName::parse(file_name).map(|name| name.arch)
+    }
+}
+
+/// Which build to prefer on hosts that can run both a native build and a universal one,
+/// such as Macs.
+///
+/// Universal builds contain a copy of the toolchain for every architecture, so they're
+/// about twice the size of native ones.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchPreference {
+    /// Prefer the build for the host's own architecture.
+    #[default]
+    Native,
+    /// Prefer the universal build.
+    Universal,
+}
+
+impl ArchPreference {
+    /// Returns the preference that selects the given architecture first.
+    pub fn of(arch: HostArch) -> Self {
+        match arch {
+            HostArch::Universal => Self::Universal,
+            _ => Self::Native,
+        }
+    }
+
+    /// Reorders a list of architectures, in order of preference, so that the preferred kind
+    /// of build comes first. The order is otherwise kept.
+    pub fn apply(self, arches: &mut [HostArch]) {
+        arches.sort_by_key(|&arch| Self::of(arch) != self);
+    }
 }
 
+#[derive(Clone)]
 pub struct ToolchainRelease {
     release: Arc<Release>,
-    version: OnceCell<ToolchainVersion>,
+    product: Product,
+    version: OnceLock<ToolchainVersion>,
 }
 
 impl ToolchainRelease {
-    const ALLOWED_EXTENSIONS: &[&str] = &["dmg", "tar.xz", "zip"];
-
     pub fn new(release: Release) -> Self {
+        Self::for_product(release, Product::EMBEDDED)
+    }
+
+    /// Wraps a GitHub release of the given product.
+    pub fn for_product(release: Release, product: Product) -> Self {
         Self {
-            version: OnceCell::new(),
+            version: OnceLock::new(),
+            product,
+            release: Arc::new(release),
+        }
+    }
+
+    /// Wraps a release whose version is already known, instead of being read from its tag.
+    pub(crate) fn with_version(
+        release: Release,
+        product: Product,
+        version: ToolchainVersion,
+    ) -> Self {
+        Self {
+            version: OnceLock::from(version),
+            product,
             release: Arc::new(release),
         }
     }
 
     pub fn version(&self) -> &ToolchainVersion {
         self.version
-            .get_or_init(|| ToolchainVersion::from_tag_name(&self.release.tag_name))
+            .get_or_init(|| self.product.version_from_tag(&self.release.tag_name))
+    }
+
+    /// Returns the name of the release's Git tag.
+    pub fn tag_name(&self) -> &str {
+        &self.release.tag_name
+    }
+
+    /// Returns whether the release is a pre-release, either because it's marked as one on
+    /// GitHub or because its version has a pre-release part (such as `21.1.0-rc1`).
+    pub fn is_prerelease(&self) -> bool {
+        self.release.prerelease || self.version().is_prerelease()
+    }
+
+    /// Returns the product this release belongs to.
+    pub fn product(&self) -> Product {
+        self.product
+    }
+
+    /// Returns the release's title, if it has one.
+    pub fn title(&self) -> Option<String> {
+        self.release
+            .name
+            .as_deref()
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+    }
+
+    /// Returns the release's description in markdown, if it has one.
+    pub fn notes(&self) -> Option<&str> {
+        self.release
+            .body
+            .as_deref()
+            .filter(|body| !body.trim().is_empty())
+    }
+
+    /// Returns when the release was published, if it has been.
+    pub fn published_at(&self) -> Option<DateTime<Utc>> {
+        self.release.published_at
     }
 
+    /// Returns every asset uploaded to this release.
+    pub fn assets(&self) -> &[Asset] {
+        &self.release.assets
+    }
+
+    /// Returns the asset for the given host, in one of the built-in archive formats.
+    ///
+    /// Use [`asset_for_formats`](Self::asset_for_formats) with the client's
+    /// [`archive_formats`](ToolchainClient::archive_formats) if it has other formats.
     pub fn asset_for(
         &self,
         os: HostOS,
         allowed_arches: &[HostArch],
+    ) -> Result<&Asset, ToolchainError> {
+        self.asset_for_formats(os, allowed_arches, ArchiveFormats::builtin())
+    }
+
+    /// Returns the asset for the given host, in one of the given archive formats.
+    ///
+    /// `allowed_arches` is in order of preference, so if the release has assets for several
+    /// of them, the one for the earliest architecture is returned.
+    pub fn asset_for_formats(
+        &self,
+        os: HostOS,
+        allowed_arches: &[HostArch],
+        formats: &ArchiveFormats,
     ) -> Result<&Asset, ToolchainError> {
         debug!(
             options = self.release.assets.len(),
-            ?os, ?allowed_arches, allowed_exts = ?Self::ALLOWED_EXTENSIONS,
+            ?os,
+            ?allowed_arches,
+            ?formats,
             "Searching for a compatible toolchain asset"
         );
 
         let asset = self
-            .release
-            .assets
-            .iter()
-            .find(|a| {
-                let mut components: Vec<&str> = a.name.split('-').collect();
-
-                // Remove the file extension from the last file name component
-                let last_idx = components.len() - 1;
-
-                let (last_component, file_extension) = components[last_idx]
-                    .split_once('.')
-                    .expect("filename has extension");
-                components[last_idx] = last_component;
-
-                let correct_os = components.contains(&os.as_ref());
-                let correct_arch = allowed_arches
-                    .iter()
-                    .any(|arch| components.contains(&arch.as_ref()));
-                let correct_extension = Self::ALLOWED_EXTENSIONS.contains(&file_extension);
-
-                let valid = correct_os && correct_arch && correct_extension;
-                trace!(
-                    name = a.name,
-                    correct_os, correct_arch, correct_extension, "Asset valid: {valid}"
-                );
-
-                valid
-            })
+            .find_asset(os, allowed_arches, formats)
             .ok_or_else(|| ToolchainError::ReleaseAssetMissing {
                 allowed_os: os,
                 allowed_arches: allowed_arches.to_vec(),
@@ -242,6 +944,48 @@ impl ToolchainRelease {
 
         Ok(asset)
     }
+
+    /// Returns whether the release has an asset for the given host.
+    ///
+    /// This is cheaper than checking whether [`asset_for`](Self::asset_for) succeeds, so it's
+    /// better suited to filtering a list of releases.
+    pub fn supports(&self, os: HostOS, allowed_arches: &[HostArch]) -> bool {
+        self.find_asset(os, allowed_arches, ArchiveFormats::builtin())
+            .is_some()
+    }
+
+    /// Finds the asset for the given host. If assets for several of the allowed architectures
+    /// exist, the one for the architecture that comes first is returned.
+    fn find_asset(
+        &self,
+        os: HostOS,
+        allowed_arches: &[HostArch],
+        formats: &ArchiveFormats,
+    ) -> Option<&Asset> {
+        self.release
+            .assets
+            .iter()
+            .filter_map(|a| {
+                let name = AssetName::parse(&a.name)?;
+
+                let correct_os = name.os == os;
+                let arch_rank = allowed_arches.iter().position(|&arch| arch == name.arch);
+                let correct_extension = formats.supports(&a.name);
+
+                let valid = correct_os && arch_rank.is_some() && correct_extension;
+                trace!(
+                    name = a.name,
+                    correct_os,
+                    ?arch_rank,
+                    correct_extension,
+                    "Asset valid: {valid}"
+                );
+
+                valid.then_some((arch_rank?, a))
+            })
+            .min_by_key(|(arch_rank, _)| *arch_rank)
+            .map(|(_, asset)| asset)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -254,43 +998,111 @@ impl ToolchainVersion {
         Self { name: name.into() }
     }
 
+    /// Extracts the version from a release tag of the Arm Toolchain for Embedded.
+    ///
+    /// For other products, use [`Product::version_from_tag`].
     pub fn from_tag_name(tag_name: impl AsRef<str>) -> Self {
-        let mut name = tag_name.as_ref();
-        name = name
-            .strip_prefix(ToolchainClient::RELEASE_PREFIX)
-            .unwrap_or(name);
-        name = name
-            .strip_suffix(ToolchainClient::RELEASE_SUFFIX)
-            .unwrap_or(name);
+        Product::EMBEDDED.version_from_tag(tag_name.as_ref())
+    }
 
-        Self {
-            name: name.to_string(),
+    /// Returns the numeric components of the version (e.g. `[20, 1, 0]` for `20.1.0`), or
+    /// `None` if it isn't made up of dot-separated numbers.
+    ///
+    /// A [pre-release](Self::pre_release) isn't included, so `21.1.0-rc1` is `[21, 1, 0]`.
+    pub fn components(&self) -> Option<Vec<u64>> {
+        let release = self
+            .name
+            .split_once('-')
+            .map_or(&*self.name, |(release, _)| release);
+        release.split('.').map(|part| part.parse().ok()).collect()
+    }
+
+    /// Returns the pre-release part of the version (e.g. `rc1` for `21.1.0-rc1`), if it has
+    /// one.
+    pub fn pre_release(&self) -> Option<&str> {
+        self.name
+            .split_once('-')
+            .map(|(_, pre_release)| pre_release)
+    }
+
+    /// Returns whether this is a pre-release, such as a release candidate.
+    pub fn is_prerelease(&self) -> bool {
+        self.pre_release().is_some()
+    }
+
+    /// Returns whether this is only the start of a version, such as `20` or `20.1`, rather
+    /// than a full version like `20.1.0`.
+    pub fn is_partial(&self) -> bool {
+        !self.is_prerelease() && self.components().is_some_and(|parts| parts.len() < 3)
+    }
+
+    /// Returns whether the version starts with the components of `prefix`, so `20.1.0`
+    /// starts with `20` and `20.1`, but not with `2`.
+    pub fn starts_with(&self, prefix: &ToolchainVersion) -> bool {
+        match (self.components(), prefix.components()) {
+            (Some(parts), Some(prefix)) => parts.starts_with(&prefix),
+            _ => false,
         }
     }
 
-    fn to_tag_name(&self) -> String {
-        format!(
-            "{}{}{}",
-            ToolchainClient::RELEASE_PREFIX,
-            self.name,
-            ToolchainClient::RELEASE_SUFFIX
-        )
+    /// Returns the major version (e.g. `20` for `20.1.0`), if it can be parsed.
+    pub fn major(&self) -> Option<u64> {
+        self.name.split('.').next()?.parse().ok()
     }
 }
 
+/// Versions are displayed with a `v` in front (e.g. `v20.1.0`), which is how they're written in
+/// every message. Parsing the displayed form with [`From<&str>`] gives back the same version.
 impl Display for ToolchainVersion {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "v{}", self.name)
     }
 }
 
+/// Versions are ordered by their numeric components, so `20.1.0` is newer than `19.1.10`.
+/// Pre-releases are older than the release they precede, so `21.1.0-rc1` is between
+/// `20.1.0` and `21.1.0`.
+///
+/// Versions that can't be parsed are considered older than ones that can, and are otherwise
+/// ordered by name.
+impl Ord for ToolchainVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.components(), other.components()) {
+            (Some(a), Some(b)) => a
+                .cmp(&b)
+                .then_with(|| other.is_prerelease().cmp(&self.is_prerelease()))
+                .then_with(|| self.name.cmp(&other.name)),
+            (Some(_), None) => Ordering::Greater,
+            (None, Some(_)) => Ordering::Less,
+            (None, None) => self.name.cmp(&other.name),
+        }
+    }
+}
+
+impl PartialOrd for ToolchainVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Versions are serialized as their bare name (e.g. `"20.1.0"`).
+impl Serialize for ToolchainVersion {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.name)
+    }
+}
+
+/// Parses a version the way it's written by users, which accepts everything that a version is
+/// displayed as: a bare name (`20.1.0`), one with a `v` in front (`v20.1.0`, which is how
+/// versions are displayed), or a product's release tag (`release-20.1.0-ATfE`).
 impl From<&str> for ToolchainVersion {
-    fn from(mut version: &str) -> Self {
-        if let Some(bare) = version.strip_prefix("v") {
-            version = bare;
+    fn from(version: &str) -> Self {
+        if let Some(product) = Product::ALL.iter().find(|p| p.matches_tag(version)) {
+            return product.version_from_tag(version);
         }
 
-        ToolchainVersion::named(version)
+        let bare = version.strip_prefix(['v', 'V']).unwrap_or(version);
+        ToolchainVersion::named(bare)
     }
 }
 
@@ -316,6 +1128,93 @@ impl InstalledToolchain {
         Ok(())
     }
 
+    /// Checks that the toolchain contains the files that every release of the given product
+    /// has, such as `bin/clang`.
+    ///
+    /// Returns [`ToolchainError::IncompleteExtraction`] describing what's missing if it doesn't.
+    pub async fn check_structure(&self, product: Product) -> Result<(), ToolchainError> {
+        structure::check_structure(self, product, None).await
+    }
+
+    /// Restores the execute permission of the toolchain's executables.
+    ///
+    /// Copying a toolchain through a filesystem that doesn't store permissions (such as FAT)
+    /// makes every file non-executable. This looks for files in `bin`, `libexec`, and `lib`
+    /// which are native executables or libraries (detected by their ELF, Mach-O, or PE
+    /// header) but aren't executable, and makes them executable by everyone who can read them.
+    ///
+    /// This is a no-op on non-Unix platforms.
+    pub async fn fix_permissions(&self) -> Result<FixReport, ToolchainError> {
+        permissions::fix_executable_bits(self).await
+    }
+
+    /// Returns the total size of the toolchain's files.
+    ///
+    /// Walking a toolchain's files is slow (especially on network filesystems), so the size
+    /// is cached in a manifest file in the toolchain's directory when it's installed. The
+    /// cached size is used unless `refresh` is true or the modification time of the
+    /// toolchain's directory has changed, in which case it is recomputed and the cache is
+    /// updated.
+    ///
+    /// Only the top-level directory's modification time is checked, so files that are
+    /// changed in place or added to a subdirectory aren't noticed without `refresh`.
+    pub async fn size(&self, refresh: bool) -> Result<ToolchainSize, ToolchainError> {
+        manifest::size(self, refresh).await
+    }
+
+    /// Returns the version of the release this toolchain was installed from, and the version
+    /// in the name of the archive's top-level directory.
+    ///
+    /// These occasionally differ (e.g. `20.1.0-rc1` inside the `20.1.0` release), which is
+    /// only a problem if their major versions differ. See [`RecordedVersions::compare`].
+    ///
+    /// Returns `None` if the versions weren't recorded, which is the case for toolchains
+    /// installed by older versions of this crate.
+    pub async fn recorded_versions(&self) -> Option<RecordedVersions> {
+        manifest::recorded_versions(self).await
+    }
+
+    /// Returns the architecture of the asset this toolchain was installed from, which shows
+    /// whether a native or universal build was installed.
+    ///
+    /// Returns `None` if it wasn't recorded, which is the case for toolchains installed by
+    /// older versions of this crate.
+    pub async fn installed_arch(&self) -> Option<HostArch> {
+        manifest::recorded_arch(self).await
+    }
+
+    /// Returns the notes of the release this toolchain was installed from, which are saved
+    /// when it's installed so that they can be read without network access.
+    ///
+    /// Returns `None` if no notes were saved, which is the case for toolchains installed by
+    /// older versions of this crate.
+    pub async fn release_notes(&self) -> Result<Option<ReleaseNotes>, ToolchainError> {
+        notes::release_notes(self).await
+    }
+
+    /// Returns the files that were modified, added, or deleted since the toolchain was
+    /// installed.
+    ///
+    /// Files are compared by their size and modification time against a list that's recorded
+    /// when the toolchain is installed, so a file that's changed without changing either isn't
+    /// noticed.
+    ///
+    /// Returns `None` if no list was recorded, which is the case for toolchains installed by
+    /// older versions of this crate.
+    pub async fn local_changes(&self) -> Result<Option<LocalChanges>, ToolchainError> {
+        changes::local_changes(self).await
+    }
+
+    /// Copies the modified and added files in `changes` into `destination`, at the same paths
+    /// relative to it as they have in the toolchain.
+    pub async fn backup_local_changes(
+        &self,
+        changes: &LocalChanges,
+        destination: &Path,
+    ) -> Result<(), ToolchainError> {
+        changes::backup_changes(self, changes, destination).await
+    }
+
     /// Returns the path to a directory containing binaries that run on the host.
     ///
     /// This directory typically contains the compiler (`clang`) and support executables
@@ -341,6 +1240,13 @@ impl InstalledToolchain {
         self.path.join("lib").join("clang-runtimes")
     }
 
+    /// Reads the toolchain's `multilib.yaml` file, which describes the runtime libraries it
+    /// ships and which flags select each of them.
+    #[cfg(feature = "multilib")]
+    pub async fn multilib(&self) -> Result<Multilib, ToolchainError> {
+        multilib::read_multilib(self).await
+    }
+
     /// Returns the path to a directory containing static libraries for the given target.
     ///
     /// Targets are considered to have both a triple and a variant. Non-library files
@@ -357,7 +1263,16 @@ impl InstalledToolchain {
     /// - `armv7m_soft_vfpv3_d16_exn_rtti` (ARMv7-M, soft float ABI, vfpv3 FPU, 16 float registers, with RTTI)
     /// - `armv7a_soft_vfpv3_d16_exn_rtti` (ARMv7-M, soft float ABI, vfpv3 FPU, 16 float registers, with RTTI)
     pub fn target_lib_dir(&self, triple: &str, variant: &str) -> PathBuf {
-        self.multilib_dir().join(triple).join(variant).join("lib")
+        self.sysroot(triple, variant).join("lib")
+    }
+
+    /// Returns the path to the sysroot of the given target, which contains its `lib` and
+    /// `include` directories.
+    ///
+    /// Targets are considered to have both a triple and a variant.
+    /// See [`Self::target_lib_dir`] for example triples and variants.
+    pub fn sysroot(&self, triple: &str, variant: &str) -> PathBuf {
+        self.multilib_dir().join(triple).join(variant)
     }
 
     /// Returns the paths to header directories for the given target.
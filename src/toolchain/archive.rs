@@ -0,0 +1,208 @@
+//! The archive formats that toolchains can be extracted from.
+//!
+//! Each format is an [`ArchiveFormat`], and a client extracts downloads with the formats in
+//! its [`ArchiveFormats`]. Asset selection uses the same formats, so an asset is only chosen
+//! if it can be extracted. The built-in formats are DMG, ZIP, and TAR.XZ, and applications
+//! can add their own with
+//! [`ToolchainClientBuilder::register_archive_format`](super::ToolchainClientBuilder::register_archive_format).
+
+use std::{
+    fmt::{self, Debug},
+    path::PathBuf,
+    sync::{Arc, LazyLock},
+};
+
+use futures::future::BoxFuture;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    fs,
+    toolchain::{ExtractLimits, InstallState, ToolchainError, extract},
+};
+
+/// A downloaded archive that's ready to be extracted.
+#[derive(Debug)]
+pub struct Archive {
+    /// The archive, opened for reading and positioned at its start.
+    pub file: fs::File,
+    /// The archive's path in the download cache.
+    pub path: PathBuf,
+    /// Limits on how much data the archive may extract to, which formats should check
+    /// while extracting.
+    pub limits: ExtractLimits,
+}
+
+/// A format that toolchain archives can be extracted from.
+pub trait ArchiveFormat: Send + Sync {
+    /// A short name for the format, such as `tar.xz`, used in logs.
+    fn name(&self) -> &str;
+
+    /// Returns whether an asset with the given file name is in this format.
+    fn matches(&self, file_name: &str) -> bool;
+
+    /// Extracts an archive to `destination`, which doesn't exist yet but whose parent does.
+    ///
+    /// If all of the archive's entries are in one directory, its contents should be
+    /// extracted instead, and its name returned, so that every format produces the same
+    /// layout. `progress` should be sent [`InstallState::ExtractCopy`] updates, and the
+    /// extraction should stop with [`ToolchainError::Cancelled`] once `cancel_token` is
+    /// cancelled.
+    fn extract<'a>(
+        &'a self,
+        archive: Archive,
+        destination: PathBuf,
+        progress: Arc<dyn Fn(InstallState) + Send + Sync>,
+        cancel_token: CancellationToken,
+    ) -> BoxFuture<'a, Result<Option<String>, ToolchainError>>;
+
+    /// Estimates how many bytes extracting an archive will write, without extracting it.
+    ///
+    /// Returns `None` by default, in which case the size is estimated from the size of the
+    /// archive.
+    fn estimated_size<'a>(&'a self, archive: &'a fs::File) -> BoxFuture<'a, Option<u64>> {
+        _ = archive;
+        Box::pin(async { None })
+    }
+}
+
+/// The archive formats known to a client, as returned by
+/// [`ToolchainClient::archive_formats`](super::ToolchainClient::archive_formats).
+#[derive(Clone)]
+pub struct ArchiveFormats {
+    formats: Vec<Arc<dyn ArchiveFormat>>,
+}
+
+impl ArchiveFormats {
+    /// Returns the built-in formats, without creating a new registry.
+    pub fn builtin() -> &'static Self {
+        static BUILTIN: LazyLock<ArchiveFormats> = LazyLock::new(ArchiveFormats::default);
+        &BUILTIN
+    }
+
+    /// Adds a format. It takes precedence over the formats that were added before it,
+    /// including the built-in ones.
+    pub fn register(&mut self, format: Box<dyn ArchiveFormat>) {
+        self.formats.push(Arc::from(format));
+    }
+
+    /// Returns the format of an asset with the given file name, or `None` if it isn't in any
+    /// known format.
+    pub fn find(&self, file_name: &str) -> Option<&dyn ArchiveFormat> {
+        self.formats
+            .iter()
+            .rev()
+            .find(|format| format.matches(file_name))
+            .map(|format| &**format)
+    }
+
+    /// Returns whether an asset with the given file name is in a known format.
+    pub fn supports(&self, file_name: &str) -> bool {
+        self.find(file_name).is_some()
+    }
+}
+
+impl Default for ArchiveFormats {
+    /// Returns the built-in formats.
+    fn default() -> Self {
+        Self {
+            formats: vec![Arc::new(Dmg), Arc::new(Zip), Arc::new(TarXz)],
+        }
+    }
+}
+
+impl Debug for ArchiveFormats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.formats.iter().map(|format| format.name()))
+            .finish()
+    }
+}
+
+/// Apple disk images, which can only be extracted on macOS.
+struct Dmg;
+
+impl ArchiveFormat for Dmg {
+    fn name(&self) -> &str {
+        "dmg"
+    }
+
+    fn matches(&self, file_name: &str) -> bool {
+        file_name.ends_with(".dmg")
+    }
+
+    fn extract<'a>(
+        &'a self,
+        archive: Archive,
+        destination: PathBuf,
+        progress: Arc<dyn Fn(InstallState) + Send + Sync>,
+        cancel_token: CancellationToken,
+    ) -> BoxFuture<'a, Result<Option<String>, ToolchainError>> {
+        Box::pin(async move {
+            extract::macos::extract_dmg(archive.path, &destination, progress, cancel_token).await
+        })
+    }
+}
+
+struct Zip;
+
+impl ArchiveFormat for Zip {
+    fn name(&self) -> &str {
+        "zip"
+    }
+
+    fn matches(&self, file_name: &str) -> bool {
+        file_name.ends_with(".zip")
+    }
+
+    fn extract<'a>(
+        &'a self,
+        archive: Archive,
+        destination: PathBuf,
+        progress: Arc<dyn Fn(InstallState) + Send + Sync>,
+        cancel_token: CancellationToken,
+    ) -> BoxFuture<'a, Result<Option<String>, ToolchainError>> {
+        Box::pin(extract::extract_zip(
+            archive.file,
+            destination,
+            archive.limits,
+            progress,
+            cancel_token,
+        ))
+    }
+
+    fn estimated_size<'a>(&'a self, archive: &'a fs::File) -> BoxFuture<'a, Option<u64>> {
+        Box::pin(extract::zip_estimated_size(archive))
+    }
+}
+
+struct TarXz;
+
+impl ArchiveFormat for TarXz {
+    fn name(&self) -> &str {
+        "tar.xz"
+    }
+
+    fn matches(&self, file_name: &str) -> bool {
+        file_name.ends_with(".tar.xz")
+    }
+
+    fn extract<'a>(
+        &'a self,
+        archive: Archive,
+        destination: PathBuf,
+        progress: Arc<dyn Fn(InstallState) + Send + Sync>,
+        cancel_token: CancellationToken,
+    ) -> BoxFuture<'a, Result<Option<String>, ToolchainError>> {
+        Box::pin(extract::extract_tar_xz(
+            archive.file,
+            destination,
+            archive.limits,
+            progress,
+            cancel_token,
+        ))
+    }
+
+    fn estimated_size<'a>(&'a self, archive: &'a fs::File) -> BoxFuture<'a, Option<u64>> {
+        Box::pin(extract::tar_xz_estimated_size(archive))
+    }
+}
@@ -0,0 +1,153 @@
+//! Parsing the file names of release assets, such as `ATfE-20.1.0-Linux-x86_64.tar.xz`.
+//!
+//! Asset names have been written a few different ways over the years, so they're parsed by
+//! recognizing each `-`-separated part rather than by its position. For example:
+//!
+//! - `ATfE-21.1.0-Linux-AArch64.tar.xz` and `ATfE-20.1.0-Windows-x86_64.zip`, the names used
+//!   by current releases.
+//! - `ATfE-21.1.0-rc1-Darwin-universal.dmg`, a release candidate, whose version is
+//!   `21.1.0-rc1`.
+//! - `LLVMEmbeddedToolchainForArm-19.1.5-Linux-x86_64.tar.xz`, from before the toolchain was
+//!   renamed.
+//! - `ATfE-Linux-x86_64-20.1.0.tar.xz`, with the version after the host.
+//!
+//! Assets that aren't built for a host, such as `ATfE-newlib-overlay-20.1.0.tar.xz` or
+//! checksum files like `ATfE-20.1.0-Linux-x86_64.tar.xz.sha256`, aren't toolchains and don't
+//! parse.
+
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+use crate::toolchain::{HostArch, HostOS, ToolchainError, ToolchainVersion, manifest};
+
+/// The parts of a toolchain asset's file name.
+///
+/// Its [`Display`] implementation writes the name in the form that current releases use, such
+/// as `ATfE-21.1.0-Linux-x86_64.tar.xz`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetName {
+    /// The product's short name, such as `ATfE`.
+    pub product: String,
+    pub version: ToolchainVersion,
+    pub os: HostOS,
+    pub arch: HostArch,
+    /// The file extension without its leading dot, such as `tar.xz` or `dmg`.
+    pub extension: String,
+}
+
+impl AssetName {
+    /// Parses an asset's file name, returning `None` if it isn't the name of a toolchain built
+    /// for a host.
+    pub fn parse(file_name: &str) -> Option<Self> {
+        if file_name.contains(['/', '\\']) {
+            return None;
+        }
+
+        let (stem, extension) = split_extension(file_name)?;
+        let version = manifest::content_version_from_root_name(stem)?;
+        let parts: Vec<&str> = stem.split('-').collect();
+        // The same part that the version was found at.
+        let version_start = parts.iter().position(|part| {
+            part.starts_with(|c: char| c.is_ascii_digit()) && part.contains('.')
+        })?;
+        let version_parts = version_start..version_start + version.name.split('-').count();
+
+        let mut product = vec![];
+        let mut os = None;
+        let mut arch = None;
+        for (index, &part) in parts.iter().enumerate() {
+            if version_parts.contains(&index) {
+                continue;
+            }
+
+            if let Some(part_os) = parse_os(part) {
+                if os.replace(part_os).is_some() {
+                    return None;
+                }
+            } else if let Some(part_arch) = parse_arch(part) {
+                if arch.replace(part_arch).is_some() {
+                    return None;
+                }
+            } else if index < version_start && os.is_none() && arch.is_none() && !part.is_empty() {
+                // The product's name may contain dashes, but always comes first.
+                product.push(part);
+            } else {
+                return None;
+            }
+        }
+
+        if product.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            product: product.join("-"),
+            version,
+            os: os?,
+            arch: arch?,
+            extension: extension.to_string(),
+        })
+    }
+}
+
+impl FromStr for AssetName {
+    type Err = ToolchainError;
+
+    fn from_str(file_name: &str) -> Result<Self, Self::Err> {
+        Self::parse(file_name).ok_or_else(|| ToolchainError::InvalidAssetName {
+            name: file_name.to_string(),
+        })
+    }
+}
+
+impl Display for AssetName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-{}-{}-{}.{}",
+            self.product,
+            self.version.name,
+            self.os.as_ref(),
+            self.arch.as_ref(),
+            self.extension
+        )
+    }
+}
+
+/// Splits a file name into its stem and extension, treating `.tar.*` as a single extension.
+fn split_extension(file_name: &str) -> Option<(&str, &str)> {
+    let (stem, extension) = match file_name.rfind(".tar.") {
+        Some(index) if !file_name[index + ".tar.".len()..].contains('.') => {
+            (&file_name[..index], &file_name[index + 1..])
+        }
+        _ => file_name.rsplit_once('.')?,
+    };
+
+    let valid = !stem.is_empty()
+        && extension
+            .split('.')
+            .all(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_alphanumeric()));
+    valid.then_some((stem, extension))
+}
+
+/// Parses the part of an asset name that names its OS.
+fn parse_os(part: &str) -> Option<HostOS> {
+    match part.to_ascii_lowercase().as_str() {
+        "linux" => Some(HostOS::Linux),
+        "darwin" | "macos" => Some(HostOS::Darwin),
+        "windows" => Some(HostOS::Windows),
+        _ => None,
+    }
+}
+
+/// Parses the part of an asset name that names its architecture.
+fn parse_arch(part: &str) -> Option<HostArch> {
+    match part.to_ascii_lowercase().as_str() {
+        "x86_64" | "amd64" => Some(HostArch::X86_64),
+        "aarch64" | "arm64" => Some(HostArch::AAarch64),
+        "universal" => Some(HostArch::Universal),
+        _ => None,
+    }
+}
@@ -0,0 +1,69 @@
+//! Deciding which requests the GitHub token is sent with.
+//!
+//! Asset URLs can come from anywhere, such as a lock file written on another machine, so the
+//! token isn't a default header of the HTTP client. It's added to each request whose URL is on
+//! GitHub itself, the configured GitHub API, or the configured asset mirror.
+
+use reqwest::{
+    RequestBuilder, Url,
+    header::{AUTHORIZATION, HeaderValue},
+};
+
+use crate::toolchain::ToolchainError;
+
+/// The hosts that the token is sent to even if they weren't configured.
+const GITHUB_ORIGINS: &[&str] = &["https://github.com", "https://api.github.com"];
+
+/// The GitHub token's `Authorization` header, and the origins it may be sent to.
+#[derive(Clone)]
+pub(crate) struct GitHubAuth {
+    header: HeaderValue,
+    origins: Vec<Origin>,
+}
+
+/// The scheme, host, and port of a URL.
+type Origin = (String, String, Option<u16>);
+
+fn origin(url: &Url) -> Option<Origin> {
+    Some((
+        url.scheme().to_string(),
+        url.host_str()?.to_ascii_lowercase(),
+        url.port_or_known_default(),
+    ))
+}
+
+impl GitHubAuth {
+    /// Creates the header for `token`, which may be sent to GitHub and to the origins of the
+    /// given URLs.
+    pub(crate) fn new<'a>(
+        token: &str,
+        trusted: impl IntoIterator<Item = &'a Url>,
+    ) -> Result<Self, ToolchainError> {
+        let mut header = HeaderValue::from_str(&format!("Bearer {token}"))
+            .map_err(|_| ToolchainError::InvalidGitHubToken)?;
+        header.set_sensitive(true);
+
+        let origins = GITHUB_ORIGINS
+            .iter()
+            .map(|url| Url::parse(url).expect("GitHub URLs are valid"))
+            .chain(trusted.into_iter().cloned())
+            .filter_map(|url| origin(&url))
+            .collect();
+
+        Ok(Self { header, origins })
+    }
+
+    /// Returns whether the token may be sent with a request to `url`.
+    pub(crate) fn allows(&self, url: &Url) -> bool {
+        origin(url).is_some_and(|origin| self.origins.contains(&origin))
+    }
+
+    /// Adds the token to a request for `url` if it may be sent there.
+    pub(crate) fn authorize(&self, request: RequestBuilder, url: &Url) -> RequestBuilder {
+        if self.allows(url) {
+            request.header(AUTHORIZATION, self.header.clone())
+        } else {
+            request
+        }
+    }
+}
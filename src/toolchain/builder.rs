@@ -0,0 +1,703 @@
+use std::{
+    env,
+    fmt::{self, Debug},
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use octocrab::Octocrab;
+use reqwest::header::{HeaderValue, USER_AGENT};
+use tracing::{debug, info_span, warn};
+
+use crate::{
+    dirs,
+    toolchain::{
+        APP_USER_AGENT, ActivationPolicy, ArchiveFormat, ArchiveFormats, ChecksumFallback,
+        DEFAULT_CONNECT_TIMEOUT, DEFAULT_DOWNLOAD_ATTEMPTS, DEFAULT_DOWNLOAD_BUFFER_SIZE,
+        DEFAULT_DOWNLOAD_STALL_TIMEOUT, DEFAULT_RELEASE_CACHE_TTL, DEFAULT_RELEASE_MEMORY_TTL,
+        DownloadVia, ExtractLimits, PermissionPolicy, Product, ReleaseLock, ToolchainClient,
+        ToolchainError, auth::GitHubAuth, base_url, in_use, layout, release_cache::MemoryCache,
+    },
+};
+
+/// Configures and creates a [`ToolchainClient`].
+///
+/// Any paths that aren't explicitly set will default to the platform-specific data
+/// directories used by [`ToolchainClient::using_data_dir`].
+#[derive(Debug, Default)]
+pub struct ToolchainClientBuilder {
+    toolchains_path: Option<PathBuf>,
+    cache_path: Option<PathBuf>,
+    download_via: DownloadVia,
+    permission_policy: PermissionPolicy,
+    extract_limits: ExtractLimits,
+    product: Product,
+    keep_failed: bool,
+    record_file_digests: bool,
+    checksum_file: Option<PathBuf>,
+    checksum_fallback: ChecksumFallback,
+    activation_policy: ActivationPolicy,
+    user_agent: Option<String>,
+    user_agent_suffix: Option<String>,
+    octocrab: Option<Arc<Octocrab>>,
+    http_client: Option<reqwest::Client>,
+    release_lock: Option<ReleaseLock>,
+    archive_formats: ArchiveFormats,
+    github_token: Option<GitHubToken>,
+    github_api_url: Option<String>,
+    github_repo: Option<String>,
+    asset_mirror: Option<String>,
+    mirror_fallback: bool,
+    release_cache_ttl: Option<Duration>,
+    release_memory_ttl: Option<Duration>,
+    skip_release_cache_writes: bool,
+    download_buffer_size: Option<usize>,
+    download_stall_timeout: Option<Duration>,
+    download_attempts: Option<u32>,
+    connect_timeout: Option<Duration>,
+    offline: bool,
+    include_prereleases: bool,
+}
+
+/// The environment variables that a GitHub token is read from, in order of priority.
+const GITHUB_TOKEN_VARS: &[&str] = &["GITHUB_TOKEN", "GH_TOKEN"];
+
+/// A GitHub access token, which is never included in debug output.
+#[derive(Clone)]
+struct GitHubToken(String);
+
+impl GitHubToken {
+    /// Reads the token from the first of [`GITHUB_TOKEN_VARS`] that's set and not empty.
+    fn from_env() -> Option<Self> {
+        GITHUB_TOKEN_VARS
+            .iter()
+            .filter_map(|name| env::var(name).ok())
+            .map(|token| token.trim().to_string())
+            .find(|token| !token.is_empty())
+            .map(Self)
+    }
+}
+
+/// The environment variable that the GitHub API's base URL is read from.
+const GITHUB_API_VAR: &str = "ARM_TOOLCHAIN_GITHUB_API";
+/// The environment variable that the repository to read releases from is read from.
+const GITHUB_REPO_VAR: &str = "ARM_TOOLCHAIN_GITHUB_REPO";
+/// The environment variable that the base URL of an asset mirror is read from.
+const MIRROR_VAR: &str = "ARM_TOOLCHAIN_MIRROR";
+
+/// Reads an environment variable, ignoring it if it's empty.
+fn non_empty_var(name: &str) -> Option<String> {
+    env::var(name)
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+impl Debug for GitHubToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("GitHubToken(<redacted>)")
+    }
+}
+
+impl ToolchainClientBuilder {
+    /// Creates a builder with the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the folder that toolchains will be installed in.
+    pub fn toolchains_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.toolchains_path = Some(path.into());
+        self
+    }
+
+    /// Sets the folder that in-progress downloads will be stored in.
+    pub fn cache_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.cache_path = Some(path.into());
+        self
+    }
+
+    /// Sets which endpoint release assets are downloaded from.
+    ///
+    /// See [`DownloadVia`] for more details.
+    pub fn download_via(mut self, download_via: DownloadVia) -> Self {
+        self.download_via = download_via;
+        self
+    }
+
+    /// Sets the permissions that will be applied to newly installed toolchains.
+    ///
+    /// See [`PermissionPolicy`] for more details.
+    pub fn permission_policy(mut self, policy: PermissionPolicy) -> Self {
+        self.permission_policy = policy;
+        self
+    }
+
+    /// Sets the limits on how much data a downloaded archive may extract to.
+    ///
+    /// See [`ExtractLimits`] for more details.
+    pub fn extract_limits(mut self, limits: ExtractLimits) -> Self {
+        self.extract_limits = limits;
+        self
+    }
+
+    /// Sets which product the client installs and manages.
+    ///
+    /// Defaults to [`Product::EMBEDDED`].
+    pub fn product(mut self, product: Product) -> Self {
+        self.product = product;
+        self
+    }
+
+    /// Sets whether a toolchain that failed its post-extraction checks should be kept for
+    /// inspection instead of being deleted.
+    ///
+    /// Toolchains that failed to extract for other reasons are always deleted.
+    pub fn keep_failed(mut self, keep_failed: bool) -> Self {
+        self.keep_failed = keep_failed;
+        self
+    }
+
+    /// Sets whether the SHA-256 digest of every file in a toolchain should be recorded when
+    /// it's installed, so that its contents can later be checked with
+    /// [`ToolchainClient::verify_installed`].
+    ///
+    /// Hashing tens of thousands of files adds noticeably to the time an install takes, so
+    /// this is off by default.
+    pub fn record_file_digests(mut self, record_file_digests: bool) -> Self {
+        self.record_file_digests = record_file_digests;
+        self
+    }
+
+    /// Sets a local file to read the expected checksums of downloaded assets from, instead of
+    /// downloading them from the release.
+    ///
+    /// The file can list the checksums of several assets in the format written by `sha256sum`
+    /// or `shasum --tag`. If the asset being installed isn't listed, installation fails
+    /// unless a fallback is set with [`checksum_fallback`](Self::checksum_fallback).
+    pub fn checksum_file(mut self, path: Option<PathBuf>) -> Self {
+        self.checksum_file = path;
+        self
+    }
+
+    /// Sets what to do when an asset isn't listed in the checksum file.
+    ///
+    /// See [`ChecksumFallback`] for more details.
+    pub fn checksum_fallback(mut self, fallback: ChecksumFallback) -> Self {
+        self.checksum_fallback = fallback;
+        self
+    }
+
+    /// Sets whether newly installed toolchains are activated.
+    ///
+    /// See [`ActivationPolicy`] for more details.
+    pub fn activation_policy(mut self, policy: ActivationPolicy) -> Self {
+        self.activation_policy = policy;
+        self
+    }
+
+    /// Replaces [`APP_USER_AGENT`] as the user agent of the client's requests.
+    ///
+    /// A suffix set with [`user_agent_suffix`](Self::user_agent_suffix) is still appended to
+    /// it. Like a suffix, this makes the client use its own Octocrab instance, unless one is
+    /// given to [`octocrab`](Self::octocrab).
+    ///
+    /// [`build`](Self::build) returns [`ToolchainError::InvalidUserAgent`] if the user agent
+    /// can't be sent in an HTTP header.
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Adds an identifier for the application using this crate (such as `cargo-v5/1.2.3`) to
+    /// the user agent of the client's requests.
+    ///
+    /// The suffix is appended to [`APP_USER_AGENT`] after a space, for both requests to the
+    /// GitHub API and asset downloads. Without a suffix, GitHub API requests are made with
+    /// the shared [`octocrab::instance`], so that any configuration made with
+    /// `octocrab::initialise` applies to them. With one, the client makes them with its own
+    /// instance instead.
+    ///
+    /// [`build`](Self::build) returns [`ToolchainError::InvalidUserAgent`] if the suffix
+    /// can't be sent in an HTTP header.
+    pub fn user_agent_suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.user_agent_suffix = Some(suffix.into());
+        self
+    }
+
+    /// Sets the Octocrab instance that requests to the GitHub API are made with, such as one
+    /// that the application has already configured with authentication, a base URL, or
+    /// middleware.
+    ///
+    /// The instance is used as is: the [`github_token`](Self::github_token),
+    /// [`github_api_url`](Self::github_api_url), and user agent settings aren't applied to it,
+    /// and only affect asset downloads. Without an instance, the client uses the shared
+    /// [`octocrab::instance`] or creates its own, as described in
+    /// [`user_agent_suffix`](Self::user_agent_suffix).
+    pub fn octocrab(mut self, octocrab: Arc<Octocrab>) -> Self {
+        self.octocrab = Some(octocrab);
+        self
+    }
+
+    /// Sets the HTTP client that assets and their checksums are downloaded with, such as one
+    /// that's configured with a proxy, custom root certificates, or a connection pool shared
+    /// with the application.
+    ///
+    /// Like a custom [`octocrab`](Self::octocrab) instance, the client is used as is: the user
+    /// agent and [`github_token`](Self::github_token) aren't added to its requests, so set
+    /// them on the client if they're needed. Without a client, the client creates its own.
+    pub fn http_client(mut self, client: reqwest::Client) -> Self {
+        self.http_client = Some(client);
+        self
+    }
+
+    /// Sets the GitHub access token to authenticate requests with.
+    ///
+    /// Authenticated requests to the GitHub API have a much higher rate limit, which matters
+    /// on CI machines that install a toolchain for every job. The token is also sent with
+    /// asset downloads from GitHub, the [GitHub API](Self::github_api_url), and the
+    /// [asset mirror](Self::asset_mirror), so that private mirrors can be used. It isn't sent
+    /// to any other host, such as one named in a lock file, or to the hosts that requests
+    /// redirect to.
+    ///
+    /// If no token is set, it's read from the `GITHUB_TOKEN` or `GH_TOKEN` environment
+    /// variable. Without a token, the client behaves as described in
+    /// [`user_agent_suffix`](Self::user_agent_suffix).
+    pub fn github_token(mut self, token: Option<String>) -> Self {
+        self.github_token = token.map(GitHubToken);
+        self
+    }
+
+    /// Sets the base URL of the GitHub API that release metadata is fetched from, such as
+    /// `https://github.example.com/api/v3` for a GitHub Enterprise Server that mirrors Arm's
+    /// releases.
+    ///
+    /// Checksums and assets are downloaded from the URLs that the API returns, so they come
+    /// from the mirror too. If no URL is set, it's read from the `ARM_TOOLCHAIN_GITHUB_API`
+    /// environment variable, and `https://api.github.com` is used if that isn't set either.
+    /// Like a token, setting a URL makes the client use its own Octocrab instance.
+    ///
+    /// Trailing slashes are ignored. [`build`](Self::build) returns
+    /// [`ToolchainError::InvalidGitHubApiUrl`] if the URL isn't an `http` or `https` base URL,
+    /// such as when it's missing a scheme or points at a specific endpoint.
+    pub fn github_api_url(mut self, url: Option<String>) -> Self {
+        self.github_api_url = url;
+        self
+    }
+
+    /// Sets the base URL of a mirror that assets and their checksum files are downloaded from
+    /// instead of GitHub, such as `https://mirror.example.com/github/`.
+    ///
+    /// The path of each asset's download URL is kept, so the mirror should have the same
+    /// layout as GitHub's downloads: `ATfE-21.1.0-Linux-x86_64.tar.xz` of the
+    /// `release-21.1.0-ATfE` release would be downloaded from
+    /// `https://mirror.example.com/github/arm/arm-toolchain/releases/download/release-21.1.0-ATfE/ATfE-21.1.0-Linux-x86_64.tar.xz`.
+    /// Release metadata still comes from the GitHub API (see
+    /// [`github_api_url`](Self::github_api_url)), and downloads made through the API (see
+    /// [`DownloadVia`]) aren't mirrored.
+    ///
+    /// If no URL is set, it's read from the `ARM_TOOLCHAIN_MIRROR` environment variable.
+    /// [`build`](Self::build) returns [`ToolchainError::InvalidMirrorUrl`] if the URL isn't
+    /// an `http` or `https` base URL.
+    pub fn asset_mirror(mut self, url: Option<String>) -> Self {
+        self.asset_mirror = url;
+        self
+    }
+
+    /// Downloads an asset from its original URL if the [mirror](Self::asset_mirror) responds
+    /// with `404 Not Found` for it, such as when the mirror hasn't caught up with a new
+    /// release yet. Off by default, so that a mirror that's missing an asset is noticed.
+    pub fn mirror_fallback(mut self, fallback: bool) -> Self {
+        self.mirror_fallback = fallback;
+        self
+    }
+
+    /// Sets the repository that release metadata is read from, as `owner/name`, instead of
+    /// the product's repository.
+    ///
+    /// This is useful with [`github_api_url`](Self::github_api_url) when a mirror publishes
+    /// the releases under a different name. If no repository is set, it's read from the
+    /// `ARM_TOOLCHAIN_GITHUB_REPO` environment variable.
+    ///
+    /// [`build`](Self::build) returns [`ToolchainError::InvalidGitHubRepo`] if the repository
+    /// isn't written as `owner/name`.
+    pub fn github_repo(mut self, repo: Option<String>) -> Self {
+        self.github_repo = repo;
+        self
+    }
+
+    /// Sets how long fetched release metadata is used without asking GitHub whether it
+    /// changed. Defaults to [`DEFAULT_RELEASE_CACHE_TTL`].
+    ///
+    /// The releases are cached in the cache directory along with their ETag. Once they're
+    /// older than the TTL, they're revalidated, which is cheaper than fetching them again and,
+    /// for authenticated clients, doesn't count against GitHub's rate limit if they haven't
+    /// changed. A TTL of zero always revalidates them.
+    pub fn release_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.release_cache_ttl = Some(ttl);
+        self
+    }
+
+    /// Sets how long release metadata that the client fetched is kept in memory, so that
+    /// calls such as [`ToolchainClient::latest_release`] made shortly after each other don't
+    /// read the cache or ask GitHub again. Defaults to [`DEFAULT_RELEASE_MEMORY_TTL`], and is
+    /// never longer than the [`release_cache_ttl`](Self::release_cache_ttl).
+    ///
+    /// Calls made at the same time always share one request, even if the TTL is zero. Clones
+    /// of the client share what it fetched.
+    pub fn release_memory_ttl(mut self, ttl: Duration) -> Self {
+        self.release_memory_ttl = Some(ttl);
+        self
+    }
+
+    /// Stops the client from saving the release metadata it fetches in the cache directory if
+    /// `write` is false, so that nothing is written to disk until something is installed,
+    /// such as for a dry run. Metadata that's already cached is still used.
+    pub fn write_release_cache(mut self, write: bool) -> Self {
+        self.skip_release_cache_writes = !write;
+        self
+    }
+
+    /// Sets how many bytes of a download are gathered in memory before they're written to the
+    /// file. Defaults to [`DEFAULT_DOWNLOAD_BUFFER_SIZE`].
+    ///
+    /// Chunks of the response at least this large are written straight from the buffer they
+    /// were received in. A larger buffer means fewer, larger writes, and a size of zero writes
+    /// every chunk as soon as it arrives.
+    pub fn download_buffer_size(mut self, size: usize) -> Self {
+        self.download_buffer_size = Some(size);
+        self
+    }
+
+    /// Sets how long a download may go without receiving any data before it fails with
+    /// [`ToolchainError::DownloadStalled`]. Defaults to [`DEFAULT_DOWNLOAD_STALL_TIMEOUT`].
+    ///
+    /// The data received before the stall is kept, so installing again resumes the download.
+    /// This is also used as the read timeout of the client's HTTP client, unless one was set
+    /// with [`http_client`](Self::http_client).
+    pub fn download_stall_timeout(mut self, timeout: Duration) -> Self {
+        self.download_stall_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets how many times an asset download is attempted before its error is returned.
+    /// Defaults to [`DEFAULT_DOWNLOAD_ATTEMPTS`], and a value of 1 (or 0) never retries.
+    ///
+    /// Only failures that might not happen again are retried: connection errors, timeouts,
+    /// [stalls](Self::download_stall_timeout), and 5xx responses. Each retry resumes the
+    /// download after a delay that doubles with every attempt, and is reported with
+    /// [`InstallState::DownloadRetry`](super::InstallState::DownloadRetry).
+    pub fn download_attempts(mut self, attempts: u32) -> Self {
+        self.download_attempts = Some(attempts);
+        self
+    }
+
+    /// Sets how long connecting to the server that assets are downloaded from may take.
+    /// Defaults to [`DEFAULT_CONNECT_TIMEOUT`].
+    ///
+    /// This has no effect if an HTTP client was set with [`http_client`](Self::http_client),
+    /// whose own timeouts are used instead.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Always revalidates cached release metadata with GitHub if `refresh` is true, as if the
+    /// [`release_cache_ttl`](Self::release_cache_ttl) was zero.
+    pub fn refresh_releases(self, refresh: bool) -> Self {
+        if refresh {
+            self.release_cache_ttl(Duration::ZERO)
+        } else {
+            self
+        }
+    }
+
+    /// Forbids the client from accessing the network if `offline` is true.
+    ///
+    /// Anything that would contact GitHub, such as fetching a release or downloading an
+    /// asset, returns [`ToolchainError::Offline`] instead. Everything that only reads or
+    /// changes the toolchains directory keeps working, and release metadata cached within the
+    /// [`release_cache_ttl`](Self::release_cache_ttl) is still used.
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Makes [`latest_release`](ToolchainClient::latest_release) and
+    /// [`newest_in_major`](ToolchainClient::newest_in_major) consider pre-releases, such as
+    /// release candidates, if `include` is true. They're skipped by default.
+    pub fn include_prereleases(mut self, include: bool) -> Self {
+        self.include_prereleases = include;
+        self
+    }
+
+    /// Sets a release lock to read the expected checksums of downloaded assets from.
+    ///
+    /// This takes priority over a [`checksum_file`](Self::checksum_file). An asset that isn't
+    /// listed in the lock can't be installed, and a download that doesn't match the lock fails
+    /// with [`ToolchainError::LockedChecksumMismatch`]. See [`ReleaseLock`] for more details.
+    pub fn release_lock(mut self, lock: Option<ReleaseLock>) -> Self {
+        self.release_lock = lock;
+        self
+    }
+
+    /// Adds a format that downloaded toolchains can be extracted from, such as one for
+    /// repackaged `.tar.zst` archives.
+    ///
+    /// It takes precedence over the built-in formats (DMG, ZIP, and TAR.XZ) and any formats
+    /// registered before it. Assets in the format can be selected with
+    /// [`ToolchainRelease::asset_for_formats`](super::ToolchainRelease::asset_for_formats).
+    pub fn register_archive_format(mut self, format: Box<dyn ArchiveFormat>) -> Self {
+        self.archive_formats.register(format);
+        self
+    }
+
+    /// Creates the toolchain client.
+    ///
+    /// The client's directories aren't created until something is written to them.
+    ///
+    /// Returns [`ToolchainError::NoHomeDirectory`] if a path wasn't set and the default
+    /// directories aren't available.
+    pub async fn build(self) -> Result<ToolchainClient, ToolchainError> {
+        // The default directories are only resolved when they're needed, so that clients with
+        // explicit paths can still be created when there's no home directory.
+        let toolchains_path = match self.toolchains_path {
+            Some(path) => path,
+            None => dirs()?.data_local_dir().join("llvm-toolchains"),
+        };
+        let cache_path = match self.cache_path {
+            Some(path) => path,
+            None => dirs()?.cache_dir().join("downloads/llvm-toolchains"),
+        };
+        let github_token = self.github_token.or_else(GitHubToken::from_env);
+        let github_api_url = match self.github_api_url {
+            Some(url) => Some((url, "ToolchainClientBuilder::github_api_url")),
+            None => non_empty_var(GITHUB_API_VAR).map(|url| (url, GITHUB_API_VAR)),
+        };
+        let (repo_owner, repo_name) =
+            match self.github_repo.or_else(|| non_empty_var(GITHUB_REPO_VAR)) {
+                Some(repo) => match repo.split_once('/') {
+                    Some((owner, name))
+                        if !owner.is_empty() && !name.is_empty() && !name.contains('/') =>
+                    {
+                        (owner.to_string(), name.to_string())
+                    }
+                    _ => return Err(ToolchainError::InvalidGitHubRepo { repo }),
+                },
+                None => (
+                    self.product.repo_owner.to_string(),
+                    self.product.repo_name.to_string(),
+                ),
+            };
+        // The URL is checked before anything else is done, so that a typo is reported as soon
+        // as possible, along with where it came from.
+        let github_api_url = github_api_url
+            .map(|(url, setting)| {
+                base_url::parse_api_base_url(
+                    &url,
+                    &format!("repos/{repo_owner}/{repo_name}/releases"),
+                )
+                .map_err(|problem| ToolchainError::InvalidGitHubApiUrl {
+                    url,
+                    setting,
+                    problem,
+                })
+            })
+            .transpose()?;
+        let asset_mirror = match self.asset_mirror {
+            Some(url) => Some((url, "ToolchainClientBuilder::asset_mirror")),
+            None => non_empty_var(MIRROR_VAR).map(|url| (url, MIRROR_VAR)),
+        }
+        .map(|(url, setting)| {
+            base_url::parse_base_url(&url, &format!("{repo_owner}/{repo_name}/")).map_err(
+                |problem| ToolchainError::InvalidMirrorUrl {
+                    url,
+                    setting,
+                    problem,
+                },
+            )
+        })
+        .transpose()?;
+        debug!(
+            ?toolchains_path,
+            ?cache_path,
+            download_via = ?self.download_via,
+            permission_policy = ?self.permission_policy,
+            checksum_file = ?self.checksum_file,
+            checksum_fallback = ?self.checksum_fallback,
+            activation_policy = ?self.activation_policy,
+            product = self.product.id,
+            archive_formats = ?self.archive_formats,
+            release_lock = self.release_lock.is_some(),
+            release_cache_ttl = ?self.release_cache_ttl,
+            release_memory_ttl = ?self.release_memory_ttl,
+            write_release_cache = !self.skip_release_cache_writes,
+            download_buffer_size = ?self.download_buffer_size,
+            download_stall_timeout = ?self.download_stall_timeout,
+            download_attempts = ?self.download_attempts,
+            connect_timeout = ?self.connect_timeout,
+            offline = self.offline,
+            include_prereleases = self.include_prereleases,
+            github_token = github_token.is_some(),
+            github_api_url = github_api_url.as_ref().map(|url| url.as_str()),
+            github_repo = format!("{repo_owner}/{repo_name}"),
+            asset_mirror = asset_mirror.as_ref().map(|url| url.as_str()),
+            mirror_fallback = self.mirror_fallback,
+            // Without a user agent, token, or API URL, the shared Octocrab instance is used,
+            // along with any authentication configured on it. Its token can't be inspected, so
+            // it isn't logged.
+            shared_github_client = self.octocrab.is_none()
+                && self.user_agent.is_none()
+                && self.user_agent_suffix.is_none()
+                && github_token.is_none()
+                && github_api_url.is_none(),
+            custom_github_client = self.octocrab.is_some(),
+            custom_http_client = self.http_client.is_some(),
+            "Initializing toolchain downloader"
+        );
+        let span = info_span!(
+            parent: None,
+            "toolchain_client",
+            product = self.product.id,
+            toolchains_path = %toolchains_path.display(),
+            cache_path = %cache_path.display(),
+        );
+
+        // Directories are created when they're first written to, so reading the current
+        // toolchain doesn't require them to exist.
+        let layout_migrations = layout::migrate_layout(&toolchains_path).await?;
+
+        // Toolchains renamed by an in-use check that was cut off would otherwise look like
+        // they've been removed.
+        let recovered = match in_use::recover_probe(&toolchains_path).await {
+            Ok(_) => in_use::recover_stranded_probes(&toolchains_path.join(self.product.id)).await,
+            Err(error) => Err(error),
+        };
+        if let Err(error) = recovered {
+            warn!(
+                ?error,
+                "Failed to recover directories renamed by an in-use check"
+            );
+        }
+
+        // A broken active toolchain file shouldn't stop every command from working, so it's
+        // treated as if there's no active toolchain until it's fixed.
+        let current_version = match ToolchainClient::read_active_toolchain_file(
+            &toolchains_path
+                .join(self.product.id)
+                .join(ToolchainClient::CURRENT_TOOLCHAIN_FILENAME),
+        )
+        .await
+        {
+            Ok(version) => version,
+            Err(error) => {
+                warn!(%error, "Ignoring the active toolchain file");
+                None
+            }
+        };
+
+        let custom_user_agent = self.user_agent.is_some() || self.user_agent_suffix.is_some();
+        for part in self.user_agent.iter().chain(&self.user_agent_suffix) {
+            if HeaderValue::from_str(part).is_err() || part.trim().is_empty() {
+                return Err(ToolchainError::InvalidUserAgent {
+                    user_agent: part.clone(),
+                });
+            }
+        }
+        let base_user_agent = self.user_agent.as_deref().unwrap_or(APP_USER_AGENT);
+        let user_agent = match &self.user_agent_suffix {
+            Some(suffix) => format!("{base_user_agent} {suffix}"),
+            None => base_user_agent.to_string(),
+        };
+
+        // The token is only sent to GitHub, the configured API, and the mirror. A custom HTTP
+        // client is used as is, without it.
+        let github_auth = github_token
+            .as_ref()
+            .map(|GitHubToken(token)| {
+                GitHubAuth::new(token, github_api_url.iter().chain(&asset_mirror))
+            })
+            .transpose()?
+            .filter(|_| self.http_client.is_none());
+
+        let gh_client = if let Some(octocrab) = self.octocrab {
+            octocrab
+        } else if !custom_user_agent && github_token.is_none() && github_api_url.is_none() {
+            octocrab::instance()
+        } else {
+            let mut builder = Octocrab::builder();
+            if let Some(url) = &github_api_url {
+                builder = builder.base_uri(url.as_str())?;
+            }
+            if custom_user_agent {
+                // Octocrab always sends its own user agent first, so this one is sent after it.
+                builder = builder.add_header(USER_AGENT, user_agent.clone());
+            }
+            if let Some(GitHubToken(token)) = &github_token {
+                builder = builder.personal_token(token.clone());
+            }
+            Arc::new(builder.build()?)
+        };
+
+        let release_cache_ttl = self.release_cache_ttl.unwrap_or(DEFAULT_RELEASE_CACHE_TTL);
+        let release_memory_ttl = self
+            .release_memory_ttl
+            .unwrap_or(DEFAULT_RELEASE_MEMORY_TTL)
+            .min(release_cache_ttl);
+        let download_stall_timeout = self
+            .download_stall_timeout
+            .unwrap_or(DEFAULT_DOWNLOAD_STALL_TIMEOUT);
+
+        let client = match self.http_client {
+            Some(client) => client,
+            None => reqwest::Client::builder()
+                .user_agent(&user_agent)
+                .connect_timeout(self.connect_timeout.unwrap_or(DEFAULT_CONNECT_TIMEOUT))
+                .read_timeout(download_stall_timeout)
+                .build()
+                .unwrap(),
+        };
+
+        Ok(ToolchainClient {
+            gh_client,
+            client,
+            github_auth,
+            toolchains_path,
+            cache_path,
+            download_via: self.download_via,
+            asset_mirror,
+            mirror_fallback: self.mirror_fallback,
+            permission_policy: self.permission_policy,
+            extract_limits: self.extract_limits,
+            product: self.product,
+            repo_owner,
+            repo_name,
+            keep_failed: self.keep_failed,
+            record_file_digests: self.record_file_digests,
+            checksum_file: self.checksum_file,
+            checksum_fallback: self.checksum_fallback,
+            activation_policy: self.activation_policy,
+            current_version: Arc::new(RwLock::new(current_version)),
+            layout_migrations,
+            user_agent,
+            release_lock: self.release_lock.map(Arc::new),
+            archive_formats: self.archive_formats,
+            release_cache_ttl,
+            release_lists: Arc::new(MemoryCache::new(release_memory_ttl)),
+            tagged_releases: Arc::new(MemoryCache::new(release_memory_ttl)),
+            write_release_cache: !self.skip_release_cache_writes,
+            download_buffer_size: self
+                .download_buffer_size
+                .unwrap_or(DEFAULT_DOWNLOAD_BUFFER_SIZE),
+            download_stall_timeout,
+            download_attempts: self
+                .download_attempts
+                .unwrap_or(DEFAULT_DOWNLOAD_ATTEMPTS)
+                .max(1),
+            offline: self.offline,
+            include_prereleases: self.include_prereleases,
+            span,
+        })
+    }
+}
@@ -0,0 +1,197 @@
+//! Detection of files that were changed after a toolchain was installed.
+//!
+//! When a toolchain is installed, the size and modification time of each of its files are
+//! recorded in a file list in its directory. Comparing the toolchain against that list shows
+//! which files were modified, added, or deleted since then, without having to hash every
+//! file of a toolchain that's several gigabytes in size.
+
+use std::{
+    collections::BTreeMap,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use serde::Serialize;
+use tracing::debug;
+
+use crate::{
+    fs,
+    toolchain::{
+        InstalledToolchain, ToolchainError, digests::DIGESTS_FILENAME, manifest::MANIFEST_FILENAME,
+    },
+};
+
+/// The name of the file list in a toolchain's directory.
+pub(crate) const FILE_LIST_FILENAME: &str = ".arm-toolchain-files";
+
+/// The files in a toolchain that differ from when it was installed, as returned by
+/// [`InstalledToolchain::local_changes`] and in the report of
+/// [`ToolchainClient::verify_installed`](super::ToolchainClient::verify_installed).
+///
+/// Paths are relative to the toolchain's directory, and sorted.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct LocalChanges {
+    /// Files whose contents changed. `local_changes` finds these by their size or
+    /// modification time, and `verify_installed` by their digest.
+    pub modified: Vec<PathBuf>,
+    /// Files that weren't part of the toolchain when it was installed.
+    pub added: Vec<PathBuf>,
+    /// Files that were part of the toolchain but no longer exist.
+    pub deleted: Vec<PathBuf>,
+}
+
+impl LocalChanges {
+    /// Returns whether no files changed.
+    pub fn is_empty(&self) -> bool {
+        self.modified.is_empty() && self.added.is_empty() && self.deleted.is_empty()
+    }
+
+    /// Returns the total number of changed files.
+    pub fn len(&self) -> usize {
+        self.modified.len() + self.added.len() + self.deleted.len()
+    }
+}
+
+/// What's recorded about each file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FileState {
+    pub size: u64,
+    /// The modification time in nanoseconds since the Unix epoch, or 0 if it's unavailable.
+    modified: u128,
+}
+
+/// Lists the files and symlinks in a toolchain, keyed by their path relative to it with `/`
+/// as the separator. Symlinks aren't followed.
+pub(crate) async fn scan(
+    toolchain_path: &Path,
+) -> Result<BTreeMap<String, FileState>, ToolchainError> {
+    let mut files = BTreeMap::new();
+    let mut pending = vec![toolchain_path.to_owned()];
+
+    while let Some(dir) = pending.pop() {
+        let mut read_dir = fs::read_dir(&dir).await?;
+        while let Some(entry) = read_dir.next_entry().await? {
+            let path = entry.path();
+            let meta = fs::symlink_metadata(&path).await?;
+            if meta.is_dir() {
+                pending.push(path);
+                continue;
+            }
+
+            let key = relative_key(toolchain_path, &path);
+            if key == MANIFEST_FILENAME || key == FILE_LIST_FILENAME || key == DIGESTS_FILENAME {
+                continue;
+            }
+
+            let modified = meta
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |duration| duration.as_nanos());
+            files.insert(
+                key,
+                FileState {
+                    size: meta.len(),
+                    modified,
+                },
+            );
+        }
+    }
+
+    Ok(files)
+}
+
+fn relative_key(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Parses a file list, which contains one `size\tmodified\tpath` line per file.
+///
+/// Malformed lines are ignored, which makes the files they described show up as added.
+fn parse(text: &str) -> BTreeMap<String, FileState> {
+    text.lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, '\t');
+            let size = parts.next()?.parse().ok()?;
+            let modified = parts.next()?.parse().ok()?;
+            let path = parts.next()?;
+            Some((path.to_string(), FileState { size, modified }))
+        })
+        .collect()
+}
+
+/// Records the current state of a toolchain's files, so that changes to them can be found
+/// with [`local_changes`].
+pub(crate) async fn record_files(toolchain: &InstalledToolchain) -> Result<(), ToolchainError> {
+    let files = scan(&toolchain.path).await?;
+
+    let mut text = String::new();
+    for (path, state) in &files {
+        text += &format!("{}\t{}\t{path}\n", state.size, state.modified);
+    }
+
+    fs::write(toolchain.path.join(FILE_LIST_FILENAME), text).await?;
+    debug!(count = files.len(), ?toolchain.path, "Recorded toolchain files");
+    Ok(())
+}
+
+/// Compares a toolchain's files against the ones recorded when it was installed.
+///
+/// Returns `None` if no file list was recorded, which is the case for toolchains installed
+/// by older versions of this crate.
+pub(crate) async fn local_changes(
+    toolchain: &InstalledToolchain,
+) -> Result<Option<LocalChanges>, ToolchainError> {
+    let recorded = match fs::read_to_string(toolchain.path.join(FILE_LIST_FILENAME)).await {
+        Ok(text) => parse(&text),
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut current = scan(&toolchain.path).await?;
+
+    let mut changes = LocalChanges::default();
+    for (path, state) in recorded {
+        match current.remove(&path) {
+            Some(current_state) if current_state == state => {}
+            Some(_) => changes.modified.push(PathBuf::from(path)),
+            None => changes.deleted.push(PathBuf::from(path)),
+        }
+    }
+    changes.added = current.into_keys().map(PathBuf::from).collect();
+
+    Ok(Some(changes))
+}
+
+/// Copies the modified and added files of a toolchain into `destination`, keeping their
+/// paths relative to the toolchain. Symlinks are copied as symlinks.
+pub(crate) async fn backup_changes(
+    toolchain: &InstalledToolchain,
+    changes: &LocalChanges,
+    destination: &Path,
+) -> Result<(), ToolchainError> {
+    for relative_path in changes.modified.iter().chain(&changes.added) {
+        let source = toolchain.path.join(relative_path);
+        let target = destination.join(relative_path);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        if fs::symlink_metadata(&source).await?.is_symlink() {
+            let link_target = fs::read_link(&source).await?;
+            #[cfg(unix)]
+            fs::symlink(link_target, &target).await?;
+            #[cfg(windows)]
+            fs::symlink_file(link_target, &target).await?;
+        } else {
+            fs::copy(&source, &target).await?;
+        }
+    }
+
+    Ok(())
+}
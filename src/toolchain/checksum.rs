@@ -0,0 +1,728 @@
+//! SHA-256 verification of downloaded archives.
+//!
+//! Hashing a large archive on a slow disk can take minutes, so progress is periodically
+//! saved to a checkpoint file next to the archive. If verification is interrupted, the next
+//! attempt resumes from the last checkpoint as long as the archive hasn't been modified.
+//!
+//! Once an archive has been verified, its checksum is recorded in the same file, so that
+//! installing it again after a failed extraction doesn't need to hash it a second time.
+
+use std::{
+    io::{ErrorKind, SeekFrom},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::UNIX_EPOCH,
+};
+
+use data_encoding::HEXLOWER;
+use tokio::io::{self, AsyncReadExt, AsyncSeekExt, BufReader};
+use tracing::{debug, trace, warn};
+
+use crate::{fs, toolchain::InstallState};
+
+/// How many bytes are hashed between each saved checkpoint.
+const CHECKPOINT_INTERVAL: u64 = 64 * 1024 * 1024;
+
+/// The initial hash values defined by FIPS 180-4.
+const SHA256_INITIAL_STATE: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+const BLOCK_SIZE: usize = 64;
+
+/// A SHA-256 hasher whose intermediate state can be saved and restored.
+struct ResumableSha256 {
+    state: [u32; 8],
+    /// The number of bytes that have been compressed into `state`.
+    compressed_len: u64,
+    /// Bytes that don't yet fill a full block.
+    buffer: Vec<u8>,
+}
+
+impl ResumableSha256 {
+    fn new() -> Self {
+        Self::from_state(SHA256_INITIAL_STATE, 0)
+    }
+
+    fn from_state(state: [u32; 8], compressed_len: u64) -> Self {
+        Self {
+            state,
+            compressed_len,
+            buffer: Vec::with_capacity(BLOCK_SIZE),
+        }
+    }
+
+    fn update(&mut self, mut data: &[u8]) {
+        if !self.buffer.is_empty() {
+            let needed = BLOCK_SIZE - self.buffer.len();
+            let taken = needed.min(data.len());
+            self.buffer.extend_from_slice(&data[..taken]);
+            data = &data[taken..];
+
+            if self.buffer.len() < BLOCK_SIZE {
+                return;
+            }
+
+            let block = std::mem::take(&mut self.buffer);
+            self.compress(&block);
+        }
+
+        let full_len = data.len() - data.len() % BLOCK_SIZE;
+        self.compress(&data[..full_len]);
+        self.buffer.extend_from_slice(&data[full_len..]);
+    }
+
+    fn compress(&mut self, blocks: &[u8]) {
+        for block in blocks.chunks_exact(BLOCK_SIZE) {
+            let block: [u8; BLOCK_SIZE] = block.try_into().unwrap();
+            sha2::compress256(&mut self.state, &[block.into()]);
+        }
+
+        self.compressed_len += blocks.len() as u64;
+    }
+
+    /// Returns the state and length of the hasher if it can be saved without losing data.
+    fn checkpoint(&self) -> Option<([u32; 8], u64)> {
+        self.buffer
+            .is_empty()
+            .then_some((self.state, self.compressed_len))
+    }
+
+    fn finalize(mut self) -> [u8; 32] {
+        let bit_len = (self.compressed_len + self.buffer.len() as u64) * 8;
+
+        let mut padding = vec![0x80];
+        let padded_len = (self.buffer.len() + 1 + 8).next_multiple_of(BLOCK_SIZE);
+        padding.resize(padded_len - self.buffer.len() - 8, 0);
+        padding.extend_from_slice(&bit_len.to_be_bytes());
+        self.update(&padding);
+        debug_assert!(self.buffer.is_empty());
+
+        let mut digest = [0; 32];
+        for (chunk, word) in digest.chunks_exact_mut(4).zip(self.state) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+}
+
+/// The saved state of an interrupted checksum calculation.
+#[derive(Debug, PartialEq, Eq)]
+struct Checkpoint {
+    file_size: u64,
+    modified_nanos: u128,
+    offset: u64,
+    state: [u32; 8],
+}
+
+impl Checkpoint {
+    fn serialize(&self) -> String {
+        let state = self
+            .state
+            .iter()
+            .map(|word| format!("{word:08x}"))
+            .collect::<String>();
+
+        format!(
+            "size={}\nmodified={}\noffset={}\nstate={state}\n",
+            self.file_size, self.modified_nanos, self.offset
+        )
+    }
+
+    fn deserialize(contents: &str) -> Option<Self> {
+        let mut file_size = None;
+        let mut modified_nanos = None;
+        let mut offset = None;
+        let mut state = None;
+
+        for line in contents.lines() {
+            let (key, value) = line.split_once('=')?;
+            match key {
+                "size" => file_size = value.parse().ok(),
+                "modified" => modified_nanos = value.parse().ok(),
+                "offset" => offset = value.parse().ok(),
+                "state" => {
+                    let bytes = HEXLOWER.decode(value.as_bytes()).ok()?;
+                    let mut words = [0; 8];
+                    if bytes.len() != words.len() * 4 {
+                        return None;
+                    }
+
+                    for (word, chunk) in words.iter_mut().zip(bytes.chunks_exact(4)) {
+                        *word = u32::from_be_bytes(chunk.try_into().unwrap());
+                    }
+                    state = Some(words);
+                }
+                _ => {}
+            }
+        }
+
+        Some(Self {
+            file_size: file_size?,
+            modified_nanos: modified_nanos?,
+            offset: offset?,
+            state: state?,
+        })
+    }
+}
+
+/// The checksum of an archive that was completely verified, which is saved in place of a
+/// [`Checkpoint`] once verification finishes.
+#[derive(Debug, PartialEq, Eq)]
+struct VerifiedRecord {
+    file_size: u64,
+    modified_nanos: u128,
+    /// The checksum in lowercase hex format.
+    hex: String,
+}
+
+impl VerifiedRecord {
+    fn serialize(&self) -> String {
+        format!(
+            "size={}\nmodified={}\nsha256={}\n",
+            self.file_size, self.modified_nanos, self.hex
+        )
+    }
+
+    fn deserialize(contents: &str) -> Option<Self> {
+        let mut file_size = None;
+        let mut modified_nanos = None;
+        let mut hex = None;
+
+        for line in contents.lines() {
+            let (key, value) = line.split_once('=')?;
+            match key {
+                "size" => file_size = value.parse().ok(),
+                "modified" => modified_nanos = value.parse().ok(),
+                "sha256" => hex = Some(value.to_string()),
+                _ => {}
+            }
+        }
+
+        Some(Self {
+            file_size: file_size?,
+            modified_nanos: modified_nanos?,
+            hex: hex?,
+        })
+    }
+}
+
+/// A line of a checksums file.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct ChecksumEntry<'a> {
+    /// The name of the file the checksum belongs to, if the line included it.
+    pub file_name: Option<&'a str>,
+    /// The SHA-256 checksum in hex format.
+    pub hex: &'a str,
+}
+
+/// Parses a file containing SHA-256 checksums.
+///
+/// Lines may be in the format written by `sha256sum` (`<hex>  <name>`, optionally with a
+/// `*` before the name), the BSD format written by `shasum --tag` (`SHA256 (<name>) = <hex>`),
+/// or contain only a checksum. Blank lines, comments starting with `#`, and lines that don't
+/// contain a valid checksum are skipped.
+pub(crate) fn parse_checksums(text: &str) -> Vec<ChecksumEntry<'_>> {
+    let is_sha256 = |hex: &str| hex.len() == 64 && hex.bytes().all(|b| b.is_ascii_hexdigit());
+
+    text.lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                return None;
+            }
+
+            let entry = if let Some(rest) = line.strip_prefix("SHA256 (") {
+                let (file_name, hex) = rest.rsplit_once(") = ")?;
+                ChecksumEntry {
+                    file_name: Some(file_name),
+                    hex: hex.trim(),
+                }
+            } else {
+                let (hex, file_name) = match line.split_once(char::is_whitespace) {
+                    Some((hex, name)) => (hex, Some(name.trim_start().trim_start_matches('*'))),
+                    None => (line, None),
+                };
+                ChecksumEntry { file_name, hex }
+            };
+
+            is_sha256(entry.hex).then_some(entry)
+        })
+        .collect()
+}
+
+/// Returns the checksum listed for the given file name in a checksums file.
+///
+/// Names in the file may include a directory (such as `./<name>`), which is ignored.
+pub(crate) fn find_checksum(text: &str, file_name: &str) -> Option<String> {
+    parse_checksums(text)
+        .into_iter()
+        .find(|entry| {
+            entry
+                .file_name
+                .is_some_and(|name| name.rsplit(['/', '\\']).next() == Some(file_name))
+        })
+        .map(|entry| entry.hex.to_ascii_lowercase())
+}
+
+/// The result of hashing a downloaded archive.
+pub(crate) struct FileChecksum {
+    /// The checksum in lowercase hex format.
+    pub hex: String,
+    /// Whether the calculation was resumed from a checkpoint saved by an earlier attempt, or
+    /// skipped because an earlier attempt already verified the file.
+    pub resumed: bool,
+}
+
+/// The extension appended to an archive's name to get the name of its checkpoint file.
+const CHECKPOINT_EXTENSION: &str = ".verify";
+
+/// Returns the path of the checkpoint file used when verifying the given archive.
+pub(crate) fn checkpoint_path(archive_path: &Path) -> PathBuf {
+    let mut path = archive_path.as_os_str().to_owned();
+    path.push(CHECKPOINT_EXTENSION);
+    PathBuf::from(path)
+}
+
+/// Returns the name of the archive that the given checkpoint file belongs to, or `None`
+/// if the file isn't a checkpoint.
+pub(crate) fn archive_name(checkpoint_name: &str) -> Option<&str> {
+    checkpoint_name.strip_suffix(CHECKPOINT_EXTENSION)
+}
+
+/// Deletes the verification checkpoint for the given archive, if there is one.
+pub(crate) async fn discard_checkpoint(archive_path: &Path) -> Result<(), io::Error> {
+    match fs::remove_file(checkpoint_path(archive_path)).await {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Records that the given archive was verified and has the given checksum, replacing its
+/// checkpoint.
+///
+/// If the archive is verified again before it's modified, [`calculate_file_checksum`] will
+/// return the recorded checksum instead of hashing it.
+pub(crate) async fn record_verified(
+    file: &fs::File,
+    archive_path: &Path,
+    hex: &str,
+) -> Result<(), io::Error> {
+    let metadata = file.metadata().await?;
+    let Some(modified_nanos) = modified_nanos(&metadata) else {
+        return discard_checkpoint(archive_path).await;
+    };
+
+    let record = VerifiedRecord {
+        file_size: metadata.len(),
+        modified_nanos,
+        hex: hex.to_ascii_lowercase(),
+    };
+    fs::write(checkpoint_path(archive_path), record.serialize()).await
+}
+
+fn modified_nanos(metadata: &std::fs::Metadata) -> Option<u128> {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_nanos())
+}
+
+/// Scans an entire file and calculates its SHA256 checksum.
+///
+/// If a previous calculation for the same file was interrupted, this will resume from the
+/// last checkpoint it saved, and if the file was already verified (see [`record_verified`]),
+/// the recorded checksum is returned without reading the file. Callers should discard the
+/// checkpoint and try again if a resumed checksum doesn't match the expected value.
+pub(crate) async fn calculate_file_checksum(
+    file: &mut fs::File,
+    archive_path: &Path,
+    progress: Arc<dyn Fn(InstallState) + Send + Sync>,
+) -> Result<FileChecksum, io::Error> {
+    let metadata = file.metadata().await?;
+    let file_size = metadata.len();
+    let modified_nanos = modified_nanos(&metadata);
+
+    progress(InstallState::VerifyingBegin {
+        asset_size: file_size,
+    });
+
+    let checkpoint_path = checkpoint_path(archive_path);
+    let saved = match modified_nanos {
+        Some(_) => fs::read_to_string(&checkpoint_path).await.ok(),
+        None => None,
+    };
+
+    // The progress events are still sent so that progress bars move through each phase.
+    if let Some(record) = saved.as_deref().and_then(VerifiedRecord::deserialize)
+        && record.file_size == file_size
+        && Some(record.modified_nanos) == modified_nanos
+    {
+        debug!("Archive was already verified, skipping checksum calculation");
+        progress(InstallState::Verifying {
+            bytes_read: file_size,
+        });
+        progress(InstallState::VerifyingFinish);
+
+        return Ok(FileChecksum {
+            hex: record.hex,
+            resumed: true,
+        });
+    }
+
+    let checkpoint = match modified_nanos {
+        Some(modified_nanos) => {
+            saved
+                .as_deref()
+                .and_then(Checkpoint::deserialize)
+                .filter(|checkpoint| {
+                    checkpoint.file_size == file_size
+                        && checkpoint.modified_nanos == modified_nanos
+                        && checkpoint.offset <= file_size
+                })
+        }
+        None => None,
+    };
+
+    #[cfg(test)]
+    tests::FILES_HASHED.set(tests::FILES_HASHED.get() + 1);
+
+    let resumed = checkpoint.is_some();
+    let mut hasher = match checkpoint {
+        Some(checkpoint) => {
+            debug!(
+                offset = checkpoint.offset,
+                "Resuming checksum from checkpoint"
+            );
+            ResumableSha256::from_state(checkpoint.state, checkpoint.offset)
+        }
+        None => ResumableSha256::new(),
+    };
+
+    let mut bytes_read = hasher.compressed_len;
+    file.seek(SeekFrom::Start(bytes_read)).await?;
+    let mut reader = BufReader::new(file);
+    let mut data = vec![0; 64 * 1024];
+    let mut last_checkpoint = bytes_read;
+
+    progress(InstallState::Verifying { bytes_read });
+
+    loop {
+        let len = reader.read(&mut data).await?;
+        if len == 0 {
+            break;
+        }
+
+        hasher.update(&data[..len]);
+
+        bytes_read += len as u64;
+        progress(InstallState::Verifying { bytes_read });
+
+        if let Some(modified_nanos) = modified_nanos
+            && bytes_read - last_checkpoint >= CHECKPOINT_INTERVAL
+            && let Some((state, offset)) = hasher.checkpoint()
+        {
+            let checkpoint = Checkpoint {
+                file_size,
+                modified_nanos,
+                offset,
+                state,
+            };
+            trace!(offset, "Saving checksum checkpoint");
+
+            if let Err(error) = fs::write(&checkpoint_path, checkpoint.serialize()).await {
+                warn!(?error, "Failed to save checksum checkpoint");
+            }
+            last_checkpoint = bytes_read;
+        }
+    }
+
+    let checksum = hasher.finalize();
+
+    progress(InstallState::VerifyingFinish);
+
+    Ok(FileChecksum {
+        hex: HEXLOWER.encode(&checksum),
+        resumed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, sync::Mutex};
+
+    use sha2::{Digest, Sha256};
+
+    use super::*;
+
+    thread_local! {
+        /// How many times [`calculate_file_checksum`] has read a file on this thread.
+        pub(super) static FILES_HASHED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    /// Lengths on either side of each place where the padding needs another block.
+    const LENGTHS: &[usize] = &[
+        0, 1, 54, 55, 56, 57, 63, 64, 65, 119, 120, 121, 127, 128, 129, 1000, 4096,
+    ];
+
+    fn data(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 31 % 251) as u8).collect()
+    }
+
+    fn resumed_digest(data: &[u8], offset: usize) -> [u8; 32] {
+        let mut hasher = ResumableSha256::new();
+        hasher.update(&data[..offset]);
+        let (state, compressed_len) = hasher.checkpoint().expect("offset is block-aligned");
+
+        // The state goes through a checkpoint file in between, as it would on disk.
+        let checkpoint = Checkpoint {
+            file_size: data.len() as u64,
+            modified_nanos: 0,
+            offset: compressed_len,
+            state,
+        };
+        let checkpoint = Checkpoint::deserialize(&checkpoint.serialize()).unwrap();
+
+        let mut hasher = ResumableSha256::from_state(checkpoint.state, checkpoint.offset);
+        hasher.update(&data[offset..]);
+        hasher.finalize()
+    }
+
+    #[test]
+    fn matches_sha256() {
+        for &len in LENGTHS {
+            let data = data(len);
+            let mut hasher = ResumableSha256::new();
+            hasher.update(&data);
+            assert_eq!(
+                hasher.finalize(),
+                *Sha256::digest(&data),
+                "{len} byte input"
+            );
+        }
+    }
+
+    #[test]
+    fn matches_sha256_when_updated_in_pieces() {
+        for &len in LENGTHS.iter().filter(|&&len| len <= 129) {
+            let data = data(len);
+            for split in 0..=len {
+                let mut hasher = ResumableSha256::new();
+                hasher.update(&data[..split]);
+                hasher.update(&data[split..]);
+                assert_eq!(
+                    hasher.finalize(),
+                    *Sha256::digest(&data),
+                    "{len} byte input split at {split}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn matches_sha256_when_resumed() {
+        for &len in LENGTHS {
+            let data = data(len);
+            for offset in (0..=len).step_by(BLOCK_SIZE) {
+                assert_eq!(
+                    resumed_digest(&data, offset),
+                    *Sha256::digest(&data),
+                    "{len} byte input resumed at {offset}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn only_checkpoints_whole_blocks() {
+        let mut hasher = ResumableSha256::new();
+        assert_eq!(hasher.checkpoint(), Some((SHA256_INITIAL_STATE, 0)));
+
+        hasher.update(&data(BLOCK_SIZE + 1));
+        assert_eq!(hasher.checkpoint(), None);
+
+        hasher.update(&data(BLOCK_SIZE - 1));
+        assert_eq!(
+            hasher.checkpoint().map(|(_, len)| len),
+            Some(2 * BLOCK_SIZE as u64)
+        );
+    }
+
+    #[tokio::test]
+    async fn resumes_a_file_from_its_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive_path = dir.path().join("archive.tar.xz");
+        let data = data(10 * BLOCK_SIZE + 7);
+        std::fs::write(&archive_path, &data).unwrap();
+
+        let offset = 4 * BLOCK_SIZE;
+        let mut hasher = ResumableSha256::new();
+        hasher.update(&data[..offset]);
+        let (state, _) = hasher.checkpoint().unwrap();
+        let mut file = fs::File::open(&archive_path).await.unwrap();
+        let metadata = file.metadata().await.unwrap();
+        let checkpoint = Checkpoint {
+            file_size: metadata.len(),
+            modified_nanos: modified_nanos(&metadata).unwrap(),
+            offset: offset as u64,
+            state,
+        };
+        std::fs::write(checkpoint_path(&archive_path), checkpoint.serialize()).unwrap();
+
+        let checksum = calculate_file_checksum(&mut file, &archive_path, Arc::new(|_| {}))
+            .await
+            .unwrap();
+
+        assert!(checksum.resumed);
+        assert_eq!(checksum.hex, HEXLOWER.encode(&Sha256::digest(&data)));
+    }
+
+    /// Hashes the archive at `path`, returning the checksum and the progress events sent.
+    async fn checksum_with_events(path: &Path) -> (FileChecksum, Vec<InstallState>) {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut file = fs::File::open(path).await.unwrap();
+        let checksum = calculate_file_checksum(&mut file, path, {
+            let events = events.clone();
+            Arc::new(move |state| events.lock().unwrap().push(state))
+        })
+        .await
+        .unwrap();
+        let events = events.lock().unwrap().clone();
+        (checksum, events)
+    }
+
+    #[tokio::test]
+    async fn skips_hashing_a_verified_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive_path = dir.path().join("archive.tar.xz");
+        let data = data(10 * BLOCK_SIZE + 7);
+        std::fs::write(&archive_path, &data).unwrap();
+
+        let (checksum, _) = checksum_with_events(&archive_path).await;
+        let file = fs::File::open(&archive_path).await.unwrap();
+        record_verified(&file, &archive_path, &checksum.hex)
+            .await
+            .unwrap();
+        let hashed = FILES_HASHED.get();
+
+        let (again, events) = checksum_with_events(&archive_path).await;
+
+        assert_eq!(FILES_HASHED.get(), hashed);
+        assert!(again.resumed);
+        assert_eq!(again.hex, HEXLOWER.encode(&Sha256::digest(&data)));
+        assert_eq!(
+            events,
+            [
+                InstallState::VerifyingBegin {
+                    asset_size: data.len() as u64
+                },
+                InstallState::Verifying {
+                    bytes_read: data.len() as u64
+                },
+                InstallState::VerifyingFinish,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn hashes_a_verified_file_again_once_it_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive_path = dir.path().join("archive.tar.xz");
+        std::fs::write(&archive_path, data(1000)).unwrap();
+
+        let (checksum, _) = checksum_with_events(&archive_path).await;
+        let file = fs::File::open(&archive_path).await.unwrap();
+        record_verified(&file, &archive_path, &checksum.hex)
+            .await
+            .unwrap();
+        let changed = data(1001);
+        std::fs::write(&archive_path, &changed).unwrap();
+        let hashed = FILES_HASHED.get();
+
+        let (again, _) = checksum_with_events(&archive_path).await;
+
+        assert_eq!(FILES_HASHED.get(), hashed + 1);
+        assert!(!again.resumed);
+        assert_eq!(again.hex, HEXLOWER.encode(&Sha256::digest(&changed)));
+    }
+
+    const HEX: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+    #[test]
+    fn parses_each_checksum_format() {
+        let upper = HEX.to_ascii_uppercase();
+        let text = format!(
+            "# Approved toolchains\n\
+             \n\
+             {HEX}  ATfE-21.1.0-Linux-x86_64.tar.xz\n\
+             {HEX} *ATfE-21.1.0-Windows-x86_64.zip\n\
+             SHA256 (ATfE-21.1.0-Darwin-universal.dmg) = {HEX}\n\
+             \t{upper}\n\
+             {HEX}  name with spaces.tar.xz\n"
+        );
+
+        assert_eq!(
+            parse_checksums(&text),
+            [
+                ChecksumEntry {
+                    file_name: Some("ATfE-21.1.0-Linux-x86_64.tar.xz"),
+                    hex: HEX,
+                },
+                ChecksumEntry {
+                    file_name: Some("ATfE-21.1.0-Windows-x86_64.zip"),
+                    hex: HEX,
+                },
+                ChecksumEntry {
+                    file_name: Some("ATfE-21.1.0-Darwin-universal.dmg"),
+                    hex: HEX,
+                },
+                ChecksumEntry {
+                    file_name: None,
+                    hex: &upper,
+                },
+                ChecksumEntry {
+                    file_name: Some("name with spaces.tar.xz"),
+                    hex: HEX,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_lines_without_a_checksum() {
+        let text = format!(
+            "{}  short.tar.xz\n\
+             {}  not-hex.tar.xz\n\
+             md5 (file) = d41d8cd98f00b204e9800998ecf8427e\n\
+             SHA256 (broken.tar.xz) {HEX}\n",
+            &HEX[1..],
+            "z".repeat(64)
+        );
+
+        assert!(parse_checksums(&text).is_empty());
+    }
+
+    #[test]
+    fn finds_a_checksum_by_file_name() {
+        let upper = HEX.to_ascii_uppercase();
+        let text = format!(
+            "{}  ./dist/ATfE-21.1.0-Linux-x86_64.tar.xz\n\
+             SHA256 (ATfE-21.1.0-Linux-AArch64.tar.xz) = {upper}\n\
+             {HEX}\n",
+            "0".repeat(64)
+        );
+
+        assert_eq!(
+            find_checksum(&text, "ATfE-21.1.0-Linux-x86_64.tar.xz"),
+            Some("0".repeat(64))
+        );
+        assert_eq!(
+            find_checksum(&text, "ATfE-21.1.0-Linux-AArch64.tar.xz"),
+            Some(HEX.to_string())
+        );
+        // A bare checksum isn't assumed to belong to any file.
+        assert_eq!(find_checksum(&text, "ATfE-21.1.0-Windows-x86_64.zip"), None);
+        assert_eq!(find_checksum(&text, "x86_64.tar.xz"), None);
+    }
+}
@@ -1,37 +1,202 @@
 use std::{
+    ffi::OsStr,
     fmt::Debug,
     io::{ErrorKind, SeekFrom},
     path::{Path, PathBuf},
-    sync::{Arc, RwLock},
+    pin::Pin,
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
 };
 
 use camino::Utf8Path;
+use chrono::Utc;
 use data_encoding::HEXLOWER;
-use futures::{TryStreamExt, future::join_all};
-use octocrab::{Octocrab, models::repos::Asset};
-use reqwest::header;
+use futures::{Stream, StreamExt, stream};
+use octocrab::{
+    FromResponse, Octocrab, Page,
+    models::repos::{Asset, Release},
+};
+use reqwest::{
+    Method, RequestBuilder, StatusCode, Url,
+    header::{self, HeaderMap, HeaderValue},
+};
 use sha2::{Digest, Sha256};
-use tokio::io::{self, AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader, BufWriter};
+use tokio::{
+    io::AsyncSeekExt,
+    sync::mpsc::{self, UnboundedReceiver},
+};
 use tokio_util::{future::FutureExt as _, sync::CancellationToken};
-use tracing::{debug, info, instrument, trace, warn};
+use tracing::{Span, debug, info, instrument, trace, warn};
 
 use crate::{
-    CheckCancellation, DIRS, TRASH, fs,
+    cancel::CheckCancellation,
+    fs,
     toolchain::{
-        APP_USER_AGENT, InstallState, InstalledToolchain, ToolchainError, ToolchainRelease,
-        ToolchainVersion, extract,
-        remove::{RemoveProgress, remove_dir_progress},
+        Archive, ArchiveFormats, AssetDownloadInfo, AssetName, CancelledState, DigestSource,
+        ExtractLimits, HostArch, HostOS, InstallEvent, InstallOutcome, InstallPlan, InstallReasons,
+        InstallState, InstalledToolchain, KeptArchive, LayoutMigration, LockedAsset,
+        PermissionPolicy, Phase, Product, PurgeAllReport, PurgeFailure, PurgeReport, PurgedEntry,
+        RecordedVersions, ReleaseAssetInfo, ReleaseLock, RemoveReport, ToolchainClientBuilder,
+        ToolchainError, ToolchainRelease, ToolchainVersion, VerifyProgress, VerifyReport,
+        VersionMatch,
+        auth::GitHubAuth,
+        changes, checksum,
+        decode::{self, ContentDecoder},
+        digests, download, extract, in_use,
+        journal::{Intent, Journal, JournalEntry, Operation},
+        layout, lock, manifest, notes, permissions, rate_limit, reasons,
+        release_cache::{self, CachedReleases, MemoryCache},
+        remove::{RemoveProgress, clear_destination, remove_dir_progress, trash_or_remove},
+        resume, scan, similar, space, structure,
     },
 };
 
+/// Roughly how many times larger an extracted toolchain is than its archive, for estimating
+/// the install size before the archive is downloaded.
+const TYPICAL_EXPANSION_RATIO: u64 = 3;
+
+/// How many pages of releases are fetched at most when listing a repository's releases.
+///
+/// At 100 releases per page, this is far more than the repository has, but keeps a
+/// misbehaving server (or API mirror) from being paged through forever.
+const RELEASE_MAX_PAGES: u32 = 10;
+
 /// A client for downloading and installing the Arm Toolchain for Embedded (ATfE).
 #[derive(Clone)]
 pub struct ToolchainClient {
-    gh_client: Arc<Octocrab>,
-    client: reqwest::Client,
-    cache_path: PathBuf,
-    toolchains_path: PathBuf,
-    current_version: Arc<RwLock<Option<ToolchainVersion>>>,
+    pub(crate) gh_client: Arc<Octocrab>,
+    pub(crate) client: reqwest::Client,
+    /// The GitHub token that's added to requests for GitHub and the configured mirror, if any.
+    pub(crate) github_auth: Option<GitHubAuth>,
+    pub(crate) cache_path: PathBuf,
+    pub(crate) toolchains_path: PathBuf,
+    pub(crate) download_via: DownloadVia,
+    /// The base URL that browser downloads are redirected to, if a mirror was configured.
+    pub(crate) asset_mirror: Option<Url>,
+    pub(crate) mirror_fallback: bool,
+    pub(crate) permission_policy: PermissionPolicy,
+    pub(crate) extract_limits: ExtractLimits,
+    pub(crate) product: Product,
+    /// The repository that release metadata is read from, which is the product's unless a
+    /// mirror was configured.
+    pub(crate) repo_owner: String,
+    pub(crate) repo_name: String,
+    pub(crate) keep_failed: bool,
+    pub(crate) record_file_digests: bool,
+    pub(crate) checksum_file: Option<PathBuf>,
+    pub(crate) checksum_fallback: ChecksumFallback,
+    pub(crate) activation_policy: ActivationPolicy,
+    pub(crate) current_version: Arc<RwLock<Option<ToolchainVersion>>>,
+    pub(crate) layout_migrations: Vec<LayoutMigration>,
+    pub(crate) user_agent: String,
+    pub(crate) release_lock: Option<Arc<ReleaseLock>>,
+    pub(crate) archive_formats: ArchiveFormats,
+    pub(crate) release_cache_ttl: Duration,
+    /// The releases this client fetched recently, shared between its clones.
+    pub(crate) release_lists: Arc<MemoryCache<Vec<Release>>>,
+    pub(crate) tagged_releases: Arc<MemoryCache<Release>>,
+    pub(crate) write_release_cache: bool,
+    pub(crate) download_buffer_size: usize,
+    pub(crate) download_stall_timeout: Duration,
+    pub(crate) download_attempts: u32,
+    pub(crate) offline: bool,
+    pub(crate) include_prereleases: bool,
+    /// The parent of every public method's span, which records which directories the client
+    /// uses so that each log line can be attributed to them.
+    pub(crate) span: Span,
+}
+
+/// An operation recorded in the [`Journal`] which was started but never finished.
+#[derive(Debug, Clone)]
+pub struct InterruptedOperation {
+    pub entry: JournalEntry,
+    /// Whether the operation stopped early because of an error or cancellation, rather than
+    /// being cut off by the process exiting.
+    pub failed: bool,
+    /// A file or directory that may have been left in an inconsistent state.
+    pub artifact: Option<PathBuf>,
+}
+
+/// A directory in the toolchains directory that can't be used as a toolchain because of its
+/// name.
+#[derive(Debug, Clone)]
+pub struct UnusableInstall {
+    pub path: PathBuf,
+    pub reason: UnusableReason,
+}
+
+/// Why an [`UnusableInstall`] can't be used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnusableReason {
+    /// The name isn't valid UTF-8, so it can't be represented as a [`ToolchainVersion`].
+    NonUtf8Name,
+    /// The name is reserved on Windows (such as `con` or `aux`), so the directory can't be
+    /// opened there.
+    ReservedName,
+}
+
+impl UnusableReason {
+    /// Returns whether a directory with the given name can't be used as a toolchain, and why.
+    pub(crate) fn of(name: &OsStr) -> Option<Self> {
+        const RESERVED: &[&str] = &["con", "prn", "aux", "nul"];
+
+        let Some(name) = name.to_str() else {
+            return Some(Self::NonUtf8Name);
+        };
+
+        // Windows ignores anything after the first dot, so `aux.1` is reserved too.
+        let stem = name.split('.').next().unwrap_or(name).trim_end();
+        let stem = stem.to_ascii_lowercase();
+        let is_numbered = |prefix: &str| {
+            stem.strip_prefix(prefix)
+                .is_some_and(|n| n.len() == 1 && matches!(n.as_bytes()[0], b'1'..=b'9'))
+        };
+
+        (RESERVED.contains(&stem.as_str()) || is_numbered("com") || is_numbered("lpt"))
+            .then_some(Self::ReservedName)
+    }
+}
+
+/// The endpoint used to download release assets.
+///
+/// Some networks allow access to `api.github.com` but block the domain that GitHub's
+/// browser download URLs redirect to (`objects.githubusercontent.com`). The GitHub REST API
+/// provides an alternate endpoint for downloading assets which can be used in that case.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli-core", derive(clap::ValueEnum))]
+pub enum DownloadVia {
+    /// Use the browser download URL, and fall back to the GitHub API if that fails.
+    #[default]
+    Auto,
+    /// Only use the browser download URL.
+    Browser,
+    /// Only use the GitHub API's asset endpoint.
+    Api,
+}
+
+/// What to do when an asset isn't listed in the checksum file set with
+/// [`ToolchainClientBuilder::checksum_file`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli-core", derive(clap::ValueEnum))]
+pub enum ChecksumFallback {
+    /// Fail with [`ToolchainError::ChecksumNotListed`].
+    #[default]
+    None,
+    /// Download the checksum from the release instead.
+    Network,
+}
+
+/// Whether [`ToolchainClient::download_and_install`] activates the toolchain it installed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ActivationPolicy {
+    /// Never activate installed toolchains, such as when provisioning a machine whose users
+    /// choose a toolchain later.
+    Never,
+    /// Activate the installed toolchain if no toolchain is active.
+    #[default]
+    IfNoneActive,
+    /// Always activate the installed toolchain, replacing the active toolchain.
+    Always,
 }
 
 impl Debug for ToolchainClient {
@@ -39,27 +204,71 @@ impl Debug for ToolchainClient {
         f.debug_struct("ToolchainClient")
             .field("cache_path", &self.cache_path)
             .field("toolchains_path", &self.toolchains_path)
+            .field("product", &self.product.id)
             .finish()
     }
 }
 
 impl ToolchainClient {
+    // These are the repository and tag format of the Arm Toolchain for Embedded. Other
+    // products are described by a `Product`.
     pub const REPO_OWNER: &str = "arm";
     pub const REPO_NAME: &str = "arm-toolchain";
     pub const RELEASE_PREFIX: &str = "release-";
     pub const RELEASE_SUFFIX: &str = "-ATfE"; // arm toolchain for embedded
     pub const CURRENT_TOOLCHAIN_FILENAME: &str = "current.txt";
 
+    /// Returns the product that this client installs.
+    pub fn product(&self) -> Product {
+        self.product
+    }
+
+    /// Returns the archive formats that this client can extract toolchains from.
+    pub fn archive_formats(&self) -> &ArchiveFormats {
+        &self.archive_formats
+    }
+
+    /// Returns whether [`latest_release`](Self::latest_release) considers pre-releases (see
+    /// [`ToolchainClientBuilder::include_prereleases`]).
+    pub fn includes_prereleases(&self) -> bool {
+        self.include_prereleases
+    }
+
+    /// Returns whether the client is forbidden from accessing the network (see
+    /// [`ToolchainClientBuilder::offline`]).
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    /// Returns [`ToolchainError::Offline`] if the client is forbidden from accessing the
+    /// network, which `action` would need to do.
+    fn require_network(&self, action: impl FnOnce() -> String) -> Result<(), ToolchainError> {
+        if self.offline {
+            debug!("Refusing to access the network in offline mode");
+            return Err(ToolchainError::Offline { action: action() });
+        }
+        Ok(())
+    }
+
+    /// Returns the directory that this client's product is installed in.
+    pub(crate) fn product_path(&self) -> PathBuf {
+        self.toolchains_path.join(self.product.id)
+    }
+
+    /// Returns the subject of journal entries about the given version of this client's product.
+    fn journal_subject(&self, version: Option<&ToolchainVersion>) -> String {
+        let name = version.map(|v| v.name.as_str()).unwrap_or_default();
+        format!("{}/{name}", self.product.id)
+    }
+
     /// Creates a new toolchain client that installs to a platform-specific data directory.
     ///
     /// For example, on macOS this is
     /// `~/Library/Application Support/dev.vexide.arm-toolchain/llvm-toolchains`.
+    ///
+    /// Returns [`ToolchainError::NoHomeDirectory`] if the current user has no home directory.
     pub async fn using_data_dir() -> Result<Self, ToolchainError> {
-        Self::new(
-            DIRS.data_local_dir().join("llvm-toolchains"),
-            DIRS.cache_dir().join("downloads/llvm-toolchains"),
-        )
-        .await
+        Self::builder().build().await
     }
 
     /// Creates a client that installs toolchains in the specified folder.
@@ -67,110 +276,490 @@ impl ToolchainClient {
         toolchains_path: impl Into<PathBuf>,
         cache_path: impl Into<PathBuf>,
     ) -> Result<Self, ToolchainError> {
-        let toolchains_path = toolchains_path.into();
-        let cache_path = cache_path.into();
-        trace!(
-            ?toolchains_path,
-            ?cache_path,
-            "Initializing toolchain downloader"
-        );
+        Self::builder()
+            .toolchains_path(toolchains_path)
+            .cache_path(cache_path)
+            .build()
+            .await
+    }
 
-        let (current_version, setup_fut) = tokio::join!(
-            fs::read_to_string(toolchains_path.join(Self::CURRENT_TOOLCHAIN_FILENAME)),
-            async {
-                tokio::try_join!(
-                    fs::create_dir_all(&toolchains_path),
-                    fs::create_dir_all(&cache_path),
-                )
-            },
-        );
+    /// Returns a builder which can be used to further configure a new client.
+    pub fn builder() -> ToolchainClientBuilder {
+        ToolchainClientBuilder::new()
+    }
 
-        setup_fut?;
+    /// Returns whether a GitHub release is one of this client's product's releases.
+    ///
+    /// Both [`latest_release`](Self::latest_release) and [`list_releases`](Self::list_releases)
+    /// use this, so they always agree on which releases exist.
+    ///
+    /// Releases whose versions have a pre-release part (such as `21.1.0-rc1`) count as
+    /// pre-releases even if they aren't marked as one on GitHub.
+    fn is_product_release(&self, release: &Release, include_prereleases: bool) -> bool {
+        self.product.matches_tag(&release.tag_name)
+            && (include_prereleases
+                || !(release.prerelease
+                    || self
+                        .product
+                        .version_from_tag(&release.tag_name)
+                        .is_prerelease()))
+    }
 
-        let current_version = current_version
-            .map(|name| ToolchainVersion::named(name.trim()))
-            .ok();
+    /// Finds the release of the given version in a list of releases.
+    fn find_release(&self, releases: &[Release], version: &ToolchainVersion) -> Option<Release> {
+        releases
+            .iter()
+            .find(|release| {
+                self.product.matches_tag(&release.tag_name)
+                    && self.product.version_from_tag(&release.tag_name) == *version
+            })
+            .cloned()
+    }
+
+    /// Finds the newest release whose version starts with `prefix` (such as the newest
+    /// `20.x.x` release for `20`), unless a release's version is exactly `prefix`.
+    ///
+    /// Pre-releases are only considered if the client includes them.
+    async fn resolve_partial(
+        &self,
+        prefix: &ToolchainVersion,
+    ) -> Result<ToolchainRelease, ToolchainError> {
+        let releases = self.releases().await?;
+        if let Some(release) = self.find_release(&releases, prefix) {
+            return Ok(ToolchainRelease::for_product(release, self.product));
+        }
+
+        let newest = releases
+            .iter()
+            .filter(|release| self.is_product_release(release, self.include_prereleases))
+            .map(|release| (self.product.version_from_tag(&release.tag_name), release))
+            .filter(|(version, _)| version.starts_with(prefix))
+            .max_by(|(a, _), (b, _)| a.cmp(b));
+        if let Some((version, release)) = newest {
+            info!("Resolved {} -> {}", prefix.name, version.name);
+            return Ok(ToolchainRelease::for_product(release.clone(), self.product));
+        }
 
-        Ok(Self {
-            gh_client: octocrab::instance(),
-            client: reqwest::Client::builder()
-                .user_agent(APP_USER_AGENT)
-                .build()
-                .unwrap(),
-            toolchains_path,
-            cache_path,
-            current_version: Arc::new(RwLock::new(current_version)),
+        // Suggest the versions that start with the same text (such as `20.1.0` for `2`), or
+        // else the newest ones.
+        let mut versions = releases
+            .iter()
+            .filter(|release| self.product.matches_tag(&release.tag_name))
+            .map(|release| self.product.version_from_tag(&release.tag_name))
+            .collect::<Vec<_>>();
+        versions.sort_by(|a, b| b.cmp(a));
+        versions.dedup();
+        let similar = versions
+            .iter()
+            .filter(|version| version.name.starts_with(&prefix.name))
+            .cloned()
+            .collect::<Vec<_>>();
+        let candidates = if similar.is_empty() {
+            versions
+        } else {
+            similar
+        };
+
+        Err(ToolchainError::NoMatchingRelease {
+            prefix: prefix.clone(),
+            candidates: candidates
+                .iter()
+                .take(5)
+                .map(|version| match version.is_prerelease() {
+                    true => format!("{version} (pre-release)"),
+                    false => version.to_string(),
+                })
+                .collect(),
         })
     }
 
     /// Fetches the latest release of the Arm Toolchain for Embedded (ATfE) from the ARM GitHub repository.
-    #[instrument(skip(self))]
+    ///
+    /// Pre-releases are skipped, unless the client was built with
+    /// [`ToolchainClientBuilder::include_prereleases`]. The releases are read from the client's
+    /// cache of release metadata if it's fresh enough (see
+    /// [`ToolchainClientBuilder::release_cache_ttl`]).
+    #[instrument(parent = &self.span, skip(self))]
     pub async fn latest_release(&self) -> Result<ToolchainRelease, ToolchainError> {
         debug!("Fetching latest release from GitHub repo");
 
-        let releases = self
-            .gh_client
-            .repos(Self::REPO_OWNER, Self::REPO_NAME)
+        let mut releases = self.releases().await?;
+        match releases
+            .iter()
+            .position(|r| self.is_product_release(r, self.include_prereleases))
+        {
+            Some(position) => Ok(ToolchainRelease::for_product(
+                releases.swap_remove(position),
+                self.product,
+            )),
+            None => Err(ToolchainError::LatestReleaseMissing {
+                candidates: releases.into_iter().map(|r| r.tag_name).collect(),
+            }),
+        }
+    }
+
+    /// Like [`latest_release`](Self::latest_release), but stops with
+    /// [`ToolchainError::Cancelled`] as soon as `cancel_token` is cancelled, instead of waiting
+    /// for GitHub to respond.
+    #[instrument(parent = &self.span, skip_all)]
+    pub async fn latest_release_with_cancellation(
+        &self,
+        cancel_token: &CancellationToken,
+    ) -> Result<ToolchainRelease, ToolchainError> {
+        self.latest_release()
+            .with_cancellation_token(cancel_token)
+            .await
+            .ok_or(ToolchainError::Cancelled)?
+    }
+
+    /// Fetches the releases of the Arm Toolchain for Embedded (ATfE), newest version first.
+    ///
+    /// At most `limit` releases are returned, if it's given. Pre-releases are only included
+    /// if `include_prereleases` is set.
+    ///
+    /// Every release is fetched even with a limit, since GitHub orders releases by when they
+    /// were published rather than by version. Like [`latest_release`](Self::latest_release),
+    /// this uses the client's cache of release metadata.
+    #[instrument(parent = &self.span, skip(self))]
+    pub async fn list_releases(
+        &self,
+        limit: Option<usize>,
+        include_prereleases: bool,
+    ) -> Result<Vec<ToolchainRelease>, ToolchainError> {
+        debug!("Fetching all releases from GitHub repo");
+
+        let mut releases = self
             .releases()
-            .list()
-            .per_page(10)
-            .send()
-            .await?;
+            .await?
+            .into_iter()
+            .filter(|r| self.is_product_release(r, include_prereleases))
+            .map(|r| ToolchainRelease::for_product(r, self.product))
+            .collect::<Vec<_>>();
+        releases.sort_by(|a, b| b.version().cmp(a.version()));
+        if let Some(limit) = limit {
+            releases.truncate(limit);
+        }
 
-        let Some(latest_embedded_release) = releases
-            .items
-            .iter()
-            .find(|r| r.tag_name.ends_with(Self::RELEASE_SUFFIX))
-        else {
-            return Err(ToolchainError::LatestReleaseMissing {
-                candidates: releases.items.into_iter().map(|r| r.tag_name).collect(),
-            });
-        };
+        trace!(count = releases.len(), "Fetched releases");
+        Ok(releases)
+    }
+
+    /// Fetches every release newer than the given version, newest version first.
+    ///
+    /// Use [`ToolchainRelease::supports`] to find out which of them can be installed on a host.
+    #[instrument(parent = &self.span, skip(self))]
+    pub async fn releases_since(
+        &self,
+        version: &ToolchainVersion,
+    ) -> Result<Vec<ToolchainRelease>, ToolchainError> {
+        let mut releases = self.list_releases(None, true).await?;
+        releases.retain(|release| release.version() > version);
+        Ok(releases)
+    }
 
-        Ok(ToolchainRelease::new(latest_embedded_release.clone()))
+    /// Fetches the newest release with the given major version (e.g. the newest `19.x.x`
+    /// release when `major` is 19), if there is one.
+    ///
+    /// Like [`latest_release`](Self::latest_release), pre-releases are only considered if the
+    /// client includes them.
+    #[instrument(parent = &self.span, skip(self))]
+    pub async fn newest_in_major(
+        &self,
+        major: u64,
+    ) -> Result<Option<ToolchainRelease>, ToolchainError> {
+        let releases = self.list_releases(None, self.include_prereleases).await?;
+        Ok(releases
+            .into_iter()
+            .find(|release| release.version().major() == Some(major)))
     }
 
     /// Fetches the given release of the Arm Toolchain for Embedded (ATfE) from the ARM GitHub repository.
-    #[instrument(skip(self))]
+    ///
+    /// A partial version such as `20` or `20.1` resolves to the newest release that starts
+    /// with it, unless a release has exactly that version. Returns
+    /// [`ToolchainError::NoMatchingRelease`] if no release starts with it.
+    #[instrument(parent = &self.span, skip(self))]
     pub async fn get_release(
         &self,
         version: &ToolchainVersion,
     ) -> Result<ToolchainRelease, ToolchainError> {
-        let tag_name = version.to_tag_name();
-        info!(%tag_name, "Fetching release data from GitHub");
+        if version.is_partial() {
+            return self.resolve_partial(version).await;
+        }
+
+        let tag_name = self.product.tag_for(version);
+        let cache_file = self.release_cache_file();
+        if let Some(cached) = release_cache::read(&cache_file).await
+            && cached.is_fresh(self.release_cache_ttl)
+            && let Some(release) = self.find_release(&cached.releases, version)
+        {
+            debug!(tag_name = %release.tag_name, "Using cached release data");
+            return Ok(ToolchainRelease::for_product(release, self.product));
+        }
+
+        // Release candidates aren't always tagged the way the product's releases are (e.g.
+        // `release-21.1.0-ATfE-rc1` instead of `release-21.1.0-rc1-ATfE`), so they're found in
+        // the list of releases first.
+        if version.is_prerelease()
+            && let Some(release) = self.find_release(&self.releases().await?, version)
+        {
+            return Ok(ToolchainRelease::for_product(release, self.product));
+        }
 
+        let route = format!(
+            "/repos/{}/{}/releases/tags/{tag_name}",
+            self.repo_owner, self.repo_name
+        );
         let release = self
-            .gh_client
-            .repos(Self::REPO_OWNER, Self::REPO_NAME)
-            .releases()
-            .get_by_tag(&tag_name)
+            .tagged_releases
+            .get_or_fetch(&route, || async {
+                self.require_network(|| format!("fetch the release tagged {tag_name}"))?;
+                info!(%tag_name, "Fetching release data from GitHub");
+
+                self.github(
+                    self.gh_client
+                        .repos(&self.repo_owner, &self.repo_name)
+                        .releases()
+                        .get_by_tag(&tag_name),
+                )
+                .await
+            })
+            .await?;
+
+        Ok(ToolchainRelease::for_product(release, self.product))
+    }
+
+    /// Like [`get_release`](Self::get_release), but stops with [`ToolchainError::Cancelled`]
+    /// as soon as `cancel_token` is cancelled, instead of waiting for GitHub to respond.
+    #[instrument(parent = &self.span, skip_all)]
+    pub async fn get_release_with_cancellation(
+        &self,
+        version: &ToolchainVersion,
+        cancel_token: &CancellationToken,
+    ) -> Result<ToolchainRelease, ToolchainError> {
+        self.get_release(version)
+            .with_cancellation_token(cancel_token)
+            .await
+            .ok_or(ToolchainError::Cancelled)?
+    }
+
+    /// Returns every release in the repository, in the order GitHub lists them.
+    ///
+    /// Releases this client fetched recently are reused, and concurrent calls share one
+    /// fetch.
+    async fn releases(&self) -> Result<Vec<Release>, ToolchainError> {
+        let route = format!("/repos/{}/{}/releases", self.repo_owner, self.repo_name);
+        self.release_lists
+            .get_or_fetch(&route, || self.fetch_releases())
+            .await
+    }
+
+    /// Reads every release in the repository from the cache, or fetches them from GitHub.
+    ///
+    /// At most [`RELEASE_MAX_PAGES`] pages of releases are fetched, newest first.
+    ///
+    /// The cached releases are used if they're fresh. Otherwise, they're revalidated with
+    /// their ETag, and only fetched again if they changed.
+    async fn fetch_releases(&self) -> Result<Vec<Release>, ToolchainError> {
+        let cache_file = self.release_cache_file();
+        let cached = release_cache::read(&cache_file).await;
+        if let Some(cached) = &cached
+            && cached.is_fresh(self.release_cache_ttl)
+        {
+            trace!(fetched_at = %cached.fetched_at, "Using cached releases");
+            return Ok(cached.releases.clone());
+        }
+        self.require_network(|| "list the releases".to_string())?;
+
+        let mut headers = HeaderMap::new();
+        if let Some(etag) = cached.as_ref().and_then(|cached| cached.etag.as_deref())
+            && let Ok(etag) = HeaderValue::from_str(etag)
+        {
+            headers.insert(header::IF_NONE_MATCH, etag);
+        }
+        let route = format!(
+            "/repos/{}/{}/releases?per_page=100",
+            self.repo_owner, self.repo_name
+        );
+        let response = self
+            .github(self.gh_client._get_with_headers(route, Some(headers)))
             .await?;
 
-        Ok(ToolchainRelease::new(release.clone()))
+        let releases = match cached {
+            Some(cached) if response.status() == StatusCode::NOT_MODIFIED => {
+                debug!("Releases haven't changed since they were cached");
+                CachedReleases {
+                    fetched_at: Utc::now(),
+                    ..cached
+                }
+            }
+            _ => {
+                let response = self.github(octocrab::map_github_error(response)).await?;
+                let etag = response
+                    .headers()
+                    .get(header::ETAG)
+                    .and_then(|etag| etag.to_str().ok())
+                    .map(str::to_string);
+                let mut page = self
+                    .github(Page::<Release>::from_response(response))
+                    .await?;
+                let mut releases = std::mem::take(&mut page.items);
+                for _ in 1..RELEASE_MAX_PAGES {
+                    match self.github(self.gh_client.get_page(&page.next)).await? {
+                        Some(mut next_page) => {
+                            releases.append(&mut next_page.items);
+                            page = next_page;
+                        }
+                        None => break,
+                    }
+                }
+                if page.next.is_some() {
+                    warn!(
+                        count = releases.len(),
+                        "Only the newest releases were fetched, since there are too many pages of them"
+                    );
+                }
+                CachedReleases {
+                    etag,
+                    fetched_at: Utc::now(),
+                    releases,
+                }
+            }
+        };
+
+        // The cache only saves requests, so failing to update it isn't an error.
+        if self.write_release_cache
+            && let Err(error) = release_cache::write(&cache_file, &releases).await
+        {
+            warn!(?error, "Failed to cache the releases");
+        }
+        Ok(releases.releases)
+    }
+
+    /// Returns the path that this client's repository's releases are cached at.
+    fn release_cache_file(&self) -> PathBuf {
+        release_cache::cache_file(&self.cache_path, &self.repo_owner, &self.repo_name)
+    }
+
+    /// Waits for a request to the GitHub API, reporting rate limit errors along with when the
+    /// limit resets.
+    async fn github<T>(
+        &self,
+        request: impl Future<Output = octocrab::Result<T>>,
+    ) -> Result<T, ToolchainError> {
+        match request.await {
+            Ok(value) => Ok(value),
+            Err(error) => Err(rate_limit::github_error(&self.gh_client, error).await),
+        }
+    }
+
+    /// Returns the path in the download cache that the given asset is downloaded to.
+    ///
+    /// The path is the cache directory joined with the asset's file name, and never depends on
+    /// anything else, such as the release, the host, or how the asset is downloaded. A file
+    /// that's placed at this path beforehand (for example, by a CI cache step or another
+    /// downloader) is resumed if it's partial, and only verified if it's complete.
+    ///
+    /// Returns [`ToolchainError::InvalidAssetName`] if the asset's name has no file name.
+    pub fn cache_path_for(&self, asset: &Asset) -> Result<PathBuf, ToolchainError> {
+        self.cache_path_for_name(&asset.name)
+    }
+
+    /// Like [`cache_path_for`](Self::cache_path_for), but takes the asset's name, such as
+    /// `ATfE-20.1.0-Linux-x86_64.tar.xz`, instead of the asset.
+    pub fn cache_path_for_name(&self, asset_name: &str) -> Result<PathBuf, ToolchainError> {
+        Ok(self.cache_path.join(asset_file_name(asset_name)?))
+    }
+
+    /// Like [`cache_path_for`](Self::cache_path_for), but fetches the given release and uses
+    /// its asset for the current host, in one of the formats that the client can extract.
+    #[instrument(parent = &self.span, skip(self))]
+    pub async fn cache_path_for_version(
+        &self,
+        version: &ToolchainVersion,
+    ) -> Result<PathBuf, ToolchainError> {
+        let release = self.get_release(version).await?;
+        let asset = release.asset_for_formats(
+            HostOS::try_current()?,
+            HostArch::try_current()?,
+            &self.archive_formats,
+        )?;
+        self.cache_path_for(asset)
     }
 
     /// Returns the path where the given toolchain version would be installed.
     pub fn install_path_for(&self, version: &ToolchainVersion) -> PathBuf {
-        self.toolchains_path.join(&version.name)
+        self.product_path().join(&version.name)
     }
 
     /// Checks if the specified toolchain version is already installed.
+    ///
+    /// A directory whose name only differs from the version by surrounding whitespace (or
+    /// by case, on a case-insensitive filesystem) also counts as an installation of it.
     pub fn version_is_installed(&self, version: &ToolchainVersion) -> bool {
-        self.install_path_for(version).exists()
+        self.install_path_for(version).exists() || !self.similar_installs(version).is_empty()
+    }
+
+    /// Returns the directories in the toolchains directory whose names are equivalent to the
+    /// given version but not identical to it.
+    ///
+    /// Names are equivalent if they only differ by surrounding whitespace, or by case if the
+    /// filesystem is case-insensitive (as detected at runtime).
+    pub fn similar_installs(&self, version: &ToolchainVersion) -> Vec<PathBuf> {
+        similar::similar_dirs(&self.product_path(), &version.name)
+    }
+
+    /// Returns groups of installed toolchain directories that could be confused with each
+    /// other, such as `20.1.0` and `20.1.0 ` (with a trailing space).
+    ///
+    /// Directories with surrounding whitespace are reported even if there's nothing else in
+    /// their group.
+    #[instrument(parent = &self.span, skip_all)]
+    pub async fn ambiguous_installs(&self) -> Result<Vec<Vec<PathBuf>>, ToolchainError> {
+        let mut names: Vec<String> = self
+            .installed_versions()
+            .await?
+            .into_iter()
+            .map(|version| version.name)
+            .collect();
+        names.sort();
+
+        Ok(similar::ambiguous_dirs(&self.product_path(), &names))
     }
 
     /// Downloads the specified toolchain asset, verifies its checksum, extracts it,
     /// and installs it to the appropriate location.
     ///
-    /// The downloaded toolchain will be activated if there is no other active toolchain. Returns
-    /// the path to the extracted toolchain directory.
+    /// By default, the downloaded toolchain will be activated if there is no other active
+    /// toolchain. This can be changed with [`ToolchainClientBuilder::activation_policy`].
+    /// Returns a summary of the installation, including the path to the extracted toolchain
+    /// directory and whether it was activated.
+    ///
+    /// If the toolchain was installed but activating it failed (e.g. because the disk is
+    /// full), the installation still succeeds, and the error is returned in
+    /// [`InstallOutcome::activation_error`].
     ///
     /// # Resuming downloads
     ///
     /// This method will also handle resuming downloads if the file already exists and is partially downloaded.
     /// If the partially-downloaded file contains invalid bytes, a checksum error will be returned and the file
     /// will be deleted.
+    ///
+    /// Checksum verification is also resumable: progress is periodically saved next to the archive
+    /// so that an interrupted verification of an unchanged file can pick up where it left off.
+    ///
+    /// # Cancellation
+    ///
+    /// The download and verification stop as soon as the token is cancelled, leaving the
+    /// partial download in the cache so that it can be resumed. Extraction stops between
+    /// archive entries (or copied chunks), and the partially extracted toolchain is deleted.
+    /// Applying permissions can also be cancelled, which leaves the extracted toolchain in place
+    /// with the permissions it was extracted with. A cancelled installation is never activated.
+    ///
+    /// Once cancelled, this returns [`ToolchainError::InstallCancelled`] describing how far the
+    /// installation got and what was kept.
     #[instrument(
+        parent = &self.span,
         skip(self, release, asset, progress, cancel_token),
         fields(version = release.version().name, asset.name)
     )]
@@ -180,124 +769,876 @@ impl ToolchainClient {
         asset: &Asset,
         progress: Arc<dyn Fn(InstallState) + Send + Sync>,
         cancel_token: CancellationToken,
-    ) -> Result<PathBuf, ToolchainError> {
-        let file_name = Utf8Path::new(&asset.name).file_name().ok_or_else(|| {
-            ToolchainError::InvalidAssetName {
-                name: asset.name.to_string(),
-            }
-        })?;
-        let archive_destination = self.cache_path.join(file_name);
-
-        debug!(asset.name, ?archive_destination, "Downloading asset");
-
-        // Begin downloading the checksum file in parallel so it's ready when we need it.
-        let checksum_future = self.fetch_asset_checksum(asset);
-
-        // Meanwhile, either begin or resume the asset download.
-        let download_task = async {
-            let mut downloaded_file = self
-                .download_asset(asset, &archive_destination, progress.clone())
-                .await?;
-
-            debug!("Calculating checksum for downloaded file");
-            let checksum_bytes =
-                calculate_file_checksum(&mut downloaded_file, progress.clone()).await?;
-            let checksum_hex = HEXLOWER.encode(&checksum_bytes);
-            trace!(?checksum_hex, "Checksum calculated");
+    ) -> Result<InstallOutcome, ToolchainError> {
+        self.install_release(
+            release,
+            asset,
+            ExpectedChecksum::Lookup,
+            progress,
+            cancel_token,
+        )
+        .await
+    }
 
-            Ok::<_, ToolchainError>((downloaded_file, checksum_hex))
+    /// Downloads a toolchain archive from an arbitrary URL and installs it under the given
+    /// version, like [`download_and_install`](Self::download_and_install) does for a release.
+    ///
+    /// This is meant for toolchains that aren't published as releases, such as patched
+    /// builds. The archive is downloaded from the URL as is, without the asset mirror, the
+    /// GitHub API, or the GitHub token. The last segment of the URL's path has to be the file
+    /// name of an archive in a format the client can extract. It's cached under that name,
+    /// prefixed with a hash of the URL.
+    ///
+    /// The download is verified against `sha256` (in hex format) if it's given. Otherwise, it
+    /// isn't verified at all: a warning is logged, and [`InstallState::VerifyingSkipped`] is
+    /// reported in place of the verification progress.
+    ///
+    /// Once installed, the toolchain is no different from one installed from a release, so it
+    /// can be activated, located, and removed by its version. Returns
+    /// [`ToolchainError::InvalidToolchainName`] if the version's name can't be used as a
+    /// directory name.
+    ///
+    /// # Cancellation
+    ///
+    /// This can be cancelled at the same points as
+    /// [`download_and_install`](Self::download_and_install), and keeps the same files.
+    #[instrument(
+        parent = &self.span,
+        skip(self, sha256, progress, cancel_token),
+        fields(%url, version = version.name)
+    )]
+    pub async fn install_from_url(
+        &self,
+        url: Url,
+        version: ToolchainVersion,
+        sha256: Option<String>,
+        progress: Arc<dyn Fn(InstallState) + Send + Sync>,
+        cancel_token: CancellationToken,
+    ) -> Result<InstallOutcome, ToolchainError> {
+        check_toolchain_name(&version.name)?;
+        let file_name = url
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .filter(|name| !name.is_empty())
+            .ok_or_else(|| ToolchainError::InvalidAssetName {
+                name: url.to_string(),
+            })?;
+
+        // Archives from different URLs can have the same file name, so the URL is part of
+        // the cached file's name. Otherwise, another URL's archive would be installed or
+        // resumed in its place, without being verified if there's no checksum.
+        let url_hash = HEXLOWER.encode(&Sha256::digest(url.as_str()));
+        let asset_name = format!("{}-{file_name}", &url_hash[..16]);
+        let asset = lock::offline_asset(&asset_name, 0, sha256.as_deref(), &url, &url);
+        let release = ToolchainRelease::with_version(
+            lock::offline_release(&version.name, vec![asset.clone()]),
+            self.product,
+            version,
+        );
+        let checksum = match sha256 {
+            Some(sha256) => ExpectedChecksum::Known(sha256),
+            None => {
+                warn!("No checksum was given, so the download won't be verified");
+                ExpectedChecksum::Unverified
+            }
         };
 
-        let ((mut downloaded_file, real_checksum), expected_checksum) =
-            async { tokio::try_join!(download_task, checksum_future) }
-                .with_cancellation_token(&cancel_token)
-                .await
-                .ok_or(ToolchainError::Cancelled)??;
-
-        // Verify the checksum to make sure the download was successful and the file is not corrupted.
+        self.direct()
+            .install_release(&release, &asset, checksum, progress, cancel_token)
+            .await
+    }
 
-        let checksums_match = real_checksum.eq_ignore_ascii_case(&expected_checksum);
-        debug!(
-            ?real_checksum,
-            ?expected_checksum,
-            "Checksum verification: {checksums_match}"
-        );
-        if !checksums_match {
-            fs::remove_file(archive_destination).await?;
-            return Err(ToolchainError::ChecksumMismatch {
-                expected: expected_checksum,
-                actual: real_checksum,
-            });
+    /// Returns a copy of the client that downloads assets from their browser download URL
+    /// as is, without the asset mirror, the GitHub API, or the GitHub token.
+    fn direct(&self) -> Self {
+        Self {
+            github_auth: None,
+            download_via: DownloadVia::Browser,
+            asset_mirror: None,
+            release_lock: None,
+            ..self.clone()
         }
+    }
 
-        debug!("Download finished");
-
-        // Now choose the extraction method based on the file extension.
-
-        let extract_location = self.install_path_for(release.version());
-
-        cancel_token.check_cancellation(ToolchainError::Cancelled)?;
-
-        debug!(archive = ?archive_destination, ?extract_location, "Extracting downloaded archive");
-        progress(InstallState::ExtractBegin);
-
-        if extract_location.exists() {
-            debug!("Destination folder already exists, removing it");
-            TRASH.delete(&extract_location)?;
+    /// Installs the asset to the toolchains directory and activates it if the activation
+    /// policy calls for it.
+    async fn install_release(
+        &self,
+        release: &ToolchainRelease,
+        asset: &Asset,
+        checksum: ExpectedChecksum,
+        progress: Arc<dyn Fn(InstallState) + Send + Sync>,
+        cancel_token: CancellationToken,
+    ) -> Result<InstallOutcome, ToolchainError> {
+        let plan = self.plan_install(release, asset).await?;
+        // Find out that the toolchain being replaced can't be removed before downloading
+        // anything.
+        if plan.replaces_existing {
+            in_use::check_not_in_use(&plan.destination).await?;
         }
-
-        downloaded_file.seek(SeekFrom::Start(0)).await?;
-        if file_name.ends_with(".dmg") {
-            extract::macos::extract_dmg(
-                archive_destination.clone(),
-                &extract_location,
-                progress.clone(),
-                cancel_token,
-            )
-            .await?;
-        } else if file_name.ends_with(".zip") {
-            extract::extract_zip(downloaded_file, extract_location.clone()).await?;
-        } else if file_name.ends_with(".tar.xz") {
-            let progress = progress.clone();
-            extract::extract_tar_xz(
-                downloaded_file,
-                extract_location.clone(),
+        self.create_toolchains_dir().await;
+        let mut outcome = self
+            .install_pipeline(
+                release,
+                asset,
+                checksum,
+                Destination {
+                    path: plan.destination,
+                    journal: Some(self.journal()),
+                },
                 progress.clone(),
                 cancel_token,
             )
             .await?;
-        } else {
-            unreachable!("Unsupported file format");
-        }
-
-        progress(InstallState::ExtractCleanUp);
-        fs::remove_file(archive_destination).await?;
-
-        progress(InstallState::ExtractDone);
 
         debug!("Updating current toolchain if necessary.");
-        if self.active_toolchain().is_none() {
+        if plan.activates {
             let new_version = release.version().clone();
-            info!(%new_version, "Updating current toolchain");
-            self.set_active_toolchain(Some(release.version().clone()))
-                .await?;
+            info!(phase = %Phase::Activate, %new_version, "Updating current toolchain");
+            match self.set_active_toolchain(Some(new_version.clone())).await {
+                Ok(()) => {
+                    outcome.activated = true;
+                    progress(InstallState::Activated {
+                        version: new_version,
+                    });
+                }
+                Err(error) => {
+                    warn!(%error, "Installed the toolchain, but failed to activate it");
+                    outcome.activation_error = Some(error.to_report());
+                }
+            }
         }
 
-        Ok(extract_location)
+        Ok(outcome)
     }
 
-    /// Downloads the asset to the specified destination path without checksum verification or extraction.
+    /// Installs a toolchain like [`download_and_install`](Self::download_and_install), but
+    /// reports its progress as a stream of events instead of calling a closure.
     ///
-    /// If the destination path already has a partially downloaded file, it will resume the download from where it left off.
-    #[instrument(skip(self, asset, progress))]
-    async fn download_asset(
+    /// The stream doesn't borrow the client or the release, so it can be moved to another
+    /// task (such as a GUI's background worker). It yields [`InstallEvent::Progress`] events
+    /// while the installation is running, followed by a single [`InstallEvent::Finished`]
+    /// event containing the outcome.
+    ///
+    /// # Cancellation
+    ///
+    /// Cancelling the token stops the installation as described in
+    /// [`download_and_install`](Self::download_and_install), after which the stream yields
+    /// [`ToolchainError::InstallCancelled`]. Dropping the stream also stops the installation, but
+    /// without cleaning up a partially extracted toolchain.
+    pub fn install_events(
         &self,
+        release: &ToolchainRelease,
         asset: &Asset,
-        destination: &Path,
-        progress: Arc<dyn Fn(InstallState) + Send + Sync>,
-    ) -> Result<fs::File, ToolchainError> {
-        if let Some(parent) = destination.parent() {
-            fs::create_dir_all(parent).await?;
+        cancel_token: CancellationToken,
+    ) -> impl Stream<Item = InstallEvent> + Send + 'static {
+        enum EventsState<F> {
+            Running {
+                install: Pin<Box<F>>,
+                receiver: UnboundedReceiver<InstallState>,
+            },
+            Draining {
+                receiver: UnboundedReceiver<InstallState>,
+                result: Result<InstallOutcome, ToolchainError>,
+            },
+            Done,
+        }
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let progress = Arc::new(move |state| {
+            // The stream may have been dropped, in which case nobody cares about progress.
+            _ = sender.send(state);
+        });
+
+        let client = self.clone();
+        let release = release.clone();
+        let asset = asset.clone();
+        let install = async move {
+            client
+                .download_and_install(&release, &asset, progress, cancel_token)
+                .await
+        };
+
+        let state = EventsState::Running {
+            install: Box::pin(install),
+            receiver,
+        };
+        stream::unfold(state, async |state| match state {
+            EventsState::Running {
+                mut install,
+                mut receiver,
+            } => {
+                tokio::select! {
+                    biased;
+                    Some(update) = receiver.recv() => Some((
+                        InstallEvent::Progress(update),
+                        EventsState::Running { install, receiver },
+                    )),
+                    result = &mut install => {
+                        // Send the progress reported just before the installation finished
+                        // before the outcome.
+                        let event = match receiver.try_recv() {
+                            Ok(update) => InstallEvent::Progress(update),
+                            Err(_) => return Some((InstallEvent::Finished(result), EventsState::Done)),
+                        };
+                        Some((event, EventsState::Draining { receiver, result }))
+                    }
+                }
+            }
+            EventsState::Draining {
+                mut receiver,
+                result,
+            } => match receiver.try_recv() {
+                Ok(update) => Some((
+                    InstallEvent::Progress(update),
+                    EventsState::Draining { receiver, result },
+                )),
+                Err(_) => Some((InstallEvent::Finished(result), EventsState::Done)),
+            },
+            EventsState::Done => None,
+        })
+    }
+
+    /// Downloads the specified toolchain asset, verifies its checksum, and extracts it to
+    /// an arbitrary directory outside of the toolchains directory.
+    ///
+    /// Unlike [`download_and_install`](Self::download_and_install), the toolchain isn't
+    /// registered with this client: it won't be activated, listed by
+    /// [`installed_versions`](Self::installed_versions), or recorded in the journal. The
+    /// archive is still downloaded to the cache directory so that downloads can be resumed.
+    ///
+    /// The destination must either not exist or be an empty directory. Use
+    /// [`install_to_replacing`](Self::install_to_replacing) to replace what's there.
+    ///
+    /// # Cancellation
+    ///
+    /// This can be cancelled at the same points as
+    /// [`download_and_install`](Self::download_and_install), and also deletes anything that
+    /// was partially extracted to the destination.
+    #[instrument(
+        parent = &self.span,
+        skip(self, release, asset, destination, progress, cancel_token),
+        fields(version = release.version().name, asset.name)
+    )]
+    pub async fn install_to(
+        &self,
+        release: &ToolchainRelease,
+        asset: &Asset,
+        destination: impl Into<PathBuf>,
+        progress: Arc<dyn Fn(InstallState) + Send + Sync>,
+        cancel_token: CancellationToken,
+    ) -> Result<InstallOutcome, ToolchainError> {
+        let plan = self.plan_install_to(release, asset, destination).await?;
+        if plan.replaces_existing {
+            return Err(ToolchainError::DestinationNotEmpty {
+                path: plan.destination,
+            });
+        }
+
+        // An empty directory is replaced by the extracted toolchain.
+        match fs::remove_dir(&plan.destination).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+
+        self.extract_to(release, asset, plan.destination, progress, cancel_token)
+            .await
+    }
+
+    /// Installs a toolchain like [`install_to`](Self::install_to), but replaces whatever is
+    /// at the destination instead of requiring it to be empty.
+    ///
+    /// The destination is only moved to the trash once the archive has been downloaded and
+    /// verified, so it's left as it was if the download fails or is cancelled.
+    #[instrument(
+        parent = &self.span,
+        skip(self, release, asset, destination, progress, cancel_token),
+        fields(version = release.version().name, asset.name)
+    )]
+    pub async fn install_to_replacing(
+        &self,
+        release: &ToolchainRelease,
+        asset: &Asset,
+        destination: impl Into<PathBuf>,
+        progress: Arc<dyn Fn(InstallState) + Send + Sync>,
+        cancel_token: CancellationToken,
+    ) -> Result<InstallOutcome, ToolchainError> {
+        self.extract_to(release, asset, destination.into(), progress, cancel_token)
+            .await
+    }
+
+    /// Runs the install pipeline for a destination outside of the toolchains directory,
+    /// which isn't recorded in the journal.
+    async fn extract_to(
+        &self,
+        release: &ToolchainRelease,
+        asset: &Asset,
+        destination: PathBuf,
+        progress: Arc<dyn Fn(InstallState) + Send + Sync>,
+        cancel_token: CancellationToken,
+    ) -> Result<InstallOutcome, ToolchainError> {
+        self.install_pipeline(
+            release,
+            asset,
+            ExpectedChecksum::Lookup,
+            Destination {
+                path: destination,
+                journal: None,
+            },
+            progress,
+            cancel_token,
+        )
+        .await
+    }
+
+    /// Describes what [`download_and_install`](Self::download_and_install) would do with the
+    /// given asset, without downloading anything or writing to the filesystem.
+    ///
+    /// Returns the same errors as `download_and_install` would before it starts downloading,
+    /// such as [`ToolchainError::SimilarInstallExists`].
+    #[instrument(parent = &self.span, skip_all)]
+    pub async fn plan_install(
+        &self,
+        release: &ToolchainRelease,
+        asset: &Asset,
+    ) -> Result<InstallPlan, ToolchainError> {
+        // Extracting over a directory with a similar name would either replace it (on a
+        // case-insensitive filesystem) or leave two near-identical toolchains installed.
+        let similar = self.similar_installs(release.version());
+        if !similar.is_empty() {
+            warn!(
+                ?similar,
+                "A toolchain with a similar name is already installed"
+            );
+            return Err(ToolchainError::SimilarInstallExists {
+                version: release.version().clone(),
+                paths: similar,
+            });
+        }
+
+        let mut plan = self
+            .plan(release, asset, self.install_path_for(release.version()))
+            .await?;
+        let active = self.active_toolchain();
+        plan.activates = match self.activation_policy {
+            ActivationPolicy::Never => false,
+            ActivationPolicy::IfNoneActive => active.is_none(),
+            ActivationPolicy::Always => active.as_ref() != Some(release.version()),
+        };
+        Ok(plan)
+    }
+
+    /// Describes what [`install_to`](Self::install_to) would do with the given asset, without
+    /// downloading anything or writing to the filesystem.
+    ///
+    /// If the destination isn't empty, [`InstallPlan::replaces_existing`] is set, and
+    /// `install_to` would fail unless the destination is emptied first or
+    /// [`install_to_replacing`](Self::install_to_replacing) is used instead.
+    #[instrument(parent = &self.span, skip_all)]
+    pub async fn plan_install_to(
+        &self,
+        release: &ToolchainRelease,
+        asset: &Asset,
+        destination: impl Into<PathBuf>,
+    ) -> Result<InstallPlan, ToolchainError> {
+        self.plan(release, asset, destination.into()).await
+    }
+
+    async fn plan(
+        &self,
+        release: &ToolchainRelease,
+        asset: &Asset,
+        destination: PathBuf,
+    ) -> Result<InstallPlan, ToolchainError> {
+        let file_name = asset_file_name(&asset.name)?;
+        let archive_path = self.cache_path_for(asset)?;
+
+        let cached_bytes = match fs::metadata(&archive_path).await {
+            Ok(meta) => meta.len(),
+            Err(e) if e.kind() == ErrorKind::NotFound => 0,
+            Err(e) => return Err(e.into()),
+        };
+
+        // A stray file or symlink is replaced too, so only a real directory is listed.
+        let replaces_existing = match fs::symlink_metadata(&destination).await {
+            Ok(meta) if meta.is_dir() => fs::read_dir(&destination)
+                .await?
+                .next_entry()
+                .await?
+                .is_some(),
+            Ok(_) => true,
+            Err(e) if e.kind() == ErrorKind::NotFound => false,
+            Err(e) => return Err(e.into()),
+        };
+
+        let download_size = asset.size as u64;
+        let estimated_install_size = if download_size > 0 && cached_bytes == download_size {
+            match (
+                self.archive_formats.find(file_name),
+                fs::File::open(&archive_path).await,
+            ) {
+                (Some(format), Ok(file)) => format.estimated_size(&file).await,
+                _ => None,
+            }
+        } else {
+            None
+        }
+        .or((download_size > 0).then(|| download_size * TYPICAL_EXPANSION_RATIO));
+
+        // The free space is only informational, so failing to probe it isn't fatal.
+        let destination_space = space::volume_space(&destination)
+            .await
+            .inspect_err(|error| warn!(%error, "Failed to probe the destination's free space"))
+            .ok();
+        let cache_space = space::volume_space(&self.cache_path)
+            .await
+            .inspect_err(|error| warn!(%error, "Failed to probe the cache's free space"))
+            .ok();
+
+        Ok(InstallPlan {
+            version: release.version().clone(),
+            asset_name: asset.name.clone(),
+            download_size,
+            cached_bytes,
+            destination,
+            replaces_existing,
+            activates: false,
+            estimated_install_size,
+            destination_space,
+            cache_space,
+        })
+    }
+
+    /// Downloads, verifies, and extracts a toolchain to the given location.
+    ///
+    /// Each phase is recorded in the journal, if one is given. If the installation is
+    /// cancelled, the error is [`ToolchainError::InstallCancelled`] describing what was kept.
+    #[instrument(
+        skip_all,
+        fields(product = self.product.id, version = %release.version(), asset = asset.name)
+    )]
+    async fn install_pipeline(
+        &self,
+        release: &ToolchainRelease,
+        asset: &Asset,
+        checksum: ExpectedChecksum,
+        destination: Destination,
+        progress: Arc<dyn Fn(InstallState) + Send + Sync>,
+        cancel_token: CancellationToken,
+    ) -> Result<InstallOutcome, ToolchainError> {
+        // The phase can't be told from the files alone, since a complete archive may be
+        // waiting to be verified or extracted.
+        let phase = Arc::new(Mutex::new(Phase::Download));
+        let tracked_progress: Arc<dyn Fn(InstallState) + Send + Sync> = Arc::new({
+            let phase = phase.clone();
+            move |state: InstallState| {
+                *phase.lock().unwrap() = state.phase();
+                progress(state);
+            }
+        });
+
+        let result = self
+            .run_install_pipeline(
+                release,
+                asset,
+                checksum,
+                destination,
+                tracked_progress,
+                cancel_token,
+            )
+            .await;
+        match result {
+            Err(ToolchainError::Cancelled) => {
+                let phase = *phase.lock().unwrap();
+                Err(ToolchainError::InstallCancelled(Box::new(
+                    self.cancelled_state(release, asset, phase).await,
+                )))
+            }
+            result => result,
+        }
+    }
+
+    /// Describes what a cancelled installation of `asset` left in the download cache.
+    async fn cancelled_state(
+        &self,
+        release: &ToolchainRelease,
+        asset: &Asset,
+        phase: Phase,
+    ) -> CancelledState {
+        let archive = match self.cache_path_for(asset) {
+            Ok(path) => match fs::metadata(&path).await {
+                Ok(metadata) if metadata.len() > 0 => Some(KeptArchive {
+                    path,
+                    downloaded_bytes: metadata.len(),
+                    total_bytes: u64::try_from(asset.size).unwrap_or(0),
+                }),
+                _ => None,
+            },
+            Err(_) => None,
+        };
+        debug!(%phase, ?archive, "Installation was cancelled");
+
+        CancelledState {
+            version: release.version().clone(),
+            phase,
+            archive,
+        }
+    }
+
+    async fn run_install_pipeline(
+        &self,
+        release: &ToolchainRelease,
+        asset: &Asset,
+        checksum: ExpectedChecksum,
+        destination: Destination,
+        progress: Arc<dyn Fn(InstallState) + Send + Sync>,
+        cancel_token: CancellationToken,
+    ) -> Result<InstallOutcome, ToolchainError> {
+        let Destination {
+            path: extract_location,
+            journal,
+        } = destination;
+        let started_at = Instant::now();
+        let file_name = asset_file_name(&asset.name)?;
+        let archive_destination = self.cache_path_for(asset)?;
+
+        // Checked before downloading, since the archive couldn't be extracted anyway.
+        let format = self.archive_formats.find(file_name).ok_or_else(|| {
+            extract::ExtractError::UnsupportedFormat {
+                file_name: file_name.to_string(),
+            }
+        })?;
+
+        debug!(phase = %Phase::Download, asset.name, ?archive_destination, "Downloading asset");
+        let download_intent = begin_intent(journal.as_ref(), Operation::Download, file_name).await;
+
+        // Begin downloading the checksum file in parallel so it's ready when we need it.
+        let verify = !matches!(checksum, ExpectedChecksum::Unverified);
+        let checksum_future = async {
+            match checksum {
+                ExpectedChecksum::Lookup => self.expected_checksum(release, asset).await.map(Some),
+                ExpectedChecksum::Known(checksum) => Ok(Some(checksum)),
+                ExpectedChecksum::Unverified => Ok(None),
+            }
+        };
+
+        // Meanwhile, either begin or resume the asset download.
+        let download_task = async {
+            let mut downloaded_file = self
+                .download_asset(asset, &archive_destination, progress.clone())
+                .await?;
+            if !verify {
+                progress(InstallState::VerifyingSkipped);
+                return Ok((downloaded_file, None));
+            }
+
+            debug!(phase = %Phase::Verify, "Calculating checksum for downloaded file");
+            let checksum = checksum::calculate_file_checksum(
+                &mut downloaded_file,
+                &archive_destination,
+                progress.clone(),
+            )
+            .await?;
+            trace!(checksum_hex = ?checksum.hex, resumed = checksum.resumed, "Checksum calculated");
+
+            Ok::<_, ToolchainError>((downloaded_file, Some(checksum)))
+        };
+
+        let ((mut downloaded_file, real_checksum), expected_checksum) =
+            async { tokio::try_join!(download_task, checksum_future) }
+                .with_cancellation_token(&cancel_token)
+                .await
+                .ok_or(ToolchainError::Cancelled)??;
+
+        match real_checksum.zip(expected_checksum) {
+            Some((real_checksum, expected_checksum)) => {
+                self.verify_download(
+                    asset,
+                    &mut downloaded_file,
+                    real_checksum,
+                    expected_checksum,
+                    progress.clone(),
+                    &cancel_token,
+                )
+                .await?;
+            }
+            None => debug!("No checksum to verify against, skipping verification"),
+        }
+
+        debug!("Download finished");
+        if let Some(intent) = download_intent {
+            intent.complete().await;
+        }
+
+        cancel_token.check_cancellation(ToolchainError::Cancelled)?;
+
+        debug!(phase = %Phase::Extract, archive = ?archive_destination, format = format.name(), ?extract_location, "Extracting downloaded archive");
+        let estimated_size = format.estimated_size(&downloaded_file).await;
+        progress(InstallState::ExtractBegin { estimated_size });
+
+        // `exists` follows symlinks, so a stray symlink is checked for without it.
+        if fs::symlink_metadata(&extract_location).await.is_ok() {
+            debug!("Destination already exists, removing it");
+            progress(InstallState::RemoveOldInstallBegin);
+            clear_destination(&extract_location, &cancel_token).await?;
+            progress(InstallState::RemoveOldInstallFinish);
+        }
+
+        // Only begun once the old toolchain is gone, so that failing to move it out of the way
+        // doesn't make it look like a partial install that should be cleaned up.
+        let extract_intent = begin_intent(
+            journal.as_ref(),
+            Operation::Extract,
+            &self.journal_subject(Some(release.version())),
+        )
+        .await;
+        if let Some(parent) = extract_location.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        let archive_size = downloaded_file.metadata().await?.len();
+        downloaded_file.seek(SeekFrom::Start(0)).await?;
+        let archive = Archive {
+            file: downloaded_file,
+            path: archive_destination.clone(),
+            limits: self.extract_limits,
+        };
+        let extract_result = format
+            .extract(
+                archive,
+                extract_location.clone(),
+                progress.clone(),
+                cancel_token.clone(),
+            )
+            .await;
+
+        // A truncated archive can sometimes be extracted without errors, so make sure the
+        // toolchain is complete before it can be activated. Extracted files are almost always
+        // larger than the compressed archive, so less than half of its size is suspicious.
+        let extract_result = match extract_result {
+            Ok(root_name) => {
+                let toolchain = InstalledToolchain::new(extract_location.clone());
+                let versions = RecordedVersions {
+                    release: release.version().clone(),
+                    content: root_name
+                        .as_deref()
+                        .and_then(manifest::content_version_from_root_name),
+                };
+                match versions.compare() {
+                    VersionMatch::Same => {}
+                    VersionMatch::Differs => debug!(
+                        ?versions,
+                        "Archive contents have a different version than the release"
+                    ),
+                    VersionMatch::MajorDiffers => warn!(
+                        ?versions,
+                        "Archive contents have a different major version than the release"
+                    ),
+                }
+                // The manifest only caches information, so failing to write it isn't fatal.
+                if let Err(error) = manifest::record_versions(&toolchain, &versions).await {
+                    warn!(?error, "Failed to record the toolchain's versions");
+                }
+                if let Some(arch) = HostArch::of_asset(&asset.name)
+                    && let Err(error) = manifest::record_arch(&toolchain, arch).await
+                {
+                    warn!(?error, "Failed to record the toolchain's architecture");
+                }
+                // Neither are the release notes, which are only for reference.
+                if let Err(error) = notes::record_release_notes(&toolchain, release).await {
+                    warn!(?error, "Failed to save the release notes");
+                }
+                // Nor is the file list, which is only used to detect local changes before
+                // the toolchain is overwritten. It's recorded before the structure check,
+                // which caches the toolchain's size along with its directory's modification time.
+                if let Err(error) = changes::record_files(&toolchain).await {
+                    warn!(?error, "Failed to record the toolchain's files");
+                }
+                // Digests are only used by `verify_installed`, which reports when none were
+                // recorded.
+                if self.record_file_digests
+                    && let Err(error) = digests::record_digests(&toolchain, &cancel_token).await
+                {
+                    warn!(?error, "Failed to record the toolchain's file digests");
+                }
+
+                structure::check_structure(&toolchain, release.product(), Some(archive_size / 2))
+                    .await
+            }
+            Err(error) => Err(error),
+        };
+
+        // Don't leave a partially-extracted toolchain behind if extraction failed or was cancelled.
+        if let Err(error) = extract_result {
+            if self.keep_failed && matches!(error, ToolchainError::IncompleteExtraction { .. }) {
+                warn!(
+                    ?extract_location,
+                    "Extracted toolchain is incomplete, keeping it for inspection"
+                );
+                return Err(error);
+            }
+
+            debug!(
+                ?error,
+                "Extraction failed, removing partially extracted toolchain"
+            );
+            if extract_location.exists()
+                && let Err(cleanup_error) = fs::remove_dir_all(&extract_location).await
+            {
+                warn!(
+                    ?cleanup_error,
+                    "Failed to remove partially extracted toolchain"
+                );
+            }
+
+            return Err(error);
+        }
+
+        permissions::apply_permissions(
+            &extract_location,
+            self.permission_policy,
+            progress.clone(),
+            &cancel_token,
+        )
+        .await?;
+
+        debug!(phase = %Phase::Cleanup, "Deleting downloaded archive");
+        progress(InstallState::ExtractCleanUp);
+        fs::remove_file(&archive_destination).await?;
+        checksum::discard_checkpoint(&archive_destination).await?;
+
+        progress(InstallState::ExtractDone);
+        if let Some(intent) = extract_intent {
+            intent.complete().await;
+        }
+
+        Ok(InstallOutcome {
+            version: release.version().clone(),
+            path: extract_location,
+            archive_size,
+            duration: started_at.elapsed(),
+            activated: false,
+            activation_error: None,
+        })
+    }
+
+    /// Checks that the downloaded archive has the expected checksum, deleting it if it doesn't.
+    async fn verify_download(
+        &self,
+        asset: &Asset,
+        downloaded_file: &mut fs::File,
+        mut real_checksum: checksum::FileChecksum,
+        expected_checksum: String,
+        progress: Arc<dyn Fn(InstallState) + Send + Sync>,
+        cancel_token: &CancellationToken,
+    ) -> Result<(), ToolchainError> {
+        let file_name = asset_file_name(&asset.name)?;
+        let archive_destination = self.cache_path_for(asset)?;
+
+        // A checkpoint or verified checksum could be stale (e.g. if the archive was rewritten
+        // with the same size and modification time), so never trust a resumed checksum that
+        // doesn't match.
+        if real_checksum.resumed && !real_checksum.hex.eq_ignore_ascii_case(&expected_checksum) {
+            warn!("Checksum resumed from a checkpoint didn't match, recalculating from scratch");
+            checksum::discard_checkpoint(&archive_destination).await?;
+
+            real_checksum =
+                checksum::calculate_file_checksum(downloaded_file, &archive_destination, progress)
+                    .with_cancellation_token(cancel_token)
+                    .await
+                    .ok_or(ToolchainError::Cancelled)??;
+        }
+
+        // Verify the checksum to make sure the download was successful and the file is not corrupted.
+
+        let real_checksum = real_checksum.hex;
+        let checksums_match = real_checksum.eq_ignore_ascii_case(&expected_checksum);
+        debug!(
+            ?real_checksum,
+            ?expected_checksum,
+            "Checksum verification: {checksums_match}"
+        );
+        if !checksums_match {
+            checksum::discard_checkpoint(&archive_destination).await?;
+            self.evict_cached(file_name).await?;
+            let file_name = file_name.to_string();
+            return Err(if self.release_lock.is_some() {
+                ToolchainError::LockedChecksumMismatch {
+                    file_name,
+                    expected: expected_checksum,
+                    actual: real_checksum,
+                }
+            } else {
+                ToolchainError::ChecksumMismatch {
+                    file_name,
+                    expected: expected_checksum,
+                    actual: real_checksum,
+                }
+            });
+        }
+
+        // If extraction fails, the next attempt can skip straight to extracting the archive.
+        if let Err(error) =
+            checksum::record_verified(downloaded_file, &archive_destination, &real_checksum).await
+        {
+            warn!(?error, "Failed to record that the archive was verified");
+        }
+
+        Ok(())
+    }
+
+    /// Downloads the asset to the specified destination path without checksum verification or extraction.
+    ///
+    /// If the destination path already has a partially downloaded file, it will resume the download from where it left off.
+    /// Transient failures (see [`download::is_transient`]) are retried with a backoff, resuming
+    /// from what the failed attempt wrote, up to the client's number of download attempts.
+    #[instrument(skip(self, asset, progress))]
+    async fn download_asset(
+        &self,
+        asset: &Asset,
+        destination: &Path,
+        progress: Arc<dyn Fn(InstallState) + Send + Sync>,
+    ) -> Result<fs::File, ToolchainError> {
+        self.require_network(|| format!("download {}", asset.name))?;
+
+        let mut attempt = 1;
+        loop {
+            let error = match self
+                .download_asset_once(asset, destination, progress.clone())
+                .await
+            {
+                Ok(file) => return Ok(file),
+                Err(error)
+                    if attempt < self.download_attempts && download::is_transient(&error) =>
+                {
+                    error
+                }
+                Err(error) => return Err(error),
+            };
+
+            let delay = download::retry_delay(attempt);
+            warn!(%error, attempt, delay = ?delay, "Download failed, retrying");
+            progress(InstallState::DownloadRetry {
+                attempt,
+                max_attempts: self.download_attempts,
+                delay,
+                reason: error.to_string(),
+            });
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Makes one attempt at downloading the asset, resuming from whatever is already in the
+    /// destination file.
+    async fn download_asset_once(
+        &self,
+        asset: &Asset,
+        destination: &Path,
+        progress: Arc<dyn Fn(InstallState) + Send + Sync>,
+    ) -> Result<fs::File, ToolchainError> {
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent).await?;
         }
 
         let mut file = fs::File::options()
@@ -307,155 +1648,974 @@ impl ToolchainClient {
             .open(&destination)
             .await?;
 
-        let mut current_file_length = file.seek(SeekFrom::End(0)).await?;
+        let mut current_file_length = file.seek(SeekFrom::End(0)).await?;
+
+        // Some mirrors and proxies report a size of zero, so try to find the real size before
+        // relying on it to resume the download.
+        let asset_size = match asset.size as u64 {
+            0 => self.probe_asset_size(asset).await,
+            size => Some(size),
+        };
+
+        let range_header = match asset_size {
+            Some(asset_size) => {
+                // Some initial checks before we start downloading to see if it makes sense to continue.
+
+                if current_file_length > asset_size {
+                    // Having *too much* data doesn't make any sense... just restart the download from scratch.
+                    warn!(
+                        ?current_file_length,
+                        ?asset_size,
+                        "File size mismatch: existing file is larger than expected. Truncating file and starting over."
+                    );
+
+                    file.set_len(0).await?;
+                    current_file_length = file.seek(SeekFrom::End(0)).await?;
+                }
+
+                if current_file_length == asset_size {
+                    debug!("File already downloaded, skipping download");
+                    // The download phase is still reported, so that progress bars and other
+                    // consumers see it finish instead of waiting for it forever.
+                    progress(InstallState::DownloadBegin {
+                        asset_size,
+                        bytes_read: asset_size,
+                    });
+                    progress(InstallState::DownloadFinish);
+                    return Ok(file);
+                }
+
+                // If there's already data in the file, we will assume that's from the last download attempt and
+                // set the Range header to continue downloading from where we left off.
+
+                let next_byte_index = current_file_length;
+                let last_byte_index = asset_size - 1;
+                let range_header = format!("bytes={next_byte_index}-{last_byte_index}");
+                trace!(?range_header, "Setting Range header for download");
+
+                if next_byte_index > 0 {
+                    debug!("Resuming an existing download");
+                }
+
+                Some(range_header)
+            }
+            None => {
+                // Without knowing the size, there's no way to tell whether an existing file
+                // is complete, so always start over.
+                warn!("Asset size is unknown, downloading it from scratch");
+                file.set_len(0).await?;
+                current_file_length = file.seek(SeekFrom::End(0)).await?;
+
+                None
+            }
+        };
+
+        // With the ETag saved when the partial download started, the server itself makes sure
+        // that the asset hasn't been re-uploaded since, and sends all of it if it has.
+        let saved_etag = match current_file_length {
+            0 => None,
+            _ => resume::saved_etag(destination).await,
+        };
+        let mut range_header = range_header;
+        let mut if_range = range_header.as_ref().and(saved_etag.as_deref());
+        let response = loop {
+            let response = self
+                .get_asset(
+                    &asset.browser_download_url,
+                    &asset.url,
+                    range_header.as_deref(),
+                    if_range,
+                )
+                .await?;
+
+            if range_header.is_none() {
+                break response;
+            }
+
+            // Make sure the server is actually continuing from the end of the file, so that
+            // data is never appended at the wrong place.
+            match check_resume(&response, current_file_length, if_range) {
+                ResumeCheck::Resumed => break response,
+                ResumeCheck::FullBody => {
+                    debug!(
+                        status = %response.status(),
+                        ?if_range,
+                        "Server sent the whole asset, because it changed or the server ignored the Range header, discarding the partial download"
+                    );
+                    file.set_len(0).await?;
+                    current_file_length = file.seek(SeekFrom::End(0)).await?;
+                    break response;
+                }
+                ResumeCheck::Restart(reason) => {
+                    debug!(
+                        status = %response.status(),
+                        content_range = ?response.headers().get(header::CONTENT_RANGE),
+                        current_file_length,
+                        "{reason}, discarding the partial download and starting over"
+                    );
+                    file.set_len(0).await?;
+                    current_file_length = file.seek(SeekFrom::End(0)).await?;
+                    range_header = None;
+                    if_range = None;
+                }
+            }
+        };
+
+        // A proxy may compress the response even though an unencoded one was requested. The
+        // decoded body is written instead, and only the checksum can tell if it's correct.
+        let encoding = decode::content_encoding(response.headers());
+        let mut decoder = None;
+        match &encoding {
+            Some(encoding) => {
+                if current_file_length > 0 {
+                    file.set_len(0).await?;
+                    current_file_length = file.seek(SeekFrom::End(0)).await?;
+                }
+
+                decoder = ContentDecoder::new(encoding);
+                if decoder.is_some() {
+                    info!(%encoding, "Server sent an encoded response, decoding it without resume support");
+                } else {
+                    warn!(%encoding, "Server sent a response with an unsupported encoding, saving it as-is");
+                }
+            }
+            None if current_file_length > 0 => {
+                debug!(offset = current_file_length, "Resuming the download");
+            }
+            None => debug!("Downloading the whole asset"),
+        }
+
+        // The ETag is saved before any data is written, so that an interrupted download is
+        // only resumed if the asset is still the same. A resumed response may leave it out,
+        // in which case the one it was resumed with still applies. Encoded responses are
+        // never resumed, so their ETags aren't needed.
+        let etag = match &encoding {
+            Some(_) => None,
+            None => resume::strong_etag(response.headers())
+                .or(if_range.filter(|_| current_file_length > 0)),
+        };
+        trace!(?etag, "Saving the asset's ETag for resuming the download");
+        resume::save_etag(destination, etag).await?;
+
+        // This is only sent once the file has been truncated (if it had to be), so
+        // `bytes_read` never counts data that was discarded.
+        progress(InstallState::DownloadBegin {
+            asset_size: asset_size.unwrap_or(0),
+            bytes_read: current_file_length,
+        });
+
+        // At this point, we're all good to just start copying bytes from the stream to the file.
+        let file = download::write_body(
+            response,
+            file,
+            decoder,
+            self.download_buffer_size,
+            self.download_stall_timeout,
+            current_file_length,
+            |bytes_read| progress(InstallState::Download { bytes_read }),
+        )
+        .await?;
+
+        resume::discard_etag(destination).await?;
+        progress(InstallState::DownloadFinish);
+        debug!(?destination, "Download completed");
+
+        Ok(file)
+    }
+
+    /// Finds the size of an asset by sending a HEAD request for it, returning `None` if the
+    /// size couldn't be determined.
+    async fn probe_asset_size(&self, asset: &Asset) -> Option<u64> {
+        let url = self.preferred_url(&asset.browser_download_url, &asset.url);
+        let accept = match self.download_via {
+            DownloadVia::Api => "application/octet-stream",
+            DownloadVia::Browser | DownloadVia::Auto => "*/*",
+        };
+
+        let response = self
+            .request(Method::HEAD, &url)
+            .header(header::ACCEPT, accept)
+            .header(header::ACCEPT_ENCODING, "identity")
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+
+        let size = match response {
+            // The length of an encoded response isn't the asset's size.
+            Ok(response) if decode::content_encoding(response.headers()).is_some() => None,
+            Ok(response) => response
+                .headers()
+                .get(header::CONTENT_LENGTH)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<u64>().ok())
+                .filter(|&size| size > 0),
+            Err(error) => {
+                debug!(%url, ?error, "Failed to probe the asset size");
+                None
+            }
+        };
+
+        debug!(%url, ?size, "Probed asset size");
+        size
+    }
+
+    /// Returns where the asset and its checksum would be downloaded from, without downloading
+    /// the asset.
+    ///
+    /// If `fetch_checksum` is true, the expected checksum is also downloaded (or read from the
+    /// client's checksum file) so that a download made with another tool can be verified.
+    #[instrument(parent = &self.span, skip_all)]
+    pub async fn asset_download_info(
+        &self,
+        release: &ToolchainRelease,
+        asset: &Asset,
+        fetch_checksum: bool,
+    ) -> Result<AssetDownloadInfo, ToolchainError> {
+        let (sha256_url, sha256_api_url) = Self::checksum_urls(release, asset);
+
+        let expected_sha256 = if fetch_checksum {
+            Some(self.expected_checksum(release, asset).await?)
+        } else {
+            None
+        };
+
+        Ok(AssetDownloadInfo {
+            url: self.preferred_url(&asset.browser_download_url, &asset.url),
+            size: asset.size as u64,
+            checksum_url: match &sha256_api_url {
+                Some(api_url) => self.preferred_url(&sha256_url, api_url),
+                None => self.browser_url(&sha256_url),
+            },
+            expected_sha256,
+        })
+    }
+
+    /// Lists the downloadable assets of a release, without their digests.
+    ///
+    /// Checksum files (`.sha256`) aren't included, since they describe the other assets.
+    pub fn release_assets(&self, release: &ToolchainRelease) -> Vec<ReleaseAssetInfo> {
+        Self::downloadable_assets(release)
+            .map(|asset| self.release_asset_info(asset))
+            .collect()
+    }
+
+    /// Lists the downloadable assets of a release along with their SHA256 digests.
+    ///
+    /// The digest GitHub computed for each asset is used when the API provides one. Otherwise,
+    /// the asset's `.sha256` file is downloaded, a few at a time to stay within GitHub's rate
+    /// limits. An asset whose digest couldn't be resolved has its
+    /// [`digest_error`](ReleaseAssetInfo::digest_error) set instead of failing the listing.
+    #[instrument(parent = &self.span, skip_all)]
+    pub async fn release_assets_with_digests(
+        &self,
+        release: &ToolchainRelease,
+    ) -> Vec<ReleaseAssetInfo> {
+        /// How many checksum files are downloaded at once.
+        const MAX_CONCURRENT_DIGESTS: usize = 4;
+
+        stream::iter(Self::downloadable_assets(release))
+            .map(|asset| async move {
+                let mut info = self.release_asset_info(asset);
+
+                if let Some(digest) = Self::github_digest(asset) {
+                    info.sha256 = Some(digest);
+                    info.digest_source = Some(DigestSource::GitHub);
+                    return info;
+                }
+
+                match self.fetch_asset_checksum(release, asset).await {
+                    Ok(digest) => {
+                        info.sha256 = Some(digest);
+                        info.digest_source = Some(DigestSource::ChecksumFile);
+                    }
+                    Err(error) => {
+                        warn!(asset = asset.name, %error, "Failed to resolve asset digest");
+                        info.digest_error = Some(error.to_report());
+                    }
+                }
+                info
+            })
+            .buffered(MAX_CONCURRENT_DIGESTS)
+            .collect()
+            .await
+    }
+
+    /// Creates a lock that records the release's tag and the URLs, size, and SHA256 digest of
+    /// each of its downloadable assets.
+    ///
+    /// Fails with [`ToolchainError::AssetDigestUnavailable`] if any asset's digest can't be
+    /// resolved, since the lock couldn't be used to verify that asset.
+    #[instrument(parent = &self.span, skip_all)]
+    pub async fn lock_release(
+        &self,
+        release: &ToolchainRelease,
+    ) -> Result<ReleaseLock, ToolchainError> {
+        let infos = self.release_assets_with_digests(release).await;
+
+        let mut assets = vec![];
+        for (asset, info) in Self::downloadable_assets(release).zip(infos) {
+            let Some(sha256) = info.sha256 else {
+                return Err(ToolchainError::AssetDigestUnavailable {
+                    file_name: info.name,
+                    reason: info
+                        .digest_error
+                        .map_or_else(|| "unknown error".to_string(), |error| error.message),
+                });
+            };
+
+            assets.push(LockedAsset {
+                name: asset.name.clone(),
+                size: asset.size as u64,
+                sha256,
+                url: asset.browser_download_url.clone(),
+                api_url: asset.url.clone(),
+            });
+        }
 
-        // Some initial checks before we start downloading to see if it makes sense to continue.
+        Ok(ReleaseLock {
+            format_version: ReleaseLock::FORMAT_VERSION,
+            product: release.product(),
+            tag_name: release.tag_name().to_string(),
+            assets,
+        })
+    }
 
-        if current_file_length > asset.size as u64 {
-            // Having *too much* data doesn't make any sense... just restart the download from scratch.
-            warn!(
-                ?current_file_length,
-                ?asset.size,
-                "File size mismatch: existing file is larger than expected. Truncating file and starting over."
-            );
+    fn downloadable_assets(release: &ToolchainRelease) -> impl Iterator<Item = &Asset> {
+        release
+            .assets()
+            .iter()
+            .filter(|asset| !asset.name.ends_with(".sha256"))
+    }
+
+    fn release_asset_info(&self, asset: &Asset) -> ReleaseAssetInfo {
+        ReleaseAssetInfo {
+            name: asset.name.clone(),
+            size: asset.size as u64,
+            url: self.preferred_url(&asset.browser_download_url, &asset.url),
+            sha256: None,
+            digest_source: None,
+            digest_error: None,
+        }
+    }
+
+    /// Returns the SHA256 digest that GitHub reports for an asset, in hex format.
+    ///
+    /// GitHub only computes digests for assets uploaded since mid-2025, and reports them as
+    /// `sha256:<hex>`.
+    fn github_digest(asset: &Asset) -> Option<String> {
+        let hex = asset.digest.as_deref()?.strip_prefix("sha256:")?;
+        (hex.len() == 64 && hex.bytes().all(|b| b.is_ascii_hexdigit()))
+            .then(|| hex.to_ascii_lowercase())
+    }
+
+    /// Returns the browser URL of the asset's checksum file, and its API URL if the checksum
+    /// was uploaded as its own release asset.
+    fn checksum_urls(release: &ToolchainRelease, asset: &Asset) -> (Url, Option<Url>) {
+        let mut sha256_url = asset.browser_download_url.clone();
+        sha256_url.set_path(&format!("{}.sha256", sha256_url.path()));
+
+        let sha256_name = format!("{}.sha256", asset.name);
+        let sha256_api_url = release
+            .assets()
+            .iter()
+            .find(|a| a.name == sha256_name)
+            .map(|a| a.url.clone());
+
+        (sha256_url, sha256_api_url)
+    }
+
+    /// Downloads the expected SHA256 checksum for the asset.
+    ///
+    /// The resulting string contains the checksum in hex format.
+    async fn fetch_asset_checksum(
+        &self,
+        release: &ToolchainRelease,
+        asset: &Asset,
+    ) -> Result<String, ToolchainError> {
+        self.require_network(|| format!("download the checksum of {}", asset.name))?;
+        let (sha256_url, sha256_api_url) = Self::checksum_urls(release, asset);
 
-            file.set_len(0).await?;
-            current_file_length = file.seek(SeekFrom::End(0)).await?;
+        let checksum_file = match &sha256_api_url {
+            Some(api_url) => self.get_asset(&sha256_url, api_url, None, None).await?,
+            None => self.get_asset_via_browser(&sha256_url, None, None).await?,
         }
+        .text()
+        .await?;
 
-        if current_file_length == asset.size as u64 {
-            debug!("File already downloaded, skipping download");
-            return Ok(file);
+        // The checksum file is usually in the format `<checksum> <filename>`, but it only
+        // describes this asset, so the filename doesn't have to match.
+        if let Some(checksum) = checksum::find_checksum(&checksum_file, &asset.name) {
+            return Ok(checksum);
         }
+        let hash_part = checksum_file.split_ascii_whitespace().next().unwrap_or("");
+        Ok(hash_part.to_string())
+    }
 
-        // If there's already data in the file, we will assume that's from the last download attempt and
-        // set the Range header to continue downloading from where we left off.
+    /// Returns the expected SHA256 checksum for the asset in hex format.
+    ///
+    /// If a release lock was configured with [`ToolchainClientBuilder::release_lock`], the
+    /// checksum recorded in it is used. If a checksum file was configured with
+    /// [`ToolchainClientBuilder::checksum_file`], the checksum is looked up in it.
+    /// Otherwise, it's downloaded from the release.
+    async fn expected_checksum(
+        &self,
+        release: &ToolchainRelease,
+        asset: &Asset,
+    ) -> Result<String, ToolchainError> {
+        if let Some(lock) = &self.release_lock {
+            return lock
+                .sha256_for(&asset.name)
+                .map(str::to_string)
+                .ok_or_else(|| ToolchainError::AssetNotLocked {
+                    file_name: asset.name.clone(),
+                });
+        }
 
-        let next_byte_index = current_file_length;
-        let last_byte_index = asset.size as u64 - 1;
-        let range_header = format!("bytes={next_byte_index}-{last_byte_index}");
-        trace!(?range_header, "Setting Range header for download");
+        let Some(path) = &self.checksum_file else {
+            return self.fetch_asset_checksum(release, asset).await;
+        };
 
-        if next_byte_index > 0 {
-            debug!("Resuming an existing download");
+        let contents = fs::read_to_string(path).await?;
+        match checksum::find_checksum(&contents, &asset.name) {
+            Some(checksum) => {
+                debug!(?path, asset = asset.name, "Using checksum from local file");
+                Ok(checksum)
+            }
+            None if self.checksum_fallback == ChecksumFallback::Network => {
+                warn!(
+                    ?path,
+                    asset = asset.name,
+                    "Asset isn't listed in the checksum file, downloading its checksum instead"
+                );
+                self.fetch_asset_checksum(release, asset).await
+            }
+            None => Err(ToolchainError::ChecksumNotListed {
+                file_name: asset.name.clone(),
+                path: path.clone(),
+            }),
         }
+    }
 
-        progress(InstallState::DownloadBegin {
-            asset_size: asset.size as u64,
-            bytes_read: current_file_length,
-        });
+    /// Starts a request for `url`, with the GitHub token if it may be sent there.
+    fn request(&self, method: Method, url: &Url) -> RequestBuilder {
+        let request = self.client.request(method, url.clone());
+        match &self.github_auth {
+            Some(auth) => auth.authorize(request, url),
+            None => request,
+        }
+    }
 
-        // At this point, we're all good to just start copying bytes from the stream to the file.
+    /// Returns the URL that [`get_asset`](Self::get_asset) requests first.
+    fn preferred_url(&self, browser_url: &Url, api_url: &Url) -> Url {
+        match self.download_via {
+            DownloadVia::Browser | DownloadVia::Auto => self.browser_url(browser_url),
+            DownloadVia::Api => api_url.clone(),
+        }
+    }
+
+    /// Returns the URL that a browser download URL is requested from, which is on the mirror
+    /// if one was configured.
+    fn browser_url(&self, url: &Url) -> Url {
+        self.mirrored_url(url).unwrap_or_else(|| url.clone())
+    }
+
+    /// Returns the URL of a browser download on the configured mirror, which has the same path
+    /// under the mirror's base URL, or `None` if there's no mirror.
+    fn mirrored_url(&self, url: &Url) -> Option<Url> {
+        let mirror = self.asset_mirror.as_ref()?;
+        mirror.join(url.path().trim_start_matches('/')).ok()
+    }
+
+    /// Sends a GET request for a release asset, using the endpoint selected by [`DownloadVia`].
+    ///
+    /// When set to [`DownloadVia::Auto`], a failed request to the browser URL is retried
+    /// through the GitHub API.
+    async fn get_asset(
+        &self,
+        browser_url: &Url,
+        api_url: &Url,
+        range: Option<&str>,
+        if_range: Option<&str>,
+    ) -> Result<reqwest::Response, ToolchainError> {
+        match self.download_via {
+            DownloadVia::Browser => {
+                self.get_asset_via_browser(browser_url, range, if_range)
+                    .await
+            }
+            DownloadVia::Api => self.get_asset_via_api(api_url, range, if_range).await,
+            DownloadVia::Auto => match self
+                .get_asset_via_browser(browser_url, range, if_range)
+                .await
+            {
+                Ok(response) => Ok(response),
+                Err(error) => {
+                    warn!(
+                        %browser_url,
+                        %api_url,
+                        ?error,
+                        "Browser download URL failed, falling back to the GitHub API"
+                    );
+                    self.get_asset_via_api(api_url, range, if_range).await
+                }
+            },
+        }
+    }
+
+    /// Sends a GET request for a browser download URL, or for its copy on the mirror if one
+    /// was configured.
+    ///
+    /// If the mirror doesn't have the asset and the client falls back to the original URL
+    /// (see [`ToolchainClientBuilder::mirror_fallback`]), the original URL is requested
+    /// instead.
+    async fn get_asset_via_browser(
+        &self,
+        url: &Url,
+        range: Option<&str>,
+        if_range: Option<&str>,
+    ) -> Result<reqwest::Response, ToolchainError> {
+        let Some(mirror_url) = self.mirrored_url(url) else {
+            return self.get_browser_url(url, range, if_range).await;
+        };
+
+        trace!(%mirror_url, "Downloading asset from the mirror");
+        match self.get_browser_url(&mirror_url, range, if_range).await {
+            Err(ToolchainError::Reqwest(error))
+                if self.mirror_fallback && error.status() == Some(StatusCode::NOT_FOUND) =>
+            {
+                warn!(%mirror_url, %url, "The mirror doesn't have the asset, downloading it from its original URL");
+                self.get_browser_url(url, range, if_range).await
+            }
+            result => result,
+        }
+    }
 
-        let mut stream = self
-            .client
-            .get(asset.browser_download_url.clone())
-            .header(header::RANGE, range_header)
+    async fn get_browser_url(
+        &self,
+        url: &Url,
+        range: Option<&str>,
+        if_range: Option<&str>,
+    ) -> Result<reqwest::Response, ToolchainError> {
+        let mut request = self
+            .request(Method::GET, url)
             .header(header::ACCEPT, "*/*")
-            .send()
-            .await?
-            .error_for_status()?
-            .bytes_stream();
+            .header(header::ACCEPT_ENCODING, "identity");
+        if let Some(range) = range {
+            request = request.header(header::RANGE, range);
+        }
+        if let Some(if_range) = if_range {
+            request = request.header(header::IF_RANGE, if_range);
+        }
 
-        let mut writer = BufWriter::new(file);
+        let response = request.send().await?;
+        // The caller restarts the download if the requested range can't be satisfied.
+        if range.is_some() && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+            return Ok(response);
+        }
+        if let Some(resets_at) = rate_limit::rate_limit_reset(response.status(), response.headers())
+        {
+            return Err(ToolchainError::RateLimited { resets_at });
+        }
 
-        while let Some(chunk) = stream.try_next().await? {
-            writer.write_all(&chunk).await?;
+        Ok(response.error_for_status()?)
+    }
 
-            current_file_length += chunk.len() as u64;
-            progress(InstallState::Download {
-                bytes_read: current_file_length,
-            });
+    async fn get_asset_via_api(
+        &self,
+        url: &Url,
+        range: Option<&str>,
+        if_range: Option<&str>,
+    ) -> Result<reqwest::Response, ToolchainError> {
+        trace!(%url, "Downloading asset through the GitHub API");
+
+        // The API responds with a redirect to the asset's storage location, which also
+        // supports Range requests.
+        let mut request = self
+            .request(Method::GET, url)
+            .header(header::ACCEPT, "application/octet-stream")
+            .header(header::ACCEPT_ENCODING, "identity");
+        if let Some(range) = range {
+            request = request.header(header::RANGE, range);
+        }
+        if let Some(if_range) = if_range {
+            request = request.header(header::IF_RANGE, if_range);
         }
 
-        writer.flush().await?;
-        progress(InstallState::DownloadFinish);
-        debug!(?destination, "Download completed");
+        let response = request.send().await?;
+        // The caller restarts the download if the requested range can't be satisfied.
+        if range.is_some() && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+            return Ok(response);
+        }
+        if let Some(resets_at) = rate_limit::rate_limit_reset(response.status(), response.headers())
+        {
+            return Err(ToolchainError::RateLimited { resets_at });
+        }
 
-        Ok(writer.into_inner())
+        Ok(response.error_for_status()?)
     }
 
-    /// Downloads the expected SHA256 checksum for the asset.
+    /// Returns every version of this client's product that's installed in the toolchains
+    /// directory, in no particular order.
     ///
-    /// The resulting string contains the checksum in hex format.
-    async fn fetch_asset_checksum(&self, asset: &Asset) -> Result<String, ToolchainError> {
-        let mut sha256_url = asset.browser_download_url.clone();
-        sha256_url.set_path(&format!("{}.sha256", sha256_url.path()));
+    /// Entries that disappear or can't be inspected while the directory is being listed (e.g.
+    /// because another process is removing a toolchain) are skipped rather than causing an error.
+    #[instrument(parent = &self.span, skip_all)]
+    pub async fn installed_versions(&self) -> Result<Vec<ToolchainVersion>, ToolchainError> {
+        let versions = self
+            .scan_installs()
+            .await?
+            .into_iter()
+            .filter_map(|install| match install {
+                Ok(version) => Some(version),
+                Err(unusable) => {
+                    warn!(
+                        path = ?unusable.path,
+                        reason = ?unusable.reason,
+                        "Skipping toolchain directory with an unusable name"
+                    );
+                    None
+                }
+            })
+            .collect();
 
-        let mut checksum_file = self
-            .client
-            .get(sha256_url)
-            .send()
+        Ok(versions)
+    }
+
+    /// Returns the directories in the toolchains directory that are skipped by
+    /// [`installed_versions`](Self::installed_versions) because of their names, such as names
+    /// that aren't valid UTF-8.
+    #[instrument(parent = &self.span, skip_all)]
+    pub async fn unusable_installs(&self) -> Result<Vec<UnusableInstall>, ToolchainError> {
+        Ok(self
+            .scan_installs()
             .await?
-            .error_for_status()?
-            .text()
-            .await?;
+            .into_iter()
+            .filter_map(Result::err)
+            .collect())
+    }
 
-        // Trim off the filename from the checksum file, which is usually in the format:
-        // `<checksum> <filename>`
+    /// Returns the reasons recorded for the installed versions of this client's product.
+    ///
+    /// Reasons recorded for versions that are no longer installed are left out.
+    #[instrument(parent = &self.span, skip_all)]
+    pub async fn install_reasons(&self) -> Result<InstallReasons, ToolchainError> {
+        let mut reasons = reasons::read_reasons(&self.product_path()).await?;
+        reasons.retain(|version| self.version_is_installed(version));
+        Ok(reasons)
+    }
 
-        let mut parts = checksum_file.split_ascii_whitespace();
-        let hash_part = parts.next().unwrap_or("");
-        checksum_file.truncate(hash_part.len());
+    /// Records why a toolchain is installed, so that it isn't pruned while it's still needed.
+    /// Returns whether the reason wasn't already recorded.
+    ///
+    /// Reasons are shared with every other tool that uses the same toolchains directory, so
+    /// they should start with the name of the tool that added them, such as
+    /// `cargo-v5: project X`. Returns [`ToolchainError::ToolchainNotInstalled`] if the
+    /// version isn't installed.
+    #[instrument(parent = &self.span, skip_all)]
+    pub async fn add_install_reason(
+        &self,
+        version: &ToolchainVersion,
+        reason: &str,
+    ) -> Result<bool, ToolchainError> {
+        let reason = reasons::validate_reason(reason)?;
+        if !self.version_is_installed(version) {
+            return Err(ToolchainError::ToolchainNotInstalled {
+                version: version.clone(),
+            });
+        }
 
-        Ok(checksum_file)
+        self.update_install_reasons(|reasons| reasons.add(version, reason))
+            .await
     }
 
-    pub async fn installed_versions(&self) -> Result<Vec<ToolchainVersion>, ToolchainError> {
-        let mut futs = vec![];
+    /// Forgets a reason added with [`add_install_reason`](Self::add_install_reason), returning
+    /// whether it was recorded.
+    #[instrument(parent = &self.span, skip_all)]
+    pub async fn remove_install_reason(
+        &self,
+        version: &ToolchainVersion,
+        reason: &str,
+    ) -> Result<bool, ToolchainError> {
+        let reason = reasons::validate_reason(reason)?;
+        self.update_install_reasons(|reasons| reasons.remove(version, reason))
+            .await
+    }
 
-        let mut dir = fs::read_dir(&self.toolchains_path).await?;
-        while let Some(entry) = dir.next_entry().await? {
-            futs.push(async move {
-                if let Ok(ty) = entry.file_type().await
-                    && ty.is_dir()
-                {
-                    let name = entry.file_name();
-                    return Some(ToolchainVersion::named(name.to_string_lossy()));
-                }
+    /// Changes this product's install reasons, and forgets the ones for toolchains that were
+    /// removed without updating them (for example, by an older version of this crate).
+    async fn update_install_reasons<R>(
+        &self,
+        change: impl FnOnce(&mut InstallReasons) -> R,
+    ) -> Result<R, ToolchainError> {
+        reasons::update_reasons(&self.product_path(), |reasons| {
+            let result = change(reasons);
+            reasons.retain(|version| self.version_is_installed(version));
+            result
+        })
+        .await
+    }
 
-                None
-            });
+    /// Lists the toolchain directories of this client's product, separating the ones that
+    /// can't be used because of their names.
+    async fn scan_installs(
+        &self,
+    ) -> Result<Vec<Result<ToolchainVersion, UnusableInstall>>, ToolchainError> {
+        let mut entries = vec![];
+
+        let mut dir = match fs::read_dir(self.product_path()).await {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+        while let Some(entry) = dir.next_entry().await.transpose() {
+            entries.push(entry);
         }
 
-        let versions = join_all(futs).await.into_iter().flatten().collect();
-        Ok(versions)
+        Ok(scan::classify_entries(entries).await)
     }
 
     /// Delete all files related to the given toolchain version.
+    ///
+    /// # Cancellation
+    ///
+    /// The token is only checked while the toolchain's files are being listed. Once deletion
+    /// has started, it runs to completion so that a toolchain is never left half-deleted
+    /// because of cancellation.
+    #[instrument(parent = &self.span, skip_all, fields(product = self.product.id, %version))]
     pub async fn remove(
         &self,
         version: &ToolchainVersion,
         progress: impl FnMut(RemoveProgress),
         cancel_token: &CancellationToken,
-    ) -> Result<(), ToolchainError> {
-        if let Ok(toolchain) = self.toolchain(version).await {
-            remove_dir_progress(toolchain.path, progress, cancel_token).await?;
+    ) -> Result<RemoveReport, ToolchainError> {
+        let toolchain = self.toolchain(version).await.ok();
+        // Refusing to remove a toolchain leaves nothing to clean up, so it isn't journaled.
+        if let Some(toolchain) = &toolchain {
+            in_use::check_not_in_use(&toolchain.path).await?;
+        }
+
+        self.create_toolchains_dir().await;
+        let intent = self
+            .journal()
+            .begin(Operation::Remove, self.journal_subject(Some(version)))
+            .await;
+
+        let mut path = None;
+        let mut bytes_removed = 0;
+        if let Some(toolchain) = toolchain {
+            debug!(phase = %Phase::Remove, path = ?toolchain.path, "Removing toolchain");
+            path = Some(toolchain.path.clone());
+            bytes_removed = remove_dir_progress(toolchain.path, progress, cancel_token).await?;
         }
 
-        if self.active_toolchain().as_ref() == Some(version) {
+        let deactivated = self.active_toolchain().as_ref() == Some(version);
+        if deactivated {
             self.set_active_toolchain(None).await?;
         }
 
-        Ok(())
+        // The toolchain is already gone, so failing here would only hide that. Reasons for
+        // missing toolchains are ignored, and forgotten during the next update.
+        let reasons = self
+            .update_install_reasons(|reasons| reasons.clear(version))
+            .await
+            .unwrap_or_else(|error| {
+                warn!(%error, "Failed to forget the toolchain's install reasons");
+                vec![]
+            });
+
+        intent.complete().await;
+        Ok(RemoveReport {
+            version: version.clone(),
+            path,
+            bytes_removed,
+            deactivated,
+            reasons,
+        })
+    }
+
+    /// Re-hashes every file in an installed toolchain and compares them against the SHA-256
+    /// digests recorded when it was installed.
+    ///
+    /// Digests are only recorded by clients built with
+    /// [`ToolchainClientBuilder::record_file_digests`], so toolchains installed without it
+    /// return [`ToolchainError::DigestsNotRecorded`].
+    #[instrument(parent = &self.span, skip_all, fields(product = self.product.id, %version))]
+    pub async fn verify_installed(
+        &self,
+        version: &ToolchainVersion,
+        progress: impl FnMut(VerifyProgress),
+        cancel_token: &CancellationToken,
+    ) -> Result<VerifyReport, ToolchainError> {
+        let toolchain = self.toolchain(version).await?;
+        debug!(path = ?toolchain.path, "Verifying toolchain");
+
+        digests::verify(&toolchain, version, progress, cancel_token)
+            .await?
+            .ok_or_else(|| ToolchainError::DigestsNotRecorded {
+                version: version.clone(),
+            })
+    }
+
+    /// Returns the total size of the files in the download cache, in bytes.
+    #[instrument(parent = &self.span, skip_all)]
+    pub async fn cache_size(&self) -> Result<u64, ToolchainError> {
+        match manifest::dir_size(&self.cache_path).await {
+            Ok(size) => Ok(size.bytes),
+            Err(ToolchainError::Io(e)) if e.kind() == ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(e),
+        }
     }
 
-    /// Delete the cache directory, returning the number of bytes deleted.
-    pub async fn purge_cache(&self) -> Result<u64, ToolchainError> {
-        let bytes = async {
-            let mut bytes = 0;
+    /// Delete the contents of the cache directory.
+    ///
+    /// This is best-effort: an entry that can't be deleted (e.g. because it's in use) is
+    /// recorded in the report, and the other entries are still deleted.
+    #[instrument(parent = &self.span, skip_all)]
+    pub async fn purge_cache(&self) -> Result<PurgeReport, ToolchainError> {
+        let mut report = PurgeReport::default();
+
+        let mut read_dir = match fs::read_dir(&self.cache_path).await {
+            Ok(read_dir) => read_dir,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(report),
+            Err(e) => return Err(e.into()),
+        };
 
-            let mut read_dir = fs::read_dir(&self.cache_path).await?;
-            while let Some(item) = read_dir.next_entry().await? {
+        while let Some(item) = read_dir.next_entry().await? {
+            let path = item.path();
+
+            let result = async {
                 let meta = item.metadata().await?;
-                bytes += meta.len();
+                if meta.is_dir() {
+                    remove_dir_progress(path.clone(), |_| {}, &CancellationToken::new()).await
+                } else {
+                    fs::remove_file(&path).await?;
+                    Ok(meta.len())
+                }
+            }
+            .await;
+
+            match result {
+                Ok(bytes) => {
+                    report.bytes_removed += bytes;
+                    report.removed.push(PurgedEntry { path, bytes });
+                }
+                Err(ToolchainError::Io(e)) if e.kind() == ErrorKind::NotFound => {}
+                Err(error) => {
+                    warn!(?path, ?error, "Failed to delete cache entry");
+                    report.failed.push(PurgeFailure {
+                        path,
+                        error: error.to_string(),
+                    });
+                }
+            }
+        }
+
+        // The directory itself can only be removed if everything in it was.
+        if report.failed.is_empty()
+            && let Err(error) = fs::remove_dir(&self.cache_path).await
+        {
+            debug!(?error, "Failed to delete cache directory");
+        }
+
+        Ok(report)
+    }
+
+    /// Deletes everything stored by clients using this client's directories: the toolchains
+    /// of every product, the active toolchain files, the journal, and the download cache.
+    ///
+    /// The toolchains directory is deleted permanently instead of being moved to the trash,
+    /// and the cache is purged with [`ToolchainClient::purge_cache`] afterwards.
+    ///
+    /// # Cancellation
+    ///
+    /// Like [`ToolchainClient::remove`], the token is only checked while files are being
+    /// listed, so once deletion has started it runs to completion.
+    #[instrument(parent = &self.span, skip_all)]
+    pub async fn purge_all(
+        &self,
+        progress: impl FnMut(RemoveProgress),
+        cancel_token: &CancellationToken,
+    ) -> Result<PurgeAllReport, ToolchainError> {
+        let mut report = PurgeAllReport::default();
+
+        for &product in Product::ALL {
+            let client = Self {
+                product,
+                ..self.clone()
+            };
+            let mut versions = client.installed_versions().await?;
+            versions.sort();
+            report.toolchains.extend(
+                versions
+                    .iter()
+                    .map(|version| client.install_path_for(version)),
+            );
+        }
+
+        match fs::symlink_metadata(&self.toolchains_path).await {
+            Ok(_) => {
+                in_use::check_not_in_use(&self.toolchains_path).await?;
+                debug!(phase = %Phase::Remove, path = ?self.toolchains_path, "Deleting toolchains directory");
+                report.bytes_removed =
+                    remove_dir_progress(self.toolchains_path.clone(), progress, cancel_token)
+                        .await?;
+                report.toolchains_dir = Some(self.toolchains_path.clone());
             }
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        *self.current_version.write().unwrap() = None;
+
+        report.cache = self.purge_cache().await?;
+        report.bytes_removed += report.cache.bytes_removed;
+
+        Ok(report)
+    }
+
+    /// Removes the cached download of one asset, along with any state saved alongside it,
+    /// returning the number of bytes deleted.
+    ///
+    /// The name can either be an asset's file name or a toolchain version, in which case
+    /// every cached asset of that version is removed. Other cached downloads are kept.
+    #[instrument(parent = &self.span, skip_all)]
+    pub async fn evict_cached(&self, name: &str) -> Result<u64, ToolchainError> {
+        if Utf8Path::new(name).file_name() != Some(name) {
+            return Err(ToolchainError::InvalidAssetName {
+                name: name.to_string(),
+            });
+        }
 
-            Ok::<u64, ToolchainError>(bytes)
+        let mut read_dir = match fs::read_dir(&self.cache_path).await {
+            Ok(read_dir) => read_dir,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
         };
 
-        let bytes = bytes.await.unwrap_or(0);
-        fs::remove_dir_all(&self.cache_path).await?;
+        let version = ToolchainVersion::from(name);
+        let mut bytes = 0;
+
+        while let Some(item) = read_dir.next_entry().await? {
+            let file_name = item.file_name();
+            let Some(file_name) = file_name.to_str() else {
+                continue;
+            };
+
+            let asset_name = checksum::archive_name(file_name)
+                .or_else(|| resume::archive_name(file_name))
+                .unwrap_or(file_name);
+            if asset_name != name
+                && AssetName::parse(asset_name).is_none_or(|asset| asset.version != version)
+            {
+                continue;
+            }
+
+            let meta = item.metadata().await?;
+            match fs::remove_file(item.path()).await {
+                Ok(()) => bytes += meta.len(),
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
         Ok(bytes)
     }
 
@@ -467,73 +2627,362 @@ impl ToolchainClient {
     /// Set the version of the active (default) toolchain.
     ///
     /// This will write the given value to disk.
+    ///
+    /// Returns [`ToolchainError::InvalidActiveToolchainFile`] if the active toolchain file
+    /// can't be replaced, such as when it's a directory.
+    #[instrument(parent = &self.span, skip_all)]
     pub async fn set_active_toolchain(
         &self,
         version: Option<ToolchainVersion>,
     ) -> Result<(), ToolchainError> {
-        let path = self.toolchains_path.join(Self::CURRENT_TOOLCHAIN_FILENAME);
-
-        if let Some(version) = &version {
-            fs::write(path, &version.name).await?;
+        let path = self.product_path().join(Self::CURRENT_TOOLCHAIN_FILENAME);
+        let subject = self.journal_subject(version.as_ref());
+        self.create_toolchains_dir().await;
+        let intent = self.journal().begin(Operation::Activate, subject).await;
+
+        let result = if let Some(version) = &version {
+            fs::create_dir_all(self.product_path()).await?;
+            fs::write(&path, &version.name).await
         } else {
-            match fs::remove_file(path).await {
-                Ok(()) => Ok(()),
+            match fs::remove_file(&path).await {
                 Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
                 other => other,
-            }?;
-        }
+            }
+        };
+        result.map_err(|error| Self::active_toolchain_file_error(&path, error))?;
 
         *self.current_version.write().unwrap() = version;
 
+        intent.complete().await;
         Ok(())
     }
 
+    /// Checks that the active toolchain file can be read and contains a version name.
+    ///
+    /// Clients ignore an invalid file (treating it as if there's no active toolchain), so
+    /// this can be used to find out why the active toolchain isn't what's expected.
+    #[instrument(parent = &self.span, skip_all)]
+    pub async fn check_active_toolchain_file(&self) -> Result<(), ToolchainError> {
+        let path = self.product_path().join(Self::CURRENT_TOOLCHAIN_FILENAME);
+        Self::read_active_toolchain_file(&path).await.map(drop)
+    }
+
+    /// Moves an invalid active toolchain file to the trash (or deletes it if it can't be
+    /// trashed), leaving no toolchain active.
+    ///
+    /// Does nothing if the file is valid. Returns whether the file was removed.
+    #[instrument(parent = &self.span, skip_all)]
+    pub async fn reset_active_toolchain_file(&self) -> Result<bool, ToolchainError> {
+        if self.check_active_toolchain_file().await.is_ok() {
+            return Ok(false);
+        }
+
+        let path = self.product_path().join(Self::CURRENT_TOOLCHAIN_FILENAME);
+        trash_or_remove(&path, &CancellationToken::new()).await?;
+        *self.current_version.write().unwrap() = None;
+
+        Ok(true)
+    }
+
+    /// Reads the active toolchain from the given file, or returns `None` if it doesn't exist.
+    pub(crate) async fn read_active_toolchain_file(
+        path: &Path,
+    ) -> Result<Option<ToolchainVersion>, ToolchainError> {
+        let name = match fs::read_to_string(path).await {
+            Ok(name) => name,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(Self::active_toolchain_file_error(path, e)),
+        };
+
+        let name = name.trim();
+        if name.is_empty() || name.contains(['/', '\\', '\n']) {
+            return Err(ToolchainError::InvalidActiveToolchainFile {
+                path: path.to_owned(),
+                reason: "it doesn't contain a toolchain version".to_string(),
+            });
+        }
+
+        Ok(Some(ToolchainVersion::named(name)))
+    }
+
+    /// Describes why the active toolchain file couldn't be read or written.
+    fn active_toolchain_file_error(path: &Path, error: std::io::Error) -> ToolchainError {
+        let reason = match error.kind() {
+            _ if path.is_dir() => "it is a directory".to_string(),
+            ErrorKind::InvalidData => "it isn't valid UTF-8 text".to_string(),
+            ErrorKind::PermissionDenied => "permission was denied".to_string(),
+            _ => return error.into(),
+        };
+
+        ToolchainError::InvalidActiveToolchainFile {
+            path: path.to_owned(),
+            reason,
+        }
+    }
+
+    /// Returns the migrations that were run on the toolchains directory's layout when this
+    /// client was created.
+    ///
+    /// This is only non-empty for the first client that opens a directory with an outdated
+    /// layout, so it can be used to tell the user about the change once.
+    pub fn layout_migrations(&self) -> &[LayoutMigration] {
+        &self.layout_migrations
+    }
+
+    /// Returns the user agent that the client sends with its requests to GitHub and when
+    /// downloading assets.
+    ///
+    /// This is [`APP_USER_AGENT`](super::APP_USER_AGENT) (or the user agent set with
+    /// [`ToolchainClientBuilder::user_agent`]), followed by the suffix set with
+    /// [`ToolchainClientBuilder::user_agent_suffix`] if there is one. A custom Octocrab
+    /// instance given to [`ToolchainClientBuilder::octocrab`] sends its own user agent.
+    pub fn user_agent(&self) -> &str {
+        &self.user_agent
+    }
+
+    /// Returns a handle to the journal of operations performed on this client's directories.
+    pub fn journal(&self) -> Journal {
+        Journal::in_dir(&self.toolchains_path)
+    }
+
+    /// Creates the toolchains directory before something is written to it, such as the
+    /// journal, so that it's created with the current layout version.
+    ///
+    /// Like writing to the journal, failing to do so isn't fatal.
+    async fn create_toolchains_dir(&self) {
+        if let Err(error) = layout::create_toolchains_dir(&self.toolchains_path).await {
+            warn!(?error, path = ?self.toolchains_path, "Failed to create the toolchains directory");
+        }
+    }
+
+    /// Returns the operations that were started but never finished, such as an installation
+    /// that was interrupted by a crash or power loss.
+    ///
+    /// Each operation is returned alongside the file or directory that it may have left
+    /// in an inconsistent state, if that still exists. Installations and removals that failed
+    /// are also returned if they left a toolchain behind, since it may be incomplete.
+    #[instrument(parent = &self.span, skip_all)]
+    pub async fn interrupted_operations(
+        &self,
+    ) -> Result<Vec<InterruptedOperation>, ToolchainError> {
+        let journal = self.journal();
+        let interrupted = journal.interrupted().await?.into_iter().map(|e| (e, false));
+        let failed = journal.failed().await?.into_iter().map(|e| (e, true));
+
+        let mut operations: Vec<_> = interrupted
+            .chain(failed)
+            .map(|(entry, failed)| {
+                // Entries written before products were introduced don't name one, and refer
+                // to the embedded toolchains that were migrated into their own directory.
+                let (product, version) = entry
+                    .subject
+                    .split_once('/')
+                    .unwrap_or((Product::EMBEDDED.id, &entry.subject));
+
+                let artifact = match entry.operation {
+                    Operation::Download => self.cache_path.join(&entry.subject),
+                    Operation::Extract | Operation::Remove => {
+                        self.toolchains_path.join(product).join(version)
+                    }
+                    Operation::Activate => self
+                        .toolchains_path
+                        .join(product)
+                        .join(Self::CURRENT_TOOLCHAIN_FILENAME),
+                    Operation::Migrate => layout::layout_file_path(&self.toolchains_path),
+                };
+
+                InterruptedOperation {
+                    artifact: artifact.exists().then_some(artifact),
+                    entry,
+                    failed,
+                }
+            })
+            .filter(|operation| {
+                !operation.failed
+                    || (operation.artifact.is_some()
+                        && matches!(
+                            operation.entry.operation,
+                            Operation::Extract | Operation::Remove
+                        ))
+            })
+            .collect();
+        operations.sort_by_key(|operation| operation.entry.timestamp);
+
+        Ok(operations)
+    }
+
+    /// Deletes the toolchains left behind by interrupted or failed installations and removals,
+    /// returning their paths.
+    ///
+    /// Operations that left nothing behind are marked as cleaned up too, so that neither are
+    /// returned by [`ToolchainClient::interrupted_operations`] again. Partial downloads are
+    /// kept so that they can be resumed.
+    #[instrument(parent = &self.span, skip_all)]
+    pub async fn clean_up_interrupted(
+        &self,
+        cancel_token: &CancellationToken,
+    ) -> Result<Vec<PathBuf>, ToolchainError> {
+        let journal = self.journal();
+        let mut removed = vec![];
+
+        for operation in self.interrupted_operations().await? {
+            let entry = &operation.entry;
+            if !matches!(entry.operation, Operation::Extract | Operation::Remove) {
+                continue;
+            }
+
+            if let Some(path) = operation.artifact {
+                debug!(
+                    ?path,
+                    ?entry,
+                    "Removing toolchain left behind by an operation"
+                );
+                in_use::check_not_in_use(&path).await?;
+                trash_or_remove(&path, cancel_token).await?;
+                removed.push(path);
+            }
+            journal.record_cleaned_up(entry).await;
+        }
+
+        Ok(removed)
+    }
+
     /// Returns a struct used to access paths of an installed toolchain.
     ///
     /// This doesn't check whether the specified version is actually installed,
     /// so make sure the paths exist before using them.
+    #[instrument(parent = &self.span, skip_all)]
     pub async fn toolchain(
         &self,
         version: &ToolchainVersion,
     ) -> Result<InstalledToolchain, ToolchainError> {
-        let toolchain = InstalledToolchain::new(self.toolchains_path.join(&version.name));
+        let mut path = self.install_path_for(version);
+        if !path.exists()
+            && let [similar] = self.similar_installs(version).as_slice()
+        {
+            warn!(?similar, "Using a toolchain directory with a similar name");
+            path = similar.clone();
+        }
+
+        let toolchain = InstalledToolchain::new(path);
         toolchain.check_installed().await?;
         Ok(toolchain)
     }
 }
 
-/// Scans an entire file and calculates its SHA256 checksum.
-async fn calculate_file_checksum(
-    file: &mut fs::File,
-    progress: Arc<dyn Fn(InstallState) + Send + Sync>,
-) -> Result<[u8; 32], io::Error> {
-    let file_size = file.metadata().await?.len();
-    progress(InstallState::VerifyingBegin {
-        asset_size: file_size,
-    });
+/// Records that an operation is about to start, if there is a journal to record it in.
+async fn begin_intent(
+    journal: Option<&Journal>,
+    operation: Operation,
+    subject: &str,
+) -> Option<Intent> {
+    match journal {
+        Some(journal) => Some(journal.begin(operation, subject).await),
+        None => None,
+    }
+}
+
+/// Where the install pipeline extracts a toolchain to.
+struct Destination {
+    path: PathBuf,
+    /// The journal that each phase is recorded in, if any.
+    journal: Option<Journal>,
+}
 
-    file.seek(SeekFrom::Start(0)).await?;
-    let mut reader = BufReader::new(file);
+/// Where the checksum that a download is verified against comes from.
+enum ExpectedChecksum {
+    /// Looked up by [`ToolchainClient::expected_checksum`].
+    Lookup,
+    /// Given along with the download, in hex format.
+    Known(String),
+    /// There's none, so the download isn't verified.
+    Unverified,
+}
 
-    let mut hasher = Sha256::default();
-    let mut data = vec![0; 64 * 1024];
+/// Checks that a toolchain can be installed under the given name, which is used as its
+/// directory name.
+fn check_toolchain_name(name: &str) -> Result<(), ToolchainError> {
+    let valid = !matches!(name, "" | "." | "..")
+        && !name.contains(['/', '\\'])
+        && name != ToolchainClient::CURRENT_TOOLCHAIN_FILENAME
+        && UnusableReason::of(OsStr::new(name)).is_none();
+    if valid {
+        Ok(())
+    } else {
+        Err(ToolchainError::InvalidToolchainName {
+            name: name.to_string(),
+        })
+    }
+}
 
-    let mut bytes_read = 0;
-    loop {
-        let len = reader.read(&mut data).await?;
-        if len == 0 {
-            break;
-        }
+/// Returns the file name of an asset, which is the name it's saved under in the download
+/// cache.
+fn asset_file_name(asset_name: &str) -> Result<&str, ToolchainError> {
+    Utf8Path::new(asset_name)
+        .file_name()
+        .ok_or_else(|| ToolchainError::InvalidAssetName {
+            name: asset_name.to_string(),
+        })
+}
 
-        hasher.update(&data[..len]);
+/// Whether a response to a request with a Range header can be appended to a partial download.
+#[derive(Debug, PartialEq, Eq)]
+enum ResumeCheck {
+    /// The response continues from the end of the partial download.
+    Resumed,
+    /// The server ignored the Range header and is sending the whole asset.
+    FullBody,
+    /// The response can't be used, so the download has to start over without a Range header.
+    Restart(&'static str),
+}
 
-        bytes_read += len as u64;
-        progress(InstallState::Verifying { bytes_read });
+/// Checks that a response to a Range request starts at `file_length`, the number of bytes
+/// that were already downloaded, and that it's for the version of the asset with the ETag in
+/// `if_range`, if one was sent.
+fn check_resume(
+    response: &reqwest::Response,
+    file_length: u64,
+    if_range: Option<&str>,
+) -> ResumeCheck {
+    // Ranges of an encoded response refer to the encoded bytes, not the asset's.
+    if decode::content_encoding(response.headers()).is_some() {
+        return ResumeCheck::Restart("Server sent an encoded response, which can't be resumed");
     }
 
-    let checksum = hasher.finalize().into();
+    match response.status() {
+        StatusCode::PARTIAL_CONTENT => {
+            // A server that doesn't support `If-Range` may resume a different version of the
+            // asset than the one that was partially downloaded.
+            if let Some(if_range) = if_range
+                && resume::strong_etag(response.headers()).is_some_and(|etag| etag != if_range)
+            {
+                return ResumeCheck::Restart("Server resumed a different version of the asset");
+            }
 
-    progress(InstallState::VerifyingFinish);
+            let start = response
+                .headers()
+                .get(header::CONTENT_RANGE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(content_range_start);
+
+            match start {
+                Some(start) if start == file_length => ResumeCheck::Resumed,
+                Some(_) => ResumeCheck::Restart("Server resumed the download at the wrong offset"),
+                None => {
+                    ResumeCheck::Restart("Server sent a missing or invalid Content-Range header")
+                }
+            }
+        }
+        StatusCode::RANGE_NOT_SATISFIABLE => {
+            ResumeCheck::Restart("Server can't resume the download from the partial file's length")
+        }
+        _ => ResumeCheck::FullBody,
+    }
+}
 
-    Ok(checksum)
+/// Parses the first byte's offset from a `Content-Range` header such as `bytes 100-199/200`.
+fn content_range_start(value: &str) -> Option<u64> {
+    let range = value.trim().strip_prefix("bytes ")?;
+    let (start, _) = range.split_once('-')?;
+    start.trim().parse().ok()
 }
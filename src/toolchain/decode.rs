@@ -0,0 +1,75 @@
+//! Decoding of asset downloads that a server or proxy compressed in transit.
+//!
+//! Assets are requested with `Accept-Encoding: identity`, but some caching proxies compress
+//! responses anyway. The compressed body can't be resumed with a Range request or compared
+//! to the asset's size, so it's decoded as it's downloaded and the checksum is relied on to
+//! catch any problems.
+
+use std::io::{self, Write};
+
+use flate2::write::{GzDecoder, ZlibDecoder};
+use reqwest::header::{self, HeaderMap};
+
+/// Returns the content encoding of a response, or `None` if its body isn't encoded.
+pub(crate) fn content_encoding(headers: &HeaderMap) -> Option<String> {
+    let encoding = headers
+        .get(header::CONTENT_ENCODING)?
+        .to_str()
+        .unwrap_or("unknown")
+        .trim()
+        .to_ascii_lowercase();
+
+    (!encoding.is_empty() && encoding != "identity").then_some(encoding)
+}
+
+/// Decodes a response body that was compressed with a content encoding.
+pub(crate) enum ContentDecoder {
+    Gzip(GzDecoder<Vec<u8>>),
+    Deflate(ZlibDecoder<Vec<u8>>),
+}
+
+impl ContentDecoder {
+    /// Creates a decoder for the given content encoding, or returns `None` if it isn't
+    /// supported.
+    pub fn new(encoding: &str) -> Option<Self> {
+        match encoding {
+            "gzip" | "x-gzip" => Some(Self::Gzip(GzDecoder::new(vec![]))),
+            "deflate" => Some(Self::Deflate(ZlibDecoder::new(vec![]))),
+            _ => None,
+        }
+    }
+
+    /// Decodes a chunk of the body and returns the bytes that were decoded from it.
+    ///
+    /// The bytes are decoded into the same buffer each time, so they're only valid until the
+    /// next chunk is decoded.
+    pub fn decode(&mut self, chunk: &[u8]) -> io::Result<&[u8]> {
+        match self {
+            Self::Gzip(decoder) => {
+                decoder.get_mut().clear();
+                decoder.write_all(chunk)?;
+                Ok(decoder.get_ref())
+            }
+            Self::Deflate(decoder) => {
+                decoder.get_mut().clear();
+                decoder.write_all(chunk)?;
+                Ok(decoder.get_ref())
+            }
+        }
+    }
+
+    /// Checks that the body was complete and returns any bytes that haven't been returned yet.
+    pub fn finish(self) -> io::Result<Vec<u8>> {
+        // The buffer still holds the bytes that the last call to `decode` returned.
+        match self {
+            Self::Gzip(mut decoder) => {
+                decoder.get_mut().clear();
+                decoder.finish()
+            }
+            Self::Deflate(mut decoder) => {
+                decoder.get_mut().clear();
+                decoder.finish()
+            }
+        }
+    }
+}
@@ -0,0 +1,229 @@
+//! Verification of an installed toolchain's contents against SHA-256 digests recorded when it
+//! was installed.
+//!
+//! Unlike the file list used to detect local changes, which only compares sizes and
+//! modification times, this notices any change to a file's contents. Hashing every file of a
+//! toolchain takes a while, so digests are only recorded if the client was built with
+//! [`ToolchainClientBuilder::record_file_digests`](super::ToolchainClientBuilder::record_file_digests).
+
+use std::{
+    collections::BTreeMap,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use data_encoding::HEXLOWER;
+use futures::{StreamExt, stream};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncReadExt;
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+
+use crate::{
+    cancel::CheckCancellation,
+    fs,
+    toolchain::{
+        InstalledToolchain, LocalChanges, Phase, ToolchainError, ToolchainVersion, changes,
+    },
+};
+
+/// The name of the digest list in a toolchain's directory.
+pub(crate) const DIGESTS_FILENAME: &str = ".arm-toolchain-digests";
+
+/// How many files are hashed at once.
+const CONCURRENCY: usize = 8;
+
+/// How many bytes of a file are read at a time while hashing it.
+const READ_CHUNK_SIZE: usize = 256 * 1024;
+
+/// The progress of [`ToolchainClient::verify_installed`](super::ToolchainClient::verify_installed).
+pub enum VerifyProgress {
+    /// The files have been listed, and hashing is about to start.
+    Start {
+        total_files: u64,
+        total_bytes: u64,
+    },
+    /// Another file has been hashed.
+    Progress {
+        files_done: u64,
+        bytes_done: u64,
+    },
+    End,
+}
+
+impl VerifyProgress {
+    /// Returns the phase that this event belongs to, which is always [`Phase::Verify`].
+    pub fn phase(&self) -> Phase {
+        Phase::Verify
+    }
+
+    /// Returns whether this is the last event of the verification.
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Self::End)
+    }
+}
+
+/// The result of [`ToolchainClient::verify_installed`](super::ToolchainClient::verify_installed).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VerifyReport {
+    /// The version of the toolchain that was verified.
+    pub version: ToolchainVersion,
+    /// The number of files that were hashed.
+    pub files_checked: u64,
+    /// The files that differ from when the toolchain was installed.
+    #[serde(flatten)]
+    pub changes: LocalChanges,
+}
+
+impl VerifyReport {
+    /// Returns whether every file matches its recorded digest, and no files were added or
+    /// deleted.
+    pub fn is_clean(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// Hashes a file, or the target of a symlink (without following it).
+async fn hash_file(path: &Path) -> Result<String, ToolchainError> {
+    let mut hasher = Sha256::new();
+
+    if fs::symlink_metadata(path).await?.is_symlink() {
+        let target = fs::read_link(path).await?;
+        hasher.update(b"symlink:");
+        hasher.update(target.to_string_lossy().as_bytes());
+    } else {
+        let mut file = fs::File::open(path).await?;
+        let mut buf = vec![0; READ_CHUNK_SIZE];
+        loop {
+            let read = file.read(&mut buf).await?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buf[..read]);
+        }
+    }
+
+    Ok(HEXLOWER.encode(&hasher.finalize()))
+}
+
+/// Hashes the given files in a toolchain, keyed by the same relative paths, with at most
+/// [`CONCURRENCY`] files being read at once.
+async fn hash_files(
+    root: &Path,
+    files: BTreeMap<String, u64>,
+    mut progress: impl FnMut(VerifyProgress),
+    cancel_token: &CancellationToken,
+) -> Result<BTreeMap<String, String>, ToolchainError> {
+    progress(VerifyProgress::Start {
+        total_files: files.len() as u64,
+        total_bytes: files.values().sum(),
+    });
+
+    let mut hashes = stream::iter(files)
+        .map(|(key, size)| async move {
+            cancel_token.check_cancellation(ToolchainError::Cancelled)?;
+            let digest = hash_file(&root.join(&key)).await?;
+            Ok::<_, ToolchainError>((key, size, digest))
+        })
+        .buffer_unordered(CONCURRENCY);
+
+    let mut digests = BTreeMap::new();
+    let mut bytes_done = 0;
+    while let Some(result) = hashes.next().await {
+        let (key, size, digest) = result?;
+        bytes_done += size;
+        digests.insert(key, digest);
+        progress(VerifyProgress::Progress {
+            files_done: digests.len() as u64,
+            bytes_done,
+        });
+    }
+
+    progress(VerifyProgress::End);
+    Ok(digests)
+}
+
+/// Lists the files in a toolchain along with their sizes.
+async fn list_files(
+    toolchain: &InstalledToolchain,
+) -> Result<BTreeMap<String, u64>, ToolchainError> {
+    Ok(changes::scan(&toolchain.path)
+        .await?
+        .into_iter()
+        .map(|(key, state)| (key, state.size))
+        .collect())
+}
+
+/// Parses a digest list, which contains one `digest\tpath` line per file.
+///
+/// Malformed lines are ignored, which makes the files they described show up as added.
+fn parse(text: &str) -> BTreeMap<String, String> {
+    text.lines()
+        .filter_map(|line| {
+            let (digest, path) = line.split_once('\t')?;
+            Some((path.to_string(), digest.to_string()))
+        })
+        .collect()
+}
+
+/// Hashes every file in a toolchain and records the digests, so that its contents can be
+/// checked with [`verify`].
+pub(crate) async fn record_digests(
+    toolchain: &InstalledToolchain,
+    cancel_token: &CancellationToken,
+) -> Result<(), ToolchainError> {
+    let files = list_files(toolchain).await?;
+    let digests = hash_files(&toolchain.path, files, |_| {}, cancel_token).await?;
+
+    let mut text = String::new();
+    for (path, digest) in &digests {
+        text += &format!("{digest}\t{path}\n");
+    }
+
+    fs::write(toolchain.path.join(DIGESTS_FILENAME), text).await?;
+    debug!(count = digests.len(), ?toolchain.path, "Recorded toolchain digests");
+    Ok(())
+}
+
+/// Hashes every file in a toolchain and compares them against the recorded digests.
+///
+/// Returns `None` if no digests were recorded.
+pub(crate) async fn verify(
+    toolchain: &InstalledToolchain,
+    version: &ToolchainVersion,
+    progress: impl FnMut(VerifyProgress),
+    cancel_token: &CancellationToken,
+) -> Result<Option<VerifyReport>, ToolchainError> {
+    let recorded = match fs::read_to_string(toolchain.path.join(DIGESTS_FILENAME)).await {
+        Ok(text) => parse(&text),
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let current = list_files(toolchain).await?;
+    let mut report = VerifyReport {
+        version: version.clone(),
+        files_checked: 0,
+        changes: LocalChanges::default(),
+    };
+
+    // Only files that are expected to be there are hashed.
+    let (expected, added): (BTreeMap<_, _>, BTreeMap<_, _>) = current
+        .into_iter()
+        .partition(|(key, _)| recorded.contains_key(key));
+    report.changes.added = added.into_keys().map(PathBuf::from).collect();
+
+    let mut hashes = hash_files(&toolchain.path, expected, progress, cancel_token).await?;
+    report.files_checked = hashes.len() as u64;
+
+    for (path, digest) in recorded {
+        match hashes.remove(&path) {
+            Some(current) if current == digest => {}
+            Some(_) => report.changes.modified.push(PathBuf::from(path)),
+            None => report.changes.deleted.push(PathBuf::from(path)),
+        }
+    }
+
+    Ok(Some(report))
+}
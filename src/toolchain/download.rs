@@ -0,0 +1,149 @@
+//! Writing the body of an asset download to its file.
+//!
+//! Each chunk of the body is written straight from the buffer `reqwest` received it in.
+//! Chunks smaller than the client's download buffer (see
+//! [`ToolchainClientBuilder::download_buffer_size`](super::ToolchainClientBuilder::download_buffer_size))
+//! are gathered in it first, so that the file is written in large blocks instead of once for
+//! each chunk, which matters because every write to a Tokio file is handed off to a blocking
+//! thread. Larger chunks bypass the buffer. Encoded bodies are decoded into one buffer that's
+//! reused for every chunk.
+//!
+//! A download that receives no data for the client's stall timeout (see
+//! [`ToolchainClientBuilder::download_stall_timeout`](super::ToolchainClientBuilder::download_stall_timeout))
+//! fails with [`ToolchainError::DownloadStalled`], keeping what was written so that it can be
+//! resumed.
+//!
+//! Failed downloads that might succeed if they're tried again are retried by the client, with
+//! a growing delay between attempts (see
+//! [`ToolchainClientBuilder::download_attempts`](super::ToolchainClientBuilder::download_attempts)).
+//!
+//! The `download_buffers` example measures the allocations made during a download with
+//! different buffer sizes.
+
+use std::time::Duration;
+
+use futures::TryStreamExt;
+use reqwest::Response;
+use tokio::io::{AsyncWrite, AsyncWriteExt, BufWriter};
+
+use crate::toolchain::{ToolchainError, decode::ContentDecoder};
+
+/// How much of a download is gathered in memory before it's written to the file, unless the
+/// client was configured otherwise.
+///
+/// This is small so that downloads use little memory. A buffer of 64 KiB or more makes fewer
+/// writes (and allocations), at the cost of holding more of the download in memory.
+pub const DEFAULT_DOWNLOAD_BUFFER_SIZE: usize = 8 * 1024;
+
+/// How long a download may go without receiving any data before it's considered stalled,
+/// unless the client was configured otherwise.
+pub const DEFAULT_DOWNLOAD_STALL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How many times an asset download is attempted before giving up, unless the client was
+/// configured otherwise.
+pub const DEFAULT_DOWNLOAD_ATTEMPTS: u32 = 4;
+
+/// The delay before the first retry of a download, which doubles with each retry after it.
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+
+/// The longest delay between two download attempts.
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// How long connecting to a download server may take, unless the client was configured
+/// otherwise.
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Writes the body of a response to `file`, decoding it first if there's a decoder, and
+/// returns the file once everything has been written and flushed.
+///
+/// `bytes_written` is how much of the asset is already in the file, and `progress` is called
+/// with the new total after each chunk is written. If no chunk arrives for `stall_timeout`,
+/// this returns [`ToolchainError::DownloadStalled`] after flushing what was received.
+pub(crate) async fn write_body<W: AsyncWrite + Unpin>(
+    response: Response,
+    file: W,
+    mut decoder: Option<ContentDecoder>,
+    buffer_size: usize,
+    stall_timeout: Duration,
+    mut bytes_written: u64,
+    mut progress: impl FnMut(u64),
+) -> Result<W, ToolchainError> {
+    let mut body = response.bytes_stream();
+    let mut writer = BufWriter::with_capacity(buffer_size, file);
+
+    loop {
+        // The client's read timeout is the same as the stall timeout, so either one can notice
+        // the stall first.
+        let next = match tokio::time::timeout(stall_timeout, body.try_next()).await {
+            Ok(Err(error)) if !error.is_timeout() => return Err(error.into()),
+            Ok(Ok(next)) => next,
+            Ok(Err(_)) | Err(_) => {
+                // Whatever was received is kept, so that the download resumes from it.
+                writer.flush().await?;
+                return Err(ToolchainError::DownloadStalled {
+                    timeout: stall_timeout,
+                    bytes_written,
+                });
+            }
+        };
+        let Some(chunk) = next else {
+            break;
+        };
+
+        let data = match &mut decoder {
+            Some(decoder) => decoder.decode(&chunk)?,
+            None => &chunk,
+        };
+        writer.write_all(data).await?;
+
+        bytes_written += data.len() as u64;
+        progress(bytes_written);
+    }
+
+    if let Some(decoder) = decoder {
+        let rest = decoder.finish()?;
+        writer.write_all(&rest).await?;
+
+        bytes_written += rest.len() as u64;
+        progress(bytes_written);
+    }
+
+    writer.flush().await?;
+    Ok(writer.into_inner())
+}
+
+/// Returns whether a failed download might succeed if it's tried again, such as when the
+/// connection was reset, timed out, or stalled, or the server responded with a 5xx status.
+///
+/// Client errors (4xx) and anything that went wrong locally aren't retried.
+pub(crate) fn is_transient(error: &ToolchainError) -> bool {
+    match error {
+        ToolchainError::DownloadStalled { .. } => true,
+        ToolchainError::Reqwest(error) => match error.status() {
+            Some(status) => status.is_server_error(),
+            // A body that ends early is reported as a decode error, since bodies are
+            // decoded while they're read (even though `reqwest` itself never decodes them
+            // here).
+            None => {
+                error.is_connect()
+                    || error.is_timeout()
+                    || error.is_request()
+                    || error.is_body()
+                    || error.is_decode()
+            }
+        },
+        _ => false,
+    }
+}
+
+/// Returns how long to wait before retrying a download after the given failed attempt
+/// (starting at 1).
+///
+/// The delay doubles after each attempt, up to [`RETRY_MAX_DELAY`], and a random part of up to
+/// half of it is left out so that many clients that failed at once don't all retry at once.
+pub(crate) fn retry_delay(attempt: u32) -> Duration {
+    let delay = RETRY_BASE_DELAY
+        .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+        .min(RETRY_MAX_DELAY);
+    delay.mul_f64(1.0 - fastrand::f64() / 2.0)
+}
@@ -1,9 +1,10 @@
 //! This module provides functionality to extract toolchain archives in formats
-//! such as DMG, ZIP, and TAR.XZ.
+//! such as DMG, ZIP, and TAR.XZ. The formats themselves are described in
+//! [`archive`](super::archive), which dispatches to these functions.
 
 use std::{
-    io::BufReader,
-    path::{Path, PathBuf},
+    io::{BufReader, Read, Seek, SeekFrom},
+    path::{Component, Path, PathBuf},
     sync::Arc,
 };
 
@@ -15,11 +16,12 @@ use thiserror::Error;
 use tokio::{io, task::spawn_blocking};
 use tokio_util::sync::CancellationToken;
 use tracing::debug;
-use zip::{read::root_dir_common_filter, result::ZipError};
+use zip::result::ZipError;
 
 use crate::{
-    CheckCancellation, fs,
-    toolchain::{InstallState, ToolchainError},
+    cancel::{self, CheckCancellation},
+    fs,
+    toolchain::{InstallState, ToolchainError, listed_code},
 };
 
 #[cfg(target_os = "macos")]
@@ -27,7 +29,6 @@ pub mod macos;
 
 #[cfg(not(target_os = "macos"))]
 pub mod macos {
-    use indicatif::ProgressBar;
     use tokio_util::sync::CancellationToken;
 
     use super::*;
@@ -37,7 +38,7 @@ pub mod macos {
         _destination_folder: &Path,
         _progress: Arc<dyn Fn(InstallState) + Send + Sync>,
         _cancel_token: CancellationToken,
-    ) -> Result<(), ToolchainError> {
+    ) -> Result<Option<String>, ToolchainError> {
         Err(ExtractError::DmgNotSupported.into())
     }
 }
@@ -48,6 +49,13 @@ pub enum ExtractError {
     #[diagnostic(code(arm_toolchain::extract::dmg_not_supported))]
     DmgNotSupported,
 
+    #[error("{file_name} isn't in a supported archive format")]
+    #[diagnostic(code(arm_toolchain::extract::unsupported_format))]
+    #[diagnostic(help(
+        "Applications using this crate as a library can add formats with `ToolchainClientBuilder::register_archive_format`."
+    ))]
+    UnsupportedFormat { file_name: String },
+
     #[error("The archive did not contain the expected contents")]
     #[diagnostic(code(arm_toolchain::extract::contents_not_found))]
     ContentsNotFound,
@@ -59,36 +67,457 @@ pub enum ExtractError {
     #[error("ZIP extraction failed")]
     #[diagnostic(code(arm_toolchain::extract::zip_failed))]
     Zip(#[from] ZipError),
+
+    #[error("Refusing to extract the archive because {reason} (entry: {entry:?})")]
+    #[diagnostic(code(arm_toolchain::extract::suspicious_archive))]
+    #[diagnostic(help("the downloaded file may be corrupted or malicious"))]
+    SuspiciousArchive { reason: String, entry: String },
+}
+
+impl ExtractError {
+    /// Returns the error's stable diagnostic code. See [`ToolchainError::stable_code`].
+    pub fn stable_code(&self) -> &'static str {
+        listed_code(self, ToolchainError::all_codes())
+    }
+}
+
+/// Limits on how much data an archive may extract to.
+///
+/// These protect against corrupted or malicious archives (such as "zip bombs") filling up
+/// the disk. The defaults are far larger than any real toolchain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtractLimits {
+    /// The maximum total size of the extracted files, in bytes.
+    pub max_uncompressed_size: u64,
+    /// The maximum ratio between the total size of the extracted files and the size
+    /// of the archive.
+    pub max_compression_ratio: u64,
+}
+
+impl Default for ExtractLimits {
+    fn default() -> Self {
+        Self {
+            max_uncompressed_size: 64 * 1024 * 1024 * 1024,
+            max_compression_ratio: 100,
+        }
+    }
+}
+
+impl ExtractLimits {
+    /// Returns the maximum number of bytes that an archive of the given size may extract to.
+    fn max_for_archive(&self, archive_size: u64) -> u64 {
+        self.max_uncompressed_size
+            .min(archive_size.saturating_mul(self.max_compression_ratio))
+    }
+
+    /// Checks that extracting `total_size` bytes from an archive of the given size is allowed.
+    fn check(&self, total_size: u64, archive_size: u64, entry: &str) -> Result<(), ExtractError> {
+        if total_size <= self.max_for_archive(archive_size) {
+            return Ok(());
+        }
+
+        let reason = if total_size > self.max_uncompressed_size {
+            format!(
+                "its contents are larger than the limit of {} bytes",
+                self.max_uncompressed_size
+            )
+        } else {
+            format!(
+                "its contents are more than {} times larger than the archive",
+                self.max_compression_ratio
+            )
+        };
+
+        Err(ExtractError::SuspiciousArchive {
+            reason,
+            entry: entry.to_string(),
+        })
+    }
+}
+
+/// Normalizes the path of an archive entry, making sure that it stays inside the
+/// destination directory.
+///
+/// Absolute paths and paths whose `..` components would escape the destination are rejected.
+fn sanitize_entry_path(path: &Path) -> Result<PathBuf, ExtractError> {
+    let suspicious = |reason: &str| ExtractError::SuspiciousArchive {
+        reason: reason.to_string(),
+        entry: path.display().to_string(),
+    };
+
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => normalized.push(part),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    return Err(suspicious("an entry's path escapes the destination"));
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => {
+                return Err(suspicious("an entry has an absolute path"));
+            }
+        }
+    }
+
+    Ok(normalized)
+}
+
+/// Checks that a link stored at `entry_path` (relative to the destination) with the given
+/// target doesn't point outside the destination.
+fn check_link_target(entry_path: &Path, target: &Path) -> Result<(), ExtractError> {
+    let parent = entry_path.parent().unwrap_or(Path::new(""));
+    sanitize_entry_path(&parent.join(target)).map_err(|_| ExtractError::SuspiciousArchive {
+        reason: "a link points outside the destination".to_string(),
+        entry: entry_path.display().to_string(),
+    })?;
+
+    Ok(())
+}
+
+/// Checks that none of the directories that `relative_path` is in (relative to
+/// `destination`) are links, so that an entry can't be written through a link that another
+/// entry created.
+fn check_no_link_ancestors(destination: &Path, relative_path: &Path) -> Result<(), ToolchainError> {
+    let Some(parent) = relative_path.parent() else {
+        return Ok(());
+    };
+
+    let mut ancestor = destination.to_owned();
+    for component in parent.components() {
+        ancestor.push(component);
+        match std::fs::symlink_metadata(&ancestor) {
+            Ok(meta) if meta.file_type().is_symlink() => {
+                return Err(ExtractError::SuspiciousArchive {
+                    reason: "an entry is inside a link".to_string(),
+                    entry: relative_path.display().to_string(),
+                }
+                .into());
+            }
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => break,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the name of an archive's root directory from its path.
+fn root_dir_name(path: &Path) -> Option<String> {
+    Some(path.file_name()?.to_string_lossy().into_owned())
+}
+
+/// Finds the directory that contains all of an archive's entries, so that every archive
+/// format produces the same layout.
+///
+/// The rules are:
+///
+/// - Metadata added by archiving tools (`__MACOSX/` and top-level `.DS_Store` or `Thumbs.db`
+///   files) is skipped, and doesn't count as an entry.
+/// - If every other entry is inside the same top-level directory, that directory is the root,
+///   and it's removed from the entries' paths.
+/// - Otherwise (for example, if there's a stray top-level file or several top-level
+///   directories), there's no root and entries are extracted as they are.
+///
+/// Entry paths must already be sanitized with [`sanitize_entry_path`].
+#[derive(Debug, Default)]
+struct RootDetector {
+    state: RootState,
+}
+
+#[derive(Debug, Default)]
+enum RootState {
+    /// No entries have been seen yet.
+    #[default]
+    Empty,
+    /// Every entry so far is inside this directory.
+    Single(PathBuf),
+    /// The entries don't share a root directory.
+    Mixed,
+}
+
+impl RootDetector {
+    /// Returns whether the entry is metadata that should be skipped.
+    fn is_ignored(path: &Path) -> bool {
+        const IGNORED_ROOT_FILES: &[&str] = &[".DS_Store", "Thumbs.db"];
+
+        path.starts_with("__MACOSX")
+            || (path.components().count() == 1
+                && path
+                    .file_name()
+                    .is_some_and(|name| IGNORED_ROOT_FILES.iter().any(|ignored| name == *ignored)))
+    }
+
+    /// Takes an entry that isn't ignored into account.
+    fn observe(&mut self, path: &Path, is_dir: bool) {
+        let mut components = path.components();
+        let Some(first) = components.next() else {
+            return;
+        };
+        let top_level = Path::new(first.as_os_str());
+
+        // Only a directory can be the root, so a top-level file means there isn't one.
+        let is_top_level_file = components.next().is_none() && !is_dir;
+
+        self.state = match &self.state {
+            _ if is_top_level_file => RootState::Mixed,
+            RootState::Empty => RootState::Single(top_level.to_owned()),
+            RootState::Single(root) if root == top_level => return,
+            RootState::Single(_) | RootState::Mixed => RootState::Mixed,
+        };
+    }
+
+    /// Returns whether any entries were observed.
+    fn is_empty(&self) -> bool {
+        matches!(self.state, RootState::Empty)
+    }
+
+    /// Returns the root directory, if every entry is inside one.
+    fn root(&self) -> Option<&Path> {
+        match &self.state {
+            RootState::Single(root) => Some(root),
+            RootState::Empty | RootState::Mixed => None,
+        }
+    }
+}
+
+/// Estimates how many bytes extracting a zip archive will write from the size of every
+/// entry, which is listed in the archive. Returns `None` if the size can't be read.
+pub async fn zip_estimated_size(archive: &fs::File) -> Option<u64> {
+    let file = archive.try_clone().await.ok()?.into_std().await;
+    spawn_blocking(move || {
+        let size = zip::ZipArchive::new(BufReader::new(file))
+            .ok()?
+            .decompressed_size()?;
+        size.try_into().ok()
+    })
+    .await
+    .unwrap()
+}
+
+/// Estimates how many bytes extracting a tar.xz archive will write from the uncompressed
+/// size recorded by xz, which includes a little overhead from the tar headers. Returns
+/// `None` if the size can't be read.
+pub async fn tar_xz_estimated_size(archive: &fs::File) -> Option<u64> {
+    let file = archive.try_clone().await.ok()?.into_std().await;
+    spawn_blocking(move || xz_uncompressed_size(&mut BufReader::new(file)))
+        .await
+        .unwrap()
+}
+
+/// The largest xz index that [`xz_uncompressed_size`] reads. Toolchain archives have
+/// indexes of a few kilobytes.
+const MAX_XZ_INDEX_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Reads the uncompressed size of an xz file from the index at its end.
+///
+/// Only files with a single stream are supported, which is what `xz` creates.
+fn xz_uncompressed_size(reader: &mut (impl Read + Seek)) -> Option<u64> {
+    // The stream footer is the CRC32 of the next two fields, the size of the index, the
+    // stream flags, and the magic bytes "YZ".
+    let mut footer = [0; 12];
+    reader.seek(SeekFrom::End(-12)).ok()?;
+    reader.read_exact(&mut footer).ok()?;
+    if &footer[10..] != b"YZ" {
+        return None;
+    }
+
+    let index_size = (u64::from(u32::from_le_bytes(footer[4..8].try_into().ok()?)) + 1) * 4;
+    if index_size > MAX_XZ_INDEX_SIZE {
+        return None;
+    }
+
+    let mut index = vec![0; index_size as usize];
+    reader.seek(SeekFrom::End(-12 - index_size as i64)).ok()?;
+    reader.read_exact(&mut index).ok()?;
+
+    // The index starts with a zero byte and the number of blocks, followed by the
+    // compressed and uncompressed size of each block.
+    let mut bytes = index.into_iter();
+    if bytes.next()? != 0 {
+        return None;
+    }
+
+    let blocks = read_xz_varint(&mut bytes)?;
+    let mut total = 0u64;
+    for _ in 0..blocks {
+        read_xz_varint(&mut bytes)?;
+        total = total.checked_add(read_xz_varint(&mut bytes)?)?;
+    }
+
+    Some(total)
+}
+
+/// Reads a variable-length integer as encoded in xz indexes (7 bits per byte, least
+/// significant first).
+fn read_xz_varint(bytes: &mut impl Iterator<Item = u8>) -> Option<u64> {
+    let mut value = 0;
+    for shift in (0..63).step_by(7) {
+        let byte = bytes.next()?;
+        value |= u64::from(byte & 0x7F) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+
+    None
 }
 
+/// Extracts a zip archive to `destination`, removing the directory that contains the
+/// archive's entries if they're all in one (see [`RootDetector`]).
+///
+/// Returns the name of that directory.
 pub async fn extract_zip(
     zip_file: fs::File,
     destination: PathBuf,
-) -> Result<fs::File, ToolchainError> {
+    limits: ExtractLimits,
+    progress: Arc<dyn Fn(InstallState) + Send + Sync>,
+    cancel_token: CancellationToken,
+) -> Result<Option<String>, ToolchainError> {
+    let archive_size = zip_file.metadata().await?.len();
     let mut reader = BufReader::new(zip_file.into_std().await);
 
-    let file = spawn_blocking(move || {
-        let mut archive = zip::ZipArchive::new(&mut reader)?;
+    // Entries are extracted one at a time (rather than with `ZipArchive::extract`) so that
+    // cancellation can be checked and entries can be validated in between them.
+    spawn_blocking(move || {
+        let mut archive = zip::ZipArchive::new(&mut reader).map_err(ExtractError::Zip)?;
 
-        archive.extract_unwrapped_root_dir(destination, root_dir_common_filter)?;
+        std::fs::create_dir_all(&destination)?;
 
-        Ok::<_, ZipError>(reader.into_inner())
+        // The central directory lists every entry, so the root can be found before
+        // extracting anything.
+        let mut detector = RootDetector::default();
+        for name in archive.file_names() {
+            let path = sanitize_entry_path(Path::new(name))?;
+            if !RootDetector::is_ignored(&path) {
+                detector.observe(&path, name.ends_with('/'));
+            }
+        }
+        if detector.is_empty() {
+            return Err(ExtractError::ContentsNotFound.into());
+        }
+        let root_dir = detector.root().map(Path::to_owned);
+        let total_size = archive
+            .decompressed_size()
+            .map_or(0, |size| size.try_into().unwrap_or(u64::MAX));
+        limits.check(total_size, archive_size, "(all entries)")?;
+        let max_size = limits.max_for_archive(archive_size);
+        let mut bytes_copied = 0;
+
+        #[cfg(unix)]
+        let mut files_by_unix_mode = vec![];
+        // Links are created after everything else, so that no entry is written through one.
+        let mut links = vec![];
+
+        for idx in 0..archive.len() {
+            cancel_token.check_cancellation(ToolchainError::Cancelled)?;
+
+            let mut entry = archive.by_index(idx).map_err(ExtractError::Zip)?;
+
+            let name = sanitize_entry_path(Path::new(entry.name()))?;
+            if RootDetector::is_ignored(&name) {
+                continue;
+            }
+            let relative_path = match &root_dir {
+                Some(root_dir) => name.strip_prefix(root_dir).unwrap_or(&name),
+                None => &name,
+            };
+            if relative_path.as_os_str().is_empty() {
+                continue;
+            }
+
+            let out_path = destination.join(relative_path);
+            if entry.is_dir() {
+                std::fs::create_dir_all(&out_path)?;
+                continue;
+            }
+
+            if entry.is_symlink() {
+                let mut target = String::new();
+                entry.read_to_string(&mut target)?;
+                check_link_target(relative_path, Path::new(&target))?;
+                links.push((relative_path.to_owned(), target));
+                continue;
+            }
+
+            if let Some(parent) = out_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            // Entry sizes are only declared by the archive, so also enforce the limits
+            // while decompressing in case they were wrong.
+            let mut out_file = std::fs::File::create(&out_path)?;
+            let remaining = max_size.saturating_sub(bytes_copied);
+            bytes_copied += cancel::copy_blocking(
+                &cancel_token,
+                &mut (&mut entry).take(remaining + 1),
+                &mut out_file,
+                |_| {},
+            )?;
+            limits.check(bytes_copied, archive_size, entry.name())?;
+
+            #[cfg(unix)]
+            if let Some(mode) = entry.unix_mode() {
+                files_by_unix_mode.push((out_path, mode));
+            }
+
+            progress(InstallState::ExtractCopy {
+                total_size,
+                bytes_copied,
+            });
+        }
+
+        for (relative_path, target) in links {
+            check_no_link_ancestors(&destination, &relative_path)?;
+            let out_path = destination.join(relative_path);
+            if let Some(parent) = out_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(target, &out_path)?;
+            #[cfg(windows)]
+            std::os::windows::fs::symlink_file(target, &out_path)?;
+        }
+
+        // Permissions are applied last, children first, in case they make a parent unwritable.
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            files_by_unix_mode.sort_by(|(a, _), (b, _)| b.cmp(a));
+            for (path, mode) in files_by_unix_mode {
+                std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+            }
+        }
+
+        Ok::<_, ToolchainError>(root_dir.as_deref().and_then(root_dir_name))
     })
     .await
     .unwrap()
-    .map_err(ExtractError::Zip)?;
-
-    Ok(file.into())
 }
 
+/// Extracts a tar.xz archive to `destination`, removing the directory that contains the
+/// archive's entries if they're all in one (see [`RootDetector`]).
+///
+/// Returns the name of that directory.
 pub async fn extract_tar_xz(
     tar_xz_file: fs::File,
     destination: PathBuf,
+    limits: ExtractLimits,
     progress: Arc<dyn Fn(InstallState) + Send + Sync>,
     cancel_token: CancellationToken,
-) -> Result<fs::File, ToolchainError> {
+) -> Result<Option<String>, ToolchainError> {
+    let archive_size = tar_xz_file.metadata().await?.len();
     let mut reader = BufReader::new(tar_xz_file.into_std().await);
 
+    // Tar entries are only found while decompressing, so progress is reported against the
+    // size of the decompressed tar archive.
+    let estimated_size = xz_uncompressed_size(&mut reader);
+    reader.seek(SeekFrom::Start(0))?;
+
     let temp_destination = Arc::new(tempdir()?);
 
     // This behavior is necessary because the archive contains a sub-directory which we want to ignore.
@@ -97,42 +526,100 @@ pub async fn extract_tar_xz(
         "This tar.xz archive will be extracted to a temporary directory before being moved to the final destination"
     );
 
-    let file = spawn_blocking({
+    // The archive can only be read once, so the root is found while extracting it to the
+    // temporary directory.
+    let detector = spawn_blocking({
         let temp_destination = temp_destination.clone();
+        let progress = progress.clone();
+        let cancel_token = cancel_token.clone();
         move || {
             let mut decompressor = XzDecoder::new(&mut reader);
             let mut archive = tar::Archive::new(&mut decompressor);
+            let mut total_size = 0;
+            let mut detector = RootDetector::default();
+
+            // Directories are unpacked last so that their permissions can't prevent
+            // their contents from being created (this mirrors `tar::Archive::unpack`). Links
+            // are unpacked after the files, so that no file is written through one.
+            let mut directories = vec![];
+            let mut links = vec![];
+            for entry in archive.entries()? {
+                cancel_token.check_cancellation(ToolchainError::Cancelled)?;
+
+                let mut entry = entry?;
+                let path = sanitize_entry_path(&entry.path()?)?;
+                if RootDetector::is_ignored(&path) {
+                    continue;
+                }
+
+                let is_dir = entry.header().entry_type() == tar::EntryType::Directory;
+                detector.observe(&path, is_dir);
+
+                let is_link = match entry.link_name()? {
+                    Some(target) => {
+                        check_link_target(&path, &target)?;
+                        true
+                    }
+                    None => false,
+                };
+
+                total_size += entry.size();
+                limits.check(total_size, archive_size, &path.display().to_string())?;
+
+                if is_dir {
+                    directories.push(entry);
+                } else if is_link {
+                    links.push(entry);
+                } else {
+                    entry.unpack_in(temp_destination.path())?;
+
+                    if let Some(estimated_size) = estimated_size {
+                        progress(InstallState::ExtractCopy {
+                            total_size: estimated_size.max(total_size),
+                            bytes_copied: total_size,
+                        });
+                    }
+                }
+            }
+
+            for mut link in links {
+                check_no_link_ancestors(
+                    temp_destination.path(),
+                    &sanitize_entry_path(&link.path()?)?,
+                )?;
+                link.unpack_in(temp_destination.path())?;
+            }
+
+            directories.sort_by(|a, b| b.path_bytes().cmp(&a.path_bytes()));
+            for mut dir in directories {
+                check_no_link_ancestors(
+                    temp_destination.path(),
+                    &sanitize_entry_path(&dir.path()?)?,
+                )?;
+                dir.unpack_in(temp_destination.path())?;
+            }
 
-            archive.unpack(temp_destination.path())?;
             debug!("Done unpacking");
-            Ok::<_, io::Error>(reader.into_inner())
+            Ok::<_, ToolchainError>(detector)
         }
     })
     .await
     .unwrap()?;
 
-    // Find the root directory in the extracted contents and move it to the destination
-    let root_dir = find_dir_contained_by(temp_destination.path()).await?;
-    debug!("mv");
-    mv(&root_dir, &destination, progress, cancel_token).await?;
-
-    Ok(file.into())
-}
-
-async fn find_dir_contained_by(parent_dir: &Path) -> Result<PathBuf, ToolchainError> {
-    let mut contents_path = None;
-
-    let mut read_dir = fs::read_dir(parent_dir).await?;
-    while let Some(entry) = read_dir.next_entry().await? {
-        let metadata = entry.metadata().await?;
-        let is_dir = metadata.is_dir() && !metadata.is_symlink();
-        if is_dir {
-            contents_path = Some(entry.path());
-            break;
-        }
+    if detector.is_empty() {
+        return Err(ExtractError::ContentsNotFound.into());
     }
 
-    Ok(contents_path.ok_or(ExtractError::ContentsNotFound)?)
+    // Move the root directory (or everything, if there isn't one) to the destination.
+    let root_dir = detector.root();
+    let source = match root_dir {
+        Some(root_dir) => temp_destination.path().join(root_dir),
+        None => temp_destination.path().to_owned(),
+    };
+    debug!(?source, "mv");
+    mv(&source, &destination, progress, cancel_token).await?;
+
+    Ok(root_dir.and_then(root_dir_name))
 }
 
 pub async fn mv(
@@ -198,13 +685,17 @@ async fn copy_folder(
 
             // fs::set_permissions(new_path, perms).await?;
         } else {
-            fs::copy(source_path, new_path).await?;
-            bytes_so_far += size;
-
-            progress(InstallState::ExtractCopy {
-                total_size,
-                bytes_copied: bytes_so_far,
+            let mut source_file = fs::File::open(&source_path).await?;
+            let mut new_file = fs::File::create(&new_path).await?;
+            cancel::copy(&cancel_token, &mut source_file, &mut new_file, |copied| {
+                progress(InstallState::ExtractCopy {
+                    total_size,
+                    bytes_copied: bytes_so_far + copied,
+                })
             })
+            .await?;
+            fs::set_permissions(&new_path, source_file.metadata().await?.permissions()).await?;
+            bytes_so_far += size;
         }
     }
 
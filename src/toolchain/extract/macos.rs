@@ -10,13 +10,13 @@ use std::{
 use dmg::detach;
 use tokio::{task::spawn_blocking, time::sleep};
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use crate::{
-    CheckCancellation,
+    cancel::CheckCancellation,
     toolchain::{
         InstallState, ToolchainError,
-        extract::{ExtractError, copy_folder, find_dir_contained_by},
+        extract::{ExtractError, copy_folder, find_dir_contained_by, root_dir_name},
     },
 };
 
@@ -25,7 +25,7 @@ pub async fn extract_dmg(
     destination_folder: &Path,
     progress: Arc<dyn Fn(InstallState) + Send + Sync>,
     cancel_token: CancellationToken,
-) -> Result<(), ToolchainError> {
+) -> Result<Option<String>, ToolchainError> {
     use dmg::Attach;
     debug!(?dmg_path, "Now mounting DMG");
 
@@ -34,17 +34,40 @@ pub async fn extract_dmg(
         .unwrap()
         .map_err(ExtractError::Dmg)?;
 
-    let dmg = scopeguard::guard(handle, |handle| {
-        // ensure the mount point is unmounted when we exit
-        handle.force_detach().expect("Failed to detach DMG");
-    });
+    let mount_point = handle.mount_point.clone();
+    let mut guard = MountGuard {
+        device: handle.device.clone(),
+        handle: Some(handle),
+    };
 
-    debug!(?dmg.mount_point, "Mounted DMG at temp path");
+    debug!(?mount_point, "Mounted DMG at temp path");
 
+    let copy_result = copy_contents(
+        &mount_point,
+        destination_folder,
+        progress.clone(),
+        &cancel_token,
+    )
+    .await;
+
+    debug!(?mount_point, "Unmounting DMG");
+    progress(InstallState::ExtractCleanUp);
+    let unmount_result = guard.unmount(&cancel_token).await;
+
+    // An error while copying is more useful than one caused by the cleanup after it.
+    copy_result.and(unmount_result)
+}
+
+async fn copy_contents(
+    mount_point: &Path,
+    destination_folder: &Path,
+    progress: Arc<dyn Fn(InstallState) + Send + Sync>,
+    cancel_token: &CancellationToken,
+) -> Result<Option<String>, ToolchainError> {
     // First directory in the mount point is the actual contents
 
     cancel_token.check_cancellation(ToolchainError::Cancelled)?;
-    let contents_path = find_dir_contained_by(&dmg.mount_point).await?;
+    let contents_path = find_dir_contained_by(mount_point).await?;
 
     info!(
         ?contents_path,
@@ -53,37 +76,89 @@ pub async fn extract_dmg(
     );
 
     cancel_token.check_cancellation(ToolchainError::Cancelled)?;
+    let root_name = root_dir_name(&contents_path);
     copy_folder(
         contents_path,
         destination_folder.to_owned(),
-        progress.clone(),
+        progress,
         cancel_token.clone(),
     )
     .await?;
 
-    debug!(?dmg.mount_point, "Unmounting DMG");
-    progress(InstallState::ExtractCleanUp);
-
-    let mut retries_left = 10;
-    while retries_left > 0 {
-        cancel_token.check_cancellation(ToolchainError::Cancelled)?;
-        retries_left -= 1;
+    Ok(root_name)
+}
 
-        // Attempt to cleanly unmount the DMG instead of force detaching it.
-        // This helps ensure everything is flushed properly.
+/// Keeps track of a mounted DMG so that it's always detached.
+///
+/// [`MountGuard::unmount`] should be called once the DMG is no longer needed. If it isn't
+/// (e.g. because the future was dropped), the DMG is force detached when the guard is dropped,
+/// which blocks the current thread.
+struct MountGuard<H> {
+    device: String,
+    /// The handle returned when attaching, or `None` once the DMG has been detached.
+    handle: Option<H>,
+}
 
-        match detach(&dmg.device, false) {
-            Ok(_) => {
-                // No need to force unmount, we can safely abort the deferred cleanup
-                mem::forget(dmg);
+impl<H> MountGuard<H> {
+    /// Detaches the DMG without blocking the runtime.
+    ///
+    /// A clean detach is attempted a few times, since the volume can still be busy right after
+    /// copying. If that fails, or the operation is cancelled, the DMG is force detached.
+    async fn unmount(&mut self, cancel_token: &CancellationToken) -> Result<(), ToolchainError> {
+        for _ in 0..10 {
+            if cancel_token.is_cancelled() {
                 break;
             }
-            Err(error) => {
-                debug!(?error, "Failed to unmount DMG, retrying...");
-                sleep(Duration::from_millis(500)).await;
+
+            // Attempt to cleanly unmount the DMG instead of force detaching it.
+            // This helps ensure everything is flushed properly.
+            let device = self.device.clone();
+            match spawn_blocking(move || detach(&device, false))
+                .await
+                .unwrap()
+            {
+                Ok(_) => {
+                    self.disarm();
+                    return Ok(());
+                }
+                Err(error) => {
+                    debug!(?error, "Failed to unmount DMG, retrying...");
+                    tokio::select! {
+                        _ = sleep(Duration::from_millis(500)) => {}
+                        _ = cancel_token.cancelled() => break,
+                    }
+                }
             }
         }
+
+        let device = self.device.clone();
+        let result = spawn_blocking(move || detach(&device, true)).await.unwrap();
+        self.disarm();
+        if let Err(error) = result {
+            warn!(?error, device = self.device, "Failed to force detach DMG");
+        }
+
+        cancel_token.check_cancellation(ToolchainError::Cancelled)
     }
 
-    Ok(())
+    /// Marks the DMG as detached, so that dropping the guard doesn't detach it again.
+    fn disarm(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            mem::forget(handle);
+        }
+    }
+}
+
+impl<H> Drop for MountGuard<H> {
+    fn drop(&mut self) {
+        if self.handle.is_none() {
+            return;
+        }
+
+        debug!(device = self.device, "Force detaching DMG during drop");
+        if let Err(error) = detach(&self.device, true) {
+            warn!(?error, device = self.device, "Failed to force detach DMG");
+        }
+        self.disarm();
+    }
 }
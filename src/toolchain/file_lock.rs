@@ -0,0 +1,37 @@
+//! Exclusive locks on lock files, shared between processes.
+//!
+//! A lock is held by keeping its file open, and is released by the operating system when the
+//! file is closed, including when the process crashes, so it can't be left behind.
+
+use std::{
+    fs::{File, OpenOptions, TryLockError},
+    io,
+    path::PathBuf,
+};
+
+/// Tries to take an exclusive lock on the file at `path`, creating it and its parent
+/// directories if they don't exist.
+///
+/// Returns `None` without waiting if another process holds the lock. Otherwise, the lock is
+/// held until the returned file is closed.
+pub(crate) async fn try_lock_file(path: PathBuf) -> io::Result<Option<File>> {
+    tokio::task::spawn_blocking(move || {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)?;
+
+        match file.try_lock() {
+            Ok(()) => Ok(Some(file)),
+            Err(TryLockError::WouldBlock) => Ok(None),
+            Err(TryLockError::Error(error)) => Err(error),
+        }
+    })
+    .await
+    .unwrap()
+}
@@ -0,0 +1,285 @@
+//! Checks for whether a toolchain's directory is in use before it's removed or overwritten.
+//!
+//! On Windows, a directory can't be deleted while it's a process's working directory or while
+//! any of its files are open, and deleting it one file at a time fails partway through,
+//! leaving a half-removed toolchain behind. These checks catch the common cases up front.
+
+use std::{
+    fmt::{self, Display},
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use tracing::{debug, warn};
+
+use crate::{fs, toolchain::ToolchainError};
+
+/// Why a directory is considered to be in use, as reported in
+/// [`ToolchainError::ToolchainInUse`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InUseReason {
+    /// The current working directory is inside the directory.
+    WorkingDirectory,
+    /// The running executable is inside the directory.
+    CurrentExe,
+    /// Another process has a file in the directory open, or uses it as its working directory.
+    OpenHandles,
+}
+
+impl Display for InUseReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::WorkingDirectory => "the current directory is inside it",
+            Self::CurrentExe => "this program is running from inside it",
+            Self::OpenHandles => "another program is using files inside it",
+        })
+    }
+}
+
+/// Returns whether `path` is `dir` or is inside it.
+///
+/// Both paths are resolved first if they exist, so that symlinks and (on Windows)
+/// differences in case don't hide that one contains the other.
+pub(crate) fn is_within(dir: &Path, path: &Path) -> bool {
+    let resolve = |path: &Path| std::fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+    resolve(path).starts_with(resolve(dir))
+}
+
+/// The suffix added to a directory's name while it's renamed to check for open files.
+const PROBE_SUFFIX: &str = ".in-use-check";
+
+/// How many times renaming a directory back from its probe name is attempted, since antivirus
+/// software on Windows can briefly open files in a directory that was just renamed.
+const RESTORE_ATTEMPTS: u32 = 5;
+
+/// Returns an error if the given directory is in use in a way that would make removing it fail
+/// partway through.
+///
+/// This only checks anything on Windows, since other platforms can delete directories that
+/// are in use. Open files are detected by briefly renaming the directory, which Windows
+/// refuses to do while anything inside it is open. If the process exits before it's renamed
+/// back, [`recover_stranded_probes`] restores it the next time a client is built.
+pub(crate) async fn check_not_in_use(dir: &Path) -> Result<(), ToolchainError> {
+    if !cfg!(windows) {
+        return Ok(());
+    }
+
+    recover_probe(dir).await?;
+
+    let in_use = |reason| ToolchainError::ToolchainInUse {
+        path: dir.to_owned(),
+        reason,
+    };
+
+    if std::env::current_dir().is_ok_and(|cwd| is_within(dir, &cwd)) {
+        return Err(in_use(InUseReason::WorkingDirectory));
+    }
+    if std::env::current_exe().is_ok_and(|exe| is_within(dir, &exe)) {
+        return Err(in_use(InUseReason::CurrentExe));
+    }
+
+    let probe_path = probe_path(dir);
+    // `fs_err` hides the OS error code, which is needed to tell why the rename failed.
+    match tokio::fs::rename(dir, &probe_path).await {
+        Ok(()) => {
+            restore(&probe_path, dir).await?;
+            Ok(())
+        }
+        Err(error) if is_in_use_error(&error) => {
+            debug!(
+                ?dir,
+                ?error,
+                "Directory couldn't be renamed, so it's in use"
+            );
+            Err(in_use(InUseReason::OpenHandles))
+        }
+        // Anything else (such as the directory not existing) is left for the removal to
+        // report.
+        Err(_) => Ok(()),
+    }
+}
+
+/// Returns a path next to `dir` that it can be briefly renamed to.
+fn probe_path(dir: &Path) -> PathBuf {
+    let mut name = dir.file_name().unwrap_or_default().to_owned();
+    name.push(PROBE_SUFFIX);
+    dir.with_file_name(name)
+}
+
+/// Returns whether a file name is one that a directory is given while it's being probed.
+pub(crate) fn is_probe_name(name: &std::ffi::OsStr) -> bool {
+    name.to_str()
+        .is_some_and(|name| name.len() > PROBE_SUFFIX.len() && name.ends_with(PROBE_SUFFIX))
+}
+
+/// Renames a probed directory back to its original name, retrying briefly if that fails.
+async fn restore(probe_path: &Path, dir: &Path) -> Result<(), io::Error> {
+    let mut attempt = 1;
+    loop {
+        match fs::rename(probe_path, dir).await {
+            Ok(()) => return Ok(()),
+            Err(error) if attempt < RESTORE_ATTEMPTS => {
+                debug!(
+                    ?error,
+                    attempt, "Failed to rename probed directory back, retrying"
+                );
+                tokio::time::sleep(Duration::from_millis(100) * attempt).await;
+                attempt += 1;
+            }
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+/// Renames `dir` back from its probe name if a probe of it was cut off, returning whether it
+/// was.
+///
+/// A probe is only recovered if nothing has been created at `dir` since.
+pub(crate) async fn recover_probe(dir: &Path) -> Result<bool, io::Error> {
+    let probe_path = probe_path(dir);
+    if fs::symlink_metadata(&probe_path).await.is_err() {
+        return Ok(false);
+    }
+
+    match fs::symlink_metadata(dir).await {
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            debug!(
+                ?probe_path,
+                ?dir,
+                "Recovering directory stranded by an in-use check"
+            );
+            restore(&probe_path, dir).await?;
+            Ok(true)
+        }
+        _ => {
+            warn!(
+                ?probe_path,
+                ?dir,
+                "Found a directory stranded by an in-use check, but its original path is taken"
+            );
+            Ok(false)
+        }
+    }
+}
+
+/// Renames every directory in `parent` that was stranded under its probe name back to its
+/// original name, returning the restored paths.
+pub(crate) async fn recover_stranded_probes(parent: &Path) -> Result<Vec<PathBuf>, io::Error> {
+    let mut read_dir = match fs::read_dir(parent).await {
+        Ok(read_dir) => read_dir,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+
+    let mut recovered = vec![];
+    while let Some(entry) = read_dir.next_entry().await? {
+        let name = entry.file_name();
+        if !is_probe_name(&name) {
+            continue;
+        }
+
+        let name = name.to_string_lossy();
+        let dir = parent.join(&name[..name.len() - PROBE_SUFFIX.len()]);
+        if recover_probe(&dir).await? {
+            recovered.push(dir);
+        }
+    }
+
+    Ok(recovered)
+}
+
+/// Returns whether an error from renaming or removing a file means that it's open in another
+/// process.
+fn is_in_use_error(error: &io::Error) -> bool {
+    /// `ERROR_ACCESS_DENIED`, `ERROR_SHARING_VIOLATION`, and `ERROR_LOCK_VIOLATION` from
+    /// `winerror.h`.
+    const IN_USE_ERRORS: &[i32] = &[5, 32, 33];
+
+    cfg!(windows)
+        && error
+            .raw_os_error()
+            .is_some_and(|code| IN_USE_ERRORS.contains(&code))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_is_within_itself_and_its_children() {
+        let dir = Path::new("/toolchains/embedded/21.1.0");
+
+        assert!(is_within(dir, dir));
+        assert!(is_within(dir, &dir.join("bin/clang")));
+        assert!(!is_within(dir, Path::new("/toolchains/embedded")));
+        assert!(!is_within(dir, Path::new("/toolchains/embedded/20.1.0")));
+    }
+
+    #[test]
+    fn sibling_with_a_longer_name_is_not_within() {
+        let dir = Path::new("/toolchains/embedded/21.1.0");
+
+        assert!(!is_within(
+            dir,
+            Path::new("/toolchains/embedded/21.1.0-rc1")
+        ));
+        assert!(!is_within(
+            dir,
+            Path::new("/toolchains/embedded/21.1.0.in-use-check/bin")
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_resolved() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().join("21.1.0");
+        std::fs::create_dir_all(dir.join("bin")).unwrap();
+        let link = temp.path().join("link");
+        std::os::unix::fs::symlink(&dir, &link).unwrap();
+
+        assert!(is_within(&dir, &link.join("bin")));
+        assert!(is_within(&link, &dir.join("bin")));
+        assert!(!is_within(&dir.join("bin"), &link));
+    }
+
+    #[test]
+    fn probe_names() {
+        assert!(is_probe_name("21.1.0.in-use-check".as_ref()));
+        assert!(!is_probe_name(".in-use-check".as_ref()));
+        assert!(!is_probe_name("21.1.0".as_ref()));
+    }
+
+    #[tokio::test]
+    async fn recovers_stranded_probes() {
+        let temp = tempfile::tempdir().unwrap();
+        let stranded = temp.path().join("21.1.0");
+        std::fs::create_dir_all(probe_path(&stranded).join("bin")).unwrap();
+        std::fs::create_dir_all(temp.path().join("20.1.0")).unwrap();
+
+        let recovered = recover_stranded_probes(temp.path()).await.unwrap();
+
+        assert_eq!(recovered, [stranded.as_path()]);
+        assert!(stranded.join("bin").is_dir());
+        assert!(!probe_path(&stranded).exists());
+    }
+
+    #[tokio::test]
+    async fn leaves_probes_whose_original_path_is_taken() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().join("21.1.0");
+        std::fs::create_dir_all(probe_path(&dir).join("old")).unwrap();
+        std::fs::create_dir_all(dir.join("new")).unwrap();
+
+        assert!(!recover_probe(&dir).await.unwrap());
+        assert!(
+            recover_stranded_probes(temp.path())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert!(dir.join("new").is_dir());
+        assert!(probe_path(&dir).join("old").is_dir());
+    }
+}
@@ -0,0 +1,394 @@
+//! A write-ahead journal of the mutating operations performed on the toolchains and
+//! cache directories.
+//!
+//! Before each phase that modifies the disk, an intent record is appended to the journal.
+//! Once the phase finishes, a matching completion record is appended, or a failure record if
+//! it returned early because of an error or cancellation. If the process is killed (or the
+//! machine loses power) part-way through, the unmatched intent records describe exactly which
+//! operations were in flight.
+
+use std::{
+    fmt::{self, Display},
+    io::Write,
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use strum::{AsRefStr, EnumString};
+use tokio::io::AsyncWriteExt;
+use tracing::{trace, warn};
+
+use crate::fs;
+
+/// The journal is rotated after it grows past this size, which is roughly a few
+/// hundred entries.
+const MAX_JOURNAL_SIZE: u64 = 32 * 1024;
+
+/// A kind of operation that is recorded in the journal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsRefStr, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum Operation {
+    /// Downloading an archive into the cache. The subject is the archive's file name.
+    Download,
+    /// Extracting an archive into the toolchains directory. The subject is the version name.
+    Extract,
+    /// Changing the active toolchain. The subject is the version name, or empty if the
+    /// active toolchain was being unset.
+    Activate,
+    /// Removing a toolchain. The subject is the version name.
+    Remove,
+    /// Migrating the toolchains directory to a new layout. The subject is `layout-` followed
+    /// by the layout version being migrated to.
+    Migrate,
+}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_ref())
+    }
+}
+
+/// Whether a [`JournalEntry`] records the start or the end of an operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsRefStr, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum Status {
+    /// The operation is about to start.
+    Begin,
+    /// The operation finished.
+    Done,
+    /// The operation stopped early because of an error or cancellation. Whatever it changed
+    /// before then is left as it was.
+    Failed,
+    /// Whatever an interrupted or failed operation left behind has since been cleaned up.
+    CleanedUp,
+}
+
+/// A single record in the journal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalEntry {
+    pub timestamp: DateTime<Utc>,
+    pub id: String,
+    pub status: Status,
+    pub operation: Operation,
+    pub subject: String,
+}
+
+impl JournalEntry {
+    /// Returns the ID of the process that recorded the entry, which is part of its ID.
+    fn process(&self) -> Option<&str> {
+        self.id.split('-').nth(1)
+    }
+
+    fn to_line(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}\n",
+            self.timestamp.to_rfc3339_opts(SecondsFormat::Secs, true),
+            self.id,
+            self.status.as_ref(),
+            self.operation,
+            self.subject,
+        )
+    }
+}
+
+impl FromStr for JournalEntry {
+    type Err = ();
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut fields = line.splitn(5, '\t');
+        let mut next = || fields.next().ok_or(());
+
+        let timestamp = DateTime::parse_from_rfc3339(next()?)
+            .map_err(|_| ())?
+            .to_utc();
+        let id = next()?.to_string();
+        let status = next()?.parse().map_err(|_| ())?;
+        let operation = next()?.parse().map_err(|_| ())?;
+        let subject = next()?.to_string();
+
+        Ok(Self {
+            timestamp,
+            id,
+            status,
+            operation,
+            subject,
+        })
+    }
+}
+
+/// An append-only log of the operations performed by a [`ToolchainClient`](super::ToolchainClient).
+#[derive(Debug, Clone)]
+pub struct Journal {
+    path: PathBuf,
+}
+
+impl Journal {
+    pub const FILENAME: &str = "journal.log";
+    pub const ROTATED_FILENAME: &str = "journal.1.log";
+
+    /// Creates a handle to the journal stored in the given directory.
+    pub fn in_dir(dir: &Path) -> Self {
+        Self {
+            path: dir.join(Self::FILENAME),
+        }
+    }
+
+    /// Returns the path of the journal file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn rotated_path(&self) -> PathBuf {
+        self.path.with_file_name(Self::ROTATED_FILENAME)
+    }
+
+    /// Records that an operation is about to start.
+    ///
+    /// Failing to write to the journal is not considered fatal, so errors are logged
+    /// instead of returned.
+    pub(crate) async fn begin(&self, operation: Operation, subject: impl Into<String>) -> Intent {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+        let id = format!(
+            "{:x}-{:x}-{:x}",
+            Utc::now().timestamp_millis(),
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        let intent = Intent {
+            journal: self.clone(),
+            id,
+            operation,
+            subject: subject.into(),
+            finished: false,
+        };
+
+        self.append(&intent.entry(Status::Begin)).await;
+        intent
+    }
+
+    async fn append(&self, entry: &JournalEntry) {
+        trace!(?entry, "Writing to journal");
+
+        let result = async {
+            if let Some(parent) = self.path.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+
+            if let Ok(meta) = fs::metadata(&self.path).await
+                && meta.len() > MAX_JOURNAL_SIZE
+            {
+                fs::rename(&self.path, self.rotated_path()).await?;
+            }
+
+            let mut file = fs::File::options()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .await?;
+            file.write_all(entry.to_line().as_bytes()).await?;
+            file.sync_data().await?;
+
+            Ok::<_, std::io::Error>(())
+        }
+        .await;
+
+        if let Err(error) = result {
+            warn!(?error, path = ?self.path, "Failed to write to the journal");
+        }
+    }
+
+    /// Like [`append`](Self::append), but blocks, for when an [`Intent`] is dropped.
+    ///
+    /// The journal isn't rotated, since that's left to the next asynchronous write.
+    fn append_blocking(&self, entry: &JournalEntry) {
+        trace!(?entry, "Writing to journal");
+
+        let result = (|| {
+            if let Some(parent) = self.path.parent() {
+                fs_err::create_dir_all(parent)?;
+            }
+
+            let mut file = fs_err::File::options()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+            file.write_all(entry.to_line().as_bytes())?;
+            file.sync_data()
+        })();
+
+        if let Err(error) = result {
+            warn!(?error, path = ?self.path, "Failed to write to the journal");
+        }
+    }
+
+    /// Reads every entry in the journal, oldest first.
+    ///
+    /// Lines which can't be parsed (for example, because a write was cut off) are skipped.
+    pub async fn entries(&self) -> Result<Vec<JournalEntry>, std::io::Error> {
+        let mut entries = vec![];
+
+        for path in [self.rotated_path(), self.path.clone()] {
+            let contents = match fs::read_to_string(&path).await {
+                Ok(contents) => contents,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+
+            entries.extend(contents.lines().filter_map(|line| line.parse().ok()));
+        }
+
+        Ok(entries)
+    }
+
+    /// Returns the intent records of operations that were started but never finished or
+    /// failed, oldest first. These were cut off by the process exiting.
+    ///
+    /// Operations which were later retried and completed successfully are not included.
+    pub async fn interrupted(&self) -> Result<Vec<JournalEntry>, std::io::Error> {
+        self.unfinished(|outcome| outcome.is_none()).await
+    }
+
+    /// Returns the intent records of operations that stopped early because of an error or
+    /// cancellation, oldest first.
+    ///
+    /// Operations which were later retried and completed successfully are not included.
+    pub async fn failed(&self) -> Result<Vec<JournalEntry>, std::io::Error> {
+        self.unfinished(|outcome| outcome == Some(Status::Failed))
+            .await
+    }
+
+    /// Records that whatever the operation started by `entry` left behind has been cleaned up,
+    /// so that it's no longer reported as interrupted or failed.
+    pub async fn record_cleaned_up(&self, entry: &JournalEntry) {
+        self.append(&JournalEntry {
+            timestamp: Utc::now(),
+            status: Status::CleanedUp,
+            ..entry.clone()
+        })
+        .await;
+    }
+
+    /// Returns the intent records of operations that didn't complete, and whose own outcome
+    /// (if any) is accepted by `filter`.
+    async fn unfinished(
+        &self,
+        filter: impl Fn(Option<Status>) -> bool,
+    ) -> Result<Vec<JournalEntry>, std::io::Error> {
+        let entries = self.entries().await?;
+
+        let unfinished = entries
+            .iter()
+            .enumerate()
+            .filter(|(idx, entry)| {
+                let later = &entries[*idx..];
+                let completed = later.iter().any(|other| {
+                    other.status == Status::Done
+                        && (other.id == entry.id
+                            || (other.operation == entry.operation
+                                && other.subject == entry.subject))
+                });
+                let outcome = later
+                    .iter()
+                    .rfind(|other| other.id == entry.id && other.status != Status::Begin)
+                    .map(|other| other.status);
+
+                entry.status == Status::Begin && !completed && filter(outcome)
+            })
+            .map(|(_, entry)| entry.clone())
+            .collect();
+
+        Ok(unfinished)
+    }
+
+    /// Returns when the most recent installation finished and how long it took, or `None` if
+    /// no installation has been recorded.
+    ///
+    /// An installation starts when its archive starts downloading, or when extraction starts
+    /// if the archive was already downloaded.
+    pub async fn last_install(&self) -> Result<Option<CompletedInstall>, std::io::Error> {
+        let entries = self.entries().await?;
+        let is_install_end = |entry: &JournalEntry| {
+            entry.status == Status::Done && entry.operation == Operation::Extract
+        };
+
+        let Some(end) = entries.iter().rposition(is_install_end) else {
+            return Ok(None);
+        };
+        let finished = entries[end].timestamp;
+        let process = entries[end].process();
+
+        // Other processes can write to the journal at the same time, so only the entries
+        // written by the same process since its previous installation are considered.
+        let started = entries[..end]
+            .iter()
+            .rev()
+            .filter(|entry| entry.process() == process)
+            .take_while(|entry| !is_install_end(entry))
+            .filter(|entry| {
+                entry.status == Status::Begin
+                    && matches!(entry.operation, Operation::Download | Operation::Extract)
+            })
+            .last()
+            .map_or(finished, |entry| entry.timestamp);
+
+        Ok(Some(CompletedInstall {
+            finished,
+            duration: (finished - started).to_std().unwrap_or_default(),
+        }))
+    }
+}
+
+/// The most recent installation recorded in the [`Journal`], as returned by
+/// [`Journal::last_install`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompletedInstall {
+    /// When the toolchain finished extracting.
+    pub finished: DateTime<Utc>,
+    /// How long the download and extraction took, to the nearest second.
+    pub duration: Duration,
+}
+
+/// An operation that has been recorded as started in the [`Journal`].
+///
+/// If it's dropped without being completed, such as when an error is returned with `?`, the
+/// operation is recorded as failed.
+#[derive(Debug)]
+#[must_use = "intents should be marked as completed"]
+pub(crate) struct Intent {
+    journal: Journal,
+    id: String,
+    operation: Operation,
+    subject: String,
+    finished: bool,
+}
+
+impl Intent {
+    fn entry(&self, status: Status) -> JournalEntry {
+        JournalEntry {
+            timestamp: Utc::now(),
+            id: self.id.clone(),
+            status,
+            operation: self.operation,
+            subject: self.subject.clone(),
+        }
+    }
+
+    /// Records that the operation has finished.
+    pub(crate) async fn complete(mut self) {
+        self.finished = true;
+        self.journal.append(&self.entry(Status::Done)).await;
+    }
+}
+
+impl Drop for Intent {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.journal.append_blocking(&self.entry(Status::Failed));
+        }
+    }
+}
@@ -0,0 +1,239 @@
+//! Versioning of the toolchains directory's layout.
+//!
+//! Each version of this crate writes the layout version it understands to a marker file in
+//! the toolchains directory. When a directory with an older layout is opened, the migrations
+//! needed to bring it up to date are run in order, and each one is recorded in the journal.
+//! A directory with a newer layout is refused, since an older version can't know how to
+//! handle it.
+
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use tracing::{debug, info, warn};
+
+use crate::{
+    fs,
+    toolchain::{
+        InstalledToolchain, Product, ToolchainClient, ToolchainError,
+        journal::{Journal, Operation},
+        manifest::MANIFEST_FILENAME,
+    },
+};
+
+/// The layout version written by this version of the crate.
+pub const LAYOUT_VERSION: u32 = 2;
+
+/// A step that updates the toolchains directory from one layout version to the next.
+///
+/// Migrations must be idempotent, because a migration that was interrupted is run again the
+/// next time the directory is opened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayoutMigration {
+    /// Starts tracking the layout version. Nothing else changes.
+    RecordLayoutVersion,
+    /// Moves toolchains installed before products were introduced into the embedded
+    /// toolchain's directory.
+    ///
+    /// Older versions only installed the Arm Toolchain for Embedded, and kept each version and
+    /// the active toolchain file directly inside the toolchains directory.
+    ProductDirectories,
+}
+
+impl LayoutMigration {
+    /// Every migration, ordered by the layout version it migrates to.
+    pub const ALL: &[Self] = &[Self::RecordLayoutVersion, Self::ProductDirectories];
+
+    /// Returns the layout version that the directory has after this migration.
+    pub fn version(self) -> u32 {
+        match self {
+            Self::RecordLayoutVersion => 1,
+            Self::ProductDirectories => 2,
+        }
+    }
+
+    /// Returns a short description of what the migration changes.
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::RecordLayoutVersion => "started recording the layout version",
+            Self::ProductDirectories => "moved toolchains into a directory for each product",
+        }
+    }
+
+    async fn apply(self, toolchains_path: &Path) -> Result<(), ToolchainError> {
+        match self {
+            Self::RecordLayoutVersion => Ok(()),
+            Self::ProductDirectories => move_into_product_directories(toolchains_path).await,
+        }
+    }
+}
+
+/// Moves the toolchains and the active toolchain file at the top of the toolchains directory
+/// into the embedded toolchain's directory.
+///
+/// Only directories that look like toolchains are moved, so anything else that was put in the
+/// toolchains directory stays where it is.
+async fn move_into_product_directories(toolchains_path: &Path) -> Result<(), ToolchainError> {
+    let mut dir = match fs::read_dir(toolchains_path).await {
+        Ok(dir) => dir,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut legacy = vec![];
+    while let Some(entry) = dir.next_entry().await? {
+        let name = entry.file_name();
+        if Product::ALL.iter().any(|product| name == product.id) {
+            continue;
+        }
+
+        if name == ToolchainClient::CURRENT_TOOLCHAIN_FILENAME
+            || (entry.file_type().await?.is_dir() && looks_like_toolchain(&entry.path()).await)
+        {
+            legacy.push(name);
+        }
+    }
+
+    if legacy.is_empty() {
+        return Ok(());
+    }
+
+    let embedded_path = toolchains_path.join(Product::EMBEDDED.id);
+    debug!(?legacy, "Moving toolchains to {embedded_path:?}");
+    fs::create_dir_all(&embedded_path).await?;
+
+    for name in legacy {
+        let destination = embedded_path.join(&name);
+        // Never overwrite something that's already been moved.
+        if fs::symlink_metadata(&destination).await.is_ok() {
+            continue;
+        }
+
+        fs::rename(toolchains_path.join(&name), destination).await?;
+    }
+
+    Ok(())
+}
+
+/// Returns whether a directory has a toolchain's `clang` or the manifest written when one is
+/// installed.
+async fn looks_like_toolchain(path: &Path) -> bool {
+    let toolchain = InstalledToolchain::new(path.to_owned());
+    let clang_name = if cfg!(windows) { "clang.exe" } else { "clang" };
+    for marker in [
+        toolchain.host_bin_dir().join(clang_name),
+        path.join(MANIFEST_FILENAME),
+    ] {
+        if fs::try_exists(&marker).await.unwrap_or(false) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Returns the path of the layout version marker in the given toolchains directory.
+pub(crate) fn layout_file_path(toolchains_path: &Path) -> PathBuf {
+    toolchains_path.join("layout-version")
+}
+
+/// Reads the layout version of a toolchains directory.
+///
+/// Directories that were created before the layout version was recorded have version 0.
+/// Returns `None` if the directory doesn't exist.
+async fn read_layout_version(toolchains_path: &Path) -> Result<Option<u32>, ToolchainError> {
+    let path = layout_file_path(toolchains_path);
+    match fs::read_to_string(&path).await {
+        Ok(contents) => match contents.trim().parse() {
+            Ok(version) => Ok(Some(version)),
+            Err(_) => {
+                // Migrations are idempotent, so it's safe to run all of them again.
+                warn!(?path, ?contents, "Ignoring invalid layout version");
+                Ok(Some(0))
+            }
+        },
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            Ok(fs::try_exists(toolchains_path).await?.then_some(0))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Creates the toolchains directory if it doesn't exist yet, recording the current layout
+/// version in it.
+///
+/// Without the marker, the next client to open the directory would take it for one created
+/// before the layout was versioned, and migrate it.
+pub(crate) async fn create_toolchains_dir(toolchains_path: &Path) -> Result<(), ToolchainError> {
+    if fs::try_exists(toolchains_path).await? {
+        return Ok(());
+    }
+
+    fs::create_dir_all(toolchains_path).await?;
+    fs::write(
+        layout_file_path(toolchains_path),
+        format!("{LAYOUT_VERSION}\n"),
+    )
+    .await?;
+    debug!(?toolchains_path, "Created toolchains directory");
+    Ok(())
+}
+
+/// Brings the layout of a toolchains directory up to date, and returns the migrations that
+/// were run.
+///
+/// Nothing is done if the directory doesn't exist yet. Returns an error if the directory was
+/// last used by a newer version of this crate.
+pub(crate) async fn migrate_layout(
+    toolchains_path: &Path,
+) -> Result<Vec<LayoutMigration>, ToolchainError> {
+    let Some(found) = read_layout_version(toolchains_path).await? else {
+        return Ok(vec![]);
+    };
+
+    if found > LAYOUT_VERSION {
+        return Err(ToolchainError::NewerLayout {
+            path: toolchains_path.to_owned(),
+            found,
+            supported: LAYOUT_VERSION,
+        });
+    }
+
+    let pending = LayoutMigration::ALL
+        .iter()
+        .copied()
+        .filter(|migration| migration.version() > found)
+        .collect::<Vec<_>>();
+    if pending.is_empty() {
+        return Ok(pending);
+    }
+
+    debug!(
+        found,
+        target = LAYOUT_VERSION,
+        ?pending,
+        "Migrating toolchains directory"
+    );
+    let journal = Journal::in_dir(toolchains_path);
+
+    for &migration in &pending {
+        let version = migration.version();
+        let intent = journal
+            .begin(Operation::Migrate, format!("layout-{version}"))
+            .await;
+
+        migration.apply(toolchains_path).await?;
+        // The version is recorded after every step so that finished steps aren't run again
+        // if a later one fails.
+        fs::write(layout_file_path(toolchains_path), format!("{version}\n")).await?;
+
+        intent.complete().await;
+        info!(
+            version,
+            "Migrated toolchains directory: {}",
+            migration.description()
+        );
+    }
+
+    Ok(pending)
+}
@@ -0,0 +1,193 @@
+//! Snapshots of a release's metadata, for installing it without asking GitHub about it.
+//!
+//! A release lock records a release's tag and the name, URLs, size, and SHA256 digest of each
+//! of its assets. Installing from a lock only downloads the asset, and the download has to
+//! match the recorded digest, so the install fails instead of using anything that changed on
+//! the server since the lock was made.
+
+use std::path::Path;
+
+use octocrab::models::repos::{Asset, Release};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    fs,
+    toolchain::{Product, ToolchainError, ToolchainRelease, ToolchainVersion},
+};
+
+/// A snapshot of a release's metadata, as created by
+/// [`ToolchainClient::lock_release`](super::ToolchainClient::lock_release).
+///
+/// Pass a lock to [`ToolchainClientBuilder::release_lock`](super::ToolchainClientBuilder::release_lock)
+/// and install the release returned by [`release`](Self::release) to install it without
+/// contacting the GitHub API.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReleaseLock {
+    /// The version of the lock format. Only [`ReleaseLock::FORMAT_VERSION`] is supported.
+    pub format_version: u32,
+    /// The product the release belongs to, stored as its [`id`](Product::id).
+    #[serde(with = "product_id")]
+    pub product: Product,
+    /// The name of the release's Git tag.
+    pub tag_name: String,
+    /// The release's downloadable assets.
+    pub assets: Vec<LockedAsset>,
+}
+
+/// An asset recorded in a [`ReleaseLock`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedAsset {
+    pub name: String,
+    /// The size of the asset in bytes.
+    pub size: u64,
+    /// The SHA256 digest of the asset in hex format.
+    pub sha256: String,
+    /// The asset's browser download URL.
+    pub url: Url,
+    /// The asset's GitHub API URL.
+    pub api_url: Url,
+}
+
+impl ReleaseLock {
+    /// The version of the lock format written by this version of the crate.
+    pub const FORMAT_VERSION: u32 = 1;
+
+    /// Reads a lock from a JSON file.
+    ///
+    /// Returns [`ToolchainError::InvalidReleaseLock`] if the file isn't a valid lock, or was
+    /// written in a newer format.
+    pub async fn read(path: &Path) -> Result<Self, ToolchainError> {
+        let contents = fs::read_to_string(path).await?;
+        let invalid = |reason: String| ToolchainError::InvalidReleaseLock {
+            path: path.to_owned(),
+            reason,
+        };
+
+        // Other formats may not have the same fields, so the version is checked first.
+        #[derive(Deserialize)]
+        struct Header {
+            format_version: u32,
+        }
+        let header: Header = serde_json::from_str(&contents).map_err(|e| invalid(e.to_string()))?;
+        if header.format_version != Self::FORMAT_VERSION {
+            return Err(invalid(format!(
+                "unsupported format version {} (expected {})",
+                header.format_version,
+                Self::FORMAT_VERSION
+            )));
+        }
+
+        serde_json::from_str(&contents).map_err(|e| invalid(e.to_string()))
+    }
+
+    /// Writes the lock to a file as pretty-printed JSON.
+    pub async fn write(&self, path: &Path) -> Result<(), ToolchainError> {
+        fs::write(path, self.to_json()).await?;
+        Ok(())
+    }
+
+    /// Formats the lock as pretty-printed JSON, ending with a newline.
+    pub fn to_json(&self) -> String {
+        let mut json = serde_json::to_string_pretty(self).expect("locks can always be serialized");
+        json.push('\n');
+        json
+    }
+
+    /// Returns the version of the locked release.
+    pub fn version(&self) -> ToolchainVersion {
+        self.product.version_from_tag(&self.tag_name)
+    }
+
+    /// Recreates the release from the lock, without contacting GitHub.
+    ///
+    /// Only the tag and assets are recorded, so the release's other details (such as its
+    /// description) are left empty.
+    pub fn release(&self) -> ToolchainRelease {
+        let assets = self
+            .assets
+            .iter()
+            .map(|asset| {
+                offline_asset(
+                    &asset.name,
+                    asset.size,
+                    Some(&asset.sha256),
+                    &asset.url,
+                    &asset.api_url,
+                )
+            })
+            .collect();
+
+        ToolchainRelease::for_product(offline_release(&self.tag_name, assets), self.product)
+    }
+
+    /// Returns the recorded digest of the asset with the given name.
+    pub(crate) fn sha256_for(&self, asset_name: &str) -> Option<&str> {
+        self.assets
+            .iter()
+            .find(|asset| asset.name == asset_name)
+            .map(|asset| asset.sha256.as_str())
+    }
+}
+
+/// Creates a release with the given tag and assets, leaving its other details empty.
+pub(crate) fn offline_release(tag_name: &str, assets: Vec<Asset>) -> Release {
+    serde_json::from_value(serde_json::json!({
+        "url": "https://api.github.com/",
+        "html_url": "https://github.com/",
+        "assets_url": "https://api.github.com/",
+        "upload_url": "https://uploads.github.com/",
+        "id": 0,
+        "node_id": "",
+        "tag_name": tag_name,
+        "target_commitish": "",
+        "draft": false,
+        "prerelease": false,
+        "assets": assets,
+    }))
+    .expect("every field required by the release is provided")
+}
+
+/// Creates an asset that's downloaded from the given URLs, with a SHA256 digest in hex format
+/// if one is known.
+pub(crate) fn offline_asset(
+    name: &str,
+    size: u64,
+    sha256: Option<&str>,
+    url: &Url,
+    api_url: &Url,
+) -> Asset {
+    serde_json::from_value(serde_json::json!({
+        "url": api_url,
+        "browser_download_url": url,
+        "id": 0,
+        "node_id": "",
+        "name": name,
+        "label": null,
+        "state": "uploaded",
+        "content_type": "application/octet-stream",
+        "size": size,
+        "digest": sha256.map(|sha256| format!("sha256:{sha256}")),
+        "download_count": 0,
+        "created_at": "1970-01-01T00:00:00Z",
+        "updated_at": "1970-01-01T00:00:00Z",
+        "uploader": null,
+    }))
+    .expect("every field required by the asset is provided")
+}
+
+/// Serializes a [`Product`] as its ID.
+mod product_id {
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    use crate::toolchain::Product;
+
+    pub fn serialize<S: Serializer>(product: &Product, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(product.id)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Product, D::Error> {
+        let id = String::deserialize(deserializer)?;
+        id.parse().map_err(D::Error::custom)
+    }
+}
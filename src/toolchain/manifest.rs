@@ -0,0 +1,403 @@
+//! A small file stored in each installed toolchain which records information about how it
+//! was installed, and caches information that is slow to compute, such as its total size.
+//!
+//! The manifest records the modification time of the toolchain's top-level directory along
+//! with the cached values. If the directory's modification time has changed since then, the
+//! cached values are considered stale and recomputed.
+
+use std::{
+    io::ErrorKind,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use chrono::DateTime;
+use serde::Serialize;
+use tokio::io::AsyncWriteExt;
+use tracing::debug;
+
+use crate::{
+    fs,
+    toolchain::{
+        HostArch, InstalledToolchain, ToolchainError, ToolchainVersion, notes::ReleaseMetadata,
+    },
+};
+
+/// The name of the manifest file in a toolchain's directory.
+pub(crate) const MANIFEST_FILENAME: &str = ".arm-toolchain-manifest";
+
+/// The total size of the files in a toolchain, as returned by [`InstalledToolchain::size`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ToolchainSize {
+    /// The total size of the files in bytes.
+    pub bytes: u64,
+    /// The number of files, not including directories or symlinks.
+    pub files: u64,
+}
+
+/// The versions recorded when a toolchain was installed, as returned by
+/// [`InstalledToolchain::recorded_versions`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RecordedVersions {
+    /// The version of the release the toolchain was installed from, which is also the name
+    /// of its directory.
+    pub release: ToolchainVersion,
+    /// The version in the name of the archive's top-level directory (such as `20.1.0-rc1` for
+    /// `ATfE-20.1.0-rc1-Linux-x86_64`), if it contained one.
+    pub content: Option<ToolchainVersion>,
+}
+
+impl RecordedVersions {
+    /// Compares the release version with the version of the archive's contents.
+    pub fn compare(&self) -> VersionMatch {
+        let Some(content) = &self.content else {
+            return VersionMatch::Same;
+        };
+
+        if content.name == self.release.name {
+            VersionMatch::Same
+        } else if let (Some(a), Some(b)) = (content.major(), self.release.major())
+            && a != b
+        {
+            VersionMatch::MajorDiffers
+        } else {
+            VersionMatch::Differs
+        }
+    }
+}
+
+/// How the version of a toolchain's release compares to the version of the archive it was
+/// installed from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionMatch {
+    /// The versions are the same, or the archive didn't contain a version.
+    Same,
+    /// The versions differ, but have the same major version (e.g. `20.1.0` and `20.1.0-rc1`).
+    ///
+    /// This happens occasionally and isn't a problem.
+    Differs,
+    /// The versions have different major versions, so the release likely contains the wrong
+    /// toolchain.
+    MajorDiffers,
+}
+
+/// Extracts the version from the name of an archive's top-level directory, such as
+/// `20.1.0-rc1` from `ATfE-20.1.0-rc1-Linux-x86_64`.
+///
+/// The version is the first `-`-separated part that starts with a digit and contains a dot,
+/// along with any pre-release parts (such as `rc1` or `beta`) that follow it.
+pub(crate) fn content_version_from_root_name(name: &str) -> Option<ToolchainVersion> {
+    const PRERELEASE_PREFIXES: &[&str] = &["rc", "alpha", "beta", "pre"];
+
+    let mut parts = name.split('-');
+    let mut version = parts
+        .find(|part| part.starts_with(|c: char| c.is_ascii_digit()) && part.contains('.'))?
+        .to_string();
+
+    for part in parts {
+        let lowercase = part.to_ascii_lowercase();
+        if !PRERELEASE_PREFIXES.iter().any(|p| lowercase.starts_with(p)) {
+            break;
+        }
+        version.push('-');
+        version.push_str(part);
+    }
+
+    Some(ToolchainVersion::named(version))
+}
+
+/// The contents of a manifest file. Every value is optional so that values can be recorded
+/// at different times, and so that manifests written by older versions can still be read.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Manifest {
+    size: Option<ToolchainSize>,
+    /// The modification time of the toolchain's directory when `size` was recorded.
+    modified: Option<SystemTime>,
+    release_version: Option<String>,
+    content_version: Option<String>,
+    release: ReleaseMetadata,
+    /// The architecture of the asset the toolchain was installed from.
+    asset_arch: Option<HostArch>,
+}
+
+impl Manifest {
+    /// Parses a manifest file, which contains one `key=value` pair per line.
+    ///
+    /// Malformed lines and unknown keys are ignored.
+    fn parse(text: &str) -> Self {
+        let mut manifest = Self::default();
+        let mut bytes = None;
+        let mut files = None;
+
+        for line in text.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+
+            match key {
+                "bytes" => bytes = value.parse().ok(),
+                "files" => files = value.parse().ok(),
+                "modified" => {
+                    manifest.modified = value
+                        .parse()
+                        .ok()
+                        .and_then(|nanos| UNIX_EPOCH.checked_add(Duration::from_nanos(nanos)));
+                }
+                "release_version" => manifest.release_version = Some(value.to_string()),
+                "content_version" => manifest.content_version = Some(value.to_string()),
+                "release_tag" => manifest.release.tag_name = Some(value.to_string()),
+                "release_title" => manifest.release.title = Some(value.to_string()),
+                "asset_arch" => manifest.asset_arch = value.parse().ok(),
+                "release_published" => {
+                    manifest.release.published_at = DateTime::parse_from_rfc3339(value)
+                        .ok()
+                        .map(|date| date.to_utc());
+                }
+                _ => {}
+            }
+        }
+
+        if let (Some(bytes), Some(files)) = (bytes, files) {
+            manifest.size = Some(ToolchainSize { bytes, files });
+        }
+
+        manifest
+    }
+
+    fn to_text(&self) -> String {
+        let mut text = String::new();
+
+        if let (Some(size), Some(modified)) = (self.size, self.modified) {
+            let modified = modified
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos();
+            text += &format!(
+                "bytes={}\nfiles={}\nmodified={modified}\n",
+                size.bytes, size.files
+            );
+        }
+        if let Some(version) = &self.release_version {
+            text += &format!("release_version={version}\n");
+        }
+        if let Some(version) = &self.content_version {
+            text += &format!("content_version={version}\n");
+        }
+        if let Some(tag_name) = &self.release.tag_name {
+            text += &format!("release_tag={tag_name}\n");
+        }
+        if let Some(title) = &self.release.title {
+            // Values can't span several lines.
+            text += &format!("release_title={}\n", title.replace(['\r', '\n'], " "));
+        }
+        if let Some(published_at) = &self.release.published_at {
+            text += &format!("release_published={}\n", published_at.to_rfc3339());
+        }
+        if let Some(arch) = self.asset_arch {
+            text += &format!("asset_arch={}\n", arch.as_ref());
+        }
+
+        text
+    }
+}
+
+/// Returns the versions recorded in a toolchain's manifest, or `None` if they weren't
+/// recorded (such as for toolchains installed by older versions).
+pub(crate) async fn recorded_versions(toolchain: &InstalledToolchain) -> Option<RecordedVersions> {
+    let manifest = read_manifest(&toolchain.path.join(MANIFEST_FILENAME)).await?;
+
+    Some(RecordedVersions {
+        release: ToolchainVersion::named(manifest.release_version?),
+        content: manifest.content_version.map(ToolchainVersion::named),
+    })
+}
+
+/// Records the version of the release a toolchain was installed from and the version of the
+/// archive's contents in the toolchain's manifest.
+pub(crate) async fn record_versions(
+    toolchain: &InstalledToolchain,
+    versions: &RecordedVersions,
+) -> Result<(), ToolchainError> {
+    let manifest_path = toolchain.path.join(MANIFEST_FILENAME);
+    let mut manifest = read_manifest(&manifest_path).await.unwrap_or_default();
+    manifest.release_version = Some(versions.release.name.clone());
+    manifest.content_version = versions.content.as_ref().map(|v| v.name.clone());
+
+    write_manifest(&toolchain.path, &manifest_path, manifest, false).await?;
+    Ok(())
+}
+
+/// Returns the details of the release recorded in a toolchain's manifest, or `None` if there
+/// is no manifest.
+pub(crate) async fn recorded_release(toolchain: &InstalledToolchain) -> Option<ReleaseMetadata> {
+    let manifest = read_manifest(&toolchain.path.join(MANIFEST_FILENAME)).await?;
+    Some(manifest.release)
+}
+
+/// Records the details of the release a toolchain was installed from in its manifest.
+pub(crate) async fn record_release(
+    toolchain: &InstalledToolchain,
+    release: &ReleaseMetadata,
+) -> Result<(), ToolchainError> {
+    let manifest_path = toolchain.path.join(MANIFEST_FILENAME);
+    let mut manifest = read_manifest(&manifest_path).await.unwrap_or_default();
+    manifest.release = release.clone();
+
+    write_manifest(&toolchain.path, &manifest_path, manifest, false).await?;
+    Ok(())
+}
+
+/// Returns the architecture of the asset recorded in a toolchain's manifest, or `None` if it
+/// wasn't recorded.
+pub(crate) async fn recorded_arch(toolchain: &InstalledToolchain) -> Option<HostArch> {
+    read_manifest(&toolchain.path.join(MANIFEST_FILENAME))
+        .await?
+        .asset_arch
+}
+
+/// Records the architecture of the asset a toolchain was installed from in its manifest.
+pub(crate) async fn record_arch(
+    toolchain: &InstalledToolchain,
+    arch: HostArch,
+) -> Result<(), ToolchainError> {
+    let manifest_path = toolchain.path.join(MANIFEST_FILENAME);
+    let mut manifest = read_manifest(&manifest_path).await.unwrap_or_default();
+    manifest.asset_arch = Some(arch);
+
+    write_manifest(&toolchain.path, &manifest_path, manifest, false).await?;
+    Ok(())
+}
+
+/// Returns the size of a toolchain, using the value cached in its manifest if the toolchain
+/// hasn't been modified since it was recorded.
+///
+/// If `refresh` is true, or the cached value is missing or stale, the size is recomputed and
+/// the manifest is updated. Failing to update the manifest isn't an error, since toolchains
+/// may be installed in read-only directories.
+pub(crate) async fn size(
+    toolchain: &InstalledToolchain,
+    refresh: bool,
+) -> Result<ToolchainSize, ToolchainError> {
+    let manifest_path = toolchain.path.join(MANIFEST_FILENAME);
+    let manifest = read_manifest(&manifest_path).await.unwrap_or_default();
+
+    if !refresh {
+        let modified = fs::metadata(&toolchain.path).await?.modified()?;
+        match (manifest.size, manifest.modified) {
+            (Some(size), Some(cached_modified)) if cached_modified == modified => {
+                debug!(?toolchain.path, "Using cached toolchain size");
+                return Ok(size);
+            }
+            (Some(_), _) => debug!(?toolchain.path, "Cached toolchain size is stale"),
+            (None, _) => debug!(?toolchain.path, "Toolchain has no cached size"),
+        }
+    }
+
+    let size = dir_size(&toolchain.path).await?;
+
+    let manifest = Manifest {
+        size: Some(size),
+        ..manifest
+    };
+    if let Err(error) = write_manifest(&toolchain.path, &manifest_path, manifest, true).await {
+        debug!(?error, ?manifest_path, "Failed to cache toolchain size");
+    }
+
+    Ok(size)
+}
+
+/// Reads a manifest, or returns `None` if it doesn't exist or can't be read.
+async fn read_manifest(path: &Path) -> Option<Manifest> {
+    match fs::read_to_string(path).await {
+        Ok(text) => Some(Manifest::parse(&text)),
+        Err(e) if e.kind() == ErrorKind::NotFound => None,
+        Err(error) => {
+            debug!(?error, ?path, "Failed to read toolchain manifest");
+            None
+        }
+    }
+}
+
+/// Writes a manifest. If `update_modified` is true, the toolchain directory's current
+/// modification time is recorded with the manifest's size.
+async fn write_manifest(
+    toolchain_path: &Path,
+    manifest_path: &Path,
+    mut manifest: Manifest,
+    update_modified: bool,
+) -> std::io::Result<()> {
+    // Creating the manifest changes the directory's modification time, but overwriting it
+    // doesn't. So the file is created before the modification time is recorded, and its
+    // contents are written in place afterwards.
+    let mut file = fs::File::options()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(manifest_path)
+        .await?;
+
+    if update_modified {
+        manifest.modified = Some(fs::metadata(toolchain_path).await?.modified()?);
+    }
+
+    file.write_all(manifest.to_text().as_bytes()).await?;
+    file.flush().await?;
+    Ok(())
+}
+
+/// Returns the total size and number of the files in a directory, not following symlinks.
+///
+/// The manifest file in the top-level directory isn't included.
+pub(crate) async fn dir_size(path: &Path) -> Result<ToolchainSize, ToolchainError> {
+    let mut size = ToolchainSize::default();
+    let manifest_path = path.join(MANIFEST_FILENAME);
+    let mut pending = vec![path.to_owned()];
+
+    while let Some(dir) = pending.pop() {
+        let mut read_dir = fs::read_dir(&dir).await?;
+        while let Some(entry) = read_dir.next_entry().await? {
+            let entry_path = entry.path();
+            let meta = fs::symlink_metadata(&entry_path).await?;
+            if meta.is_dir() {
+                pending.push(entry_path);
+            } else if meta.is_file() && entry_path != manifest_path {
+                size.bytes += meta.len();
+                size.files += 1;
+            }
+        }
+    }
+
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_version_from_the_root_name() {
+        for (name, version) in [
+            ("ATfE-21.1.0-Linux-x86_64", Some("21.1.0")),
+            ("ATfE-20.1.0-rc1-Linux-x86_64", Some("20.1.0-rc1")),
+            ("ATfE-21.1.0-RC2-Darwin-universal", Some("21.1.0-RC2")),
+            (
+                "ATfE-22.1.0-beta-pre3-Windows-x86_64",
+                Some("22.1.0-beta-pre3"),
+            ),
+            ("ATfE-Linux-x86_64-20.1.0", Some("20.1.0")),
+            (
+                "LLVMEmbeddedToolchainForArm-19.1.5-Windows-x86_64",
+                Some("19.1.5"),
+            ),
+            ("ATfE-21-Linux-x86_64", None),
+            ("toolchain", None),
+            ("", None),
+        ] {
+            assert_eq!(
+                content_version_from_root_name(name),
+                version.map(ToolchainVersion::named),
+                "{name}"
+            );
+        }
+    }
+}
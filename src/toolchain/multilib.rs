@@ -0,0 +1,379 @@
+//! Choosing a toolchain's runtime libraries the way clang does, from its `multilib.yaml`.
+//!
+//! Each toolchain ships prebuilt C libraries for many combinations of architecture, FPU, and
+//! float ABI, in directories such as `arm-none-eabi/armv7em_hard_fpv4_sp_d16`. clang turns the
+//! command line into a set of normalized flags, extends them with the file's `Mappings`, and
+//! selects every variant whose flags are all in the set. When several variants in an
+//! exclusive group match, the last one wins.
+//!
+//! People know their chip rather than these directory names, so [`TargetSpec`] describes a
+//! target by its CPU, float ABI, and FPU, and is turned into the flags clang would use for it.
+
+use std::{collections::BTreeSet, fmt};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use strum::{AsRefStr, EnumString};
+
+use crate::{
+    fs,
+    toolchain::{InstalledToolchain, ToolchainError},
+};
+
+/// The name of the file in a toolchain's multilib directory that describes its variants.
+pub const MULTILIB_FILENAME: &str = "multilib.yaml";
+
+/// A toolchain's `multilib.yaml` file, as returned by [`InstalledToolchain::multilib`].
+#[derive(Debug, Clone)]
+pub struct Multilib {
+    variants: Vec<MultilibVariant>,
+    groups: Vec<MultilibGroup>,
+    mappings: Vec<FlagMapping>,
+}
+
+/// One set of runtime libraries that a toolchain ships.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MultilibVariant {
+    /// The variant's directory relative to the multilib directory, such as
+    /// `arm-none-eabi/armv6m_soft_nofp`. Variants that only report an error have none.
+    pub dir: Option<String>,
+    /// The flags that must all be used for this variant to be selected.
+    #[serde(default)]
+    pub flags: Vec<String>,
+    /// The group that the variant belongs to, if any.
+    pub group: Option<String>,
+    /// The error reported if this variant is selected, for combinations of flags that have no
+    /// runtime libraries.
+    pub error: Option<String>,
+}
+
+impl MultilibVariant {
+    /// Splits the variant's directory into its triple and variant name, such as
+    /// `arm-none-eabi` and `armv6m_soft_nofp`, for use with [`InstalledToolchain::sysroot`].
+    pub fn triple_and_name(&self) -> Option<(&str, &str)> {
+        self.dir.as_deref()?.split_once('/')
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct MultilibGroup {
+    name: String,
+    #[serde(rename = "Type")]
+    kind: String,
+}
+
+#[derive(Debug, Clone)]
+struct FlagMapping {
+    pattern: Regex,
+    flags: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RawMultilib {
+    #[serde(default)]
+    variants: Vec<MultilibVariant>,
+    #[serde(default)]
+    groups: Vec<MultilibGroup>,
+    #[serde(default)]
+    mappings: Vec<RawMapping>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RawMapping {
+    #[serde(rename = "Match")]
+    pattern: String,
+    #[serde(default)]
+    flags: Vec<String>,
+}
+
+/// The result of [`Multilib::select`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MultilibSelection {
+    /// The flags that variants were matched against, after applying the mappings.
+    pub flags: Vec<String>,
+    /// The selected variants, in the order clang searches them. The last one is the one
+    /// whose libraries take priority.
+    pub variants: Vec<MultilibVariant>,
+}
+
+impl MultilibSelection {
+    /// Returns the error message of a selected variant that reports one, if any.
+    pub fn error(&self) -> Option<&str> {
+        self.variants.iter().find_map(|v| v.error.as_deref())
+    }
+
+    /// Returns the variant whose libraries take priority, if any have a directory.
+    pub fn primary(&self) -> Option<&MultilibVariant> {
+        self.variants.iter().rev().find(|v| v.dir.is_some())
+    }
+}
+
+impl Multilib {
+    /// Parses the contents of a `multilib.yaml` file.
+    ///
+    /// Returns a description of the problem if it isn't valid YAML, or a mapping's pattern
+    /// isn't a valid regular expression.
+    pub fn parse(yaml: &str) -> Result<Self, String> {
+        let raw: RawMultilib = serde_norway::from_str(yaml).map_err(|e| e.to_string())?;
+        let mappings = raw
+            .mappings
+            .into_iter()
+            .map(|mapping| {
+                // clang requires the whole flag to match.
+                let pattern = Regex::new(&format!("^(?:{})$", mapping.pattern))
+                    .map_err(|e| format!("invalid pattern {:?}: {e}", mapping.pattern))?;
+                Ok(FlagMapping {
+                    pattern,
+                    flags: mapping.flags,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(Self {
+            variants: raw.variants,
+            groups: raw.groups,
+            mappings,
+        })
+    }
+
+    /// Returns every variant, in the order they're listed in the file.
+    pub fn variants(&self) -> &[MultilibVariant] {
+        &self.variants
+    }
+
+    /// Returns whether any variant or mapping refers to the given flag, either directly or
+    /// through a mapping's pattern.
+    pub fn mentions_flag(&self, flag: &str) -> bool {
+        self.variants
+            .iter()
+            .any(|v| v.flags.iter().any(|f| f == flag))
+            || self
+                .mappings
+                .iter()
+                .any(|m| m.pattern.is_match(flag) || m.flags.iter().any(|f| f == flag))
+    }
+
+    /// Selects the variants that clang would use for the given normalized flags, such as
+    /// the ones returned by [`TargetSpec::multilib_flags`].
+    pub fn select(&self, flags: &[String]) -> MultilibSelection {
+        let mut expanded: BTreeSet<String> = flags.iter().cloned().collect();
+        for mapping in &self.mappings {
+            if flags.iter().any(|flag| mapping.pattern.is_match(flag)) {
+                expanded.extend(mapping.flags.iter().cloned());
+            }
+        }
+
+        let mut selected: Vec<&MultilibVariant> = Vec::new();
+        for variant in &self.variants {
+            if !variant.flags.iter().all(|flag| expanded.contains(flag)) {
+                continue;
+            }
+
+            // Only the last matching variant of an exclusive group is used.
+            if let Some(group) = &variant.group
+                && self
+                    .groups
+                    .iter()
+                    .any(|g| &g.name == group && g.kind.eq_ignore_ascii_case("exclusive"))
+            {
+                selected.retain(|v| v.group.as_ref() != Some(group));
+            }
+            selected.push(variant);
+        }
+
+        MultilibSelection {
+            flags: expanded.into_iter().collect(),
+            variants: selected.into_iter().cloned().collect(),
+        }
+    }
+
+    /// Returns the names of the CPUs in [`KNOWN_CPUS`] that this multilib has variants for.
+    pub fn supported_cpus(&self) -> Vec<&'static str> {
+        KNOWN_CPUS
+            .iter()
+            .filter(|cpu| {
+                [FloatAbi::Soft, FloatAbi::Hard]
+                    .iter()
+                    .any(|&abi| self.mentions_flag(&format!("--target={}", cpu.triple(abi))))
+            })
+            .map(|cpu| cpu.name)
+            .collect()
+    }
+}
+
+/// How floating point arguments are passed, as set by clang's `-mfloat-abi`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, AsRefStr, EnumString)]
+#[cfg_attr(feature = "cli-core", derive(clap::ValueEnum))]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum FloatAbi {
+    /// Floating point is done in software, even if there is an FPU.
+    #[default]
+    Soft,
+    /// The FPU is used, but arguments are passed in integer registers.
+    Softfp,
+    /// The FPU is used, and arguments are passed in floating point registers.
+    Hard,
+}
+
+/// A CPU that a [`TargetSpec`] can be created for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuInfo {
+    /// The name passed to clang's `-mcpu`, such as `cortex-m4`.
+    pub name: &'static str,
+    /// The architecture in the CPU's target triple, such as `thumbv7em`.
+    pub arch: &'static str,
+    /// The FPU used when none is given, or `None` if the CPU usually has no FPU.
+    pub default_fpu: Option<&'static str>,
+    /// Whether the CPU supports unaligned memory accesses.
+    pub unaligned_access: bool,
+}
+
+impl CpuInfo {
+    fn is_aarch64(&self) -> bool {
+        self.arch == "aarch64"
+    }
+
+    /// Returns the normalized target triple that clang uses for this CPU.
+    fn triple(&self, float_abi: FloatAbi) -> String {
+        if self.is_aarch64() {
+            "aarch64-unknown-none-elf".to_string()
+        } else if float_abi == FloatAbi::Hard {
+            format!("{}-unknown-none-eabihf", self.arch)
+        } else {
+            format!("{}-unknown-none-eabi", self.arch)
+        }
+    }
+}
+
+/// The CPUs that targets can be described by, and how clang treats each of them.
+pub const KNOWN_CPUS: &[CpuInfo] = &[
+    cpu("cortex-m0", "thumbv6m", None, false),
+    cpu("cortex-m0plus", "thumbv6m", None, false),
+    cpu("cortex-m1", "thumbv6m", None, false),
+    cpu("cortex-m3", "thumbv7m", None, true),
+    cpu("cortex-m4", "thumbv7em", Some("fpv4-sp-d16"), true),
+    cpu("cortex-m7", "thumbv7em", Some("fpv5-d16"), true),
+    cpu("cortex-m23", "thumbv8m.base", None, false),
+    cpu("cortex-m33", "thumbv8m.main", Some("fpv5-sp-d16"), true),
+    cpu("cortex-m35p", "thumbv8m.main", Some("fpv5-sp-d16"), true),
+    cpu(
+        "cortex-m55",
+        "thumbv8.1m.main",
+        Some("fp-armv8-fullfp16-d16"),
+        true,
+    ),
+    cpu(
+        "cortex-m85",
+        "thumbv8.1m.main",
+        Some("fp-armv8-fullfp16-d16"),
+        true,
+    ),
+    cpu("cortex-r4", "armv7r", None, true),
+    cpu("cortex-r5", "armv7r", Some("vfpv3-d16"), true),
+    cpu("cortex-r52", "armv8r", Some("fp-armv8-fullfp16-d16"), true),
+    cpu("cortex-a5", "armv7a", Some("vfpv4-d16"), true),
+    cpu("cortex-a7", "armv7a", Some("vfpv4-d16"), true),
+    cpu("cortex-a8", "armv7a", Some("vfpv3-d16"), true),
+    cpu("cortex-a9", "armv7a", Some("vfpv3-d16"), true),
+    cpu("cortex-a53", "aarch64", None, true),
+    cpu("cortex-a55", "aarch64", None, true),
+    cpu("cortex-a72", "aarch64", None, true),
+];
+
+const fn cpu(
+    name: &'static str,
+    arch: &'static str,
+    default_fpu: Option<&'static str>,
+    unaligned_access: bool,
+) -> CpuInfo {
+    CpuInfo {
+        name,
+        arch,
+        default_fpu,
+        unaligned_access,
+    }
+}
+
+/// A target described by its CPU, float ABI, and FPU, such as a Cortex-M4 with hardware
+/// floating point.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetSpec {
+    /// The CPU, from [`KNOWN_CPUS`].
+    pub cpu: CpuInfo,
+    /// The float ABI. AArch64 CPUs always use the hardware ABI, so this is ignored for them.
+    pub float_abi: FloatAbi,
+    /// The FPU, such as `fpv4-sp-d16`. If this is `None`, the CPU's default FPU is used.
+    pub fpu: Option<String>,
+}
+
+impl TargetSpec {
+    /// Creates a target for the named CPU.
+    ///
+    /// Returns `None` if the CPU isn't in [`KNOWN_CPUS`].
+    pub fn new(cpu: &str, float_abi: FloatAbi, fpu: Option<String>) -> Option<Self> {
+        let cpu = KNOWN_CPUS
+            .iter()
+            .find(|known| known.name.eq_ignore_ascii_case(cpu))?;
+        Some(Self {
+            cpu: *cpu,
+            float_abi,
+            fpu,
+        })
+    }
+
+    /// Returns the normalized flags that clang matches against `multilib.yaml` for this
+    /// target, such as `--target=thumbv7em-unknown-none-eabihf` and `-mfpu=fpv4-sp-d16`.
+    pub fn multilib_flags(&self) -> Vec<String> {
+        let mut flags = vec![format!("--target={}", self.cpu.triple(self.float_abi))];
+
+        if !self.cpu.is_aarch64() {
+            // Software floating point never uses the FPU, so clang reports it as having none.
+            let fpu = match self.float_abi {
+                FloatAbi::Soft => "none",
+                FloatAbi::Softfp | FloatAbi::Hard => self
+                    .fpu
+                    .as_deref()
+                    .or(self.cpu.default_fpu)
+                    .unwrap_or("none"),
+            };
+            flags.push(format!("-mfloat-abi={}", self.float_abi.as_ref()));
+            flags.push(format!("-mfpu={fpu}"));
+        }
+
+        flags.push(if self.cpu.unaligned_access {
+            "-munaligned-access".to_string()
+        } else {
+            "-mno-unaligned-access".to_string()
+        });
+
+        flags
+    }
+}
+
+impl fmt::Display for TargetSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.cpu.name)?;
+        if !self.cpu.is_aarch64() {
+            write!(f, " ({} float", self.float_abi.as_ref())?;
+            if let Some(fpu) = &self.fpu {
+                write!(f, ", {fpu}")?;
+            }
+            f.write_str(")")?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads and parses the `multilib.yaml` file of an installed toolchain.
+pub(crate) async fn read_multilib(
+    toolchain: &InstalledToolchain,
+) -> Result<Multilib, ToolchainError> {
+    let path = toolchain.multilib_dir().join(MULTILIB_FILENAME);
+    let yaml = fs::read_to_string(&path).await?;
+    Multilib::parse(&yaml).map_err(|reason| ToolchainError::InvalidMultilib { path, reason })
+}
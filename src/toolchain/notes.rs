@@ -0,0 +1,112 @@
+//! The release notes saved in each toolchain when it's installed.
+//!
+//! The notes are the release's description from GitHub, written as a markdown file in the
+//! toolchain's directory along with the release's tag, title, and publication date. The same
+//! details are recorded in the manifest, so they can be read without parsing the markdown.
+
+use std::io::ErrorKind;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::{
+    fs,
+    toolchain::{InstalledToolchain, ToolchainError, ToolchainRelease, manifest},
+};
+
+/// The name of the release notes file in a toolchain's directory.
+pub(crate) const RELEASE_NOTES_FILENAME: &str = ".release-notes.md";
+
+/// The release notes of an installed toolchain, as returned by
+/// [`InstalledToolchain::release_notes`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReleaseNotes {
+    /// The name of the release's Git tag.
+    pub tag_name: Option<String>,
+    /// The release's title, if it has one.
+    pub title: Option<String>,
+    /// When the release was published.
+    pub published_at: Option<DateTime<Utc>>,
+    /// The contents of the notes file, in markdown.
+    pub markdown: String,
+}
+
+/// Details about a release that are recorded in the manifest.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct ReleaseMetadata {
+    pub tag_name: Option<String>,
+    pub title: Option<String>,
+    pub published_at: Option<DateTime<Utc>>,
+}
+
+/// Formats a release's notes as a markdown document, starting with its details.
+fn to_markdown(release: &ToolchainRelease) -> String {
+    let product = release.product();
+    let tag_name = release.tag_name();
+    let heading = release
+        .title()
+        .unwrap_or_else(|| format!("{} {}", product.display_name, release.version()));
+
+    let mut text = format!("# {heading}\n\n- Tag: `{tag_name}`\n");
+    if let Some(published_at) = release.published_at() {
+        text += &format!("- Published: {}\n", published_at.format("%Y-%m-%d"));
+    }
+    text += &format!(
+        "- Release page: https://github.com/{}/{}/releases/tag/{tag_name}\n\n",
+        product.repo_owner, product.repo_name
+    );
+
+    match release.notes() {
+        Some(notes) => {
+            text += notes.trim_end();
+            text.push('\n');
+        }
+        None => text += "This release has no notes.\n",
+    }
+
+    text
+}
+
+/// Saves a release's notes in the toolchain installed from it, and records its details in
+/// the toolchain's manifest.
+pub(crate) async fn record_release_notes(
+    toolchain: &InstalledToolchain,
+    release: &ToolchainRelease,
+) -> Result<(), ToolchainError> {
+    fs::write(
+        toolchain.path.join(RELEASE_NOTES_FILENAME),
+        to_markdown(release),
+    )
+    .await?;
+
+    let metadata = ReleaseMetadata {
+        tag_name: Some(release.tag_name().to_string()),
+        title: release.title(),
+        published_at: release.published_at(),
+    };
+    manifest::record_release(toolchain, &metadata).await
+}
+
+/// Reads the release notes saved in a toolchain.
+///
+/// Returns `None` if no notes were saved, which is the case for toolchains installed by
+/// older versions of this crate.
+pub(crate) async fn release_notes(
+    toolchain: &InstalledToolchain,
+) -> Result<Option<ReleaseNotes>, ToolchainError> {
+    let markdown = match fs::read_to_string(toolchain.path.join(RELEASE_NOTES_FILENAME)).await {
+        Ok(markdown) => markdown,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let metadata = manifest::recorded_release(toolchain)
+        .await
+        .unwrap_or_default();
+
+    Ok(Some(ReleaseNotes {
+        tag_name: metadata.tag_name,
+        title: metadata.title,
+        published_at: metadata.published_at,
+        markdown,
+    }))
+}
@@ -0,0 +1,218 @@
+//! Post-extraction permission adjustments for toolchains installed in shared directories.
+
+use std::{path::Path, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+
+use crate::toolchain::{FixReport, InstallState, InstalledToolchain, ToolchainError};
+
+/// Controls the file permissions of an extracted toolchain.
+///
+/// Archives store the permissions of their files, which are applied verbatim when extracting.
+/// These are usually only writable by the owner, which prevents other members of a group
+/// from using a toolchain installed in a shared directory such as `/opt`.
+///
+/// Permission policies only have an effect on Unix-like systems.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PermissionPolicy {
+    /// Keep the permissions stored in the archive.
+    #[default]
+    Preserve,
+    /// Make the toolchain readable and writable by its group and readable by everyone else.
+    ///
+    /// Directories are set to mode `2775` so that new files inherit their group, and files are
+    /// set to mode `664` (or `775` if they were already executable).
+    SharedRead,
+    /// Add the given mode bits to every file and directory.
+    ///
+    /// Execute bits are only added to files that were already executable.
+    Mask(u32),
+}
+
+impl PermissionPolicy {
+    /// Returns the new mode for an entry with the given mode, or `None` if it shouldn't change.
+    #[cfg(unix)]
+    fn apply(self, mode: u32, is_dir: bool) -> Option<u32> {
+        const EXEC_BITS: u32 = 0o111;
+
+        let new_mode = match self {
+            Self::Preserve => return None,
+            Self::SharedRead if is_dir => 0o2775,
+            Self::SharedRead if mode & EXEC_BITS != 0 => 0o775,
+            Self::SharedRead => 0o664,
+            Self::Mask(mask) if is_dir || mode & EXEC_BITS != 0 => mode | mask,
+            Self::Mask(mask) => mode | (mask & !EXEC_BITS),
+        };
+
+        (new_mode & 0o7777 != mode & 0o7777).then_some(new_mode)
+    }
+}
+
+/// Applies a permission policy to every file and directory inside `dir`.
+///
+/// Symbolic links are not followed.
+#[cfg(unix)]
+pub async fn apply_permissions(
+    dir: &Path,
+    policy: PermissionPolicy,
+    progress: Arc<dyn Fn(InstallState) + Send + Sync>,
+    cancel_token: &CancellationToken,
+) -> Result<(), ToolchainError> {
+    use std::os::unix::fs::PermissionsExt;
+
+    use tracing::debug;
+
+    use crate::{cancel::CheckCancellation, fs, toolchain::Phase};
+
+    if policy == PermissionPolicy::Preserve {
+        return Ok(());
+    }
+
+    debug!(phase = %Phase::Permissions, ?dir, ?policy, "Applying permission policy");
+
+    // Enumerate everything up-front so that progress can be reported accurately.
+    let mut entries = vec![dir.to_path_buf()];
+    let mut idx = 0;
+    while idx < entries.len() {
+        cancel_token.check_cancellation(ToolchainError::Cancelled)?;
+
+        let meta = fs::symlink_metadata(&entries[idx]).await?;
+        if meta.is_dir() {
+            let mut read_dir = fs::read_dir(&entries[idx]).await?;
+            while let Some(entry) = read_dir.next_entry().await? {
+                entries.push(entry.path());
+            }
+        }
+
+        idx += 1;
+    }
+
+    let total_entries = entries.len() as u64;
+    progress(InstallState::ApplyPermissionsBegin { total_entries });
+
+    for (entries_done, path) in entries.into_iter().enumerate() {
+        cancel_token.check_cancellation(ToolchainError::Cancelled)?;
+
+        let meta = fs::symlink_metadata(&path).await?;
+        if !meta.is_symlink()
+            && let Some(mode) = policy.apply(meta.permissions().mode(), meta.is_dir())
+        {
+            fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).await?;
+        }
+
+        progress(InstallState::ApplyPermissions {
+            entries_done: entries_done as u64 + 1,
+        });
+    }
+
+    progress(InstallState::ApplyPermissionsFinish);
+
+    Ok(())
+}
+
+/// Applies a permission policy to every file and directory inside `dir`.
+///
+/// This is a no-op on non-Unix platforms.
+#[cfg(not(unix))]
+pub async fn apply_permissions(
+    _dir: &Path,
+    _policy: PermissionPolicy,
+    _progress: Arc<dyn Fn(InstallState) + Send + Sync>,
+    _cancel_token: &CancellationToken,
+) -> Result<(), ToolchainError> {
+    Ok(())
+}
+
+/// Makes every native binary in a toolchain's executable directories executable again.
+///
+/// `bin` and `libexec` are searched recursively, while only the top level of `lib` is searched
+/// so that the multilib's target libraries are left alone.
+#[cfg(unix)]
+pub(crate) async fn fix_executable_bits(
+    toolchain: &InstalledToolchain,
+) -> Result<FixReport, ToolchainError> {
+    use std::os::unix::fs::PermissionsExt;
+
+    use tokio::io::AsyncReadExt;
+    use tracing::debug;
+
+    use crate::fs;
+
+    let mut report = FixReport::default();
+    let mut pending = vec![
+        (toolchain.host_bin_dir(), true),
+        (toolchain.path.join("libexec"), true),
+        (toolchain.lib_dir(), false),
+    ];
+
+    while let Some((dir, recursive)) = pending.pop() {
+        let mut read_dir = match fs::read_dir(&dir).await {
+            Ok(read_dir) => read_dir,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+
+        while let Some(entry) = read_dir.next_entry().await? {
+            let path = entry.path();
+            let meta = fs::symlink_metadata(&path).await?;
+
+            if meta.is_dir() {
+                if recursive {
+                    pending.push((path, true));
+                }
+                continue;
+            }
+
+            let mode = meta.permissions().mode();
+            if !meta.is_file() || mode & 0o111 != 0 {
+                continue;
+            }
+
+            let mut magic = [0; 4];
+            let mut file = fs::File::open(&path).await?;
+            let len = file.read(&mut magic).await?;
+            if !is_native_binary(&magic[..len]) {
+                continue;
+            }
+
+            // Anyone who can read the file should be able to execute it.
+            let new_mode = mode | (mode & 0o444) >> 2;
+            debug!(?path, "Restoring execute permission");
+            fs::set_permissions(&path, std::fs::Permissions::from_mode(new_mode)).await?;
+            report.fixed.push(path);
+        }
+    }
+
+    report.fixed.sort();
+    Ok(report)
+}
+
+/// Makes every native binary in a toolchain's executable directories executable again.
+///
+/// This is a no-op on non-Unix platforms.
+#[cfg(not(unix))]
+pub(crate) async fn fix_executable_bits(
+    _toolchain: &InstalledToolchain,
+) -> Result<FixReport, ToolchainError> {
+    Ok(FixReport::default())
+}
+
+/// Returns whether a file starting with the given bytes is an ELF, Mach-O, or PE file.
+#[cfg(unix)]
+fn is_native_binary(header: &[u8]) -> bool {
+    const MAGICS: &[&[u8]] = &[
+        b"\x7fELF",
+        // Mach-O (32 and 64-bit, both byte orders) and universal binaries
+        &[0xfe, 0xed, 0xfa, 0xce],
+        &[0xfe, 0xed, 0xfa, 0xcf],
+        &[0xce, 0xfa, 0xed, 0xfe],
+        &[0xcf, 0xfa, 0xed, 0xfe],
+        &[0xca, 0xfe, 0xba, 0xbe],
+        // PE
+        b"MZ",
+    ];
+
+    MAGICS.iter().any(|magic| header.starts_with(magic))
+}
@@ -0,0 +1,129 @@
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+use crate::toolchain::{ToolchainClient, ToolchainVersion};
+
+/// A family of toolchains published by Arm, such as the Arm Toolchain for Embedded.
+///
+/// Each product has its own GitHub release tags, and its toolchains are installed in a separate
+/// subdirectory of the toolchains directory with their own active version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Product {
+    /// A short identifier, which is also the name of the product's directory.
+    pub id: &'static str,
+    /// The name of the product shown to users.
+    pub display_name: &'static str,
+    /// The owner of the GitHub repository that releases are published to.
+    pub repo_owner: &'static str,
+    /// The name of the GitHub repository that releases are published to.
+    pub repo_name: &'static str,
+    /// The start of every release tag, before the version.
+    pub tag_prefix: &'static str,
+    /// The end of every release tag, after the version.
+    pub tag_suffix: &'static str,
+    /// Whether the product's toolchains contain a multilib (`lib/clang-runtimes`) with
+    /// libraries for each supported target.
+    pub has_multilib: bool,
+}
+
+impl Product {
+    /// The Arm Toolchain for Embedded (ATfE), which targets bare-metal Arm devices.
+    pub const EMBEDDED: Self = Self {
+        id: "embedded",
+        display_name: "Arm Toolchain for Embedded",
+        repo_owner: ToolchainClient::REPO_OWNER,
+        repo_name: ToolchainClient::REPO_NAME,
+        tag_prefix: ToolchainClient::RELEASE_PREFIX,
+        tag_suffix: ToolchainClient::RELEASE_SUFFIX,
+        has_multilib: true,
+    };
+
+    /// The Arm Toolchain for Linux (ATfL), which targets A-profile (AArch64) Linux systems.
+    pub const LINUX: Self = Self {
+        id: "linux",
+        display_name: "Arm Toolchain for Linux",
+        repo_owner: "arm",
+        repo_name: "arm-toolchain",
+        tag_prefix: "release-",
+        tag_suffix: "-ATfL",
+        has_multilib: false,
+    };
+
+    /// Every built-in product.
+    pub const ALL: &[Self] = &[Self::EMBEDDED, Self::LINUX];
+
+    /// Returns whether the given release tag belongs to this product.
+    ///
+    /// Besides tags like `release-21.1.0-ATfE`, this accepts release candidates tagged with
+    /// a pre-release after the suffix, like `release-21.1.0-ATfE-rc1`.
+    pub fn matches_tag(&self, tag_name: &str) -> bool {
+        tag_name
+            .strip_prefix(self.tag_prefix)
+            .is_some_and(|rest| self.split_suffix(rest).is_some())
+    }
+
+    /// Extracts the version from one of this product's release tags.
+    ///
+    /// A pre-release after the suffix is moved into the version, so both
+    /// `release-21.1.0-rc1-ATfE` and `release-21.1.0-ATfE-rc1` are version `21.1.0-rc1`.
+    pub fn version_from_tag(&self, tag_name: &str) -> ToolchainVersion {
+        let name = tag_name.strip_prefix(self.tag_prefix).unwrap_or(tag_name);
+        match self.split_suffix(name) {
+            Some((version, "")) => ToolchainVersion::named(version),
+            Some((version, pre_release)) => {
+                ToolchainVersion::named(format!("{version}-{pre_release}"))
+            }
+            None => ToolchainVersion::named(name),
+        }
+    }
+
+    /// Splits a tag without its prefix into the part before the suffix and the pre-release
+    /// after it (which is empty if there isn't one), or returns `None` if it doesn't contain
+    /// the suffix.
+    fn split_suffix<'a>(&self, name: &'a str) -> Option<(&'a str, &'a str)> {
+        let (version, after) = name.rsplit_once(self.tag_suffix)?;
+        match after.strip_prefix('-') {
+            Some(pre_release) if !pre_release.is_empty() => Some((version, pre_release)),
+            _ if after.is_empty() => Some((version, "")),
+            _ => None,
+        }
+    }
+
+    /// Returns the release tag of the given version of this product.
+    pub fn tag_for(&self, version: &ToolchainVersion) -> String {
+        format!("{}{}{}", self.tag_prefix, version.name, self.tag_suffix)
+    }
+}
+
+impl Default for Product {
+    fn default() -> Self {
+        Self::EMBEDDED
+    }
+}
+
+impl Display for Product {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.id)
+    }
+}
+
+impl FromStr for Product {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "embedded" | "atfe" => Ok(Self::EMBEDDED),
+            "linux" | "atfl" | "a-profile" => Ok(Self::LINUX),
+            _ => Err(format!(
+                "unknown product {s:?} (expected one of: {})",
+                Self::ALL
+                    .iter()
+                    .map(|p| p.id)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    }
+}
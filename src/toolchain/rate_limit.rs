@@ -0,0 +1,201 @@
+//! Recognizing GitHub's rate limit errors, so that they can be reported along with when the
+//! limit resets.
+//!
+//! Octocrab's errors don't include the response's headers, so for API requests the reset time
+//! is looked up with the `/rate_limit` endpoint, which doesn't count against the limit. Asset
+//! and checksum downloads are made with `reqwest`, whose responses have the headers.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Local, TimeDelta, Utc};
+use octocrab::Octocrab;
+use reqwest::{StatusCode, header::HeaderMap};
+use tracing::{debug, warn};
+
+use crate::toolchain::ToolchainError;
+
+/// How long to wait after hitting one of GitHub's secondary rate limits, which don't have a
+/// reset time. GitHub asks for at least a minute.
+const SECONDARY_LIMIT_WAIT: Duration = Duration::from_secs(60);
+
+/// Converts an error from the GitHub API into a [`ToolchainError`], replacing rate limit errors
+/// with [`ToolchainError::RateLimited`].
+///
+/// If the reset time can't be found, the original error is returned.
+pub(crate) async fn github_error(gh_client: &Octocrab, error: octocrab::Error) -> ToolchainError {
+    let octocrab::Error::GitHub { source, .. } = &error else {
+        return error.into();
+    };
+    let is_rate_limit = matches!(
+        source.status_code,
+        StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS
+    ) && source.message.to_ascii_lowercase().contains("rate limit");
+    if !is_rate_limit {
+        return error.into();
+    }
+
+    match gh_client.ratelimit().get().await {
+        Ok(rate_limit) => {
+            let core = rate_limit.resources.core;
+            debug!(?core, "Hit GitHub's rate limit");
+            let resets_at = if core.remaining == 0 {
+                DateTime::from_timestamp(core.reset as i64, 0)
+            } else {
+                // There are requests left, so it must have been a secondary limit.
+                Some(Utc::now() + SECONDARY_LIMIT_WAIT)
+            };
+            match resets_at {
+                Some(resets_at) => ToolchainError::RateLimited { resets_at },
+                None => error.into(),
+            }
+        }
+        Err(rate_limit_error) => {
+            warn!(
+                ?rate_limit_error,
+                "Failed to find out when the rate limit resets"
+            );
+            error.into()
+        }
+    }
+}
+
+/// Returns when the rate limit resets if a response with the given status and headers was
+/// rejected because of one, based on its `x-ratelimit-*` or `retry-after` headers.
+pub(crate) fn rate_limit_reset(status: StatusCode, headers: &HeaderMap) -> Option<DateTime<Utc>> {
+    if !matches!(
+        status,
+        StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS
+    ) {
+        return None;
+    }
+
+    let header = |name: &str| headers.get(name)?.to_str().ok()?.trim().parse::<i64>().ok();
+
+    if header("x-ratelimit-remaining") == Some(0) {
+        return DateTime::from_timestamp(header("x-ratelimit-reset")?, 0);
+    }
+    let retry_after = header("retry-after")?;
+    Some(Utc::now() + TimeDelta::seconds(retry_after))
+}
+
+/// Describes when a rate limit resets in local time, such as `14:05 (in 23 minutes)`.
+pub(crate) fn describe_reset(resets_at: &DateTime<Utc>) -> String {
+    let local = resets_at.with_timezone(&Local);
+    let time = if local.date_naive() == Local::now().date_naive() {
+        local.format("%H:%M").to_string()
+    } else {
+        local.format("%Y-%m-%d %H:%M").to_string()
+    };
+
+    let seconds = (*resets_at - Utc::now()).num_seconds().max(0) as u64;
+    let minutes = seconds.div_ceil(60);
+    let wait = match minutes {
+        0 => "now".to_string(),
+        1 => "in 1 minute".to_string(),
+        2..60 => format!("in {minutes} minutes"),
+        _ => format!("in {}h {:02}m", minutes / 60, minutes % 60),
+    };
+
+    format!("{time} ({wait})")
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    fn headers(pairs: &[(&str, &str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.parse().unwrap(), HeaderValue::from_str(value).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn exhausted_limits_reset_at_the_given_time() {
+        let headers = headers(&[
+            ("x-ratelimit-remaining", "0"),
+            ("x-ratelimit-reset", " 1767225600 "),
+        ]);
+        let resets_at = DateTime::from_timestamp(1_767_225_600, 0);
+
+        assert_eq!(rate_limit_reset(StatusCode::FORBIDDEN, &headers), resets_at);
+        assert_eq!(
+            rate_limit_reset(StatusCode::TOO_MANY_REQUESTS, &headers),
+            resets_at
+        );
+    }
+
+    #[test]
+    fn secondary_limits_reset_after_the_retry_after_header() {
+        let headers = headers(&[("x-ratelimit-remaining", "10"), ("retry-after", "120")]);
+
+        let before = Utc::now();
+        let resets_at = rate_limit_reset(StatusCode::TOO_MANY_REQUESTS, &headers).unwrap();
+
+        let wait = resets_at - before;
+        assert!(
+            (TimeDelta::seconds(120)..TimeDelta::seconds(125)).contains(&wait),
+            "{wait}"
+        );
+    }
+
+    #[test]
+    fn other_responses_are_not_rate_limits() {
+        let exhausted = headers(&[
+            ("x-ratelimit-remaining", "0"),
+            ("x-ratelimit-reset", "1767225600"),
+        ]);
+        // Only rejected requests are rate limited, even if that was the last request left.
+        assert_eq!(rate_limit_reset(StatusCode::OK, &exhausted), None);
+        assert_eq!(rate_limit_reset(StatusCode::NOT_FOUND, &exhausted), None);
+
+        // A 403 without the headers is some other kind of refusal.
+        assert_eq!(
+            rate_limit_reset(StatusCode::FORBIDDEN, &HeaderMap::new()),
+            None
+        );
+        let remaining = headers(&[("x-ratelimit-remaining", "59"), ("x-ratelimit-reset", "1")]);
+        assert_eq!(rate_limit_reset(StatusCode::FORBIDDEN, &remaining), None);
+        let unreadable = headers(&[
+            ("x-ratelimit-remaining", "0"),
+            ("x-ratelimit-reset", "soon"),
+        ]);
+        assert_eq!(rate_limit_reset(StatusCode::FORBIDDEN, &unreadable), None);
+    }
+
+    #[test]
+    fn describes_the_wait_until_the_reset() {
+        let wait = |seconds| {
+            let description = describe_reset(&(Utc::now() + TimeDelta::seconds(seconds)));
+            let (_, wait) = description.split_once(" (").unwrap();
+            wait.strip_suffix(')').unwrap().to_string()
+        };
+
+        assert_eq!(wait(-30), "now");
+        assert_eq!(wait(30), "in 1 minute");
+        assert_eq!(wait(22 * 60 + 30), "in 23 minutes");
+        assert_eq!(wait(3 * 3600 + 4 * 60 + 30), "in 3h 05m");
+    }
+
+    #[test]
+    fn describes_the_reset_in_local_time() {
+        let resets_at = Utc::now() + TimeDelta::seconds(90);
+        let local = resets_at.with_timezone(&Local);
+        // The date is only left out when the limit resets today.
+        let time = match local.date_naive() == Local::now().date_naive() {
+            true => local.format("%H:%M").to_string(),
+            false => local.format("%Y-%m-%d %H:%M").to_string(),
+        };
+        assert_eq!(describe_reset(&resets_at), format!("{time} (in 2 minutes)"));
+
+        let later = Utc::now() + TimeDelta::days(3);
+        let date = later.with_timezone(&Local).format("%Y-%m-%d %H:%M");
+        assert!(
+            describe_reset(&later).starts_with(&format!("{date} (in ")),
+            "{}",
+            describe_reset(&later)
+        );
+    }
+}
@@ -0,0 +1,197 @@
+//! A registry of the reasons each toolchain is installed.
+//!
+//! Several tools install toolchains into the same data directory, so a toolchain being
+//! installed doesn't say whether anything still needs it. Each tool records a short reason
+//! when it installs or starts relying on a toolchain (such as `cli: use` or
+//! `cargo-v5: project X`), and a toolchain with no remaining reasons can be pruned safely.
+//!
+//! The registry is shared between processes, so it's only changed while holding an OS lock on
+//! a lock file, and each change replaces the whole file in one step.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::ErrorKind,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use tokio::time::sleep;
+use tracing::warn;
+
+use crate::{
+    fs,
+    toolchain::{ToolchainError, ToolchainVersion, file_lock::try_lock_file},
+};
+
+/// How long to wait for another process to finish updating the registry.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long to wait between attempts to take the lock.
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The reasons that installed toolchains are needed, as returned by
+/// [`ToolchainClient::install_reasons`](super::ToolchainClient::install_reasons).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InstallReasons {
+    entries: BTreeMap<ToolchainVersion, Vec<String>>,
+}
+
+impl InstallReasons {
+    /// The name of the registry file in a product's toolchains directory.
+    pub const FILENAME: &str = "install-reasons";
+
+    /// Returns the reasons recorded for a version, in the order they were added.
+    pub fn get(&self, version: &ToolchainVersion) -> &[String] {
+        self.entries.get(version).map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns every version that has reasons, oldest first, along with its reasons.
+    pub fn iter(&self) -> impl Iterator<Item = (&ToolchainVersion, &[String])> {
+        self.entries
+            .iter()
+            .map(|(version, reasons)| (version, reasons.as_slice()))
+    }
+
+    /// Returns whether no reasons are recorded.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Records a reason for a version, returning whether it wasn't already recorded.
+    pub(crate) fn add(&mut self, version: &ToolchainVersion, reason: &str) -> bool {
+        let reasons = self.entries.entry(version.clone()).or_default();
+        if reasons.iter().any(|existing| existing == reason) {
+            return false;
+        }
+
+        reasons.push(reason.to_string());
+        true
+    }
+
+    /// Forgets a reason for a version, returning whether it was recorded.
+    pub(crate) fn remove(&mut self, version: &ToolchainVersion, reason: &str) -> bool {
+        let Some(reasons) = self.entries.get_mut(version) else {
+            return false;
+        };
+
+        let len = reasons.len();
+        reasons.retain(|existing| existing != reason);
+        let removed = reasons.len() != len;
+        if reasons.is_empty() {
+            self.entries.remove(version);
+        }
+
+        removed
+    }
+
+    /// Forgets every reason for a version, returning the ones that were recorded.
+    pub(crate) fn clear(&mut self, version: &ToolchainVersion) -> Vec<String> {
+        self.entries.remove(version).unwrap_or_default()
+    }
+
+    /// Forgets the reasons for versions that don't match the predicate.
+    pub(crate) fn retain(&mut self, mut keep: impl FnMut(&ToolchainVersion) -> bool) {
+        self.entries.retain(|version, _| keep(version));
+    }
+
+    fn parse(contents: &str) -> Self {
+        let mut reasons = Self::default();
+        for line in contents.lines().filter(|line| !line.is_empty()) {
+            match line.split_once('\t') {
+                Some((version, reason)) if !version.is_empty() && !reason.is_empty() => {
+                    reasons.add(&ToolchainVersion::named(version), reason);
+                }
+                _ => warn!(?line, "Ignoring invalid install reason"),
+            }
+        }
+        reasons
+    }
+
+    fn to_contents(&self) -> String {
+        let mut contents = String::new();
+        for (version, reasons) in self.iter() {
+            for reason in reasons {
+                contents.push_str(&format!("{}\t{reason}\n", version.name));
+            }
+        }
+        contents
+    }
+}
+
+/// Checks that a reason can be stored in the registry, returning it without surrounding
+/// whitespace.
+pub(crate) fn validate_reason(reason: &str) -> Result<&str, ToolchainError> {
+    let trimmed = reason.trim();
+    if trimmed.is_empty() || trimmed.chars().any(char::is_control) {
+        return Err(ToolchainError::InvalidInstallReason {
+            reason: reason.to_string(),
+        });
+    }
+
+    Ok(trimmed)
+}
+
+/// Reads the registry in the given directory. A missing registry has no reasons.
+pub(crate) async fn read_reasons(dir: &Path) -> Result<InstallReasons, ToolchainError> {
+    match fs::read_to_string(dir.join(InstallReasons::FILENAME)).await {
+        Ok(contents) => Ok(InstallReasons::parse(&contents)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(InstallReasons::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Changes the registry in the given directory while holding its lock, and returns the
+/// result of the change.
+///
+/// The registry is only written if the change modified it.
+pub(crate) async fn update_reasons<R>(
+    dir: &Path,
+    change: impl FnOnce(&mut InstallReasons) -> R,
+) -> Result<R, ToolchainError> {
+    fs::create_dir_all(dir).await?;
+    let lock = RegistryLock::acquire(dir).await?;
+
+    let result = async {
+        let mut reasons = read_reasons(dir).await?;
+        let original = reasons.clone();
+        let result = change(&mut reasons);
+
+        if reasons != original {
+            // Readers don't take the lock, so they must never see a partially written file.
+            let path = dir.join(InstallReasons::FILENAME);
+            let temp_path = dir.join(format!("{}.tmp", InstallReasons::FILENAME));
+            fs::write(&temp_path, reasons.to_contents()).await?;
+            fs::rename(&temp_path, &path).await?;
+        }
+
+        Ok(result)
+    }
+    .await;
+
+    drop(lock);
+    result
+}
+
+/// An exclusive lock on a registry, held by locking a lock file next to it.
+struct RegistryLock {
+    _file: File,
+}
+
+impl RegistryLock {
+    async fn acquire(dir: &Path) -> Result<Self, ToolchainError> {
+        let path = dir.join(format!("{}.lock", InstallReasons::FILENAME));
+        let started = Instant::now();
+
+        loop {
+            if let Some(file) = try_lock_file(path.clone()).await? {
+                return Ok(Self { _file: file });
+            }
+
+            if started.elapsed() > LOCK_TIMEOUT {
+                return Err(ToolchainError::InstallReasonsLocked { path });
+            }
+
+            sleep(LOCK_RETRY_INTERVAL).await;
+        }
+    }
+}
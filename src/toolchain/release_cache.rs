@@ -0,0 +1,148 @@
+//! A cache of the release metadata fetched from GitHub, so that commands run in quick
+//! succession don't each ask the GitHub API for it.
+//!
+//! The list of every release in the repository is saved in the download cache, along with the
+//! ETag of its first page. Within the client's TTL (see
+//! [`ToolchainClientBuilder::release_cache_ttl`](super::ToolchainClientBuilder::release_cache_ttl)),
+//! the saved list is used as-is. After that, it's revalidated with `If-None-Match`, and reused
+//! if GitHub responds with `304 Not Modified`, which doesn't count against an authenticated
+//! client's rate limit.
+//!
+//! On top of that, each client keeps what it fetched in memory for a short while (see
+//! [`ToolchainClientBuilder::release_memory_ttl`](super::ToolchainClientBuilder::release_memory_ttl)),
+//! and callers that ask for the same endpoint at the same time wait for one request instead of
+//! each sending their own.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use octocrab::models::repos::Release;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, trace};
+
+use crate::{fs, toolchain::ToolchainError};
+
+/// How long fetched release metadata is used without asking GitHub whether it changed, unless
+/// the client was configured otherwise.
+pub const DEFAULT_RELEASE_CACHE_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// How long a client reuses release metadata it fetched without reading the cache or asking
+/// GitHub again, unless it was configured otherwise.
+pub const DEFAULT_RELEASE_MEMORY_TTL: Duration = Duration::from_secs(60);
+
+/// The name of the directory in the download cache that release metadata is saved in.
+const RELEASE_CACHE_DIR: &str = "release-metadata";
+
+/// The saved list of releases in a repository.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct CachedReleases {
+    /// The ETag of the first page of releases, which changes whenever a release is published
+    /// or edited.
+    pub etag: Option<String>,
+    /// When the list was last fetched or revalidated.
+    pub fetched_at: DateTime<Utc>,
+    /// Every release, in the order GitHub lists them.
+    pub releases: Vec<Release>,
+}
+
+impl CachedReleases {
+    /// Returns whether the list was fetched or revalidated less than `ttl` ago.
+    pub fn is_fresh(&self, ttl: Duration) -> bool {
+        Utc::now()
+            .signed_duration_since(self.fetched_at)
+            .to_std()
+            .is_ok_and(|age| age < ttl)
+    }
+}
+
+/// Returns the path that the releases of the given repository are saved at.
+pub(crate) fn cache_file(cache_path: &Path, repo_owner: &str, repo_name: &str) -> PathBuf {
+    cache_path
+        .join(RELEASE_CACHE_DIR)
+        .join(format!("{repo_owner}-{repo_name}.json"))
+}
+
+/// Reads the saved releases, or returns `None` if there aren't any or they can't be read.
+pub(crate) async fn read(path: &Path) -> Option<CachedReleases> {
+    let contents = fs::read(path).await.ok()?;
+    serde_json::from_slice(&contents)
+        .inspect_err(|error| debug!(?path, %error, "Ignoring unreadable release metadata cache"))
+        .ok()
+}
+
+/// Saves the releases, replacing the file atomically so that a concurrent command never reads
+/// half of it.
+pub(crate) async fn write(path: &Path, releases: &CachedReleases) -> Result<(), ToolchainError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+
+    let contents = serde_json::to_vec(releases).map_err(std::io::Error::other)?;
+    let temp_path = path.with_extension(format!("json.{}.tmp", std::process::id()));
+    fs::write(&temp_path, contents).await?;
+    fs::rename(&temp_path, path).await?;
+
+    Ok(())
+}
+
+/// Release metadata that a client fetched, keyed by the API endpoint it came from.
+///
+/// Each endpoint has its own lock, which is held while the metadata is fetched, so concurrent
+/// callers wait for the first one's result. Failures aren't kept, so the next caller tries
+/// again, and a caller that's cancelled lets the next one fetch it instead.
+pub(crate) struct MemoryCache<T> {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, Arc<MemoryEntry<T>>>>,
+}
+
+/// The last metadata fetched from an endpoint, along with when it was fetched.
+type MemoryEntry<T> = tokio::sync::Mutex<Option<(Instant, T)>>;
+
+impl<T: Clone> MemoryCache<T> {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::default(),
+        }
+    }
+
+    /// Returns the metadata fetched from `endpoint` less than the TTL ago, or fetches it with
+    /// `fetch` if there isn't any. If a fetch is already in progress, this waits for it and
+    /// returns its result instead.
+    pub async fn get_or_fetch<F>(
+        &self,
+        endpoint: &str,
+        fetch: impl FnOnce() -> F,
+    ) -> Result<T, ToolchainError>
+    where
+        F: Future<Output = Result<T, ToolchainError>>,
+    {
+        let requested_at = Instant::now();
+        let entry = self
+            .entries
+            .lock()
+            .unwrap()
+            .entry(endpoint.to_string())
+            .or_default()
+            .clone();
+
+        let mut entry = entry.lock().await;
+        // A fetch that finished after this call started was in progress while it waited, so
+        // its result is used even if the TTL is zero.
+        if let Some((fetched_at, value)) = &*entry
+            && (fetched_at.elapsed() < self.ttl || *fetched_at >= requested_at)
+        {
+            trace!(endpoint, "Using release metadata fetched by this client");
+            return Ok(value.clone());
+        }
+
+        let value = fetch().await?;
+        *entry = Some((Instant::now(), value.clone()));
+        Ok(value)
+    }
+}
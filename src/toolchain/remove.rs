@@ -1,9 +1,14 @@
-use std::path::PathBuf;
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
 
 use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+use trash::TrashContext;
 
-use crate::toolchain::ToolchainError;
-use crate::{CheckCancellation, fs};
+use crate::toolchain::{Phase, ToolchainError};
+use crate::{TRASH, cancel::CheckCancellation, fs};
 
 pub enum RemoveProgress {
     Start { total_bytes: u64 },
@@ -11,11 +16,23 @@ pub enum RemoveProgress {
     End,
 }
 
+impl RemoveProgress {
+    /// Returns the phase that this event belongs to, which is always [`Phase::Remove`].
+    pub fn phase(&self) -> Phase {
+        Phase::Remove
+    }
+
+    /// Returns whether this is the last event of the removal.
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Self::End)
+    }
+}
+
 pub async fn remove_dir_progress(
     dir: PathBuf,
     mut progress: impl FnMut(RemoveProgress),
     cancel_token: &CancellationToken,
-) -> Result<(), ToolchainError> {
+) -> Result<u64, ToolchainError> {
     let mut items = vec![];
     let total_bytes = enumerate_dir(dir, &mut items, cancel_token).await?;
     let mut bytes_removed = 0;
@@ -41,7 +58,119 @@ pub async fn remove_dir_progress(
 
     progress(RemoveProgress::End);
 
-    Ok(())
+    Ok(bytes_removed)
+}
+
+/// Moves a directory to the trash, or deletes it permanently if the trash isn't available.
+///
+/// The trash can be unavailable on headless systems such as CI containers, where there's
+/// no desktop session providing it.
+pub(crate) async fn trash_or_remove(
+    path: &Path,
+    cancel_token: &CancellationToken,
+) -> Result<(), ToolchainError> {
+    trash_or_remove_with(&*TRASH, path, cancel_token).await
+}
+
+/// Somewhere that directories can be moved to instead of being deleted outright.
+trait Trash {
+    fn delete(&self, path: &Path) -> Result<(), trash::Error>;
+}
+
+impl Trash for TrashContext {
+    fn delete(&self, path: &Path) -> Result<(), trash::Error> {
+        TrashContext::delete(self, path)
+    }
+}
+
+/// Implements [`trash_or_remove`] with the given trash.
+async fn trash_or_remove_with(
+    trash: &impl Trash,
+    path: &Path,
+    cancel_token: &CancellationToken,
+) -> Result<(), ToolchainError> {
+    let Err(trash_error) = trash.delete(path) else {
+        return Ok(());
+    };
+
+    warn!(
+        ?path,
+        ?trash_error,
+        "Failed to move directory to the trash, deleting it permanently instead"
+    );
+
+    match remove_dir_progress(path.to_owned(), |_| {}, cancel_token).await {
+        Ok(_) => Ok(()),
+        Err(ToolchainError::Cancelled) => Err(ToolchainError::Cancelled),
+        Err(source) => Err(ToolchainError::RemoveFailed {
+            path: path.to_owned(),
+            trash_error,
+            source: Box::new(source),
+        }),
+    }
+}
+
+/// Clears the way for a toolchain to be extracted to `path`, returning whether anything was
+/// there.
+///
+/// A directory is moved to the trash (or deleted). A regular file or symlink is deleted on its
+/// own, without following the link, so that whatever a stray symlink points to is left alone.
+/// Anything else, such as a device node, is refused.
+pub(crate) async fn clear_destination(
+    path: &Path,
+    cancel_token: &CancellationToken,
+) -> Result<bool, ToolchainError> {
+    let meta = match fs::symlink_metadata(path).await {
+        Ok(meta) => meta,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e.into()),
+    };
+
+    let file_type = meta.file_type();
+    if file_type.is_symlink() {
+        debug!(
+            ?path,
+            "Removing symlink at the destination without following it"
+        );
+        // Windows has separate kinds of symlinks for files and directories.
+        if cfg!(windows) && fs::metadata(path).await.is_ok_and(|target| target.is_dir()) {
+            fs::remove_dir(path).await?;
+        } else {
+            fs::remove_file(path).await?;
+        }
+    } else if file_type.is_file() {
+        debug!(?path, "Removing file at the destination");
+        fs::remove_file(path).await?;
+    } else if file_type.is_dir() {
+        trash_or_remove(path, cancel_token).await?;
+    } else {
+        return Err(ToolchainError::UnsupportedDestination {
+            path: path.to_owned(),
+            kind: special_file_kind(&meta),
+        });
+    }
+
+    Ok(true)
+}
+
+/// Describes a file that isn't a directory, regular file, or symlink.
+#[cfg_attr(not(unix), allow(unused_variables))]
+fn special_file_kind(meta: &std::fs::Metadata) -> &'static str {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+
+        let file_type = meta.file_type();
+        if file_type.is_block_device() || file_type.is_char_device() {
+            return "device";
+        } else if file_type.is_fifo() {
+            return "named pipe";
+        } else if file_type.is_socket() {
+            return "socket";
+        }
+    }
+
+    "special file"
 }
 
 async fn enumerate_dir(
@@ -97,3 +226,72 @@ struct Item {
     dir: bool,
     size: u64,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A trash that's never available, like on a system without a desktop session.
+    struct FailingTrash;
+
+    impl Trash for FailingTrash {
+        fn delete(&self, _path: &Path) -> Result<(), trash::Error> {
+            Err(trash::Error::Unknown {
+                description: "The name org.freedesktop.Trash was not provided".to_string(),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn deletes_permanently_when_the_trash_fails() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().join("21.1.0");
+        std::fs::create_dir_all(dir.join("bin")).unwrap();
+        std::fs::write(dir.join("bin/clang"), "clang").unwrap();
+
+        trash_or_remove_with(&FailingTrash, &dir, &CancellationToken::new())
+            .await
+            .unwrap();
+
+        assert!(!dir.exists());
+        assert!(temp.path().exists());
+    }
+
+    #[tokio::test]
+    async fn reports_the_path_when_both_fail() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().join("missing");
+
+        let error = trash_or_remove_with(&FailingTrash, &dir, &CancellationToken::new())
+            .await
+            .unwrap_err();
+
+        let ToolchainError::RemoveFailed {
+            path,
+            trash_error,
+            source,
+        } = error
+        else {
+            panic!("expected RemoveFailed, got {error:?}");
+        };
+        assert_eq!(path, dir);
+        assert!(matches!(trash_error, trash::Error::Unknown { .. }));
+        assert!(matches!(*source, ToolchainError::Io(_)), "{source:?}");
+    }
+
+    #[tokio::test]
+    async fn cancelling_the_fallback_is_not_a_failure() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().join("21.1.0");
+        std::fs::create_dir_all(dir.join("bin")).unwrap();
+        let cancel_token = CancellationToken::new();
+        cancel_token.cancel();
+
+        let error = trash_or_remove_with(&FailingTrash, &dir, &cancel_token)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(error, ToolchainError::Cancelled), "{error:?}");
+        assert!(dir.exists());
+    }
+}
@@ -0,0 +1,367 @@
+//! Summaries of completed operations, for callers that want to report on them.
+
+use std::{
+    fmt::{self, Display},
+    path::PathBuf,
+    time::Duration,
+};
+
+use miette::Diagnostic;
+use reqwest::Url;
+use serde::{Serialize, Serializer};
+
+use crate::toolchain::{Phase, ToolchainVersion};
+
+/// A summary of a successful call to [`ToolchainClient::download_and_install`](super::ToolchainClient::download_and_install).
+#[derive(Debug, Clone, Serialize)]
+pub struct InstallOutcome {
+    /// The version that was installed.
+    pub version: ToolchainVersion,
+    /// The directory the toolchain was extracted to.
+    pub path: PathBuf,
+    /// The size of the downloaded archive in bytes.
+    pub archive_size: u64,
+    /// How long the download, verification, and extraction took.
+    #[serde(rename = "duration_secs", serialize_with = "serialize_secs")]
+    pub duration: Duration,
+    /// Whether the toolchain was activated, as decided by the client's
+    /// [`ActivationPolicy`](super::ActivationPolicy).
+    pub activated: bool,
+    /// Why the toolchain wasn't activated even though the policy called for it, if writing
+    /// the active toolchain file failed.
+    ///
+    /// The toolchain is installed and usable either way, so this doesn't fail the
+    /// installation. Activation can be retried with
+    /// [`set_active_toolchain`](super::ToolchainClient::set_active_toolchain).
+    pub activation_error: Option<ErrorReport>,
+}
+
+/// How far an installation got before it was cancelled, as carried by
+/// [`ToolchainError::InstallCancelled`](super::ToolchainError::InstallCancelled).
+///
+/// This is read from the filesystem after the installation stopped, so it describes what's
+/// actually left behind.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CancelledState {
+    /// The version that was being installed.
+    pub version: ToolchainVersion,
+    /// The phase that was running when the installation was cancelled.
+    pub phase: Phase,
+    /// The archive that was kept in the download cache, or `None` if nothing was downloaded.
+    ///
+    /// Installing the same asset again resumes the download from it, or skips the download
+    /// entirely if it's complete.
+    pub archive: Option<KeptArchive>,
+}
+
+/// A downloaded archive that was kept after an installation was cancelled.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KeptArchive {
+    /// Where the archive is in the download cache.
+    pub path: PathBuf,
+    /// The number of bytes of the archive that were downloaded.
+    pub downloaded_bytes: u64,
+    /// The size of the whole archive in bytes, or zero if it's unknown.
+    pub total_bytes: u64,
+}
+
+impl KeptArchive {
+    /// Returns whether the whole archive was downloaded.
+    pub fn is_complete(&self) -> bool {
+        self.total_bytes != 0 && self.downloaded_bytes >= self.total_bytes
+    }
+
+    /// Returns the percentage of the archive that was downloaded, or `None` if its size is
+    /// unknown.
+    pub fn percent(&self) -> Option<u64> {
+        (self.total_bytes != 0).then(|| (self.downloaded_bytes * 100 / self.total_bytes).min(100))
+    }
+}
+
+/// What installing a toolchain would do, as returned by
+/// [`ToolchainClient::plan_install`](super::ToolchainClient::plan_install).
+#[derive(Debug, Clone, Serialize)]
+pub struct InstallPlan {
+    /// The version that would be installed.
+    pub version: ToolchainVersion,
+    /// The name of the release asset that would be downloaded.
+    pub asset_name: String,
+    /// The size of the asset in bytes, or zero if it's unknown.
+    pub download_size: u64,
+    /// The number of bytes of the asset that are already in the download cache. The download
+    /// resumes from here if the cached file is a prefix of the asset.
+    pub cached_bytes: u64,
+    /// The directory the toolchain would be extracted to.
+    pub destination: PathBuf,
+    /// Whether the destination already contains files that would be replaced, or is a file
+    /// or symlink itself.
+    pub replaces_existing: bool,
+    /// Whether the toolchain would be activated, as decided by the client's
+    /// [`ActivationPolicy`](super::ActivationPolicy).
+    pub activates: bool,
+    /// The approximate size of the extracted toolchain in bytes, or `None` if the download
+    /// size is unknown.
+    ///
+    /// When the whole archive is already in the download cache, the size is read from it.
+    /// Otherwise, it's a rough estimate based on the download size.
+    pub estimated_install_size: Option<u64>,
+    /// The free space on the volume the toolchain would be extracted to, or `None` if it
+    /// couldn't be determined.
+    pub destination_space: Option<VolumeSpace>,
+    /// The free space on the volume of the download cache, or `None` if it couldn't be
+    /// determined. This is often the same volume as the destination.
+    pub cache_space: Option<VolumeSpace>,
+}
+
+impl InstallPlan {
+    /// Returns how many more bytes the install would need on each volume than it has free,
+    /// or zero if everything fits (or the free space is unknown).
+    ///
+    /// The rest of the download is stored in the cache, and the extracted toolchain at the
+    /// destination. If they're on the same volume, both have to fit in its free space.
+    pub fn space_shortfall(&self) -> u64 {
+        let remaining_download = self.download_size.saturating_sub(self.cached_bytes);
+        let install_size = self.estimated_install_size.unwrap_or(0);
+
+        match (&self.destination_space, &self.cache_space) {
+            (Some(destination), Some(cache)) if destination.mount_point == cache.mount_point => {
+                (remaining_download + install_size).saturating_sub(destination.available)
+            }
+            (destination, cache) => {
+                let destination_shortfall = destination
+                    .as_ref()
+                    .map_or(0, |space| install_size.saturating_sub(space.available));
+                let cache_shortfall = cache.as_ref().map_or(0, |space| {
+                    remaining_download.saturating_sub(space.available)
+                });
+                destination_shortfall + cache_shortfall
+            }
+        }
+    }
+}
+
+/// The free space on a volume.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VolumeSpace {
+    /// Where the volume is mounted, such as `/` or `C:\`.
+    pub mount_point: PathBuf,
+    /// The number of bytes that can be written to the volume by the current user.
+    pub available: u64,
+}
+
+/// A summary of a successful call to [`ToolchainClient::remove`](super::ToolchainClient::remove).
+#[derive(Debug, Clone, Serialize)]
+pub struct RemoveReport {
+    /// The version that was removed.
+    pub version: ToolchainVersion,
+    /// The directory that was deleted, or `None` if the toolchain wasn't installed.
+    pub path: Option<PathBuf>,
+    /// The number of bytes of files that were deleted.
+    pub bytes_removed: u64,
+    /// Whether the toolchain was unset because it was the active toolchain.
+    pub deactivated: bool,
+    /// The install reasons that were recorded for the toolchain, which were forgotten along
+    /// with it.
+    pub reasons: Vec<String>,
+}
+
+/// Where a release asset is downloaded from, as returned by
+/// [`ToolchainClient::asset_download_info`](super::ToolchainClient::asset_download_info).
+#[derive(Debug, Clone, Serialize)]
+pub struct AssetDownloadInfo {
+    /// The URL the asset is downloaded from.
+    ///
+    /// When downloading through the GitHub API, this must be requested with an
+    /// `Accept: application/octet-stream` header.
+    pub url: Url,
+    /// The size of the asset in bytes, as reported by GitHub. This is zero if the size
+    /// is unknown.
+    pub size: u64,
+    /// The URL of the file containing the asset's SHA256 checksum.
+    pub checksum_url: Url,
+    /// The expected SHA256 checksum in hex format, if it was fetched.
+    pub expected_sha256: Option<String>,
+}
+
+/// A downloadable asset of a release, as returned by
+/// [`ToolchainClient::release_assets`](super::ToolchainClient::release_assets) and
+/// [`ToolchainClient::release_assets_with_digests`](super::ToolchainClient::release_assets_with_digests).
+#[derive(Debug, Clone, Serialize)]
+pub struct ReleaseAssetInfo {
+    /// The asset's file name.
+    pub name: String,
+    /// The size of the asset in bytes, as reported by GitHub.
+    pub size: u64,
+    /// The URL the asset is downloaded from.
+    pub url: Url,
+    /// The asset's SHA256 digest in hex format, if it was resolved.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Where the digest was found, if it was resolved.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub digest_source: Option<DigestSource>,
+    /// Why the digest couldn't be resolved, if it was requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub digest_error: Option<ErrorReport>,
+}
+
+/// Where the digest in a [`ReleaseAssetInfo`] was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DigestSource {
+    /// The digest that GitHub computed when the asset was uploaded.
+    #[serde(rename = "github")]
+    GitHub,
+    /// The `.sha256` file published alongside the asset.
+    ChecksumFile,
+}
+
+/// A summary of a call to [`ToolchainClient::purge_cache`](super::ToolchainClient::purge_cache).
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PurgeReport {
+    /// The entries that were deleted.
+    pub removed: Vec<PurgedEntry>,
+    /// The entries that couldn't be deleted.
+    pub failed: Vec<PurgeFailure>,
+    /// The total number of bytes deleted.
+    pub bytes_removed: u64,
+}
+
+/// A cache entry that was deleted by [`ToolchainClient::purge_cache`](super::ToolchainClient::purge_cache).
+#[derive(Debug, Clone, Serialize)]
+pub struct PurgedEntry {
+    /// The path of the deleted file.
+    pub path: PathBuf,
+    /// The size of the deleted file in bytes.
+    pub bytes: u64,
+}
+
+/// A cache entry that [`ToolchainClient::purge_cache`](super::ToolchainClient::purge_cache)
+/// couldn't delete.
+#[derive(Debug, Clone, Serialize)]
+pub struct PurgeFailure {
+    /// The path of the file that's still in the cache.
+    pub path: PathBuf,
+    /// Why the file couldn't be deleted.
+    pub error: String,
+}
+
+/// A summary of a call to [`ToolchainClient::purge_all`](super::ToolchainClient::purge_all).
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PurgeAllReport {
+    /// The directories of the toolchains that were deleted.
+    pub toolchains: Vec<PathBuf>,
+    /// The toolchains directory, if it was deleted along with everything else inside it.
+    pub toolchains_dir: Option<PathBuf>,
+    /// The result of purging the download cache.
+    pub cache: PurgeReport,
+    /// The total number of bytes deleted, including the cache.
+    pub bytes_removed: u64,
+}
+
+/// A summary of a call to [`InstalledToolchain::fix_permissions`](super::InstalledToolchain::fix_permissions).
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FixReport {
+    /// The executables that were made executable again.
+    pub fixed: Vec<PathBuf>,
+}
+
+fn serialize_secs<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+/// An owned description of an error, as returned by
+/// [`ToolchainError::to_report`](super::ToolchainError::to_report).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ErrorReport {
+    /// The error's diagnostic code, such as `arm_toolchain::toolchain::cancelled`.
+    pub code: Option<String>,
+    /// The error's message.
+    pub message: String,
+    /// A suggestion for how to fix the error, if there is one.
+    pub help: Option<String>,
+    /// The messages of the errors that caused this one, starting with the most direct cause.
+    pub causes: Vec<String>,
+}
+
+impl ErrorReport {
+    /// Describes any error that implements [`Diagnostic`].
+    pub fn from_diagnostic(error: &dyn Diagnostic) -> Self {
+        let mut causes = vec![];
+        let mut source = error.source();
+        while let Some(cause) = source {
+            causes.push(cause.to_string());
+            source = cause.source();
+        }
+
+        Self {
+            code: error.code().map(|code| code.to_string()),
+            message: error.to_string(),
+            help: error.help().map(|help| help.to_string()),
+            causes,
+        }
+    }
+}
+
+impl Display for ErrorReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ErrorReport {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan(destination: Option<(&str, u64)>, cache: Option<(&str, u64)>) -> InstallPlan {
+        let space = |(mount_point, available): (&str, u64)| VolumeSpace {
+            mount_point: PathBuf::from(mount_point),
+            available,
+        };
+        InstallPlan {
+            version: ToolchainVersion::named("21.1.0"),
+            asset_name: "ATfE-21.1.0-Linux-x86_64.tar.xz".to_string(),
+            download_size: 1_000,
+            cached_bytes: 400,
+            destination: PathBuf::from("/data/toolchains/21.1.0"),
+            replaces_existing: false,
+            activates: false,
+            estimated_install_size: Some(3_000),
+            destination_space: destination.map(space),
+            cache_space: cache.map(space),
+        }
+    }
+
+    #[test]
+    fn shortfall_on_one_volume_counts_the_download_and_install_together() {
+        // 600 bytes left to download and 3000 to extract.
+        assert_eq!(
+            plan(Some(("/", 3_600)), Some(("/", 3_600))).space_shortfall(),
+            0
+        );
+        assert_eq!(
+            plan(Some(("/", 3_000)), Some(("/", 3_000))).space_shortfall(),
+            600
+        );
+    }
+
+    #[test]
+    fn shortfall_on_separate_volumes_counts_each_on_its_own() {
+        assert_eq!(
+            plan(Some(("/data", 3_000)), Some(("/", 600))).space_shortfall(),
+            0
+        );
+        assert_eq!(
+            plan(Some(("/data", 2_500)), Some(("/", 100))).space_shortfall(),
+            500 + 500
+        );
+    }
+
+    #[test]
+    fn unknown_free_space_has_no_shortfall() {
+        assert_eq!(plan(None, None).space_shortfall(), 0);
+        assert_eq!(plan(None, Some(("/", 100))).space_shortfall(), 500);
+    }
+}
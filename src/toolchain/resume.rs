@@ -0,0 +1,67 @@
+//! The ETag saved next to a partial download, so that resuming it never appends data from a
+//! different version of the asset.
+//!
+//! GitHub gives each release asset a strong ETag, which changes if the asset is re-uploaded.
+//! It's saved when a download starts, and sent in an `If-Range` header when the download is
+//! resumed, so the server only sends the rest of the asset if it's still the same object. If
+//! it isn't, the server sends the whole asset instead, and the partial download is discarded.
+//! Downloads from servers that don't send an ETag are resumed based on their size alone.
+
+use std::{
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use reqwest::header::{self, HeaderMap};
+
+use crate::fs;
+
+/// The extension appended to an archive's name to get the name of the file its ETag is
+/// saved in.
+const ETAG_EXTENSION: &str = ".etag";
+
+/// Returns the path of the file that the ETag of the given archive is saved in.
+pub(crate) fn etag_path(archive_path: &Path) -> PathBuf {
+    let mut path = archive_path.as_os_str().to_owned();
+    path.push(ETAG_EXTENSION);
+    PathBuf::from(path)
+}
+
+/// Returns the name of the archive that the given ETag file belongs to, or `None` if the
+/// file isn't an ETag file.
+pub(crate) fn archive_name(etag_name: &str) -> Option<&str> {
+    etag_name.strip_suffix(ETAG_EXTENSION)
+}
+
+/// Returns the strong ETag of a response, or `None` if it doesn't have one.
+///
+/// Weak ETags (starting with `W/`) are ignored, since servers must not use them to evaluate
+/// `If-Range`.
+pub(crate) fn strong_etag(headers: &HeaderMap) -> Option<&str> {
+    let etag = headers.get(header::ETAG)?.to_str().ok()?.trim();
+    (etag.starts_with('"') && etag.len() > 1).then_some(etag)
+}
+
+/// Reads the ETag saved for a partial download, if there is one.
+pub(crate) async fn saved_etag(archive_path: &Path) -> Option<String> {
+    let etag = fs::read_to_string(etag_path(archive_path)).await.ok()?;
+    let etag = etag.trim();
+    (!etag.is_empty()).then(|| etag.to_string())
+}
+
+/// Saves the ETag of the asset being downloaded to `archive_path`, or deletes the saved one
+/// if the server didn't send one.
+pub(crate) async fn save_etag(archive_path: &Path, etag: Option<&str>) -> Result<(), io::Error> {
+    match etag {
+        Some(etag) => fs::write(etag_path(archive_path), etag).await,
+        None => discard_etag(archive_path).await,
+    }
+}
+
+/// Deletes the ETag saved for the given archive, if there is one.
+pub(crate) async fn discard_etag(archive_path: &Path) -> Result<(), io::Error> {
+    match fs::remove_file(etag_path(archive_path)).await {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,223 @@
+//! Sorting the entries of a product's toolchains directory into installed versions.
+//!
+//! Other processes may add or remove toolchains while the directory is being listed, so
+//! entries that can't be inspected are skipped instead of failing the whole scan.
+
+use std::{ffi::OsString, io, path::PathBuf};
+
+use futures::{StreamExt, stream};
+use tracing::debug;
+
+use crate::{
+    fs,
+    toolchain::{ToolchainClient, ToolchainVersion, UnusableInstall, UnusableReason, in_use},
+};
+
+/// How many entries are inspected at once, so that directories with thousands of entries
+/// don't open thousands of files at the same time.
+pub(crate) const MAX_CONCURRENT_ENTRIES: usize = 16;
+
+/// An entry found while listing a toolchains directory.
+pub(crate) trait ScannedEntry {
+    fn file_name(&self) -> OsString;
+
+    fn path(&self) -> PathBuf;
+
+    /// Returns whether the entry is a directory.
+    async fn is_dir(&self) -> io::Result<bool>;
+}
+
+impl ScannedEntry for fs::DirEntry {
+    fn file_name(&self) -> OsString {
+        self.file_name()
+    }
+
+    fn path(&self) -> PathBuf {
+        self.path()
+    }
+
+    async fn is_dir(&self) -> io::Result<bool> {
+        Ok(self.file_type().await?.is_dir())
+    }
+}
+
+/// Returns the toolchains among the listed entries, and the directories that can't be used as
+/// toolchains because of their names, in no particular order.
+///
+/// Entries that couldn't be listed or inspected are skipped.
+pub(crate) async fn classify_entries<E: ScannedEntry>(
+    entries: Vec<io::Result<E>>,
+) -> Vec<Result<ToolchainVersion, UnusableInstall>> {
+    let entries = entries.into_iter().filter_map(|entry| {
+        entry
+            .inspect_err(|error| {
+                debug!(?error, "Skipping unreadable entry in toolchains directory");
+            })
+            .ok()
+    });
+
+    stream::iter(entries)
+        .map(async |entry| {
+            let name = entry.file_name();
+            // A directory with the active toolchain file's name isn't a toolchain, even
+            // though it makes the active toolchain file invalid.
+            if name == ToolchainClient::CURRENT_TOOLCHAIN_FILENAME {
+                return None;
+            }
+            // Nor is a toolchain that's renamed while it's checked for open files.
+            if in_use::is_probe_name(&name) {
+                return None;
+            }
+
+            match entry.is_dir().await {
+                Ok(true) => Some(match UnusableReason::of(&name) {
+                    // A lossy name would never match the real path, so it can't be used.
+                    Some(reason) => Err(UnusableInstall {
+                        path: entry.path(),
+                        reason,
+                    }),
+                    None => Ok(ToolchainVersion::named(name.to_string_lossy())),
+                }),
+                Ok(false) => None,
+                Err(error) => {
+                    debug!(
+                        ?name,
+                        ?error,
+                        "Skipping toolchain directory that can't be read"
+                    );
+                    None
+                }
+            }
+        })
+        .buffer_unordered(MAX_CONCURRENT_ENTRIES)
+        .filter_map(async |version| version)
+        .collect()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::ErrorKind,
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use super::*;
+
+    /// An entry that another process may remove between it being listed and inspected.
+    struct RacingEntry<'a> {
+        name: &'static str,
+        is_dir: bool,
+        removed: bool,
+        in_flight: &'a AtomicUsize,
+        max_in_flight: &'a AtomicUsize,
+    }
+
+    impl<'a> RacingEntry<'a> {
+        fn new(name: &'static str, counters: &'a (AtomicUsize, AtomicUsize)) -> Self {
+            Self {
+                name,
+                is_dir: true,
+                removed: false,
+                in_flight: &counters.0,
+                max_in_flight: &counters.1,
+            }
+        }
+    }
+
+    impl ScannedEntry for RacingEntry<'_> {
+        fn file_name(&self) -> OsString {
+            self.name.into()
+        }
+
+        fn path(&self) -> PathBuf {
+            PathBuf::from("toolchains").join(self.name)
+        }
+
+        async fn is_dir(&self) -> io::Result<bool> {
+            let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(1)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+            if self.removed {
+                return Err(ErrorKind::NotFound.into());
+            }
+            Ok(self.is_dir)
+        }
+    }
+
+    fn names(installs: &[Result<ToolchainVersion, UnusableInstall>]) -> Vec<String> {
+        let mut names: Vec<_> = installs
+            .iter()
+            .map(|install| match install {
+                Ok(version) => version.name.clone(),
+                Err(unusable) => format!("unusable {}", unusable.path.display()),
+            })
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[tokio::test]
+    async fn skips_entries_that_disappear() {
+        let counters = Default::default();
+        let entries = vec![
+            Ok(RacingEntry::new("20.1.0", &counters)),
+            Ok(RacingEntry {
+                removed: true,
+                ..RacingEntry::new("21.1.0", &counters)
+            }),
+            Err(io::Error::from(ErrorKind::NotFound)),
+            Ok(RacingEntry::new("21.1.1", &counters)),
+        ];
+
+        let installs = classify_entries(entries).await;
+
+        assert_eq!(names(&installs), ["20.1.0", "21.1.1"]);
+    }
+
+    #[tokio::test]
+    async fn skips_files_and_reserved_entries() {
+        let counters = Default::default();
+        let entries = vec![
+            Ok(RacingEntry::new("20.1.0", &counters)),
+            Ok(RacingEntry {
+                is_dir: false,
+                ..RacingEntry::new("notes.txt", &counters)
+            }),
+            Ok(RacingEntry::new(
+                ToolchainClient::CURRENT_TOOLCHAIN_FILENAME,
+                &counters,
+            )),
+            Ok(RacingEntry::new("21.1.0.in-use-check", &counters)),
+            Ok(RacingEntry::new("aux", &counters)),
+        ];
+
+        let installs = classify_entries(entries).await;
+
+        assert_eq!(
+            names(&installs),
+            [
+                "20.1.0",
+                &format!("unusable {}", PathBuf::from("toolchains/aux").display())
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn bounds_how_many_entries_are_inspected_at_once() {
+        const NAMES: [&str; 4] = ["a", "b", "c", "d"];
+        let counters = Default::default();
+        let entries = (0..1000)
+            .map(|i| Ok(RacingEntry::new(NAMES[i % NAMES.len()], &counters)))
+            .collect();
+
+        let installs = classify_entries(entries).await;
+
+        assert_eq!(installs.len(), 1000);
+        assert_eq!(counters.0.load(Ordering::SeqCst), 0);
+        assert_eq!(counters.1.load(Ordering::SeqCst), MAX_CONCURRENT_ENTRIES);
+    }
+}
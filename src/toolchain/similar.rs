@@ -0,0 +1,173 @@
+//! Detection of toolchain directories whose names only differ from a version by case or
+//! surrounding whitespace.
+//!
+//! On case-insensitive filesystems (the default on macOS and Windows), `20.1.0-rc1` and
+//! `20.1.0-RC1` are the same directory, and a directory restored from a backup may have picked
+//! up a trailing space. Treating these as unrelated versions leads to near-duplicate toolchains,
+//! or one toolchain being extracted over another.
+
+use std::path::{Path, PathBuf};
+
+/// Returns the name that directories of the given version are compared by.
+///
+/// Surrounding whitespace is always ignored, and case is ignored if the filesystem is
+/// case-insensitive.
+pub(crate) fn normalize_version_name(name: &str, case_insensitive: bool) -> String {
+    let name = name.trim();
+    if case_insensitive {
+        name.to_lowercase()
+    } else {
+        name.to_string()
+    }
+}
+
+/// Returns whether the filesystem containing `dir` ignores the case of file names.
+///
+/// This is detected by checking whether the directory can also be found with the case of
+/// its name swapped, so it must exist and have a name containing letters. Otherwise, the
+/// filesystem is assumed to be case-sensitive.
+pub(crate) fn is_case_insensitive(dir: &Path) -> bool {
+    let Some(name) = dir.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+
+    let swapped: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_lowercase() {
+                c.to_ascii_uppercase()
+            } else {
+                c.to_ascii_lowercase()
+            }
+        })
+        .collect();
+
+    swapped != name && dir.with_file_name(swapped).exists()
+}
+
+/// Returns the directories in `dir` whose names are equivalent to `name` but not identical
+/// to it, such as `20.1.0 ` (with a trailing space) when looking for `20.1.0`.
+pub(crate) fn similar_dirs(dir: &Path, name: &str) -> Vec<PathBuf> {
+    let Ok(read_dir) = std::fs::read_dir(dir) else {
+        return vec![];
+    };
+
+    let case_insensitive = is_case_insensitive(dir);
+    let normalized = normalize_version_name(name, case_insensitive);
+
+    let mut similar: Vec<PathBuf> = read_dir
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|ty| ty.is_dir()))
+        .filter(|entry| {
+            let entry_name = entry.file_name();
+            let entry_name = entry_name.to_string_lossy();
+            entry_name != name
+                && normalize_version_name(&entry_name, case_insensitive) == normalized
+        })
+        .map(|entry| entry.path())
+        .collect();
+
+    similar.sort();
+    similar
+}
+
+/// Groups the directories in `dir` whose names are equivalent to each other, or that have
+/// surrounding whitespace.
+///
+/// Each group contains at least one directory that doesn't exactly match its version.
+pub(crate) fn ambiguous_dirs(dir: &Path, names: &[String]) -> Vec<Vec<PathBuf>> {
+    let case_insensitive = is_case_insensitive(dir);
+
+    let mut groups: Vec<(String, Vec<PathBuf>)> = vec![];
+    for name in names {
+        let normalized = normalize_version_name(name, case_insensitive);
+        match groups.iter_mut().find(|(n, _)| *n == normalized) {
+            Some((_, paths)) => paths.push(dir.join(name)),
+            None => groups.push((normalized, vec![dir.join(name)])),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(_, paths)| paths)
+        .filter(|paths| {
+            paths.len() > 1
+                || paths[0]
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().trim() != name.to_string_lossy())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_whitespace_and_case() {
+        assert_eq!(normalize_version_name(" 20.1.0\t", false), "20.1.0");
+        assert_eq!(normalize_version_name("20.1.0-RC1", false), "20.1.0-RC1");
+        assert_eq!(normalize_version_name("20.1.0-RC1 ", true), "20.1.0-rc1");
+        assert_eq!(
+            normalize_version_name("20.1.0-rc1", true),
+            normalize_version_name("20.1.0-RC1", true)
+        );
+    }
+
+    #[test]
+    fn case_sensitivity_needs_an_existing_name_with_letters() {
+        let temp = tempfile::tempdir().unwrap();
+        let digits = temp.path().join("2025");
+        std::fs::create_dir(&digits).unwrap();
+
+        assert!(!is_case_insensitive(&digits));
+        assert!(!is_case_insensitive(&temp.path().join("missing")));
+    }
+
+    #[test]
+    fn detects_the_filesystem_case_sensitivity() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().join("embedded");
+        std::fs::create_dir(&dir).unwrap();
+
+        let found_with_other_case = temp.path().join("EMBEDDED").exists();
+
+        assert_eq!(is_case_insensitive(&dir), found_with_other_case);
+    }
+
+    #[test]
+    fn finds_directories_with_surrounding_whitespace() {
+        let temp = tempfile::tempdir().unwrap();
+        for name in ["20.1.0", "20.1.0 ", " 20.1.0", "20.1.00", "21.1.0 "] {
+            std::fs::create_dir(temp.path().join(name)).unwrap();
+        }
+        std::fs::write(temp.path().join("20.1.0\t"), "").unwrap();
+
+        assert_eq!(
+            similar_dirs(temp.path(), "20.1.0"),
+            [temp.path().join(" 20.1.0"), temp.path().join("20.1.0 ")]
+        );
+        assert_eq!(
+            similar_dirs(temp.path(), "21.1.0"),
+            [temp.path().join("21.1.0 ")]
+        );
+        assert!(similar_dirs(temp.path(), "19.1.0").is_empty());
+        assert!(similar_dirs(&temp.path().join("missing"), "20.1.0").is_empty());
+    }
+
+    #[test]
+    fn groups_ambiguous_directories() {
+        let temp = tempfile::tempdir().unwrap();
+        let names: Vec<String> = ["19.1.0", "20.1.0", "20.1.0 ", "21.1.0 "]
+            .map(String::from)
+            .into();
+
+        assert_eq!(
+            ambiguous_dirs(temp.path(), &names),
+            [
+                vec![temp.path().join("20.1.0"), temp.path().join("20.1.0 ")],
+                vec![temp.path().join("21.1.0 ")],
+            ]
+        );
+    }
+}
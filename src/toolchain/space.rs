@@ -0,0 +1,115 @@
+//! Free disk space probing, so that installs can report whether a toolchain will fit.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::toolchain::VolumeSpace;
+
+/// Returns the free space on the volume containing `path`, and where that volume is mounted.
+///
+/// The path doesn't have to exist yet. Its closest existing ancestor is probed instead.
+pub(crate) async fn volume_space(path: &Path) -> io::Result<VolumeSpace> {
+    let path = path.to_owned();
+    tokio::task::spawn_blocking(move || {
+        let existing = path
+            .ancestors()
+            .find(|ancestor| ancestor.exists())
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+
+        Ok(VolumeSpace {
+            mount_point: mount_point(existing)?,
+            available: available_space(existing)?,
+        })
+    })
+    .await
+    .unwrap()
+}
+
+/// Returns the number of bytes that the current user can write to the volume containing the
+/// existing path.
+#[cfg(unix)]
+fn available_space(path: &Path) -> io::Result<u64> {
+    use std::{ffi::CString, mem::MaybeUninit, os::unix::ffi::OsStrExt};
+
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+    let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+
+    // SAFETY: `c_path` is a valid C string and `stat` is only read if the call succeeds.
+    let stat = unsafe {
+        if libc::statvfs(c_path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        stat.assume_init()
+    };
+
+    // The field types differ between platforms.
+    #[allow(clippy::unnecessary_cast, clippy::useless_conversion)]
+    Ok(u64::from(stat.f_bavail) * stat.f_frsize as u64)
+}
+
+#[cfg(windows)]
+fn available_space(path: &Path) -> io::Result<u64> {
+    use std::os::windows::ffi::OsStrExt;
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn GetDiskFreeSpaceExW(
+            directory_name: *const u16,
+            free_bytes_available_to_caller: *mut u64,
+            total_number_of_bytes: *mut u64,
+            total_number_of_free_bytes: *mut u64,
+        ) -> i32;
+    }
+
+    let wide_path = path
+        .as_os_str()
+        .encode_wide()
+        .chain(Some(0))
+        .collect::<Vec<_>>();
+    let mut available = 0;
+
+    // SAFETY: `wide_path` is null-terminated, and the other totals are optional.
+    let succeeded = unsafe {
+        GetDiskFreeSpaceExW(
+            wide_path.as_ptr(),
+            &mut available,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    if succeeded == 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(available)
+}
+
+/// Returns the directory the volume containing the existing path is mounted at, which is the
+/// highest ancestor on the same device.
+#[cfg(unix)]
+fn mount_point(path: &Path) -> io::Result<PathBuf> {
+    use std::os::unix::fs::MetadataExt;
+
+    let path = std::fs::canonicalize(path)?;
+    let device = std::fs::metadata(&path)?.dev();
+
+    let mut mount_point = path.as_path();
+    while let Some(parent) = mount_point.parent() {
+        if std::fs::metadata(parent)?.dev() != device {
+            break;
+        }
+        mount_point = parent;
+    }
+
+    Ok(mount_point.to_owned())
+}
+
+/// Returns the root of the drive containing the existing path, such as `C:\`.
+#[cfg(windows)]
+fn mount_point(path: &Path) -> io::Result<PathBuf> {
+    let path = std::fs::canonicalize(path)?;
+    Ok(path.ancestors().last().unwrap_or(&path).to_owned())
+}
@@ -0,0 +1,68 @@
+//! Sanity checks for the contents of an extracted toolchain.
+//!
+//! An archive that was truncated can sometimes still be extracted without errors, so these
+//! checks make sure the important parts of the toolchain are actually present.
+
+use std::{io::ErrorKind, path::Path};
+
+use crate::{
+    fs,
+    toolchain::{InstalledToolchain, Product, ToolchainError},
+};
+
+/// Checks that a toolchain has the files every release of its product contains.
+///
+/// If `min_size` is given, the total size of the toolchain's files must also be at least
+/// that many bytes.
+pub(crate) async fn check_structure(
+    toolchain: &InstalledToolchain,
+    product: Product,
+    min_size: Option<u64>,
+) -> Result<(), ToolchainError> {
+    let mut problems = vec![];
+
+    let clang_name = if cfg!(windows) { "clang.exe" } else { "clang" };
+    let bin_dir = toolchain.host_bin_dir();
+    if !bin_dir.is_dir() {
+        problems.push("the `bin` directory is missing".to_string());
+    } else if !bin_dir.join(clang_name).exists() {
+        problems.push(format!("`bin/{clang_name}` is missing"));
+    }
+
+    if product.has_multilib {
+        let multilib_dir = toolchain.multilib_dir();
+        match is_empty_dir(&multilib_dir).await? {
+            None => problems.push("the `lib/clang-runtimes` directory is missing".to_string()),
+            Some(true) => problems.push("the `lib/clang-runtimes` directory is empty".to_string()),
+            Some(false) => {}
+        }
+    }
+
+    if let Some(min_size) = min_size {
+        // This also caches the size in the toolchain's manifest.
+        let size = toolchain.size(true).await?.bytes;
+        if size < min_size {
+            problems.push(format!(
+                "only {size} bytes were extracted, but at least {min_size} were expected"
+            ));
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(ToolchainError::IncompleteExtraction {
+            path: toolchain.path.clone(),
+            details: problems,
+        })
+    }
+}
+
+/// Returns whether a directory is empty, or `None` if it doesn't exist.
+async fn is_empty_dir(path: &Path) -> Result<Option<bool>, ToolchainError> {
+    match fs::read_dir(path).await {
+        Ok(mut read_dir) => Ok(Some(read_dir.next_entry().await?.is_none())),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
@@ -0,0 +1,151 @@
+//! Whether installing a toolchain activates it is decided by the client's activation policy,
+//! and the plan reports the same decision that the install makes. A reinstall only activates
+//! the toolchain once the old install has been replaced.
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use arm_toolchain::toolchain::{ActivationPolicy, InstallState, ToolchainClient, ToolchainVersion};
+use common::{MockGitHub, MockRelease, MockServer};
+use tokio_util::sync::CancellationToken;
+
+#[tokio::test]
+async fn each_policy_with_and_without_an_active_toolchain() {
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let installed = ToolchainVersion::named("21.1.0");
+    let previous = ToolchainVersion::named("20.1.0");
+
+    for (policy, active, activates) in [
+        (ActivationPolicy::Never, None, false),
+        (ActivationPolicy::Never, Some(&previous), false),
+        (ActivationPolicy::IfNoneActive, None, true),
+        (ActivationPolicy::IfNoneActive, Some(&previous), false),
+        (ActivationPolicy::Always, None, true),
+        (ActivationPolicy::Always, Some(&previous), true),
+    ] {
+        let dir = tempfile::tempdir().unwrap();
+        let client = common::client_builder(dir.path(), &server)
+            .activation_policy(policy)
+            .build()
+            .await
+            .unwrap();
+        client.set_active_toolchain(active.cloned()).await.unwrap();
+        let release = client.get_release(&installed).await.unwrap();
+        let asset = common::host_asset(&client, &release);
+
+        let plan = client.plan_install(&release, asset).await.unwrap();
+        let outcome = client
+            .download_and_install(&release, asset, Arc::new(|_| {}), CancellationToken::new())
+            .await
+            .unwrap();
+
+        let case = format!("{policy:?} with {active:?} active");
+        assert_eq!(plan.activates, activates, "{case}");
+        assert_eq!(outcome.activated, activates, "{case}");
+        let expected_active = if activates { Some(&installed) } else { active };
+        assert_eq!(
+            client.active_toolchain().as_ref(),
+            expected_active,
+            "{case}"
+        );
+        assert!(client.version_is_installed(&installed), "{case}");
+    }
+}
+
+#[tokio::test]
+async fn install_succeeds_when_activating_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let client = common::client(dir.path(), &server).await;
+    // The active toolchain file can't be written where a directory is in the way.
+    std::fs::create_dir_all(
+        dir.path()
+            .join("toolchains/embedded")
+            .join(ToolchainClient::CURRENT_TOOLCHAIN_FILENAME),
+    )
+    .unwrap();
+    let installed = ToolchainVersion::named("21.1.0");
+    let release = client.get_release(&installed).await.unwrap();
+    let asset = common::host_asset(&client, &release);
+
+    let outcome = client
+        .download_and_install(&release, asset, Arc::new(|_| {}), CancellationToken::new())
+        .await
+        .unwrap();
+
+    assert!(!outcome.activated);
+    let error = outcome.activation_error.unwrap();
+    assert_eq!(
+        error.code.as_deref(),
+        Some("arm_toolchain::toolchain::invalid_active_toolchain_file")
+    );
+    assert_eq!(client.active_toolchain(), None);
+    assert!(client.version_is_installed(&installed));
+    assert!(outcome.path.join("bin/clang").is_file());
+}
+
+#[tokio::test]
+async fn reinstalling_removes_the_old_install_before_activating() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let client = common::client_builder(dir.path(), &server)
+        .activation_policy(ActivationPolicy::Always)
+        .build()
+        .await
+        .unwrap();
+    let installed = ToolchainVersion::named("21.1.0");
+    let release = client.get_release(&installed).await.unwrap();
+    let asset = common::host_asset(&client, &release);
+    client
+        .download_and_install(&release, asset, Arc::new(|_| {}), CancellationToken::new())
+        .await
+        .unwrap();
+    client.set_active_toolchain(None).await.unwrap();
+
+    let events = Arc::new(Mutex::new(vec![]));
+    let outcome = client
+        .download_and_install(
+            &release,
+            asset,
+            Arc::new({
+                let events = events.clone();
+                move |state| events.lock().unwrap().push(state)
+            }),
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+    assert!(outcome.activated);
+    let events = events.lock().unwrap();
+    let steps: Vec<_> = events
+        .iter()
+        .filter_map(|state| match state {
+            InstallState::ExtractBegin { .. } => Some("extract"),
+            InstallState::RemoveOldInstallBegin => Some("remove old install"),
+            InstallState::RemoveOldInstallFinish => Some("removed old install"),
+            InstallState::ExtractDone => Some("extracted"),
+            InstallState::Activated { version } => {
+                assert_eq!(version, &installed);
+                Some("activated")
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        steps,
+        [
+            "extract",
+            "remove old install",
+            "removed old install",
+            "extracted",
+            "activated"
+        ]
+    );
+    assert!(
+        matches!(events.last(), Some(InstallState::Activated { .. })),
+        "{events:#?}"
+    );
+    assert_eq!(client.active_toolchain().as_ref(), Some(&installed));
+}
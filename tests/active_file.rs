@@ -0,0 +1,114 @@
+//! An active toolchain file that can't be used is ignored when building a client, and
+//! reported with its path and what's wrong with it.
+
+mod common;
+
+use std::{fs, path::PathBuf};
+
+use arm_toolchain::toolchain::{ToolchainClient, ToolchainError, ToolchainVersion};
+use common::{MockGitHub, MockServer};
+
+/// Returns where the active toolchain file of the default product is kept under `dir`.
+fn active_file(dir: &std::path::Path) -> PathBuf {
+    dir.join("toolchains/embedded")
+        .join(ToolchainClient::CURRENT_TOOLCHAIN_FILENAME)
+}
+
+/// Builds a client after `setup` broke its active toolchain file, checks that the client
+/// ignores the file, and returns the reason it's reported as invalid.
+async fn invalid_reason(setup: impl FnOnce(&std::path::Path)) -> String {
+    let dir = tempfile::tempdir().unwrap();
+    let path = active_file(dir.path());
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    setup(&path);
+    let server = MockServer::github(MockGitHub::new([]));
+
+    let client = common::client(dir.path(), &server).await;
+
+    assert_eq!(client.active_toolchain(), None);
+    match client.check_active_toolchain_file().await {
+        Err(ToolchainError::InvalidActiveToolchainFile {
+            path: error_path,
+            reason,
+        }) => {
+            assert_eq!(error_path, path);
+            reason
+        }
+        other => panic!("{other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn directory() {
+    let reason = invalid_reason(|path| fs::create_dir(path).unwrap()).await;
+
+    assert_eq!(reason, "it is a directory");
+}
+
+#[tokio::test]
+async fn not_utf8() {
+    let reason = invalid_reason(|path| fs::write(path, b"21.1.0\xff\xfe").unwrap()).await;
+
+    assert_eq!(reason, "it isn't valid UTF-8 text");
+}
+
+#[tokio::test]
+async fn no_version() {
+    for contents in ["", "  \n", "../../etc/passwd", "21.1.0\n20.1.0\n"] {
+        let reason = invalid_reason(|path| fs::write(path, contents).unwrap()).await;
+
+        assert_eq!(
+            reason, "it doesn't contain a toolchain version",
+            "{contents:?}"
+        );
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn unreadable() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let unreadable = |path: &std::path::Path| {
+        fs::write(path, "21.1.0").unwrap();
+        fs::set_permissions(path, fs::Permissions::from_mode(0o000)).unwrap();
+    };
+    let probe = tempfile::tempdir().unwrap();
+    unreadable(&probe.path().join("probe"));
+    if fs::read(probe.path().join("probe")).is_ok() {
+        // Running as root, which ignores the file's permissions.
+        return;
+    }
+
+    let reason = invalid_reason(unreadable).await;
+
+    assert_eq!(reason, "permission was denied");
+}
+
+#[tokio::test]
+async fn directory_is_not_a_toolchain_and_cannot_be_replaced() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = active_file(dir.path());
+    fs::create_dir_all(&path).unwrap();
+    let server = MockServer::github(MockGitHub::new([]));
+    let client = common::client(dir.path(), &server).await;
+
+    assert!(client.installed_versions().await.unwrap().is_empty());
+    let error = client
+        .set_active_toolchain(Some(ToolchainVersion::named("21.1.0")))
+        .await
+        .unwrap_err();
+
+    let ToolchainError::InvalidActiveToolchainFile {
+        path: error_path, ..
+    } = &error
+    else {
+        panic!("{error:?}");
+    };
+    assert_eq!(*error_path, path);
+    assert_eq!(
+        error.stable_code(),
+        "arm_toolchain::toolchain::invalid_active_toolchain_file"
+    );
+    assert!(error.to_string().contains(path.to_str().unwrap()));
+}
@@ -0,0 +1,163 @@
+//! Applications can register their own archive formats, which are used both to select a
+//! release's asset and to extract it.
+
+mod common;
+
+use std::{
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use arm_toolchain::toolchain::{
+    Archive, ArchiveFormat, HostArch, HostOS, InstallState, ToolchainError, ToolchainVersion,
+};
+use common::{MockGitHub, MockRelease, MockServer, host_asset_name};
+use futures::future::BoxFuture;
+use tokio_util::sync::CancellationToken;
+
+/// A format whose archives list one `path=contents` file per line, and which counts how many
+/// archives it extracted.
+struct Listing {
+    extension: &'static str,
+    extracted: Arc<AtomicUsize>,
+}
+
+impl ArchiveFormat for Listing {
+    fn name(&self) -> &str {
+        "listing"
+    }
+
+    fn matches(&self, file_name: &str) -> bool {
+        file_name.ends_with(self.extension)
+    }
+
+    fn extract<'a>(
+        &'a self,
+        archive: Archive,
+        destination: PathBuf,
+        _progress: Arc<dyn Fn(InstallState) + Send + Sync>,
+        _cancel_token: CancellationToken,
+    ) -> BoxFuture<'a, Result<Option<String>, ToolchainError>> {
+        Box::pin(async move {
+            self.extracted.fetch_add(1, Ordering::SeqCst);
+            let listing = std::fs::read_to_string(&archive.path)?;
+            for line in listing.lines() {
+                let (path, contents) = line.split_once('=').unwrap();
+                let path = destination.join(path);
+                std::fs::create_dir_all(path.parent().unwrap())?;
+                std::fs::write(path, contents)?;
+            }
+            Ok(None)
+        })
+    }
+}
+
+fn listing(extension: &'static str) -> (Box<Listing>, Arc<AtomicUsize>) {
+    let extracted = Arc::new(AtomicUsize::new(0));
+    let format = Listing {
+        extension,
+        extracted: extracted.clone(),
+    };
+    (Box::new(format), extracted)
+}
+
+fn listing_asset_name() -> String {
+    let asset_name = host_asset_name("21.1.0");
+    format!("{}.lst", asset_name.strip_suffix(".tar.xz").unwrap())
+}
+
+/// A toolchain in the listing format, with everything that's checked after it's extracted.
+const LISTING: &str =
+    "bin/clang=#!/bin/sh\nlib/clang-runtimes/multilib.yaml=MultilibVersion: 1.0\n";
+
+/// A release whose only toolchain is in the listing format.
+fn listing_release() -> MockRelease {
+    MockRelease::empty("21.1.0").asset_with_checksum(&listing_asset_name(), LISTING.into())
+}
+
+#[tokio::test]
+async fn registered_format_is_selected_and_extracted() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(MockGitHub::new([listing_release()]));
+    let (format, extracted) = listing(".lst");
+    let client = common::client_builder(dir.path(), &server)
+        .register_archive_format(format)
+        .build()
+        .await
+        .unwrap();
+    let release = client
+        .get_release(&ToolchainVersion::named("21.1.0"))
+        .await
+        .unwrap();
+    let asset = common::host_asset(&client, &release);
+    assert_eq!(asset.name, listing_asset_name());
+
+    let outcome = client
+        .download_and_install(&release, asset, Arc::new(|_| {}), CancellationToken::new())
+        .await
+        .unwrap();
+
+    assert_eq!(extracted.load(Ordering::SeqCst), 1);
+    assert_eq!(
+        std::fs::read_to_string(outcome.path.join("bin/clang")).unwrap(),
+        "#!/bin/sh"
+    );
+}
+
+#[tokio::test]
+async fn registered_format_takes_precedence_over_a_builtin_one() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(MockGitHub::new([MockRelease::empty("21.1.0")
+        .asset_with_checksum(&host_asset_name("21.1.0"), LISTING.into())]));
+    let (format, extracted) = listing(".tar.xz");
+    let client = common::client_builder(dir.path(), &server)
+        .register_archive_format(format)
+        .build()
+        .await
+        .unwrap();
+    let release = client
+        .get_release(&ToolchainVersion::named("21.1.0"))
+        .await
+        .unwrap();
+
+    let outcome = client
+        .download_and_install(
+            &release,
+            common::host_asset(&client, &release),
+            Arc::new(|_| {}),
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(extracted.load(Ordering::SeqCst), 1);
+    assert_eq!(
+        std::fs::read_to_string(outcome.path.join("bin/clang")).unwrap(),
+        "#!/bin/sh"
+    );
+}
+
+#[tokio::test]
+async fn unregistered_format_is_not_selected() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(MockGitHub::new([listing_release()]));
+    let client = common::client(dir.path(), &server).await;
+    let release = client
+        .get_release(&ToolchainVersion::named("21.1.0"))
+        .await
+        .unwrap();
+
+    let result = release.asset_for_formats(
+        HostOS::try_current().unwrap(),
+        HostArch::try_current().unwrap(),
+        client.archive_formats(),
+    );
+
+    assert!(
+        matches!(result, Err(ToolchainError::ReleaseAssetMissing { .. })),
+        "{result:?}"
+    );
+}
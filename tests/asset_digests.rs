@@ -0,0 +1,127 @@
+//! Listing a release's assets with their digests fetches a few checksum files at a time, and
+//! reports an asset whose digest couldn't be found without failing the rest.
+
+mod common;
+
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+use arm_toolchain::toolchain::{DigestSource, ToolchainVersion};
+use common::{MockGitHub, MockRelease, MockServer, Response};
+
+const VERSION: &str = "21.1.0";
+
+/// A release with the given number of assets, each with a checksum file.
+fn release(assets: usize) -> MockRelease {
+    (0..assets).fold(MockRelease::empty(VERSION), |release, i| {
+        release.asset_with_checksum(&format!("asset-{i}.tar.xz"), common::noise(100 + i))
+    })
+}
+
+#[tokio::test]
+async fn checksum_files_are_fetched_a_few_at_a_time() {
+    let release = release(12);
+    let expected = (0..12)
+        .map(|i| common::sha256_hex(release.data(&format!("asset-{i}.tar.xz"))))
+        .collect::<Vec<_>>();
+    let github = MockGitHub::new([release]);
+    let in_flight = Arc::new(AtomicUsize::new(0));
+    let most_in_flight = Arc::new(AtomicUsize::new(0));
+    let server = MockServer::start({
+        let in_flight = in_flight.clone();
+        let most_in_flight = most_in_flight.clone();
+        move |request| {
+            if !request.file_name().ends_with(".sha256") {
+                return github.respond(request);
+            }
+
+            let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            most_in_flight.fetch_max(now, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(50));
+            in_flight.fetch_sub(1, Ordering::SeqCst);
+            github.respond(request)
+        }
+    });
+    let dir = tempfile::tempdir().unwrap();
+    let client = common::client(dir.path(), &server).await;
+    let release = client
+        .get_release(&ToolchainVersion::named(VERSION))
+        .await
+        .unwrap();
+
+    let assets = client.release_assets_with_digests(&release).await;
+
+    let digests = assets
+        .iter()
+        .map(|asset| asset.sha256.clone().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(digests, expected);
+    assert!(
+        assets
+            .iter()
+            .all(|asset| asset.digest_source == Some(DigestSource::ChecksumFile))
+    );
+    let most_in_flight = most_in_flight.load(Ordering::SeqCst);
+    assert!((2..=4).contains(&most_in_flight), "{most_in_flight}");
+}
+
+#[tokio::test]
+async fn missing_checksum_is_reported_on_its_asset() {
+    let github = MockGitHub::new([release(3)]);
+    let server = MockServer::start(move |request| {
+        if request.file_name() == "asset-1.tar.xz.sha256" {
+            Response::not_found()
+        } else {
+            github.respond(request)
+        }
+    });
+    let dir = tempfile::tempdir().unwrap();
+    let client = common::client(dir.path(), &server).await;
+    let release = client
+        .get_release(&ToolchainVersion::named(VERSION))
+        .await
+        .unwrap();
+
+    let assets = client.release_assets_with_digests(&release).await;
+
+    let names = assets
+        .iter()
+        .map(|asset| asset.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        ["asset-0.tar.xz", "asset-1.tar.xz", "asset-2.tar.xz"]
+    );
+    assert!(assets[0].sha256.is_some() && assets[0].digest_error.is_none());
+    assert!(assets[1].sha256.is_none() && assets[1].digest_source.is_none());
+    assert!(assets[1].digest_error.is_some(), "{:?}", assets[1]);
+    assert!(assets[2].sha256.is_some() && assets[2].digest_error.is_none());
+}
+
+#[tokio::test]
+async fn github_digest_is_used_without_fetching_the_checksum_file() {
+    let mut release = release(2);
+    let digest = common::sha256_hex(release.data("asset-0.tar.xz"));
+    release.assets[0].digest = Some(format!("sha256:{}", digest.to_ascii_uppercase()));
+    let server = MockServer::github(MockGitHub::new([release]));
+    let dir = tempfile::tempdir().unwrap();
+    let client = common::client(dir.path(), &server).await;
+    let release = client
+        .get_release(&ToolchainVersion::named(VERSION))
+        .await
+        .unwrap();
+
+    let assets = client.release_assets_with_digests(&release).await;
+
+    assert_eq!(assets[0].sha256.as_deref(), Some(digest.as_str()));
+    assert_eq!(assets[0].digest_source, Some(DigestSource::GitHub));
+    assert_eq!(assets[1].digest_source, Some(DigestSource::ChecksumFile));
+    assert!(server.requests_for("asset-0.tar.xz.sha256").is_empty());
+    assert_eq!(server.requests_for("asset-1.tar.xz.sha256").len(), 1);
+}
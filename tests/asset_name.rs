@@ -0,0 +1,253 @@
+//! The names of the assets in Arm Toolchain for Embedded releases are parsed into their parts,
+//! whichever of the historical forms they're written in.
+
+use arm_toolchain::toolchain::{AssetName, HostArch, HostOS};
+
+/// An asset's name, and its product, version, OS, architecture and extension if it's a
+/// toolchain.
+type Case = (
+    &'static str,
+    Option<(&'static str, &'static str, HostOS, HostArch, &'static str)>,
+);
+
+const CASES: &[Case] = &[
+    // 21.1.1
+    (
+        "ATfE-21.1.1-Linux-x86_64.tar.xz",
+        Some(("ATfE", "21.1.1", HostOS::Linux, HostArch::X86_64, "tar.xz")),
+    ),
+    (
+        "ATfE-21.1.1-Linux-AArch64.tar.xz",
+        Some((
+            "ATfE",
+            "21.1.1",
+            HostOS::Linux,
+            HostArch::AAarch64,
+            "tar.xz",
+        )),
+    ),
+    (
+        "ATfE-21.1.1-Darwin-universal.dmg",
+        Some(("ATfE", "21.1.1", HostOS::Darwin, HostArch::Universal, "dmg")),
+    ),
+    (
+        "ATfE-21.1.1-Darwin-universal.tar.xz",
+        Some((
+            "ATfE",
+            "21.1.1",
+            HostOS::Darwin,
+            HostArch::Universal,
+            "tar.xz",
+        )),
+    ),
+    (
+        "ATfE-21.1.1-Windows-x86_64.zip",
+        Some(("ATfE", "21.1.1", HostOS::Windows, HostArch::X86_64, "zip")),
+    ),
+    ("ATfE-21.1.1-Windows-x86_64.zip.sha256", None),
+    ("ATfE-21.1.1-Linux-x86_64.tar.xz.sha256", None),
+    ("ATfE-newlib-overlay-21.1.1.tar.xz", None),
+    // 21.1.0
+    (
+        "ATfE-21.1.0-Linux-x86_64.tar.xz",
+        Some(("ATfE", "21.1.0", HostOS::Linux, HostArch::X86_64, "tar.xz")),
+    ),
+    (
+        "ATfE-21.1.0-Windows-x86_64.zip",
+        Some(("ATfE", "21.1.0", HostOS::Windows, HostArch::X86_64, "zip")),
+    ),
+    ("ATfE-newlib-overlay-21.1.0.tar.xz", None),
+    // A release candidate.
+    (
+        "ATfE-21.1.0-rc1-Darwin-universal.dmg",
+        Some((
+            "ATfE",
+            "21.1.0-rc1",
+            HostOS::Darwin,
+            HostArch::Universal,
+            "dmg",
+        )),
+    ),
+    (
+        "ATfE-21.1.0-rc1-Linux-AArch64.tar.xz",
+        Some((
+            "ATfE",
+            "21.1.0-rc1",
+            HostOS::Linux,
+            HostArch::AAarch64,
+            "tar.xz",
+        )),
+    ),
+    // 20.1.0
+    (
+        "ATfE-20.1.0-Linux-x86_64.tar.xz",
+        Some(("ATfE", "20.1.0", HostOS::Linux, HostArch::X86_64, "tar.xz")),
+    ),
+    (
+        "ATfE-20.1.0-Linux-AArch64.tar.xz",
+        Some((
+            "ATfE",
+            "20.1.0",
+            HostOS::Linux,
+            HostArch::AAarch64,
+            "tar.xz",
+        )),
+    ),
+    (
+        "ATfE-20.1.0-Darwin-universal.dmg",
+        Some(("ATfE", "20.1.0", HostOS::Darwin, HostArch::Universal, "dmg")),
+    ),
+    (
+        "ATfE-20.1.0-Windows-x86_64.zip",
+        Some(("ATfE", "20.1.0", HostOS::Windows, HostArch::X86_64, "zip")),
+    ),
+    ("ATfE-20.1.0.tar.xz", None),
+    // With the version after the host.
+    (
+        "ATfE-Linux-x86_64-20.1.0.tar.xz",
+        Some(("ATfE", "20.1.0", HostOS::Linux, HostArch::X86_64, "tar.xz")),
+    ),
+    // From before the toolchain was renamed.
+    (
+        "LLVMEmbeddedToolchainForArm-19.1.5-Linux-x86_64.tar.xz",
+        Some((
+            "LLVMEmbeddedToolchainForArm",
+            "19.1.5",
+            HostOS::Linux,
+            HostArch::X86_64,
+            "tar.xz",
+        )),
+    ),
+    (
+        "LLVMEmbeddedToolchainForArm-19.1.5-Windows-x86_64.zip",
+        Some((
+            "LLVMEmbeddedToolchainForArm",
+            "19.1.5",
+            HostOS::Windows,
+            HostArch::X86_64,
+            "zip",
+        )),
+    ),
+    (
+        "LLVM-ET-Arm-19.1.5-Darwin-universal.dmg",
+        Some((
+            "LLVM-ET-Arm",
+            "19.1.5",
+            HostOS::Darwin,
+            HostArch::Universal,
+            "dmg",
+        )),
+    ),
+    (
+        "LLVMEmbeddedToolchainForArm-18.1.3-Linux-AArch64.tar.xz",
+        Some((
+            "LLVMEmbeddedToolchainForArm",
+            "18.1.3",
+            HostOS::Linux,
+            HostArch::AAarch64,
+            "tar.xz",
+        )),
+    ),
+    // Spelled differently by mirrors.
+    (
+        "ATfE-21.1.1-macOS-arm64.tar.gz",
+        Some((
+            "ATfE",
+            "21.1.1",
+            HostOS::Darwin,
+            HostArch::AAarch64,
+            "tar.gz",
+        )),
+    ),
+    (
+        "ATfE-21.1.1-linux-amd64.tar.zst",
+        Some(("ATfE", "21.1.1", HostOS::Linux, HostArch::X86_64, "tar.zst")),
+    ),
+    // Not toolchains.
+    ("Source code.tar.gz", None),
+    ("ATfE-21.1.1-Linux.tar.xz", None),
+    ("ATfE-21.1.1-Linux-Linux-x86_64.tar.xz", None),
+    ("ATfE-21.1.1-Linux-x86_64", None),
+    ("21.1.1-Linux-x86_64.tar.xz", None),
+    ("release/ATfE-21.1.1-Linux-x86_64.tar.xz", None),
+];
+
+#[test]
+fn parses_release_asset_names() {
+    for &(name, expected) in CASES {
+        let parsed = AssetName::parse(name).map(|asset| {
+            (
+                asset.product,
+                asset.version.name,
+                asset.os,
+                asset.arch,
+                asset.extension,
+            )
+        });
+        let expected = expected.map(|(product, version, os, arch, extension)| {
+            (
+                product.to_string(),
+                version.to_string(),
+                os,
+                arch,
+                extension.to_string(),
+            )
+        });
+
+        assert_eq!(parsed, expected, "{name}");
+    }
+}
+
+#[test]
+fn displays_the_canonical_name() {
+    for (name, canonical) in [
+        (
+            "ATfE-21.1.1-Linux-AArch64.tar.xz",
+            "ATfE-21.1.1-Linux-AArch64.tar.xz",
+        ),
+        (
+            "ATfE-21.1.0-rc1-Darwin-universal.dmg",
+            "ATfE-21.1.0-rc1-Darwin-universal.dmg",
+        ),
+        (
+            "ATfE-Linux-x86_64-20.1.0.tar.xz",
+            "ATfE-20.1.0-Linux-x86_64.tar.xz",
+        ),
+        (
+            "ATfE-21.1.1-macOS-arm64.tar.gz",
+            "ATfE-21.1.1-Darwin-AArch64.tar.gz",
+        ),
+    ] {
+        assert_eq!(AssetName::parse(name).unwrap().to_string(), canonical);
+    }
+}
+
+#[test]
+fn round_trips_through_display() {
+    for &(name, _) in CASES {
+        let Some(asset) = AssetName::parse(name) else {
+            continue;
+        };
+
+        let displayed = asset.to_string();
+
+        assert_eq!(AssetName::parse(&displayed), Some(asset), "{name}");
+    }
+}
+
+#[test]
+fn from_str_reports_the_invalid_name() {
+    let error = "ATfE-newlib-overlay-21.1.1.tar.xz"
+        .parse::<AssetName>()
+        .unwrap_err();
+
+    assert_eq!(
+        error.stable_code(),
+        "arm_toolchain::toolchain::invalid_asset_name"
+    );
+    assert!(
+        error
+            .to_string()
+            .contains("ATfE-newlib-overlay-21.1.1.tar.xz")
+    );
+}
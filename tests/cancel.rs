@@ -0,0 +1,140 @@
+//! The cancellation helpers stop copying within one chunk of the token being cancelled.
+
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use arm_toolchain::{
+    cancel::{self, COPY_CHUNK_SIZE, CancellationToken, CheckCancellation},
+    toolchain::ToolchainError,
+};
+
+/// How long a copy may keep going after it was cancelled. Copying a chunk takes microseconds,
+/// so this only leaves room for a slow machine.
+const LATENCY_BOUND: Duration = Duration::from_secs(1);
+
+/// Cancels the token after a short while, from another task.
+fn cancel_soon(cancel_token: &CancellationToken) -> tokio::task::JoinHandle<Instant> {
+    let cancel_token = cancel_token.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        cancel_token.cancel();
+        Instant::now()
+    })
+}
+
+#[test]
+fn check_cancellation_returns_the_error_once_cancelled() {
+    let cancel_token = CancellationToken::new();
+    assert_eq!(cancel_token.check_cancellation("cancelled"), Ok(()));
+
+    cancel_token.cancel();
+
+    assert_eq!(
+        cancel_token.check_cancellation("cancelled"),
+        Err("cancelled")
+    );
+}
+
+#[tokio::test]
+async fn copy_copies_everything_and_reports_progress() {
+    let data: Vec<u8> = (0..COPY_CHUNK_SIZE * 3 + 100).map(|i| i as u8).collect();
+    let mut reader = &data[..];
+    let mut writer = Vec::new();
+    let mut reported = vec![];
+
+    let copied = cancel::copy(
+        &CancellationToken::new(),
+        &mut reader,
+        &mut writer,
+        |copied| reported.push(copied),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(copied, data.len() as u64);
+    assert_eq!(writer, data);
+    assert!(reported.is_sorted(), "{reported:?}");
+    assert_eq!(reported.last(), Some(&(data.len() as u64)));
+}
+
+#[tokio::test]
+async fn copy_stops_before_the_next_chunk() {
+    let data = vec![0; COPY_CHUNK_SIZE * 4];
+    let mut reader = &data[..];
+    let mut writer = Vec::new();
+    let cancel_token = CancellationToken::new();
+
+    let result = cancel::copy(&cancel_token, &mut reader, &mut writer, |_| {
+        cancel_token.cancel()
+    })
+    .await;
+
+    assert!(
+        matches!(result, Err(ToolchainError::Cancelled)),
+        "{result:?}"
+    );
+    assert!(writer.len() <= COPY_CHUNK_SIZE, "{}", writer.len());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn copy_stops_promptly_when_cancelled_elsewhere() {
+    let cancel_token = CancellationToken::new();
+    let cancelled_at = cancel_soon(&cancel_token);
+
+    let result = cancel::copy(
+        &cancel_token,
+        &mut tokio::io::repeat(0),
+        &mut tokio::io::sink(),
+        |_| {},
+    )
+    .await;
+
+    let stopped_at = Instant::now();
+    assert!(
+        matches!(result, Err(ToolchainError::Cancelled)),
+        "{result:?}"
+    );
+    let latency = stopped_at - cancelled_at.await.unwrap();
+    assert!(
+        latency < LATENCY_BOUND,
+        "stopped {latency:?} after cancelling"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn copy_blocking_stops_promptly_when_cancelled_elsewhere() {
+    let cancel_token = CancellationToken::new();
+    let copied = Arc::new(AtomicU64::new(0));
+    let copy = tokio::task::spawn_blocking({
+        let cancel_token = cancel_token.clone();
+        let copied = copied.clone();
+        move || {
+            let result = cancel::copy_blocking(
+                &cancel_token,
+                &mut std::io::repeat(0),
+                &mut std::io::sink(),
+                |total| copied.store(total, Ordering::Relaxed),
+            );
+            (result, Instant::now())
+        }
+    });
+    let cancelled_at = cancel_soon(&cancel_token);
+
+    let (result, stopped_at) = copy.await.unwrap();
+
+    assert!(
+        matches!(result, Err(ToolchainError::Cancelled)),
+        "{result:?}"
+    );
+    assert!(copied.load(Ordering::Relaxed) > 0);
+    let latency = stopped_at - cancelled_at.await.unwrap();
+    assert!(
+        latency < LATENCY_BOUND,
+        "stopped {latency:?} after cancelling"
+    );
+}
@@ -0,0 +1,111 @@
+//! Cancelling an installation while a zip archive is being extracted stops it between
+//! entries, and leaves nothing behind.
+
+mod common;
+
+use std::{
+    io::Write,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use arm_toolchain::toolchain::{
+    Archive, ArchiveFormats, ExtractLimits, InstallState, Phase, ToolchainError, ToolchainVersion,
+};
+use common::{MockGitHub, MockRelease, MockServer, host_asset_name};
+use tokio_util::sync::CancellationToken;
+
+const ENTRIES: usize = 5000;
+
+/// A zip of a toolchain with thousands of small files in it.
+fn many_entry_zip(root: &str) -> Vec<u8> {
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default();
+    for (path, contents, _) in common::toolchain_files(root) {
+        zip.start_file(path, options).unwrap();
+        zip.write_all(&contents).unwrap();
+    }
+    for index in 0..ENTRIES {
+        zip.start_file(format!("{root}/share/doc/{index}.txt"), options)
+            .unwrap();
+        zip.write_all(format!("file {index}\n").as_bytes()).unwrap();
+    }
+    zip.finish().unwrap().into_inner()
+}
+
+#[tokio::test]
+async fn cancelled_zip_extraction_stops_promptly() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("toolchain.zip");
+    std::fs::write(&path, many_entry_zip("toolchain")).unwrap();
+    let archive = Archive {
+        file: fs_err::tokio::File::open(&path).await.unwrap(),
+        path,
+        limits: ExtractLimits::default(),
+    };
+    let cancel_token = CancellationToken::new();
+    cancel_token.cancel();
+
+    let started = Instant::now();
+    let result = ArchiveFormats::builtin()
+        .find("toolchain.zip")
+        .unwrap()
+        .extract(
+            archive,
+            dir.path().join("out"),
+            Arc::new(|_| {}),
+            cancel_token,
+        )
+        .await;
+
+    assert!(
+        matches!(result, Err(ToolchainError::Cancelled)),
+        "{result:?}"
+    );
+    assert!(started.elapsed() < Duration::from_secs(5));
+    let extracted = common::list_tree(&dir.path().join("out"))
+        .into_iter()
+        .filter(|path| path.ends_with(".txt"))
+        .count();
+    assert!(
+        extracted < ENTRIES,
+        "all {extracted} entries were extracted"
+    );
+}
+
+#[tokio::test]
+async fn cancelled_install_removes_the_partial_toolchain() {
+    let name = host_asset_name("21.1.0").replace(".tar.xz", ".zip");
+    let root = name.trim_end_matches(".zip").to_string();
+    let release = MockRelease::empty("21.1.0").asset_with_checksum(&name, many_entry_zip(&root));
+    let server = MockServer::github(MockGitHub::new([release]));
+    let dir = tempfile::tempdir().unwrap();
+    let client = common::client(dir.path(), &server).await;
+    let release = client
+        .get_release(&ToolchainVersion::named("21.1.0"))
+        .await
+        .unwrap();
+    let asset = common::host_asset(&client, &release);
+    assert_eq!(asset.name, name);
+
+    // Cancels the installation as soon as extraction starts, like Ctrl-C would.
+    let cancel_token = CancellationToken::new();
+    let progress = Arc::new({
+        let cancel_token = cancel_token.clone();
+        move |state| {
+            if let InstallState::ExtractBegin { .. } = state {
+                cancel_token.cancel();
+            }
+        }
+    });
+    let destination = dir.path().join("toolchain");
+    let result = client
+        .install_to(&release, asset, destination.clone(), progress, cancel_token)
+        .await;
+
+    match result {
+        Err(ToolchainError::InstallCancelled(state)) => assert_eq!(state.phase, Phase::Extract),
+        other => panic!("the install wasn't cancelled: {other:?}"),
+    }
+    assert!(!destination.exists());
+}
@@ -0,0 +1,86 @@
+//! Fetching a release's metadata stops as soon as it's cancelled, instead of waiting for
+//! GitHub to respond.
+
+mod common;
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use arm_toolchain::toolchain::{ToolchainError, ToolchainVersion};
+use common::{MockGitHub, MockRelease, MockServer};
+use tokio_util::sync::CancellationToken;
+
+/// How long a fetch may keep going after it was cancelled.
+const LATENCY_BOUND: Duration = Duration::from_secs(2);
+
+/// A server that takes far longer to answer than any test waits for.
+fn slow_server() -> MockServer {
+    let github = MockGitHub::new([MockRelease::toolchain("21.1.0")]);
+    MockServer::start(move |request| {
+        thread::sleep(Duration::from_secs(60));
+        github.respond(request)
+    })
+}
+
+/// Cancels the token after a short while, from another task.
+fn cancel_soon(cancel_token: &CancellationToken) -> tokio::task::JoinHandle<Instant> {
+    let cancel_token = cancel_token.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        cancel_token.cancel();
+        Instant::now()
+    })
+}
+
+#[tokio::test]
+async fn get_release_is_cancelled_while_waiting_for_github() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = slow_server();
+    let client = common::client(dir.path(), &server).await;
+    let cancel_token = CancellationToken::new();
+    let cancelled_at = cancel_soon(&cancel_token);
+
+    let result = client
+        .get_release_with_cancellation(&ToolchainVersion::named("21.1.0"), &cancel_token)
+        .await;
+
+    let returned_at = Instant::now();
+    assert!(matches!(result, Err(ToolchainError::Cancelled)));
+    assert!(returned_at - cancelled_at.await.unwrap() < LATENCY_BOUND);
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn latest_release_is_cancelled_while_waiting_for_github() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = slow_server();
+    let client = common::client(dir.path(), &server).await;
+    let cancel_token = CancellationToken::new();
+    let cancelled_at = cancel_soon(&cancel_token);
+
+    let result = client.latest_release_with_cancellation(&cancel_token).await;
+
+    let returned_at = Instant::now();
+    assert!(matches!(result, Err(ToolchainError::Cancelled)));
+    assert!(returned_at - cancelled_at.await.unwrap() < LATENCY_BOUND);
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn uncancelled_fetch_returns_the_release() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let client = common::client(dir.path(), &server).await;
+
+    let release = client
+        .get_release_with_cancellation(
+            &ToolchainVersion::named("21.1.0"),
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(release.version(), &ToolchainVersion::named("21.1.0"));
+}
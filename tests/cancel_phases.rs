@@ -0,0 +1,118 @@
+//! A cancelled installation reports the phase it was in and how much of the archive was kept,
+//! matching what's actually left in the download cache.
+
+mod common;
+
+use std::{sync::Arc, time::Duration};
+
+use arm_toolchain::toolchain::{
+    CancelledState, InstallState, Phase, ToolchainError, ToolchainVersion,
+};
+use common::{MockGitHub, MockRelease, MockServer, host_asset_name};
+use tokio_util::sync::CancellationToken;
+
+const VERSION: &str = "21.1.0";
+
+/// Starts a server that sends the archive slowly, so that it can be cancelled halfway.
+fn slow_server() -> MockServer {
+    let github = MockGitHub::new([MockRelease::toolchain(VERSION)]);
+    MockServer::start(move |request| {
+        let response = github.respond(request);
+        match request.file_name() == host_asset_name(VERSION) {
+            true => response.throttle(4096, Duration::from_millis(10)),
+            false => response,
+        }
+    })
+}
+
+/// Installs the toolchain, cancelling it as soon as `cancel_at` returns true for a progress
+/// event, and returns the state it was cancelled in along with the size of the cached
+/// archive.
+async fn cancelled_install(
+    cancel_at: impl Fn(&InstallState) -> bool + Send + Sync + 'static,
+) -> (CancelledState, Option<u64>, u64) {
+    let server = slow_server();
+    let dir = tempfile::tempdir().unwrap();
+    let client = common::client(dir.path(), &server).await;
+    let release = client
+        .get_release(&ToolchainVersion::named(VERSION))
+        .await
+        .unwrap();
+    let asset = common::host_asset(&client, &release);
+
+    let cancel_token = CancellationToken::new();
+    let progress = Arc::new({
+        let cancel_token = cancel_token.clone();
+        move |state: InstallState| {
+            if cancel_at(&state) {
+                cancel_token.cancel();
+            }
+        }
+    });
+    let result = client
+        .download_and_install(&release, asset, progress, cancel_token)
+        .await;
+
+    let Err(ToolchainError::InstallCancelled(state)) = result else {
+        panic!("the install wasn't cancelled");
+    };
+    let cached = std::fs::metadata(client.cache_path_for(asset).unwrap())
+        .ok()
+        .map(|metadata| metadata.len());
+    (*state, cached, asset.size as u64)
+}
+
+#[tokio::test]
+async fn cancelled_before_downloading_keeps_nothing() {
+    let (state, cached, _) =
+        cancelled_install(|state| matches!(state, InstallState::DownloadBegin { .. })).await;
+
+    assert_eq!(state.version, ToolchainVersion::named(VERSION));
+    assert_eq!(state.phase, Phase::Download);
+    assert_eq!(state.archive, None);
+    assert_eq!(cached.unwrap_or(0), 0);
+}
+
+#[tokio::test]
+async fn cancelled_download_reports_the_partial_archive() {
+    let (state, cached, size) = cancelled_install(
+        |state| matches!(state, InstallState::Download { bytes_read } if *bytes_read >= 16 * 1024),
+    )
+    .await;
+
+    assert_eq!(state.phase, Phase::Download);
+    let archive = state.archive.expect("the partial download was kept");
+    assert_eq!(Some(archive.downloaded_bytes), cached);
+    assert_eq!(archive.total_bytes, size);
+    assert!(archive.downloaded_bytes > 0 && archive.downloaded_bytes < size);
+    assert!(!archive.is_complete());
+    assert_eq!(
+        archive.percent(),
+        Some(archive.downloaded_bytes * 100 / size)
+    );
+}
+
+#[tokio::test]
+async fn cancelled_verification_reports_the_complete_archive() {
+    let (state, cached, size) =
+        cancelled_install(|state| matches!(state, InstallState::VerifyingBegin { .. })).await;
+
+    assert_eq!(state.phase, Phase::Verify);
+    let archive = state.archive.expect("the download was kept");
+    assert_eq!(archive.downloaded_bytes, size);
+    assert_eq!(cached, Some(size));
+    assert!(archive.is_complete());
+    assert_eq!(archive.percent(), Some(100));
+}
+
+#[tokio::test]
+async fn cancelled_extraction_reports_the_complete_archive() {
+    let (state, cached, size) =
+        cancelled_install(|state| matches!(state, InstallState::ExtractBegin { .. })).await;
+
+    assert_eq!(state.phase, Phase::Extract);
+    let archive = state.archive.expect("the download was kept");
+    assert_eq!(Some(archive.downloaded_bytes), cached);
+    assert!(archive.is_complete());
+    assert_eq!(archive.downloaded_bytes, size);
+}
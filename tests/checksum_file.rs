@@ -0,0 +1,131 @@
+//! Downloads are verified against a local checksums file instead of the release's checksum
+//! assets, and only fall back to the network when that's allowed.
+
+mod common;
+
+use std::{path::Path, sync::Arc};
+
+use arm_toolchain::toolchain::{
+    ChecksumFallback, InstallOutcome, ToolchainError, ToolchainVersion,
+};
+use common::{MockGitHub, MockRelease, MockServer, host_asset_name};
+use tokio_util::sync::CancellationToken;
+
+const VERSION: &str = "21.1.0";
+
+fn server() -> (MockServer, String) {
+    let release = MockRelease::toolchain(VERSION);
+    let hex = common::sha256_hex(release.data(&host_asset_name(VERSION)));
+    (MockServer::github(MockGitHub::new([release])), hex)
+}
+
+/// Installs the release with `checksums` written to the checksum file.
+async fn install(
+    server: &MockServer,
+    dir: &Path,
+    checksums: &str,
+    fallback: ChecksumFallback,
+) -> Result<InstallOutcome, ToolchainError> {
+    let checksum_file = dir.join("approved.sha256");
+    std::fs::write(&checksum_file, checksums).unwrap();
+    let client = common::client_builder(dir, server)
+        .checksum_file(Some(checksum_file))
+        .checksum_fallback(fallback)
+        .build()
+        .await
+        .unwrap();
+    let release = client
+        .get_release(&ToolchainVersion::named(VERSION))
+        .await
+        .unwrap();
+    client
+        .download_and_install(
+            &release,
+            common::host_asset(&client, &release),
+            Arc::new(|_| {}),
+            CancellationToken::new(),
+        )
+        .await
+}
+
+/// Returns whether the release's own checksum file was downloaded.
+fn fetched_remote_checksum(server: &MockServer) -> bool {
+    !server
+        .requests_for(&format!("{}.sha256", host_asset_name(VERSION)))
+        .is_empty()
+}
+
+#[tokio::test]
+async fn uses_the_listed_checksum() {
+    let (server, hex) = server();
+    let dir = tempfile::tempdir().unwrap();
+    let checksums = format!(
+        "{}  ATfE-20.1.0-Linux-x86_64.tar.xz\n{hex}  {}\n",
+        "0".repeat(64),
+        host_asset_name(VERSION)
+    );
+
+    install(&server, dir.path(), &checksums, ChecksumFallback::None)
+        .await
+        .unwrap();
+
+    assert!(!fetched_remote_checksum(&server));
+}
+
+#[tokio::test]
+async fn takes_precedence_over_the_release() {
+    let (server, _) = server();
+    let dir = tempfile::tempdir().unwrap();
+    let approved = "0".repeat(64);
+    let checksums = format!("SHA256 ({}) = {approved}\n", host_asset_name(VERSION));
+
+    let error = install(&server, dir.path(), &checksums, ChecksumFallback::Network)
+        .await
+        .unwrap_err();
+
+    let ToolchainError::ChecksumMismatch { expected, .. } = &error else {
+        panic!("{error:?}");
+    };
+    assert_eq!(*expected, approved);
+    assert!(!fetched_remote_checksum(&server));
+    assert!(
+        !dir.path()
+            .join("toolchains/embedded")
+            .join(VERSION)
+            .exists()
+    );
+}
+
+#[tokio::test]
+async fn fails_when_the_asset_is_not_listed() {
+    let (server, hex) = server();
+    let dir = tempfile::tempdir().unwrap();
+    let checksums = format!("{hex}  ATfE-20.1.0-Linux-x86_64.tar.xz\n");
+
+    let error = install(&server, dir.path(), &checksums, ChecksumFallback::None)
+        .await
+        .unwrap_err();
+
+    let ToolchainError::ChecksumNotListed { file_name, path } = &error else {
+        panic!("{error:?}");
+    };
+    assert_eq!(*file_name, host_asset_name(VERSION));
+    assert_eq!(*path, dir.path().join("approved.sha256"));
+    assert_eq!(
+        error.stable_code(),
+        "arm_toolchain::toolchain::checksum_not_listed"
+    );
+    assert!(!fetched_remote_checksum(&server));
+}
+
+#[tokio::test]
+async fn falls_back_to_the_release_when_allowed() {
+    let (server, _) = server();
+    let dir = tempfile::tempdir().unwrap();
+
+    install(&server, dir.path(), "", ChecksumFallback::Network)
+        .await
+        .unwrap();
+
+    assert!(fetched_remote_checksum(&server));
+}
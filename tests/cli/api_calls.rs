@@ -0,0 +1,67 @@
+use crate::{
+    Cli,
+    common::{MockGitHub, MockRelease, MockServer},
+    stderr,
+};
+
+/// Returns the paths of the GitHub API requests the server received.
+fn api_requests(server: &MockServer) -> Vec<String> {
+    server
+        .requests()
+        .into_iter()
+        .map(|request| request.path)
+        .filter(|path| path.starts_with("/repos/"))
+        .collect()
+}
+
+fn server() -> MockServer {
+    MockServer::github(MockGitHub::new([
+        MockRelease::toolchain("21.1.0"),
+        MockRelease::toolchain("20.1.0"),
+    ]))
+}
+
+#[test]
+fn already_installed_version_makes_no_api_calls() {
+    let cli = Cli::default();
+    let installer = server();
+    let output = cli.run(&installer, &["install", "21.1.0", "--yes"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let server = server();
+    let output = cli.run(&server, &["install", "21.1.0", "--yes"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(api_requests(&server), Vec::<String>::new());
+}
+
+#[test]
+fn new_version_fetches_its_release_once() {
+    let cli = Cli::default();
+    let server = server();
+
+    let output = cli.run(&server, &["install", "21.1.0", "--yes"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        api_requests(&server),
+        ["/repos/arm/arm-toolchain/releases/tags/release-21.1.0-ATfE"]
+    );
+}
+
+#[test]
+fn latest_only_lists_the_releases() {
+    let cli = Cli::default();
+    let server = server();
+
+    let output = cli.run(&server, &["use", "latest", "--yes"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let requests = api_requests(&server);
+    assert_eq!(requests.len(), 1, "{requests:?}");
+    assert!(
+        requests[0].starts_with("/repos/arm/arm-toolchain/releases")
+            && !requests[0].contains("/tags/"),
+        "{requests:?}"
+    );
+}
@@ -0,0 +1,96 @@
+use std::{fs, io::Read};
+
+use arm_toolchain::cli::redact;
+
+use crate::{
+    Cli,
+    common::{MockGitHub, MockServer},
+    stderr,
+};
+
+/// Each line, and what it should look like once redacted.
+const REDACTIONS: &[(&str, &str)] = &[
+    ("GITHUB_TOKEN=ghp_abc123", "GITHUB_TOKEN=[redacted]"),
+    ("token: \"abc123\"", "token: \"[redacted]\""),
+    (
+        "password = 'hunter2' # old",
+        "password = '[redacted]' # old",
+    ),
+    (
+        "Authorization: Bearer abc.def-123",
+        "Authorization: [redacted]",
+    ),
+    (
+        "authorization=token abc123 more words\nnext line",
+        "authorization=[redacted]\nnext line",
+    ),
+    (
+        "headers: {\"authorization\": \"Bearer abc123\", \"accept\": \"*/*\"}\r\nnext line",
+        "headers: {\"authorization\": \"[redacted]\r\nnext line",
+    ),
+    (
+        "cloning with github_pat_11ABC_def in the url",
+        "cloning with [redacted] in the url",
+    ),
+    ("nothing secret here", "nothing secret here"),
+];
+
+#[test]
+fn redacts_secrets() {
+    for (text, expected) in REDACTIONS {
+        assert_eq!(redact(text), *expected, "{text:?}");
+    }
+}
+
+#[test]
+fn bundle_contains_no_secrets() {
+    let cli = Cli::default();
+    let server = MockServer::github(MockGitHub::new([]));
+    let log_file = cli.home().join("arm-toolchain.log");
+    fs::write(
+        &log_file,
+        "DEBUG sending request\n\
+         DEBUG Authorization: Bearer gho_logged abc.def\n\
+         DEBUG authorization=token second-secret trailing\n\
+         DEBUG request finished\n",
+    )
+    .unwrap();
+    let config_dir = cli.home().join(".config/arm-toolchain");
+    fs::create_dir_all(&config_dir).unwrap();
+    fs::write(
+        config_dir.join("config.toml"),
+        format!(
+            "log_file = {log_file:?}\n\
+             [run]\n\
+             env = {{ GITHUB_TOKEN = \"ghp_configured\", API_PASSWORD = \"hunter2\" }}\n"
+        ),
+    )
+    .unwrap();
+    let bundle = cli.home().join("bundle.zip");
+
+    let output = cli.run(&server, &["doctor", "--bundle", bundle.to_str().unwrap()]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let mut zip = zip::ZipArchive::new(fs::File::open(&bundle).unwrap()).unwrap();
+    let mut contents = String::new();
+    for index in 0..zip.len() {
+        zip.by_index(index)
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+    }
+
+    for secret in [
+        "gho_logged",
+        "abc.def",
+        "second-secret",
+        "trailing",
+        "ghp_configured",
+        "hunter2",
+    ] {
+        assert!(!contents.contains(secret), "{secret} in {contents}");
+    }
+    for kept in ["DEBUG request finished", "[run]", "GITHUB_TOKEN = "] {
+        assert!(contents.contains(kept), "{kept} not in {contents}");
+    }
+}
@@ -0,0 +1,244 @@
+use std::{
+    fs,
+    process::Stdio,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    Cli,
+    common::{MockGitHub, MockRelease, MockServer, Response, host_asset_name},
+    stderr, stdout,
+};
+
+/// Waits until `condition` holds, for at most 30 seconds.
+fn wait_for(mut condition: impl FnMut() -> bool) {
+    let started = Instant::now();
+    while !condition() {
+        assert!(
+            started.elapsed() < Duration::from_secs(30),
+            "timed out waiting"
+        );
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn reports_a_download_cut_off_by_the_process_exiting() {
+    let cli = Cli::default();
+    let asset_name = host_asset_name("21.1.0");
+    let release = MockRelease::toolchain("21.1.0");
+    let archive = release.data(&asset_name).to_vec();
+    let github = MockGitHub::new([release]);
+    let server = MockServer::start({
+        let asset_name = asset_name.clone();
+        move |request| {
+            if request.file_name() == asset_name && request.method == "GET" {
+                Response::ok(archive.clone()).stall(1024, Duration::from_secs(60))
+            } else {
+                github.respond(request)
+            }
+        }
+    });
+
+    let mut install = cli
+        .command(&server)
+        .args(["install", "21.1.0", "--yes"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let journal = cli.toolchains_path().join("journal.log");
+    wait_for(|| {
+        fs::read_to_string(&journal).is_ok_and(|journal| journal.contains("\tbegin\tdownload\t"))
+            && !server.requests_for(&asset_name).is_empty()
+    });
+    install.kill().unwrap();
+    install.wait().unwrap();
+
+    let output = cli.run(&server, &["doctor"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let report = stdout(&output);
+    assert!(
+        report.contains("Found 1 interrupted operation(s)"),
+        "{report}"
+    );
+    assert!(
+        report.contains(&format!("download {asset_name} (started")),
+        "{report}"
+    );
+    assert!(
+        report.contains("A partial download was left at"),
+        "{report}"
+    );
+}
+
+#[test]
+fn does_not_report_a_failed_download() {
+    let cli = Cli::default();
+    let asset_name = host_asset_name("21.1.0");
+    let github = MockGitHub::new([MockRelease::toolchain("21.1.0")]);
+    let server = MockServer::start(move |request| {
+        if request.file_name() == asset_name {
+            Response::not_found()
+        } else {
+            github.respond(request)
+        }
+    });
+
+    let install = cli.run(&server, &["install", "21.1.0", "--yes"]);
+    assert!(!install.status.success());
+    let output = cli.run(&server, &["doctor"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let report = stdout(&output);
+    assert!(!report.contains("interrupted"), "{report}");
+}
+
+#[test]
+fn fix_removes_a_partially_extracted_toolchain() {
+    let cli = Cli::default();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let partial = cli.toolchains_path().join("embedded/21.1.0");
+    fs::create_dir_all(partial.join("bin")).unwrap();
+    fs::write(
+        cli.toolchains_path().join("journal.log"),
+        "2026-01-01T00:00:00Z\t1-1-0\tbegin\textract\tembedded/21.1.0\n",
+    )
+    .unwrap();
+
+    let output = cli.run(&server, &["doctor"]);
+    let report = stdout(&output);
+    assert!(
+        report.contains("extract embedded/21.1.0 (started"),
+        "{report}"
+    );
+    assert!(report.contains("may be incomplete"), "{report}");
+
+    let output = cli.run(&server, &["doctor", "--fix"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!partial.exists());
+
+    let output = cli.run(&server, &["doctor"]);
+    let report = stdout(&output);
+    assert!(!report.contains("interrupted"), "{report}");
+}
+
+#[test]
+fn reports_an_incomplete_toolchain() {
+    let cli = Cli::default();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let output = cli.run(&server, &["install", "21.1.0", "--yes"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let runtimes = cli
+        .toolchains_path()
+        .join("embedded/21.1.0/lib/clang-runtimes");
+    fs::remove_dir_all(&runtimes).unwrap();
+    fs::create_dir(&runtimes).unwrap();
+
+    let output = cli.run(&server, &["doctor"]);
+
+    let report = stdout(&output);
+    assert!(
+        report.contains("Found 1 incomplete toolchain(s):"),
+        "{report}"
+    );
+    assert!(
+        report.contains("the `lib/clang-runtimes` directory is empty"),
+        "{report}"
+    );
+    assert!(
+        report.contains("Reinstall it with `install --force v21.1.0`."),
+        "{report}"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn fix_restores_lost_execute_bits() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let cli = Cli::default();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let output = cli.run(&server, &["install", "21.1.0", "--yes"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let lld = cli.toolchains_path().join("embedded/21.1.0/bin/ld.lld");
+    fs::write(&lld, b"\x7fELF\x02\x01\x01\0").unwrap();
+    fs::set_permissions(&lld, fs::Permissions::from_mode(0o644)).unwrap();
+
+    let output = cli.run(&server, &["doctor", "--fix-permissions"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let report = format!("{}{}", stdout(&output), stderr(&output));
+    assert!(
+        report.contains("permissions of 1 file(s) in Arm Toolchain for Embedded v21.1.0"),
+        "{report}"
+    );
+    assert!(report.contains("  • bin/ld.lld"), "{report}");
+    assert_eq!(
+        fs::metadata(&lld).unwrap().permissions().mode() & 0o777,
+        0o755
+    );
+}
+
+#[test]
+fn fix_removes_an_invalid_active_toolchain_file() {
+    let cli = Cli::default();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let output = cli.run(&server, &["install", "21.1.0", "--yes"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let active_file = cli.toolchains_path().join("embedded/current.txt");
+    fs::remove_file(&active_file).unwrap();
+    fs::create_dir(&active_file).unwrap();
+
+    let output = cli.run(&server, &["doctor"]);
+    let report = stdout(&output);
+    assert!(
+        report.contains("Found 1 invalid active toolchain file(s):"),
+        "{report}"
+    );
+    assert!(
+        report.contains(&format!(
+            "The active toolchain file at {} is invalid: it is a directory",
+            active_file.display()
+        )),
+        "{report}"
+    );
+
+    let output = cli.run(&server, &["doctor", "--fix"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!active_file.exists());
+
+    let output = cli.run(&server, &["use", "21.1.0"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(fs::read_to_string(&active_file).unwrap(), "21.1.0");
+}
+
+#[test]
+fn reports_only_a_different_major_version_in_the_archive() {
+    let server = MockServer::github(MockGitHub::new([
+        MockRelease::toolchain_containing("21.1.0", "21.1.0-rc2"),
+        MockRelease::toolchain_containing("20.1.0", "19.1.5"),
+    ]));
+    let cli = Cli::default();
+    for version in ["21.1.0", "20.1.0"] {
+        let output = cli.run(&server, &["install", version, "--yes"]);
+        assert!(output.status.success(), "{}", stderr(&output));
+    }
+
+    let output = cli.run(&server, &["doctor"]);
+
+    let report = stdout(&output);
+    assert!(
+        report.contains(
+            "Found 1 toolchain(s) containing a different major version than their release:"
+        ),
+        "{report}"
+    );
+    assert!(
+        report.contains("- Arm Toolchain for Embedded v20.1.0 contains v19.1.5\n"),
+        "{report}"
+    );
+    assert!(!report.contains("21.1.0-rc2"), "{report}");
+}
@@ -0,0 +1,219 @@
+use std::fs;
+
+use serde_json::Value;
+
+use crate::{
+    Cli,
+    common::{self, MockGitHub, MockRelease, MockServer, host_asset_name},
+    stderr, stdout,
+};
+
+fn server() -> MockServer {
+    MockServer::github(MockGitHub::new([
+        MockRelease::toolchain("21.1.0"),
+        MockRelease::toolchain("20.1.0"),
+    ]))
+}
+
+/// What's in the home directory, and how many requests the server has received.
+struct Snapshot {
+    files: Vec<String>,
+    requests: usize,
+}
+
+impl Snapshot {
+    fn take(cli: &Cli, server: &MockServer) -> Self {
+        Self {
+            files: common::list_tree(cli.home()),
+            requests: server.requests().len(),
+        }
+    }
+
+    /// Checks that nothing was written to the home directory since the snapshot was taken, and
+    /// that only release metadata was fetched.
+    fn assert_nothing_done(&self, cli: &Cli, server: &MockServer) {
+        assert_eq!(common::list_tree(cli.home()), self.files);
+        let paths: Vec<_> = server.requests()[self.requests..]
+            .iter()
+            .map(|request| request.path.clone())
+            .collect();
+        assert!(
+            paths.iter().all(|path| path.starts_with("/repos/")),
+            "{paths:?}"
+        );
+    }
+}
+
+#[test]
+fn install_reports_the_plan_without_writing_anything() {
+    let cli = Cli::default();
+    let server = server();
+    let before = Snapshot::take(&cli, &server);
+
+    let output = cli.run(&server, &["install", "latest", "--dry-run"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let report = stdout(&output);
+    let archive = MockRelease::toolchain("21.1.0")
+        .data(&host_asset_name("21.1.0"))
+        .len();
+    assert!(
+        report.contains(&format!(
+            "Would download {} ({} download",
+            host_asset_name("21.1.0"),
+            humansize::format_size(archive, humansize::DECIMAL)
+        )),
+        "{report}"
+    );
+    assert!(
+        report.contains(&format!(
+            "Would install v21.1.0 to {}\n",
+            cli.toolchains_path().join("embedded/21.1.0").display()
+        )),
+        "{report}"
+    );
+    assert!(
+        report.contains("Would activate v21.1.0 because no toolchain is active"),
+        "{report}"
+    );
+    before.assert_nothing_done(&cli, &server);
+}
+
+#[test]
+fn install_reports_the_plan_as_json() {
+    let cli = Cli::default();
+    let server = server();
+    let before = Snapshot::take(&cli, &server);
+
+    let output = cli.run(
+        &server,
+        &["install", "20.1.0", "--dry-run", "--output", "json"],
+    );
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let plan = &report["result"];
+    assert_eq!(plan["outcome"], "dry_run");
+    assert_eq!(plan["version"], "20.1.0");
+    assert_eq!(plan["asset_name"], host_asset_name("20.1.0"));
+    let archive = MockRelease::toolchain("20.1.0")
+        .data(&host_asset_name("20.1.0"))
+        .len();
+    assert_eq!(plan["download_size"], archive as u64);
+    assert_eq!(plan["cached_bytes"], 0);
+    assert_eq!(
+        plan["destination"],
+        cli.toolchains_path()
+            .join("embedded/20.1.0")
+            .to_str()
+            .unwrap()
+    );
+    assert_eq!(plan["replaces_existing"], false);
+    assert_eq!(plan["activates"], true);
+    before.assert_nothing_done(&cli, &server);
+}
+
+#[test]
+fn install_matches_what_installing_does() {
+    let cli = Cli::default();
+    let server = server();
+    let output = cli.run(&server, &["install", "20.1.0", "--yes"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let before = Snapshot::take(&cli, &server);
+
+    let output = cli.run(
+        &server,
+        &["install", "21.1.0", "--dry-run", "--output", "json"],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    let plan: Value = serde_json::from_slice(&output.stdout).unwrap();
+    before.assert_nothing_done(&cli, &server);
+
+    let output = cli.run(&server, &["install", "21.1.0", "--yes", "--output", "json"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let installed: Value = serde_json::from_slice(&output.stdout).unwrap();
+
+    let (plan, installed) = (&plan["result"], &installed["result"]);
+    assert_eq!(plan["version"], installed["version"]);
+    assert_eq!(plan["destination"], installed["path"]);
+    assert_eq!(plan["download_size"], installed["archive_size"]);
+    // 20.1.0 is already active.
+    assert_eq!(plan["activates"], false);
+    assert_eq!(installed["activated"], false);
+}
+
+#[test]
+fn install_prefix_reports_replacing_its_contents() {
+    let cli = Cli::default();
+    let server = server();
+    let prefix = cli.home().join("prefix");
+    fs::create_dir_all(&prefix).unwrap();
+    fs::write(prefix.join("keep.txt"), "mine").unwrap();
+    let before = Snapshot::take(&cli, &server);
+
+    let output = cli.run(
+        &server,
+        &[
+            "install",
+            "21.1.0",
+            "--dry-run",
+            "--force",
+            "--prefix",
+            prefix.to_str().unwrap(),
+        ],
+    );
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let report = stdout(&output);
+    assert!(
+        report.contains(&format!(
+            "Would install v21.1.0 to {} (replacing its current contents)",
+            prefix.display()
+        )),
+        "{report}"
+    );
+    before.assert_nothing_done(&cli, &server);
+}
+
+#[test]
+fn use_reports_installing_and_activating() {
+    let cli = Cli::default();
+    let server = server();
+    let before = Snapshot::take(&cli, &server);
+
+    let output = cli.run(&server, &["use", "21.1.0", "--dry-run"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let report = stdout(&output);
+    assert!(
+        report.contains(&format!("Would download {}", host_asset_name("21.1.0"))),
+        "{report}"
+    );
+    assert!(report.contains("Would activate v21.1.0\n"), "{report}");
+    before.assert_nothing_done(&cli, &server);
+}
+
+#[test]
+fn use_reports_switching_to_an_installed_toolchain() {
+    let cli = Cli::default();
+    let server = server();
+    for version in ["21.1.0", "20.1.0"] {
+        let output = cli.run(&server, &["install", version, "--yes"]);
+        assert!(output.status.success(), "{}", stderr(&output));
+    }
+    let before = Snapshot::take(&cli, &server);
+    let active_file = cli.toolchains_path().join("embedded/current.txt");
+    let active = fs::read_to_string(&active_file).unwrap();
+
+    let output = cli.run(&server, &["use", "20.1.0", "--dry-run", "--output", "json"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let result = &report["result"];
+    assert_eq!(result["dry_run"], true);
+    assert_eq!(result["activated"], true);
+    assert_eq!(result["installed"], Value::Null);
+    assert!(result.get("install_plan").is_none(), "{result}");
+    assert_eq!(fs::read_to_string(&active_file).unwrap(), active);
+    before.assert_nothing_done(&cli, &server);
+}
@@ -0,0 +1,120 @@
+use std::fs;
+
+use crate::{
+    Cli,
+    common::{MockGitHub, MockRelease, MockServer, Response, host_asset_name, sha256_hex},
+    stderr, stdout,
+};
+
+#[test]
+fn urls_are_printed_on_the_mirror() {
+    let release = MockRelease::toolchain("21.1.0");
+    let asset_name = host_asset_name("21.1.0");
+    let sha256 = sha256_hex(release.data(&asset_name));
+    let github = MockGitHub::new([release]);
+    let server = MockServer::start(move |request| {
+        let mut request = request.clone();
+        if let Some(path) = request.path.strip_prefix("/mirror") {
+            request.path = path.to_string();
+        }
+        github.respond(&request)
+    });
+    let cli = Cli::default();
+
+    let output = cli
+        .command(&server)
+        .env("ARM_TOOLCHAIN_MIRROR", server.url("/mirror/"))
+        .args(["info", "21.1.0", "--urls"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let report = stdout(&output);
+    let url = server.url(&format!(
+        "/mirror/download/release-21.1.0-ATfE/{asset_name}"
+    ));
+    assert!(report.contains(&format!("URL: {url}\n")), "{report}");
+    assert!(
+        report.contains(&format!("Checksum URL: {url}.sha256\n")),
+        "{report}"
+    );
+    assert!(report.contains(&format!("SHA256: {sha256}\n")), "{report}");
+}
+
+#[test]
+fn installed_toolchain_shows_its_contents_version() {
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain_containing(
+        "20.1.0",
+        "20.1.0-rc1",
+    )]));
+    let cli = Cli::default();
+    let output = cli.run(&server, &["install", "20.1.0", "--yes"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = cli.run(&server, &["info", "20.1.0"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let report = stdout(&output);
+    assert!(
+        report.contains(&format!(
+            "Installed at: {}\n",
+            cli.toolchains_path().join("embedded/20.1.0").display()
+        )),
+        "{report}"
+    );
+    assert!(
+        report.contains("Contents version: v20.1.0-rc1 (differs from the release version)\n"),
+        "{report}"
+    );
+}
+
+#[test]
+fn notes_are_printed_offline() {
+    let server = MockServer::github(MockGitHub::new([MockRelease {
+        body: Some("- Faster linking".to_string()),
+        ..MockRelease::toolchain("21.1.0")
+    }]));
+    let cli = Cli::default();
+    let output = cli.run(&server, &["install", "21.1.0", "--yes"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let offline = MockServer::start(|_| Response::new(503, "offline"));
+
+    let output = cli.run(&offline, &["info", "21.1.0", "--notes"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let notes = stdout(&output);
+    assert!(notes.starts_with("# release-21.1.0-ATfE\n"), "{notes}");
+    assert!(notes.ends_with("\n\n- Faster linking\n"), "{notes}");
+    assert_eq!(offline.requests().len(), 0);
+
+    let output = cli.run(&offline, &["info", "--notes"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), notes);
+}
+
+#[test]
+fn missing_notes_are_reported() {
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let cli = Cli::default();
+    let output = cli.run(&server, &["install", "21.1.0", "--yes"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    fs::remove_file(
+        cli.toolchains_path()
+            .join("embedded/21.1.0/.release-notes.md"),
+    )
+    .unwrap();
+
+    let output = cli.run(&server, &["info", "21.1.0", "--notes"]);
+
+    assert!(!output.status.success());
+    let error = stderr(&output);
+    assert!(
+        error.contains("arm_toolchain::cli::release_notes_missing"),
+        "{error}"
+    );
+    assert!(
+        error.contains("No release notes were saved when v21.1.0 was installed"),
+        "{error}"
+    );
+}
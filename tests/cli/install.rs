@@ -0,0 +1,545 @@
+use std::{
+    fs,
+    process::Stdio,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    Cli,
+    common::{self, MockGitHub, MockRelease, MockServer, Response, host_asset_name},
+    stderr, stdout,
+};
+
+/// Fills a prefix with a file that `--force` would replace.
+fn existing_prefix(cli: &Cli) -> std::path::PathBuf {
+    let prefix = cli.home().join("prefix");
+    fs::create_dir_all(&prefix).unwrap();
+    fs::write(prefix.join("keep.txt"), "mine").unwrap();
+    prefix
+}
+
+#[test]
+fn force_prefix_is_kept_when_not_confirmed() {
+    let cli = Cli::default();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let prefix = existing_prefix(&cli);
+
+    let output = cli.run(
+        &server,
+        &[
+            "install",
+            "21.1.0",
+            "--force",
+            "--prefix",
+            prefix.to_str().unwrap(),
+        ],
+    );
+
+    assert!(!output.status.success(), "{}", stderr(&output));
+    assert_eq!(fs::read_to_string(prefix.join("keep.txt")).unwrap(), "mine");
+    assert!(server.requests_for(&host_asset_name("21.1.0")).is_empty());
+}
+
+#[test]
+fn force_prefix_is_kept_when_the_download_fails() {
+    let cli = Cli::default();
+    let asset_name = host_asset_name("21.1.0");
+    let github = MockGitHub::new([MockRelease::toolchain("21.1.0")]);
+    let server = MockServer::start({
+        let asset_name = asset_name.clone();
+        move |request| {
+            if request.file_name() == asset_name {
+                Response::not_found()
+            } else {
+                github.respond(request)
+            }
+        }
+    });
+    let prefix = existing_prefix(&cli);
+
+    let output = cli.run(
+        &server,
+        &[
+            "install",
+            "21.1.0",
+            "--force",
+            "--yes",
+            "--prefix",
+            prefix.to_str().unwrap(),
+        ],
+    );
+
+    assert!(!output.status.success(), "{}", stderr(&output));
+    assert_eq!(fs::read_to_string(prefix.join("keep.txt")).unwrap(), "mine");
+}
+
+#[test]
+fn force_prefix_is_replaced_without_touching_the_toolchains_directory() {
+    let cli = Cli::default();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let prefix = existing_prefix(&cli);
+
+    let output = cli.run(
+        &server,
+        &[
+            "install",
+            "21.1.0",
+            "--force",
+            "--yes",
+            "--prefix",
+            prefix.to_str().unwrap(),
+        ],
+    );
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!prefix.join("keep.txt").exists());
+    assert!(prefix.join("bin/clang").is_file());
+    let managed = common::list_tree(&cli.toolchains_path());
+    assert!(
+        !managed.iter().any(|path| path.contains("21.1.0")),
+        "the toolchains directory changed: {managed:?}"
+    );
+}
+
+#[test]
+fn downloads_the_toolchain_for_another_host() {
+    let cli = Cli::default();
+    let windows_asset = "ATfE-21.1.0-Windows-x86_64.zip";
+    let release = MockRelease::toolchain("21.1.0").asset(windows_asset, common::noise(1024));
+    let server = MockServer::github(MockGitHub::new([release]));
+
+    let output = cli.run(
+        &server,
+        &[
+            "install",
+            "21.1.0",
+            "--host-os",
+            "windows",
+            "--host-arch",
+            "x86_64",
+            "--dry-run",
+        ],
+    );
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let report = stdout(&output);
+    assert!(
+        report.contains(&format!("Would download {windows_asset}")),
+        "{report}"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn shared_installs_are_group_writable() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let cli = Cli::default();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+
+    let output = cli.run(&server, &["install", "21.1.0", "--shared", "--yes"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let toolchain = cli.toolchains_path().join("embedded/21.1.0");
+    let mode = |path: &str| {
+        fs::metadata(toolchain.join(path))
+            .unwrap()
+            .permissions()
+            .mode()
+            & 0o7777
+    };
+    assert_eq!(mode("bin"), 0o2775);
+    assert_eq!(mode("bin/clang"), 0o775);
+    assert_eq!(mode("lib/clang-runtimes/multilib.yaml"), 0o664);
+}
+
+#[test]
+fn checksum_file_from_the_config_is_used() {
+    let cli = Cli::default();
+    let release = MockRelease::toolchain("21.1.0");
+    let asset_name = host_asset_name("21.1.0");
+    let approved = cli.home().join("approved.sha256");
+    fs::write(
+        &approved,
+        format!(
+            "{}  {asset_name}\n",
+            common::sha256_hex(release.data(&asset_name))
+        ),
+    )
+    .unwrap();
+    let config_dir = cli.home().join(".config/arm-toolchain");
+    fs::create_dir_all(&config_dir).unwrap();
+    fs::write(
+        config_dir.join("config.toml"),
+        format!("checksum_file = {:?}\n", approved.to_str().unwrap()),
+    )
+    .unwrap();
+    let server = MockServer::github(MockGitHub::new([release]));
+
+    let output = cli.run(&server, &["install", "21.1.0", "--yes"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        server
+            .requests_for(&format!("{asset_name}.sha256"))
+            .is_empty()
+    );
+
+    // The file passed on the command line replaces the one in the config.
+    let unlisted = cli.home().join("unlisted.sha256");
+    fs::write(&unlisted, "").unwrap();
+    let output = cli.run(
+        &server,
+        &[
+            "install",
+            "21.1.0",
+            "--yes",
+            "--force",
+            "--checksum-file",
+            unlisted.to_str().unwrap(),
+        ],
+    );
+
+    assert!(!output.status.success());
+    let error = stderr(&output);
+    assert!(
+        error.contains(&format!("{asset_name} isn't listed in the checksum file")),
+        "{error}"
+    );
+}
+
+#[test]
+fn failed_activation_is_a_partial_success() {
+    let cli = Cli::default();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    // The active toolchain file can't be written where a directory is in the way.
+    fs::create_dir_all(cli.toolchains_path().join("embedded/current.txt")).unwrap();
+
+    let output = cli.run(&server, &["install", "21.1.0", "--yes"]);
+
+    assert_eq!(output.status.code(), Some(3), "{}", stderr(&output));
+    let report = stderr(&output);
+    assert!(
+        report.contains("21.1.0 was installed, but could not be activated"),
+        "{report}"
+    );
+    assert!(
+        cli.toolchains_path()
+            .join("embedded/21.1.0/bin/clang")
+            .is_file()
+    );
+
+    // The suggested command activates it once the file can be written.
+    let command = report
+        .split('`')
+        .nth(1)
+        .expect("a command to activate the toolchain");
+    fs::remove_dir(cli.toolchains_path().join("embedded/current.txt")).unwrap();
+    let args: Vec<_> = command.split(' ').collect();
+    let output = cli.run(&server, &args);
+
+    assert_eq!(args[0], "use");
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        fs::read_to_string(cli.toolchains_path().join("embedded/current.txt")).unwrap(),
+        "21.1.0"
+    );
+}
+
+#[test]
+fn all_is_rejected_before_anything_is_requested() {
+    let cli = Cli::default();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+
+    let output = cli.run(&server, &["install", "all", "--yes"]);
+
+    assert_eq!(output.status.code(), Some(2), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("\"all\" can't be used with this command"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn ctrl_c_cancels_while_waiting_for_github() {
+    let github = MockGitHub::new([MockRelease::toolchain("21.1.0")]);
+    let server = MockServer::start(move |request| {
+        thread::sleep(Duration::from_secs(60));
+        github.respond(request)
+    });
+    let cli = Cli::default();
+    let install = cli
+        .command(&server)
+        .args(["install", "21.1.0", "--yes"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let started = Instant::now();
+    while server.requests().is_empty() {
+        assert!(started.elapsed() < Duration::from_secs(30), "no request");
+        thread::sleep(Duration::from_millis(50));
+    }
+    // SAFETY: The install is a child process that hasn't been waited for, so its PID is
+    // still its own.
+    unsafe { libc::kill(install.id() as libc::pid_t, libc::SIGINT) };
+    let cancelled_at = Instant::now();
+    let output = install.wait_with_output().unwrap();
+
+    assert!(cancelled_at.elapsed() < Duration::from_secs(5));
+    assert!(!output.status.success());
+    let error = stderr(&output);
+    assert!(error.contains("Cancelled."), "{error}");
+    assert!(
+        error.contains("arm_toolchain::toolchain::cancelled"),
+        "{error}"
+    );
+    assert!(!cli.toolchains_path().join("embedded/21.1.0").exists());
+}
+
+#[test]
+fn ctrl_c_during_the_download_reports_what_was_kept() {
+    let asset_name = host_asset_name("21.1.0");
+    let github = MockGitHub::new([MockRelease::toolchain("21.1.0")]);
+    let server = MockServer::start({
+        let asset_name = asset_name.clone();
+        move |request| {
+            let response = github.respond(request);
+            match request.file_name() == asset_name {
+                true => response.throttle(1024, Duration::from_millis(50)),
+                false => response,
+            }
+        }
+    });
+    let cli = Cli::default();
+    let cached = cli
+        .home()
+        .join(".cache/arm-toolchain/downloads/llvm-toolchains")
+        .join(&asset_name);
+    let install = cli
+        .command(&server)
+        .args(["install", "21.1.0", "--yes", "--output", "json"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let started = Instant::now();
+    while fs::metadata(&cached).map_or(0, |metadata| metadata.len()) < 4096 {
+        assert!(started.elapsed() < Duration::from_secs(30), "no download");
+        thread::sleep(Duration::from_millis(20));
+    }
+    // SAFETY: The install is a child process that hasn't been waited for, so its PID is
+    // still its own.
+    unsafe { libc::kill(install.id() as libc::pid_t, libc::SIGINT) };
+    let output = install.wait_with_output().unwrap();
+
+    assert!(!output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let error = &report["error"];
+    assert_eq!(error["code"], "arm_toolchain::cli::install_cancelled");
+    let details = &error["details"];
+    assert_eq!(details["resume_command"], "arm-toolchain install v21.1.0");
+    let cancelled = &details["cancelled"];
+    assert_eq!(cancelled["version"], "21.1.0");
+    assert_eq!(cancelled["phase"], "download");
+
+    // The summary describes the partial file that's actually on disk.
+    let kept = fs::metadata(&cached).unwrap().len();
+    let total = MockRelease::toolchain("21.1.0").data(&asset_name).len() as u64;
+    let archive = &cancelled["archive"];
+    assert_eq!(archive["path"], cached.to_str().unwrap());
+    assert_eq!(archive["downloaded_bytes"], kept);
+    assert_eq!(archive["total_bytes"], total);
+    assert!(kept < total);
+    let help = error["help"].as_str().unwrap();
+    assert!(
+        help.contains(&format!(
+            "The partial download was kept at {}",
+            cached.display()
+        )),
+        "{help}"
+    );
+    assert!(help.contains(&format!("{}%", kept * 100 / total)), "{help}");
+    assert!(
+        help.ends_with("Resume the installation with `arm-toolchain install v21.1.0`."),
+        "{help}"
+    );
+}
+
+/// The URL of the host's toolchain archive of a release served by `server`.
+fn asset_url(server: &MockServer, version: &str) -> String {
+    server.url(&format!(
+        "/download/release-{version}-ATfE/{}",
+        host_asset_name(version)
+    ))
+}
+
+#[test]
+fn url_installs_without_a_checksum_warn_once() {
+    let cli = Cli::default();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let url = asset_url(&server, "21.1.0");
+
+    let output = cli.run(
+        &server,
+        &[
+            "install",
+            "--url",
+            &url,
+            "--name",
+            "21.1.0-patched",
+            "--yes",
+        ],
+    );
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let message = stderr(&output);
+    assert_eq!(message.matches("won't be verified").count(), 1, "{message}");
+    assert!(
+        cli.toolchains_path()
+            .join("embedded/21.1.0-patched/bin/clang")
+            .is_file()
+    );
+}
+
+#[test]
+fn url_installs_are_checked_against_the_given_checksum() {
+    let cli = Cli::default();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let url = asset_url(&server, "21.1.0");
+    let wrong = "0".repeat(64);
+
+    let output = cli.run(
+        &server,
+        &[
+            "install",
+            "--url",
+            &url,
+            "--name",
+            "21.1.0-patched",
+            "--sha256",
+            &wrong,
+            "--yes",
+        ],
+    );
+
+    let message = stderr(&output);
+    assert!(!output.status.success(), "{message}");
+    assert!(
+        message.contains("arm_toolchain::toolchain::checksum_mismatch"),
+        "{message}"
+    );
+    assert!(!message.contains("won't be verified"), "{message}");
+    assert!(
+        !cli.toolchains_path()
+            .join("embedded/21.1.0-patched")
+            .exists()
+    );
+}
+
+#[test]
+fn url_installs_can_be_used_and_run_by_name() {
+    let cli = Cli::default();
+    let release = MockRelease::toolchain("21.1.0");
+    let checksum = common::sha256_hex(release.data(&host_asset_name("21.1.0")));
+    let server = MockServer::github(MockGitHub::new([release]));
+    let url = asset_url(&server, "21.1.0");
+    let output = cli.run(
+        &server,
+        &[
+            "install",
+            "--url",
+            &url,
+            "--name",
+            "21.1.0-patched",
+            "--sha256",
+            &checksum,
+            "--yes",
+        ],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = cli.run(&server, &["use", "21.1.0-patched"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        fs::read_to_string(cli.toolchains_path().join("embedded/current.txt")).unwrap(),
+        "21.1.0-patched"
+    );
+
+    let output = cli.run(&server, &["locate", "bin"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output).trim(),
+        cli.toolchains_path()
+            .join("embedded/21.1.0-patched/bin")
+            .to_str()
+            .unwrap()
+    );
+
+    // The fixture's clang echoes its arguments.
+    let output = cli.run(&server, &["run", "clang", "--target=arm"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "clang --target=arm\n");
+}
+
+#[test]
+fn checksum_mismatches_download_again_after_removing_the_cached_file() {
+    let cli = Cli::default();
+    let asset_name = host_asset_name("21.1.0");
+    let release = MockRelease::toolchain("21.1.0");
+    let mut corrupted = release.data(&asset_name).to_vec();
+    let middle = corrupted.len() / 2;
+    corrupted[middle] ^= 0xff;
+    let github = MockGitHub::new([release]);
+    let archive_requests = std::sync::atomic::AtomicUsize::new(0);
+    let server = MockServer::start({
+        let asset_name = asset_name.clone();
+        move |request| {
+            let response = github.respond(request);
+            let is_first_download = request.file_name() == asset_name
+                && request.method == "GET"
+                && archive_requests.fetch_add(1, std::sync::atomic::Ordering::SeqCst) == 0;
+            match is_first_download {
+                true => Response::ok(corrupted.clone()),
+                false => response,
+            }
+        }
+    });
+
+    let output = cli.run(&server, &["install", "21.1.0", "--yes"]);
+    let message = stderr(&output);
+    assert!(!output.status.success(), "{message}");
+    assert!(
+        message.contains("arm_toolchain::toolchain::checksum_mismatch"),
+        "{message}"
+    );
+    assert!(message.contains("arm-toolchain cache remove"), "{message}");
+
+    let output = cli.run(&server, &["cache", "remove", &asset_name]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = cli.run(&server, &["install", "21.1.0", "--yes"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        cli.toolchains_path()
+            .join("embedded/21.1.0/bin/clang")
+            .is_file()
+    );
+
+    // The second download started over instead of resuming from the corrupted one.
+    let downloads: Vec<_> = server
+        .requests_for(&asset_name)
+        .into_iter()
+        .filter(|request| request.method == "GET")
+        .collect();
+    assert_eq!(downloads.len(), 2);
+    let range = downloads[1].header("range").unwrap_or("bytes=0-");
+    assert!(range.starts_with("bytes=0-"), "{range}");
+}
@@ -0,0 +1,105 @@
+//! Runs the `arm-toolchain` binary against a [`MockServer`], with its home directory in a
+//! temporary directory.
+//!
+//! The default directories can only be moved with environment variables on Linux, so these
+//! tests only run there.
+
+#![cfg(target_os = "linux")]
+
+#[path = "../common/mod.rs"]
+mod common;
+
+mod api_calls;
+mod bundle;
+mod doctor;
+mod dry_run;
+mod info;
+mod install;
+mod notice;
+mod output_json;
+mod prompts;
+mod purge_all;
+mod remove;
+mod run;
+mod setup;
+mod update;
+mod use_cmd;
+mod verify;
+
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+use common::MockServer;
+use tempfile::TempDir;
+
+/// A home directory for the CLI to keep its files in.
+pub struct Cli {
+    home: TempDir,
+}
+
+impl Default for Cli {
+    fn default() -> Self {
+        Self {
+            home: tempfile::tempdir().unwrap(),
+        }
+    }
+}
+
+impl Cli {
+    pub fn home(&self) -> &Path {
+        self.home.path()
+    }
+
+    /// The directory that toolchains are installed to.
+    pub fn toolchains_path(&self) -> PathBuf {
+        self.home()
+            .join(".local/share/arm-toolchain/llvm-toolchains")
+    }
+
+    /// Returns a command that runs the CLI with the given server standing in for GitHub.
+    pub fn command(&self, server: &MockServer) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_arm-toolchain"));
+        command
+            .current_dir(self.home())
+            .env("HOME", self.home())
+            .env_remove("XDG_DATA_HOME")
+            .env_remove("XDG_CACHE_HOME")
+            .env_remove("XDG_CONFIG_HOME")
+            .env_remove("GITHUB_TOKEN")
+            .env_remove("GH_TOKEN")
+            .env_remove("ARM_TOOLCHAIN_MIRROR")
+            .env_remove("ARM_TOOLCHAIN_GITHUB_REPO")
+            .env_remove("ARM_TOOLCHAIN_SETUP_ANSWERS")
+            .env("ARM_TOOLCHAIN_GITHUB_API", server.base_url())
+            .env("NO_COLOR", "1");
+        command
+    }
+
+    /// Runs the CLI with the given arguments and waits for it to exit.
+    pub fn run(&self, server: &MockServer, args: &[&str]) -> Output {
+        self.command(server).args(args).output().unwrap()
+    }
+}
+
+/// Returns the text that a command printed to stderr.
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+/// Returns the text that a command printed to stdout, without any colors.
+pub fn stdout(output: &Output) -> String {
+    let text = String::from_utf8_lossy(&output.stdout);
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skips a `CSI ... m` sequence, which is all that colors are made of.
+            chars.by_ref().find(|&c| c == 'm');
+        } else {
+            plain.push(c);
+        }
+    }
+    plain
+}
@@ -0,0 +1,204 @@
+use std::{
+    fs,
+    path::PathBuf,
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    Cli,
+    common::{MockGitHub, MockRelease, MockServer, Response},
+    stderr,
+};
+
+/// The command run with the toolchain, which outlasts the check for a newer release so that
+/// the notice isn't abandoned.
+const COMMAND: &[&str] = &["sleep", "1"];
+
+fn releases() -> MockGitHub {
+    MockGitHub::new([
+        MockRelease::toolchain("22.1.0"),
+        MockRelease::toolchain("21.1.0"),
+    ])
+}
+
+/// A home directory with 21.1.0 installed and active, while 22.1.0 is available too.
+fn installed(server: &MockServer, config: &str) -> Cli {
+    let cli = Cli::default();
+    let output = cli.run(server, &["install", "21.1.0", "--yes"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let config_dir = cli.home().join(".config/arm-toolchain");
+    fs::create_dir_all(&config_dir).unwrap();
+    fs::write(config_dir.join("config.toml"), config).unwrap();
+    cli
+}
+
+fn state_path(cli: &Cli) -> PathBuf {
+    cli.home()
+        .join(".local/share/arm-toolchain/update-notice-embedded")
+}
+
+/// Returns the time a number of days ago, in seconds since the Unix epoch.
+fn days_ago(days: u64) -> u64 {
+    let time = SystemTime::now() - Duration::from_secs(days * 24 * 60 * 60);
+    time.duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+/// Runs a command with the toolchain, and returns what was printed to stderr and the number
+/// of requests that were made.
+fn run(cli: &Cli, server: &MockServer, flags: &[&str], command: &[&str]) -> (String, usize) {
+    let before = server.requests().len();
+    let mut args = vec!["run"];
+    args.extend(flags);
+    args.extend(command);
+
+    let output = cli.run(server, &args);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    (stderr(&output), server.requests().len() - before)
+}
+
+#[test]
+fn disabled_by_default() {
+    let server = MockServer::github(releases());
+    let cli = installed(&server, "");
+
+    let (stderr, requests) = run(&cli, &server, &[], COMMAND);
+
+    assert!(!stderr.contains("note:"), "{stderr}");
+    assert_eq!(requests, 0);
+    assert!(!state_path(&cli).exists());
+}
+
+#[test]
+fn newer_release_is_checked_once_per_day() {
+    let server = MockServer::github(releases());
+    let cli = installed(&server, "notify_updates = true\n");
+
+    let (stderr, _) = run(&cli, &server, &[], COMMAND);
+
+    assert!(
+        stderr.contains("v22.1.0 is available (installed: v21.1.0); run `arm-toolchain update`"),
+        "{stderr}"
+    );
+    let state = fs::read_to_string(state_path(&cli)).unwrap();
+    assert_eq!(state.lines().nth(1), Some("22.1.0"), "{state}");
+
+    // Until the next check, the notice is based on the release that was found.
+    let (stderr, requests) = run(&cli, &server, &[], COMMAND);
+
+    assert!(stderr.contains("v22.1.0 is available"), "{stderr}");
+    assert_eq!(requests, 0);
+    assert_eq!(fs::read_to_string(state_path(&cli)).unwrap(), state);
+}
+
+#[test]
+fn no_notice_once_the_latest_release_is_installed() {
+    let server = MockServer::github(releases());
+    let cli = installed(&server, "notify_updates = true\n");
+    run(&cli, &server, &[], COMMAND);
+    let output = cli.run(&server, &["install", "22.1.0", "--yes"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let (stderr, requests) = run(&cli, &server, &[], COMMAND);
+
+    assert!(!stderr.contains("note:"), "{stderr}");
+    assert_eq!(requests, 0);
+}
+
+#[test]
+fn short_commands_use_the_last_check_without_waiting() {
+    // GitHub takes longer to answer than the command takes to run.
+    let github = releases();
+    let server = MockServer::start(move |request| {
+        if request
+            .path
+            .starts_with("/repos/arm/arm-toolchain/releases")
+        {
+            thread::sleep(Duration::from_secs(5));
+        }
+        github.respond(request)
+    });
+    let cli = installed(&server, "notify_updates = true\n");
+    let state = format!("{}\n22.1.0\n", days_ago(2));
+    fs::write(state_path(&cli), &state).unwrap();
+
+    let started = Instant::now();
+    let (stderr, _) = run(&cli, &server, &[], &["true"]);
+
+    assert!(started.elapsed() < Duration::from_secs(4));
+    assert!(stderr.contains("v22.1.0 is available"), "{stderr}");
+    // The abandoned check saved nothing, so the next run checks again.
+    assert_eq!(fs::read_to_string(state_path(&cli)).unwrap(), state);
+}
+
+#[test]
+fn offline_uses_the_last_check() {
+    let server = MockServer::github(releases());
+    let cli = installed(&server, "notify_updates = true\n");
+    let state = format!("{}\n22.1.0\n", days_ago(2));
+    fs::write(state_path(&cli), &state).unwrap();
+
+    let (stderr, requests) = run(&cli, &server, &["--offline"], COMMAND);
+
+    assert!(stderr.contains("v22.1.0 is available"), "{stderr}");
+    assert_eq!(requests, 0);
+    assert_eq!(fs::read_to_string(state_path(&cli)).unwrap(), state);
+}
+
+#[test]
+fn check_from_over_a_day_ago_is_repeated() {
+    let server = MockServer::github(releases());
+    let cli = installed(&server, "notify_updates = true\n");
+    let seconds = days_ago(2);
+    fs::write(state_path(&cli), format!("{seconds}\n21.1.0\n")).unwrap();
+
+    let (stderr, _) = run(&cli, &server, &[], COMMAND);
+
+    assert!(stderr.contains("v22.1.0 is available"), "{stderr}");
+    let state = fs::read_to_string(state_path(&cli)).unwrap();
+    let checked_at: u64 = state.lines().next().unwrap().parse().unwrap();
+    assert!(checked_at > seconds, "{state}");
+}
+
+#[test]
+fn failed_check_is_silent_and_waits_a_day() {
+    // The release list is unavailable, but the toolchain can still be installed by its tag.
+    let github = releases();
+    let server = MockServer::start(move |request| {
+        if request
+            .path
+            .starts_with("/repos/arm/arm-toolchain/releases?")
+            || request.path == "/repos/arm/arm-toolchain/releases"
+        {
+            Response::new(503, "unavailable")
+        } else {
+            github.respond(request)
+        }
+    });
+    let cli = installed(&server, "notify_updates = true\n");
+
+    let (stderr, requests) = run(&cli, &server, &[], COMMAND);
+
+    assert!(requests > 0);
+    assert!(!stderr.contains("note:"), "{stderr}");
+    let state = fs::read_to_string(state_path(&cli)).unwrap();
+    assert_eq!(state.lines().count(), 1, "{state}");
+
+    let (_, requests) = run(&cli, &server, &[], COMMAND);
+
+    assert_eq!(requests, 0);
+}
+
+#[test]
+fn quiet_skips_the_check() {
+    let server = MockServer::github(releases());
+    let cli = installed(&server, "notify_updates = true\n");
+
+    let (stderr, requests) = run(&cli, &server, &["--quiet"], COMMAND);
+
+    assert!(!stderr.contains("note:"), "{stderr}");
+    assert_eq!(requests, 0);
+    assert!(!state_path(&cli).exists());
+}
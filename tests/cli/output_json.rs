@@ -0,0 +1,116 @@
+use serde_json::Value;
+
+use crate::{
+    Cli,
+    common::{self, MockGitHub, MockRelease, MockServer, host_asset_name},
+    stderr,
+};
+
+/// Runs a command with `--output json`, checking that stdout is a single JSON document.
+fn run_json(cli: &Cli, server: &MockServer, args: &[&str]) -> (bool, Value) {
+    let mut args = args.to_vec();
+    args.extend(["--output", "json"]);
+    let output = cli.run(server, &args);
+
+    let stdout = String::from_utf8(output.stdout.clone()).unwrap();
+    assert_eq!(stdout.lines().count(), 1, "{args:?}: {stdout}");
+    let report: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(
+        report["success"],
+        output.status.success(),
+        "{args:?}: {}",
+        stderr(&output)
+    );
+    (output.status.success(), report)
+}
+
+fn server() -> MockServer {
+    MockServer::github(MockGitHub::new([
+        MockRelease::toolchain("21.1.0"),
+        MockRelease::toolchain("20.1.0"),
+    ]))
+}
+
+#[test]
+fn install_reports_the_installed_toolchain() {
+    let cli = Cli::default();
+    let server = server();
+
+    let (success, report) = run_json(&cli, &server, &["install", "21.1.0", "--yes"]);
+
+    assert!(success);
+    let result = &report["result"];
+    assert_eq!(result["outcome"], "installed");
+    assert_eq!(result["version"], "21.1.0");
+    assert_eq!(
+        result["path"],
+        cli.toolchains_path()
+            .join("embedded/21.1.0")
+            .to_str()
+            .unwrap()
+    );
+    let archive = MockRelease::toolchain("21.1.0")
+        .data(&host_asset_name("21.1.0"))
+        .len();
+    assert_eq!(result["archive_size"], archive as u64);
+    assert!(result["duration_secs"].is_number());
+    assert_eq!(result["activated"], true);
+}
+
+#[test]
+fn install_reports_an_existing_toolchain() {
+    let cli = Cli::default();
+    let server = server();
+    run_json(&cli, &server, &["install", "21.1.0", "--yes"]);
+
+    let (success, report) = run_json(&cli, &server, &["install", "21.1.0", "--yes"]);
+
+    assert!(success);
+    assert_eq!(report["result"]["outcome"], "already_installed");
+    assert_eq!(report["result"]["version"], "21.1.0");
+    assert_eq!(report["result"]["active"], true);
+}
+
+#[test]
+fn install_reports_the_error_code() {
+    let cli = Cli::default();
+    let name = host_asset_name("21.1.0");
+    let release = MockRelease::empty("21.1.0")
+        .asset(&name, common::toolchain_archive("ATfE-21.1.0"))
+        .asset(
+            &format!("{name}.sha256"),
+            format!("{}  {name}\n", "0".repeat(64)).into_bytes(),
+        );
+    let server = MockServer::github(MockGitHub::new([release]));
+
+    let (success, report) = run_json(&cli, &server, &["install", "21.1.0", "--yes"]);
+
+    assert!(!success);
+    assert_eq!(
+        report["error"]["code"],
+        "arm_toolchain::toolchain::checksum_mismatch"
+    );
+    assert!(report["error"]["message"].is_string());
+    assert!(report.get("result").is_none());
+}
+
+#[test]
+fn use_and_remove_report_their_results() {
+    let cli = Cli::default();
+    let server = server();
+    run_json(&cli, &server, &["install", "21.1.0", "--yes"]);
+
+    let (success, report) = run_json(&cli, &server, &["use", "20.1.0", "--yes"]);
+    assert!(success);
+    assert_eq!(report["result"]["version"], "20.1.0");
+    assert_eq!(report["result"]["activated"], true);
+    assert_eq!(report["result"]["installed"]["version"], "20.1.0");
+
+    let (success, report) = run_json(&cli, &server, &["remove", "21.1.0"]);
+    assert!(success);
+    let removed = report["result"]["removed"].as_array().unwrap();
+    assert_eq!(removed.len(), 1);
+    assert_eq!(removed[0]["version"], "21.1.0");
+    assert_eq!(removed[0]["deactivated"], false);
+    assert!(report["result"]["bytes_removed"].as_u64().unwrap() > 0);
+}
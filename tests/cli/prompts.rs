@@ -0,0 +1,143 @@
+use crate::{
+    Cli,
+    common::{MockGitHub, MockRelease, MockServer},
+    stderr,
+};
+
+/// Runs a command with stdin closed, and checks that it fails because it couldn't ask for
+/// confirmation, naming `flag` as the way to skip the question.
+fn assert_non_interactive(cli: &Cli, server: &MockServer, args: &[&str], flag: &str) {
+    let output = cli.run(server, args);
+
+    let message = stderr(&output);
+    assert!(!output.status.success(), "{args:?}: {message}");
+    assert!(
+        message.contains("arm_toolchain::cli::non_interactive"),
+        "{args:?}: {message}"
+    );
+    assert!(
+        message.contains(&format!("Pass `{flag}` to continue without being asked.")),
+        "{args:?}: {message}"
+    );
+}
+
+#[test]
+fn install_explains_how_to_skip_the_prompt() {
+    let cli = Cli::default();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+
+    assert_non_interactive(&cli, &server, &["install", "21.1.0"], "--yes");
+
+    assert!(!cli.toolchains_path().join("embedded/21.1.0").exists());
+}
+
+#[test]
+fn use_explains_how_to_skip_the_prompt() {
+    let cli = Cli::default();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+
+    assert_non_interactive(&cli, &server, &["use", "21.1.0"], "--yes");
+}
+
+#[test]
+fn remove_explains_how_to_skip_the_prompt() {
+    let cli = Cli::default();
+    let server = MockServer::github(MockGitHub::new([
+        MockRelease::toolchain("21.1.0"),
+        MockRelease::toolchain("20.1.0"),
+    ]));
+    for version in ["21.1.0", "20.1.0"] {
+        let output = cli.run(&server, &["install", version, "--yes"]);
+        assert!(output.status.success(), "{}", stderr(&output));
+    }
+
+    assert_non_interactive(&cli, &server, &["remove", "21.1.0", "20.1.0"], "--yes");
+
+    assert!(cli.toolchains_path().join("embedded/21.1.0").exists());
+    assert!(cli.toolchains_path().join("embedded/20.1.0").exists());
+}
+
+#[test]
+fn purge_all_explains_how_to_skip_the_prompt() {
+    let cli = Cli::default();
+    let server = MockServer::github(MockGitHub::new([]));
+
+    assert_non_interactive(&cli, &server, &["purge-all"], "--yes");
+}
+
+#[test]
+fn setup_points_to_the_commands_without_a_terminal() {
+    let cli = Cli::default();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+
+    let output = cli.run(&server, &["setup"]);
+
+    let message = stderr(&output);
+    assert!(!output.status.success(), "{message}");
+    assert!(
+        message.contains("arm_toolchain::cli::setup_not_interactive"),
+        "{message}"
+    );
+    assert!(message.contains("`use latest`"), "{message}");
+    assert!(message.contains("--local`"), "{message}");
+    // Nothing is looked up before the terminal is checked.
+    assert!(server.requests().is_empty(), "{:#?}", server.requests());
+    assert!(!cli.toolchains_path().exists());
+}
+
+#[test]
+fn setup_refuses_to_run_with_no_input() {
+    let cli = Cli::default();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+
+    // Not even scripted answers are used.
+    let output = cli
+        .command(&server)
+        .args(["setup", "--no-input"])
+        .env("ARM_TOOLCHAIN_SETUP_ANSWERS", "yes,yes,yes,yes")
+        .output()
+        .unwrap();
+
+    let message = stderr(&output);
+    assert!(!output.status.success(), "{message}");
+    assert!(
+        message.contains("arm_toolchain::cli::setup_not_interactive"),
+        "{message}"
+    );
+    assert!(message.contains("`use latest`"), "{message}");
+    assert!(message.contains("--local`"), "{message}");
+    assert!(server.requests().is_empty(), "{:#?}", server.requests());
+    assert!(!cli.toolchains_path().exists());
+}
+
+#[test]
+fn only_missing_terminals_are_non_interactive() {
+    use std::io;
+
+    use arm_toolchain::cli::CliError;
+    use inquire::InquireError;
+
+    for (error, code) in [
+        (InquireError::NotTTY, "arm_toolchain::cli::non_interactive"),
+        (
+            InquireError::IO(io::ErrorKind::UnexpectedEof.into()),
+            "arm_toolchain::cli::non_interactive",
+        ),
+        (
+            InquireError::IO(io::ErrorKind::BrokenPipe.into()),
+            "arm_toolchain::cli::non_interactive",
+        ),
+        (
+            InquireError::IO(io::ErrorKind::PermissionDenied.into()),
+            "arm_toolchain::cli::interactive_prompt_failed",
+        ),
+        (
+            InquireError::OperationInterrupted,
+            "arm_toolchain::cli::interactive_prompt_failed",
+        ),
+    ] {
+        let description = format!("{error:?}");
+        let error = CliError::from_prompt_error(error, "--yes");
+        assert_eq!(error.stable_code(), code, "{description}");
+    }
+}
@@ -0,0 +1,56 @@
+use std::fs;
+
+use crate::{
+    Cli,
+    common::{MockGitHub, MockRelease, MockServer},
+    stderr, stdout,
+};
+
+/// Runs `list` and returns what it printed, with the home directory replaced by `~`.
+fn list(cli: &Cli, server: &MockServer) -> String {
+    let output = cli.run(server, &["list"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    stdout(&output).replace(cli.home().to_str().unwrap(), "~")
+}
+
+#[test]
+fn leaves_a_fresh_profile() {
+    let server = MockServer::github(MockGitHub::new([
+        MockRelease::toolchain("21.1.0"),
+        MockRelease::toolchain("20.1.0"),
+    ]));
+    let cli = Cli::default();
+    for version in ["21.1.0", "20.1.0"] {
+        let output = cli.run(&server, &["install", version, "--yes"]);
+        assert!(output.status.success(), "{}", stderr(&output));
+    }
+    let config = cli.home().join(".config/arm-toolchain/config.toml");
+    fs::create_dir_all(config.parent().unwrap()).unwrap();
+    fs::write(&config, "notify_updates = false\n").unwrap();
+
+    let output = cli.run(&server, &["purge-all", "--yes"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let summary = stdout(&output);
+    for version in ["21.1.0", "20.1.0"] {
+        let path = cli.toolchains_path().join("embedded").join(version);
+        assert!(
+            summary.contains(&format!("Deleted toolchain {}", path.display())),
+            "{summary}"
+        );
+    }
+    assert!(
+        summary.contains(&format!("Deleted {}", config.display())),
+        "{summary}"
+    );
+    for dir in [
+        ".local/share/arm-toolchain",
+        ".cache/arm-toolchain",
+        ".config/arm-toolchain",
+    ] {
+        assert!(!cli.home().join(dir).exists(), "{dir} was left behind");
+    }
+
+    let fresh = Cli::default();
+    assert_eq!(list(&cli, &server), list(&fresh, &server));
+}
@@ -0,0 +1,237 @@
+use std::{fs, path::PathBuf};
+
+use serde_json::Value;
+
+use crate::{
+    Cli,
+    common::{MockGitHub, MockRelease, MockServer},
+    stderr,
+};
+
+/// A home directory with 21.1.0, 20.1.0 and 19.1.5 installed.
+fn installed(server: &MockServer) -> Cli {
+    let cli = Cli::default();
+    for version in ["21.1.0", "20.1.0", "19.1.5"] {
+        let output = cli.run(server, &["install", version, "--yes"]);
+        assert!(output.status.success(), "{}", stderr(&output));
+    }
+    cli
+}
+
+fn server() -> MockServer {
+    MockServer::github(MockGitHub::new([
+        MockRelease::toolchain("21.1.0"),
+        MockRelease::toolchain("20.1.0"),
+        MockRelease::toolchain("19.1.5"),
+    ]))
+}
+
+fn installed_versions(cli: &Cli) -> Vec<String> {
+    let mut versions: Vec<_> = fs::read_dir(cli.toolchains_path().join("embedded"))
+        .unwrap()
+        .map(|entry| entry.unwrap())
+        .filter(|entry| entry.file_type().unwrap().is_dir())
+        .map(|entry| entry.file_name().into_string().unwrap())
+        .collect();
+    versions.sort();
+    versions
+}
+
+#[test]
+fn removes_several_versions() {
+    let server = server();
+    let cli = installed(&server);
+
+    let output = cli.run(
+        &server,
+        &["remove", "19.1.5", "21.1.0", "--yes", "--output", "json"],
+    );
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let summary = &report["result"];
+    let mut removed: Vec<_> = summary["removed"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|report| report["version"].as_str().unwrap())
+        .collect();
+    removed.sort();
+    assert_eq!(removed, ["19.1.5", "21.1.0"]);
+    let bytes: u64 = summary["removed"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|report| report["bytes_removed"].as_u64().unwrap())
+        .sum();
+    assert!(bytes > 0);
+    assert_eq!(summary["bytes_removed"], bytes);
+    assert_eq!(installed_versions(&cli), ["20.1.0"]);
+}
+
+#[test]
+fn reports_every_missing_version_and_removes_nothing() {
+    let server = server();
+    let cli = installed(&server);
+
+    let output = cli.run(
+        &server,
+        &["remove", "21.1.0", "18.1.3", "20.1.0", "17.0.1", "--yes"],
+    );
+
+    assert!(!output.status.success());
+    let error = stderr(&output);
+    assert!(
+        error.contains("arm_toolchain::cli::remove_missing"),
+        "{error}"
+    );
+    assert!(
+        error.contains("v17.0.1") && error.contains("v18.1.3"),
+        "{error}"
+    );
+    assert_eq!(installed_versions(&cli), ["19.1.5", "20.1.0", "21.1.0"]);
+}
+
+#[test]
+fn suggests_a_version_when_one_is_missing() {
+    let server = server();
+    let cli = installed(&server);
+
+    let output = cli.run(&server, &["remove", "20.1.0", "21.1.1", "--yes"]);
+
+    assert!(!output.status.success());
+    let error = stderr(&output);
+    assert!(
+        error.contains("arm_toolchain::cli::remove_missing"),
+        "{error}"
+    );
+    assert!(
+        error.contains("The toolchain v21.1.1 is not installed."),
+        "{error}"
+    );
+    assert!(error.contains("v21.1.0"), "{error}");
+    assert_eq!(installed_versions(&cli), ["19.1.5", "20.1.0", "21.1.0"]);
+}
+
+#[test]
+fn all_must_be_the_only_version() {
+    let server = server();
+    let cli = installed(&server);
+
+    let output = cli.run(&server, &["remove", "all", "21.1.0", "--yes"]);
+
+    assert!(!output.status.success());
+    let error = stderr(&output);
+    assert!(
+        error.contains("\"all\" can't be combined with other versions"),
+        "{error}"
+    );
+    assert_eq!(installed_versions(&cli), ["19.1.5", "20.1.0", "21.1.0"]);
+}
+
+/// Pins 20.1.0 in a project directory, and returns the pin file's path.
+fn pinned_project(cli: &Cli, server: &MockServer) -> PathBuf {
+    let project = cli.home().join("project");
+    fs::create_dir(&project).unwrap();
+
+    let output = cli.run(
+        server,
+        &[
+            "use",
+            "20.1.0",
+            "--local",
+            "--path",
+            project.to_str().unwrap(),
+            "--yes",
+        ],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    project.join("arm-toolchain.toml")
+}
+
+#[test]
+fn reports_pins_of_removed_versions() {
+    let server = server();
+    let cli = installed(&server);
+    let pin_path = pinned_project(&cli, &server);
+    let pin = fs::read_to_string(&pin_path).unwrap();
+
+    let output = cli.run(&server, &["remove", "20.1.0", "--yes", "--output", "json"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let dangling = &report["result"]["dangling_pins"][0];
+    assert_eq!(dangling["version"], "20.1.0");
+    assert_eq!(dangling.get("updated_to"), None);
+    assert!(stderr(&output).contains("which was removed"));
+    assert_eq!(fs::read_to_string(&pin_path).unwrap(), pin);
+}
+
+#[test]
+fn repins_to_the_given_version_keeping_unknown_fields() {
+    let server = server();
+    let cli = installed(&server);
+    let pin_path = pinned_project(&cli, &server);
+    let pin = fs::read_to_string(&pin_path).unwrap();
+    fs::write(&pin_path, format!("{pin}channel = \"beta\"\n")).unwrap();
+
+    let output = cli.run(
+        &server,
+        &[
+            "remove",
+            "20.1.0",
+            "--yes",
+            "--update-pins-to",
+            "21.1.0",
+            "--output",
+            "json",
+        ],
+    );
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["result"]["dangling_pins"][0]["updated_to"], "21.1.0");
+    assert_eq!(
+        fs::read_to_string(&pin_path).unwrap(),
+        "version = \"21.1.0\"\nchannel = \"beta\"\n"
+    );
+
+    // The new pin is written next to the old one and renamed over it.
+    let mut project_files: Vec<_> = fs::read_dir(pin_path.parent().unwrap())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    project_files.sort();
+    assert_eq!(project_files, ["arm-toolchain.toml"]);
+}
+
+#[test]
+fn does_not_repin_hand_edited_pins() {
+    let server = server();
+    let cli = installed(&server);
+    let pin_path = pinned_project(&cli, &server);
+    let pin = format!("# Matches CI\n{}", fs::read_to_string(&pin_path).unwrap());
+    fs::write(&pin_path, &pin).unwrap();
+
+    let output = cli.run(
+        &server,
+        &[
+            "remove",
+            "20.1.0",
+            "--yes",
+            "--update-pins-to",
+            "21.1.0",
+            "--output",
+            "json",
+        ],
+    );
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let dangling = &report["result"]["dangling_pins"][0];
+    assert_eq!(dangling["version"], "20.1.0");
+    assert_eq!(dangling.get("updated_to"), None);
+    assert!(stderr(&output).contains("edited by hand"));
+    assert_eq!(fs::read_to_string(&pin_path).unwrap(), pin);
+}
@@ -0,0 +1,244 @@
+use std::path::PathBuf;
+
+use serde_json::Value;
+
+use crate::{
+    Cli,
+    common::{self, MockGitHub, MockRelease, MockServer},
+    stderr, stdout,
+};
+
+/// A home directory with 21.1.0 installed and active.
+fn installed(server: &MockServer) -> Cli {
+    let cli = Cli::default();
+    let output = cli.run(server, &["install", "21.1.0", "--yes"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    cli
+}
+
+#[test]
+fn env_record_describes_the_toolchain_and_environment() {
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let cli = installed(&server);
+
+    // The command prints the record, which shows that it was written before the command ran.
+    let output = cli.run(
+        &server,
+        &[
+            "run",
+            "--emit-env-record",
+            "record.json",
+            "cat",
+            "record.json",
+        ],
+    );
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let record: Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(record["toolchain_version"], "21.1.0");
+    let install_path = PathBuf::from(record["install_path"].as_str().unwrap());
+    assert!(install_path.starts_with(cli.toolchains_path()), "{record}");
+    assert!(install_path.join("bin/clang").is_file(), "{record}");
+    // The fixture's clang echoes its arguments.
+    assert_eq!(record["clang_version"], "clang --version");
+    let env = record["env"].as_object().unwrap();
+    assert_eq!(env["TARGET_CC"], "clang");
+    assert_eq!(env["TARGET_AR"], "llvm-ar");
+    let path = env["PATH"].as_str().unwrap();
+    assert!(
+        path.starts_with(install_path.join("bin").to_str().unwrap()),
+        "{path}"
+    );
+}
+
+#[test]
+fn env_record_lists_the_whole_isolated_environment() {
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let cli = installed(&server);
+
+    let output = cli
+        .command(&server)
+        .env("KEPT", "kept")
+        .env("DROPPED", "dropped")
+        .args([
+            "run",
+            "--isolate",
+            "--keep",
+            "KEPT",
+            "--no-cross-env",
+            "--emit-env-record",
+            "record.json",
+            "true",
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let record: Value =
+        serde_json::from_str(&std::fs::read_to_string(cli.home().join("record.json")).unwrap())
+            .unwrap();
+    let mut names: Vec<_> = record["env"].as_object().unwrap().keys().collect();
+    names.sort();
+    assert_eq!(names, ["HOME", "KEPT", "PATH"]);
+    assert_eq!(record["env"]["HOME"], cli.home().to_str().unwrap());
+}
+
+#[test]
+fn isolated_command_only_sees_the_minimal_environment() {
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let cli = installed(&server);
+
+    let output = cli
+        .command(&server)
+        .env("KEPT", "kept")
+        .env("DROPPED", "dropped")
+        .args(["run", "--isolate", "--keep", "KEPT", "env"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let stdout = stdout(&output);
+    let vars: Vec<_> = stdout
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .collect();
+    for (name, _) in &vars {
+        assert!(
+            ["HOME", "TMPDIR", "PATH", "TARGET_CC", "TARGET_AR", "KEPT"].contains(name),
+            "{stdout}"
+        );
+    }
+    assert!(vars.contains(&("KEPT", "kept")), "{stdout}");
+    assert!(
+        vars.contains(&("HOME", cli.home().to_str().unwrap())),
+        "{stdout}"
+    );
+    let path = vars.iter().find(|(name, _)| *name == "PATH").unwrap().1;
+    let bin_dir = cli.toolchains_path().join("embedded/21.1.0/bin");
+    assert_eq!(
+        path,
+        format!("{}:/usr/local/bin:/usr/bin:/bin", bin_dir.display())
+    );
+}
+
+#[test]
+fn no_record_without_the_flag() {
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let cli = installed(&server);
+    let before = common::list_tree(cli.home());
+
+    let output = cli.run(&server, &["run", "true"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(common::list_tree(cli.home()), before);
+}
+
+/// Writes the CLI's config file.
+fn write_config(cli: &Cli, contents: &str) {
+    let config_dir = cli.home().join(".config/arm-toolchain");
+    std::fs::create_dir_all(&config_dir).unwrap();
+    std::fs::write(config_dir.join("config.toml"), contents).unwrap();
+}
+
+#[test]
+fn config_env_is_set_for_the_command() {
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let cli = installed(&server);
+    write_config(
+        &cli,
+        "[run.env]\n\
+         TOOLCHAIN_ROOT = \"{install_dir}\"\n\
+         EXTRA_CFLAGS = \"--sysroot={sysroot:arm-none-eabi:armv7a_soft_nofp}\"\n\
+         TARGET_CC = \"{bin_dir}/clang\"\n",
+    );
+
+    let output = cli.run(
+        &server,
+        &[
+            "run",
+            "sh",
+            "-c",
+            "printf '%s\\n' \"$TOOLCHAIN_ROOT\" \"$EXTRA_CFLAGS\" \"$TARGET_CC\"",
+        ],
+    );
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let root = cli.toolchains_path().join("embedded/21.1.0");
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "{root}\n--sysroot={root}/lib/clang-runtimes/arm-none-eabi/armv7a_soft_nofp\n\
+             {root}/bin/clang\n",
+            root = root.display()
+        )
+    );
+}
+
+#[test]
+fn config_env_with_an_unknown_placeholder_is_an_error() {
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let cli = installed(&server);
+    write_config(&cli, "[run.env]\nTOOLCHAIN_ROOT = \"{root}\"\n");
+
+    let output = cli.run(&server, &["run", "true"]);
+
+    assert!(!output.status.success());
+    let error = stderr(&output);
+    assert!(error.contains("Invalid placeholder \"{root}\""), "{error}");
+    assert!(
+        error.contains("{install_dir}") && error.contains("{sysroot:<triple>:<variant>}"),
+        "{error}"
+    );
+}
+
+/// A command that keeps the CPU busy for a moment, and then exits with 3.
+#[cfg(unix)]
+const BUSY_LOOP: &[&str] = &[
+    "sh",
+    "-c",
+    "i=0; while [ $i -lt 200000 ]; do i=$((i + 1)); done; exit 3",
+];
+
+#[cfg(unix)]
+#[test]
+fn time_reports_the_command_usage() {
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let cli = installed(&server);
+    let mut args = vec!["run", "--time"];
+    args.extend(BUSY_LOOP);
+
+    let output = cli.run(&server, &args);
+
+    assert_eq!(output.status.code(), Some(3), "{}", stderr(&output));
+    let report = stderr(&output);
+    let line = report
+        .lines()
+        .find(|line| line.contains("Finished"))
+        .unwrap_or_else(|| panic!("{report}"));
+    for measurement in ["s wall, ", "s user, ", "s system, ", " max RSS"] {
+        assert!(line.contains(measurement), "{line}");
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn time_report_can_be_json() {
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let cli = installed(&server);
+    let mut args = vec!["run", "--time", "--output", "json"];
+    args.extend(BUSY_LOOP);
+
+    let output = cli.run(&server, &args);
+
+    assert_eq!(output.status.code(), Some(3), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "");
+    let report = stderr(&output);
+    let report: Value = serde_json::from_str(report.lines().last().unwrap()).unwrap();
+    assert_eq!(report["exit_code"], 3);
+    let usage = &report["usage"];
+    assert!(usage["wall_time_secs"].as_f64().unwrap() > 0.0, "{report}");
+    let cpu_time =
+        usage["user_time_secs"].as_f64().unwrap() + usage["system_time_secs"].as_f64().unwrap();
+    assert!(cpu_time > 0.0, "{report}");
+    assert!(usage["max_rss_bytes"].as_u64().unwrap() > 0, "{report}");
+}
@@ -0,0 +1,109 @@
+use std::{fs, process::Output};
+
+use crate::{
+    Cli,
+    common::{MockGitHub, MockRelease, MockServer},
+    stderr, stdout,
+};
+
+/// Runs `setup` in a bash session, answering its questions with `answers`.
+fn setup(cli: &Cli, server: &MockServer, answers: &str) -> Output {
+    let output = cli
+        .command(server)
+        .args(["setup"])
+        .env("SHELL", "/bin/bash")
+        .env("ARM_TOOLCHAIN_SETUP_ANSWERS", answers)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    output
+}
+
+fn server() -> MockServer {
+    MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]))
+}
+
+fn completions_file(cli: &Cli) -> std::path::PathBuf {
+    cli.home()
+        .join(".local/share/bash-completion/completions/arm-toolchain")
+}
+
+#[test]
+fn installs_pins_and_shows_the_path_snippet() {
+    let cli = Cli::default();
+    let server = server();
+
+    let output = setup(&cli, &server, "yes,yes,yes,yes");
+
+    assert_eq!(
+        fs::read_to_string(cli.toolchains_path().join("embedded/current.txt")).unwrap(),
+        "21.1.0"
+    );
+    let pin = fs::read_to_string(cli.home().join("arm-toolchain.toml")).unwrap();
+    assert_eq!(pin, "version = \"21.1.0\"\n");
+    let completions = fs::read_to_string(completions_file(&cli)).unwrap();
+    assert!(completions.contains("complete -F _arm__toolchain"), "{completions}");
+
+    let stdout = stdout(&output);
+    assert!(
+        stdout.contains(
+            "Add this to ~/.bashrc:\n\n    export PATH=\"$(arm-toolchain locate bin):$PATH\""
+        ),
+        "{stdout}"
+    );
+    let summary = format!(
+        "These commands were run:\n    arm-toolchain use latest\n    arm-toolchain use v21.1.0 --local\n    arm-toolchain completions bash > {}\n",
+        completions_file(&cli).display()
+    );
+    assert!(stdout.contains(&summary), "{stdout}");
+}
+
+#[test]
+fn every_step_can_be_declined() {
+    let cli = Cli::default();
+    let server = server();
+
+    let output = setup(&cli, &server, "no,no,no");
+
+    // Without a toolchain, there's nothing to pin, so that question isn't asked.
+    assert!(!cli.toolchains_path().join("embedded/21.1.0").exists());
+    assert!(!cli.home().join("arm-toolchain.toml").exists());
+    assert!(!completions_file(&cli).exists());
+    let stdout = stdout(&output);
+    assert!(!stdout.contains("Pin "), "{stdout}");
+    assert!(!stdout.contains("export PATH"), "{stdout}");
+    assert!(stdout.contains("Nothing was changed."), "{stdout}");
+}
+
+#[test]
+fn declined_steps_are_skipped() {
+    for (answers, installed, pinned, completions, snippet) in [
+        ("yes,no,yes,yes", true, false, true, true),
+        ("yes,yes,no,yes", true, true, false, true),
+        ("yes,yes,yes,no", true, true, true, false),
+    ] {
+        let cli = Cli::default();
+        let server = server();
+
+        let output = setup(&cli, &server, answers);
+
+        let stdout = stdout(&output);
+        assert_eq!(
+            cli.toolchains_path().join("embedded/21.1.0").exists(),
+            installed,
+            "{answers}"
+        );
+        assert_eq!(
+            cli.home().join("arm-toolchain.toml").exists(),
+            pinned,
+            "{answers}"
+        );
+        assert_eq!(completions_file(&cli).exists(), completions, "{answers}");
+        assert_eq!(
+            stdout.contains("export PATH"),
+            snippet,
+            "{answers}: {stdout}"
+        );
+        assert_eq!(stdout.contains("--local"), pinned, "{answers}: {stdout}");
+    }
+}
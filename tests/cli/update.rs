@@ -0,0 +1,154 @@
+use std::{
+    fs,
+    process::Stdio,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    Cli,
+    common::{MockGitHub, MockRelease, MockServer},
+    stderr, stdout,
+};
+
+/// A home directory with 20.1.0 and 21.1.1 installed and 21.1.1 active, while 20.1.2 is
+/// available too.
+fn two_majors() -> (Cli, MockServer) {
+    let cli = Cli::default();
+    let server = MockServer::github(MockGitHub::new([
+        MockRelease::toolchain("20.1.2"),
+        MockRelease::toolchain("21.1.1"),
+        MockRelease::toolchain("20.1.0"),
+    ]));
+    for args in [
+        &["install", "20.1.0", "--yes"][..],
+        &["install", "21.1.1", "--yes"],
+        &["use", "21.1.1"],
+    ] {
+        let output = cli.run(&server, args);
+        assert!(output.status.success(), "{args:?}: {}", stderr(&output));
+    }
+    (cli, server)
+}
+
+fn active(cli: &Cli) -> String {
+    fs::read_to_string(cli.toolchains_path().join("embedded/current.txt"))
+        .unwrap()
+        .trim()
+        .to_string()
+}
+
+#[test]
+fn list_reports_updates_for_each_major() {
+    let (cli, server) = two_majors();
+
+    let output = cli.run(&server, &["list", "--check-updates"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let report = stdout(&output);
+    assert!(report.contains("- v20.1.0 (v20.1.2 available)"), "{report}");
+    assert!(report.contains("- v21.1.1 (up to date)"), "{report}");
+    assert!(report.contains("Latest: v21.1.1"), "{report}");
+}
+
+#[test]
+fn update_stays_within_the_major() {
+    let (cli, server) = two_majors();
+
+    let output = cli.run(&server, &["update", "--major", "20", "--yes"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(cli.toolchains_path().join("embedded/20.1.2").is_dir());
+    // The active toolchain is from another major, so it isn't replaced.
+    assert_eq!(active(&cli), "21.1.1");
+
+    let output = cli.run(&server, &["list", "--check-updates"]);
+    let report = stdout(&output);
+    assert!(
+        report.contains("- v20.1.0 (superseded by v20.1.2, already installed)"),
+        "{report}"
+    );
+    assert!(report.contains("- v20.1.2 (up to date)"), "{report}");
+}
+
+#[test]
+fn update_to_a_major_without_releases_fails() {
+    let (cli, server) = two_majors();
+
+    let output = cli.run(&server, &["update", "--major", "18", "--yes"]);
+
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("arm_toolchain::cli::no_release_in_major"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn once_installs_the_newest_and_removes_what_updates_superseded() {
+    let cli = Cli::default();
+    let old_server = MockServer::github(MockGitHub::new([
+        MockRelease::toolchain("20.1.0"),
+        MockRelease::toolchain("19.1.0"),
+    ]));
+    for args in [&["install", "19.1.0", "--yes"][..], &["update", "--once"]] {
+        let output = cli.run(&old_server, args);
+        assert!(output.status.success(), "{args:?}: {}", stderr(&output));
+    }
+    let config_dir = cli.home().join(".config/arm-toolchain");
+    fs::create_dir_all(&config_dir).unwrap();
+    fs::write(config_dir.join("config.toml"), "[update]\nkeep = 0\n").unwrap();
+    let server = MockServer::github(MockGitHub::new([
+        MockRelease::toolchain("20.1.2"),
+        MockRelease::toolchain("20.1.0"),
+        MockRelease::toolchain("19.1.0"),
+    ]));
+
+    let output = cli.run(&server, &["update", "--once", "--refresh"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(active(&cli), "20.1.2");
+    // 20.1.0 was only installed by `update`, but 19.1.0 was installed by hand.
+    assert!(!cli.toolchains_path().join("embedded/20.1.0").exists());
+    assert!(cli.toolchains_path().join("embedded/19.1.0").is_dir());
+}
+
+#[test]
+fn daemon_updates_and_stops_on_sigterm() {
+    let cli = Cli::default();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let daemon = cli
+        .command(&server)
+        .args(["update", "--daemon"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // The first check happens as soon as the daemon starts.
+    let current = cli.toolchains_path().join("embedded/current.txt");
+    let started = Instant::now();
+    while !current.exists() {
+        assert!(started.elapsed() < Duration::from_secs(30), "no update");
+        thread::sleep(Duration::from_millis(50));
+    }
+    assert_eq!(active(&cli), "21.1.0");
+
+    let second = cli.run(&server, &["update", "--daemon"]);
+    assert!(!second.status.success());
+    assert!(
+        stderr(&second).contains("arm_toolchain::cli::update_daemon_running"),
+        "{}",
+        stderr(&second)
+    );
+
+    // SAFETY: The daemon is a child process that hasn't been waited for, so its PID is
+    // still its own.
+    unsafe { libc::kill(daemon.id() as libc::pid_t, libc::SIGTERM) };
+    let output = daemon.wait_with_output().unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("Stopped"), "{}", stderr(&output));
+}
@@ -0,0 +1,106 @@
+use std::fs;
+
+use crate::{
+    Cli,
+    common::{MockGitHub, MockRelease, MockServer},
+    stderr, stdout,
+};
+
+/// A home directory with 20.1.0 and 21.1.0 installed, and 20.1.0 active.
+fn installed(server: &MockServer) -> Cli {
+    let cli = Cli::default();
+    for version in ["21.1.0", "20.1.0"] {
+        let output = cli.run(server, &["install", version, "--yes"]);
+        assert!(output.status.success(), "{}", stderr(&output));
+    }
+    let output = cli.run(server, &["use", "20.1.0"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    cli
+}
+
+fn server() -> MockServer {
+    MockServer::github(MockGitHub::new([
+        MockRelease::toolchain("21.1.0"),
+        MockRelease::toolchain("20.1.0"),
+    ]))
+}
+
+fn active_toolchain_file(cli: &Cli) -> String {
+    fs::read_to_string(cli.toolchains_path().join("embedded/current.txt")).unwrap()
+}
+
+#[test]
+fn local_path_reports_the_toolchain_used_there() {
+    let server = server();
+    let cli = installed(&server);
+    let active = active_toolchain_file(&cli);
+    let project = cli.home().join("project");
+    fs::create_dir_all(&project).unwrap();
+
+    let output = cli.run(
+        &server,
+        &[
+            "use",
+            "21.1.0",
+            "--local",
+            "--path",
+            project.to_str().unwrap(),
+        ],
+    );
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let report = stdout(&output);
+    assert!(
+        report.contains(&format!(
+            "The toolchain used in {} is now v21.1.0.",
+            project.display()
+        )),
+        "{report}"
+    );
+    assert!(
+        fs::read_to_string(project.join("arm-toolchain.toml"))
+            .unwrap()
+            .contains("21.1.0")
+    );
+    assert_eq!(active_toolchain_file(&cli), active);
+}
+
+#[test]
+fn local_path_ignores_the_pin_in_the_current_directory() {
+    let server = server();
+    let cli = installed(&server);
+    let active = active_toolchain_file(&cli);
+    // The CLI runs in the home directory, which is pinned to another version.
+    let output = cli.run(&server, &["use", "20.1.0", "--local"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let project = cli.home().join("project");
+    fs::create_dir_all(&project).unwrap();
+    fs::write(project.join("arm-toolchain.toml"), "version = \"20.1.0\"\n").unwrap();
+
+    let output = cli.run(
+        &server,
+        &[
+            "use",
+            "21.1.0",
+            "--local",
+            "--path",
+            project.to_str().unwrap(),
+        ],
+    );
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let report = stdout(&output);
+    assert!(
+        report.contains(&format!(
+            "The toolchain used in {} is now v21.1.0.",
+            project.display()
+        )),
+        "{report}"
+    );
+    assert!(
+        fs::read_to_string(cli.home().join("arm-toolchain.toml"))
+            .unwrap()
+            .contains("20.1.0")
+    );
+    assert_eq!(active_toolchain_file(&cli), active);
+}
@@ -0,0 +1,94 @@
+use std::fs;
+
+use crate::{
+    Cli,
+    common::{MockGitHub, MockRelease, MockServer},
+    stderr, stdout,
+};
+
+/// Installs 21.1.0, recording its file digests if `record_digests` is set.
+fn install(cli: &Cli, server: &MockServer, record_digests: bool) {
+    let mut args = vec!["install", "21.1.0", "--yes"];
+    if record_digests {
+        args.push("--record-digests");
+    }
+    let output = cli.run(server, &args);
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+#[test]
+fn clean_toolchain_exits_successfully() {
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let cli = Cli::default();
+    install(&cli, &server, true);
+
+    let output = cli.run(&server, &["verify", "--installed", "21.1.0"]);
+
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+}
+
+#[test]
+fn modified_toolchain_exits_with_code_4() {
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let cli = Cli::default();
+    install(&cli, &server, true);
+    let toolchain = cli.toolchains_path().join("embedded/21.1.0");
+    fs::write(
+        toolchain.join("lib/clang-runtimes/multilib.yaml"),
+        "MultilibVersion: 9.9\n",
+    )
+    .unwrap();
+
+    let output = cli.run(&server, &["verify", "--installed", "21.1.0"]);
+
+    assert_eq!(output.status.code(), Some(4), "{}", stderr(&output));
+    let report = stdout(&output);
+    assert!(
+        report.contains("modified lib/clang-runtimes/multilib.yaml"),
+        "{report}"
+    );
+
+    // The changes are listed under the same names as the local changes `install` warns about.
+    let output = cli.run(
+        &server,
+        &["verify", "--installed", "21.1.0", "--output", "json"],
+    );
+    assert_eq!(output.status.code(), Some(4), "{}", stderr(&output));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["result"]["status"], "modified");
+    assert_eq!(
+        report["result"]["modified"],
+        serde_json::json!(["lib/clang-runtimes/multilib.yaml"])
+    );
+    assert_eq!(report["result"]["added"], serde_json::json!([]));
+    assert_eq!(report["result"]["deleted"], serde_json::json!([]));
+}
+
+#[test]
+fn toolchain_without_digests_exits_with_code_5() {
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let cli = Cli::default();
+    install(&cli, &server, false);
+
+    let output = cli.run(&server, &["verify", "--installed", "21.1.0"]);
+
+    assert_eq!(output.status.code(), Some(5), "{}", stderr(&output));
+    let message = stderr(&output);
+    assert!(
+        message.contains("No file digests were recorded when v21.1.0 was installed"),
+        "{message}"
+    );
+    assert!(
+        message.contains("install --force --record-digests"),
+        "{message}"
+    );
+
+    let output = cli.run(
+        &server,
+        &["verify", "--installed", "21.1.0", "--output", "json"],
+    );
+
+    assert_eq!(output.status.code(), Some(5), "{}", stderr(&output));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["result"]["status"], "unavailable");
+}
@@ -0,0 +1,79 @@
+//! Concurrent calls for the same release metadata share one request to GitHub.
+
+mod common;
+
+use std::{thread, time::Duration};
+
+use arm_toolchain::toolchain::ToolchainVersion;
+use common::{MockGitHub, MockRelease, MockServer};
+use futures::future::join_all;
+
+const RELEASES_PATH: &str = "/repos/arm/arm-toolchain/releases";
+
+/// Starts a server that takes a while to list the releases, so that calls made at the same
+/// time are all waiting for it at once.
+fn slow_server() -> MockServer {
+    let github = MockGitHub::new([MockRelease::empty("21.1.0"), MockRelease::empty("20.1.0")]);
+    MockServer::start(move |request| {
+        thread::sleep(Duration::from_millis(300));
+        github.respond(request)
+    })
+}
+
+/// Returns how many times the server was asked for the list of releases.
+fn list_requests(server: &MockServer) -> usize {
+    server
+        .requests()
+        .iter()
+        .filter(|request| request.path.split('?').next() == Some(RELEASES_PATH))
+        .count()
+}
+
+#[tokio::test]
+async fn concurrent_calls_share_one_request() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = slow_server();
+    let client = common::client(dir.path(), &server).await;
+
+    let releases = join_all((0..10).map(|_| client.latest_release())).await;
+
+    for release in releases {
+        assert_eq!(
+            release.unwrap().version(),
+            &ToolchainVersion::named("21.1.0")
+        );
+    }
+    assert_eq!(list_requests(&server), 1);
+
+    // Within the TTL, later calls reuse what was fetched too.
+    client.latest_release().await.unwrap();
+    assert_eq!(list_requests(&server), 1);
+}
+
+#[tokio::test]
+async fn concurrent_calls_share_one_request_without_a_ttl() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = slow_server();
+    let client = common::client_builder(dir.path(), &server)
+        .release_cache_ttl(Duration::ZERO)
+        .release_memory_ttl(Duration::ZERO)
+        .build()
+        .await
+        .unwrap();
+
+    let releases = join_all((0..10).map(|_| client.latest_release())).await;
+
+    // Every call waited for the same request, which finished after they were made, so its
+    // result is fresh enough for all of them.
+    for release in releases {
+        assert_eq!(
+            release.unwrap().version(),
+            &ToolchainVersion::named("21.1.0")
+        );
+    }
+    assert_eq!(list_requests(&server), 1);
+
+    // Without a TTL, a call made after that request finished asks again.
+    client.latest_release().await.unwrap();
+    assert_eq!(list_requests(&server), 2);
+}
@@ -0,0 +1,178 @@
+//! The diagnostic codes of errors are part of the public interface, since tools key off them,
+//! so the full set is listed here and any change to it has to be made on purpose.
+
+#![cfg(feature = "cli-core")]
+
+use std::{collections::BTreeSet, fs, path::Path};
+
+use arm_toolchain::{
+    cli::CliError,
+    toolchain::{ToolchainError, ToolchainVersion},
+};
+use miette::Diagnostic;
+
+/// Every code, sorted. Codes may be added, but never renamed or removed.
+const CODES: &[&str] = &[
+    "arm_toolchain::cli::ambiguous_version",
+    "arm_toolchain::cli::bundle_failed",
+    "arm_toolchain::cli::download_stalled",
+    "arm_toolchain::cli::install_cancelled",
+    "arm_toolchain::cli::interactive_prompt_failed",
+    "arm_toolchain::cli::invalid_alias",
+    "arm_toolchain::cli::invalid_config",
+    "arm_toolchain::cli::invalid_env_template",
+    "arm_toolchain::cli::invalid_pin",
+    "arm_toolchain::cli::invalid_pin_target",
+    "arm_toolchain::cli::no_previous_toolchain",
+    "arm_toolchain::cli::no_release_in_major",
+    "arm_toolchain::cli::no_runtime_for_target",
+    "arm_toolchain::cli::no_toolchain_enabled",
+    "arm_toolchain::cli::no_toolchains_installed",
+    "arm_toolchain::cli::non_interactive",
+    "arm_toolchain::cli::release_notes_missing",
+    "arm_toolchain::cli::remove_all_with_versions",
+    "arm_toolchain::cli::remove_missing",
+    "arm_toolchain::cli::setup_not_interactive",
+    "arm_toolchain::cli::toolchain_missing",
+    "arm_toolchain::cli::unknown_alias",
+    "arm_toolchain::cli::unknown_cpu",
+    "arm_toolchain::cli::unsupported_host",
+    "arm_toolchain::cli::update_daemon_running",
+    "arm_toolchain::extract::contents_not_found",
+    "arm_toolchain::extract::dmg_failed",
+    "arm_toolchain::extract::dmg_not_supported",
+    "arm_toolchain::extract::suspicious_archive",
+    "arm_toolchain::extract::unsupported_format",
+    "arm_toolchain::extract::zip_failed",
+    "arm_toolchain::toolchain::asset_digest_unavailable",
+    "arm_toolchain::toolchain::asset_not_locked",
+    "arm_toolchain::toolchain::cancelled",
+    "arm_toolchain::toolchain::checksum_mismatch",
+    "arm_toolchain::toolchain::checksum_not_listed",
+    "arm_toolchain::toolchain::destination_not_empty",
+    "arm_toolchain::toolchain::digests_not_recorded",
+    "arm_toolchain::toolchain::download_failed",
+    "arm_toolchain::toolchain::download_stalled",
+    "arm_toolchain::toolchain::github_api",
+    "arm_toolchain::toolchain::incomplete_extraction",
+    "arm_toolchain::toolchain::install_reasons_locked",
+    "arm_toolchain::toolchain::invalid_active_toolchain_file",
+    "arm_toolchain::toolchain::invalid_asset_name",
+    "arm_toolchain::toolchain::invalid_github_api_url",
+    "arm_toolchain::toolchain::invalid_github_repo",
+    "arm_toolchain::toolchain::invalid_github_token",
+    "arm_toolchain::toolchain::invalid_install_reason",
+    "arm_toolchain::toolchain::invalid_mirror_url",
+    "arm_toolchain::toolchain::invalid_multilib",
+    "arm_toolchain::toolchain::invalid_release_lock",
+    "arm_toolchain::toolchain::invalid_toolchain_name",
+    "arm_toolchain::toolchain::invalid_user_agent",
+    "arm_toolchain::toolchain::io_error",
+    "arm_toolchain::toolchain::latest_release_not_found",
+    "arm_toolchain::toolchain::locked_checksum_mismatch",
+    "arm_toolchain::toolchain::newer_layout",
+    "arm_toolchain::toolchain::no_home_directory",
+    "arm_toolchain::toolchain::no_matching_release",
+    "arm_toolchain::toolchain::not_installed",
+    "arm_toolchain::toolchain::offline",
+    "arm_toolchain::toolchain::rate_limited",
+    "arm_toolchain::toolchain::release_asset_not_found",
+    "arm_toolchain::toolchain::remove_failed",
+    "arm_toolchain::toolchain::similar_install_exists",
+    "arm_toolchain::toolchain::toolchain_in_use",
+    "arm_toolchain::toolchain::trash_op_failed",
+    "arm_toolchain::toolchain::unsupported_destination",
+    "arm_toolchain::toolchain::unsupported_host",
+];
+
+#[test]
+fn codes_are_unchanged() {
+    let codes: Vec<_> = CliError::all_codes()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+
+    assert_eq!(codes, CODES);
+    assert_eq!(
+        CliError::all_codes().count(),
+        CODES.len(),
+        "a code is listed twice"
+    );
+}
+
+#[test]
+fn toolchain_codes_are_a_subset() {
+    for code in ToolchainError::all_codes() {
+        assert!(code.starts_with("arm_toolchain::"), "{code}");
+        assert!(CODES.contains(&code), "{code}");
+    }
+}
+
+/// Finds the codes given to `#[diagnostic(code(...))]` attributes in the crate's source, so
+/// that a new error can't be added without listing its code. `stable_code` looks the codes up
+/// in these lists, so the library's own errors have to be listed by `ToolchainError` too.
+#[test]
+fn every_declared_code_is_listed() {
+    let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+    let mut declared = BTreeSet::new();
+    let mut declared_by_library = BTreeSet::new();
+    let mut pending = vec![src.clone()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            if path.extension().is_none_or(|extension| extension != "rs") {
+                continue;
+            }
+            let is_cli = path.starts_with(src.join("cli")) || path == src.join("cli.rs");
+            let source = fs::read_to_string(&path).unwrap();
+            for (start, _) in source.match_indices("code(arm_toolchain::") {
+                let code = &source[start + "code(".len()..];
+                let code = code[..code.find(')').unwrap()].to_string();
+                if !is_cli {
+                    declared_by_library.insert(code.clone());
+                }
+                declared.insert(code);
+            }
+        }
+    }
+
+    let listed: BTreeSet<_> = CliError::all_codes().map(str::to_string).collect();
+    assert_eq!(declared, listed);
+    let listed_by_library: BTreeSet<_> = ToolchainError::all_codes().map(str::to_string).collect();
+    assert_eq!(declared_by_library, listed_by_library);
+}
+
+#[test]
+fn stable_codes_match_the_diagnostic_codes() {
+    let errors = [
+        CliError::RemoveAllWithVersions,
+        CliError::NonInteractive { flag: "--yes" },
+        CliError::CannotRemoveMissingToolchains {
+            versions: vec![ToolchainVersion::named("21.1.0")],
+        },
+        CliError::Toolchain(ToolchainError::ChecksumNotListed {
+            file_name: "ATfE-21.1.0-Linux-x86_64.tar.xz".to_string(),
+            path: "approved.sha256".into(),
+        }),
+        CliError::Toolchain(ToolchainError::ChecksumMismatch {
+            file_name: "ATfE-21.1.0-Linux-x86_64.tar.xz".to_string(),
+            expected: "0".repeat(64),
+            actual: "1".repeat(64),
+        }),
+        CliError::Toolchain(ToolchainError::Cancelled),
+        CliError::Toolchain(ToolchainError::Io(std::io::ErrorKind::NotFound.into())),
+    ];
+
+    for error in errors {
+        assert_eq!(
+            error.code().map(|code| code.to_string()).as_deref(),
+            Some(error.stable_code()),
+            "{error:?}"
+        );
+        assert!(CODES.contains(&error.stable_code()), "{error:?}");
+    }
+}
@@ -0,0 +1,628 @@
+//! A local HTTP server that stands in for GitHub, and toolchain archives for it to serve.
+//!
+//! Each test starts its own [`MockServer`] with a handler that answers every request. Most
+//! handlers fall back to [`MockGitHub::respond`], which serves releases the way the GitHub
+//! API and its browser download URLs do, including `Range` and `If-Range` support.
+
+#![allow(dead_code)]
+
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    path::Path,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use arm_toolchain::toolchain::{
+    DownloadVia, HostArch, HostOS, ToolchainClient, ToolchainClientBuilder, ToolchainRelease,
+};
+use data_encoding::HEXLOWER;
+use octocrab::models::repos::Asset;
+use sha2::{Digest, Sha256};
+
+/// A request received by a [`MockServer`].
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    /// The path and query, such as `/download/ATfE-21.1.0-Linux-x86_64.tar.xz`.
+    pub path: String,
+    /// The headers, with lowercase names.
+    pub headers: Vec<(String, String)>,
+    /// The server's base URL, such as `http://127.0.0.1:1234`.
+    pub base_url: String,
+}
+
+impl Request {
+    /// Returns the value of a header, looked up case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let name = name.to_ascii_lowercase();
+        self.headers
+            .iter()
+            .find(|(header, _)| *header == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the last segment of the path, without the query.
+    pub fn file_name(&self) -> &str {
+        let path = self.path.split('?').next().unwrap_or_default();
+        path.rsplit('/').next().unwrap_or_default()
+    }
+}
+
+/// A response for a [`MockServer`] to send.
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// Sends the body in pieces of this size, waiting for the given time between them.
+    pub throttle: Option<(usize, Duration)>,
+    /// Closes the connection after sending this much of the body, as if it dropped.
+    pub cut_after: Option<usize>,
+    /// Waits this long before closing a connection that was cut, as if it stalled.
+    pub stall: Option<Duration>,
+}
+
+impl Response {
+    pub fn new(status: u16, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status,
+            headers: vec![],
+            body: body.into(),
+            throttle: None,
+            cut_after: None,
+            stall: None,
+        }
+    }
+
+    pub fn ok(body: impl Into<Vec<u8>>) -> Self {
+        Self::new(200, body)
+    }
+
+    pub fn not_found() -> Self {
+        Self::new(404, Vec::new())
+    }
+
+    pub fn header(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.headers.push((name.to_string(), value.into()));
+        self
+    }
+
+    pub fn throttle(mut self, piece_size: usize, interval: Duration) -> Self {
+        self.throttle = Some((piece_size, interval));
+        self
+    }
+
+    pub fn cut_after(mut self, bytes: usize) -> Self {
+        self.cut_after = Some(bytes);
+        self
+    }
+
+    pub fn stall(mut self, bytes: usize, duration: Duration) -> Self {
+        self.cut_after = Some(bytes);
+        self.stall = Some(duration);
+        self
+    }
+}
+
+type Handler = dyn Fn(&Request) -> Response + Send + Sync;
+
+/// A server on a random local port, which answers each request on its own thread until the
+/// test exits.
+pub struct MockServer {
+    base_url: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl MockServer {
+    pub fn start(handler: impl Fn(&Request) -> Response + Send + Sync + 'static) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(vec![]));
+        let handler: Arc<Handler> = Arc::new(handler);
+
+        thread::spawn({
+            let base_url = base_url.clone();
+            let requests = requests.clone();
+            move || {
+                for stream in listener.incoming().flatten() {
+                    let base_url = base_url.clone();
+                    let requests = requests.clone();
+                    let handler = handler.clone();
+                    thread::spawn(move || {
+                        _ = respond(stream, &base_url, &requests, &*handler);
+                    });
+                }
+            }
+        });
+
+        Self { base_url, requests }
+    }
+
+    /// Starts a server that only serves the given releases.
+    pub fn github(github: MockGitHub) -> Self {
+        Self::start(move |request| github.respond(request))
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Returns the full URL of a path on the server.
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{path}", self.base_url)
+    }
+
+    /// Returns every request received so far, in order.
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Returns the requests for paths that end with the given file name.
+    pub fn requests_for(&self, file_name: &str) -> Vec<Request> {
+        self.requests()
+            .into_iter()
+            .filter(|request| request.file_name() == file_name)
+            .collect()
+    }
+}
+
+/// Reads one request, answers it, and closes the connection.
+fn respond(
+    mut stream: TcpStream,
+    base_url: &str,
+    requests: &Mutex<Vec<Request>>,
+    handler: &Handler,
+) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut headers = vec![];
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let name = name.trim().to_ascii_lowercase();
+            let value = value.trim().to_string();
+            if name == "content-length" {
+                content_length = value.parse().unwrap_or(0);
+            }
+            headers.push((name, value));
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+
+    let mut parts = request_line.split_whitespace();
+    let request = Request {
+        method: parts.next().unwrap_or_default().to_string(),
+        path: parts.next().unwrap_or_default().to_string(),
+        headers,
+        base_url: base_url.to_string(),
+    };
+    requests.lock().unwrap().push(request.clone());
+    let response = handler(&request);
+
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        reason_phrase(response.status),
+        response.body.len()
+    );
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes())?;
+    if request.method == "HEAD" {
+        return stream.flush();
+    }
+
+    let body = match response.cut_after {
+        Some(limit) => &response.body[..limit.min(response.body.len())],
+        None => &response.body[..],
+    };
+    match response.throttle {
+        Some((piece_size, interval)) => {
+            // Sending the body in small pieces makes it arrive in chunks of about the same
+            // size each time, like it would over a real network.
+            stream.set_nodelay(true)?;
+            for piece in body.chunks(piece_size) {
+                stream.write_all(piece)?;
+                thread::sleep(interval);
+            }
+        }
+        None => stream.write_all(body)?,
+    }
+    stream.flush()?;
+
+    if let Some(stall) = response.stall {
+        thread::sleep(stall);
+    }
+    Ok(())
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        206 => "Partial Content",
+        304 => "Not Modified",
+        404 => "Not Found",
+        416 => "Range Not Satisfiable",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "Status",
+    }
+}
+
+/// Answers a request for a file, honoring `Range` and `If-Range` like GitHub's asset storage
+/// does.
+pub fn file_response(request: &Request, data: &[u8], etag: Option<&str>) -> Response {
+    let with_etag = |response: Response| match etag {
+        Some(etag) => response.header("ETag", etag),
+        None => response,
+    };
+    let full = with_etag(Response::ok(data));
+
+    let Some(range) = request.header("range") else {
+        return full;
+    };
+    // A changed (or missing) ETag means the partial download is of something else.
+    if let Some(if_range) = request.header("if-range")
+        && etag != Some(if_range)
+    {
+        return full;
+    }
+
+    let Some((start, end)) = range
+        .strip_prefix("bytes=")
+        .and_then(|range| range.split_once('-'))
+    else {
+        return full;
+    };
+    let len = data.len();
+    let start: usize = start.parse().unwrap_or(0);
+    let end: usize = end
+        .parse()
+        .map_or(len.saturating_sub(1), |end: usize| end.min(len - 1));
+    if start >= len || start > end {
+        return Response::new(416, Vec::new()).header("Content-Range", format!("bytes */{len}"));
+    }
+
+    with_etag(
+        Response::new(206, &data[start..=end])
+            .header("Content-Range", format!("bytes {start}-{end}/{len}")),
+    )
+}
+
+/// A release served by [`MockGitHub`].
+#[derive(Debug, Clone)]
+pub struct MockRelease {
+    pub tag_name: String,
+    pub prerelease: bool,
+    /// Reports the size of every asset as zero, like some mirrors and proxies do.
+    pub sizes_unknown: bool,
+    /// The release's description, which is `null` if it's `None`.
+    pub body: Option<String>,
+    pub assets: Vec<MockAsset>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MockAsset {
+    pub name: String,
+    pub data: Vec<u8>,
+    pub etag: Option<String>,
+    /// The digest GitHub reports for the asset, such as `sha256:<hex>`.
+    pub digest: Option<String>,
+}
+
+impl MockRelease {
+    /// A release of the Arm Toolchain for Embedded with a toolchain for the current host and
+    /// its checksum file.
+    pub fn toolchain(version: &str) -> Self {
+        let name = host_asset_name(version);
+        let archive = toolchain_archive(name.trim_end_matches(".tar.xz"));
+        Self::empty(version).asset_with_checksum(&name, archive)
+    }
+
+    /// Like [`toolchain`](Self::toolchain), but with the archive's top-level directory named
+    /// after `content_version` instead, as if the release was packaged from another build.
+    pub fn toolchain_containing(version: &str, content_version: &str) -> Self {
+        let name = host_asset_name(version);
+        let root = host_asset_name(content_version);
+        let archive = toolchain_archive(root.trim_end_matches(".tar.xz"));
+        Self::empty(version).asset_with_checksum(&name, archive)
+    }
+
+    /// A release of the Arm Toolchain for Embedded without any assets.
+    pub fn empty(version: &str) -> Self {
+        Self {
+            tag_name: format!("release-{version}-ATfE"),
+            prerelease: false,
+            sizes_unknown: false,
+            body: Some("Release notes".to_string()),
+            assets: vec![],
+        }
+    }
+
+    pub fn asset(mut self, name: &str, data: Vec<u8>) -> Self {
+        let etag = format!("\"{}\"", &sha256_hex(&data)[..16]);
+        self.assets.push(MockAsset {
+            name: name.to_string(),
+            data,
+            etag: Some(etag),
+            digest: None,
+        });
+        self
+    }
+
+    /// Adds an asset and a `.sha256` file with its checksum.
+    pub fn asset_with_checksum(self, name: &str, data: Vec<u8>) -> Self {
+        let checksum_file = format!("{}  {name}\n", sha256_hex(&data));
+        self.asset(name, data)
+            .asset(&format!("{name}.sha256"), checksum_file.into_bytes())
+    }
+
+    /// Returns the data of the asset with the given name.
+    pub fn data(&self, name: &str) -> &[u8] {
+        &self
+            .assets
+            .iter()
+            .find(|asset| asset.name == name)
+            .expect("the release has the asset")
+            .data
+    }
+
+    fn to_json(&self, base_url: &str) -> serde_json::Value {
+        let assets = self
+            .assets
+            .iter()
+            .enumerate()
+            .map(|(id, asset)| {
+                let mut json = serde_json::json!({
+                    "url": format!("{base_url}/assets/{}", asset.name),
+                    "browser_download_url": format!("{base_url}/download/{}/{}", self.tag_name, asset.name),
+                    "id": id + 1,
+                    "node_id": "asset",
+                    "name": asset.name,
+                    "label": null,
+                    "state": "uploaded",
+                    "content_type": "application/octet-stream",
+                    "size": if self.sizes_unknown { 0 } else { asset.data.len() },
+                    "download_count": 0,
+                    "created_at": "2025-01-01T00:00:00Z",
+                    "updated_at": "2025-01-01T00:00:00Z",
+                    "uploader": null,
+                });
+                if let Some(digest) = &asset.digest {
+                    json["digest"] = digest.clone().into();
+                }
+                json
+            })
+            .collect::<Vec<_>>();
+
+        serde_json::json!({
+            "url": format!("{base_url}/release"),
+            "html_url": format!("{base_url}/release"),
+            "assets_url": format!("{base_url}/release/assets"),
+            "upload_url": format!("{base_url}/release/assets"),
+            "id": 1,
+            "node_id": "release",
+            "tag_name": self.tag_name,
+            "target_commitish": "main",
+            "name": self.tag_name,
+            "draft": false,
+            "prerelease": self.prerelease,
+            "created_at": "2025-01-01T00:00:00Z",
+            "published_at": "2025-01-01T00:00:00Z",
+            "author": null,
+            "assets": assets,
+            "tarball_url": null,
+            "zipball_url": null,
+            "body": self.body,
+        })
+    }
+}
+
+/// Serves releases like the GitHub API (under `/repos/arm/arm-toolchain/releases`) and their
+/// assets (under `/download` and `/assets`).
+#[derive(Debug, Clone, Default)]
+pub struct MockGitHub {
+    pub releases: Vec<MockRelease>,
+    /// Lists this many releases per page, with a `Link` header to the next page, instead of
+    /// listing every release at once.
+    pub per_page: Option<usize>,
+}
+
+impl MockGitHub {
+    pub fn new(releases: impl IntoIterator<Item = MockRelease>) -> Self {
+        Self {
+            releases: releases.into_iter().collect(),
+            per_page: None,
+        }
+    }
+
+    /// Lists the releases `per_page` at a time, like GitHub does.
+    pub fn paginated(mut self, per_page: usize) -> Self {
+        self.per_page = Some(per_page);
+        self
+    }
+
+    pub fn respond(&self, request: &Request) -> Response {
+        let path = request.path.split('?').next().unwrap_or_default();
+        let base_url = &request.base_url;
+
+        if path == "/repos/arm/arm-toolchain/releases" {
+            let per_page = self.per_page.unwrap_or(self.releases.len().max(1));
+            let page: usize = request
+                .path
+                .split_once('?')
+                .into_iter()
+                .flat_map(|(_, query)| query.split('&'))
+                .find_map(|pair| pair.strip_prefix("page="))
+                .and_then(|page| page.parse().ok())
+                .unwrap_or(1);
+            let releases = self
+                .releases
+                .iter()
+                .skip((page - 1) * per_page)
+                .take(per_page)
+                .map(|release| release.to_json(base_url))
+                .collect::<Vec<_>>();
+            let response = Response::ok(serde_json::to_vec(&releases).unwrap())
+                .header("Content-Type", "application/json");
+            return match page * per_page < self.releases.len() {
+                true => response.header(
+                    "Link",
+                    format!(
+                        "<{base_url}{path}?per_page={per_page}&page={}>; rel=\"next\"",
+                        page + 1
+                    ),
+                ),
+                false => response,
+            };
+        }
+        if let Some(tag_name) = path.strip_prefix("/repos/arm/arm-toolchain/releases/tags/") {
+            return match self.releases.iter().find(|r| r.tag_name == tag_name) {
+                Some(release) => Response::ok(release.to_json(base_url).to_string())
+                    .header("Content-Type", "application/json"),
+                None => Response::new(
+                    404,
+                    r#"{"message":"Not Found","documentation_url":"https://docs.github.com/rest"}"#,
+                )
+                .header("Content-Type", "application/json"),
+            };
+        }
+        if path.starts_with("/download/") || path.starts_with("/assets/") {
+            let asset = self
+                .releases
+                .iter()
+                .flat_map(|release| &release.assets)
+                .find(|asset| asset.name == request.file_name());
+            return match asset {
+                Some(asset) => file_response(request, &asset.data, asset.etag.as_deref()),
+                None => Response::not_found(),
+            };
+        }
+
+        Response::not_found()
+    }
+}
+
+/// Returns the name of a toolchain asset for the current host, such as
+/// `ATfE-21.1.0-Linux-x86_64.tar.xz`.
+pub fn host_asset_name(version: &str) -> String {
+    let os = HostOS::try_current().unwrap();
+    let arch = HostArch::try_current().unwrap()[0];
+    format!("ATfE-{version}-{}-{}.tar.xz", os.as_ref(), arch.as_ref())
+}
+
+/// Builds a `.tar.xz` archive of a toolchain in the directory `root`, with everything that's
+/// checked after it's extracted.
+pub fn toolchain_archive(root: &str) -> Vec<u8> {
+    tar_xz(&toolchain_files(root))
+}
+
+/// Builds a `.tar.xz` archive of the given files, with their contents and permissions.
+pub fn tar_xz(files: &[(String, Vec<u8>, u32)]) -> Vec<u8> {
+    let mut tar = tar::Builder::new(liblzma::write::XzEncoder::new(Vec::new(), 0));
+    for (path, contents, mode) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(*mode);
+        tar.append_data(&mut header, path, &contents[..]).unwrap();
+    }
+    tar.into_inner().unwrap().finish().unwrap()
+}
+
+/// The files in a toolchain archive, with their contents and permissions.
+pub fn toolchain_files(root: &str) -> Vec<(String, Vec<u8>, u32)> {
+    let clang_name = if cfg!(windows) { "clang.exe" } else { "clang" };
+    vec![
+        (
+            format!("{root}/bin/{clang_name}"),
+            b"#!/bin/sh\necho clang \"$@\"\n".to_vec(),
+            0o755,
+        ),
+        (
+            format!("{root}/lib/clang-runtimes/multilib.yaml"),
+            b"MultilibVersion: 1.0\n".to_vec(),
+            0o644,
+        ),
+        // Incompressible, so the archive is neither suspiciously small nor much larger than
+        // what's extracted from it.
+        (
+            format!("{root}/lib/clang-runtimes/arm-none-eabi/lib/libc.a"),
+            noise(64 * 1024),
+            0o644,
+        ),
+    ]
+}
+
+/// Returns the same pseudo-random bytes on every run.
+pub fn noise(len: usize) -> Vec<u8> {
+    let mut rng = fastrand::Rng::with_seed(len as u64);
+    (0..len).map(|_| rng.u8(..)).collect()
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    HEXLOWER.encode(&Sha256::digest(data))
+}
+
+/// Returns a builder for a client whose directories are inside `dir` and which talks to the
+/// given server instead of GitHub.
+pub fn client_builder(dir: &Path, server: &MockServer) -> ToolchainClientBuilder {
+    ToolchainClient::builder()
+        .toolchains_path(dir.join("toolchains"))
+        .cache_path(dir.join("cache"))
+        .github_api_url(Some(server.base_url().to_string()))
+        .github_token(None)
+        .download_via(DownloadVia::Browser)
+}
+
+/// Builds a client with [`client_builder`].
+pub async fn client(dir: &Path, server: &MockServer) -> ToolchainClient {
+    client_builder(dir, server).build().await.unwrap()
+}
+
+/// Returns the release's asset for the current host.
+pub fn host_asset<'a>(client: &ToolchainClient, release: &'a ToolchainRelease) -> &'a Asset {
+    release
+        .asset_for_formats(
+            HostOS::try_current().unwrap(),
+            HostArch::try_current().unwrap(),
+            client.archive_formats(),
+        )
+        .unwrap()
+}
+
+/// Lists every file and directory under `dir`, relative to it and sorted, for comparing
+/// what changed in it.
+pub fn list_tree(dir: &Path) -> Vec<String> {
+    let mut entries = vec![];
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        let Ok(read_dir) = std::fs::read_dir(&current) else {
+            continue;
+        };
+        for entry in read_dir.flatten() {
+            let path = entry.path();
+            entries.push(
+                path.strip_prefix(dir)
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace('\\', "/"),
+            );
+            if entry.file_type().is_ok_and(|ty| ty.is_dir()) {
+                pending.push(path);
+            }
+        }
+    }
+    entries.sort();
+    entries
+}
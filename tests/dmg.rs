@@ -0,0 +1,158 @@
+//! DMG images are copied out of while attached, and always detached afterwards, even when the
+//! extraction is cancelled or abandoned.
+
+mod common;
+
+use std::{path::Path, sync::Arc};
+
+use arm_toolchain::toolchain::{
+    Archive, ArchiveFormats, ExtractLimits, InstallState, ToolchainError,
+};
+use tokio_util::sync::CancellationToken;
+
+/// Extracts the DMG at `path` to `destination` with the built-in format.
+async fn extract(
+    path: &Path,
+    destination: &Path,
+    progress: Arc<dyn Fn(InstallState) + Send + Sync>,
+    cancel_token: CancellationToken,
+) -> Result<Option<String>, ToolchainError> {
+    let archive = Archive {
+        file: fs_err::tokio::File::open(path).await.unwrap(),
+        path: path.to_owned(),
+        limits: ExtractLimits::default(),
+    };
+    ArchiveFormats::builtin()
+        .find("toolchain.dmg")
+        .unwrap()
+        .extract(archive, destination.to_owned(), progress, cancel_token)
+        .await
+}
+
+#[cfg(not(target_os = "macos"))]
+#[tokio::test]
+async fn unsupported_on_other_hosts() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("toolchain.dmg");
+    std::fs::write(&path, b"not a disk image").unwrap();
+
+    let error = extract(
+        &path,
+        &dir.path().join("out"),
+        Arc::new(|_| {}),
+        CancellationToken::new(),
+    )
+    .await
+    .unwrap_err();
+
+    assert_eq!(
+        error.stable_code(),
+        "arm_toolchain::extract::dmg_not_supported"
+    );
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::{os::unix::fs::PermissionsExt, path::PathBuf, process::Command};
+
+    use tokio::sync::Notify;
+
+    use super::*;
+
+    const ROOT: &str = "ATfE-21.1.0-Darwin-universal";
+
+    /// Creates a DMG of the fixture toolchain in `dir`, and returns its path.
+    fn create_dmg(dir: &Path) -> PathBuf {
+        let source = dir.join("source");
+        for (path, contents, mode) in common::toolchain_files(ROOT) {
+            let path = source.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, contents).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+        }
+
+        let dmg = dir.join("toolchain.dmg");
+        let status = Command::new("hdiutil")
+            .args(["create", "-quiet", "-volname", "ATfE", "-format", "UDZO"])
+            .arg("-srcfolder")
+            .arg(&source)
+            .arg(&dmg)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        dmg
+    }
+
+    /// Returns whether the DMG at `path` is attached.
+    fn is_attached(path: &Path) -> bool {
+        let output = Command::new("hdiutil").arg("info").output().unwrap();
+        let path = std::fs::canonicalize(path).unwrap();
+        String::from_utf8_lossy(&output.stdout).contains(path.to_str().unwrap())
+    }
+
+    #[tokio::test]
+    async fn extracts_and_detaches() {
+        let dir = tempfile::tempdir().unwrap();
+        let dmg = create_dmg(dir.path());
+        let destination = dir.path().join("out");
+
+        let root = extract(
+            &dmg,
+            &destination,
+            Arc::new(|_| {}),
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(root.as_deref(), Some(ROOT));
+        assert!(destination.join("bin/clang").is_file());
+        assert!(!is_attached(&dmg));
+    }
+
+    #[tokio::test]
+    async fn detaches_when_cancelled() {
+        let dir = tempfile::tempdir().unwrap();
+        let dmg = create_dmg(dir.path());
+        let cancel_token = CancellationToken::new();
+        cancel_token.cancel();
+
+        let error = extract(
+            &dmg,
+            &dir.path().join("out"),
+            Arc::new(|_| {}),
+            cancel_token,
+        )
+        .await
+        .unwrap_err();
+
+        assert!(matches!(error, ToolchainError::Cancelled), "{error:?}");
+        assert!(!is_attached(&dmg));
+    }
+
+    #[tokio::test]
+    async fn detaches_when_abandoned() {
+        let dir = tempfile::tempdir().unwrap();
+        let dmg = create_dmg(dir.path());
+        let copying = Arc::new(Notify::new());
+        let progress = {
+            let copying = copying.clone();
+            Arc::new(move |state: InstallState| {
+                if matches!(state, InstallState::ExtractCopy { .. }) {
+                    copying.notify_one();
+                }
+            })
+        };
+
+        // The extraction is dropped while it's copying out of the attached image.
+        tokio::select! {
+            biased;
+            () = copying.notified() => {}
+            result = extract(&dmg, &dir.path().join("out"), progress, CancellationToken::new()) => {
+                panic!("finished before it was abandoned: {result:?}");
+            }
+        }
+
+        assert!(!is_attached(&dmg));
+    }
+}
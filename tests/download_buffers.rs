@@ -0,0 +1,104 @@
+//! Downloads are written the same way whatever the download buffer size is, including when
+//! the response is encoded in transit.
+
+mod common;
+
+use std::{
+    io::Write,
+    sync::{Arc, Mutex},
+};
+
+use arm_toolchain::toolchain::{DEFAULT_DOWNLOAD_BUFFER_SIZE, InstallState, ToolchainVersion};
+use common::{MockGitHub, MockRelease, MockServer, Response, host_asset_name};
+use flate2::{Compression, write::GzEncoder};
+use tokio_util::sync::CancellationToken;
+
+const BUFFER_SIZES: &[usize] = &[0, 1, DEFAULT_DOWNLOAD_BUFFER_SIZE, 64 * 1024, 1024 * 1024];
+
+/// Installs the release with the given buffer size, returning the archive as it was in the
+/// cache once it was downloaded.
+async fn downloaded_archive(server: &MockServer, buffer_size: usize) -> Vec<u8> {
+    let dir = tempfile::tempdir().unwrap();
+    let client = common::client_builder(dir.path(), server)
+        .download_buffer_size(buffer_size)
+        .build()
+        .await
+        .unwrap();
+    let release = client
+        .get_release(&ToolchainVersion::named("21.1.0"))
+        .await
+        .unwrap();
+    let asset = common::host_asset(&client, &release);
+
+    let archive_path = client.cache_path_for(asset).unwrap();
+    let downloaded = Arc::new(Mutex::new(None));
+    let progress = {
+        let downloaded = downloaded.clone();
+        Arc::new(move |state| {
+            if let InstallState::DownloadFinish = state {
+                *downloaded.lock().unwrap() = std::fs::read(&archive_path).ok();
+            }
+        })
+    };
+    client
+        .install_to(
+            &release,
+            asset,
+            dir.path().join("toolchain"),
+            progress,
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+    downloaded
+        .lock()
+        .unwrap()
+        .take()
+        .expect("the download finished")
+}
+
+#[tokio::test]
+async fn identical_with_every_buffer_size() {
+    let release = MockRelease::toolchain("21.1.0");
+    let expected = release.data(&host_asset_name("21.1.0")).to_vec();
+    let server = MockServer::github(MockGitHub::new([release]));
+
+    for &buffer_size in BUFFER_SIZES {
+        let archive = downloaded_archive(&server, buffer_size).await;
+        assert!(
+            archive == expected,
+            "the archive downloaded with a {buffer_size} byte buffer differs"
+        );
+    }
+}
+
+#[tokio::test]
+async fn identical_when_gzip_encoded_in_transit() {
+    let release = MockRelease::toolchain("21.1.0");
+    let asset_name = host_asset_name("21.1.0");
+    let expected = release.data(&asset_name).to_vec();
+
+    let mut gzip = GzEncoder::new(Vec::new(), Compression::fast());
+    gzip.write_all(&expected).unwrap();
+    let gzipped = gzip.finish().unwrap();
+
+    let github = MockGitHub::new([release]);
+    let server = MockServer::start(move |request| {
+        if request.file_name() == asset_name && request.method == "GET" {
+            Response::ok(gzipped.clone())
+                .header("Content-Encoding", "gzip")
+                .throttle(4096, std::time::Duration::ZERO)
+        } else {
+            github.respond(request)
+        }
+    });
+
+    for &buffer_size in BUFFER_SIZES {
+        let archive = downloaded_archive(&server, buffer_size).await;
+        assert!(
+            archive == expected,
+            "the gzipped archive downloaded with a {buffer_size} byte buffer differs"
+        );
+    }
+}
@@ -0,0 +1,137 @@
+//! The download phase is reported with the same sequence of events whatever is left in the
+//! download cache: it begins at the number of bytes that are kept, and always finishes.
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use arm_toolchain::toolchain::{InstallState, Phase, ToolchainVersion};
+use common::{MockGitHub, MockRelease, MockServer, host_asset_name};
+use tokio_util::sync::CancellationToken;
+
+const VERSION: &str = "21.1.0";
+
+fn archive() -> Vec<u8> {
+    MockRelease::toolchain(VERSION)
+        .data(&host_asset_name(VERSION))
+        .to_vec()
+}
+
+/// Installs the release after leaving `cached` in the download cache (unless it's `None`),
+/// and returns the events of the download phase.
+async fn download_events(cached: Option<Vec<u8>>) -> Vec<InstallState> {
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain(VERSION)]));
+    let dir = tempfile::tempdir().unwrap();
+    let client = common::client(dir.path(), &server).await;
+    let release = client
+        .get_release(&ToolchainVersion::named(VERSION))
+        .await
+        .unwrap();
+    let asset = common::host_asset(&client, &release);
+    if let Some(cached) = cached {
+        let archive_path = client.cache_path_for(asset).unwrap();
+        std::fs::create_dir_all(archive_path.parent().unwrap()).unwrap();
+        std::fs::write(&archive_path, cached).unwrap();
+    }
+
+    let events = Arc::new(Mutex::new(vec![]));
+    client
+        .download_and_install(
+            &release,
+            asset,
+            Arc::new({
+                let events = events.clone();
+                move |state| events.lock().unwrap().push(state)
+            }),
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+    let events = events.lock().unwrap();
+    events
+        .iter()
+        .filter(|state| state.phase() == Phase::Download)
+        .cloned()
+        .collect()
+}
+
+/// Checks that the events begin at `start`, count up to the whole archive, and finish.
+fn assert_downloaded_from(events: &[InstallState], start: usize, size: usize) {
+    let [begin, progress @ .., finish] = events else {
+        panic!("{events:#?}");
+    };
+    assert_eq!(
+        *begin,
+        InstallState::DownloadBegin {
+            asset_size: size as u64,
+            bytes_read: start as u64,
+        }
+    );
+    assert_eq!(*finish, InstallState::DownloadFinish);
+
+    let bytes_read: Vec<_> = progress
+        .iter()
+        .map(|state| match state {
+            InstallState::Download { bytes_read } => *bytes_read,
+            state => panic!("{state:?}"),
+        })
+        .collect();
+    let mut counted = vec![start as u64];
+    counted.extend(&bytes_read);
+    assert!(
+        counted.windows(2).all(|pair| pair[0] < pair[1]),
+        "{bytes_read:?}"
+    );
+    let expected_last = (start < size).then_some(size as u64);
+    assert_eq!(bytes_read.last().copied(), expected_last, "{bytes_read:?}");
+}
+
+#[tokio::test]
+async fn fresh_download() {
+    let size = archive().len();
+
+    let events = download_events(None).await;
+
+    assert_downloaded_from(&events, 0, size);
+}
+
+#[tokio::test]
+async fn partial_download_is_resumed() {
+    let archive = archive();
+    let half = archive.len() / 2;
+
+    let events = download_events(Some(archive[..half].to_vec())).await;
+
+    assert_downloaded_from(&events, half, archive.len());
+}
+
+#[tokio::test]
+async fn complete_download_begins_and_finishes_at_once() {
+    let archive = archive();
+    let size = archive.len();
+
+    let events = download_events(Some(archive)).await;
+
+    assert_eq!(
+        events,
+        [
+            InstallState::DownloadBegin {
+                asset_size: size as u64,
+                bytes_read: size as u64,
+            },
+            InstallState::DownloadFinish,
+        ]
+    );
+}
+
+#[tokio::test]
+async fn oversized_download_starts_over_from_zero() {
+    let mut oversized = archive();
+    let size = oversized.len();
+    oversized.extend(common::noise(1024));
+
+    let events = download_events(Some(oversized)).await;
+
+    assert_downloaded_from(&events, 0, size);
+}
@@ -0,0 +1,215 @@
+//! The URLs reported for pre-staging a download are the ones that installing it fetches,
+//! including on a mirror or through the GitHub API.
+
+mod common;
+
+use std::sync::Arc;
+
+use arm_toolchain::toolchain::{DownloadVia, ToolchainVersion};
+use common::{MockGitHub, MockRelease, MockServer, host_asset_name};
+use tokio_util::sync::CancellationToken;
+
+const VERSION: &str = "21.1.0";
+
+/// Starts a server that serves the release both as GitHub does and under `/mirror/`.
+fn server_with_mirror() -> MockServer {
+    server_with_mirror_at("/mirror")
+}
+
+/// Starts a server that serves the release both as GitHub does and under `prefix`.
+fn server_with_mirror_at(prefix: &'static str) -> MockServer {
+    let github = MockGitHub::new([MockRelease::toolchain(VERSION)]);
+    MockServer::start(move |request| {
+        let mut request = request.clone();
+        if let Some(path) = request.path.strip_prefix(prefix) {
+            request.path = path.to_string();
+        }
+        github.respond(&request)
+    })
+}
+
+/// Returns the paths that the archive was requested from while installing it.
+async fn installed_from(
+    server: &MockServer,
+    download_via: DownloadVia,
+    mirror: bool,
+) -> Vec<String> {
+    let dir = tempfile::tempdir().unwrap();
+    let client = common::client_builder(dir.path(), server)
+        .download_via(download_via)
+        .asset_mirror(mirror.then(|| server.url("/mirror/")))
+        .build()
+        .await
+        .unwrap();
+    let release = client
+        .get_release(&ToolchainVersion::named(VERSION))
+        .await
+        .unwrap();
+    client
+        .download_and_install(
+            &release,
+            common::host_asset(&client, &release),
+            Arc::new(|_| {}),
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+    server
+        .requests_for(&host_asset_name(VERSION))
+        .into_iter()
+        .filter(|request| request.method == "GET")
+        .map(|request| request.path)
+        .collect()
+}
+
+#[tokio::test]
+async fn browser_urls() {
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain(VERSION)]));
+    let dir = tempfile::tempdir().unwrap();
+    let client = common::client(dir.path(), &server).await;
+    let release = client
+        .get_release(&ToolchainVersion::named(VERSION))
+        .await
+        .unwrap();
+    let asset = common::host_asset(&client, &release);
+
+    let info = client
+        .asset_download_info(&release, asset, false)
+        .await
+        .unwrap();
+
+    assert_eq!(info.url, asset.browser_download_url);
+    assert_eq!(info.size, asset.size as u64);
+    assert_eq!(
+        info.checksum_url.as_str(),
+        format!("{}.sha256", asset.browser_download_url)
+    );
+    assert_eq!(info.expected_sha256, None);
+}
+
+#[tokio::test]
+async fn fetches_the_expected_checksum() {
+    let release = MockRelease::toolchain(VERSION);
+    let expected = common::sha256_hex(release.data(&host_asset_name(VERSION)));
+    let server = MockServer::github(MockGitHub::new([release]));
+    let dir = tempfile::tempdir().unwrap();
+    let client = common::client(dir.path(), &server).await;
+    let release = client
+        .get_release(&ToolchainVersion::named(VERSION))
+        .await
+        .unwrap();
+
+    let info = client
+        .asset_download_info(&release, common::host_asset(&client, &release), true)
+        .await
+        .unwrap();
+
+    assert_eq!(info.expected_sha256, Some(expected));
+}
+
+#[tokio::test]
+async fn mirror_urls_are_the_ones_installing_fetches() {
+    let server = server_with_mirror();
+    let dir = tempfile::tempdir().unwrap();
+    let client = common::client_builder(dir.path(), &server)
+        .asset_mirror(Some(server.url("/mirror/")))
+        .build()
+        .await
+        .unwrap();
+    let release = client
+        .get_release(&ToolchainVersion::named(VERSION))
+        .await
+        .unwrap();
+    let asset = common::host_asset(&client, &release);
+
+    let info = client
+        .asset_download_info(&release, asset, false)
+        .await
+        .unwrap();
+
+    let path = format!("/mirror{}", asset.browser_download_url.path());
+    assert_eq!(info.url.as_str(), server.url(&path));
+    assert_eq!(
+        info.checksum_url.as_str(),
+        server.url(&format!("{path}.sha256"))
+    );
+    assert_eq!(
+        installed_from(&server, DownloadVia::Browser, true).await,
+        [path]
+    );
+}
+
+#[tokio::test]
+async fn mirror_paths_can_name_releases_and_assets() {
+    // Unlike the GitHub API URL, a mirror is just a directory of files, which is often named
+    // after what's in it.
+    let server = server_with_mirror_at("/github/releases");
+    let dir = tempfile::tempdir().unwrap();
+    let client = common::client_builder(dir.path(), &server)
+        .asset_mirror(Some(server.url("/github/releases/")))
+        .build()
+        .await
+        .unwrap();
+    let release = client
+        .get_release(&ToolchainVersion::named(VERSION))
+        .await
+        .unwrap();
+    let asset = common::host_asset(&client, &release);
+
+    let info = client
+        .asset_download_info(&release, asset, false)
+        .await
+        .unwrap();
+
+    let path = format!("/github/releases{}", asset.browser_download_url.path());
+    assert_eq!(info.url.as_str(), server.url(&path));
+    client
+        .download_and_install(&release, asset, Arc::new(|_| {}), CancellationToken::new())
+        .await
+        .unwrap();
+    assert_eq!(
+        server
+            .requests_for(&asset.name)
+            .into_iter()
+            .filter(|request| request.method == "GET")
+            .map(|request| request.path)
+            .collect::<Vec<_>>(),
+        [path]
+    );
+}
+
+#[tokio::test]
+async fn api_urls_are_the_ones_installing_fetches() {
+    let server = server_with_mirror();
+    let dir = tempfile::tempdir().unwrap();
+    // The mirror only stands in for browser downloads, so it's not used through the API.
+    let client = common::client_builder(dir.path(), &server)
+        .download_via(DownloadVia::Api)
+        .asset_mirror(Some(server.url("/mirror/")))
+        .build()
+        .await
+        .unwrap();
+    let release = client
+        .get_release(&ToolchainVersion::named(VERSION))
+        .await
+        .unwrap();
+    let asset = common::host_asset(&client, &release);
+    let checksum_asset = release
+        .assets()
+        .iter()
+        .find(|checksum| checksum.name == format!("{}.sha256", asset.name))
+        .unwrap();
+
+    let info = client
+        .asset_download_info(&release, asset, false)
+        .await
+        .unwrap();
+
+    assert_eq!(info.url, asset.url);
+    assert_eq!(info.checksum_url, checksum_asset.url);
+    assert_eq!(
+        installed_from(&server, DownloadVia::Api, true).await,
+        [info.url.path()]
+    );
+}
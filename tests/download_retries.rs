@@ -0,0 +1,186 @@
+//! Downloads that fail in a way that might not happen again are retried, resuming from what
+//! was kept, until they've been attempted the configured number of times.
+
+mod common;
+
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicUsize, Ordering},
+};
+
+use arm_toolchain::toolchain::{
+    DEFAULT_DOWNLOAD_ATTEMPTS, InstallState, ToolchainError, ToolchainVersion,
+};
+use common::{MockGitHub, MockRelease, MockServer, Request, Response, host_asset_name};
+use tokio_util::sync::CancellationToken;
+
+const VERSION: &str = "21.1.0";
+
+/// Starts a server that answers the `n`th request for the archive (starting at 0) with
+/// `respond`, given GitHub's response to it. Every other request is answered like GitHub.
+fn server(
+    release: MockRelease,
+    respond: impl Fn(usize, Response) -> Response + Send + Sync + 'static,
+) -> MockServer {
+    let github = MockGitHub::new([release]);
+    let archive_requests = AtomicUsize::new(0);
+    MockServer::start(move |request| {
+        let response = github.respond(request);
+        if request.file_name() == host_asset_name(VERSION) && request.method == "GET" {
+            respond(archive_requests.fetch_add(1, Ordering::SeqCst), response)
+        } else {
+            response
+        }
+    })
+}
+
+/// Installs the release, returning the result, the progress events that were reported, and
+/// the requests for the archive.
+async fn install(
+    server: &MockServer,
+) -> (Result<(), ToolchainError>, Vec<InstallState>, Vec<Request>) {
+    let dir = tempfile::tempdir().unwrap();
+    let client = common::client(dir.path(), server).await;
+    let release = client
+        .get_release(&ToolchainVersion::named(VERSION))
+        .await
+        .unwrap();
+
+    let events = Arc::new(Mutex::new(vec![]));
+    let result = client
+        .download_and_install(
+            &release,
+            common::host_asset(&client, &release),
+            Arc::new({
+                let events = events.clone();
+                move |state| events.lock().unwrap().push(state)
+            }),
+            CancellationToken::new(),
+        )
+        .await
+        .map(drop);
+
+    let requests = server
+        .requests_for(&host_asset_name(VERSION))
+        .into_iter()
+        .filter(|request| request.method == "GET")
+        .collect();
+    let events = events.lock().unwrap().clone();
+    (result, events, requests)
+}
+
+/// Returns the attempts reported by `DownloadRetry` events, in order, checking that each one
+/// reports the default number of attempts.
+fn retried_attempts(events: &[InstallState]) -> Vec<u32> {
+    events
+        .iter()
+        .filter_map(|state| match state {
+            InstallState::DownloadRetry {
+                attempt,
+                max_attempts,
+                ..
+            } => {
+                assert_eq!(*max_attempts, DEFAULT_DOWNLOAD_ATTEMPTS);
+                Some(*attempt)
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn dropped_connections_and_server_errors_are_retried_from_what_was_kept() {
+    let release = MockRelease::toolchain(VERSION);
+    let size = release.data(&host_asset_name(VERSION)).len();
+    let half = size / 2;
+    let server = server(release, move |n, response| match n {
+        0 => response.cut_after(half),
+        1 => Response::new(503, "unavailable"),
+        _ => response,
+    });
+
+    let (result, events, requests) = install(&server).await;
+
+    result.unwrap();
+    assert_eq!(retried_attempts(&events), [1, 2]);
+    assert_eq!(requests.len(), 3);
+    let started_from = format!("bytes=0-{}", size - 1);
+    assert_eq!(requests[0].header("range"), Some(&*started_from));
+    let resumed_from = format!("bytes={half}-{}", size - 1);
+    assert_eq!(requests[1].header("range"), Some(&*resumed_from));
+    assert_eq!(requests[2].header("range"), Some(&*resumed_from));
+
+    // A download only begins once the server answers with the archive, so the attempt that
+    // got a server error doesn't begin one. Both retries are reported before the download
+    // begins again, at the bytes that were kept.
+    let kinds: Vec<_> = events
+        .iter()
+        .filter_map(|state| match state {
+            InstallState::DownloadBegin { bytes_read, .. } => Some(format!("begin {bytes_read}")),
+            InstallState::DownloadRetry { attempt, .. } => Some(format!("retry {attempt}")),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        kinds,
+        ["begin 0", "retry 1", "retry 2", &format!("begin {half}")]
+    );
+}
+
+#[tokio::test]
+async fn client_errors_are_not_retried() {
+    for status in [403, 404] {
+        let server = server(MockRelease::toolchain(VERSION), move |_, _| {
+            Response::new(status, "no")
+        });
+
+        let (result, events, requests) = install(&server).await;
+
+        let error = result.unwrap_err();
+        assert!(
+            matches!(&error, ToolchainError::Reqwest(error) if error.status().map(|s| s.as_u16()) == Some(status)),
+            "{error:?}"
+        );
+        assert_eq!(retried_attempts(&events), Vec::<u32>::new());
+        assert_eq!(requests.len(), 1);
+    }
+}
+
+#[tokio::test]
+async fn checksum_mismatches_are_not_retried() {
+    let name = host_asset_name(VERSION);
+    let archive = MockRelease::toolchain(VERSION).data(&name).to_vec();
+    let release = MockRelease::empty(VERSION).asset(&name, archive).asset(
+        &format!("{name}.sha256"),
+        format!("{}  {name}\n", "0".repeat(64)).into_bytes(),
+    );
+    let server = server(release, |_, response| response);
+
+    let (result, events, requests) = install(&server).await;
+
+    let error = result.unwrap_err();
+    assert!(
+        matches!(error, ToolchainError::ChecksumMismatch { .. }),
+        "{error:?}"
+    );
+    assert_eq!(retried_attempts(&events), Vec::<u32>::new());
+    assert_eq!(requests.len(), 1);
+}
+
+#[tokio::test]
+async fn attempts_stop_at_the_default_limit() {
+    let server = server(MockRelease::toolchain(VERSION), |_, _| {
+        Response::new(503, "unavailable")
+    });
+
+    let (result, events, requests) = install(&server).await;
+
+    let error = result.unwrap_err();
+    assert!(
+        matches!(&error, ToolchainError::Reqwest(error) if error.status().is_some_and(|s| s.as_u16() == 503)),
+        "{error:?}"
+    );
+    let expected: Vec<_> = (1..DEFAULT_DOWNLOAD_ATTEMPTS).collect();
+    assert_eq!(retried_attempts(&events), expected);
+    assert_eq!(requests.len(), DEFAULT_DOWNLOAD_ATTEMPTS as usize);
+}
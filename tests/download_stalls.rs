@@ -0,0 +1,153 @@
+//! A download that stops receiving data fails once the stall timeout passes, keeping what was
+//! received, and stops as soon as it's cancelled instead of waiting for the timeout.
+
+mod common;
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use arm_toolchain::toolchain::{
+    InstallState, Phase, ToolchainClient, ToolchainError, ToolchainVersion,
+};
+use common::{MockGitHub, MockRelease, MockServer, host_asset_name};
+use tokio_util::sync::CancellationToken;
+
+const VERSION: &str = "21.1.0";
+
+/// How long the server hangs after sending part of the archive. Each test has to finish well
+/// before this.
+const HANG: Duration = Duration::from_secs(30);
+
+/// Starts a server that sends the first half of the archive, then hangs. Returns the server
+/// and how many bytes it sends.
+fn hanging_server() -> (MockServer, u64) {
+    let release = MockRelease::toolchain(VERSION);
+    let half = release.data(&host_asset_name(VERSION)).len() / 2;
+    let github = MockGitHub::new([release]);
+    let server = MockServer::start(move |request| {
+        let response = github.respond(request);
+        match request.file_name() == host_asset_name(VERSION) {
+            true => response.stall(half, HANG),
+            false => response,
+        }
+    });
+    (server, half as u64)
+}
+
+async fn install(
+    client: &ToolchainClient,
+    progress: impl Fn(InstallState) + Send + Sync + 'static,
+    cancel_token: CancellationToken,
+) -> ToolchainError {
+    let release = client
+        .get_release(&ToolchainVersion::named(VERSION))
+        .await
+        .unwrap();
+    let Err(error) = client
+        .download_and_install(
+            &release,
+            common::host_asset(client, &release),
+            Arc::new(progress),
+            cancel_token,
+        )
+        .await
+    else {
+        panic!("expected the download to fail");
+    };
+    error
+}
+
+#[tokio::test]
+async fn stalls_fail_after_the_timeout_and_keep_what_was_received() {
+    let (server, half) = hanging_server();
+    let dir = tempfile::tempdir().unwrap();
+    let timeout = Duration::from_millis(500);
+    let client = common::client_builder(dir.path(), &server)
+        .download_stall_timeout(timeout)
+        .download_attempts(1)
+        .build()
+        .await
+        .unwrap();
+
+    let started = Instant::now();
+    let error = install(&client, |_| {}, CancellationToken::new()).await;
+    let elapsed = started.elapsed();
+
+    assert!(
+        matches!(
+            error,
+            ToolchainError::DownloadStalled { timeout: t, bytes_written }
+                if t == timeout && bytes_written == half
+        ),
+        "{error:?}"
+    );
+    assert!(
+        elapsed >= timeout && elapsed < HANG / 2,
+        "failed after {elapsed:?}"
+    );
+
+    let release = client
+        .get_release(&ToolchainVersion::named(VERSION))
+        .await
+        .unwrap();
+    let archive = client
+        .cache_path_for(common::host_asset(&client, &release))
+        .unwrap();
+    assert_eq!(std::fs::metadata(archive).unwrap().len(), half);
+}
+
+#[tokio::test]
+async fn cancelling_during_a_stall_stops_right_away() {
+    let (server, half) = hanging_server();
+    let dir = tempfile::tempdir().unwrap();
+    let client = common::client_builder(dir.path(), &server)
+        .download_stall_timeout(HANG * 2)
+        .build()
+        .await
+        .unwrap();
+
+    // Cancel once everything the server is going to send has arrived, so the download is
+    // waiting on the stall.
+    let cancel_token = CancellationToken::new();
+    let cancelled_at = Arc::new(std::sync::Mutex::new(None));
+    let progress = {
+        let cancel_token = cancel_token.clone();
+        let cancelled_at = cancelled_at.clone();
+        move |state| {
+            if let InstallState::Download { bytes_read } = state
+                && bytes_read == half
+            {
+                let cancel_token = cancel_token.clone();
+                let cancelled_at = cancelled_at.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+                    *cancelled_at.lock().unwrap() = Some(Instant::now());
+                    cancel_token.cancel();
+                });
+            }
+        }
+    };
+
+    let error = install(&client, progress, cancel_token).await;
+    let stopped_at = Instant::now();
+
+    let ToolchainError::InstallCancelled(state) = error else {
+        panic!("expected the install to be cancelled, got {error:?}");
+    };
+    assert_eq!(state.phase, Phase::Download);
+    assert_eq!(
+        state.archive.map(|archive| archive.downloaded_bytes),
+        Some(half)
+    );
+    let cancelled_at = cancelled_at
+        .lock()
+        .unwrap()
+        .expect("the token was cancelled");
+    let latency = stopped_at - cancelled_at;
+    assert!(
+        latency < Duration::from_secs(1),
+        "stopped after {latency:?}"
+    );
+}
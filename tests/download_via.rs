@@ -0,0 +1,114 @@
+//! Assets are downloaded from their browser download URL, from the GitHub API's asset
+//! endpoint, or from the API when the browser download URL is blocked.
+
+mod common;
+
+use std::sync::Arc;
+
+use arm_toolchain::toolchain::{DownloadVia, ToolchainError, ToolchainVersion};
+use common::{MockGitHub, MockRelease, MockServer, Response, host_asset_name};
+use tokio_util::sync::CancellationToken;
+
+/// Starts a server whose browser download URLs are blocked, like behind a proxy that only
+/// allows the API.
+fn server_blocking_browser_urls() -> MockServer {
+    let github = MockGitHub::new([MockRelease::toolchain("21.1.0")]);
+    MockServer::start(move |request| {
+        if request.path.starts_with("/download/") {
+            Response::new(403, "Blocked by proxy")
+        } else {
+            github.respond(request)
+        }
+    })
+}
+
+async fn install(server: &MockServer, download_via: DownloadVia) -> Result<(), ToolchainError> {
+    let dir = tempfile::tempdir().unwrap();
+    let client = common::client_builder(dir.path(), server)
+        .download_via(download_via)
+        .build()
+        .await
+        .unwrap();
+    let release = client
+        .get_release(&ToolchainVersion::named("21.1.0"))
+        .await
+        .unwrap();
+    client
+        .download_and_install(
+            &release,
+            common::host_asset(&client, &release),
+            Arc::new(|_| {}),
+            CancellationToken::new(),
+        )
+        .await
+        .map(|_| ())
+}
+
+/// Returns the paths that the toolchain archive was requested from.
+fn archive_paths(server: &MockServer) -> Vec<String> {
+    server
+        .requests_for(&host_asset_name("21.1.0"))
+        .into_iter()
+        .map(|request| request.path)
+        .collect()
+}
+
+#[tokio::test]
+async fn auto_falls_back_to_the_api() {
+    let server = server_blocking_browser_urls();
+
+    install(&server, DownloadVia::Auto).await.unwrap();
+
+    let requests = server.requests_for(&host_asset_name("21.1.0"));
+    assert!(requests[0].path.starts_with("/download/"), "{requests:#?}");
+    let api_request = requests
+        .iter()
+        .find(|request| request.path.starts_with("/assets/"))
+        .expect("the archive was requested through the API");
+    assert_eq!(
+        api_request.header("accept"),
+        Some("application/octet-stream")
+    );
+}
+
+#[tokio::test]
+async fn auto_uses_the_browser_url_when_it_works() {
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+
+    install(&server, DownloadVia::Auto).await.unwrap();
+
+    let paths = archive_paths(&server);
+    assert!(!paths.is_empty());
+    assert!(
+        paths.iter().all(|path| path.starts_with("/download/")),
+        "{paths:?}"
+    );
+}
+
+#[tokio::test]
+async fn browser_does_not_fall_back() {
+    let server = server_blocking_browser_urls();
+
+    let error = install(&server, DownloadVia::Browser).await.unwrap_err();
+
+    assert!(matches!(error, ToolchainError::Reqwest(_)), "{error:?}");
+    let paths = archive_paths(&server);
+    assert!(
+        paths.iter().all(|path| path.starts_with("/download/")),
+        "{paths:?}"
+    );
+}
+
+#[tokio::test]
+async fn api_only_uses_the_api() {
+    let server = server_blocking_browser_urls();
+
+    install(&server, DownloadVia::Api).await.unwrap();
+
+    let paths = archive_paths(&server);
+    assert!(!paths.is_empty());
+    assert!(
+        paths.iter().all(|path| path.starts_with("/assets/")),
+        "{paths:?}"
+    );
+}
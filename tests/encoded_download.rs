@@ -0,0 +1,130 @@
+//! Assets that a proxy compresses in transit, even though they're requested without an
+//! encoding, are decoded as they're downloaded and still verified against their checksum.
+
+mod common;
+
+use std::{io::Write, path::Path, sync::Arc};
+
+use arm_toolchain::toolchain::{ToolchainError, ToolchainVersion};
+use common::{MockGitHub, MockRelease, MockServer, Request, Response, host_asset_name};
+use flate2::{
+    Compression,
+    write::{GzEncoder, ZlibEncoder},
+};
+use tokio_util::sync::CancellationToken;
+
+const VERSION: &str = "21.1.0";
+
+/// A server that always sends the archive with the given encoding, whatever was asked for.
+fn encoding_server(encoding: &'static str, encode: fn(&[u8]) -> Vec<u8>) -> MockServer {
+    let release = MockRelease::toolchain(VERSION);
+    let asset_name = host_asset_name(VERSION);
+    let encoded = encode(release.data(&asset_name));
+    let github = MockGitHub::new([release]);
+
+    MockServer::start(move |request| {
+        if request.file_name() == asset_name && request.method == "GET" {
+            Response::ok(encoded.clone()).header("Content-Encoding", encoding)
+        } else {
+            github.respond(request)
+        }
+    })
+}
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::fast());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// Installs the release, after leaving `partial` in the cache as an interrupted download.
+async fn install(dir: &Path, server: &MockServer, partial: &[u8]) -> Result<(), ToolchainError> {
+    let client = common::client(dir, server).await;
+    let release = client
+        .get_release(&ToolchainVersion::named(VERSION))
+        .await
+        .unwrap();
+    let asset = common::host_asset(&client, &release);
+
+    if !partial.is_empty() {
+        let archive_path = client.cache_path_for(asset).unwrap();
+        std::fs::create_dir_all(archive_path.parent().unwrap()).unwrap();
+        std::fs::write(&archive_path, partial).unwrap();
+    }
+
+    client
+        .install_to(
+            &release,
+            asset,
+            dir.join("toolchain"),
+            Arc::new(|_| {}),
+            CancellationToken::new(),
+        )
+        .await
+        .map(|_| ())
+}
+
+fn archive_requests(server: &MockServer) -> Vec<Request> {
+    server.requests_for(&host_asset_name(VERSION))
+}
+
+#[tokio::test]
+async fn gzip_encoded_archive_is_decoded_and_verified() {
+    let server = encoding_server("gzip", gzip);
+    let dir = tempfile::tempdir().unwrap();
+
+    install(dir.path(), &server, &[]).await.unwrap();
+
+    assert!(dir.path().join("toolchain/bin/clang").is_file());
+    let requests = archive_requests(&server);
+    assert!(!requests.is_empty());
+    for request in requests {
+        assert_eq!(request.header("Accept-Encoding"), Some("identity"));
+    }
+}
+
+#[tokio::test]
+async fn deflate_encoded_archive_is_decoded_and_verified() {
+    let server = encoding_server("deflate", deflate);
+    let dir = tempfile::tempdir().unwrap();
+
+    install(dir.path(), &server, &[]).await.unwrap();
+
+    assert!(dir.path().join("toolchain/bin/clang").is_file());
+}
+
+#[tokio::test]
+async fn encoded_response_replaces_a_partial_download() {
+    let server = encoding_server("gzip", gzip);
+    let dir = tempfile::tempdir().unwrap();
+    let archive = MockRelease::toolchain(VERSION)
+        .data(&host_asset_name(VERSION))
+        .to_vec();
+
+    // The encoded body is the whole archive, so keeping this would fail verification.
+    install(dir.path(), &server, &archive[..archive.len() / 2])
+        .await
+        .unwrap();
+
+    assert!(dir.path().join("toolchain/bin/clang").is_file());
+}
+
+#[tokio::test]
+async fn unsupported_encoding_fails_verification() {
+    let server = encoding_server("br", gzip);
+    let dir = tempfile::tempdir().unwrap();
+
+    let error = install(dir.path(), &server, &[]).await.unwrap_err();
+
+    assert!(
+        matches!(error, ToolchainError::ChecksumMismatch { .. }),
+        "{error:?}"
+    );
+    assert!(!dir.path().join("toolchain").exists());
+}
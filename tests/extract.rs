@@ -0,0 +1,238 @@
+//! Extracting archives: finding the directory that holds the toolchain the same way for every
+//! format, and refusing links that would let later entries escape the destination.
+
+#![cfg(unix)]
+
+mod common;
+
+use std::{io::Write, path::Path, sync::Arc};
+
+use arm_toolchain::toolchain::{Archive, ArchiveFormats, ExtractLimits, ToolchainError};
+use tokio_util::sync::CancellationToken;
+
+/// An entry in an archive fixture.
+enum Entry {
+    File(&'static str, &'static [u8]),
+    Link(&'static str, &'static str),
+}
+
+use Entry::{File, Link};
+
+/// A link to the destination itself, a link inside it to the destination's parent, and a
+/// file written through the second link.
+const LINK_CHAIN: &[Entry] = &[Link("x", "."), Link("x/y", ".."), File("y/pwned", b"pwned")];
+
+fn zip_archive(entries: &[Entry]) -> Vec<u8> {
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default();
+    for entry in entries {
+        match entry {
+            File(path, contents) => {
+                zip.start_file(*path, options).unwrap();
+                zip.write_all(contents).unwrap();
+            }
+            Link(path, target) => zip.add_symlink(*path, *target, options).unwrap(),
+        }
+    }
+    zip.finish().unwrap().into_inner()
+}
+
+fn tar_xz_archive(entries: &[Entry]) -> Vec<u8> {
+    let mut tar = tar::Builder::new(liblzma::write::XzEncoder::new(Vec::new(), 0));
+    for entry in entries {
+        let mut header = tar::Header::new_gnu();
+        header.set_mode(0o644);
+        match entry {
+            File(path, contents) => {
+                header.set_size(contents.len() as u64);
+                tar.append_data(&mut header, path, *contents).unwrap();
+            }
+            Link(path, target) => {
+                header.set_entry_type(tar::EntryType::Symlink);
+                header.set_size(0);
+                tar.append_link(&mut header, path, target).unwrap();
+            }
+        }
+    }
+    tar.into_inner().unwrap().finish().unwrap()
+}
+
+/// Extracts an archive with the given file name to `dir/out/toolchain`.
+async fn extract(
+    dir: &Path,
+    file_name: &str,
+    data: &[u8],
+) -> Result<Option<String>, ToolchainError> {
+    let path = dir.join(file_name);
+    std::fs::write(&path, data).unwrap();
+    std::fs::create_dir_all(dir.join("out")).unwrap();
+
+    let archive = Archive {
+        file: fs_err::tokio::File::open(&path).await.unwrap(),
+        path,
+        limits: ExtractLimits::default(),
+    };
+    ArchiveFormats::builtin()
+        .find(file_name)
+        .unwrap()
+        .extract(
+            archive,
+            dir.join("out/toolchain"),
+            Arc::new(|_| {}),
+            CancellationToken::new(),
+        )
+        .await
+}
+
+fn assert_suspicious(result: Result<Option<String>, ToolchainError>) {
+    match result {
+        Err(error) => assert_eq!(
+            error.stable_code(),
+            "arm_toolchain::extract::suspicious_archive",
+            "{error:?}"
+        ),
+        Ok(_) => panic!("the archive was extracted"),
+    }
+}
+
+#[tokio::test]
+async fn zip_link_chain_is_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let result = extract(dir.path(), "toolchain.zip", &zip_archive(LINK_CHAIN)).await;
+
+    assert_suspicious(result);
+    assert!(!dir.path().join("out/pwned").exists());
+}
+
+#[tokio::test]
+async fn tar_xz_link_chain_is_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let result = extract(dir.path(), "toolchain.tar.xz", &tar_xz_archive(LINK_CHAIN)).await;
+
+    assert_suspicious(result);
+    assert!(!dir.path().join("pwned").exists());
+    assert!(!dir.path().join("out/pwned").exists());
+}
+
+#[tokio::test]
+async fn zip_file_after_its_link_is_not_written_through_it() {
+    let dir = tempfile::tempdir().unwrap();
+    let entries = [Link("lib", "."), File("lib/pwned", b"pwned")];
+    // The file can't be extracted to where the link would have been, either way.
+    _ = extract(dir.path(), "toolchain.zip", &zip_archive(&entries)).await;
+
+    let toolchain = dir.path().join("out/toolchain");
+    assert!(!toolchain.join("pwned").exists());
+}
+
+#[tokio::test]
+async fn links_outside_the_destination_are_refused() {
+    let entries = [File("bin/clang", b"clang"), Link("bin/escape", "../../..")];
+
+    let dir = tempfile::tempdir().unwrap();
+    assert_suspicious(extract(dir.path(), "toolchain.zip", &zip_archive(&entries)).await);
+    let dir = tempfile::tempdir().unwrap();
+    assert_suspicious(extract(dir.path(), "toolchain.tar.xz", &tar_xz_archive(&entries)).await);
+}
+
+#[tokio::test]
+async fn links_inside_the_destination_are_extracted() {
+    let entries = [
+        Link("root/bin/clang", "clang-21"),
+        File("root/bin/clang-21", b"clang"),
+        Link("root/lib/current", "../bin"),
+    ];
+
+    for (file_name, data) in [
+        ("toolchain.zip", zip_archive(&entries)),
+        ("toolchain.tar.xz", tar_xz_archive(&entries)),
+    ] {
+        let dir = tempfile::tempdir().unwrap();
+        let root = extract(dir.path(), file_name, &data).await.unwrap();
+
+        let toolchain = dir.path().join("out/toolchain");
+        assert_eq!(root.as_deref(), Some("root"));
+        assert_eq!(
+            std::fs::read(toolchain.join("bin/clang")).unwrap(),
+            b"clang"
+        );
+        assert_eq!(
+            std::fs::read(toolchain.join("lib/current/clang-21")).unwrap(),
+            b"clang"
+        );
+    }
+}
+
+/// Layouts of archive entries, the root directory expected to be found in them, and the tree
+/// expected to be extracted from them.
+const ROOT_LAYOUTS: &[(&[Entry], Option<&str>, &[&str])] = &[
+    (
+        &[
+            File("root/bin/clang", b"clang"),
+            File("root/lib/libc.a", b""),
+        ],
+        Some("root"),
+        &["bin", "bin/clang", "lib", "lib/libc.a"],
+    ),
+    // Metadata added by macOS and Windows doesn't count as another root.
+    (
+        &[
+            File("__MACOSX/root/bin/._clang", b""),
+            File(".DS_Store", b""),
+            File("root/bin/clang", b"clang"),
+            File("Thumbs.db", b""),
+        ],
+        Some("root"),
+        &["bin", "bin/clang"],
+    ),
+    // A top-level file means that there's no root to strip.
+    (
+        &[File("README", b"readme"), File("root/bin/clang", b"clang")],
+        None,
+        &["README", "root", "root/bin", "root/bin/clang"],
+    ),
+    (
+        &[File("a/bin/clang", b"clang"), File("b/lib/libc.a", b"")],
+        None,
+        &["a", "a/bin", "a/bin/clang", "b", "b/lib", "b/lib/libc.a"],
+    ),
+];
+
+#[tokio::test]
+async fn root_is_found_the_same_way_in_every_format() {
+    for (entries, expected_root, expected_tree) in ROOT_LAYOUTS {
+        for (file_name, data) in [
+            ("toolchain.zip", zip_archive(entries)),
+            ("toolchain.tar.xz", tar_xz_archive(entries)),
+        ] {
+            let dir = tempfile::tempdir().unwrap();
+            let root = extract(dir.path(), file_name, &data).await.unwrap();
+
+            assert_eq!(root.as_deref(), *expected_root, "{file_name}");
+            assert_eq!(
+                common::list_tree(&dir.path().join("out/toolchain")),
+                *expected_tree,
+                "{file_name}"
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn archive_with_only_metadata_has_no_contents() {
+    let entries = [File("__MACOSX/._root", b""), File(".DS_Store", b"")];
+
+    for (file_name, data) in [
+        ("toolchain.zip", zip_archive(&entries)),
+        ("toolchain.tar.xz", tar_xz_archive(&entries)),
+    ] {
+        let dir = tempfile::tempdir().unwrap();
+        let error = extract(dir.path(), file_name, &data).await.unwrap_err();
+
+        assert_eq!(
+            error.stable_code(),
+            "arm_toolchain::extract::contents_not_found",
+            "{file_name}: {error:?}"
+        );
+    }
+}
@@ -0,0 +1,45 @@
+//! The crate builds without warnings with each of its features on their own, so that
+//! embedding only part of it neither breaks nor pulls in the rest.
+
+use std::{path::Path, process::Command};
+
+/// Each set of features to build with, on top of `--no-default-features`.
+const COMBINATIONS: &[&str] = &[
+    "",
+    "multilib",
+    "cli-core",
+    "cli-interactive",
+    "cli",
+    "cli-core,multilib",
+    "bin",
+];
+
+#[test]
+fn builds_with_each_feature_combination() {
+    // A target directory of its own, so that this doesn't wait on the one running the tests.
+    let target_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("features");
+    let manifest_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+
+    for features in COMBINATIONS {
+        let targets: &[&str] = match *features {
+            "bin" => &["--lib", "--bins"],
+            _ => &["--lib"],
+        };
+        let output = Command::new(env!("CARGO"))
+            .arg("check")
+            .arg("--manifest-path")
+            .arg(&manifest_path)
+            .args(targets)
+            .args(["--no-default-features", "--features", features])
+            .env("CARGO_TARGET_DIR", &target_dir)
+            .env("RUSTFLAGS", "-D warnings")
+            .output()
+            .unwrap();
+
+        assert!(
+            output.status.success(),
+            "failed to build with features {features:?}:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
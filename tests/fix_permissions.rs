@@ -0,0 +1,86 @@
+//! Native binaries that lost their execute permission, such as after being copied through a
+//! FAT-formatted drive, are made executable again.
+
+#![cfg(unix)]
+
+use std::{fs, os::unix::fs::PermissionsExt, path::Path};
+
+use arm_toolchain::toolchain::InstalledToolchain;
+
+const ELF: &[u8] = b"\x7fELF\x02\x01\x01\0";
+const MACH_O: &[u8] = &[0xcf, 0xfa, 0xed, 0xfe, 0x07, 0x00, 0x00, 0x01];
+
+fn write(root: &Path, path: &str, contents: &[u8], mode: u32) {
+    let path = root.join(path);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(&path, contents).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+}
+
+fn mode(root: &Path, path: &str) -> u32 {
+    fs::metadata(root.join(path)).unwrap().permissions().mode() & 0o777
+}
+
+#[tokio::test]
+async fn restores_the_execute_bits_of_native_binaries() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    write(root, "bin/clang", ELF, 0o644);
+    write(root, "bin/ld.lld", ELF, 0o755);
+    write(root, "bin/llvm-objcopy", MACH_O, 0o640);
+    write(root, "bin/clang.cfg", b"--target=arm-none-eabi\n", 0o644);
+    write(root, "libexec/tools/helper", b"MZ\x90\0", 0o600);
+    write(root, "lib/libLTO.so", ELF, 0o644);
+    write(
+        root,
+        "lib/clang-runtimes/arm-none-eabi/lib/crt0.o",
+        ELF,
+        0o644,
+    );
+    write(
+        root,
+        "lib/clang-runtimes/multilib.yaml",
+        b"MultilibVersion: 1.0\n",
+        0o644,
+    );
+
+    let report = InstalledToolchain::new(root.to_owned())
+        .fix_permissions()
+        .await
+        .unwrap();
+
+    assert_eq!(
+        report.fixed,
+        [
+            root.join("bin/clang"),
+            root.join("bin/llvm-objcopy"),
+            root.join("lib/libLTO.so"),
+            root.join("libexec/tools/helper"),
+        ]
+    );
+    // Only those who can read a file are allowed to execute it.
+    assert_eq!(mode(root, "bin/clang"), 0o755);
+    assert_eq!(mode(root, "bin/llvm-objcopy"), 0o750);
+    assert_eq!(mode(root, "libexec/tools/helper"), 0o700);
+    assert_eq!(mode(root, "lib/libLTO.so"), 0o755);
+    // Already executable, not a native binary, or nested under `lib`.
+    assert_eq!(mode(root, "bin/ld.lld"), 0o755);
+    assert_eq!(mode(root, "bin/clang.cfg"), 0o644);
+    assert_eq!(
+        mode(root, "lib/clang-runtimes/arm-none-eabi/lib/crt0.o"),
+        0o644
+    );
+}
+
+#[tokio::test]
+async fn nothing_to_fix() {
+    let dir = tempfile::tempdir().unwrap();
+    write(dir.path(), "bin/clang", ELF, 0o755);
+
+    let report = InstalledToolchain::new(dir.path().to_owned())
+        .fix_permissions()
+        .await
+        .unwrap();
+
+    assert!(report.fixed.is_empty());
+}
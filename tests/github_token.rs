@@ -0,0 +1,121 @@
+//! The GitHub token is only sent to GitHub, the configured API, and the configured mirror.
+
+mod common;
+
+use std::sync::Arc;
+
+use arm_toolchain::toolchain::{ToolchainClient, ToolchainVersion};
+use common::{MockGitHub, MockRelease, MockServer, host_asset_name};
+use reqwest::Url;
+use tokio_util::sync::CancellationToken;
+
+const TOKEN: &str = "ghp_secret";
+
+fn authorized(server: &MockServer) -> Vec<String> {
+    server
+        .requests()
+        .into_iter()
+        .filter(|request| request.header("authorization").is_some())
+        .map(|request| request.path)
+        .collect()
+}
+
+async fn install(client: &ToolchainClient) {
+    let release = client
+        .get_release(&ToolchainVersion::named("21.1.0"))
+        .await
+        .unwrap();
+    client
+        .download_and_install(
+            &release,
+            common::host_asset(client, &release),
+            Arc::new(|_| {}),
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn not_sent_to_asset_urls_on_other_hosts() {
+    let github = MockGitHub::new([MockRelease::toolchain("21.1.0")]);
+    let assets = MockServer::github(github.clone());
+    // The API describes assets that are stored on another host.
+    let api = MockServer::start({
+        let assets_url = assets.base_url().to_string();
+        move |request| {
+            let mut request = request.clone();
+            request.base_url = assets_url.clone();
+            github.respond(&request)
+        }
+    });
+
+    let dir = tempfile::tempdir().unwrap();
+    let client = common::client_builder(dir.path(), &api)
+        .github_token(Some(TOKEN.to_string()))
+        .build()
+        .await
+        .unwrap();
+    install(&client).await;
+
+    assert!(!api.requests().is_empty());
+    assert_eq!(authorized(&api).len(), api.requests().len());
+    assert!(!assets.requests_for(&host_asset_name("21.1.0")).is_empty());
+    assert_eq!(authorized(&assets), Vec::<String>::new());
+}
+
+#[tokio::test]
+async fn sent_to_the_configured_mirror() {
+    let github = MockGitHub::new([MockRelease::toolchain("21.1.0")]);
+    let api = MockServer::github(github.clone());
+    let mirror = MockServer::github(github);
+
+    let dir = tempfile::tempdir().unwrap();
+    let client = common::client_builder(dir.path(), &api)
+        .github_token(Some(TOKEN.to_string()))
+        .asset_mirror(Some(mirror.url("/")))
+        .build()
+        .await
+        .unwrap();
+    install(&client).await;
+
+    let downloads = mirror.requests_for(&host_asset_name("21.1.0"));
+    assert!(!downloads.is_empty());
+    for request in downloads {
+        assert_eq!(
+            request.header("authorization"),
+            Some(format!("Bearer {TOKEN}").as_str())
+        );
+    }
+}
+
+#[tokio::test]
+async fn not_sent_when_installing_from_a_url() {
+    let release = MockRelease::toolchain("21.1.0");
+    let asset_name = host_asset_name("21.1.0");
+    let checksum = common::sha256_hex(release.data(&asset_name));
+    let server = MockServer::github(MockGitHub::new([release]));
+
+    let dir = tempfile::tempdir().unwrap();
+    // The URL is on the configured API's host, which would otherwise be sent the token.
+    let client = common::client_builder(dir.path(), &server)
+        .github_token(Some(TOKEN.to_string()))
+        .build()
+        .await
+        .unwrap();
+    let url =
+        Url::parse(&server.url(&format!("/download/release-21.1.0-ATfE/{asset_name}"))).unwrap();
+    client
+        .install_from_url(
+            url,
+            ToolchainVersion::named("from-url"),
+            Some(checksum),
+            Arc::new(|_| {}),
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+    assert!(!server.requests().is_empty());
+    assert_eq!(authorized(&server), Vec::<String>::new());
+}
@@ -0,0 +1,72 @@
+//! Hosts that the Arm Toolchain for Embedded isn't built for are reported as errors instead
+//! of panicking.
+
+use arm_toolchain::toolchain::{HostArch, HostOS, UnsupportedHost};
+
+#[test]
+fn maps_supported_targets() {
+    for (target_os, target_arch, os, arches) in [
+        (
+            "macos",
+            "aarch64",
+            HostOS::Darwin,
+            &[HostArch::AAarch64, HostArch::Universal][..],
+        ),
+        (
+            "macos",
+            "x86_64",
+            HostOS::Darwin,
+            &[HostArch::X86_64, HostArch::Universal],
+        ),
+        ("linux", "x86_64", HostOS::Linux, &[HostArch::X86_64]),
+        ("linux", "aarch64", HostOS::Linux, &[HostArch::AAarch64]),
+        ("windows", "x86_64", HostOS::Windows, &[HostArch::X86_64]),
+    ] {
+        assert_eq!(HostOS::for_target(target_os), Some(os), "{target_os}");
+        assert_eq!(
+            HostArch::for_target(target_os, target_arch),
+            Some(arches),
+            "{target_os} {target_arch}"
+        );
+    }
+}
+
+#[test]
+fn rejects_unsupported_targets() {
+    for target_os in ["freebsd", "netbsd", "android", "ios", ""] {
+        assert_eq!(HostOS::for_target(target_os), None, "{target_os}");
+    }
+    for (target_os, target_arch) in [
+        ("linux", "riscv64"),
+        ("linux", "arm"),
+        ("linux", "x86"),
+        ("windows", "x86"),
+        ("linux", "powerpc64"),
+    ] {
+        assert_eq!(
+            HostArch::for_target(target_os, target_arch),
+            None,
+            "{target_os} {target_arch}"
+        );
+    }
+}
+
+#[test]
+fn the_current_host_is_supported() {
+    // The tests only run on hosts that the toolchain supports.
+    assert_eq!(HostOS::try_current().unwrap(), HostOS::current());
+    assert_eq!(HostArch::try_current().unwrap(), HostArch::current());
+}
+
+#[test]
+fn names_the_unsupported_host() {
+    let error = UnsupportedHost {
+        os: "linux".to_string(),
+        arch: "riscv64".to_string(),
+    };
+
+    assert_eq!(
+        error.to_string(),
+        "The Arm Toolchain for Embedded is not available for this host (linux riscv64)"
+    );
+}
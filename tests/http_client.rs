@@ -0,0 +1,57 @@
+//! Assets and their checksums are downloaded with the HTTP client given to the builder, such
+//! as one that's configured with the headers a proxy needs.
+
+mod common;
+
+use std::sync::Arc;
+
+use arm_toolchain::toolchain::ToolchainVersion;
+use common::{MockGitHub, MockRelease, MockServer, host_asset_name};
+use reqwest::header::{HeaderMap, HeaderValue};
+use tokio_util::sync::CancellationToken;
+
+#[tokio::test]
+async fn downloads_use_the_given_client() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let mut headers = HeaderMap::new();
+    headers.insert("x-proxy-auth", HeaderValue::from_static("letmein"));
+    let http_client = reqwest::Client::builder()
+        .default_headers(headers)
+        .build()
+        .unwrap();
+    let client = common::client_builder(dir.path(), &server)
+        .http_client(http_client)
+        .build()
+        .await
+        .unwrap();
+
+    let release = client
+        .get_release(&ToolchainVersion::named("21.1.0"))
+        .await
+        .unwrap();
+    client
+        .download_and_install(
+            &release,
+            common::host_asset(&client, &release),
+            Arc::new(|_| {}),
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+    let asset_name = host_asset_name("21.1.0");
+    for file_name in [asset_name.clone(), format!("{asset_name}.sha256")] {
+        let requests = server.requests_for(&file_name);
+        assert!(!requests.is_empty(), "{file_name} wasn't downloaded");
+        for request in requests {
+            assert_eq!(
+                request.header("x-proxy-auth"),
+                Some("letmein"),
+                "{} {}",
+                request.method,
+                request.path
+            );
+        }
+    }
+}
@@ -0,0 +1,42 @@
+//! Toolchains stranded under their probe name by an in-use check that was cut off are renamed
+//! back when a client is built.
+
+mod common;
+
+use std::fs;
+
+use arm_toolchain::toolchain::ToolchainVersion;
+use common::{MockGitHub, MockServer};
+
+#[tokio::test]
+async fn stranded_toolchain_is_recovered() {
+    let dir = tempfile::tempdir().unwrap();
+    let product_path = dir.path().join("toolchains/embedded");
+    fs::create_dir_all(product_path.join("21.1.0.in-use-check/bin")).unwrap();
+    let server = MockServer::github(MockGitHub::new([]));
+
+    let client = common::client(dir.path(), &server).await;
+
+    assert_eq!(
+        client.installed_versions().await.unwrap(),
+        [ToolchainVersion::named("21.1.0")]
+    );
+    assert!(product_path.join("21.1.0/bin").is_dir());
+}
+
+#[tokio::test]
+async fn stranded_duplicate_is_not_listed() {
+    let dir = tempfile::tempdir().unwrap();
+    let product_path = dir.path().join("toolchains/embedded");
+    fs::create_dir_all(product_path.join("21.1.0.in-use-check")).unwrap();
+    fs::create_dir_all(product_path.join("21.1.0")).unwrap();
+    let server = MockServer::github(MockGitHub::new([]));
+
+    let client = common::client(dir.path(), &server).await;
+
+    assert_eq!(
+        client.installed_versions().await.unwrap(),
+        [ToolchainVersion::named("21.1.0")]
+    );
+    assert!(product_path.join("21.1.0.in-use-check").is_dir());
+}
@@ -0,0 +1,106 @@
+//! Whatever is left where a toolchain is about to be extracted is removed without following
+//! symlinks, so that nothing outside of the toolchains directory is deleted.
+
+mod common;
+
+use std::{fs, path::PathBuf, sync::Arc};
+
+use arm_toolchain::toolchain::{ToolchainClient, ToolchainError, ToolchainVersion};
+use common::{MockGitHub, MockRelease, MockServer};
+use tokio_util::sync::CancellationToken;
+
+/// A client for a server with 21.1.0, and the directory that 21.1.0 is installed to.
+async fn client(dir: &std::path::Path, server: &MockServer) -> (ToolchainClient, PathBuf) {
+    let client = common::client(dir, server).await;
+    let install_path = dir.join("toolchains/embedded/21.1.0");
+    fs::create_dir_all(install_path.parent().unwrap()).unwrap();
+    (client, install_path)
+}
+
+async fn install(client: &ToolchainClient) -> Result<(), ToolchainError> {
+    let release = client
+        .get_release(&ToolchainVersion::named("21.1.0"))
+        .await
+        .unwrap();
+    client
+        .download_and_install(
+            &release,
+            common::host_asset(client, &release),
+            Arc::new(|_| {}),
+            CancellationToken::new(),
+        )
+        .await
+        .map(|_| ())
+}
+
+#[tokio::test]
+async fn file_is_replaced() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let (client, install_path) = client(dir.path(), &server).await;
+    fs::write(&install_path, "stray").unwrap();
+
+    install(&client).await.unwrap();
+
+    assert!(install_path.join("bin/clang").is_file());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn symlink_is_replaced_without_touching_its_target() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let (client, install_path) = client(dir.path(), &server).await;
+    let target = dir.path().join("elsewhere");
+    fs::create_dir_all(target.join("bin")).unwrap();
+    fs::write(target.join("bin/clang"), "mine").unwrap();
+    std::os::unix::fs::symlink(&target, &install_path).unwrap();
+
+    install(&client).await.unwrap();
+
+    assert!(!install_path.is_symlink());
+    assert!(install_path.join("bin/clang").is_file());
+    assert_eq!(
+        fs::read_to_string(target.join("bin/clang")).unwrap(),
+        "mine"
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn dangling_symlink_is_replaced() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let (client, install_path) = client(dir.path(), &server).await;
+    std::os::unix::fs::symlink(dir.path().join("missing"), &install_path).unwrap();
+
+    install(&client).await.unwrap();
+
+    assert!(!install_path.is_symlink());
+    assert!(install_path.join("bin/clang").is_file());
+    assert!(!dir.path().join("missing").exists());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn socket_is_refused() {
+    use std::os::unix::fs::FileTypeExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let (client, install_path) = client(dir.path(), &server).await;
+    let _socket = std::os::unix::net::UnixListener::bind(&install_path).unwrap();
+
+    let error = install(&client).await.unwrap_err();
+
+    match error {
+        ToolchainError::UnsupportedDestination { path, .. } => assert_eq!(path, install_path),
+        error => panic!("{error:?}"),
+    }
+    assert!(
+        fs::symlink_metadata(&install_path)
+            .unwrap()
+            .file_type()
+            .is_socket()
+    );
+}
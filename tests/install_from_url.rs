@@ -0,0 +1,64 @@
+//! Toolchains installed from a URL don't reuse archives downloaded from elsewhere.
+
+mod common;
+
+use std::{
+    fs,
+    sync::{Arc, Mutex},
+};
+
+use arm_toolchain::toolchain::{InstallState, ToolchainVersion};
+use common::{MockGitHub, MockRelease, MockServer, host_asset_name, tar_xz, toolchain_files};
+use reqwest::Url;
+use tokio_util::sync::CancellationToken;
+
+#[tokio::test]
+async fn cached_archives_with_the_same_name_are_not_reused() {
+    let asset_name = host_asset_name("21.1.0");
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let path = format!("/download/release-21.1.0-ATfE/{asset_name}");
+
+    // A complete archive of the same name, from another release or URL.
+    let mut other_files = toolchain_files("other");
+    other_files.push(("other/FROM_ANOTHER_URL".to_string(), vec![], 0o644));
+    let dir = tempfile::tempdir().unwrap();
+    let cache = dir.path().join("cache");
+    fs::create_dir_all(&cache).unwrap();
+    fs::write(cache.join(&asset_name), tar_xz(&other_files)).unwrap();
+
+    let client = common::client(dir.path(), &server).await;
+    let states = Arc::new(Mutex::new(vec![]));
+    let progress_states = states.clone();
+    // Without a checksum, nothing would catch the wrong archive being installed.
+    let outcome = client
+        .install_from_url(
+            Url::parse(&server.url(&path)).unwrap(),
+            ToolchainVersion::named("from-url"),
+            None,
+            Arc::new(move |state| progress_states.lock().unwrap().push(state)),
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+    assert!(
+        server.requests().iter().any(|request| request.path == path),
+        "{:#?}",
+        server.requests()
+    );
+    assert!(outcome.path.join("bin").exists());
+    assert!(!outcome.path.join("FROM_ANOTHER_URL").exists());
+    let states = states.lock().unwrap();
+    assert!(
+        states.contains(&InstallState::VerifyingSkipped),
+        "{states:?}"
+    );
+    assert!(
+        !states
+            .iter()
+            .any(|state| matches!(state, InstallState::VerifyingBegin { .. })),
+        "{states:?}"
+    );
+    // The other archive is left alone.
+    assert!(cache.join(&asset_name).exists());
+}
@@ -0,0 +1,85 @@
+//! An install plan has the sizes that the confirmation prompt and `--dry-run` show: how much
+//! is left to download, roughly how large the toolchain is once extracted, and how much space
+//! is free for both.
+
+mod common;
+
+use std::io::Read;
+
+use arm_toolchain::toolchain::{InstallPlan, ToolchainVersion};
+use common::{MockGitHub, MockRelease, MockServer, host_asset_name};
+
+/// Plans installing 21.1.0 after leaving `cached` in the download cache, if it isn't empty.
+async fn plan_with_cached(dir: &std::path::Path, cached: &[u8]) -> InstallPlan {
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let client = common::client(dir, &server).await;
+    let release = client
+        .get_release(&ToolchainVersion::named("21.1.0"))
+        .await
+        .unwrap();
+    let asset = common::host_asset(&client, &release);
+    if !cached.is_empty() {
+        let archive_path = client.cache_path_for(asset).unwrap();
+        std::fs::create_dir_all(archive_path.parent().unwrap()).unwrap();
+        std::fs::write(archive_path, cached).unwrap();
+    }
+
+    client.plan_install(&release, asset).await.unwrap()
+}
+
+fn archive() -> Vec<u8> {
+    MockRelease::toolchain("21.1.0")
+        .data(&host_asset_name("21.1.0"))
+        .to_vec()
+}
+
+#[tokio::test]
+async fn install_size_is_guessed_until_the_archive_is_cached() {
+    let archive = archive();
+    let size = archive.len() as u64;
+
+    for cached in [&[][..], &archive[..archive.len() / 2]] {
+        let dir = tempfile::tempdir().unwrap();
+        let plan = plan_with_cached(dir.path(), cached).await;
+
+        assert_eq!(plan.download_size, size);
+        assert_eq!(plan.cached_bytes, cached.len() as u64);
+        assert_eq!(plan.estimated_install_size, Some(size * 3));
+    }
+}
+
+#[tokio::test]
+async fn install_size_is_read_from_a_cached_archive() {
+    let archive = archive();
+    let mut tar = vec![];
+    liblzma::read::XzDecoder::new(&archive[..])
+        .read_to_end(&mut tar)
+        .unwrap();
+    let dir = tempfile::tempdir().unwrap();
+
+    let plan = plan_with_cached(dir.path(), &archive).await;
+
+    assert_eq!(plan.cached_bytes, plan.download_size);
+    assert_eq!(plan.estimated_install_size, Some(tar.len() as u64));
+}
+
+#[tokio::test]
+async fn free_space_is_probed_before_anything_exists() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let plan = plan_with_cached(dir.path(), &[]).await;
+
+    assert!(!plan.destination.exists());
+    let destination = plan.destination_space.unwrap();
+    let cache = plan.cache_space.unwrap();
+    assert!(
+        dir.path()
+            .canonicalize()
+            .unwrap()
+            .starts_with(&destination.mount_point),
+        "{destination:?}"
+    );
+    // Both are in the same temporary directory.
+    assert_eq!(cache.mount_point, destination.mount_point);
+    assert!(destination.available > 0);
+}
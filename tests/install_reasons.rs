@@ -0,0 +1,105 @@
+//! The install reasons registry can be changed by several clients at once without losing any
+//! of their changes.
+
+mod common;
+
+use std::{
+    fs,
+    time::{Duration, Instant},
+};
+
+use arm_toolchain::toolchain::{InstallReasons, ToolchainVersion};
+use common::{MockGitHub, MockServer};
+
+const WRITERS: usize = 16;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_writers_keep_every_reason() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(MockGitHub::new([]));
+    let version = ToolchainVersion::named("21.1.0");
+
+    fs::create_dir_all(dir.path().join("toolchains/embedded/21.1.0")).unwrap();
+
+    let mut writers = vec![];
+    for index in 0..WRITERS {
+        let client = common::client(dir.path(), &server).await;
+        let version = version.clone();
+        writers.push(tokio::spawn(async move {
+            client
+                .add_install_reason(&version, &format!("writer {index}"))
+                .await
+                .unwrap();
+        }));
+    }
+    for writer in writers {
+        writer.await.unwrap();
+    }
+
+    let client = common::client(dir.path(), &server).await;
+    let reasons = client.install_reasons().await.unwrap();
+    let mut recorded = reasons.get(&version).to_vec();
+    recorded.sort();
+    let mut expected: Vec<_> = (0..WRITERS)
+        .map(|index| format!("writer {index}"))
+        .collect();
+    expected.sort();
+    assert_eq!(recorded, expected);
+}
+
+#[tokio::test]
+async fn leftover_lock_file_does_not_block_writers() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(MockGitHub::new([]));
+    let client = common::client(dir.path(), &server).await;
+    let product_path = dir.path().join("toolchains/embedded");
+    fs::create_dir_all(product_path.join("21.1.0")).unwrap();
+    // Left behind by a process that crashed while holding the lock.
+    fs::write(
+        product_path.join(format!("{}.lock", InstallReasons::FILENAME)),
+        "",
+    )
+    .unwrap();
+
+    let started = Instant::now();
+    client
+        .add_install_reason(&ToolchainVersion::named("21.1.0"), "cli: use")
+        .await
+        .unwrap();
+
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(
+        client
+            .install_reasons()
+            .await
+            .unwrap()
+            .get(&ToolchainVersion::named("21.1.0")),
+        ["cli: use"]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn writers_wait_for_the_lock() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(MockGitHub::new([]));
+    let client = common::client(dir.path(), &server).await;
+    let product_path = dir.path().join("toolchains/embedded");
+    fs::create_dir_all(product_path.join("21.1.0")).unwrap();
+    let lock =
+        fs::File::create(product_path.join(format!("{}.lock", InstallReasons::FILENAME))).unwrap();
+    lock.lock().unwrap();
+
+    let held_for = Duration::from_millis(300);
+    let started = Instant::now();
+    let release = std::thread::spawn(move || {
+        std::thread::sleep(held_for);
+        drop(lock);
+    });
+    client
+        .add_install_reason(&ToolchainVersion::named("21.1.0"), "cli: use")
+        .await
+        .unwrap();
+
+    assert!(started.elapsed() >= held_for);
+    release.join().unwrap();
+}
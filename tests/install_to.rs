@@ -0,0 +1,117 @@
+//! Installing to a directory outside of the toolchains directory.
+
+mod common;
+
+use std::{fs, sync::Arc};
+
+use arm_toolchain::toolchain::{ToolchainError, ToolchainVersion};
+use common::{MockGitHub, MockRelease, MockServer, Response, host_asset_name};
+use tokio_util::sync::CancellationToken;
+
+fn existing_destination(dir: &std::path::Path) -> std::path::PathBuf {
+    let destination = dir.join("prefix");
+    fs::create_dir_all(&destination).unwrap();
+    fs::write(destination.join("keep.txt"), "mine").unwrap();
+    destination
+}
+
+#[tokio::test]
+async fn refuses_a_destination_that_isnt_empty() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let client = common::client(dir.path(), &server).await;
+    let release = client
+        .get_release(&ToolchainVersion::named("21.1.0"))
+        .await
+        .unwrap();
+    let destination = existing_destination(dir.path());
+
+    let result = client
+        .install_to(
+            &release,
+            common::host_asset(&client, &release),
+            &destination,
+            Arc::new(|_| {}),
+            CancellationToken::new(),
+        )
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(ToolchainError::DestinationNotEmpty { .. })
+    ));
+    assert_eq!(
+        fs::read_to_string(destination.join("keep.txt")).unwrap(),
+        "mine"
+    );
+}
+
+#[tokio::test]
+async fn replacing_keeps_the_destination_when_the_download_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    let asset_name = host_asset_name("21.1.0");
+    let github = MockGitHub::new([MockRelease::toolchain("21.1.0")]);
+    let server = MockServer::start(move |request| {
+        if request.file_name() == asset_name {
+            Response::not_found()
+        } else {
+            github.respond(request)
+        }
+    });
+    let client = common::client(dir.path(), &server).await;
+    let release = client
+        .get_release(&ToolchainVersion::named("21.1.0"))
+        .await
+        .unwrap();
+    let destination = existing_destination(dir.path());
+
+    let result = client
+        .install_to_replacing(
+            &release,
+            common::host_asset(&client, &release),
+            &destination,
+            Arc::new(|_| {}),
+            CancellationToken::new(),
+        )
+        .await;
+
+    assert!(result.is_err());
+    assert_eq!(
+        fs::read_to_string(destination.join("keep.txt")).unwrap(),
+        "mine"
+    );
+}
+
+#[tokio::test]
+async fn replacing_leaves_the_toolchains_directory_alone() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let client = common::client(dir.path(), &server).await;
+    let release = client
+        .get_release(&ToolchainVersion::named("21.1.0"))
+        .await
+        .unwrap();
+    let destination = existing_destination(dir.path());
+    let managed_before = common::list_tree(&dir.path().join("toolchains"));
+
+    let outcome = client
+        .install_to_replacing(
+            &release,
+            common::host_asset(&client, &release),
+            &destination,
+            Arc::new(|_| {}),
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(outcome.path, destination);
+    assert!(!outcome.activated);
+    assert!(!destination.join("keep.txt").exists());
+    assert!(destination.join("bin/clang").is_file());
+    assert_eq!(
+        common::list_tree(&dir.path().join("toolchains")),
+        managed_before
+    );
+    assert!(client.installed_versions().await.unwrap().is_empty());
+}
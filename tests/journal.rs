@@ -0,0 +1,165 @@
+//! Every journaled operation records how it ended, so that only operations cut off by the
+//! process exiting are reported as interrupted.
+
+mod common;
+
+use std::{fs, sync::Arc};
+
+use arm_toolchain::toolchain::{
+    ToolchainClient, ToolchainError, ToolchainVersion,
+    journal::{Journal, Operation, Status},
+};
+use common::{MockGitHub, MockRelease, MockServer, Response, host_asset_name};
+use tokio_util::sync::CancellationToken;
+
+/// A server whose 21.1.0 release can't be downloaded.
+fn missing_asset_server() -> MockServer {
+    let asset_name = host_asset_name("21.1.0");
+    let github = MockGitHub::new([MockRelease::toolchain("21.1.0")]);
+    MockServer::start(move |request| {
+        if request.file_name() == asset_name {
+            Response::not_found()
+        } else {
+            github.respond(request)
+        }
+    })
+}
+
+async fn install(client: &ToolchainClient) -> Result<(), ToolchainError> {
+    let release = client
+        .get_release(&ToolchainVersion::named("21.1.0"))
+        .await
+        .unwrap();
+    let asset = common::host_asset(client, &release);
+    client
+        .download_and_install(&release, asset, Arc::new(|_| {}), CancellationToken::new())
+        .await
+        .map(|_| ())
+}
+
+/// Writes a journal containing only the intent record of an operation that never finished.
+fn write_interrupted(journal: &Journal, operation: &str, subject: &str) {
+    fs::create_dir_all(journal.path().parent().unwrap()).unwrap();
+    fs::write(
+        journal.path(),
+        format!("2026-01-01T00:00:00Z\t1-1-0\tbegin\t{operation}\t{subject}\n"),
+    )
+    .unwrap();
+}
+
+#[tokio::test]
+async fn completed_install_is_not_reported() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let client = common::client(dir.path(), &server).await;
+
+    install(&client).await.unwrap();
+
+    let entries = client.journal().entries().await.unwrap();
+    let done: Vec<_> = entries
+        .iter()
+        .filter(|entry| entry.status == Status::Done)
+        .map(|entry| entry.operation)
+        .collect();
+    assert_eq!(
+        done,
+        [Operation::Download, Operation::Extract, Operation::Activate]
+    );
+    assert!(client.interrupted_operations().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn failed_download_is_not_reported_as_interrupted() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = missing_asset_server();
+    let client = common::client(dir.path(), &server).await;
+
+    install(&client).await.unwrap_err();
+
+    let failed = client.journal().failed().await.unwrap();
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].operation, Operation::Download);
+    assert!(client.journal().interrupted().await.unwrap().is_empty());
+    assert!(client.interrupted_operations().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn failed_activation_is_not_reported_as_interrupted() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let client = common::client(dir.path(), &server).await;
+    install(&client).await.unwrap();
+
+    // The active toolchain file can't be written over a directory with something in it.
+    let current = dir.path().join("toolchains/embedded/current.txt");
+    fs::remove_file(&current).unwrap();
+    fs::create_dir_all(current.join("blocker")).unwrap();
+    client
+        .set_active_toolchain(Some(ToolchainVersion::named("21.1.0")))
+        .await
+        .unwrap_err();
+
+    let failed = client.journal().failed().await.unwrap();
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].operation, Operation::Activate);
+    assert!(client.interrupted_operations().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn retried_install_resolves_a_failed_download() {
+    let dir = tempfile::tempdir().unwrap();
+    let client = common::client(dir.path(), &missing_asset_server()).await;
+    install(&client).await.unwrap_err();
+
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let client = common::client(dir.path(), &server).await;
+    install(&client).await.unwrap();
+
+    assert!(client.journal().failed().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn interrupted_extract_is_cleaned_up() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let client = common::client(dir.path(), &server).await;
+    let partial = dir.path().join("toolchains/embedded/21.1.0");
+    fs::create_dir_all(partial.join("bin")).unwrap();
+    write_interrupted(&client.journal(), "extract", "embedded/21.1.0");
+
+    let interrupted = client.interrupted_operations().await.unwrap();
+    assert_eq!(interrupted.len(), 1);
+    assert!(!interrupted[0].failed);
+    assert_eq!(interrupted[0].artifact.as_deref(), Some(partial.as_path()));
+
+    let removed = client
+        .clean_up_interrupted(&CancellationToken::new())
+        .await
+        .unwrap();
+    assert_eq!(removed, [partial.as_path()]);
+    assert!(!partial.exists());
+    assert!(client.interrupted_operations().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn interrupted_download_is_kept_by_cleanup() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let client = common::client(dir.path(), &server).await;
+    let asset_name = host_asset_name("21.1.0");
+    let partial = client.cache_path_for_name(&asset_name).unwrap();
+    fs::create_dir_all(partial.parent().unwrap()).unwrap();
+    fs::write(&partial, b"partial").unwrap();
+    write_interrupted(&client.journal(), "download", &asset_name);
+
+    let removed = client
+        .clean_up_interrupted(&CancellationToken::new())
+        .await
+        .unwrap();
+
+    assert!(removed.is_empty());
+    assert!(partial.exists());
+    let interrupted = client.interrupted_operations().await.unwrap();
+    assert_eq!(interrupted.len(), 1);
+    assert_eq!(interrupted[0].entry.operation, Operation::Download);
+}
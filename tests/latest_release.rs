@@ -0,0 +1,68 @@
+//! The latest release is found past the first page of releases, which may be taken up by
+//! other products published from the same repository.
+
+mod common;
+
+use arm_toolchain::toolchain::{ToolchainError, ToolchainVersion};
+use common::{MockGitHub, MockRelease, MockServer};
+
+const RELEASES_PATH: &str = "/repos/arm/arm-toolchain/releases";
+
+/// A release of another product in the same repository.
+fn other_product(version: &str) -> MockRelease {
+    MockRelease {
+        tag_name: format!("release-{version}-ATfL"),
+        ..MockRelease::empty(version)
+    }
+}
+
+/// Returns how many pages of releases the server was asked for.
+fn page_requests(server: &MockServer) -> usize {
+    server
+        .requests()
+        .iter()
+        .filter(|request| request.path.split('?').next() == Some(RELEASES_PATH))
+        .count()
+}
+
+#[tokio::test]
+async fn finds_the_latest_release_on_a_later_page() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(
+        MockGitHub::new([
+            other_product("21.1.4"),
+            other_product("21.1.3"),
+            other_product("21.1.2"),
+            other_product("21.1.1"),
+            MockRelease::empty("21.1.0"),
+            MockRelease::empty("20.1.0"),
+        ])
+        .paginated(2),
+    );
+    let client = common::client(dir.path(), &server).await;
+
+    let latest = client.latest_release().await.unwrap();
+
+    assert_eq!(latest.version(), &ToolchainVersion::named("21.1.0"));
+    assert_eq!(page_requests(&server), 3);
+}
+
+#[tokio::test]
+async fn stops_after_a_bounded_number_of_pages() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut releases = (1..=12)
+        .rev()
+        .map(|patch| other_product(&format!("21.1.{patch}")))
+        .collect::<Vec<_>>();
+    releases.push(MockRelease::empty("21.1.0"));
+    let server = MockServer::github(MockGitHub::new(releases).paginated(1));
+    let client = common::client(dir.path(), &server).await;
+
+    let Err(ToolchainError::LatestReleaseMissing { candidates }) = client.latest_release().await
+    else {
+        panic!("expected the latest release to be missing");
+    };
+    assert_eq!(candidates.len(), 10);
+    assert_eq!(candidates[0], "release-21.1.12-ATfL");
+    assert_eq!(page_requests(&server), 10);
+}
@@ -0,0 +1,168 @@
+//! Migrating the layout of the toolchains directory when a client is created.
+
+use std::{fs, path::Path};
+
+use arm_toolchain::toolchain::{
+    LAYOUT_VERSION, LayoutMigration, Product, ToolchainClient, ToolchainError, ToolchainVersion,
+    journal::{Journal, Operation, Status},
+};
+
+/// Creates a toolchain that was installed before manifests were written.
+fn old_toolchain(path: &Path) {
+    fs::create_dir_all(path.join("bin")).unwrap();
+    fs::write(path.join("bin/clang"), "").unwrap();
+}
+
+async fn open(toolchains_path: &Path) -> Result<ToolchainClient, ToolchainError> {
+    ToolchainClient::builder()
+        .toolchains_path(toolchains_path)
+        .cache_path(toolchains_path.join("../cache"))
+        .offline(true)
+        .build()
+        .await
+}
+
+fn layout_version(toolchains_path: &Path) -> String {
+    fs::read_to_string(toolchains_path.join("layout-version")).unwrap()
+}
+
+#[tokio::test]
+async fn flat_layout_is_moved_into_product_directories() {
+    let dir = tempfile::tempdir().unwrap();
+    let toolchains = dir.path().join("toolchains");
+    old_toolchain(&toolchains.join("21.1.0"));
+    fs::write(toolchains.join("current.txt"), "21.1.0").unwrap();
+    // Other directories that were put there aren't toolchains.
+    fs::create_dir_all(toolchains.join("notes")).unwrap();
+    fs::write(toolchains.join("notes/todo.txt"), "").unwrap();
+
+    let client = open(&toolchains).await.unwrap();
+
+    assert_eq!(
+        client.layout_migrations(),
+        [
+            LayoutMigration::RecordLayoutVersion,
+            LayoutMigration::ProductDirectories
+        ]
+    );
+    let embedded = toolchains.join(Product::EMBEDDED.id);
+    assert!(embedded.join("21.1.0/bin/clang").is_file());
+    assert!(embedded.join("current.txt").is_file());
+    assert!(!toolchains.join("21.1.0").exists());
+    assert!(toolchains.join("notes/todo.txt").is_file());
+    assert_eq!(
+        client.active_toolchain(),
+        Some(ToolchainVersion::named("21.1.0"))
+    );
+    assert_eq!(layout_version(&toolchains), format!("{LAYOUT_VERSION}\n"));
+}
+
+#[tokio::test]
+async fn product_layout_is_left_alone() {
+    let dir = tempfile::tempdir().unwrap();
+    let toolchains = dir.path().join("toolchains");
+    let embedded = toolchains.join(Product::EMBEDDED.id);
+    old_toolchain(&embedded.join("21.1.0"));
+    fs::write(embedded.join("current.txt"), "21.1.0").unwrap();
+    fs::write(toolchains.join("layout-version"), "1\n").unwrap();
+
+    let client = open(&toolchains).await.unwrap();
+
+    assert_eq!(
+        client.layout_migrations(),
+        [LayoutMigration::ProductDirectories]
+    );
+    assert!(embedded.join("21.1.0/bin/clang").is_file());
+    assert!(!embedded.join(Product::EMBEDDED.id).exists());
+    assert_eq!(
+        client.active_toolchain(),
+        Some(ToolchainVersion::named("21.1.0"))
+    );
+}
+
+#[tokio::test]
+async fn each_migration_runs_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let toolchains = dir.path().join("toolchains");
+    old_toolchain(&toolchains.join("21.1.0"));
+
+    let first = open(&toolchains).await.unwrap();
+    let second = open(&toolchains).await.unwrap();
+
+    assert_eq!(first.layout_migrations(), LayoutMigration::ALL);
+    assert!(second.layout_migrations().is_empty());
+    let migrations = Journal::in_dir(&toolchains)
+        .entries()
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|entry| entry.operation == Operation::Migrate)
+        .map(|entry| (entry.status, entry.subject))
+        .collect::<Vec<_>>();
+    let expected = LayoutMigration::ALL
+        .iter()
+        .flat_map(|migration| {
+            let subject = format!("layout-{}", migration.version());
+            [(Status::Begin, subject.clone()), (Status::Done, subject)]
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(migrations, expected);
+    assert!(
+        toolchains
+            .join(Product::EMBEDDED.id)
+            .join("21.1.0/bin/clang")
+            .is_file()
+    );
+}
+
+#[tokio::test]
+async fn current_layout_is_not_migrated_again() {
+    let dir = tempfile::tempdir().unwrap();
+    let toolchains = dir.path().join("toolchains");
+    fs::create_dir_all(&toolchains).unwrap();
+    fs::write(
+        toolchains.join("layout-version"),
+        format!("{LAYOUT_VERSION}\n"),
+    )
+    .unwrap();
+    // Something that looks like a toolchain, but was put there after the migration.
+    old_toolchain(&toolchains.join("custom"));
+
+    let client = open(&toolchains).await.unwrap();
+
+    assert!(client.layout_migrations().is_empty());
+    assert!(toolchains.join("custom/bin/clang").is_file());
+}
+
+#[tokio::test]
+async fn newer_layout_is_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let toolchains = dir.path().join("toolchains");
+    fs::create_dir_all(&toolchains).unwrap();
+    fs::write(
+        toolchains.join("layout-version"),
+        format!("{}\n", LAYOUT_VERSION + 1),
+    )
+    .unwrap();
+
+    let result = open(&toolchains).await;
+
+    assert!(matches!(result, Err(ToolchainError::NewerLayout { .. })));
+}
+
+#[tokio::test]
+async fn new_directory_is_created_with_the_current_layout() {
+    let dir = tempfile::tempdir().unwrap();
+    let toolchains = dir.path().join("toolchains");
+    let client = open(&toolchains).await.unwrap();
+    assert!(!toolchains.exists());
+
+    client
+        .set_active_toolchain(Some(ToolchainVersion::named("21.1.0")))
+        .await
+        .unwrap();
+
+    assert_eq!(layout_version(&toolchains), format!("{LAYOUT_VERSION}\n"));
+    let reopened = open(&toolchains).await.unwrap();
+    assert!(reopened.layout_migrations().is_empty());
+}
@@ -0,0 +1,112 @@
+//! Files that were changed after a toolchain was installed are found by comparing it against
+//! the file list recorded when it was installed, so that they aren't lost to a reinstall.
+
+mod common;
+
+use std::{fs, path::PathBuf, sync::Arc};
+
+use arm_toolchain::toolchain::{InstalledToolchain, LocalChanges, ToolchainVersion};
+use common::{MockGitHub, MockRelease, MockServer};
+use tokio_util::sync::CancellationToken;
+
+/// Installs 21.1.0 into `dir` and returns it.
+async fn installed(dir: &std::path::Path) -> InstalledToolchain {
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let client = common::client(dir, &server).await;
+    let version = ToolchainVersion::named("21.1.0");
+    let release = client.get_release(&version).await.unwrap();
+    client
+        .download_and_install(
+            &release,
+            common::host_asset(&client, &release),
+            Arc::new(|_| {}),
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+    client.toolchain(&version).await.unwrap()
+}
+
+/// Modifies `multilib.yaml`, adds `link.ld`, and deletes `libc.a`.
+fn change_files(toolchain: &InstalledToolchain) {
+    let runtimes = toolchain.path.join("lib/clang-runtimes");
+    fs::write(
+        runtimes.join("multilib.yaml"),
+        "MultilibVersion: 1.1\n# patched\n",
+    )
+    .unwrap();
+    fs::write(runtimes.join("arm-none-eabi/lib/link.ld"), "SECTIONS {}\n").unwrap();
+    fs::remove_file(runtimes.join("arm-none-eabi/lib/libc.a")).unwrap();
+}
+
+fn paths(paths: &[&str]) -> Vec<PathBuf> {
+    paths.iter().map(PathBuf::from).collect()
+}
+
+#[tokio::test]
+async fn fresh_install_has_no_changes() {
+    let dir = tempfile::tempdir().unwrap();
+    let toolchain = installed(dir.path()).await;
+
+    let changes = toolchain.local_changes().await.unwrap().unwrap();
+
+    assert!(changes.is_empty(), "{changes:?}");
+}
+
+#[tokio::test]
+async fn modified_added_and_deleted_files_are_found() {
+    let dir = tempfile::tempdir().unwrap();
+    let toolchain = installed(dir.path()).await;
+    change_files(&toolchain);
+
+    let changes = toolchain.local_changes().await.unwrap().unwrap();
+
+    assert_eq!(
+        changes,
+        LocalChanges {
+            modified: paths(&["lib/clang-runtimes/multilib.yaml"]),
+            added: paths(&["lib/clang-runtimes/arm-none-eabi/lib/link.ld"]),
+            deleted: paths(&["lib/clang-runtimes/arm-none-eabi/lib/libc.a"]),
+        }
+    );
+    assert_eq!(changes.len(), 3);
+}
+
+#[tokio::test]
+async fn backup_has_the_modified_and_added_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let toolchain = installed(dir.path()).await;
+    change_files(&toolchain);
+    let changes = toolchain.local_changes().await.unwrap().unwrap();
+    let backup = dir.path().join("backup");
+
+    toolchain
+        .backup_local_changes(&changes, &backup)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        common::list_tree(&backup),
+        [
+            "lib",
+            "lib/clang-runtimes",
+            "lib/clang-runtimes/arm-none-eabi",
+            "lib/clang-runtimes/arm-none-eabi/lib",
+            "lib/clang-runtimes/arm-none-eabi/lib/link.ld",
+            "lib/clang-runtimes/multilib.yaml",
+        ]
+    );
+    assert_eq!(
+        fs::read_to_string(backup.join("lib/clang-runtimes/multilib.yaml")).unwrap(),
+        "MultilibVersion: 1.1\n# patched\n"
+    );
+}
+
+#[tokio::test]
+async fn toolchain_without_a_file_list_has_unknown_changes() {
+    let dir = tempfile::tempdir().unwrap();
+    let toolchain = installed(dir.path()).await;
+    fs::remove_file(toolchain.path.join(".arm-toolchain-files")).unwrap();
+
+    assert_eq!(toolchain.local_changes().await.unwrap(), None);
+}
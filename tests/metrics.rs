@@ -0,0 +1,73 @@
+//! The metrics textfile is read by node_exporter, so its format is compared against files in
+//! the Prometheus text exposition format.
+
+#![cfg(feature = "cli-core")]
+
+use std::time::Duration;
+
+use arm_toolchain::{
+    cli::{MetricsSnapshot, OperationMetrics, ProductMetrics},
+    toolchain::{Product, ToolchainVersion, journal::CompletedInstall},
+};
+use chrono::{DateTime, Utc};
+
+fn time(rfc3339: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(rfc3339).unwrap().to_utc()
+}
+
+#[test]
+fn fresh_profile_after_a_failed_install() {
+    let snapshot = MetricsSnapshot {
+        products: vec![ProductMetrics {
+            product: Product::EMBEDDED,
+            installed: 0,
+            active: None,
+        }],
+        cache_bytes: 0,
+        last_install: None,
+        last_operation: OperationMetrics {
+            command: "install",
+            success: false,
+            finished: time("2026-01-02T03:04:05Z"),
+        },
+    };
+
+    assert_eq!(
+        snapshot.render(),
+        include_str!("fixtures/metrics/fresh.prom")
+    );
+}
+
+#[test]
+fn installed_toolchains_of_each_product() {
+    let snapshot = MetricsSnapshot {
+        products: vec![
+            ProductMetrics {
+                product: Product::EMBEDDED,
+                installed: 2,
+                active: Some(ToolchainVersion::named("21.1.0")),
+            },
+            ProductMetrics {
+                product: Product::LINUX,
+                installed: 1,
+                // Label values are escaped.
+                active: Some(ToolchainVersion::named(r#"custom "build" \ 2"#)),
+            },
+        ],
+        cache_bytes: 123_456_789,
+        last_install: Some(CompletedInstall {
+            finished: time("2026-01-02T03:00:00Z"),
+            duration: Duration::from_secs(95),
+        }),
+        last_operation: OperationMetrics {
+            command: "use",
+            success: true,
+            finished: time("2026-01-02T03:04:05Z"),
+        },
+    };
+
+    assert_eq!(
+        snapshot.render(),
+        include_str!("fixtures/metrics/installed.prom")
+    );
+}
@@ -0,0 +1,206 @@
+//! Runtime libraries are selected from a `multilib.yaml` the way clang selects them.
+
+#![cfg(feature = "multilib")]
+
+use arm_toolchain::toolchain::{FloatAbi, InstalledToolchain, Multilib, TargetSpec};
+
+const MULTILIB_YAML: &str = include_str!("fixtures/multilib.yaml");
+
+/// The variant directory selected for each target, or `None` if no runtime matches it.
+const CASES: &[(&str, FloatAbi, Option<&str>, Option<&str>)] = &[
+    ("cortex-m0", FloatAbi::Soft, None, Some("armv6m_soft_nofp")),
+    (
+        "cortex-m0plus",
+        FloatAbi::Soft,
+        None,
+        Some("armv6m_soft_nofp"),
+    ),
+    ("cortex-m3", FloatAbi::Soft, None, Some("armv7m_soft_nofp")),
+    ("cortex-m4", FloatAbi::Soft, None, Some("armv7m_soft_nofp")),
+    (
+        "cortex-m4",
+        FloatAbi::Hard,
+        None,
+        Some("armv7em_hard_fpv4_sp_d16"),
+    ),
+    ("cortex-m7", FloatAbi::Soft, None, Some("armv7m_soft_nofp")),
+    (
+        "cortex-m7",
+        FloatAbi::Hard,
+        None,
+        Some("armv7em_hard_fpv5_d16"),
+    ),
+    (
+        "cortex-m7",
+        FloatAbi::Hard,
+        Some("fpv4-sp-d16"),
+        Some("armv7em_hard_fpv4_sp_d16"),
+    ),
+    (
+        "cortex-m33",
+        FloatAbi::Soft,
+        None,
+        Some("armv8m.main_soft_nofp"),
+    ),
+    (
+        "cortex-m33",
+        FloatAbi::Hard,
+        None,
+        Some("armv8m.main_hard_fp"),
+    ),
+    ("cortex-a5", FloatAbi::Soft, None, Some("armv7a_soft_nofp")),
+    (
+        "cortex-a7",
+        FloatAbi::Hard,
+        None,
+        Some("armv7a_hard_vfpv3_d16"),
+    ),
+    (
+        "cortex-a9",
+        FloatAbi::Hard,
+        None,
+        Some("armv7a_hard_vfpv3_d16"),
+    ),
+    ("cortex-a53", FloatAbi::Hard, None, Some("aarch64a")),
+    ("cortex-a72", FloatAbi::Soft, None, Some("aarch64a")),
+    // No runtimes for these.
+    ("cortex-m0", FloatAbi::Hard, None, None),
+    ("cortex-m4", FloatAbi::Hard, Some("fpv5-sp-d16"), None),
+    ("cortex-m55", FloatAbi::Hard, None, None),
+    ("cortex-m85", FloatAbi::Soft, None, None),
+    ("cortex-r5", FloatAbi::Hard, None, None),
+    ("cortex-r52", FloatAbi::Hard, None, None),
+];
+
+fn multilib() -> Multilib {
+    Multilib::parse(MULTILIB_YAML).unwrap()
+}
+
+#[test]
+fn selects_the_runtime_clang_would() {
+    let multilib = multilib();
+
+    for &(cpu, float_abi, fpu, expected) in CASES {
+        let target = TargetSpec::new(cpu, float_abi, fpu.map(str::to_string)).unwrap();
+        let selection = multilib.select(&target.multilib_flags());
+
+        let selected = selection
+            .primary()
+            .and_then(|variant| variant.triple_and_name());
+        assert_eq!(
+            selected,
+            expected.map(|name| ("arm-none-eabi", name)),
+            "{target}: {selection:#?}"
+        );
+        assert_eq!(selection.error(), None, "{target}");
+    }
+}
+
+#[test]
+fn only_the_last_variant_of_an_exclusive_group_is_selected() {
+    let target = TargetSpec::new("cortex-m7", FloatAbi::Hard, None).unwrap();
+
+    let selection = multilib().select(&target.multilib_flags());
+
+    let dirs: Vec<_> = selection
+        .variants
+        .iter()
+        .filter_map(|variant| variant.dir.as_deref())
+        .collect();
+    assert_eq!(dirs, ["arm-none-eabi/armv7em_hard_fpv5_d16"]);
+}
+
+#[test]
+fn mappings_add_flags() {
+    let target = TargetSpec::new("cortex-m4", FloatAbi::Soft, None).unwrap();
+
+    let selection = multilib().select(&target.multilib_flags());
+
+    assert!(
+        selection
+            .flags
+            .contains(&"--target=thumbv7m-unknown-none-eabi".to_string())
+    );
+    assert!(
+        selection
+            .flags
+            .contains(&"--target=thumbv7em-unknown-none-eabi".to_string())
+    );
+}
+
+#[test]
+fn reports_the_error_of_an_unsupported_combination() {
+    let target = TargetSpec::new("cortex-m4", FloatAbi::Softfp, None).unwrap();
+
+    let selection = multilib().select(&target.multilib_flags());
+
+    assert!(selection.primary().is_none());
+    assert!(
+        selection
+            .error()
+            .is_some_and(|error| error.contains("softfp float ABI is not supported")),
+        "{selection:#?}"
+    );
+}
+
+#[test]
+fn lists_the_supported_cpus() {
+    let supported = multilib().supported_cpus();
+
+    for cpu in [
+        "cortex-m0",
+        "cortex-m4",
+        "cortex-m33",
+        "cortex-a9",
+        "cortex-a53",
+    ] {
+        assert!(supported.contains(&cpu), "{cpu} in {supported:?}");
+    }
+    for cpu in ["cortex-m55", "cortex-r52"] {
+        assert!(!supported.contains(&cpu), "{cpu} in {supported:?}");
+    }
+}
+
+#[test]
+fn rejects_invalid_files() {
+    assert!(Multilib::parse("Variants: [").is_err());
+    assert!(Multilib::parse("Variants: 1").is_err());
+
+    let error = Multilib::parse("Mappings:\n- Match: '('\n  Flags: []\n").unwrap_err();
+    assert!(error.contains("invalid pattern"), "{error}");
+}
+
+#[tokio::test]
+async fn reads_an_installed_toolchains_multilib() {
+    let dir = tempfile::tempdir().unwrap();
+    let toolchain = InstalledToolchain::new(dir.path().to_owned());
+    std::fs::create_dir_all(toolchain.multilib_dir()).unwrap();
+    std::fs::write(
+        toolchain.multilib_dir().join("multilib.yaml"),
+        MULTILIB_YAML,
+    )
+    .unwrap();
+
+    let multilib = toolchain.multilib().await.unwrap();
+
+    assert_eq!(multilib.variants().len(), 10);
+}
+
+#[tokio::test]
+async fn reports_a_damaged_multilib() {
+    let dir = tempfile::tempdir().unwrap();
+    let toolchain = InstalledToolchain::new(dir.path().to_owned());
+    std::fs::create_dir_all(toolchain.multilib_dir()).unwrap();
+    std::fs::write(
+        toolchain.multilib_dir().join("multilib.yaml"),
+        "Variants: [",
+    )
+    .unwrap();
+
+    let error = toolchain.multilib().await.unwrap_err();
+
+    assert_eq!(
+        error.stable_code(),
+        "arm_toolchain::toolchain::invalid_multilib"
+    );
+}
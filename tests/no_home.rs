@@ -0,0 +1,57 @@
+//! Clients with explicit paths work without a home directory, such as in a systemd service
+//! or a container.
+//!
+//! This is its own test binary with a single test, because it changes the environment of
+//! the whole process.
+
+mod common;
+
+use std::sync::Arc;
+
+use arm_toolchain::toolchain::{ToolchainClient, ToolchainVersion};
+use common::{MockGitHub, MockRelease, MockServer};
+use tokio_util::sync::CancellationToken;
+
+#[test]
+fn explicit_paths_do_not_need_a_home_directory() {
+    // SAFETY: No other threads are running yet, since this is the only test in the binary.
+    unsafe {
+        std::env::remove_var("HOME");
+        std::env::remove_var("XDG_DATA_HOME");
+        std::env::remove_var("XDG_CACHE_HOME");
+    }
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let dir = tempfile::tempdir().unwrap();
+        let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+
+        let client = ToolchainClient::new(dir.path().join("toolchains"), dir.path().join("cache"))
+            .await
+            .unwrap();
+        assert!(client.installed_versions().await.unwrap().is_empty());
+
+        let client = common::client(dir.path(), &server).await;
+        let release = client
+            .get_release(&ToolchainVersion::named("21.1.0"))
+            .await
+            .unwrap();
+        client
+            .download_and_install(
+                &release,
+                common::host_asset(&client, &release),
+                Arc::new(|_| {}),
+                CancellationToken::new(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            client.active_toolchain(),
+            Some(ToolchainVersion::named("21.1.0"))
+        );
+    });
+}
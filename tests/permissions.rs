@@ -0,0 +1,135 @@
+//! Permission policies are applied to every file and directory of an extracted toolchain.
+
+#![cfg(unix)]
+
+mod common;
+
+use std::{
+    os::unix::fs::PermissionsExt,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use arm_toolchain::toolchain::{InstallState, PermissionPolicy, ToolchainVersion};
+use common::{MockGitHub, MockRelease, MockServer};
+use tokio_util::sync::CancellationToken;
+
+/// Installs a toolchain with the given policy, returning where it was installed and the
+/// progress events that were reported.
+async fn install(dir: &Path, policy: PermissionPolicy) -> (std::path::PathBuf, Vec<InstallState>) {
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let client = common::client_builder(dir, &server)
+        .permission_policy(policy)
+        .build()
+        .await
+        .unwrap();
+    let release = client
+        .get_release(&ToolchainVersion::named("21.1.0"))
+        .await
+        .unwrap();
+    let events = Arc::new(Mutex::new(vec![]));
+    let destination = dir.join("toolchain");
+    client
+        .install_to(
+            &release,
+            common::host_asset(&client, &release),
+            destination.clone(),
+            Arc::new({
+                let events = events.clone();
+                move |state| events.lock().unwrap().push(state)
+            }),
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+    let events = events.lock().unwrap().clone();
+    (destination, events)
+}
+
+fn mode(path: &Path) -> u32 {
+    std::fs::symlink_metadata(path)
+        .unwrap()
+        .permissions()
+        .mode()
+        & 0o7777
+}
+
+/// The mode of each file in the fixture toolchain, relative to its root.
+fn modes(root: &Path) -> Vec<(&'static str, u32)> {
+    [
+        "",
+        "bin",
+        "bin/clang",
+        "lib/clang-runtimes",
+        "lib/clang-runtimes/multilib.yaml",
+        "lib/clang-runtimes/arm-none-eabi/lib/libc.a",
+    ]
+    .into_iter()
+    .map(|path| (path, mode(&root.join(path))))
+    .collect()
+}
+
+#[tokio::test]
+async fn shared_read_opens_the_toolchain_to_its_group() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let (toolchain, events) = install(dir.path(), PermissionPolicy::SharedRead).await;
+
+    assert_eq!(
+        modes(&toolchain),
+        [
+            ("", 0o2775),
+            ("bin", 0o2775),
+            ("bin/clang", 0o775),
+            ("lib/clang-runtimes", 0o2775),
+            ("lib/clang-runtimes/multilib.yaml", 0o664),
+            ("lib/clang-runtimes/arm-none-eabi/lib/libc.a", 0o664),
+        ]
+    );
+
+    let total = events.iter().find_map(|event| match event {
+        InstallState::ApplyPermissionsBegin { total_entries } => Some(*total_entries),
+        _ => None,
+    });
+    let done = events.iter().rev().find_map(|event| match event {
+        InstallState::ApplyPermissions { entries_done } => Some(*entries_done),
+        _ => None,
+    });
+    assert!(total.is_some_and(|total| total >= 6), "{events:?}");
+    assert_eq!(done, total);
+    assert!(events.contains(&InstallState::ApplyPermissionsFinish));
+}
+
+#[tokio::test]
+async fn mask_only_makes_executables_executable() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let (toolchain, _) = install(dir.path(), PermissionPolicy::Mask(0o077)).await;
+
+    assert_eq!(mode(&toolchain.join("bin/clang")), 0o777);
+    assert_eq!(
+        mode(&toolchain.join("lib/clang-runtimes/multilib.yaml")),
+        0o666
+    );
+    assert_eq!(mode(&toolchain.join("bin")) & 0o077, 0o077);
+}
+
+#[tokio::test]
+async fn preserve_keeps_the_archives_modes() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let (toolchain, events) = install(dir.path(), PermissionPolicy::Preserve).await;
+
+    assert_eq!(mode(&toolchain.join("bin/clang")) & 0o111, 0o111);
+    assert_eq!(
+        mode(&toolchain.join("lib/clang-runtimes/multilib.yaml")) & 0o111,
+        0
+    );
+    assert!(
+        !events
+            .iter()
+            .any(|event| matches!(event, InstallState::ApplyPermissionsBegin { .. })),
+        "{events:?}"
+    );
+}
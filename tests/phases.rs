@@ -0,0 +1,192 @@
+//! Progress events are grouped into phases with stable names, which the crate's own logs use
+//! and which applications can use as metric labels.
+
+use std::time::Duration;
+
+use arm_toolchain::toolchain::{InstallState, Phase, RemoveProgress, ToolchainVersion};
+use strum::VariantArray;
+
+/// One of each install event, with whether it's the last event of its phase.
+fn install_states() -> Vec<(InstallState, Phase, bool)> {
+    let states = vec![
+        (
+            InstallState::DownloadBegin {
+                asset_size: 100,
+                bytes_read: 0,
+            },
+            Phase::Download,
+            false,
+        ),
+        (
+            InstallState::Download { bytes_read: 50 },
+            Phase::Download,
+            false,
+        ),
+        (InstallState::DownloadFinish, Phase::Download, true),
+        (
+            InstallState::DownloadRetry {
+                attempt: 1,
+                max_attempts: 3,
+                delay: Duration::from_secs(1),
+                reason: "connection reset".to_string(),
+            },
+            Phase::Download,
+            false,
+        ),
+        (
+            InstallState::VerifyingBegin { asset_size: 100 },
+            Phase::Verify,
+            false,
+        ),
+        (
+            InstallState::Verifying { bytes_read: 50 },
+            Phase::Verify,
+            false,
+        ),
+        (InstallState::VerifyingFinish, Phase::Verify, true),
+        (InstallState::VerifyingSkipped, Phase::Verify, true),
+        (
+            InstallState::ExtractBegin {
+                estimated_size: None,
+            },
+            Phase::Extract,
+            false,
+        ),
+        (InstallState::RemoveOldInstallBegin, Phase::Extract, false),
+        (InstallState::RemoveOldInstallFinish, Phase::Extract, false),
+        (
+            InstallState::ExtractCopy {
+                total_size: 100,
+                bytes_copied: 50,
+            },
+            Phase::Extract,
+            false,
+        ),
+        (
+            InstallState::ApplyPermissionsBegin { total_entries: 10 },
+            Phase::Permissions,
+            false,
+        ),
+        (
+            InstallState::ApplyPermissions { entries_done: 5 },
+            Phase::Permissions,
+            false,
+        ),
+        (
+            InstallState::ApplyPermissionsFinish,
+            Phase::Permissions,
+            true,
+        ),
+        (InstallState::ExtractCleanUp, Phase::Cleanup, false),
+        (InstallState::ExtractDone, Phase::Cleanup, true),
+        (
+            InstallState::Activated {
+                version: ToolchainVersion::named("21.1.0"),
+            },
+            Phase::Activate,
+            true,
+        ),
+    ];
+
+    // Fails to compile when a variant is added, so that it's added to the list above too.
+    for (state, _, _) in &states {
+        match state {
+            InstallState::DownloadBegin { .. }
+            | InstallState::Download { .. }
+            | InstallState::DownloadFinish
+            | InstallState::DownloadRetry { .. }
+            | InstallState::VerifyingBegin { .. }
+            | InstallState::Verifying { .. }
+            | InstallState::VerifyingFinish
+            | InstallState::VerifyingSkipped
+            | InstallState::ExtractBegin { .. }
+            | InstallState::RemoveOldInstallBegin
+            | InstallState::RemoveOldInstallFinish
+            | InstallState::ExtractCopy { .. }
+            | InstallState::ApplyPermissionsBegin { .. }
+            | InstallState::ApplyPermissions { .. }
+            | InstallState::ApplyPermissionsFinish
+            | InstallState::ExtractCleanUp
+            | InstallState::ExtractDone
+            | InstallState::Activated { .. } => {}
+        }
+    }
+
+    states
+}
+
+#[test]
+fn names_are_stable() {
+    let names: Vec<_> = Phase::VARIANTS.iter().map(|phase| phase.as_str()).collect();
+
+    assert_eq!(
+        names,
+        [
+            "download",
+            "verify",
+            "extract",
+            "permissions",
+            "cleanup",
+            "activate",
+            "remove"
+        ]
+    );
+}
+
+#[test]
+fn names_are_lowercase_and_round_trip() {
+    for &phase in Phase::VARIANTS {
+        let name = phase.as_str();
+
+        assert!(!name.is_empty());
+        assert!(
+            name.chars().all(|c| c.is_ascii_lowercase() || c == '_'),
+            "{name}"
+        );
+        assert_eq!(phase.to_string(), name);
+        assert_eq!(name.parse::<Phase>().unwrap(), phase);
+        assert_eq!(serde_json::to_value(phase).unwrap(), name);
+    }
+}
+
+#[test]
+fn every_install_event_has_a_phase() {
+    for (state, phase, terminal) in install_states() {
+        assert_eq!(state.phase(), phase, "{state:?}");
+        assert_eq!(state.is_terminal(), terminal, "{state:?}");
+    }
+}
+
+#[test]
+fn every_phase_but_remove_is_part_of_installing() {
+    let install_phases: Vec<_> = install_states()
+        .into_iter()
+        .map(|(state, _, _)| state.phase())
+        .collect();
+
+    for &phase in Phase::VARIANTS {
+        assert_eq!(
+            install_phases.contains(&phase),
+            phase != Phase::Remove,
+            "{phase}"
+        );
+    }
+}
+
+#[test]
+fn remove_events_are_in_the_remove_phase() {
+    for (event, terminal) in [
+        (RemoveProgress::Start { total_bytes: 100 }, false),
+        (RemoveProgress::Progress { bytes_removed: 50 }, false),
+        (RemoveProgress::End, true),
+    ] {
+        assert_eq!(event.phase(), Phase::Remove);
+        assert_eq!(event.is_terminal(), terminal);
+    }
+}
+
+#[test]
+fn unknown_names_are_rejected() {
+    assert!("Download".parse::<Phase>().is_err());
+    assert!("unpack".parse::<Phase>().is_err());
+}
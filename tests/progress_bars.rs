@@ -0,0 +1,165 @@
+//! The CLI's progress bars can be added to an application's own `MultiProgress`, and cleared
+//! from it without disturbing the application's bars.
+
+#![cfg(feature = "cli-interactive")]
+
+mod common;
+
+use std::{
+    io,
+    sync::{Arc, Mutex},
+};
+
+use arm_toolchain::{
+    cli::{HostArgs, ProgressBars, install_with_progress_bar, remove_with_progress_bar},
+    toolchain::ToolchainVersion,
+};
+use common::{MockGitHub, MockRelease, MockServer};
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle, TermLike};
+use tokio_util::sync::CancellationToken;
+
+/// A terminal that records the lines drawn on it.
+#[derive(Debug, Clone, Default)]
+struct RecordingTerm {
+    lines: Arc<Mutex<Vec<String>>>,
+}
+
+impl RecordingTerm {
+    /// Returns the lines drawn since the last call.
+    fn take(&self) -> Vec<String> {
+        std::mem::take(&mut *self.lines.lock().unwrap())
+    }
+}
+
+impl TermLike for RecordingTerm {
+    fn width(&self) -> u16 {
+        120
+    }
+
+    fn move_cursor_up(&self, _n: usize) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn move_cursor_down(&self, _n: usize) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn move_cursor_right(&self, _n: usize) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn move_cursor_left(&self, _n: usize) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_line(&self, s: &str) -> io::Result<()> {
+        self.write_str(s)
+    }
+
+    fn write_str(&self, s: &str) -> io::Result<()> {
+        if !s.is_empty() {
+            self.lines.lock().unwrap().push(s.to_string());
+        }
+        Ok(())
+    }
+
+    fn clear_line(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// An application's progress bars, drawn on a recording terminal, with one bar of its own.
+fn application_bars() -> (RecordingTerm, MultiProgress, ProgressBar) {
+    let term = RecordingTerm::default();
+    let multi =
+        MultiProgress::with_draw_target(ProgressDrawTarget::term_like(Box::new(term.clone())));
+    let own_bar = multi.add(
+        ProgressBar::new(10)
+            .with_style(ProgressStyle::with_template("{msg} {pos}/{len}").unwrap())
+            .with_message("Building project"),
+    );
+    own_bar.tick();
+    (term, multi, own_bar)
+}
+
+#[tokio::test]
+async fn install_bars_join_and_leave_the_application_bars() {
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let dir = tempfile::tempdir().unwrap();
+    let client = common::client(dir.path(), &server).await;
+    let release = client
+        .get_release(&ToolchainVersion::named("21.1.0"))
+        .await
+        .unwrap();
+    let (term, multi, own_bar) = application_bars();
+    let progress_bars = ProgressBars::with_multi_progress(multi.clone());
+
+    install_with_progress_bar(
+        &client,
+        &release,
+        &HostArgs::default(),
+        None,
+        Some(&progress_bars),
+        CancellationToken::new(),
+    )
+    .await
+    .unwrap();
+
+    let drawn = term.take().join("\n");
+    assert!(drawn.contains("Verifying"), "{drawn}");
+    assert!(drawn.contains("Building project 0/10"), "{drawn}");
+
+    progress_bars.clear();
+    own_bar.finish_with_message("Built project");
+
+    let drawn = term.take().join("\n");
+    assert!(drawn.contains("Built project 10/10"), "{drawn}");
+    assert!(!drawn.contains("Verifying"), "{drawn}");
+}
+
+#[tokio::test]
+async fn remove_bar_joins_the_application_bars() {
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let dir = tempfile::tempdir().unwrap();
+    let client = common::client(dir.path(), &server).await;
+    let release = client
+        .get_release(&ToolchainVersion::named("21.1.0"))
+        .await
+        .unwrap();
+    install_with_progress_bar(
+        &client,
+        &release,
+        &HostArgs::default(),
+        None,
+        Some(&ProgressBars::with_multi_progress(
+            MultiProgress::with_draw_target(ProgressDrawTarget::hidden()),
+        )),
+        CancellationToken::new(),
+    )
+    .await
+    .unwrap();
+    let (term, multi, _own_bar) = application_bars();
+    let progress_bars = ProgressBars::with_multi_progress(multi);
+
+    remove_with_progress_bar(
+        client.clone(),
+        ToolchainVersion::named("21.1.0"),
+        CancellationToken::new(),
+        Some(&progress_bars),
+    )
+    .await
+    .unwrap();
+
+    assert!(
+        !client
+            .install_path_for(&ToolchainVersion::named("21.1.0"))
+            .exists()
+    );
+    let drawn = term.take().join("\n");
+    assert!(drawn.contains("Building project 0/10"), "{drawn}");
+    assert!(drawn.contains("Removing v21.1.0"), "{drawn}");
+}
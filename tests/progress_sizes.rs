@@ -0,0 +1,140 @@
+//! Every phase of an install reports how much work it has as soon as it begins, so that
+//! progress can be sized up front, including when a download is resumed.
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use arm_toolchain::toolchain::{InstallState, ToolchainVersion};
+use common::{MockGitHub, MockRelease, MockServer, host_asset_name};
+use tokio_util::sync::CancellationToken;
+
+const VERSION: &str = "21.1.0";
+
+/// Installs the release after leaving the first half of its archive in the cache, and returns
+/// the archive's size, the size of the cached half, and the events that were reported.
+async fn resumed_install_events() -> (u64, u64, Vec<InstallState>) {
+    let release = MockRelease::toolchain(VERSION);
+    let data = release.data(&host_asset_name(VERSION)).to_vec();
+    let half = data.len() / 2;
+    let server = MockServer::github(MockGitHub::new([release]));
+
+    let dir = tempfile::tempdir().unwrap();
+    let client = common::client(dir.path(), &server).await;
+    let release = client
+        .get_release(&ToolchainVersion::named(VERSION))
+        .await
+        .unwrap();
+    let asset = common::host_asset(&client, &release);
+    let archive_path = client.cache_path_for(asset).unwrap();
+    std::fs::create_dir_all(archive_path.parent().unwrap()).unwrap();
+    std::fs::write(&archive_path, &data[..half]).unwrap();
+
+    let events = Arc::new(Mutex::new(vec![]));
+    client
+        .install_to(
+            &release,
+            asset,
+            dir.path().join("toolchain"),
+            Arc::new({
+                let events = events.clone();
+                move |state| events.lock().unwrap().push(state)
+            }),
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+    let events = events.lock().unwrap().clone();
+    (data.len() as u64, half as u64, events)
+}
+
+#[tokio::test]
+async fn download_and_verify_are_sized_when_they_begin() {
+    let (size, half, events) = resumed_install_events().await;
+
+    assert_eq!(
+        events.first(),
+        Some(&InstallState::DownloadBegin {
+            asset_size: size,
+            bytes_read: half,
+        })
+    );
+    let verify_begin = events
+        .iter()
+        .position(|state| matches!(state, InstallState::VerifyingBegin { .. }))
+        .unwrap();
+    assert_eq!(
+        events[verify_begin],
+        InstallState::VerifyingBegin { asset_size: size }
+    );
+    assert!(
+        events[..verify_begin]
+            .iter()
+            .all(|state| !matches!(state, InstallState::Verifying { .. })),
+        "{events:#?}"
+    );
+}
+
+#[tokio::test]
+async fn overall_progress_starts_at_the_resumed_fraction_and_only_grows() {
+    let (size, half, events) = resumed_install_events().await;
+
+    // Download and verification each read the whole archive, so they're weighed equally.
+    let total = 2 * size;
+    let mut downloaded = 0;
+    let mut verified = 0;
+    let mut fractions = vec![];
+    for state in &events {
+        match *state {
+            InstallState::DownloadBegin { bytes_read, .. }
+            | InstallState::Download { bytes_read } => downloaded = bytes_read,
+            InstallState::DownloadFinish => downloaded = size,
+            InstallState::Verifying { bytes_read } => verified = bytes_read,
+            InstallState::VerifyingFinish => verified = size,
+            _ => continue,
+        }
+        fractions.push((downloaded + verified) as f64 / total as f64);
+    }
+
+    assert_eq!(fractions[0], half as f64 / total as f64);
+    assert!(
+        fractions.windows(2).all(|pair| pair[0] <= pair[1]),
+        "{fractions:?}"
+    );
+    assert_eq!(fractions.last(), Some(&1.0));
+}
+
+#[tokio::test]
+async fn extraction_is_sized_by_its_estimate() {
+    let (_, _, events) = resumed_install_events().await;
+
+    let estimate = events
+        .iter()
+        .find_map(|state| match state {
+            InstallState::ExtractBegin { estimated_size } => Some(*estimated_size),
+            _ => None,
+        })
+        .unwrap()
+        .expect("tar.xz archives record their uncompressed size");
+    let copies: Vec<_> = events
+        .iter()
+        .filter_map(|state| match *state {
+            InstallState::ExtractCopy {
+                total_size,
+                bytes_copied,
+            } => Some((total_size, bytes_copied)),
+            _ => None,
+        })
+        .collect();
+
+    assert!(!copies.is_empty());
+    assert!(
+        copies.iter().all(|&(total_size, _)| total_size == estimate),
+        "{copies:?}"
+    );
+    assert!(
+        copies.windows(2).all(|pair| pair[0].1 <= pair[1].1),
+        "{copies:?}"
+    );
+}
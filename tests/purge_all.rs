@@ -0,0 +1,92 @@
+//! Purging everything deletes every toolchain, the active toolchain, and the download cache,
+//! leaving the client as if it had never been used.
+
+mod common;
+
+use std::sync::Arc;
+
+use arm_toolchain::toolchain::{RemoveProgress, ToolchainVersion};
+use common::{MockGitHub, MockRelease, MockServer};
+use tokio_util::sync::CancellationToken;
+
+#[tokio::test]
+async fn deletes_every_toolchain_and_the_cache() {
+    let server = MockServer::github(MockGitHub::new([
+        MockRelease::toolchain("21.1.0"),
+        MockRelease::toolchain("20.1.0"),
+    ]));
+    let dir = tempfile::tempdir().unwrap();
+    let client = common::client(dir.path(), &server).await;
+    for version in ["21.1.0", "20.1.0"] {
+        let release = client
+            .get_release(&ToolchainVersion::named(version))
+            .await
+            .unwrap();
+        client
+            .download_and_install(
+                &release,
+                common::host_asset(&client, &release),
+                Arc::new(|_| {}),
+                CancellationToken::new(),
+            )
+            .await
+            .unwrap();
+    }
+    client
+        .set_active_toolchain(Some(ToolchainVersion::named("21.1.0")))
+        .await
+        .unwrap();
+    // Installing removes the downloaded archives, so leave one from an interrupted download.
+    std::fs::write(
+        dir.path().join("cache/ATfE-19.1.0-Linux-x86_64.tar.xz"),
+        [0; 100],
+    )
+    .unwrap();
+
+    let mut removed = 0;
+    let report = client
+        .purge_all(
+            |progress| {
+                if let RemoveProgress::Progress { bytes_removed } = progress {
+                    removed = bytes_removed;
+                }
+            },
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        report.toolchains,
+        [
+            client.install_path_for(&ToolchainVersion::named("20.1.0")),
+            client.install_path_for(&ToolchainVersion::named("21.1.0")),
+        ]
+    );
+    assert_eq!(report.toolchains_dir, Some(dir.path().join("toolchains")));
+    assert_eq!(report.cache.bytes_removed, 100);
+    assert!(report.cache.failed.is_empty());
+    assert_eq!(report.bytes_removed, removed + report.cache.bytes_removed);
+    assert!(common::list_tree(dir.path()).is_empty());
+    assert_eq!(client.active_toolchain(), None);
+
+    // A new client sees a fresh profile.
+    let client = common::client(dir.path(), &server).await;
+    assert_eq!(client.active_toolchain(), None);
+    assert!(client.installed_versions().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn nothing_to_purge() {
+    let server = MockServer::github(MockGitHub::new([]));
+    let dir = tempfile::tempdir().unwrap();
+    let client = common::client(dir.path(), &server).await;
+
+    let report = client
+        .purge_all(|_| {}, &CancellationToken::new())
+        .await
+        .unwrap();
+
+    assert!(report.toolchains.is_empty());
+    assert_eq!(report.bytes_removed, 0);
+}
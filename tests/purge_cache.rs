@@ -0,0 +1,88 @@
+//! Purging the download cache deletes whatever it can, and reports what it couldn't.
+
+mod common;
+
+use std::fs;
+
+use common::{MockGitHub, MockServer};
+
+#[tokio::test]
+async fn deletes_every_entry() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(MockGitHub::new([]));
+    let client = common::client(dir.path(), &server).await;
+    let cache = dir.path().join("cache");
+    fs::create_dir_all(cache.join("partial")).unwrap();
+    fs::write(cache.join("ATfE-21.1.0-Linux-x86_64.tar.xz"), [0; 100]).unwrap();
+    fs::write(cache.join("ATfE-21.1.0-Linux-x86_64.tar.xz.etag"), "\"a\"").unwrap();
+    fs::write(cache.join("partial/data"), [0; 20]).unwrap();
+
+    let report = client.purge_cache().await.unwrap();
+
+    let mut removed: Vec<_> = report
+        .removed
+        .iter()
+        .map(|entry| entry.path.file_name().unwrap().to_str().unwrap())
+        .collect();
+    removed.sort();
+    assert_eq!(
+        removed,
+        [
+            "ATfE-21.1.0-Linux-x86_64.tar.xz",
+            "ATfE-21.1.0-Linux-x86_64.tar.xz.etag",
+            "partial"
+        ]
+    );
+    assert!(report.failed.is_empty(), "{report:?}");
+    assert_eq!(report.bytes_removed, 123);
+    assert!(!cache.exists());
+}
+
+#[tokio::test]
+async fn missing_cache_is_empty() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(MockGitHub::new([]));
+    let client = common::client(dir.path(), &server).await;
+
+    let report = client.purge_cache().await.unwrap();
+
+    assert!(report.removed.is_empty());
+    assert!(report.failed.is_empty());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn keeps_going_past_entries_it_cannot_delete() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(MockGitHub::new([]));
+    let client = common::client(dir.path(), &server).await;
+    let cache = dir.path().join("cache");
+    let locked = cache.join("locked");
+    fs::create_dir_all(&locked).unwrap();
+    fs::write(locked.join("ATfE-20.1.0-Linux-x86_64.tar.xz"), [0; 10]).unwrap();
+    fs::write(cache.join("ATfE-21.1.0-Linux-x86_64.tar.xz"), [0; 100]).unwrap();
+    // Files in a read-only directory can't be deleted, like a file held open on Windows.
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o555)).unwrap();
+    if fs::write(locked.join("probe"), "").is_ok() {
+        // Running as root, which ignores the directory's permissions.
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        return;
+    }
+
+    let report = client.purge_cache().await;
+
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+    let report = report.unwrap();
+    assert_eq!(report.removed.len(), 1, "{report:?}");
+    assert_eq!(
+        report.removed[0].path,
+        cache.join("ATfE-21.1.0-Linux-x86_64.tar.xz")
+    );
+    assert_eq!(report.bytes_removed, 100);
+    assert_eq!(report.failed.len(), 1, "{report:?}");
+    assert_eq!(report.failed[0].path, locked);
+    assert!(!report.failed[0].error.is_empty());
+    assert!(locked.join("ATfE-20.1.0-Linux-x86_64.tar.xz").exists());
+}
@@ -0,0 +1,218 @@
+//! Requests that GitHub rejects because of a rate limit are reported as
+//! [`ToolchainError::RateLimited`], along with when the limit resets, and other refusals are
+//! reported as they are.
+
+mod common;
+
+use std::sync::Arc;
+
+use arm_toolchain::toolchain::{ToolchainError, ToolchainVersion};
+use chrono::{DateTime, Utc};
+use common::{MockGitHub, MockRelease, MockServer, Response, host_asset_name};
+use tokio_util::sync::CancellationToken;
+
+const VERSION: &str = "21.1.0";
+const RESET: i64 = 1_767_225_600;
+
+/// A JSON error from the GitHub API.
+fn api_error(status: u16, message: &str) -> Response {
+    let body = serde_json::json!({
+        "message": message,
+        "documentation_url": "https://docs.github.com/rest",
+    });
+    Response::new(status, body.to_string()).header("Content-Type", "application/json")
+}
+
+/// The response to `/rate_limit` when `remaining` requests are left until `RESET`.
+fn rate_limit(remaining: usize) -> Response {
+    let rate = serde_json::json!({
+        "limit": 60,
+        "used": 60 - remaining,
+        "remaining": remaining,
+        "reset": RESET,
+    });
+    let body = serde_json::json!({
+        "resources": { "core": rate, "search": rate },
+        "rate": rate,
+    });
+    Response::ok(body.to_string()).header("Content-Type", "application/json")
+}
+
+/// Starts a server that answers API requests for the release with `release_response` and
+/// `/rate_limit` with `rate_limit_response`. Everything else is answered like GitHub.
+fn api_server(
+    release_response: impl Fn() -> Response + Send + Sync + 'static,
+    rate_limit_response: impl Fn() -> Response + Send + Sync + 'static,
+) -> MockServer {
+    let github = MockGitHub::new([MockRelease::toolchain(VERSION)]);
+    MockServer::start(move |request| {
+        if request
+            .path
+            .starts_with("/repos/arm/arm-toolchain/releases")
+        {
+            release_response()
+        } else if request.path == "/rate_limit" {
+            rate_limit_response()
+        } else {
+            github.respond(request)
+        }
+    })
+}
+
+async fn get_release(server: &MockServer) -> ToolchainError {
+    let dir = tempfile::tempdir().unwrap();
+    let client = common::client(dir.path(), server).await;
+    let Err(error) = client.get_release(&ToolchainVersion::named(VERSION)).await else {
+        panic!("expected the release to be refused");
+    };
+    error
+}
+
+/// Starts a server that answers requests for the archive with `archive_response`.
+fn asset_server(archive_response: impl Fn() -> Response + Send + Sync + 'static) -> MockServer {
+    let github = MockGitHub::new([MockRelease::toolchain(VERSION)]);
+    MockServer::start(move |request| {
+        if request.file_name() == host_asset_name(VERSION) {
+            archive_response()
+        } else {
+            github.respond(request)
+        }
+    })
+}
+
+async fn download(server: &MockServer) -> ToolchainError {
+    let dir = tempfile::tempdir().unwrap();
+    let client = common::client(dir.path(), server).await;
+    let release = client
+        .get_release(&ToolchainVersion::named(VERSION))
+        .await
+        .unwrap();
+    client
+        .download_and_install(
+            &release,
+            common::host_asset(&client, &release),
+            Arc::new(|_| {}),
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap_err()
+}
+
+fn assert_resets_in(error: &ToolchainError, wait: std::ops::Range<i64>) {
+    let ToolchainError::RateLimited { resets_at } = error else {
+        panic!("expected a rate limit, got {error:?}");
+    };
+    let waited = (*resets_at - Utc::now()).num_seconds();
+    assert!(wait.contains(&waited), "resets in {waited}s");
+}
+
+#[tokio::test]
+async fn api_rate_limits_reset_when_github_says() {
+    for status in [403, 429] {
+        let server = api_server(
+            move || {
+                api_error(status, "API rate limit exceeded for 127.0.0.1.")
+                    .header("x-ratelimit-remaining", "0")
+                    .header("x-ratelimit-reset", RESET.to_string())
+            },
+            || rate_limit(0),
+        );
+
+        let error = get_release(&server).await;
+
+        assert!(
+            matches!(
+                error,
+                ToolchainError::RateLimited { resets_at }
+                    if Some(resets_at) == DateTime::from_timestamp(RESET, 0)
+            ),
+            "{error:?}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn secondary_api_rate_limits_wait_a_minute() {
+    // There are requests left, so the limit that was hit has no reset time.
+    let server = api_server(
+        || api_error(403, "You have exceeded a secondary rate limit."),
+        || rate_limit(42),
+    );
+
+    let error = get_release(&server).await;
+
+    assert_resets_in(&error, 55..61);
+}
+
+#[tokio::test]
+async fn other_api_refusals_are_not_rate_limits() {
+    let server = api_server(
+        || {
+            api_error(403, "Resource not accessible by integration")
+                .header("x-ratelimit-remaining", "59")
+                .header("x-ratelimit-reset", RESET.to_string())
+        },
+        || panic!("only rate limits look up when the limit resets"),
+    );
+
+    let error = get_release(&server).await;
+
+    assert!(
+        matches!(
+            error,
+            ToolchainError::GitHubApi(octocrab::Error::GitHub { .. })
+        ),
+        "{error:?}"
+    );
+    assert!(
+        server.requests().iter().all(|r| r.path != "/rate_limit"),
+        "{:?}",
+        server.requests()
+    );
+}
+
+#[tokio::test]
+async fn asset_rate_limits_reset_when_the_headers_say() {
+    let server = asset_server(|| {
+        Response::new(403, "rate limit exceeded")
+            .header("x-ratelimit-remaining", "0")
+            .header("x-ratelimit-reset", RESET.to_string())
+    });
+
+    let error = download(&server).await;
+
+    assert!(
+        matches!(
+            error,
+            ToolchainError::RateLimited { resets_at }
+                if Some(resets_at) == DateTime::from_timestamp(RESET, 0)
+        ),
+        "{error:?}"
+    );
+}
+
+#[tokio::test]
+async fn asset_rate_limits_reset_after_retry_after() {
+    let server =
+        asset_server(|| Response::new(429, "too many requests").header("retry-after", "120"));
+
+    let error = download(&server).await;
+
+    assert_resets_in(&error, 115..121);
+}
+
+#[tokio::test]
+async fn other_asset_refusals_are_not_rate_limits() {
+    let server = asset_server(|| {
+        Response::new(403, "forbidden")
+            .header("x-ratelimit-remaining", "59")
+            .header("x-ratelimit-reset", RESET.to_string())
+    });
+
+    let error = download(&server).await;
+
+    assert!(
+        matches!(&error, ToolchainError::Reqwest(error) if error.status().is_some_and(|s| s.as_u16() == 403)),
+        "{error:?}"
+    );
+}
@@ -0,0 +1,109 @@
+//! The version in an archive's top-level directory is recorded next to the release's own
+//! version, and only a different major version counts as a mismatch.
+
+mod common;
+
+use std::sync::Arc;
+
+use arm_toolchain::toolchain::{HostArch, RecordedVersions, ToolchainVersion, VersionMatch};
+use common::{MockGitHub, MockRelease, MockServer};
+use tokio_util::sync::CancellationToken;
+
+fn recorded(release: &str, content: Option<&str>) -> RecordedVersions {
+    RecordedVersions {
+        release: ToolchainVersion::named(release),
+        content: content.map(ToolchainVersion::named),
+    }
+}
+
+#[test]
+fn compares_the_release_and_content_versions() {
+    for (release, content, expected) in [
+        ("20.1.0", None, VersionMatch::Same),
+        ("20.1.0", Some("20.1.0"), VersionMatch::Same),
+        ("20.1.0", Some("20.1.0-rc1"), VersionMatch::Differs),
+        ("21.1.1", Some("21.1.0"), VersionMatch::Differs),
+        ("20.1.0", Some("19.1.5"), VersionMatch::MajorDiffers),
+        ("21.1.0-rc1", Some("20.1.0"), VersionMatch::MajorDiffers),
+        // Without a major version, there's nothing to compare them by.
+        ("nightly", Some("20.1.0"), VersionMatch::Differs),
+    ] {
+        assert_eq!(
+            recorded(release, content).compare(),
+            expected,
+            "{release} containing {content:?}"
+        );
+    }
+}
+
+async fn install(release: MockRelease) -> (tempfile::TempDir, Option<RecordedVersions>) {
+    let version = ToolchainVersion::named(release.tag_name.split('-').nth(1).unwrap());
+    let server = MockServer::github(MockGitHub::new([release]));
+    let dir = tempfile::tempdir().unwrap();
+    let client = common::client(dir.path(), &server).await;
+    let release = client.get_release(&version).await.unwrap();
+    let outcome = client
+        .download_and_install(
+            &release,
+            common::host_asset(&client, &release),
+            Arc::new(|_| {}),
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+    // The install path comes from the release, whatever the archive contains.
+    assert_eq!(outcome.path, client.install_path_for(&version));
+    let toolchain = client.toolchain(&version).await.unwrap();
+    (dir, toolchain.recorded_versions().await)
+}
+
+#[tokio::test]
+async fn records_both_versions() {
+    let (_dir, recorded) = install(MockRelease::toolchain_containing("20.1.0", "20.1.0-rc1")).await;
+
+    let recorded = recorded.unwrap();
+    assert_eq!(recorded.release.name, "20.1.0");
+    assert_eq!(
+        recorded.content,
+        Some(ToolchainVersion::named("20.1.0-rc1"))
+    );
+    assert_eq!(recorded.compare(), VersionMatch::Differs);
+}
+
+#[tokio::test]
+async fn records_matching_versions() {
+    let (_dir, recorded) = install(MockRelease::toolchain("21.1.0")).await;
+
+    let recorded = recorded.unwrap();
+    assert_eq!(recorded.content, Some(ToolchainVersion::named("21.1.0")));
+    assert_eq!(recorded.compare(), VersionMatch::Same);
+}
+
+#[tokio::test]
+async fn records_the_architecture_of_the_installed_asset() {
+    let version = ToolchainVersion::named("21.1.0");
+    let native = common::host_asset_name("21.1.0");
+    let universal = "ATfE-21.1.0-Darwin-universal.tar.xz";
+    let archive = common::toolchain_archive(native.trim_end_matches(".tar.xz"));
+    let server = MockServer::github(MockGitHub::new([MockRelease::empty("21.1.0")
+        .asset_with_checksum(&native, archive.clone())
+        .asset_with_checksum(universal, archive)]));
+
+    for (name, expected) in [
+        (native.as_str(), HostArch::try_current().unwrap()[0]),
+        (universal, HostArch::Universal),
+    ] {
+        let dir = tempfile::tempdir().unwrap();
+        let client = common::client(dir.path(), &server).await;
+        let release = client.get_release(&version).await.unwrap();
+        let asset = release.assets().iter().find(|a| a.name == name).unwrap();
+        client
+            .download_and_install(&release, asset, Arc::new(|_| {}), CancellationToken::new())
+            .await
+            .unwrap();
+
+        let toolchain = client.toolchain(&version).await.unwrap();
+        assert_eq!(toolchain.installed_arch().await, Some(expected), "{name}");
+    }
+}
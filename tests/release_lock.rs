@@ -0,0 +1,191 @@
+//! A release lock records everything that's needed to install a release, so that installing
+//! from it only downloads the asset, and fails if the asset changed since it was locked.
+
+mod common;
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+use arm_toolchain::toolchain::{ReleaseLock, ToolchainClient, ToolchainError, ToolchainVersion};
+use common::{MockGitHub, MockRelease, MockServer};
+use tokio_util::sync::CancellationToken;
+
+/// Locks 21.1.0 as served by `server`.
+async fn lock(dir: &std::path::Path, server: &MockServer) -> ReleaseLock {
+    let client = common::client(dir, server).await;
+    let release = client
+        .get_release(&ToolchainVersion::named("21.1.0"))
+        .await
+        .unwrap();
+    client.lock_release(&release).await.unwrap()
+}
+
+/// Installs the locked release with a client that uses the lock.
+async fn install_locked(
+    dir: &std::path::Path,
+    server: &MockServer,
+    lock: ReleaseLock,
+) -> Result<ToolchainClient, ToolchainError> {
+    let release = lock.release();
+    let client = common::client_builder(dir, server)
+        .release_lock(Some(lock))
+        .build()
+        .await
+        .unwrap();
+    client
+        .download_and_install(
+            &release,
+            common::host_asset(&client, &release),
+            Arc::new(|_| {}),
+            CancellationToken::new(),
+        )
+        .await?;
+    Ok(client)
+}
+
+#[tokio::test]
+async fn lock_round_trips_through_a_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let lock = lock(dir.path(), &server).await;
+    let path = dir.path().join("releases.lock");
+
+    lock.write(&path).await.unwrap();
+    let read = ReleaseLock::read(&path).await.unwrap();
+
+    assert_eq!(read, lock);
+    assert_eq!(read.version(), ToolchainVersion::named("21.1.0"));
+    let asset_name = common::host_asset_name("21.1.0");
+    let asset = read
+        .assets
+        .iter()
+        .find(|asset| asset.name == asset_name)
+        .unwrap();
+    assert_eq!(
+        asset.sha256,
+        common::sha256_hex(MockRelease::toolchain("21.1.0").data(&asset_name))
+    );
+
+    let release = read.release();
+    assert_eq!(release.version(), &read.version());
+    let names: Vec<_> = release.assets().iter().map(|asset| &asset.name).collect();
+    let locked_names: Vec<_> = read.assets.iter().map(|asset| &asset.name).collect();
+    assert_eq!(names, locked_names);
+}
+
+#[tokio::test]
+async fn invalid_locks_are_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("releases.lock");
+
+    for contents in [
+        "not json",
+        r#"{ "format_version": 2, "something": "else" }"#,
+        r#"{ "format_version": 1, "product": "embedded" }"#,
+    ] {
+        std::fs::write(&path, contents).unwrap();
+
+        match ReleaseLock::read(&path).await {
+            Err(ToolchainError::InvalidReleaseLock {
+                path: error_path, ..
+            }) => assert_eq!(error_path, path),
+            other => panic!("{contents}: {other:?}"),
+        }
+    }
+}
+
+#[tokio::test]
+async fn locked_install_only_downloads_the_asset() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let lock = lock(&dir.path().join("locking"), &server).await;
+    let requests_before = server.requests().len();
+
+    let client = install_locked(dir.path(), &server, lock).await.unwrap();
+
+    assert!(client.version_is_installed(&ToolchainVersion::named("21.1.0")));
+    let requests = &server.requests()[requests_before..];
+    assert!(!requests.is_empty());
+    assert!(
+        requests
+            .iter()
+            .all(|request| request.file_name() == common::host_asset_name("21.1.0")),
+        "{requests:#?}"
+    );
+}
+
+#[tokio::test]
+async fn install_is_refused_when_the_asset_changed() {
+    let dir = tempfile::tempdir().unwrap();
+    let drifted = Arc::new(AtomicBool::new(false));
+    let server = MockServer::start({
+        let original = MockGitHub::new([MockRelease::toolchain("21.1.0")]);
+        // Re-uploaded with a matching checksum file, so only the lock can tell.
+        let rebuilt = MockGitHub::new([MockRelease::toolchain_containing("21.1.0", "rebuilt")]);
+        let drifted = drifted.clone();
+        move |request| match drifted.load(Ordering::SeqCst) {
+            false => original.respond(request),
+            true => rebuilt.respond(request),
+        }
+    });
+    let lock = lock(&dir.path().join("locking"), &server).await;
+    drifted.store(true, Ordering::SeqCst);
+
+    let error = install_locked(dir.path(), &server, lock.clone())
+        .await
+        .err()
+        .unwrap();
+
+    let asset_name = common::host_asset_name("21.1.0");
+    match error {
+        ToolchainError::LockedChecksumMismatch {
+            file_name,
+            expected,
+            ..
+        } => {
+            assert_eq!(file_name, asset_name);
+            assert_eq!(Some(expected.as_str()), lock_sha256(&lock, &asset_name));
+        }
+        error => panic!("{error:?}"),
+    }
+    assert!(!dir.path().join("toolchains/embedded/21.1.0").exists());
+}
+
+#[tokio::test]
+async fn asset_missing_from_the_lock_is_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let mut lock = lock(&dir.path().join("locking"), &server).await;
+    let asset_name = common::host_asset_name("21.1.0");
+    let release = lock.release();
+    lock.assets.retain(|asset| asset.name != asset_name);
+    let client = common::client_builder(dir.path(), &server)
+        .release_lock(Some(lock))
+        .build()
+        .await
+        .unwrap();
+
+    let error = client
+        .download_and_install(
+            &release,
+            common::host_asset(&client, &release),
+            Arc::new(|_| {}),
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap_err();
+
+    match error {
+        ToolchainError::AssetNotLocked { file_name } => assert_eq!(file_name, asset_name),
+        error => panic!("{error:?}"),
+    }
+}
+
+fn lock_sha256<'a>(lock: &'a ReleaseLock, name: &str) -> Option<&'a str> {
+    lock.assets
+        .iter()
+        .find(|asset| asset.name == name)
+        .map(|asset| asset.sha256.as_str())
+}
@@ -0,0 +1,92 @@
+//! A release's notes are saved in the toolchain installed from it, so that they can be read
+//! later without access to GitHub.
+
+mod common;
+
+use std::sync::Arc;
+
+use arm_toolchain::toolchain::{InstalledToolchain, ToolchainVersion};
+use chrono::{TimeZone, Utc};
+use common::{MockGitHub, MockRelease, MockServer};
+use tokio_util::sync::CancellationToken;
+
+/// Installs 21.1.0 from `release` and returns the installed toolchain.
+async fn install(dir: &std::path::Path, release: MockRelease) -> InstalledToolchain {
+    let version = ToolchainVersion::named("21.1.0");
+    let server = MockServer::github(MockGitHub::new([release]));
+    let client = common::client(dir, &server).await;
+    let release = client.get_release(&version).await.unwrap();
+    client
+        .download_and_install(
+            &release,
+            common::host_asset(&client, &release),
+            Arc::new(|_| {}),
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+    client.toolchain(&version).await.unwrap()
+}
+
+#[tokio::test]
+async fn notes_are_saved_when_installing() {
+    let dir = tempfile::tempdir().unwrap();
+    let release = MockRelease {
+        body: Some("## Changes\n\n- Faster linking\n\n".to_string()),
+        ..MockRelease::toolchain("21.1.0")
+    };
+
+    let toolchain = install(dir.path(), release).await;
+
+    let markdown = std::fs::read_to_string(toolchain.path.join(".release-notes.md")).unwrap();
+    assert_eq!(
+        markdown,
+        "# release-21.1.0-ATfE\n\
+         \n\
+         - Tag: `release-21.1.0-ATfE`\n\
+         - Published: 2025-01-01\n\
+         - Release page: https://github.com/arm/arm-toolchain/releases/tag/release-21.1.0-ATfE\n\
+         \n\
+         ## Changes\n\
+         \n\
+         - Faster linking\n"
+    );
+
+    let notes = toolchain.release_notes().await.unwrap().unwrap();
+    assert_eq!(notes.markdown, markdown);
+    assert_eq!(notes.tag_name.as_deref(), Some("release-21.1.0-ATfE"));
+    assert_eq!(notes.title.as_deref(), Some("release-21.1.0-ATfE"));
+    assert_eq!(
+        notes.published_at,
+        Some(Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap())
+    );
+}
+
+#[tokio::test]
+async fn release_without_notes_says_so() {
+    for body in [None, Some(""), Some("  \n")] {
+        let dir = tempfile::tempdir().unwrap();
+        let release = MockRelease {
+            body: body.map(str::to_string),
+            ..MockRelease::toolchain("21.1.0")
+        };
+
+        let toolchain = install(dir.path(), release).await;
+
+        let notes = toolchain.release_notes().await.unwrap().unwrap();
+        assert!(
+            notes.markdown.ends_with("\n\nThis release has no notes.\n"),
+            "{body:?}: {}",
+            notes.markdown
+        );
+    }
+}
+
+#[tokio::test]
+async fn toolchain_from_before_notes_were_saved_has_none() {
+    let dir = tempfile::tempdir().unwrap();
+    let toolchain = install(dir.path(), MockRelease::toolchain("21.1.0")).await;
+    std::fs::remove_file(toolchain.path.join(".release-notes.md")).unwrap();
+
+    assert_eq!(toolchain.release_notes().await.unwrap(), None);
+}
@@ -0,0 +1,224 @@
+//! Releases are found by version, whatever order GitHub lists them in.
+
+mod common;
+
+use arm_toolchain::toolchain::{ArchPreference, HostArch, HostOS, ToolchainVersion};
+use common::{MockGitHub, MockRelease, MockServer};
+
+/// Releases in the order they might have been published in, with patches for older majors
+/// published after newer majors.
+fn github() -> MockGitHub {
+    let mut prerelease = MockRelease::empty("22.1.0-rc1");
+    prerelease.prerelease = true;
+    MockGitHub::new([
+        prerelease,
+        MockRelease::empty("20.1.2"),
+        MockRelease::empty("19.1.5"),
+        MockRelease::empty("21.1.1"),
+        MockRelease::empty("21.1.0"),
+        MockRelease::empty("20.1.0"),
+        MockRelease::empty("19.1.1"),
+    ])
+}
+
+fn version(name: &str) -> ToolchainVersion {
+    ToolchainVersion::named(name)
+}
+
+#[tokio::test]
+async fn newest_in_major_finds_the_newest_patch() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(github());
+    let client = common::client(dir.path(), &server).await;
+
+    for (major, expected) in [
+        (19, Some("19.1.5")),
+        (20, Some("20.1.2")),
+        (21, Some("21.1.1")),
+        (18, None),
+        (22, None),
+    ] {
+        let newest = client.newest_in_major(major).await.unwrap();
+        assert_eq!(
+            newest.as_ref().map(|release| release.version()),
+            expected.map(version).as_ref(),
+            "{major}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn newest_in_major_includes_prereleases_if_the_client_does() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(github());
+    let client = common::client_builder(dir.path(), &server)
+        .include_prereleases(true)
+        .build()
+        .await
+        .unwrap();
+
+    let newest = client.newest_in_major(22).await.unwrap().unwrap();
+
+    assert_eq!(newest.version(), &version("22.1.0-rc1"));
+}
+
+#[tokio::test]
+async fn list_releases_is_sorted_newest_first() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut github = github();
+    // Releases of other products in the same repository are left out.
+    github.releases.push(MockRelease {
+        tag_name: "release-21.1.0-ATfL".to_string(),
+        ..MockRelease::empty("21.1.0")
+    });
+    let server = MockServer::github(github);
+    let client = common::client(dir.path(), &server).await;
+
+    for (limit, include_prereleases, expected) in [
+        (
+            None,
+            false,
+            &["21.1.1", "21.1.0", "20.1.2", "20.1.0", "19.1.5", "19.1.1"][..],
+        ),
+        (Some(2), false, &["21.1.1", "21.1.0"]),
+        (Some(2), true, &["22.1.0-rc1", "21.1.1"]),
+        (Some(0), true, &[]),
+    ] {
+        let releases = client
+            .list_releases(limit, include_prereleases)
+            .await
+            .unwrap();
+        let versions = releases
+            .iter()
+            .map(|release| release.version().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            versions,
+            expected.iter().map(|v| version(v)).collect::<Vec<_>>(),
+            "{limit:?} {include_prereleases}"
+        );
+    }
+    // The list was only fetched once.
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn releases_since_lists_newer_releases_newest_first() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(github());
+    let client = common::client(dir.path(), &server).await;
+
+    for (since, expected) in [
+        ("20.1.0", &["22.1.0-rc1", "21.1.1", "21.1.0", "20.1.2"][..]),
+        ("21.1.1", &["22.1.0-rc1"]),
+        ("22.1.0-rc1", &[]),
+    ] {
+        let releases = client.releases_since(&version(since)).await.unwrap();
+        let versions = releases
+            .iter()
+            .map(|release| release.version().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            versions,
+            expected.iter().map(|v| version(v)).collect::<Vec<_>>(),
+            "{since}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn releases_are_filtered_by_the_hosts_they_support() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(MockGitHub::new([
+        MockRelease::empty("21.1.0")
+            .asset("ATfE-21.1.0-Linux-x86_64.tar.xz", vec![])
+            .asset("ATfE-21.1.0-Linux-AArch64.tar.xz", vec![])
+            .asset("ATfE-21.1.0-Darwin-universal.dmg", vec![])
+            .asset("ATfE-21.1.0-Windows-x86_64.zip", vec![]),
+        MockRelease::empty("20.1.0")
+            .asset("ATfE-20.1.0-Linux-x86_64.tar.xz", vec![])
+            .asset("ATfE-20.1.0-Linux-x86_64.tar.xz.sha256", vec![])
+            .asset("ATfE-20.1.0-Darwin-AArch64.tar.xz", vec![]),
+        MockRelease::empty("19.1.0").asset("ATfE-19.1.0-src.tar.xz", vec![]),
+    ]));
+    let client = common::client(dir.path(), &server).await;
+    let releases = client.releases_since(&version("19.0.0")).await.unwrap();
+
+    for (os, arches, expected) in [
+        (
+            HostOS::Linux,
+            &[HostArch::X86_64][..],
+            &["21.1.0", "20.1.0"][..],
+        ),
+        (HostOS::Linux, &[HostArch::AAarch64], &["21.1.0"]),
+        (
+            HostOS::Darwin,
+            &[HostArch::AAarch64, HostArch::Universal],
+            &["21.1.0", "20.1.0"],
+        ),
+        (HostOS::Darwin, &[HostArch::X86_64], &[]),
+        (HostOS::Windows, &[HostArch::X86_64], &["21.1.0"]),
+    ] {
+        let supported = releases
+            .iter()
+            .filter(|release| release.supports(os, arches))
+            .map(|release| release.version().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            supported,
+            expected.iter().map(|v| version(v)).collect::<Vec<_>>(),
+            "{os:?} {arches:?}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn macos_assets_follow_the_arch_preference() {
+    const NATIVE: &str = "ATfE-21.1.0-Darwin-AArch64.tar.xz";
+    const UNIVERSAL: &str = "ATfE-21.1.0-Darwin-universal.tar.xz";
+
+    // Whichever asset GitHub lists first, the preference decides.
+    for assets in [[NATIVE, UNIVERSAL], [UNIVERSAL, NATIVE]] {
+        let dir = tempfile::tempdir().unwrap();
+        let release = assets
+            .iter()
+            .fold(MockRelease::empty("21.1.0"), |release, name| {
+                release.asset(name, vec![])
+            });
+        let server = MockServer::github(MockGitHub::new([release]));
+        let client = common::client(dir.path(), &server).await;
+        let release = client.get_release(&version("21.1.0")).await.unwrap();
+
+        for (preference, expected) in [
+            (ArchPreference::Native, NATIVE),
+            (ArchPreference::Universal, UNIVERSAL),
+        ] {
+            let mut arches = HostArch::for_target("macos", "aarch64").unwrap().to_vec();
+            preference.apply(&mut arches);
+
+            let asset = release.asset_for(HostOS::Darwin, &arches).unwrap();
+
+            assert_eq!(asset.name, expected, "{preference:?} with {assets:?}");
+        }
+
+        // An Intel Mac can only run the universal build, whatever it prefers.
+        let mut arches = HostArch::for_target("macos", "x86_64").unwrap().to_vec();
+        ArchPreference::Native.apply(&mut arches);
+        let asset = release.asset_for(HostOS::Darwin, &arches).unwrap();
+        assert_eq!(asset.name, UNIVERSAL);
+    }
+}
+
+#[test]
+fn versions_are_ordered_by_their_numbers() {
+    let mut versions = ["19.1.10", "20.1.0", "19.1.9", "9.2.0", "19.1.1"].map(version);
+
+    versions.sort();
+
+    assert_eq!(
+        versions,
+        ["9.2.0", "19.1.1", "19.1.9", "19.1.10", "20.1.0"].map(version)
+    );
+    assert_eq!(version("20.1.0").major(), Some(20));
+    assert_eq!(version("20.1.0").components(), Some(vec![20, 1, 0]));
+}
@@ -0,0 +1,190 @@
+//! Partial downloads are only resumed when the server continues them from the right place,
+//! and with the same version of the asset. Otherwise they're discarded and started over.
+
+mod common;
+
+use std::sync::Arc;
+
+use arm_toolchain::toolchain::ToolchainVersion;
+use common::{MockGitHub, MockRelease, MockServer, Request, Response, host_asset_name};
+use tokio_util::sync::CancellationToken;
+
+const VERSION: &str = "21.1.0";
+
+/// Installs the release after leaving `partial` in the cache as an interrupted download, with
+/// `etag` saved for it, and returns the requests made for the archive.
+async fn install_after_partial(
+    server: &MockServer,
+    partial: &[u8],
+    etag: Option<&str>,
+) -> Vec<Request> {
+    let dir = tempfile::tempdir().unwrap();
+    let client = common::client(dir.path(), server).await;
+    let release = client
+        .get_release(&ToolchainVersion::named(VERSION))
+        .await
+        .unwrap();
+    let asset = common::host_asset(&client, &release);
+
+    let archive_path = client.cache_path_for(asset).unwrap();
+    std::fs::create_dir_all(archive_path.parent().unwrap()).unwrap();
+    std::fs::write(&archive_path, partial).unwrap();
+    if let Some(etag) = etag {
+        let mut etag_path = archive_path.into_os_string();
+        etag_path.push(".etag");
+        std::fs::write(etag_path, etag).unwrap();
+    }
+
+    client
+        .install_to(
+            &release,
+            asset,
+            dir.path().join("toolchain"),
+            Arc::new(|_| {}),
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+    server
+        .requests_for(&host_asset_name(VERSION))
+        .into_iter()
+        .filter(|request| request.method == "GET")
+        .collect()
+}
+
+/// The archive served for the current host, and the number of bytes in half of it.
+fn archive(release: &MockRelease) -> (Vec<u8>, usize) {
+    let data = release.data(&host_asset_name(VERSION)).to_vec();
+    let half = data.len() / 2;
+    (data, half)
+}
+
+/// Starts a server that answers requests for the archive that have a Range header with
+/// `respond`, given the archive and the offset it was asked to start from.
+fn server_answering_ranges(
+    release: MockRelease,
+    respond: impl Fn(&[u8], usize) -> Response + Send + Sync + 'static,
+) -> MockServer {
+    let (data, _) = archive(&release);
+    let github = MockGitHub::new([release]);
+    MockServer::start(move |request| {
+        let range_start = request
+            .header("range")
+            .and_then(|range| range.strip_prefix("bytes="))
+            .and_then(|range| range.split_once('-'))
+            .and_then(|(start, _)| start.parse().ok());
+        match range_start {
+            Some(start) if request.file_name() == host_asset_name(VERSION) => respond(&data, start),
+            _ => github.respond(request),
+        }
+    })
+}
+
+/// Checks that the download was resumed from `half`, then started over without a Range
+/// header.
+fn assert_restarted(requests: &[Request], half: usize, len: usize) {
+    assert_eq!(requests.len(), 2, "{requests:#?}");
+    assert_eq!(
+        requests[0].header("range"),
+        Some(format!("bytes={half}-{}", len - 1).as_str())
+    );
+    assert_eq!(requests[1].header("range"), None);
+    assert_eq!(requests[1].header("if-range"), None);
+}
+
+#[tokio::test]
+async fn resumes_with_a_matching_etag() {
+    let release = MockRelease::toolchain(VERSION);
+    let (data, half) = archive(&release);
+    let etag = release.assets[0].etag.clone().unwrap();
+    let server = MockServer::github(MockGitHub::new([release]));
+
+    let requests = install_after_partial(&server, &data[..half], Some(&etag)).await;
+
+    assert_eq!(requests.len(), 1, "{requests:#?}");
+    assert_eq!(
+        requests[0].header("range"),
+        Some(format!("bytes={half}-{}", data.len() - 1).as_str())
+    );
+    assert_eq!(requests[0].header("if-range"), Some(etag.as_str()));
+}
+
+#[tokio::test]
+async fn starts_over_when_the_etag_changed() {
+    let release = MockRelease::toolchain(VERSION);
+    let (data, half) = archive(&release);
+    let server = MockServer::github(MockGitHub::new([release]));
+
+    // Part of an asset that has since been re-uploaded, which the server sends all of.
+    let stale = common::noise(half);
+    let requests = install_after_partial(&server, &stale, Some("\"re-uploaded\"")).await;
+
+    assert_eq!(requests.len(), 1, "{requests:#?}");
+    assert_eq!(
+        requests[0].header("range"),
+        Some(format!("bytes={half}-{}", data.len() - 1).as_str())
+    );
+    assert_eq!(requests[0].header("if-range"), Some("\"re-uploaded\""));
+}
+
+#[tokio::test]
+async fn resumes_by_size_without_an_etag() {
+    let mut release = MockRelease::toolchain(VERSION);
+    for asset in &mut release.assets {
+        asset.etag = None;
+    }
+    let (data, half) = archive(&release);
+    let server = MockServer::github(MockGitHub::new([release]));
+
+    let requests = install_after_partial(&server, &data[..half], None).await;
+
+    assert_eq!(requests.len(), 1, "{requests:#?}");
+    assert_eq!(
+        requests[0].header("range"),
+        Some(format!("bytes={half}-{}", data.len() - 1).as_str())
+    );
+    assert_eq!(requests[0].header("if-range"), None);
+}
+
+#[tokio::test]
+async fn starts_over_when_the_range_is_not_satisfiable() {
+    let release = MockRelease::toolchain(VERSION);
+    let (data, half) = archive(&release);
+    let server = server_answering_ranges(release, |data, _| {
+        Response::new(416, Vec::new()).header("Content-Range", format!("bytes */{}", data.len()))
+    });
+
+    let requests = install_after_partial(&server, &data[..half], None).await;
+
+    assert_restarted(&requests, half, data.len());
+}
+
+#[tokio::test]
+async fn starts_over_when_resumed_at_the_wrong_offset() {
+    let release = MockRelease::toolchain(VERSION);
+    let (data, half) = archive(&release);
+    let server = server_answering_ranges(release, |data, start| {
+        let start = start - 16;
+        let end = data.len() - 1;
+        Response::new(206, &data[start..]).header(
+            "Content-Range",
+            format!("bytes {start}-{end}/{}", data.len()),
+        )
+    });
+
+    let requests = install_after_partial(&server, &data[..half], None).await;
+
+    assert_restarted(&requests, half, data.len());
+}
+
+#[tokio::test]
+async fn starts_over_without_a_content_range() {
+    let release = MockRelease::toolchain(VERSION);
+    let (data, half) = archive(&release);
+    let server = server_answering_ranges(release, |data, start| Response::new(206, &data[start..]));
+
+    let requests = install_after_partial(&server, &data[..half], None).await;
+
+    assert_restarted(&requests, half, data.len());
+}
@@ -0,0 +1,81 @@
+//! A toolchain directory whose name only differs from a version by surrounding whitespace,
+//! such as one restored from a backup, is treated as that version instead of as another one.
+
+mod common;
+
+use std::{fs, path::Path, sync::Arc};
+
+use arm_toolchain::toolchain::{ToolchainError, ToolchainVersion};
+use common::{MockGitHub, MockRelease, MockServer};
+use tokio_util::sync::CancellationToken;
+
+/// Writes the fixture toolchain to the directory with the given name.
+fn install_as(dir: &Path, name: &str) {
+    let root = dir.join("toolchains/embedded").join(name);
+    for (path, contents, _) in common::toolchain_files(".") {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+}
+
+#[tokio::test]
+async fn trailing_space() {
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("20.1.0")]));
+    let dir = tempfile::tempdir().unwrap();
+    install_as(dir.path(), "20.1.0 ");
+    let client = common::client(dir.path(), &server).await;
+    let version = ToolchainVersion::named("20.1.0");
+    let spaced = dir.path().join("toolchains/embedded/20.1.0 ");
+
+    assert!(client.version_is_installed(&version));
+    assert_eq!(
+        client.similar_installs(&version),
+        std::slice::from_ref(&spaced)
+    );
+    assert_eq!(client.toolchain(&version).await.unwrap().path, spaced);
+    assert_eq!(
+        client.ambiguous_installs().await.unwrap(),
+        [vec![spaced.clone()]]
+    );
+
+    let release = client.get_release(&version).await.unwrap();
+    let error = client
+        .download_and_install(
+            &release,
+            common::host_asset(&client, &release),
+            Arc::new(|_| {}),
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap_err();
+
+    let ToolchainError::SimilarInstallExists { paths, .. } = &error else {
+        panic!("{error:?}");
+    };
+    assert_eq!(*paths, std::slice::from_ref(&spaced));
+    assert!(spaced.join("bin/clang").exists());
+    assert!(!client.install_path_for(&version).exists());
+}
+
+#[tokio::test]
+async fn exact_and_spaced_directories_are_grouped() {
+    let server = MockServer::github(MockGitHub::new([]));
+    let dir = tempfile::tempdir().unwrap();
+    install_as(dir.path(), "20.1.0");
+    install_as(dir.path(), "20.1.0 ");
+    install_as(dir.path(), "21.1.0");
+    let client = common::client(dir.path(), &server).await;
+    let version = ToolchainVersion::named("20.1.0");
+
+    // The exact match is used when there is one.
+    assert_eq!(
+        client.toolchain(&version).await.unwrap().path,
+        client.install_path_for(&version)
+    );
+    let embedded = dir.path().join("toolchains/embedded");
+    assert_eq!(
+        client.ambiguous_installs().await.unwrap(),
+        [vec![embedded.join("20.1.0"), embedded.join("20.1.0 ")]]
+    );
+}
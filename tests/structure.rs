@@ -0,0 +1,205 @@
+//! Extracted toolchains are checked for the files every release has, so that a truncated
+//! archive that still extracts without errors isn't installed and activated.
+
+mod common;
+
+use std::{fs, path::Path, sync::Arc};
+
+use arm_toolchain::toolchain::{InstalledToolchain, Product, ToolchainError, ToolchainVersion};
+use common::{MockGitHub, MockRelease, MockServer, host_asset_name};
+use tokio_util::sync::CancellationToken;
+
+const VERSION: &str = "21.1.0";
+
+/// Writes the fixture toolchain's files to `dir`, except those for which `keep` is false.
+fn write_toolchain(dir: &Path, keep: impl Fn(&str) -> bool) {
+    for (path, contents, _) in common::toolchain_files(".") {
+        if keep(&path) {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+    }
+}
+
+/// Returns what's missing from a toolchain, or nothing if it's complete.
+async fn problems(dir: &Path, product: Product) -> Vec<String> {
+    match InstalledToolchain::new(dir.to_owned())
+        .check_structure(product)
+        .await
+    {
+        Ok(()) => vec![],
+        Err(ToolchainError::IncompleteExtraction { path, details }) => {
+            assert_eq!(path, dir);
+            details
+        }
+        Err(error) => panic!("{error:?}"),
+    }
+}
+
+#[tokio::test]
+async fn complete_toolchain() {
+    let dir = tempfile::tempdir().unwrap();
+    write_toolchain(dir.path(), |_| true);
+
+    assert!(problems(dir.path(), Product::EMBEDDED).await.is_empty());
+}
+
+#[tokio::test]
+async fn missing_clang() {
+    let dir = tempfile::tempdir().unwrap();
+    write_toolchain(dir.path(), |path| !path.contains("/bin/"));
+    fs::create_dir(dir.path().join("bin")).unwrap();
+
+    let clang_name = if cfg!(windows) { "clang.exe" } else { "clang" };
+    assert_eq!(
+        problems(dir.path(), Product::EMBEDDED).await,
+        [format!("`bin/{clang_name}` is missing")]
+    );
+}
+
+#[tokio::test]
+async fn missing_bin_and_runtimes() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join("lib")).unwrap();
+
+    assert_eq!(
+        problems(dir.path(), Product::EMBEDDED).await,
+        [
+            "the `bin` directory is missing",
+            "the `lib/clang-runtimes` directory is missing"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn empty_runtimes() {
+    let dir = tempfile::tempdir().unwrap();
+    write_toolchain(dir.path(), |path| !path.contains("clang-runtimes"));
+    fs::create_dir_all(dir.path().join("lib/clang-runtimes")).unwrap();
+
+    assert_eq!(
+        problems(dir.path(), Product::EMBEDDED).await,
+        ["the `lib/clang-runtimes` directory is empty"]
+    );
+}
+
+#[tokio::test]
+async fn runtimes_are_only_required_with_a_multilib() {
+    let dir = tempfile::tempdir().unwrap();
+    write_toolchain(dir.path(), |path| !path.contains("clang-runtimes"));
+
+    assert!(problems(dir.path(), Product::LINUX).await.is_empty());
+}
+
+/// Installs a release whose archive only has the fixture toolchain's files for which `keep`
+/// is true, and returns the result and where it was installed to.
+async fn install_truncated(
+    keep: impl Fn(&str) -> bool,
+    keep_failed: bool,
+) -> (
+    Result<(), ToolchainError>,
+    tempfile::TempDir,
+    std::path::PathBuf,
+) {
+    let name = host_asset_name(VERSION);
+    let root = name.trim_end_matches(".tar.xz");
+    let files: Vec<_> = common::toolchain_files(root)
+        .into_iter()
+        .filter(|(path, _, _)| keep(path))
+        .collect();
+    let release = MockRelease::empty(VERSION).asset_with_checksum(&name, common::tar_xz(&files));
+    let server = MockServer::github(MockGitHub::new([release]));
+    let dir = tempfile::tempdir().unwrap();
+    let client = common::client_builder(dir.path(), &server)
+        .keep_failed(keep_failed)
+        .build()
+        .await
+        .unwrap();
+    let release = client
+        .get_release(&ToolchainVersion::named(VERSION))
+        .await
+        .unwrap();
+
+    let result = client
+        .download_and_install(
+            &release,
+            common::host_asset(&client, &release),
+            Arc::new(|_| {}),
+            CancellationToken::new(),
+        )
+        .await
+        .map(|_| ());
+
+    let path = client.install_path_for(&ToolchainVersion::named(VERSION));
+    assert_eq!(client.active_toolchain(), None);
+    (result, dir, path)
+}
+
+#[tokio::test]
+async fn incomplete_install_is_removed() {
+    let (result, _dir, path) =
+        install_truncated(|path| !path.contains("clang-runtimes"), false).await;
+
+    let error = result.unwrap_err();
+    assert_eq!(
+        error.stable_code(),
+        "arm_toolchain::toolchain::incomplete_extraction"
+    );
+    assert!(!path.exists());
+}
+
+#[tokio::test]
+async fn incomplete_install_is_kept_for_inspection() {
+    let (result, _dir, path) =
+        install_truncated(|path| !path.contains("clang-runtimes"), true).await;
+
+    assert!(
+        matches!(result, Err(ToolchainError::IncompleteExtraction { .. })),
+        "{result:?}"
+    );
+    assert!(path.join("bin/clang").exists());
+}
+
+#[tokio::test]
+async fn too_little_extracted_for_the_archive() {
+    let name = host_asset_name(VERSION);
+    let root = name.trim_end_matches(".tar.xz");
+    // A tar whose end is followed by data that's never extracted, like an archive whose
+    // entries were cut off while its size wasn't.
+    let mut tar = tar::Builder::new(Vec::new());
+    for (path, contents, mode) in common::toolchain_files(root) {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(mode);
+        tar.append_data(&mut header, path, &contents[..]).unwrap();
+    }
+    let mut data = tar.into_inner().unwrap();
+    data.extend(common::noise(512 * 1024));
+    let mut xz = liblzma::write::XzEncoder::new(Vec::new(), 0);
+    std::io::Write::write_all(&mut xz, &data).unwrap();
+    let release = MockRelease::empty(VERSION).asset_with_checksum(&name, xz.finish().unwrap());
+    let server = MockServer::github(MockGitHub::new([release]));
+    let dir = tempfile::tempdir().unwrap();
+    let client = common::client(dir.path(), &server).await;
+    let release = client
+        .get_release(&ToolchainVersion::named(VERSION))
+        .await
+        .unwrap();
+
+    let error = client
+        .download_and_install(
+            &release,
+            common::host_asset(&client, &release),
+            Arc::new(|_| {}),
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap_err();
+
+    let ToolchainError::IncompleteExtraction { details, .. } = error else {
+        panic!("{error:?}");
+    };
+    assert_eq!(details.len(), 1, "{details:?}");
+    assert!(details[0].starts_with("only "), "{details:?}");
+}
@@ -0,0 +1,118 @@
+//! The size of an installed toolchain is cached in its manifest, and recomputed when the
+//! toolchain's directory changes.
+
+mod common;
+
+use std::{fs, path::Path, sync::Arc};
+
+use arm_toolchain::toolchain::{InstalledToolchain, ToolchainSize, ToolchainVersion};
+use common::{MockGitHub, MockRelease, MockServer};
+use tokio_util::sync::CancellationToken;
+
+const MANIFEST: &str = ".arm-toolchain-manifest";
+
+/// Writes the fixture toolchain's files to `path`, without a manifest, and returns their size.
+fn write_toolchain(path: &Path) -> ToolchainSize {
+    let mut size = ToolchainSize::default();
+    for (file, contents, _) in common::toolchain_files(".") {
+        let file = path.join(file);
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(file, &contents).unwrap();
+        size.bytes += contents.len() as u64;
+        size.files += 1;
+    }
+    size
+}
+
+fn grown(size: ToolchainSize, bytes: u64) -> ToolchainSize {
+    ToolchainSize {
+        bytes: size.bytes + bytes,
+        files: size.files + 1,
+    }
+}
+
+#[tokio::test]
+async fn computes_the_size_without_a_manifest() {
+    let dir = tempfile::tempdir().unwrap();
+    let expected = write_toolchain(dir.path());
+    let toolchain = InstalledToolchain::new(dir.path().to_owned());
+
+    assert_eq!(toolchain.size(false).await.unwrap(), expected);
+
+    let manifest = fs::read_to_string(dir.path().join(MANIFEST)).unwrap();
+    assert!(manifest.contains(&expected.bytes.to_string()), "{manifest}");
+    // The manifest itself isn't counted.
+    assert_eq!(toolchain.size(true).await.unwrap(), expected);
+}
+
+#[tokio::test]
+async fn uses_the_cached_size_until_refreshed() {
+    let dir = tempfile::tempdir().unwrap();
+    let expected = write_toolchain(dir.path());
+    let toolchain = InstalledToolchain::new(dir.path().to_owned());
+    toolchain.size(false).await.unwrap();
+
+    // Adding a file to a subdirectory doesn't change the top-level modification time.
+    fs::write(dir.path().join("bin/extra"), [0; 100]).unwrap();
+
+    assert_eq!(toolchain.size(false).await.unwrap(), expected);
+    assert_eq!(toolchain.size(true).await.unwrap(), grown(expected, 100));
+    // Refreshing updates the cache.
+    assert_eq!(toolchain.size(false).await.unwrap(), grown(expected, 100));
+}
+
+#[tokio::test]
+async fn recomputes_after_a_file_is_added() {
+    let dir = tempfile::tempdir().unwrap();
+    let expected = write_toolchain(dir.path());
+    let toolchain = InstalledToolchain::new(dir.path().to_owned());
+    toolchain.size(false).await.unwrap();
+
+    fs::write(dir.path().join("extra"), [0; 100]).unwrap();
+
+    assert_eq!(toolchain.size(false).await.unwrap(), grown(expected, 100));
+}
+
+#[tokio::test]
+async fn recomputes_when_the_manifest_is_malformed() {
+    let dir = tempfile::tempdir().unwrap();
+    let expected = write_toolchain(dir.path());
+    fs::write(dir.path().join(MANIFEST), "size_bytes=oops\n\0garbage").unwrap();
+    let toolchain = InstalledToolchain::new(dir.path().to_owned());
+
+    assert_eq!(toolchain.size(false).await.unwrap(), expected);
+}
+
+#[tokio::test]
+async fn installing_records_the_size() {
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let dir = tempfile::tempdir().unwrap();
+    let client = common::client(dir.path(), &server).await;
+    let release = client
+        .get_release(&ToolchainVersion::named("21.1.0"))
+        .await
+        .unwrap();
+    client
+        .download_and_install(
+            &release,
+            common::host_asset(&client, &release),
+            Arc::new(|_| {}),
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+    let toolchain = client
+        .toolchain(&ToolchainVersion::named("21.1.0"))
+        .await
+        .unwrap();
+    let versions = toolchain.recorded_versions().await;
+
+    // The size recorded by the install is used without walking the toolchain, so it doesn't
+    // include a file added to a subdirectory since.
+    fs::write(toolchain.path.join("bin/extra"), [0; 100]).unwrap();
+    let installed = toolchain.size(false).await.unwrap();
+    // Updating the size keeps the rest of the manifest.
+    assert_eq!(toolchain.size(true).await.unwrap(), grown(installed, 100));
+    assert!(versions.is_some());
+    assert_eq!(toolchain.recorded_versions().await, versions);
+}
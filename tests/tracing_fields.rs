@@ -0,0 +1,106 @@
+//! Every span of a client's operations carries its directories, so that logs from several
+//! clients can be told apart, and the GitHub token is never logged.
+
+mod common;
+
+use std::{
+    io::Write,
+    sync::{Arc, Mutex},
+};
+
+use arm_toolchain::toolchain::ToolchainVersion;
+use common::{MockGitHub, MockRelease, MockServer};
+use tokio_util::sync::CancellationToken;
+use tracing::level_filters::LevelFilter;
+
+const TOKEN: &str = "ghp_secret";
+
+/// A writer that keeps everything written to it, for reading the logs back.
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Buffer {
+    fn lines(&self) -> Vec<String> {
+        String::from_utf8_lossy(&self.0.lock().unwrap())
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn spans_carry_the_directories_and_the_token_is_redacted() {
+    let buffer = Buffer::default();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(LevelFilter::TRACE)
+        .with_ansi(false)
+        .with_writer({
+            let buffer = buffer.clone();
+            move || buffer.clone()
+        })
+        .finish();
+    // The test runs on a single thread, so everything the client logs on it is captured.
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let client = common::client_builder(dir.path(), &server)
+        .github_token(Some(TOKEN.to_string()))
+        .build()
+        .await
+        .unwrap();
+    let version = ToolchainVersion::named("21.1.0");
+    let release = client.get_release(&version).await.unwrap();
+    client
+        .download_and_install(
+            &release,
+            common::host_asset(&client, &release),
+            Arc::new(|_| {}),
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+    client
+        .remove(&version, |_| {}, &CancellationToken::new())
+        .await
+        .unwrap();
+
+    let lines = buffer.lines();
+    let toolchains_path = format!(
+        "toolchains_path={}",
+        dir.path().join("toolchains").display()
+    );
+    let cache_path = format!("cache_path={}", dir.path().join("cache").display());
+    for operation in ["download_and_install", "remove"] {
+        let logged = lines
+            .iter()
+            .filter(|line| line.contains(&format!("}}:{operation}")))
+            .collect::<Vec<_>>();
+        assert!(!logged.is_empty(), "nothing was logged by {operation}");
+        for line in logged {
+            assert!(line.contains("toolchain_client{"), "{line}");
+            assert!(line.contains(&toolchains_path), "{line}");
+            assert!(line.contains(&cache_path), "{line}");
+        }
+    }
+
+    let construction = lines
+        .iter()
+        .find(|line| line.contains("Initializing toolchain downloader"))
+        .expect("the construction was logged");
+    assert!(construction.contains("github_token=true"), "{construction}");
+    for line in &lines {
+        assert!(!line.contains(TOKEN), "the token was logged: {line}");
+    }
+}
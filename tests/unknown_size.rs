@@ -0,0 +1,104 @@
+//! Assets reported with a size of zero, as some mirrors and proxies do, are probed for their
+//! size, or downloaded from scratch if it can't be found.
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use arm_toolchain::toolchain::{InstallState, ToolchainVersion};
+use common::{MockGitHub, MockRelease, MockServer, Request, Response, host_asset_name};
+use tokio_util::sync::CancellationToken;
+
+const VERSION: &str = "21.1.0";
+
+/// A release whose assets are all reported with a size of zero.
+fn release() -> MockRelease {
+    MockRelease {
+        sizes_unknown: true,
+        ..MockRelease::toolchain(VERSION)
+    }
+}
+
+/// Installs the release after leaving `partial` in the cache as an interrupted download, and
+/// returns the requests made for the archive and the `DownloadBegin` events.
+async fn install_after_partial(server: &MockServer, partial: &[u8]) -> (Vec<Request>, Vec<u64>) {
+    let dir = tempfile::tempdir().unwrap();
+    let client = common::client(dir.path(), server).await;
+    let release = client
+        .get_release(&ToolchainVersion::named(VERSION))
+        .await
+        .unwrap();
+    let asset = common::host_asset(&client, &release);
+    assert_eq!(asset.size, 0);
+
+    let archive_path = client.cache_path_for(asset).unwrap();
+    std::fs::create_dir_all(archive_path.parent().unwrap()).unwrap();
+    std::fs::write(&archive_path, partial).unwrap();
+
+    let sizes = Arc::new(Mutex::new(vec![]));
+    client
+        .install_to(
+            &release,
+            asset,
+            dir.path().join("toolchain"),
+            Arc::new({
+                let sizes = sizes.clone();
+                move |state| {
+                    if let InstallState::DownloadBegin { asset_size, .. } = state {
+                        sizes.lock().unwrap().push(asset_size);
+                    }
+                }
+            }),
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+    let requests = server.requests_for(&host_asset_name(VERSION));
+    let sizes = sizes.lock().unwrap().clone();
+    (requests, sizes)
+}
+
+#[tokio::test]
+async fn probes_the_size_and_resumes() {
+    let release = release();
+    let data = release.data(&host_asset_name(VERSION)).to_vec();
+    let half = data.len() / 2;
+    let server = MockServer::github(MockGitHub::new([release]));
+
+    let (requests, sizes) = install_after_partial(&server, &data[..half]).await;
+
+    assert_eq!(requests.len(), 2, "{requests:#?}");
+    assert_eq!(requests[0].method, "HEAD");
+    assert_eq!(requests[1].method, "GET");
+    assert_eq!(
+        requests[1].header("range"),
+        Some(format!("bytes={half}-{}", data.len() - 1).as_str())
+    );
+    assert_eq!(sizes, [data.len() as u64]);
+}
+
+#[tokio::test]
+async fn downloads_from_scratch_when_the_probe_fails() {
+    let release = release();
+    let data = release.data(&host_asset_name(VERSION)).to_vec();
+    let github = MockGitHub::new([release]);
+    let server = MockServer::start(move |request| {
+        if request.method == "HEAD" {
+            Response::new(405, "Method Not Allowed")
+        } else {
+            github.respond(request)
+        }
+    });
+
+    // Without knowing the size, there's no telling how much of this is missing.
+    let (requests, sizes) = install_after_partial(&server, &data[..data.len() / 2]).await;
+
+    let gets: Vec<_> = requests
+        .iter()
+        .filter(|request| request.method == "GET")
+        .collect();
+    assert_eq!(gets.len(), 1, "{requests:#?}");
+    assert_eq!(gets[0].header("range"), None);
+    assert_eq!(sizes, [0]);
+}
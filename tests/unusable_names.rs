@@ -0,0 +1,78 @@
+//! Toolchain directories whose names can't be used as versions are skipped when listing
+//! toolchains, and reported separately.
+
+mod common;
+
+use std::fs;
+
+use arm_toolchain::toolchain::{ToolchainVersion, UnusableReason};
+use common::{MockGitHub, MockServer};
+use tokio_util::sync::CancellationToken;
+
+#[tokio::test]
+async fn reserved_names_are_skipped() {
+    let dir = tempfile::tempdir().unwrap();
+    let product_path = dir.path().join("toolchains/embedded");
+    for name in ["21.1.0", "aux", "COM3.x", "lpt1", "com10", "console"] {
+        fs::create_dir_all(product_path.join(name)).unwrap();
+    }
+    let server = MockServer::github(MockGitHub::new([]));
+    let client = common::client(dir.path(), &server).await;
+
+    let mut installed = client.installed_versions().await.unwrap();
+    installed.sort();
+    let mut unusable = client.unusable_installs().await.unwrap();
+    unusable.sort_by(|a, b| a.path.cmp(&b.path));
+    let unusable = unusable
+        .into_iter()
+        .map(|install| (install.path, install.reason))
+        .collect::<Vec<_>>();
+
+    // Only a single digit after "com" or "lpt" makes a reserved name.
+    assert_eq!(
+        installed,
+        ["com10", "console", "21.1.0"].map(ToolchainVersion::named)
+    );
+    assert_eq!(
+        unusable,
+        ["COM3.x", "aux", "lpt1"]
+            .map(|name| (product_path.join(name), UnusableReason::ReservedName))
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn non_utf8_names_are_skipped() {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    let dir = tempfile::tempdir().unwrap();
+    let product_path = dir.path().join("toolchains/embedded");
+    let non_utf8 = product_path.join(OsStr::from_bytes(b"21.1.0-\xff"));
+    fs::create_dir_all(product_path.join("21.1.0/bin")).unwrap();
+    fs::create_dir_all(&non_utf8).unwrap();
+    let server = MockServer::github(MockGitHub::new([]));
+    let client = common::client(dir.path(), &server).await;
+
+    assert_eq!(
+        client.installed_versions().await.unwrap(),
+        [ToolchainVersion::named("21.1.0")]
+    );
+    let unusable = client.unusable_installs().await.unwrap();
+    assert_eq!(unusable.len(), 1, "{unusable:?}");
+    assert_eq!(unusable[0].path, non_utf8);
+    assert_eq!(unusable[0].reason, UnusableReason::NonUtf8Name);
+
+    // Removing the other toolchain leaves the directory alone.
+    client
+        .remove(
+            &ToolchainVersion::named("21.1.0"),
+            |_| {},
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+    assert!(client.installed_versions().await.unwrap().is_empty());
+    assert!(non_utf8.is_dir());
+    assert_eq!(client.unusable_installs().await.unwrap().len(), 1);
+}
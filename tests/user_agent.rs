@@ -0,0 +1,100 @@
+//! Applications can identify themselves in the user agent, which is sent with both GitHub API
+//! requests and asset downloads.
+
+mod common;
+
+use std::sync::Arc;
+
+use arm_toolchain::toolchain::{APP_USER_AGENT, ToolchainError, ToolchainVersion};
+use common::{MockGitHub, MockRelease, MockServer, Request};
+use tokio_util::sync::CancellationToken;
+
+/// Installs 21.1.0 with a client built with `suffix`, and returns the client's user agent and
+/// the requests it made.
+async fn install_with_suffix(suffix: Option<&str>) -> (String, Vec<Request>) {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let mut builder = common::client_builder(dir.path(), &server);
+    if let Some(suffix) = suffix {
+        builder = builder.user_agent_suffix(suffix);
+    }
+    let client = builder.build().await.unwrap();
+
+    let release = client
+        .get_release(&ToolchainVersion::named("21.1.0"))
+        .await
+        .unwrap();
+    client
+        .download_and_install(
+            &release,
+            common::host_asset(&client, &release),
+            Arc::new(|_| {}),
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+    (client.user_agent().to_string(), server.requests())
+}
+
+fn is_api_request(request: &Request) -> bool {
+    request.path.starts_with("/repos/")
+}
+
+fn is_download(request: &Request) -> bool {
+    request.file_name().ends_with(".tar.xz")
+}
+
+/// Checks that some of the requests were made, and that each was sent with `user_agent` as
+/// one of its user agents.
+fn assert_sent_with<'a>(requests: impl IntoIterator<Item = &'a Request>, user_agent: &str) {
+    let requests: Vec<_> = requests.into_iter().collect();
+    assert!(!requests.is_empty());
+
+    for request in requests {
+        // Octocrab sends its own user agent before the client's.
+        assert!(
+            request
+                .headers
+                .iter()
+                .any(|(name, value)| name == "user-agent" && value == user_agent),
+            "{request:#?}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn suffix_is_appended_for_api_requests_and_downloads() {
+    let (user_agent, requests) = install_with_suffix(Some("cargo-v5/1.2.3")).await;
+
+    assert_eq!(user_agent, format!("{APP_USER_AGENT} cargo-v5/1.2.3"));
+    assert_sent_with(requests.iter().filter(|r| is_api_request(r)), &user_agent);
+    assert_sent_with(requests.iter().filter(|r| is_download(r)), &user_agent);
+}
+
+#[tokio::test]
+async fn downloads_use_the_crate_user_agent_without_a_suffix() {
+    let (user_agent, requests) = install_with_suffix(None).await;
+
+    assert_eq!(user_agent, APP_USER_AGENT);
+    assert_sent_with(requests.iter().filter(|r| is_download(r)), APP_USER_AGENT);
+}
+
+#[tokio::test]
+async fn suffix_that_is_not_a_header_value_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::github(MockGitHub::new([]));
+
+    for suffix in ["cargo-v5\n1.2.3", "  "] {
+        let error = common::client_builder(dir.path(), &server)
+            .user_agent_suffix(suffix)
+            .build()
+            .await
+            .unwrap_err();
+
+        match error {
+            ToolchainError::InvalidUserAgent { user_agent } => assert_eq!(user_agent, suffix),
+            error => panic!("{error:?}"),
+        }
+    }
+}
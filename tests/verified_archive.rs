@@ -0,0 +1,137 @@
+//! An archive that was verified isn't hashed again when installing it is retried after
+//! extracting it failed, unless the archive changed in between.
+
+mod common;
+
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use arm_toolchain::toolchain::{
+    ExtractLimits, InstallState, ToolchainClient, ToolchainError, ToolchainRelease,
+    ToolchainVersion,
+};
+use common::{MockGitHub, MockRelease, MockServer, host_asset_name};
+use tokio_util::sync::CancellationToken;
+
+const VERSION: &str = "21.1.0";
+
+/// Installs the release to `destination`, returning the verification events that were sent.
+async fn install_to(
+    client: &ToolchainClient,
+    release: &ToolchainRelease,
+    destination: &Path,
+) -> (Result<(), ToolchainError>, Vec<InstallState>) {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let result = client
+        .install_to(
+            release,
+            common::host_asset(client, release),
+            destination.to_path_buf(),
+            {
+                let events = events.clone();
+                Arc::new(move |state| {
+                    if matches!(
+                        state,
+                        InstallState::VerifyingBegin { .. }
+                            | InstallState::Verifying { .. }
+                            | InstallState::VerifyingFinish
+                    ) {
+                        events.lock().unwrap().push(state);
+                    }
+                })
+            },
+            CancellationToken::new(),
+        )
+        .await
+        .map(|_| ());
+    let events = events.lock().unwrap().clone();
+    (result, events)
+}
+
+/// Fails to extract the release after its archive was downloaded and verified, as if the disk
+/// filled up, and returns a client for trying again.
+async fn fail_extraction(
+    dir: &Path,
+    server: &MockServer,
+) -> (ToolchainClient, ToolchainRelease, Vec<InstallState>) {
+    let limited = common::client_builder(dir, server)
+        .extract_limits(ExtractLimits {
+            max_uncompressed_size: 1,
+            ..Default::default()
+        })
+        .build()
+        .await
+        .unwrap();
+    let release = limited
+        .get_release(&ToolchainVersion::named(VERSION))
+        .await
+        .unwrap();
+
+    let (result, events) = install_to(&limited, &release, &dir.join("toolchain")).await;
+
+    let error = result.unwrap_err();
+    assert!(matches!(error, ToolchainError::Extract(_)), "{error:?}");
+    (common::client(dir, server).await, release, events)
+}
+
+#[tokio::test]
+async fn retry_skips_verification() {
+    let release = MockRelease::toolchain(VERSION);
+    let size = release.data(&host_asset_name(VERSION)).len() as u64;
+    let server = MockServer::github(MockGitHub::new([release]));
+    let dir = tempfile::tempdir().unwrap();
+    let (client, release, first_events) = fail_extraction(dir.path(), &server).await;
+    assert!(first_events.len() > 3, "{first_events:?}");
+
+    let (result, events) = install_to(&client, &release, &dir.path().join("toolchain")).await;
+
+    result.unwrap();
+    // The events are still sent, but the archive isn't read again.
+    assert_eq!(
+        events,
+        [
+            InstallState::VerifyingBegin { asset_size: size },
+            InstallState::Verifying { bytes_read: size },
+            InstallState::VerifyingFinish,
+        ]
+    );
+    let downloads = server
+        .requests_for(&host_asset_name(VERSION))
+        .into_iter()
+        .filter(|request| request.method == "GET")
+        .count();
+    assert_eq!(downloads, 1);
+    assert!(
+        common::list_tree(&dir.path().join("cache")).is_empty(),
+        "{:#?}",
+        common::list_tree(&dir.path().join("cache"))
+    );
+}
+
+#[tokio::test]
+async fn retry_verifies_an_archive_that_changed() {
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain(VERSION)]));
+    let dir = tempfile::tempdir().unwrap();
+    let (client, release, _) = fail_extraction(dir.path(), &server).await;
+
+    // Corrupted in the cache, which changes its modification time.
+    let archive_path = client
+        .cache_path_for(common::host_asset(&client, &release))
+        .unwrap();
+    let mut archive = std::fs::read(&archive_path).unwrap();
+    let last = archive.len() - 1;
+    archive[last] ^= 0xff;
+    std::thread::sleep(std::time::Duration::from_millis(10));
+    std::fs::write(&archive_path, archive).unwrap();
+
+    let (result, events) = install_to(&client, &release, &dir.path().join("toolchain")).await;
+
+    let error = result.unwrap_err();
+    assert!(
+        matches!(error, ToolchainError::ChecksumMismatch { .. }),
+        "{error:?}"
+    );
+    assert!(events.len() > 3, "{events:?}");
+}
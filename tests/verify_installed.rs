@@ -0,0 +1,140 @@
+//! Installed toolchains are verified by re-hashing their files against the digests recorded
+//! when they were installed.
+
+mod common;
+
+use std::{fs, path::PathBuf, sync::Arc};
+
+use arm_toolchain::toolchain::{ToolchainClient, ToolchainError, ToolchainVersion, VerifyProgress};
+use common::{MockGitHub, MockRelease, MockServer};
+use tokio_util::sync::CancellationToken;
+
+const MULTILIB: &str = "lib/clang-runtimes/multilib.yaml";
+const LIBC: &str = "lib/clang-runtimes/arm-none-eabi/lib/libc.a";
+
+fn version() -> ToolchainVersion {
+    ToolchainVersion::named("21.1.0")
+}
+
+/// Installs 21.1.0 into `dir` with a client that records digests if `record_digests` is set,
+/// and returns the client and the toolchain's directory.
+async fn installed(
+    dir: &std::path::Path,
+    record_digests: bool,
+) -> (ToolchainClient, PathBuf, MockServer) {
+    let server = MockServer::github(MockGitHub::new([MockRelease::toolchain("21.1.0")]));
+    let client = common::client_builder(dir, &server)
+        .record_file_digests(record_digests)
+        .build()
+        .await
+        .unwrap();
+    let release = client.get_release(&version()).await.unwrap();
+    client
+        .download_and_install(
+            &release,
+            common::host_asset(&client, &release),
+            Arc::new(|_| {}),
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+    let path = client.toolchain(&version()).await.unwrap().path;
+    (client, path, server)
+}
+
+fn paths(paths: &[&str]) -> Vec<PathBuf> {
+    paths.iter().map(PathBuf::from).collect()
+}
+
+#[tokio::test]
+async fn fresh_install_is_clean() {
+    let dir = tempfile::tempdir().unwrap();
+    let (client, _, _server) = installed(dir.path(), true).await;
+    let mut events = vec![];
+
+    let report = client
+        .verify_installed(
+            &version(),
+            |event| events.push(event),
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+    assert!(report.is_clean(), "{report:?}");
+    // The three files from the archive, and the release notes saved next to them.
+    assert_eq!(report.files_checked, 4);
+    assert!(matches!(
+        events.first(),
+        Some(VerifyProgress::Start { total_files: 4, .. })
+    ));
+    assert!(events.last().is_some_and(VerifyProgress::is_terminal));
+}
+
+#[tokio::test]
+async fn modified_file_is_found_even_if_its_size_is_the_same() {
+    let dir = tempfile::tempdir().unwrap();
+    let (client, path, _server) = installed(dir.path(), true).await;
+    // The same length as the original, so only hashing notices the difference.
+    fs::write(path.join(MULTILIB), "MultilibVersion: 9.9\n").unwrap();
+
+    let report = client
+        .verify_installed(&version(), |_| {}, &CancellationToken::new())
+        .await
+        .unwrap();
+
+    assert_eq!(report.changes.modified, paths(&[MULTILIB]));
+    assert!(report.changes.deleted.is_empty() && report.changes.added.is_empty());
+    assert!(!report.is_clean());
+}
+
+#[tokio::test]
+async fn deleted_file_is_found() {
+    let dir = tempfile::tempdir().unwrap();
+    let (client, path, _server) = installed(dir.path(), true).await;
+    fs::remove_file(path.join(LIBC)).unwrap();
+
+    let report = client
+        .verify_installed(&version(), |_| {}, &CancellationToken::new())
+        .await
+        .unwrap();
+
+    assert_eq!(report.changes.deleted, paths(&[LIBC]));
+    assert!(report.changes.modified.is_empty() && report.changes.added.is_empty());
+    assert_eq!(report.files_checked, 3);
+}
+
+#[tokio::test]
+async fn added_file_is_found() {
+    let dir = tempfile::tempdir().unwrap();
+    let (client, path, _server) = installed(dir.path(), true).await;
+    fs::write(path.join("bin/extra-tool"), "#!/bin/sh\n").unwrap();
+
+    let report = client
+        .verify_installed(&version(), |_| {}, &CancellationToken::new())
+        .await
+        .unwrap();
+
+    assert_eq!(report.changes.added, paths(&["bin/extra-tool"]));
+    assert!(report.changes.modified.is_empty() && report.changes.deleted.is_empty());
+    // Files that weren't part of the toolchain aren't hashed.
+    assert_eq!(report.files_checked, 4);
+}
+
+#[tokio::test]
+async fn toolchain_without_digests_cannot_be_verified() {
+    let dir = tempfile::tempdir().unwrap();
+    let (client, _, _server) = installed(dir.path(), false).await;
+
+    let result = client
+        .verify_installed(&version(), |_| {}, &CancellationToken::new())
+        .await;
+
+    assert!(
+        matches!(
+            result,
+            Err(ToolchainError::DigestsNotRecorded { ref version }) if *version == self::version()
+        ),
+        "{result:?}"
+    );
+}